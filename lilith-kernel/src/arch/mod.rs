@@ -0,0 +1,7 @@
+//! 架构相关代码
+//!
+//! 本模块按目标架构选择具体实现，并重新导出其接口
+
+pub mod riscv;
+
+pub use riscv::*;
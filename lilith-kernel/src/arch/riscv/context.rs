@@ -0,0 +1,75 @@
+//! RISC-V任务上下文切换
+
+/// 任务切换时保存的寄存器（被调用者保存寄存器）
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TaskContext {
+    /// 返回地址
+    pub ra: usize,
+    /// 栈指针
+    pub sp: usize,
+    /// s0-s11
+    pub s: [usize; 12],
+}
+
+impl TaskContext {
+    /// 创建从 `entry` 开始、使用栈顶 `stack_top` 的上下文
+    pub fn new(entry: usize, stack_top: usize) -> Self {
+        Self {
+            ra: entry,
+            sp: stack_top,
+            s: [0; 12],
+        }
+    }
+}
+
+/// 保存当前上下文到 `old` 并切换到 `new`
+///
+/// # Safety
+///
+/// 调用者必须保证两个指针有效，且 `new` 指向的上下文此时没有在其他核上运行
+#[naked]
+pub unsafe extern "C" fn switch_context(old: *mut TaskContext, new: *const TaskContext) {
+    core::arch::asm!(
+        "sd ra, 0(a0)",
+        "sd sp, 8(a0)",
+        "sd s0, 16(a0)",
+        "sd s1, 24(a0)",
+        "sd s2, 32(a0)",
+        "sd s3, 40(a0)",
+        "sd s4, 48(a0)",
+        "sd s5, 56(a0)",
+        "sd s6, 64(a0)",
+        "sd s7, 72(a0)",
+        "sd s8, 80(a0)",
+        "sd s9, 88(a0)",
+        "sd s10, 96(a0)",
+        "sd s11, 104(a0)",
+        "ld ra, 0(a1)",
+        "ld sp, 8(a1)",
+        "ld s0, 16(a1)",
+        "ld s1, 24(a1)",
+        "ld s2, 32(a1)",
+        "ld s3, 40(a1)",
+        "ld s4, 48(a1)",
+        "ld s5, 56(a1)",
+        "ld s6, 64(a1)",
+        "ld s7, 72(a1)",
+        "ld s8, 80(a1)",
+        "ld s9, 88(a1)",
+        "ld s10, 96(a1)",
+        "ld s11, 104(a1)",
+        "ret",
+        options(noreturn)
+    )
+}
+
+/// 当前hart编号（启动代码将其保存在tp寄存器中）
+#[inline]
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
@@ -0,0 +1,243 @@
+//! 用户任务浮点寄存器的延迟切换
+//!
+//! 本模块按需保存与恢复用户任务的F/D扩展寄存器（f0-f31与fcsr），包括：
+//! - 返回用户态时，寄存器中已是本任务的状态则sstatus.FS设为Clean，否则设为Off
+//! - FS为Off时用户程序的第一条浮点指令引起非法指令异常，此时载入任务保存的状态
+//!   （从未使用过浮点时为0）后重新执行该指令
+//! - 切换任务时只有FS为Dirty（任务修改过寄存器）才保存，不使用浮点的任务没有额外开销
+//! - 每个hart记录寄存器中是哪个任务的状态，任务在其他hart上载入过时重新载入
+//! - 新的用户任务（clone）继承当前任务的浮点状态
+//!
+//! 内核自身不使用浮点寄存器
+
+use super::{hart_id, TrapFrame};
+use crate::sched::MAX_HARTS;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// sstatus.FS 字段及其取值
+const SSTATUS_FS: usize = 3 << 13;
+const FS_OFF: usize = 0;
+const FS_CLEAN: usize = 2 << 13;
+const FS_DIRTY: usize = 3 << 13;
+
+/// 寄存器中没有任何任务的状态
+const NO_OWNER: usize = usize::MAX;
+
+/// 各hart的浮点寄存器中是哪个任务（编号）的状态
+#[allow(clippy::declare_interior_mutable_const)]
+const OWNER_INIT: AtomicUsize = AtomicUsize::new(NO_OWNER);
+static OWNERS: [AtomicUsize; MAX_HARTS] = [OWNER_INIT; MAX_HARTS];
+
+/// 保存的浮点寄存器
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct FpuRegs {
+    f: [u64; 32],
+    fcsr: u64,
+}
+
+/// 任务的浮点状态
+pub struct FpuContext {
+    /// 所属任务的编号
+    owner: usize,
+    /// 切换出去时保存的寄存器
+    regs: UnsafeCell<FpuRegs>,
+    /// 最近一次载入寄存器的hart
+    hart: AtomicUsize,
+}
+
+impl FpuContext {
+    /// 编号为 `owner` 的任务的浮点状态，寄存器全为0
+    pub const fn new(owner: usize) -> Self {
+        Self {
+            owner,
+            regs: UnsafeCell::new(FpuRegs { f: [0; 32], fcsr: 0 }),
+            hart: AtomicUsize::new(NO_OWNER),
+        }
+    }
+
+    /// 当前hart的寄存器中是否是本任务的状态
+    fn loaded(&self) -> bool {
+        let hart = hart_id();
+        self.hart.load(Ordering::Relaxed) == hart && OWNERS[hart].load(Ordering::Relaxed) == self.owner
+    }
+
+    /// 任务被切换出去：修改过的寄存器保存到任务中
+    pub fn switch_out(&self) {
+        // 嵌套陷入恢复的sstatus可能带有过时的Dirty，寄存器已属于其他任务时不能保存
+        if self.loaded() && fs() == FS_DIRTY {
+            unsafe { save(self.regs.get()) };
+            set_fs(FS_CLEAN);
+        }
+    }
+
+    /// 以 `parent` 的浮点状态初始化新任务
+    pub fn copy_from(&self, parent: &FpuContext) {
+        unsafe {
+            if parent.loaded() {
+                save(self.regs.get());
+            } else {
+                *self.regs.get() = *parent.regs.get();
+            }
+        }
+    }
+}
+
+/// 当前的sstatus.FS
+fn fs() -> usize {
+    let sstatus: usize;
+    unsafe {
+        core::arch::asm!("csrr {}, sstatus", out(reg) sstatus);
+    }
+    sstatus & SSTATUS_FS
+}
+
+/// 设置sstatus.FS
+fn set_fs(fs: usize) {
+    unsafe {
+        core::arch::asm!(
+            "csrc sstatus, {mask}",
+            "csrs sstatus, {fs}",
+            mask = in(reg) SSTATUS_FS,
+            fs = in(reg) fs,
+        );
+    }
+}
+
+/// 把浮点寄存器保存到 `regs`，FS为Off时先打开
+unsafe fn save(regs: *mut FpuRegs) {
+    core::arch::asm!(
+        "csrs sstatus, {clean}",
+        "fsd f0, 0*8({regs})",
+        "fsd f1, 1*8({regs})",
+        "fsd f2, 2*8({regs})",
+        "fsd f3, 3*8({regs})",
+        "fsd f4, 4*8({regs})",
+        "fsd f5, 5*8({regs})",
+        "fsd f6, 6*8({regs})",
+        "fsd f7, 7*8({regs})",
+        "fsd f8, 8*8({regs})",
+        "fsd f9, 9*8({regs})",
+        "fsd f10, 10*8({regs})",
+        "fsd f11, 11*8({regs})",
+        "fsd f12, 12*8({regs})",
+        "fsd f13, 13*8({regs})",
+        "fsd f14, 14*8({regs})",
+        "fsd f15, 15*8({regs})",
+        "fsd f16, 16*8({regs})",
+        "fsd f17, 17*8({regs})",
+        "fsd f18, 18*8({regs})",
+        "fsd f19, 19*8({regs})",
+        "fsd f20, 20*8({regs})",
+        "fsd f21, 21*8({regs})",
+        "fsd f22, 22*8({regs})",
+        "fsd f23, 23*8({regs})",
+        "fsd f24, 24*8({regs})",
+        "fsd f25, 25*8({regs})",
+        "fsd f26, 26*8({regs})",
+        "fsd f27, 27*8({regs})",
+        "fsd f28, 28*8({regs})",
+        "fsd f29, 29*8({regs})",
+        "fsd f30, 30*8({regs})",
+        "fsd f31, 31*8({regs})",
+        "frcsr {tmp}",
+        "sd {tmp}, 32*8({regs})",
+        regs = in(reg) regs,
+        clean = in(reg) FS_CLEAN,
+        tmp = out(reg) _,
+    );
+}
+
+/// 从 `regs` 载入浮点寄存器，FS必须已经打开
+unsafe fn restore(regs: *const FpuRegs) {
+    core::arch::asm!(
+        "fld f0, 0*8({regs})",
+        "fld f1, 1*8({regs})",
+        "fld f2, 2*8({regs})",
+        "fld f3, 3*8({regs})",
+        "fld f4, 4*8({regs})",
+        "fld f5, 5*8({regs})",
+        "fld f6, 6*8({regs})",
+        "fld f7, 7*8({regs})",
+        "fld f8, 8*8({regs})",
+        "fld f9, 9*8({regs})",
+        "fld f10, 10*8({regs})",
+        "fld f11, 11*8({regs})",
+        "fld f12, 12*8({regs})",
+        "fld f13, 13*8({regs})",
+        "fld f14, 14*8({regs})",
+        "fld f15, 15*8({regs})",
+        "fld f16, 16*8({regs})",
+        "fld f17, 17*8({regs})",
+        "fld f18, 18*8({regs})",
+        "fld f19, 19*8({regs})",
+        "fld f20, 20*8({regs})",
+        "fld f21, 21*8({regs})",
+        "fld f22, 22*8({regs})",
+        "fld f23, 23*8({regs})",
+        "fld f24, 24*8({regs})",
+        "fld f25, 25*8({regs})",
+        "fld f26, 26*8({regs})",
+        "fld f27, 27*8({regs})",
+        "fld f28, 28*8({regs})",
+        "fld f29, 29*8({regs})",
+        "fld f30, 30*8({regs})",
+        "fld f31, 31*8({regs})",
+        "ld {tmp}, 32*8({regs})",
+        "fscsr {tmp}",
+        regs = in(reg) regs,
+        tmp = out(reg) _,
+    );
+}
+
+/// 设置返回用户态时的sstatus.FS：寄存器中是 `fpu` 的状态时保留（至少为Clean），否则关闭
+pub fn prepare_return(fpu: &FpuContext, frame: &mut TrapFrame) {
+    let fs = match frame.sstatus & SSTATUS_FS {
+        _ if !fpu.loaded() => FS_OFF,
+        FS_OFF => FS_CLEAN,
+        fs => fs,
+    };
+    frame.sstatus = (frame.sstatus & !SSTATUS_FS) | fs;
+}
+
+/// 用户态非法指令异常：FS为Off时载入当前任务的浮点状态，返回是否应重新执行该指令
+///
+/// FS已经打开或硬件没有浮点单元时是真正的非法指令，返回假
+pub fn lazy_restore(frame: &mut TrapFrame) -> bool {
+    if frame.sstatus & SSTATUS_FS != FS_OFF {
+        return false;
+    }
+    let Some(task) = crate::sched::current() else {
+        return false;
+    };
+    // 没有F/D扩展时FS固定为Off
+    set_fs(FS_CLEAN);
+    if fs() == FS_OFF {
+        return false;
+    }
+    let fpu = task.fpu();
+    unsafe { restore(fpu.regs.get()) };
+    let hart = hart_id();
+    fpu.hart.store(hart, Ordering::Relaxed);
+    OWNERS[hart].store(fpu.owner, Ordering::Relaxed);
+    frame.sstatus = (frame.sstatus & !SSTATUS_FS) | FS_CLEAN;
+    true
+}
+
+crate::kernel_test! {
+    fn fpu_state_follows_owner() {
+        let parent = FpuContext::new(usize::MAX - 1);
+        unsafe { (*parent.regs.get()).f[1] = 0x4045_0000_0000_0000 };
+        let child = FpuContext::new(usize::MAX - 2);
+        child.copy_from(&parent);
+        assert_eq!(unsafe { (*child.regs.get()).f[1] }, 0x4045_0000_0000_0000);
+
+        // 寄存器中不是该任务的状态时以Off返回，修改过的状态不会被误保存
+        let mut frame = TrapFrame::new_user(0, 0);
+        frame.sstatus |= FS_DIRTY;
+        prepare_return(&child, &mut frame);
+        assert_eq!(frame.sstatus & SSTATUS_FS, FS_OFF);
+        assert!(!lazy_restore(&mut TrapFrame { sstatus: FS_CLEAN, ..frame }));
+    }
+}
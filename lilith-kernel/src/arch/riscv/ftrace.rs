@@ -0,0 +1,80 @@
+//! 函数跟踪的架构相关部分
+//!
+//! 以 `-Z instrument-mcount` 编译时，每个函数在序言之后调用 `mcount`：
+//! - 此时s0已是被跟踪函数的帧指针，其返回地址保存在 `s0 - 8`
+//! - `mcount` 的返回地址位于被跟踪函数内部，用于确定是哪个函数
+//! - 跟踪关闭或本hart正在处理跟踪事件时立即返回，处理函数本身调用的函数不会再次进入
+//!
+//! `function_graph` 跟踪把被跟踪函数的返回地址替换为 `return_to_handler`，
+//! 函数返回时记录退出事件，再跳回原来的返回地址
+
+use crate::debug::ftrace::{ftrace_entry, ftrace_return, FTRACE_BUSY, FTRACE_ENABLED};
+
+/// 插桩代码在函数入口调用的钩子
+///
+/// 插桩调用按普通函数调用处理，调用者已保存调用者保存寄存器，这里只需保留ra与s0
+#[naked]
+#[no_mangle]
+pub extern "C" fn mcount() {
+    unsafe {
+        core::arch::asm!(
+            "la t0, {enabled}",
+            "lbu t0, 0(t0)",
+            "beqz t0, 2f",
+            "la t0, {busy}",
+            "add t0, t0, tp",
+            "lbu t1, 0(t0)",
+            "bnez t1, 2f",
+            "li t1, 1",
+            "sb t1, 0(t0)",
+            "addi sp, sp, -16",
+            "sd ra, 8(sp)",
+            "sd s0, 0(sp)",
+            "mv a0, ra",
+            "mv a1, s0",
+            "call {entry}",
+            "ld ra, 8(sp)",
+            "ld s0, 0(sp)",
+            "addi sp, sp, 16",
+            "la t0, {busy}",
+            "add t0, t0, tp",
+            "sb zero, 0(t0)",
+            "2:",
+            "ret",
+            enabled = sym FTRACE_ENABLED,
+            busy = sym FTRACE_BUSY,
+            entry = sym ftrace_entry,
+            options(noreturn)
+        );
+    }
+}
+
+/// 被替换了返回地址的函数返回到这里
+///
+/// 保留返回值a0/a1，记录退出事件后跳回 `ftrace_return` 给出的原返回地址
+#[naked]
+pub extern "C" fn return_to_handler() {
+    unsafe {
+        core::arch::asm!(
+            "addi sp, sp, -16",
+            "sd a0, 0(sp)",
+            "sd a1, 8(sp)",
+            "la t0, {busy}",
+            "add t0, t0, tp",
+            "li t1, 1",
+            "sb t1, 0(t0)",
+            "call {ret}",
+            "mv ra, a0",
+            "la t0, {busy}",
+            "add t0, t0, tp",
+            "sb zero, 0(t0)",
+            "ld a0, 0(sp)",
+            "ld a1, 8(sp)",
+            "addi sp, sp, 16",
+            "ret",
+            busy = sym FTRACE_BUSY,
+            ret = sym ftrace_return,
+            options(noreturn)
+        );
+    }
+}
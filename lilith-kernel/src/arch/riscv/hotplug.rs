@@ -0,0 +1,86 @@
+//! hart热插拔的架构相关部分
+//!
+//! 通过SBI HSM扩展在运行时启动与停止hart：
+//! - 启动：为hart准备内核栈与页表，固件让其在分页关闭的状态下从 `hart_entry` 开始执行
+//! - `hart_entry` 打开分页、切换到内核栈后进入 `sched::hotplug::secondary_main`
+//! - 停止：hart在空闲任务中自行调用 `hart_stop`，不再返回
+//! - 核间中断：唤醒wfi中的hart，使其看到下线请求
+
+use super::sbi::{self, HART_STATE_STOPPED};
+use crate::error::KernelError;
+use crate::sched::MAX_HARTS;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::satp;
+
+/// 传给新hart的启动信息，`hart_entry` 按偏移读取
+#[repr(C)]
+struct HartBootInfo {
+    /// 内核页表（satp的值）
+    satp: AtomicUsize,
+    /// 内核栈顶
+    stack_top: AtomicUsize,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const BOOT_INFO_INIT: HartBootInfo = HartBootInfo {
+    satp: AtomicUsize::new(0),
+    stack_top: AtomicUsize::new(0),
+};
+
+static BOOT_INFO: [HartBootInfo; MAX_HARTS] = [BOOT_INFO_INIT; MAX_HARTS];
+
+/// 新hart的入口（汇编实现），a0为hart编号，a1为 `HartBootInfo` 的地址
+#[naked]
+extern "C" fn hart_entry() {
+    unsafe {
+        core::arch::asm!(
+            "ld t0, 0(a1)",
+            "csrw satp, t0",
+            "sfence.vma",
+            "ld sp, 8(a1)",
+            // 其他代码从tp读取hart编号
+            "mv tp, a0",
+            "call {main}",
+            "2:",
+            "wfi",
+            "j 2b",
+            main = sym crate::sched::hotplug::secondary_main,
+            options(noreturn)
+        );
+    }
+}
+
+/// hart是否存在且处于停止状态
+pub fn hart_is_stopped(hart_id: usize) -> Option<bool> {
+    let ret = sbi::hart_get_status(hart_id);
+    (ret.error == 0).then_some(ret.value == HART_STATE_STOPPED)
+}
+
+/// 启动处于停止状态的hart，使其在 `stack_top` 栈上进入调度器
+pub fn hart_start(hart_id: usize, stack_top: usize) -> Result<(), KernelError> {
+    let info = BOOT_INFO.get(hart_id).ok_or(KernelError::InvalidArgument)?;
+    info.satp.store(satp::read().bits(), Ordering::Relaxed);
+    info.stack_top.store(stack_top, Ordering::Relaxed);
+    // 启动信息须在新hart读取之前写入内存
+    core::sync::atomic::fence(Ordering::SeqCst);
+
+    let ret = sbi::hart_start(hart_id, hart_entry as *const () as usize, info as *const HartBootInfo as usize);
+    if ret.error != 0 {
+        return Err(KernelError::DeviceError);
+    }
+    Ok(())
+}
+
+/// 停止当前hart
+pub fn hart_stop() -> ! {
+    sbi::hart_stop();
+    // 固件不支持HSM时停在这里
+    loop {
+        super::wait_for_interrupt();
+    }
+}
+
+/// 向 `hart_id` 发送核间中断
+pub fn send_ipi(hart_id: usize) {
+    sbi::send_ipi(1, hart_id);
+}
@@ -0,0 +1,480 @@
+//! RISC-V虚拟化扩展（H扩展）
+//!
+//! 本模块实现了在VS-mode运行客户机所需的硬件操作，包括：
+//! - 检测H扩展（M-mode启动时从misa读取）并确认hgatp支持Sv39x4
+//! - G-stage页表：客户机物理地址到宿主物理地址的映射，根页表16KiB对齐，叶表项都带U位
+//! - 进入与退出VS-mode：保存宿主的被调用者保存寄存器，换用退出专用的陷入入口，
+//!   载入客户机的通用寄存器与VS级CSR后sret；客户机陷入时保存客户机现场并返回调用者
+//! - 客户机自己处理的异常（断点、缺页、用户态ecall等）与VS级中断委托给客户机
+//!
+//! 客户机运行时宿主的中断可以打断客户机（V=1时HS级中断总是打开），
+//! 调用者关中断进入，退出后重新打开中断即可处理。客户机不能使用浮点与向量指令。
+//! 固件需要把客户机引起的异常（VS-mode的ecall、客户机缺页与虚拟指令）委托给S-mode
+
+use super::pgtable::{PAGE_SIZE, PTE_R, PTE_U, PTE_V, PTE_W, PTE_X};
+use crate::error::KernelError;
+use crate::mm::page_owner::Pages;
+use alloc::vec::Vec;
+
+/// 读写CSR，`csrr`/`csrw` 只接受立即数形式的CSR编号
+macro_rules! read_csr {
+    ($csr:literal) => {{
+        let value: usize;
+        unsafe {
+            core::arch::asm!(concat!("csrr {}, ", stringify!($csr)), out(reg) value);
+        }
+        value
+    }};
+}
+
+macro_rules! write_csr {
+    ($csr:literal, $value:expr) => {
+        unsafe {
+            core::arch::asm!(concat!("csrw ", stringify!($csr), ", {}"), in(reg) $value);
+        }
+    };
+}
+
+/// sstatus中进入客户机时修改的位
+const SSTATUS_SPP: usize = 1 << 8;
+const SSTATUS_SPIE: usize = 1 << 5;
+const SSTATUS_FS: usize = 3 << 13;
+const SSTATUS_VS: usize = 3 << 9;
+
+/// hstatus：sret进入V=1、客户机处于S-mode、客户机执行wfi引起虚拟指令异常
+const HSTATUS_SPV: usize = 1 << 7;
+const HSTATUS_SPVP: usize = 1 << 8;
+const HSTATUS_VTW: usize = 1 << 21;
+
+/// 委托给客户机的异常：指令不对齐、断点、U-mode的ecall与三种缺页
+const HEDELEG_GUEST: usize = 1 << 0 | 1 << 3 | 1 << 8 | 1 << 12 | 1 << 13 | 1 << 15;
+/// 委托给客户机的中断：VS级软件、时钟与外部中断
+const HIDELEG_GUEST: usize = 1 << 2 | 1 << 6 | 1 << 10;
+/// hvip.VSTIP：向客户机注入时钟中断
+pub const HVIP_VSTIP: usize = 1 << 6;
+/// 客户机可以读取cycle、time与instret
+const HCOUNTEREN_GUEST: usize = 0x7;
+
+/// hgatp：Sv39x4模式，根页表的物理页号
+const HGATP_MODE_SV39X4: usize = 8 << 60;
+
+/// vsatp：客户机自己的地址转换模式与根页表的物理页号
+const VSATP_MODE_SHIFT: usize = 60;
+const VSATP_MODE_BARE: usize = 0;
+const VSATP_MODE_SV39: usize = 8;
+const VSATP_PPN: usize = (1 << 44) - 1;
+
+/// Sv39x4的客户机物理地址空间大小
+pub const GUEST_PHYS_LIMIT: usize = 1 << 41;
+
+/// G-stage根页表：2048项，16KiB对齐
+const ROOT_SIZE: usize = 4 * PAGE_SIZE;
+const ROOT_ENTRIES: usize = ROOT_SIZE / 8;
+
+/// 页表项的访问位与脏位（G-stage不由硬件置位，预先设置）
+const PTE_A: usize = 1 << 6;
+const PTE_D: usize = 1 << 7;
+
+/// 硬件是否支持H扩展与Sv39x4
+pub fn available() -> bool {
+    if !crate::boot::get_machine_config().is_some_and(|config| config.hypervisor_support) {
+        return false;
+    }
+    // hgatp的MODE是WARL，不支持的模式写入后读回0
+    write_csr!(0x680, HGATP_MODE_SV39X4);
+    let supported = read_csr!(0x680) & HGATP_MODE_SV39X4 == HGATP_MODE_SV39X4;
+    write_csr!(0x680, 0usize);
+    supported
+}
+
+/// 当前的time计数，客户机看到的时间与宿主相同（htimedelta为0）
+pub fn read_time() -> u64 {
+    read_csr!(0xc01) as u64
+}
+
+/// G-stage页表与客户机内存
+pub struct GuestMemory {
+    /// 客户机内存的起始客户机物理地址
+    base: usize,
+    /// 客户机内存
+    ram: Pages,
+    /// 根页表所在的分配，按16KiB对齐后使用其中的一段
+    root: Pages,
+    /// 下级页表
+    tables: Vec<Pages>,
+}
+
+impl GuestMemory {
+    /// 分配 `size` 字节清零的客户机内存，映射到客户机物理地址 `base`
+    pub fn new(base: usize, size: usize) -> Result<Self, KernelError> {
+        let end = base.checked_add(size).ok_or(KernelError::InvalidArgument)?;
+        if size == 0 || base % PAGE_SIZE != 0 || size % PAGE_SIZE != 0 || end > GUEST_PHYS_LIMIT {
+            return Err(KernelError::InvalidArgument);
+        }
+        let mut memory = Self {
+            base,
+            ram: Pages::new(size, "guest")?,
+            root: Pages::new(2 * ROOT_SIZE, "guest")?,
+            tables: Vec::new(),
+        };
+        let hpa = memory.ram.addr();
+        for offset in (0..size).step_by(PAGE_SIZE) {
+            memory.map(base + offset, hpa + offset)?;
+        }
+        Ok(memory)
+    }
+
+    /// 客户机内存的起始客户机物理地址
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// 客户机内存的宿主地址（内核恒等映射，即宿主物理地址）
+    pub fn addr(&self) -> usize {
+        self.ram.addr()
+    }
+
+    /// 客户机内存的大小
+    pub fn size(&self) -> usize {
+        self.ram.size()
+    }
+
+    /// 根页表的地址
+    fn root_table(&self) -> *mut usize {
+        ((self.root.addr() + ROOT_SIZE - 1) & !(ROOT_SIZE - 1)) as *mut usize
+    }
+
+    /// 客户机运行时使用的hgatp
+    pub fn hgatp(&self) -> usize {
+        HGATP_MODE_SV39X4 | (self.root_table() as usize / PAGE_SIZE)
+    }
+
+    /// 把客户机物理页 `gpa` 映射到宿主物理页 `hpa`
+    fn map(&mut self, gpa: usize, hpa: usize) -> Result<(), KernelError> {
+        let mut table = self.root_table();
+        let mut index = (gpa >> 30) % ROOT_ENTRIES;
+        for shift in [21, 12] {
+            let entry = unsafe { &mut *table.add(index) };
+            if *entry & PTE_V == 0 {
+                let page = Pages::new(PAGE_SIZE, "guest")?;
+                *entry = (page.addr() / PAGE_SIZE) << 10 | PTE_V;
+                self.tables.push(page);
+            }
+            table = ((*entry >> 10) * PAGE_SIZE) as *mut usize;
+            index = (gpa >> shift) % 512;
+        }
+        unsafe {
+            *table.add(index) = (hpa / PAGE_SIZE) << 10 | PTE_V | PTE_R | PTE_W | PTE_X | PTE_U | PTE_A | PTE_D;
+        }
+        Ok(())
+    }
+
+    /// 客户机物理地址 `gpa` 起 `len` 字节对应的宿主地址，超出客户机内存时返回 `None`
+    pub fn host_addr(&self, gpa: usize, len: usize) -> Option<usize> {
+        let offset = gpa.checked_sub(self.base)?;
+        (offset.checked_add(len)? <= self.size()).then(|| self.addr() + offset)
+    }
+
+    /// 读取客户机物理地址 `gpa` 处的 `T`，超出客户机内存时返回 `None`
+    fn read<T: Copy>(&self, gpa: usize) -> Option<T> {
+        let addr = self.host_addr(gpa, core::mem::size_of::<T>())?;
+        Some(unsafe { core::ptr::read_unaligned(addr as *const T) })
+    }
+
+    /// 按客户机的 `vsatp` 把客户机虚拟地址转换为客户机物理地址
+    ///
+    /// 只支持Bare与Sv39，不检查权限；页表不在客户机内存中或没有映射时返回 `None`
+    pub fn translate(&self, vsatp: usize, va: usize) -> Option<usize> {
+        match vsatp >> VSATP_MODE_SHIFT {
+            VSATP_MODE_BARE => Some(va),
+            VSATP_MODE_SV39 => {
+                let mut table = (vsatp & VSATP_PPN) * PAGE_SIZE;
+                for level in (0..3).rev() {
+                    let shift = 12 + 9 * level;
+                    let entry: usize = self.read(table + ((va >> shift) % 512) * 8)?;
+                    if entry & PTE_V == 0 {
+                        return None;
+                    }
+                    let addr = (entry >> 10) * PAGE_SIZE;
+                    if entry & (PTE_R | PTE_X) != 0 {
+                        // 叶表项，可能是大页
+                        let mask = (1 << shift) - 1;
+                        return Some((addr & !mask) | (va & mask));
+                    }
+                    table = addr;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// 读取客户机虚拟地址 `pc` 处的32位指令，两半分别转换以允许跨页
+    pub fn fetch_insn(&self, vsatp: usize, pc: usize) -> Option<u32> {
+        let low: u16 = self.read(self.translate(vsatp, pc)?)?;
+        let high: u16 = self.read(self.translate(vsatp, pc.wrapping_add(2))?)?;
+        Some(u32::from(low) | u32::from(high) << 16)
+    }
+}
+
+/// 客户机的通用寄存器与pc，布局被进入与退出的汇编使用
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestRegs {
+    /// x0-x31（x0位置不使用）
+    pub regs: [usize; 32],
+    /// 下一条要执行的指令
+    pub pc: usize,
+}
+
+/// 客户机的VS级CSR，宿主不使用，每次进入与退出时载入与保存
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuestCsrs {
+    pub vsstatus: usize,
+    pub vsie: usize,
+    pub vstvec: usize,
+    pub vsscratch: usize,
+    pub vsepc: usize,
+    pub vscause: usize,
+    pub vstval: usize,
+    pub vsatp: usize,
+}
+
+/// 进入客户机时保存的宿主现场：ra、sp、gp、tp、s0-s11、stvec与sscratch
+#[repr(C)]
+#[derive(Debug, Default)]
+struct HostRegs([usize; 18]);
+
+/// 进入与退出的汇编使用的现场
+#[repr(C)]
+#[derive(Debug, Default)]
+struct RunContext {
+    guest: GuestRegs,
+    host: HostRegs,
+}
+
+/// `RunContext` 中宿主现场的偏移
+const HOST_OFFSET: usize = 33 * 8;
+
+/// 客户机陷入HS-mode的原因
+#[derive(Debug, Clone, Copy)]
+pub struct GuestExit {
+    pub scause: usize,
+    pub stval: usize,
+    /// 客户机缺页时为客户机物理地址右移2位
+    pub htval: usize,
+    /// 引起陷入的指令（转换后的形式），硬件不提供时为0
+    pub htinst: usize,
+}
+
+/// 以 `regs` 与 `csrs` 在VS-mode运行客户机直到其陷入HS-mode，`hvip` 为注入的VS级中断
+///
+/// 必须在关中断时调用。返回时客户机现场已保存回 `regs` 与 `csrs`，sstatus与hstatus已恢复
+pub fn run_guest(memory: &GuestMemory, regs: &mut GuestRegs, csrs: &mut GuestCsrs, hvip: usize) -> GuestExit {
+    let sstatus = read_csr!(0x100);
+    let hstatus = read_csr!(0x600);
+
+    write_csr!(0x602, HEDELEG_GUEST);
+    write_csr!(0x603, HIDELEG_GUEST);
+    write_csr!(0x606, HCOUNTEREN_GUEST);
+    write_csr!(0x605, 0usize);
+    write_csr!(0x645, hvip);
+    write_csr!(0x200, csrs.vsstatus);
+    write_csr!(0x204, csrs.vsie);
+    write_csr!(0x205, csrs.vstvec);
+    write_csr!(0x240, csrs.vsscratch);
+    write_csr!(0x241, csrs.vsepc);
+    write_csr!(0x242, csrs.vscause);
+    write_csr!(0x243, csrs.vstval);
+    write_csr!(0x280, csrs.vsatp);
+    write_csr!(0x680, memory.hgatp());
+    // 不同客户机共用VMID 0，切换G-stage页表后刷新全部客户机的TLB
+    unsafe {
+        // hfence.gvma x0, x0
+        core::arch::asm!(".insn r 0x73, 0, 0x31, x0, x0, x0");
+    }
+    write_csr!(0x600, (hstatus | HSTATUS_SPV | HSTATUS_SPVP | HSTATUS_VTW));
+    // sret进入客户机的S-mode；浮点与向量单元关闭，宿主用户程序的寄存器不会被客户机改写
+    write_csr!(
+        0x100,
+        ((sstatus | SSTATUS_SPP) & !(SSTATUS_SPIE | SSTATUS_FS | SSTATUS_VS))
+    );
+
+    let mut context = RunContext {
+        guest: *regs,
+        host: HostRegs::default(),
+    };
+    unsafe { guest_enter(&mut context) };
+
+    let exit = GuestExit {
+        scause: read_csr!(0x142),
+        stval: read_csr!(0x143),
+        htval: read_csr!(0x643),
+        htinst: read_csr!(0x64a),
+    };
+    *regs = context.guest;
+    csrs.vsstatus = read_csr!(0x200);
+    csrs.vsie = read_csr!(0x204);
+    csrs.vstvec = read_csr!(0x205);
+    csrs.vsscratch = read_csr!(0x240);
+    csrs.vsepc = read_csr!(0x241);
+    csrs.vscause = read_csr!(0x242);
+    csrs.vstval = read_csr!(0x243);
+    csrs.vsatp = read_csr!(0x280);
+    // 陷入时hstatus.SPV被置位，恢复后宿主返回用户态时不会进入VU-mode
+    write_csr!(0x600, hstatus);
+    write_csr!(0x100, sstatus);
+    exit
+}
+
+/// 保存宿主现场后进入客户机，客户机陷入时保存客户机现场并返回
+///
+/// 客户机运行期间stvec指向本函数中的退出入口，sscratch保存 `context`
+#[naked]
+unsafe extern "C" fn guest_enter(context: *mut RunContext) {
+    core::arch::asm!(
+        "sd ra, {host}+0*8(a0)",
+        "sd sp, {host}+1*8(a0)",
+        "sd gp, {host}+2*8(a0)",
+        "sd tp, {host}+3*8(a0)",
+        "sd s0, {host}+4*8(a0)",
+        "sd s1, {host}+5*8(a0)",
+        "sd s2, {host}+6*8(a0)",
+        "sd s3, {host}+7*8(a0)",
+        "sd s4, {host}+8*8(a0)",
+        "sd s5, {host}+9*8(a0)",
+        "sd s6, {host}+10*8(a0)",
+        "sd s7, {host}+11*8(a0)",
+        "sd s8, {host}+12*8(a0)",
+        "sd s9, {host}+13*8(a0)",
+        "sd s10, {host}+14*8(a0)",
+        "sd s11, {host}+15*8(a0)",
+        "csrr t0, stvec",
+        "sd t0, {host}+16*8(a0)",
+        "csrr t0, sscratch",
+        "sd t0, {host}+17*8(a0)",
+        "la t0, 2f",
+        "csrw stvec, t0",
+        "csrw sscratch, a0",
+        "ld t0, 32*8(a0)",
+        "csrw sepc, t0",
+        "ld x1, 1*8(a0)",
+        "ld x2, 2*8(a0)",
+        "ld x3, 3*8(a0)",
+        "ld x4, 4*8(a0)",
+        "ld x5, 5*8(a0)",
+        "ld x6, 6*8(a0)",
+        "ld x7, 7*8(a0)",
+        "ld x8, 8*8(a0)",
+        "ld x9, 9*8(a0)",
+        "ld x11, 11*8(a0)",
+        "ld x12, 12*8(a0)",
+        "ld x13, 13*8(a0)",
+        "ld x14, 14*8(a0)",
+        "ld x15, 15*8(a0)",
+        "ld x16, 16*8(a0)",
+        "ld x17, 17*8(a0)",
+        "ld x18, 18*8(a0)",
+        "ld x19, 19*8(a0)",
+        "ld x20, 20*8(a0)",
+        "ld x21, 21*8(a0)",
+        "ld x22, 22*8(a0)",
+        "ld x23, 23*8(a0)",
+        "ld x24, 24*8(a0)",
+        "ld x25, 25*8(a0)",
+        "ld x26, 26*8(a0)",
+        "ld x27, 27*8(a0)",
+        "ld x28, 28*8(a0)",
+        "ld x29, 29*8(a0)",
+        "ld x30, 30*8(a0)",
+        "ld x31, 31*8(a0)",
+        "ld x10, 10*8(a0)",
+        "sret",
+        // 客户机陷入HS-mode的入口（stvec要求4字节对齐）
+        ".align 2",
+        "2:",
+        "csrrw a0, sscratch, a0",
+        "sd x1, 1*8(a0)",
+        "sd x2, 2*8(a0)",
+        "sd x3, 3*8(a0)",
+        "sd x4, 4*8(a0)",
+        "sd x5, 5*8(a0)",
+        "sd x6, 6*8(a0)",
+        "sd x7, 7*8(a0)",
+        "sd x8, 8*8(a0)",
+        "sd x9, 9*8(a0)",
+        "sd x11, 11*8(a0)",
+        "sd x12, 12*8(a0)",
+        "sd x13, 13*8(a0)",
+        "sd x14, 14*8(a0)",
+        "sd x15, 15*8(a0)",
+        "sd x16, 16*8(a0)",
+        "sd x17, 17*8(a0)",
+        "sd x18, 18*8(a0)",
+        "sd x19, 19*8(a0)",
+        "sd x20, 20*8(a0)",
+        "sd x21, 21*8(a0)",
+        "sd x22, 22*8(a0)",
+        "sd x23, 23*8(a0)",
+        "sd x24, 24*8(a0)",
+        "sd x25, 25*8(a0)",
+        "sd x26, 26*8(a0)",
+        "sd x27, 27*8(a0)",
+        "sd x28, 28*8(a0)",
+        "sd x29, 29*8(a0)",
+        "sd x30, 30*8(a0)",
+        "sd x31, 31*8(a0)",
+        "csrr t0, sscratch",
+        "sd t0, 10*8(a0)",
+        "csrr t0, sepc",
+        "sd t0, 32*8(a0)",
+        "ld t0, {host}+16*8(a0)",
+        "csrw stvec, t0",
+        "ld t0, {host}+17*8(a0)",
+        "csrw sscratch, t0",
+        "ld ra, {host}+0*8(a0)",
+        "ld sp, {host}+1*8(a0)",
+        "ld gp, {host}+2*8(a0)",
+        "ld tp, {host}+3*8(a0)",
+        "ld s0, {host}+4*8(a0)",
+        "ld s1, {host}+5*8(a0)",
+        "ld s2, {host}+6*8(a0)",
+        "ld s3, {host}+7*8(a0)",
+        "ld s4, {host}+8*8(a0)",
+        "ld s5, {host}+9*8(a0)",
+        "ld s6, {host}+10*8(a0)",
+        "ld s7, {host}+11*8(a0)",
+        "ld s8, {host}+12*8(a0)",
+        "ld s9, {host}+13*8(a0)",
+        "ld s10, {host}+14*8(a0)",
+        "ld s11, {host}+15*8(a0)",
+        "ret",
+        host = const HOST_OFFSET,
+        options(noreturn)
+    );
+}
+
+crate::kernel_test! {
+    fn guest_memory_maps_ram() {
+        let memory = GuestMemory::new(0x8000_0000, 4 * PAGE_SIZE).unwrap();
+        assert_eq!(memory.root_table() as usize % ROOT_SIZE, 0);
+        assert_eq!(memory.hgatp() & !HGATP_MODE_SV39X4, memory.root_table() as usize / PAGE_SIZE);
+        assert_eq!(memory.host_addr(0x8000_1000, 8), Some(memory.addr() + PAGE_SIZE));
+        assert_eq!(memory.host_addr(0x8000_3ff8, 16), None);
+        assert_eq!(memory.host_addr(0x7fff_f000, 8), None);
+        assert!(GuestMemory::new(0x8000_0000, 100).is_err());
+        assert!(GuestMemory::new(GUEST_PHYS_LIMIT - PAGE_SIZE, 2 * PAGE_SIZE).is_err());
+
+        // 客户机页表放在第一页：虚拟地址0起的1GiB大页映射到客户机内存起点，wfi放在最后一页
+        let root = memory.addr() as *mut usize;
+        unsafe {
+            *root = (0x8000_0000 / PAGE_SIZE) << 10 | PTE_V | PTE_R | PTE_X;
+            *((memory.addr() + 3 * PAGE_SIZE) as *mut u32) = 0x1050_0073;
+        }
+        let vsatp = (VSATP_MODE_SV39 << VSATP_MODE_SHIFT) | (0x8000_0000 / PAGE_SIZE);
+        assert_eq!(memory.translate(vsatp, 0x3004), Some(0x8000_3004));
+        assert_eq!(memory.translate(vsatp, 0x4000_0000), None);
+        assert_eq!(memory.fetch_insn(vsatp, 0x3000), Some(0x1050_0073));
+        assert_eq!(memory.fetch_insn(0, 0x8000_3000), Some(0x1050_0073));
+    }
+}
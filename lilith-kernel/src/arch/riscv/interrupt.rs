@@ -1,14 +1,299 @@
 //! RISC-V中断处理实现
+//!
+//! 外部中断按中断号分发给登记的处理函数。慢速设备可以登记线程化的处理函数：
+//! 中断中只执行应答函数并唤醒该中断专用的内核任务（`irq/N`），其余处理在任务中进行，可以睡眠。
+//! 中断线在处理函数完成前保持认领状态，电平触发的设备须在应答函数中清除中断条件。
+//! 有PLIC时登记处理函数会开启对应的中断源（见 `plic`）
 
 use crate::error::KernelError;
+use crate::sched;
+use crate::sync::{rcu_read_lock, RcuCell, SpinLock, WaitQueue};
+use alloc::collections::btree_map::Entry;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 外部中断处理函数，参数为中断号
+pub type IrqHandler = fn(irq: usize);
+
+/// 中断处理函数表，中断路径上无锁读取
+static IRQ_HANDLERS: RcuCell<BTreeMap<usize, IrqHandler>> = RcuCell::empty();
 
 /// 初始化中断系统
 pub fn init_interrupt_system() -> Result<(), KernelError> {
     crate::early_println!("初始化RISC-V中断系统...");
     
-    // 这里将实现中断系统的初始化
-    // 包括PLIC配置、中断向量设置等
-    
+    // 设置S-mode陷入入口（异常、中断与系统调用）
+    super::trap::init_trap();
+
     crate::early_println!("RISC-V中断系统初始化完成");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 注册外部中断处理函数
+pub fn register_irq_handler(irq: usize, handler: IrqHandler) -> Result<(), KernelError> {
+    let mut result = Ok(());
+    IRQ_HANDLERS.update(|handlers| {
+        let mut handlers = handlers.cloned().unwrap_or_default();
+        match handlers.entry(irq) {
+            Entry::Occupied(_) => result = Err(KernelError::ResourceBusy),
+            Entry::Vacant(entry) => {
+                entry.insert(handler);
+            }
+        }
+        Some(handlers)
+    });
+    if result.is_ok() {
+        super::plic::enable_irq(irq);
+    }
+    result
+}
+
+/// 注销外部中断处理函数，线程化的处理函数的内核任务随之退出
+///
+/// 返回后其他hart可能仍在执行旧的处理函数，
+/// 释放处理函数使用的资源前需调用 `synchronize_rcu`
+pub fn unregister_irq_handler(irq: usize) {
+    super::plic::disable_irq(irq);
+    IRQ_HANDLERS.update(|handlers| {
+        let mut handlers = handlers.cloned().unwrap_or_default();
+        handlers.remove(&irq);
+        Some(handlers)
+    });
+    let mut removed = None;
+    IRQ_THREADS.update(|threads| {
+        let mut threads = threads.cloned().unwrap_or_default();
+        removed = threads.remove(&irq);
+        Some(threads)
+    });
+    if let Some(thread) = removed {
+        thread.exiting.store(true, Ordering::Release);
+        thread.wait.wake_all();
+    }
+}
+
+/// 线程化中断处理函数的状态
+struct IrqThread {
+    irq: usize,
+    /// 在中断中执行的应答函数
+    ack: Option<IrqHandler>,
+    /// 在内核任务中执行的处理函数
+    thread_fn: IrqHandler,
+    /// 中断已到来、尚未处理
+    pending: AtomicBool,
+    /// 已注销，任务应退出
+    exiting: AtomicBool,
+    wait: WaitQueue,
+}
+
+/// 线程化的中断，中断路径上无锁读取
+static IRQ_THREADS: RcuCell<BTreeMap<usize, Arc<IrqThread>>> = RcuCell::empty();
+
+/// 已登记、等待内核任务取走的线程化中断
+static STARTING_THREADS: SpinLock<Vec<Arc<IrqThread>>> = SpinLock::new(Vec::new());
+
+/// 线程化中断在中断中的部分：应答后唤醒处理任务
+fn irq_wake_thread(irq: usize) {
+    let guard = rcu_read_lock();
+    let Some(thread) = IRQ_THREADS.read(&guard).and_then(|threads| threads.get(&irq)) else {
+        return;
+    };
+    if let Some(ack) = thread.ack {
+        ack(irq);
+    }
+    thread.pending.store(true, Ordering::Release);
+    thread.wait.wake_all();
+}
+
+/// 线程化中断的处理任务
+fn irq_thread_main() {
+    // 每次创建任务前放入一个，任务取走哪一个都可以
+    let Some(thread) = STARTING_THREADS.lock().pop() else {
+        return;
+    };
+    loop {
+        thread
+            .wait
+            .wait_until(|| thread.pending.load(Ordering::Acquire) || thread.exiting.load(Ordering::Acquire));
+        if thread.exiting.load(Ordering::Acquire) {
+            return;
+        }
+        thread.pending.store(false, Ordering::Release);
+        (thread.thread_fn)(thread.irq);
+    }
+}
+
+/// 注册线程化的外部中断处理函数
+///
+/// 中断到来时在中断上下文中调用 `ack`（可以为空），随后由内核任务 `irq/N` 调用 `thread_fn`；
+/// 处理任务运行前到来的多次中断合并为一次调用
+pub fn register_threaded_irq_handler(
+    irq: usize,
+    ack: Option<IrqHandler>,
+    thread_fn: IrqHandler,
+) -> Result<(), KernelError> {
+    let thread = Arc::new(IrqThread {
+        irq,
+        ack,
+        thread_fn,
+        pending: AtomicBool::new(false),
+        exiting: AtomicBool::new(false),
+        wait: WaitQueue::new(),
+    });
+    let mut result = Ok(());
+    IRQ_THREADS.update(|threads| {
+        let mut threads = threads.cloned().unwrap_or_default();
+        match threads.entry(irq) {
+            Entry::Occupied(_) => result = Err(KernelError::ResourceBusy),
+            Entry::Vacant(entry) => {
+                entry.insert(thread.clone());
+            }
+        }
+        Some(threads)
+    });
+    result?;
+    if let Err(e) = register_irq_handler(irq, irq_wake_thread) {
+        IRQ_THREADS.update(|threads| {
+            let mut threads = threads.cloned().unwrap_or_default();
+            threads.remove(&irq);
+            Some(threads)
+        });
+        return Err(e);
+    }
+
+    STARTING_THREADS.lock().push(thread);
+    sched::spawn(&format!("irq/{}", irq), irq_thread_main);
+    Ok(())
+}
+
+/// 分发外部中断，返回是否有处理函数
+pub fn dispatch_irq(irq: usize) -> bool {
+    // 处理函数在读侧临界区内执行，注销方据此等待其完成
+    let guard = rcu_read_lock();
+    let handler = IRQ_HANDLERS
+        .read(&guard)
+        .and_then(|handlers| handlers.get(&irq).copied());
+
+    match handler {
+        Some(handler) => {
+            handler(irq);
+            true
+        }
+        None => false,
+    }
+}
+
+/// sstatus.SIE 位
+const SSTATUS_SIE: usize = 1 << 1;
+
+/// sie.SSIE 位（sip.SSIP 位置相同）
+const SIE_SSIE: usize = 1 << 1;
+
+/// sie.STIE 位
+const SIE_STIE: usize = 1 << 5;
+
+/// sie.SEIE 位
+const SIE_SEIE: usize = 1 << 9;
+
+/// 允许S-mode时钟中断
+pub fn enable_timer_interrupt() {
+    unsafe {
+        core::arch::asm!("csrs sie, {}", in(reg) SIE_STIE);
+    }
+}
+
+/// 屏蔽S-mode时钟中断
+pub fn disable_timer_interrupt() {
+    unsafe {
+        core::arch::asm!("csrc sie, {}", in(reg) SIE_STIE);
+    }
+}
+
+/// 允许S-mode软件中断（核间中断）
+pub fn enable_software_interrupt() {
+    unsafe {
+        core::arch::asm!("csrs sie, {}", in(reg) SIE_SSIE);
+    }
+}
+
+/// 允许S-mode外部中断
+pub fn enable_external_interrupt() {
+    unsafe {
+        core::arch::asm!("csrs sie, {}", in(reg) SIE_SEIE);
+    }
+}
+
+/// 清除挂起的软件中断
+pub fn clear_software_interrupt() {
+    unsafe {
+        core::arch::asm!("csrc sip, {}", in(reg) SIE_SSIE);
+    }
+}
+
+/// 关闭本核中断并返回之前的中断状态
+#[inline]
+pub fn local_irq_save() -> usize {
+    let flags: usize;
+    unsafe {
+        core::arch::asm!("csrrci {}, sstatus, {}", out(reg) flags, const SSTATUS_SIE);
+    }
+    flags & SSTATUS_SIE
+}
+
+/// 恢复由 `local_irq_save` 保存的中断状态
+#[inline]
+pub fn local_irq_restore(flags: usize) {
+    if flags & SSTATUS_SIE != 0 {
+        unsafe {
+            core::arch::asm!("csrsi sstatus, {}", const SSTATUS_SIE);
+        }
+    }
+}
+
+/// 打开本核中断
+#[inline]
+pub fn local_irq_enable() {
+    unsafe {
+        core::arch::asm!("csrsi sstatus, {}", const SSTATUS_SIE);
+    }
+}
+
+/// 关闭本核中断
+#[inline]
+pub fn local_irq_disable() {
+    unsafe {
+        core::arch::asm!("csrci sstatus, {}", const SSTATUS_SIE);
+    }
+}
+
+/// 本核中断是否已关闭
+#[inline]
+pub fn irqs_disabled() -> bool {
+    let sstatus: usize;
+    unsafe {
+        core::arch::asm!("csrr {}, sstatus", out(reg) sstatus);
+    }
+    sstatus & SSTATUS_SIE == 0
+}
+
+crate::kernel_test! {
+    fn threaded_irq_ack_and_wake() {
+        use core::sync::atomic::AtomicUsize;
+
+        static ACKS: AtomicUsize = AtomicUsize::new(0);
+        fn ack(_irq: usize) {
+            ACKS.fetch_add(1, Ordering::Relaxed);
+        }
+        fn thread_fn(_irq: usize) {}
+
+        const IRQ: usize = 1023;
+        register_threaded_irq_handler(IRQ, Some(ack), thread_fn).unwrap();
+        assert_eq!(register_irq_handler(IRQ, ack), Err(KernelError::ResourceBusy));
+        assert!(dispatch_irq(IRQ));
+        assert_eq!(ACKS.load(Ordering::Relaxed), 1);
+        unregister_irq_handler(IRQ);
+        assert!(!dispatch_irq(IRQ));
+    }
+}
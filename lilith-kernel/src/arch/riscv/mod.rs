@@ -5,7 +5,23 @@
 pub mod registers;
 pub mod interrupt;
 pub mod memory;
+#[cfg(feature = "smp")]
 pub mod smp;
+#[cfg(feature = "smp")]
+pub mod hotplug;
+pub mod context;
+#[cfg(feature = "ftrace")]
+pub mod ftrace;
+pub mod fpu;
+pub mod hypervisor;
+pub mod pgtable;
+pub mod plic;
+pub mod pmu;
+pub mod sbi;
+pub mod trap;
+pub mod uaccess;
+pub mod vdso;
+pub mod vector;
 
 use crate::error::KernelError;
 
@@ -13,7 +29,12 @@ use crate::error::KernelError;
 pub use registers::*;
 pub use interrupt::*;
 pub use memory::*;
+#[cfg(feature = "smp")]
 pub use smp::*;
+pub use context::*;
+pub use trap::*;
+pub use uaccess::*;
+pub use vdso::*;
 
 /// 等待中断
 pub fn wait_for_interrupt() {
@@ -22,9 +43,39 @@ pub fn wait_for_interrupt() {
     }
 }
 
+/// 使所有hart之后的取指看到此前写入内存的指令（如加载的模块）
+pub fn flush_icache() {
+    unsafe {
+        core::arch::asm!("fence.i");
+    }
+    #[cfg(feature = "smp")]
+    sbi::remote_fence_i();
+}
+
+/// 读取当前的帧指针（s0）
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, s0", out(reg) fp);
+    }
+    fp
+}
+
+/// 读取当前的栈指针
+#[inline(always)]
+pub fn stack_pointer() -> usize {
+    let sp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, sp", out(reg) sp);
+    }
+    sp
+}
+
 /// 停止所有CPU核心
 pub fn halt_all_cores() -> ! {
     // 发送停止信号给其他核心
+    #[cfg(feature = "smp")]
     smp::halt_other_cores();
     
     // 停止当前核心
@@ -35,6 +86,42 @@ pub fn halt_all_cores() -> ! {
     }
 }
 
+/// 通过固件关闭电源，失败时停止所有核心
+pub fn machine_power_off() -> ! {
+    sbi::system_reset(sbi::RESET_TYPE_SHUTDOWN, sbi::RESET_REASON_NONE);
+    // 不支持SRST的旧固件
+    sbi::legacy_shutdown();
+    halt_all_cores()
+}
+
+/// 通过固件冷重启，失败时停止所有核心
+pub fn machine_restart() -> ! {
+    sbi::system_reset(sbi::RESET_TYPE_COLD_REBOOT, sbi::RESET_REASON_NONE);
+    halt_all_cores()
+}
+
+/// 关闭分页并跳转到kexec加载的内核
+///
+/// 按Linux RISC-V启动约定传递参数：a0为当前hart编号，a1为设备树的物理地址
+///
+/// # Safety
+///
+/// 调用前必须已停止其他hart并关闭中断；`entry` 与 `fdt` 必须是已写入内核映像与
+/// 设备树的物理地址，且当前代码位于恒等映射中
+pub unsafe fn kexec_jump(entry: usize, hart_id: usize, fdt: usize) -> ! {
+    core::arch::asm!(
+        "csrw sie, zero",
+        "csrw satp, zero",
+        "sfence.vma",
+        "fence.i",
+        "jr {entry}",
+        entry = in(reg) entry,
+        in("a0") hart_id,
+        in("a1") fdt,
+        options(noreturn)
+    );
+}
+
 /// 初始化中断系统
 pub fn interrupt_init() -> Result<(), KernelError> {
     interrupt::init_interrupt_system()
@@ -0,0 +1,260 @@
+//! Sv39页表操作
+//!
+//! 内核页表由启动代码建立，内核运行在恒等映射中（虚拟地址等于物理地址），本模块在其上：
+//! - 遍历所有叶子映射，查询用户页的物理地址
+//! - 修改一段地址上内核映射的权限位，大页只部分落在范围内时拆分为下一级页表
+//! - 修改一段地址上已建立的用户映射的访问权限（mprotect）
+//! - 刷新本hart与其他hart的TLB，可以只刷新一段地址
+//!
+//! 修改内核映射时跳过用户映射，反之亦然；拆分出的页表页从内核堆分配，不再释放
+
+use crate::error::KernelError;
+use riscv::register::satp;
+
+/// 页表项标志
+pub const PTE_V: usize = 1 << 0;
+pub const PTE_R: usize = 1 << 1;
+pub const PTE_W: usize = 1 << 2;
+pub const PTE_X: usize = 1 << 3;
+pub const PTE_U: usize = 1 << 4;
+
+/// 软件保留位：不可访问（`PROT_NONE`）的用户页。清除U位使用户态无法访问，保留R位使其仍是叶子
+pub const PTE_PROT_NONE: usize = 1 << 8;
+
+/// 用户映射：带U位或被设为不可访问
+const PTE_USER: usize = PTE_U | PTE_PROT_NONE;
+
+/// 页大小
+pub const PAGE_SIZE: usize = 4096;
+
+/// 页表级数，第0级映射4KiB页
+const LEVELS: usize = 3;
+
+/// 每个页表页的表项数
+const ENTRIES: usize = 512;
+
+/// satp中Sv39的模式值
+const SATP_MODE_SV39: usize = 8;
+
+/// 按页刷新TLB的最大页数，超过时刷新全部
+const FLUSH_PAGES_MAX: usize = 64;
+
+/// 第 `level` 级表项映射的大小
+const fn level_size(level: usize) -> usize {
+    PAGE_SIZE << (9 * level)
+}
+
+/// 把39位虚拟地址按第38位符号扩展为64位
+const fn sign_extend(va: usize) -> usize {
+    ((va << 25) as isize >> 25) as usize
+}
+
+/// 表项是否为叶子（映射页而不是指向下一级页表）
+const fn is_leaf(pte: usize) -> bool {
+    pte & (PTE_R | PTE_W | PTE_X) != 0
+}
+
+/// 表项指向的页表页
+fn child_table(pte: usize) -> *mut usize {
+    ((pte >> 10) << 12) as *mut usize
+}
+
+/// 当前的根页表，未开启Sv39分页时返回 `None`
+pub fn root_table() -> Option<*mut usize> {
+    let bits = satp::read().bits();
+    if bits >> 60 != SATP_MODE_SV39 {
+        return None;
+    }
+    Some(((bits & ((1 << 44) - 1)) << 12) as *mut usize)
+}
+
+/// 刷新所有hart的TLB
+pub fn flush_tlb_all() {
+    unsafe {
+        core::arch::asm!("sfence.vma");
+    }
+    #[cfg(feature = "smp")]
+    super::sbi::remote_sfence_vma(0, usize::MAX);
+}
+
+/// 刷新所有hart上 `[start, end)` 的TLB，范围较大时刷新全部
+pub fn flush_tlb_range(start: usize, end: usize) {
+    let start = start & !(PAGE_SIZE - 1);
+    if end <= start {
+        return;
+    }
+    if (end - start) / PAGE_SIZE > FLUSH_PAGES_MAX {
+        flush_tlb_all();
+        return;
+    }
+    for va in (start..end).step_by(PAGE_SIZE) {
+        unsafe {
+            core::arch::asm!("sfence.vma {}, zero", in(reg) va);
+        }
+    }
+    #[cfg(feature = "smp")]
+    super::sbi::remote_sfence_vma(start, end - start);
+}
+
+/// 按地址顺序对每个叶子映射调用 `f(起始地址, 大小, 表项)`
+pub fn for_each_leaf(mut f: impl FnMut(usize, usize, usize)) {
+    fn walk(table: *mut usize, level: usize, base: usize, f: &mut dyn FnMut(usize, usize, usize)) {
+        let size = level_size(level);
+        for index in 0..ENTRIES {
+            let va = sign_extend(base + index * size);
+            let pte = unsafe { *table.add(index) };
+            if pte & PTE_V == 0 {
+                continue;
+            }
+            if is_leaf(pte) {
+                f(va, size, pte);
+            } else if level > 0 {
+                walk(child_table(pte), level - 1, va, f);
+            }
+        }
+    }
+
+    if let Some(root) = root_table() {
+        walk(root, LEVELS - 1, 0, &mut f);
+    }
+}
+
+/// `va` 所在映射的叶子表项，未映射时返回 `None`
+pub fn leaf_entry(va: usize) -> Option<*mut usize> {
+    let mut table = root_table()?;
+    for level in (0..LEVELS).rev() {
+        let index = (va >> (12 + 9 * level)) & (ENTRIES - 1);
+        let entry = unsafe { table.add(index) };
+        let pte = unsafe { *entry };
+        if pte & PTE_V == 0 {
+            return None;
+        }
+        if is_leaf(pte) {
+            return Some(entry);
+        }
+        table = child_table(pte);
+    }
+    None
+}
+
+/// 查询 `va` 所在映射的表项，未映射时返回 `None`
+pub fn lookup(va: usize) -> Option<usize> {
+    leaf_entry(va).map(|entry| unsafe { *entry })
+}
+
+/// `va` 所在的用户页的物理地址（页对齐），未映射或不是用户映射时返回 `None`
+pub fn user_page(va: usize) -> Option<usize> {
+    let mut table = root_table()?;
+    for level in (0..LEVELS).rev() {
+        let index = (va >> (12 + 9 * level)) & (ENTRIES - 1);
+        let pte = unsafe { *table.add(index) };
+        if pte & PTE_V == 0 {
+            return None;
+        }
+        if is_leaf(pte) {
+            if pte & PTE_USER == 0 {
+                return None;
+            }
+            // 大页中按页的偏移
+            let offset = va & (level_size(level) - 1) & !(PAGE_SIZE - 1);
+            return Some(((pte >> 10) << 12) + offset);
+        }
+        table = child_table(pte);
+    }
+    None
+}
+
+/// 把第 `level` 级的大页拆分为下一级页表，返回指向新页表的表项
+fn split(pte: usize, level: usize) -> Result<usize, KernelError> {
+    let table = crate::mm::page_owner::alloc_pages(PAGE_SIZE, "pgtable")?.as_ptr() as *mut usize;
+    // 子表项继承权限，物理页号依次递增
+    let step = (level_size(level - 1) / PAGE_SIZE) << 10;
+    for index in 0..ENTRIES {
+        unsafe { *table.add(index) = pte + index * step };
+    }
+    Ok(((table as usize >> 12) << 10) | PTE_V)
+}
+
+/// 对 `[start, end)` 中 `select` 选中的叶子表项应用 `modify`
+///
+/// 地址按页向外对齐，未映射的部分跳过，大页只有一部分在范围内时先拆分
+fn update_leaves(
+    start: usize,
+    end: usize,
+    select: &dyn Fn(usize) -> bool,
+    modify: &dyn Fn(usize) -> usize,
+) -> Result<(), KernelError> {
+    fn update(
+        table: *mut usize,
+        level: usize,
+        base: usize,
+        range: (usize, usize),
+        select: &dyn Fn(usize) -> bool,
+        modify: &dyn Fn(usize) -> usize,
+    ) -> Result<(), KernelError> {
+        let size = level_size(level);
+        for index in 0..ENTRIES {
+            let va = sign_extend(base + index * size);
+            let last = va + (size - 1);
+            if last < range.0 || va > range.1 {
+                continue;
+            }
+            let entry = unsafe { &mut *table.add(index) };
+            if *entry & PTE_V == 0 {
+                continue;
+            }
+            if is_leaf(*entry) {
+                if !select(*entry) {
+                    continue;
+                }
+                if range.0 <= va && last <= range.1 {
+                    *entry = modify(*entry);
+                    continue;
+                }
+                // 大页只有一部分在范围内
+                *entry = split(*entry, level)?;
+            }
+            if level > 0 {
+                update(child_table(*entry), level - 1, va, range, select, modify)?;
+            }
+        }
+        Ok(())
+    }
+
+    let Some(root) = root_table() else {
+        return Ok(());
+    };
+    if end <= start {
+        return Ok(());
+    }
+    // 用闭区间表示，避免范围末尾为地址空间顶端时溢出
+    let range = (start & !(PAGE_SIZE - 1), (end - 1) | (PAGE_SIZE - 1));
+    update(root, LEVELS - 1, 0, range, select, modify)
+}
+
+/// 修改 `[start, end)` 中内核映射的权限：置上 `set` 中的位，清除 `clear` 中的位
+///
+/// 地址按页向外对齐，未映射的部分跳过。修改后须调用 `flush_tlb_all`
+pub fn update_kernel_flags(start: usize, end: usize, set: usize, clear: usize) -> Result<(), KernelError> {
+    update_leaves(start, end, &|pte| pte & PTE_USER == 0, &|pte| (pte | set) & !clear)
+}
+
+/// 把 `[start, end)` 中已建立的用户映射的访问权限设为 `perms`（`PTE_R`、`PTE_W`、`PTE_X` 的组合）
+///
+/// `perms` 为0时页保留但用户态不能访问；只有写权限时同时给读权限（RISC-V不允许只写的页）。
+/// 内核映射不受影响，修改后须调用 `flush_tlb_range`
+pub fn update_user_flags(start: usize, end: usize, perms: usize) -> Result<(), KernelError> {
+    let mut perms = perms & (PTE_R | PTE_W | PTE_X);
+    if perms & PTE_W != 0 {
+        perms |= PTE_R;
+    }
+    let modify = move |pte: usize| {
+        let pte = pte & !(PTE_R | PTE_W | PTE_X | PTE_USER);
+        if perms == 0 {
+            pte | PTE_R | PTE_PROT_NONE
+        } else {
+            pte | perms | PTE_U
+        }
+    };
+    update_leaves(start, end, &|pte| pte & PTE_USER != 0, &modify)
+}
@@ -0,0 +1,351 @@
+//! 平台级中断控制器（PLIC）
+//!
+//! 本模块实现了RISC-V PLIC驱动与外部中断的hart亲和性，包括：
+//! - 从设备树找到PLIC（`riscv,plic0` 或 `sifive,plic-1.0.0`），读取中断源数量 `riscv,ndev`
+//! - 外部中断到来时认领（claim）中断号，处理后完成（complete）
+//! - 登记处理函数时开启中断源，注销时关闭；每个中断源只路由到一个hart
+//! - /proc/irq/N/smp_affinity：读取与设置允许处理该中断的hart位图（十六进制），
+//!   从位图中选择第一个在线的hart
+//! - 中断均衡：每隔 `BALANCE_INTERVAL` 按各中断源这段时间的次数，把最频繁的中断依次分配给负载最小的hart；
+//!   手动设置过亲和性的中断只在其位图内移动，路由到已下线hart的中断总会被移走。启动参数 `noirqbalance` 关闭均衡
+//!
+//! hart的S-mode上下文编号按QEMU virt等平台的约定取 `2 * hart + 1`
+
+use crate::boot::{cmdline, fdt};
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sched::workqueue::{self, DelayedWork};
+use crate::sched::MAX_HARTS;
+use crate::sync::SpinLockIrqSave;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+use spin::Once;
+
+/// 寄存器偏移
+const PRIORITY_BASE: usize = 0x0;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0x0;
+const CONTEXT_CLAIM: usize = 0x4;
+
+/// 开启的中断源使用的优先级（0表示屏蔽）
+const DEFAULT_PRIORITY: u32 = 1;
+
+/// 均衡的间隔
+const BALANCE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 所有hart的位图
+const ALL_HARTS: usize = (1 << MAX_HARTS) - 1;
+
+/// 一个中断源的状态
+struct Source {
+    /// 是否登记了处理函数
+    enabled: AtomicBool,
+    /// 允许处理的hart位图
+    affinity: AtomicUsize,
+    /// 当前路由到的hart
+    target: AtomicUsize,
+    /// 亲和性是否被手动设置过
+    user_set: AtomicBool,
+    /// 累计次数与上次均衡时的次数
+    count: AtomicU64,
+    balanced_count: AtomicU64,
+}
+
+/// PLIC
+struct Plic {
+    base: usize,
+    /// 中断源数量，中断号为 `1..=ndev`
+    ndev: usize,
+    /// 按中断号索引，0号不使用
+    sources: Vec<Source>,
+    /// 串行化开启位的读改写
+    route_lock: SpinLockIrqSave<()>,
+}
+
+static PLIC: Once<Plic> = Once::new();
+
+/// hart的S-mode上下文
+const fn context(hart: usize) -> usize {
+    2 * hart + 1
+}
+
+/// 在线hart的位图
+fn online_mask() -> usize {
+    #[cfg(feature = "smp")]
+    {
+        crate::sched::hotplug::online_mask()
+    }
+    #[cfg(not(feature = "smp"))]
+    {
+        1
+    }
+}
+
+impl Plic {
+    unsafe fn read(&self, offset: usize) -> u32 {
+        core::ptr::read_volatile((self.base + offset) as *const u32)
+    }
+
+    unsafe fn write(&self, offset: usize, value: u32) {
+        core::ptr::write_volatile((self.base + offset) as *mut u32, value);
+    }
+
+    fn source(&self, irq: usize) -> Option<&Source> {
+        self.sources.get(irq).filter(|_| irq != 0)
+    }
+
+    /// 只在 `hart` 的上下文中开启中断源，`hart` 为 `None` 时在所有上下文中关闭
+    fn route(&self, irq: usize, hart: Option<usize>) {
+        let _guard = self.route_lock.lock();
+        let (word, bit) = (irq / 32, 1u32 << (irq % 32));
+        for h in 0..MAX_HARTS {
+            let offset = ENABLE_BASE + context(h) * ENABLE_STRIDE + word * 4;
+            unsafe {
+                let value = self.read(offset);
+                let value = if Some(h) == hart { value | bit } else { value & !bit };
+                self.write(offset, value);
+            }
+        }
+    }
+
+    /// 改变中断源的目标hart，开启的中断源立即重新路由
+    fn retarget(&self, irq: usize, hart: usize) {
+        let Some(source) = self.source(irq) else {
+            return;
+        };
+        source.target.store(hart, Ordering::Relaxed);
+        if source.enabled.load(Ordering::Acquire) {
+            self.route(irq, Some(hart));
+        }
+    }
+}
+
+/// 是否有PLIC
+pub fn present() -> bool {
+    PLIC.get().is_some()
+}
+
+/// 开启中断源并路由到其目标hart
+pub fn enable_irq(irq: usize) {
+    let Some(plic) = PLIC.get() else { return };
+    let Some(source) = plic.source(irq) else { return };
+    source.enabled.store(true, Ordering::Release);
+    unsafe {
+        plic.write(PRIORITY_BASE + irq * 4, DEFAULT_PRIORITY);
+    }
+    plic.route(irq, Some(source.target.load(Ordering::Relaxed)));
+}
+
+/// 关闭中断源
+pub fn disable_irq(irq: usize) {
+    let Some(plic) = PLIC.get() else { return };
+    let Some(source) = plic.source(irq) else { return };
+    source.enabled.store(false, Ordering::Release);
+    unsafe {
+        plic.write(PRIORITY_BASE + irq * 4, 0);
+    }
+    plic.route(irq, None);
+}
+
+/// 设置中断源的亲和性，路由到位图中第一个在线的hart
+///
+/// 中断号无效或位图中没有在线的hart时返回 `InvalidArgument`
+pub fn set_affinity(irq: usize, mask: usize) -> Result<(), KernelError> {
+    let plic = PLIC.get().ok_or(KernelError::NotSupported)?;
+    let source = plic.source(irq).ok_or(KernelError::InvalidArgument)?;
+    let allowed = mask & ALL_HARTS & online_mask();
+    if allowed == 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    source.affinity.store(mask & ALL_HARTS, Ordering::Relaxed);
+    source.user_set.store(true, Ordering::Relaxed);
+    plic.retarget(irq, allowed.trailing_zeros() as usize);
+    Ok(())
+}
+
+/// 中断源的亲和性位图
+pub fn affinity(irq: usize) -> Option<usize> {
+    Some(PLIC.get()?.source(irq)?.affinity.load(Ordering::Relaxed))
+}
+
+/// 认领当前hart上待处理的中断，没有时返回 `None`
+pub fn claim() -> Option<usize> {
+    let plic = PLIC.get()?;
+    let offset = CONTEXT_BASE + context(crate::arch::hart_id()) * CONTEXT_STRIDE + CONTEXT_CLAIM;
+    let irq = unsafe { plic.read(offset) } as usize;
+    let source = plic.source(irq)?;
+    source.count.fetch_add(1, Ordering::Relaxed);
+    Some(irq)
+}
+
+/// 完成中断处理，之后同一中断源才能再次到来
+pub fn complete(irq: usize) {
+    let Some(plic) = PLIC.get() else { return };
+    let offset = CONTEXT_BASE + context(crate::arch::hart_id()) * CONTEXT_STRIDE + CONTEXT_CLAIM;
+    unsafe {
+        plic.write(offset, irq as u32);
+    }
+}
+
+/// 按最近的中断次数把中断源分配给负载最小的hart
+fn balance(plic: &Plic) {
+    let online = online_mask();
+    // (次数, 中断号)
+    let mut loads: Vec<(u64, usize)> = Vec::new();
+    for (irq, source) in plic.sources.iter().enumerate().skip(1) {
+        if !source.enabled.load(Ordering::Acquire) {
+            continue;
+        }
+        let count = source.count.load(Ordering::Relaxed);
+        let last = source.balanced_count.swap(count, Ordering::Relaxed);
+        let target = source.target.load(Ordering::Relaxed);
+        // 手动设置的中断只有目标hart下线时才移动
+        if source.user_set.load(Ordering::Relaxed) && online & (1 << target) != 0 {
+            continue;
+        }
+        loads.push((count - last, irq));
+    }
+    loads.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut hart_load = [0u64; MAX_HARTS];
+    for (delta, irq) in loads {
+        let source = &plic.sources[irq];
+        let allowed = match source.affinity.load(Ordering::Relaxed) & online {
+            0 => online,
+            allowed => allowed,
+        };
+        let Some(hart) = (0..MAX_HARTS)
+            .filter(|&hart| allowed & (1 << hart) != 0)
+            .min_by_key(|&hart| hart_load[hart])
+        else {
+            continue;
+        };
+        hart_load[hart] += delta;
+        if source.target.load(Ordering::Relaxed) != hart {
+            plic.retarget(irq, hart);
+        }
+    }
+}
+
+/// 均衡任务，每次执行后重新排队
+static BALANCER: Once<Arc<DelayedWork>> = Once::new();
+
+fn balance_work() {
+    if let Some(plic) = PLIC.get() {
+        if online_mask().count_ones() > 1 {
+            balance(plic);
+        }
+    }
+    if let Some(work) = BALANCER.get() {
+        workqueue::schedule_delayed_work(work, BALANCE_INTERVAL);
+    }
+}
+
+/// 设备树节点 `path` 的 `reg` 中的第一个地址，按父节点的 `#address-cells` 解析
+fn reg_base(fdt: &fdt::Fdt, path: &str) -> Option<usize> {
+    let parent = &path[..path.rfind('/')?];
+    let parent = if parent.is_empty() { "/" } else { parent };
+    let cells = fdt.property_u64(parent, "#address-cells").unwrap_or(2) as usize;
+    let reg = fdt.property(path, "reg")?.get(..cells * 4)?;
+    Some(reg.chunks(4).fold(0u64, |value, chunk| {
+        (value << 32) | u64::from(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+    }) as usize)
+}
+
+/// 初始化PLIC：屏蔽所有中断源，各hart的阈值设为0，登记亲和性文件并启动均衡
+///
+/// 设备树中没有PLIC时不做任何事，外部中断按0号中断分发
+pub fn plic_init() -> Result<(), KernelError> {
+    let Some(fdt) = fdt::fdt() else {
+        return Ok(());
+    };
+    let Some(device) = fdt
+        .devices()
+        .into_iter()
+        .find(|device| device.enabled && matches!(device.compatible, "riscv,plic0" | "sifive,plic-1.0.0"))
+    else {
+        return Ok(());
+    };
+    let base = reg_base(fdt, &device.path).ok_or(KernelError::InvalidArgument)?;
+    let ndev = fdt
+        .property_u64(&device.path, "riscv,ndev")
+        .ok_or(KernelError::InvalidArgument)? as usize;
+
+    let boot_hart = crate::arch::hart_id();
+    let sources = (0..=ndev)
+        .map(|_| Source {
+            enabled: AtomicBool::new(false),
+            affinity: AtomicUsize::new(ALL_HARTS),
+            target: AtomicUsize::new(boot_hart),
+            user_set: AtomicBool::new(false),
+            count: AtomicU64::new(0),
+            balanced_count: AtomicU64::new(0),
+        })
+        .collect();
+    let plic = PLIC.call_once(|| Plic {
+        base,
+        ndev,
+        sources,
+        route_lock: SpinLockIrqSave::new(()),
+    });
+    for irq in 1..=plic.ndev {
+        unsafe {
+            plic.write(PRIORITY_BASE + irq * 4, 0);
+        }
+        plic.route(irq, None);
+    }
+    for hart in 0..MAX_HARTS {
+        unsafe {
+            plic.write(CONTEXT_BASE + context(hart) * CONTEXT_STRIDE + CONTEXT_THRESHOLD, 0);
+        }
+    }
+
+    for irq in 1..=plic.ndev {
+        procfs::register(
+            &format!("irq/{}/smp_affinity", irq),
+            Some(Box::new(move || format!("{:x}\n", affinity(irq).unwrap_or(0)))),
+            Some(Box::new(move |data| {
+                let mask = usize::from_str_radix(data.trim(), 16).map_err(|_| KernelError::InvalidArgument)?;
+                set_affinity(irq, mask)
+            })),
+        )?;
+    }
+
+    if !cmdline::has("noirqbalance") {
+        let work = BALANCER.call_once(|| DelayedWork::new("irqbalance", balance_work));
+        workqueue::schedule_delayed_work(work, BALANCE_INTERVAL);
+    }
+    super::enable_external_interrupt();
+    crate::log_info!("PLIC: 基地址 0x{:x}，{}个中断源", base, ndev);
+    Ok(())
+}
+
+crate::kernel_test! {
+    fn plic_set_affinity() {
+        let Some(plic) = PLIC.get() else {
+            assert_eq!(set_affinity(1, 1), Err(KernelError::NotSupported));
+            return;
+        };
+        let hart = crate::arch::hart_id();
+        assert_eq!(set_affinity(0, 1 << hart), Err(KernelError::InvalidArgument));
+        assert_eq!(set_affinity(plic.ndev + 1, 1 << hart), Err(KernelError::InvalidArgument));
+        // 没有在线hart的位图被拒绝，原设置不变
+        assert_eq!(set_affinity(1, 0), Err(KernelError::InvalidArgument));
+        assert_eq!(affinity(1), Some(ALL_HARTS));
+
+        assert_eq!(set_affinity(1, 1 << hart), Ok(()));
+        assert_eq!(affinity(1), Some(1 << hart));
+        assert_eq!(plic.sources[1].target.load(Ordering::Relaxed), hart);
+
+        let source = &plic.sources[1];
+        source.affinity.store(ALL_HARTS, Ordering::Relaxed);
+        source.user_set.store(false, Ordering::Relaxed);
+    }
+}
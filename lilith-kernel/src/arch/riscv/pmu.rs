@@ -0,0 +1,148 @@
+//! 硬件性能计数器
+//!
+//! 通过SBI PMU扩展为每个事件分配一个hpmcounter并启动计数，之后在S-mode直接读取计数器CSR：
+//! - 每个hart上线时各自配置，同一事件在不同hart上的计数器可能不同
+//! - 固件或硬件不支持的事件不分配计数器，读取时返回 `None`
+//! - 固件没有PMU扩展时只使用cycle与instret（OpenSBI默认允许S-mode读取）
+
+use super::{hart_id, sbi};
+use crate::perf::{HwEvent, NR_HW_EVENTS};
+use crate::sched::MAX_HARTS;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 固定计数器的CSR编号
+const CSR_CYCLE: usize = 0xc00;
+const CSR_INSTRET: usize = 0xc02;
+
+/// 没有分配计数器
+const NO_COUNTER: usize = usize::MAX;
+
+/// 不是通过SBI分配的计数器（不需要释放）
+const NO_INDEX: usize = 0xfff;
+
+/// 计数器描述的编码：位0-11为CSR编号，位12-18为位宽，位20-31为SBI计数器编号
+const fn encode(csr: usize, width: usize, index: usize) -> usize {
+    csr | width << 12 | index << 20
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const SLOT_INIT: AtomicUsize = AtomicUsize::new(NO_COUNTER);
+#[allow(clippy::declare_interior_mutable_const)]
+const HART_INIT: [AtomicUsize; NR_HW_EVENTS] = [SLOT_INIT; NR_HW_EVENTS];
+
+/// 各hart为每个事件分配的计数器
+static COUNTERS: [[AtomicUsize; NR_HW_EVENTS]; MAX_HARTS] = [HART_INIT; MAX_HARTS];
+
+/// 事件对应的SBI事件编号
+///
+/// 通用硬件事件的类型为0；缓存事件的类型为1，编号为 `缓存<<3 | 操作<<1 | 结果`
+fn sbi_event(event: HwEvent) -> usize {
+    match event {
+        HwEvent::Cycles => 0x1,
+        HwEvent::Instructions => 0x2,
+        HwEvent::CacheReferences => 0x3,
+        HwEvent::CacheMisses => 0x4,
+        HwEvent::BranchInstructions => 0x5,
+        HwEvent::BranchMisses => 0x6,
+        // DTLB(3)、读(0)、缺失(1)
+        HwEvent::DtlbMisses => 0x1_0019,
+        // ITLB(4)、读(0)、缺失(1)
+        HwEvent::ItlbMisses => 0x1_0021,
+    }
+}
+
+/// 按编号读取计数器CSR，`csrr` 只接受立即数形式的CSR编号
+macro_rules! read_counter_csr {
+    ($csr:expr; $($num:literal)*) => {
+        match $csr {
+            $(
+                $num => {
+                    let value: u64;
+                    unsafe {
+                        core::arch::asm!(concat!("csrr {}, ", stringify!($num)), out(reg) value);
+                    }
+                    value
+                }
+            )*
+            _ => 0,
+        }
+    };
+}
+
+/// 读取计数器CSR（cycle、time、instret与hpmcounter3-31）
+fn read_csr(csr: usize) -> u64 {
+    read_counter_csr!(csr;
+        0xc00 0xc01 0xc02 0xc03 0xc04 0xc05 0xc06 0xc07 0xc08 0xc09 0xc0a 0xc0b 0xc0c 0xc0d 0xc0e 0xc0f
+        0xc10 0xc11 0xc12 0xc13 0xc14 0xc15 0xc16 0xc17 0xc18 0xc19 0xc1a 0xc1b 0xc1c 0xc1d 0xc1e 0xc1f)
+}
+
+/// 在当前hart上为所有事件分配并启动计数器，返回分配到计数器的事件数
+pub fn pmu_hart_init() -> usize {
+    let slots = &COUNTERS[hart_id()];
+    for slot in slots.iter() {
+        slot.store(NO_COUNTER, Ordering::Relaxed);
+    }
+
+    if !sbi::pmu_available() {
+        slots[HwEvent::Cycles as usize].store(encode(CSR_CYCLE, 64, NO_INDEX), Ordering::Relaxed);
+        slots[HwEvent::Instructions as usize].store(encode(CSR_INSTRET, 64, NO_INDEX), Ordering::Relaxed);
+        return 2;
+    }
+
+    let num = sbi::pmu_num_counters().min(usize::BITS as usize);
+    let mask = if num == usize::BITS as usize {
+        usize::MAX
+    } else {
+        (1 << num) - 1
+    };
+    let mut configured = 0;
+    for event in HwEvent::ALL {
+        let flags = sbi::PMU_CFG_FLAG_CLEAR_VALUE | sbi::PMU_CFG_FLAG_AUTO_START;
+        let ret = sbi::pmu_counter_config_matching(0, mask, flags, sbi_event(event), 0);
+        if ret.error != 0 {
+            continue;
+        }
+        let index = ret.value;
+        let info = sbi::pmu_counter_get_info(index);
+        // 固件计数器不能通过CSR读取
+        if info.error != 0 || info.value >> 63 != 0 {
+            sbi::pmu_counter_stop(index, 1, sbi::PMU_STOP_FLAG_RESET);
+            continue;
+        }
+        let csr = info.value & 0xfff;
+        let width = ((info.value >> 12) & 0x3f) + 1;
+        slots[event as usize].store(encode(csr, width, index), Ordering::Relaxed);
+        configured += 1;
+    }
+    configured
+}
+
+/// 当前hart下线前停止并释放其计数器
+pub fn pmu_hart_exit() {
+    for slot in COUNTERS[hart_id()].iter() {
+        let counter = slot.swap(NO_COUNTER, Ordering::Relaxed);
+        if counter == NO_COUNTER {
+            continue;
+        }
+        let index = counter >> 20;
+        if index != NO_INDEX {
+            sbi::pmu_counter_stop(index, 1, sbi::PMU_STOP_FLAG_RESET);
+        }
+    }
+}
+
+/// 当前hart上事件的计数器是否可用
+pub fn counter_available(event: HwEvent) -> bool {
+    COUNTERS[hart_id()][event as usize].load(Ordering::Relaxed) != NO_COUNTER
+}
+
+/// 读取当前hart上事件的计数器，返回计数值与计数器位宽对应的掩码
+pub fn read_counter(event: HwEvent) -> Option<(u64, u64)> {
+    let counter = COUNTERS[hart_id()][event as usize].load(Ordering::Relaxed);
+    if counter == NO_COUNTER {
+        return None;
+    }
+    let width = (counter >> 12) & 0x7f;
+    let mask = if width >= 64 { u64::MAX } else { (1 << width) - 1 };
+    Some((read_csr(counter & 0xfff), mask))
+}
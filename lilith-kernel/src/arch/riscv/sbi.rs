@@ -0,0 +1,179 @@
+//! SBI（监管者二进制接口）调用
+//!
+//! S-mode通过ecall请求M-mode固件（OpenSBI）提供的服务：
+//! - 调用约定：a7为扩展号，a6为功能号，a0-a5为参数，返回时a0为错误码、a1为值
+//! - TIME扩展：设置下一次时钟中断的时间
+//! - RFENCE扩展：让其他hart执行fence.i与sfence.vma
+//! - SRST扩展：关机与重启，固件不支持时退回旧版关机调用
+//! - HSM扩展：启动、停止hart与查询hart状态
+//! - IPI扩展：向其他hart发送软件中断
+//! - PMU扩展：为硬件性能计数器选择事件并启动计数
+
+/// TIME扩展号（"TIME"）
+const EID_TIME: usize = 0x5449_4d45;
+/// RFENCE扩展号（"RFNC"）
+const EID_RFENCE: usize = 0x5246_4e43;
+/// SRST扩展号（"SRST"）
+const EID_SRST: usize = 0x5352_5354;
+/// HSM扩展号（"HSM"）
+const EID_HSM: usize = 0x0048_534d;
+/// IPI扩展号（"sPI"）
+const EID_IPI: usize = 0x0073_5049;
+/// PMU扩展号（"PMU"）
+const EID_PMU: usize = 0x0050_4d55;
+/// 基本扩展号
+const EID_BASE: usize = 0x10;
+/// 旧版（v0.1）关机调用
+const EID_LEGACY_SHUTDOWN: usize = 0x08;
+
+/// 系统复位类型
+pub const RESET_TYPE_SHUTDOWN: usize = 0;
+pub const RESET_TYPE_COLD_REBOOT: usize = 1;
+pub const RESET_TYPE_WARM_REBOOT: usize = 2;
+
+/// HSM中的hart状态
+pub const HART_STATE_STARTED: usize = 0;
+pub const HART_STATE_STOPPED: usize = 1;
+
+/// 系统复位原因
+pub const RESET_REASON_NONE: usize = 0;
+pub const RESET_REASON_SYSTEM_FAILURE: usize = 1;
+
+/// SBI调用结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbiRet {
+    /// 错误码，0表示成功
+    pub error: isize,
+    /// 返回值
+    pub value: usize,
+}
+
+/// 发起SBI调用
+#[inline]
+pub fn sbi_call(eid: usize, fid: usize, args: [usize; 3]) -> SbiRet {
+    let error: isize;
+    let value: usize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    SbiRet { error, value }
+}
+
+/// 发起最多6个参数的SBI调用（PMU扩展的部分功能需要5个参数）
+#[inline]
+pub fn sbi_call6(eid: usize, fid: usize, args: [usize; 6]) -> SbiRet {
+    let error: isize;
+    let value: usize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a5") args[5],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    SbiRet { error, value }
+}
+
+/// 固件是否实现了扩展 `eid`
+pub fn probe_extension(eid: usize) -> bool {
+    sbi_call(EID_BASE, 3, [eid, 0, 0]).value != 0
+}
+
+/// 在 `time` 计数到达 `stime_value` 时产生时钟中断，同时清除当前挂起的时钟中断
+pub fn set_timer(stime_value: u64) {
+    sbi_call(EID_TIME, 0, [stime_value as usize, 0, 0]);
+}
+
+/// 让所有hart执行fence.i
+pub fn remote_fence_i() {
+    // hart_mask_base为-1表示所有hart
+    sbi_call(EID_RFENCE, 0, [0, usize::MAX, 0]);
+}
+
+/// 让所有hart刷新 `[start_addr, start_addr + size)` 的TLB，`size` 为 `usize::MAX` 时刷新全部
+pub fn remote_sfence_vma(start_addr: usize, size: usize) {
+    sbi_call6(EID_RFENCE, 1, [0, usize::MAX, start_addr, size, 0, 0]);
+}
+
+/// 请求系统复位，成功时不返回
+pub fn system_reset(reset_type: usize, reason: usize) -> SbiRet {
+    sbi_call(EID_SRST, 0, [reset_type, reason, 0])
+}
+
+/// 旧版关机调用，成功时不返回
+pub fn legacy_shutdown() {
+    sbi_call(EID_LEGACY_SHUTDOWN, 0, [0, 0, 0]);
+}
+
+/// 让 `hart_id` 在S-mode从 `start_addr` 开始执行，a0为hart编号，a1为 `opaque`
+///
+/// 新hart启动时分页关闭
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> SbiRet {
+    sbi_call(EID_HSM, 0, [hart_id, start_addr, opaque])
+}
+
+/// 停止当前hart，成功时不返回
+pub fn hart_stop() -> SbiRet {
+    sbi_call(EID_HSM, 1, [0, 0, 0])
+}
+
+/// 查询hart的状态，编号无效时返回错误
+pub fn hart_get_status(hart_id: usize) -> SbiRet {
+    sbi_call(EID_HSM, 2, [hart_id, 0, 0])
+}
+
+/// 向 `hart_mask_base` 起的 `hart_mask` 中的hart发送软件中断
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) {
+    sbi_call(EID_IPI, 0, [hart_mask, hart_mask_base, 0]);
+}
+
+/// PMU扩展：配置计数器时清零计数
+pub const PMU_CFG_FLAG_CLEAR_VALUE: usize = 1 << 0;
+/// PMU扩展：配置计数器后立即开始计数
+pub const PMU_CFG_FLAG_AUTO_START: usize = 1 << 1;
+/// PMU扩展：停止计数器后释放，可再分配给其他事件
+pub const PMU_STOP_FLAG_RESET: usize = 1 << 0;
+
+/// 固件是否提供PMU扩展
+pub fn pmu_available() -> bool {
+    probe_extension(EID_PMU)
+}
+
+/// 本hart的计数器总数（硬件与固件计数器）
+pub fn pmu_num_counters() -> usize {
+    sbi_call(EID_PMU, 0, [0, 0, 0]).value
+}
+
+/// 计数器信息：位0-11为CSR编号，位12-17为位宽减1，最高位为1表示固件计数器
+pub fn pmu_counter_get_info(counter: usize) -> SbiRet {
+    sbi_call(EID_PMU, 1, [counter, 0, 0])
+}
+
+/// 在 `counter_base` 起的 `counter_mask` 中找一个能统计 `event_idx` 的计数器并配置，返回计数器编号
+pub fn pmu_counter_config_matching(
+    counter_base: usize,
+    counter_mask: usize,
+    flags: usize,
+    event_idx: usize,
+    event_data: usize,
+) -> SbiRet {
+    sbi_call6(EID_PMU, 2, [counter_base, counter_mask, flags, event_idx, event_data, 0])
+}
+
+/// 停止 `counter_base` 起的 `counter_mask` 中的计数器
+pub fn pmu_counter_stop(counter_base: usize, counter_mask: usize, flags: usize) -> SbiRet {
+    sbi_call(EID_PMU, 4, [counter_base, counter_mask, flags])
+}
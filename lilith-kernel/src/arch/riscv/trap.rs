@@ -0,0 +1,339 @@
+//! RISC-V监管者模式陷入处理
+//!
+//! 本模块负责S-mode的异常与中断入口：
+//! - 保存完整的通用寄存器现场到内核栈上的 `TrapFrame`
+//! - 来自U-mode时通过sscratch切换到任务的内核栈
+//! - 按scause分发：ecall进入系统调用，时钟中断驱动定时器与调度节拍，外部中断交给中断处理表，
+//!   软件中断（核间中断）唤醒空闲的hart
+//! - 中断返回前处理中断中置位的软中断
+//! - 用户态浮点与向量寄存器的延迟载入（见 `fpu` 与 `vector`）
+//! - 用户程序引起的异常只结束该进程（oops），内核自身的异常才恐慌
+
+use crate::debug::{oops, tracepoint};
+use crate::mm::vma::USER_SPACE_END;
+use crate::sched::signal::{SIGBUS, SIGILL, SIGSEGV, SIGTRAP};
+use crate::syscall::{self, SyscallArgs};
+use riscv::register::scause::{self, Exception, Interrupt, Trap};
+use riscv::register::{sscratch, stval, stvec};
+
+/// 陷入现场大小（32个通用寄存器 + sepc + sstatus）
+const TRAP_FRAME_SIZE: usize = 34 * 8;
+
+/// 陷入时保存的现场
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TrapFrame {
+    /// x0-x31（x0位置不使用）
+    pub regs: [usize; 32],
+    /// 陷入前的pc
+    pub sepc: usize,
+    /// 陷入前的sstatus
+    pub sstatus: usize,
+}
+
+/// 寄存器编号
+pub const REG_SP: usize = 2;
+pub const REG_TP: usize = 4;
+pub const REG_A0: usize = 10;
+pub const REG_A7: usize = 17;
+
+/// sstatus.SPP：陷入前处于S-mode
+const SSTATUS_SPP: usize = 1 << 8;
+/// sstatus.SPIE：sret后打开中断
+const SSTATUS_SPIE: usize = 1 << 5;
+
+impl TrapFrame {
+    /// 从 `entry` 开始执行用户程序的现场，栈指针为 `sp`，其余寄存器为0
+    pub fn new_user(entry: usize, sp: usize) -> Self {
+        let mut regs = [0; 32];
+        regs[REG_SP] = sp;
+        Self {
+            regs,
+            sepc: entry,
+            sstatus: SSTATUS_SPIE,
+        }
+    }
+
+    /// 陷入是否来自U-mode
+    pub fn from_user(&self) -> bool {
+        self.sstatus & SSTATUS_SPP == 0
+    }
+}
+
+/// 设置陷入入口
+pub fn init_trap() {
+    unsafe {
+        // 处于内核态时sscratch为0，入口据此判断陷入来源
+        sscratch::write(0);
+        stvec::write(supervisor_trap_handler as *const () as usize, stvec::TrapMode::Direct);
+    }
+}
+
+/// 监管者模式陷入入口（汇编实现）
+///
+/// 恢复现场的部分导出为全局标号 `__trap_return`，供 `enter_user` 复用
+#[allow(named_asm_labels)]
+#[naked]
+#[no_mangle]
+pub extern "C" fn supervisor_trap_handler() {
+    unsafe {
+        core::arch::asm!(
+            // 来自U-mode时sscratch保存内核栈顶，交换后sp非0
+            "csrrw sp, sscratch, sp",
+            "bnez sp, 2f",
+            // 来自S-mode：换回原来的sp
+            "csrrw sp, sscratch, sp",
+            "2:",
+            "addi sp, sp, -{size}",
+            "sd x1, 1*8(sp)",
+            "sd x3, 3*8(sp)",
+            "sd x4, 4*8(sp)",
+            "sd x5, 5*8(sp)",
+            "sd x6, 6*8(sp)",
+            "sd x7, 7*8(sp)",
+            "sd x8, 8*8(sp)",
+            "sd x9, 9*8(sp)",
+            "sd x10, 10*8(sp)",
+            "sd x11, 11*8(sp)",
+            "sd x12, 12*8(sp)",
+            "sd x13, 13*8(sp)",
+            "sd x14, 14*8(sp)",
+            "sd x15, 15*8(sp)",
+            "sd x16, 16*8(sp)",
+            "sd x17, 17*8(sp)",
+            "sd x18, 18*8(sp)",
+            "sd x19, 19*8(sp)",
+            "sd x20, 20*8(sp)",
+            "sd x21, 21*8(sp)",
+            "sd x22, 22*8(sp)",
+            "sd x23, 23*8(sp)",
+            "sd x24, 24*8(sp)",
+            "sd x25, 25*8(sp)",
+            "sd x26, 26*8(sp)",
+            "sd x27, 27*8(sp)",
+            "sd x28, 28*8(sp)",
+            "sd x29, 29*8(sp)",
+            "sd x30, 30*8(sp)",
+            "sd x31, 31*8(sp)",
+            // 保存陷入前的sp：来自U-mode时在sscratch中，否则为当前栈帧之上
+            "csrr t0, sscratch",
+            "bnez t0, 3f",
+            "addi t0, sp, {size}",
+            "3:",
+            "sd t0, 2*8(sp)",
+            "csrw sscratch, x0",
+            "csrr t0, sepc",
+            "sd t0, 32*8(sp)",
+            "csrr t0, sstatus",
+            "sd t0, 33*8(sp)",
+            // 进入Rust处理函数
+            "mv a0, sp",
+            "call {handler}",
+            // 恢复现场，`enter_user` 从这里进入
+            ".globl __trap_return",
+            "__trap_return:",
+            "ld t0, 32*8(sp)",
+            "csrw sepc, t0",
+            "ld t0, 33*8(sp)",
+            "csrw sstatus, t0",
+            // 返回U-mode时在sscratch中记录内核栈顶，供下次陷入使用
+            "andi t0, t0, {spp}",
+            "bnez t0, 4f",
+            "addi t0, sp, {size}",
+            "csrw sscratch, t0",
+            "4:",
+            "ld x1, 1*8(sp)",
+            "ld x3, 3*8(sp)",
+            "ld x4, 4*8(sp)",
+            "ld x5, 5*8(sp)",
+            "ld x6, 6*8(sp)",
+            "ld x7, 7*8(sp)",
+            "ld x8, 8*8(sp)",
+            "ld x9, 9*8(sp)",
+            "ld x10, 10*8(sp)",
+            "ld x11, 11*8(sp)",
+            "ld x12, 12*8(sp)",
+            "ld x13, 13*8(sp)",
+            "ld x14, 14*8(sp)",
+            "ld x15, 15*8(sp)",
+            "ld x16, 16*8(sp)",
+            "ld x17, 17*8(sp)",
+            "ld x18, 18*8(sp)",
+            "ld x19, 19*8(sp)",
+            "ld x20, 20*8(sp)",
+            "ld x21, 21*8(sp)",
+            "ld x22, 22*8(sp)",
+            "ld x23, 23*8(sp)",
+            "ld x24, 24*8(sp)",
+            "ld x25, 25*8(sp)",
+            "ld x26, 26*8(sp)",
+            "ld x27, 27*8(sp)",
+            "ld x28, 28*8(sp)",
+            "ld x29, 29*8(sp)",
+            "ld x30, 30*8(sp)",
+            "ld x31, 31*8(sp)",
+            "ld sp, 2*8(sp)",
+            "sret",
+            size = const TRAP_FRAME_SIZE,
+            spp = const SSTATUS_SPP,
+            handler = sym trap_handler,
+            options(noreturn)
+        );
+    }
+}
+
+/// 以 `frame` 中的现场返回用户态
+///
+/// # Safety
+///
+/// `frame` 必须位于当前任务内核栈的顶部，返回后该位置被下次陷入复用
+#[naked]
+pub unsafe extern "C" fn enter_user(frame: *const TrapFrame) -> ! {
+    core::arch::asm!(
+        "mv sp, a0",
+        "j __trap_return",
+        options(noreturn)
+    );
+}
+
+/// 用户态异常对应的信号
+fn exception_signal(exception: Exception) -> u32 {
+    match exception {
+        Exception::IllegalInstruction => SIGILL,
+        Exception::Breakpoint => SIGTRAP,
+        Exception::InstructionMisaligned | Exception::StoreMisaligned => SIGBUS,
+        _ => SIGSEGV,
+    }
+}
+
+/// Rust实现的陷入处理函数
+extern "C" fn trap_handler(frame: &mut TrapFrame) {
+    // 陷入现场已压入当前栈，先确认没有越过栈底
+    crate::sched::check_stack_canary();
+
+    let scause = scause::read();
+    let stval = stval::read();
+    let is_interrupt = scause.is_interrupt();
+    if is_interrupt {
+        crate::softirq::irq_enter();
+    }
+
+    match scause.cause() {
+        Trap::Interrupt(_) => crate::tracepoint!(tracepoint::IrqEntry {
+            cause: scause.code(),
+            ip: frame.sepc,
+        }),
+        Trap::Exception(Exception::InstructionPageFault | Exception::LoadPageFault | Exception::StorePageFault) => {
+            crate::tracepoint!(tracepoint::PageFault {
+                addr: stval,
+                ip: frame.sepc,
+                cause: scause.code(),
+                user: frame.from_user(),
+            })
+        }
+        _ => {}
+    }
+
+    match scause.cause() {
+        Trap::Exception(Exception::UserEnvCall) => {
+            // 返回到ecall的下一条指令
+            frame.sepc += 4;
+            let args = SyscallArgs {
+                nr: frame.regs[REG_A7],
+                args: [
+                    frame.regs[REG_A0],
+                    frame.regs[REG_A0 + 1],
+                    frame.regs[REG_A0 + 2],
+                    frame.regs[REG_A0 + 3],
+                    frame.regs[REG_A0 + 4],
+                    frame.regs[REG_A0 + 5],
+                ],
+            };
+            frame.regs[REG_A0] = syscall::dispatch(&args) as usize;
+        }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // 高精度定时器也会产生时钟中断，只有经过新的节拍时才执行周期性工作
+            crate::random::add_interrupt_randomness(0);
+            crate::perf::profile::profile_tick(frame);
+            if crate::time::timer::run_timers() {
+                crate::time::tick();
+                crate::klog::wake_readers();
+                crate::sched::scheduler_tick();
+            }
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // 核间中断只用于唤醒wfi中的hart，返回后由空闲循环检查需要做的工作
+            super::clear_software_interrupt();
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) if super::plic::present() => {
+            // 依次认领路由到本hart的中断，处理后完成
+            while let Some(irq) = super::plic::claim() {
+                crate::random::add_interrupt_randomness(irq);
+                crate::power::suspend::pm_wakeup_irq(irq);
+                super::dispatch_irq(irq);
+                super::plic::complete(irq);
+            }
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            // 没有PLIC时中断号固定为0
+            crate::random::add_interrupt_randomness(0);
+            crate::power::suspend::pm_wakeup_irq(0);
+            super::dispatch_irq(0);
+        }
+        // 内核中的断点交给调试桩处理，调试桩未开启时进入断点监视器
+        Trap::Exception(Exception::Breakpoint) if !frame.from_user() => {
+            if !crate::debug::gdbstub::handle_breakpoint(frame) {
+                crate::debug::monitor::handle_breakpoint(frame);
+            }
+        }
+        // 浮点或向量单元关闭时用户程序的第一条浮点或向量指令：载入任务的状态后重新执行
+        Trap::Exception(Exception::IllegalInstruction)
+            if frame.from_user() && (super::fpu::lazy_restore(frame) || super::vector::lazy_restore(frame)) => {}
+        Trap::Exception(exception) => {
+            let is_access_fault = matches!(
+                exception,
+                Exception::LoadFault | Exception::StoreFault | Exception::LoadPageFault | Exception::StorePageFault
+            );
+            if frame.from_user() {
+                // 用户程序自身的错误：只结束该进程
+                oops::user_fault(
+                    frame,
+                    format_args!("用户态异常 {:?}, stval=0x{:x}", exception, stval),
+                    exception_signal(exception),
+                );
+            } else if is_access_fault {
+                // 用户内存复制中的访问异常：跳转到修复代码，由调用方返回EFAULT
+                if let Some(fixup) = super::search_exception_table(frame.sepc) {
+                    frame.sepc = fixup;
+                    return;
+                }
+                // 其他访问用户地址的错误同样只结束当前进程
+                if stval < USER_SPACE_END {
+                    oops::user_fault(
+                        frame,
+                        format_args!("内核访问用户地址出错 {:?}, stval=0x{:x}", exception, stval),
+                        SIGSEGV,
+                    );
+                }
+            }
+            panic!(
+                "未处理的S-mode异常: {:?}, sepc=0x{:x}, stval=0x{:x}",
+                exception, frame.sepc, stval
+            );
+        }
+        Trap::Interrupt(interrupt) => {
+            crate::early_println!("未处理的S-mode中断: {:?}", interrupt);
+        }
+    }
+
+    if is_interrupt {
+        crate::softirq::irq_exit();
+    }
+    // 期间可能切换过任务，按浮点与向量寄存器的归属设置返回用户态时的sstatus.FS与VS
+    if frame.from_user() {
+        if let Some(task) = crate::sched::current() {
+            super::fpu::prepare_return(task.fpu(), frame);
+            super::vector::prepare_return(task.vector(), frame);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! RISC-V用户内存访问原语
+//!
+//! 用户内存复制由汇编实现，其中可能因用户页缺失而触发异常的指令登记在
+//! 异常表中。S-mode访问异常发生在这些指令上时，陷入处理将sepc改为修复
+//! 代码的地址，复制过程提前结束并返回未复制的字节数，而不是让内核崩溃
+
+use core::arch::global_asm;
+use riscv::register::sstatus;
+
+global_asm!(
+    ".section .text.uaccess",
+    ".globl __copy_user",
+    // a0 = 目的地址，a1 = 源地址，a2 = 长度；返回未复制的字节数
+    "__copy_user:",
+    "    beqz a2, 13f",
+    "10: lb t0, 0(a1)",
+    "11: sb t0, 0(a0)",
+    "    addi a0, a0, 1",
+    "    addi a1, a1, 1",
+    "    addi a2, a2, -1",
+    "    bnez a2, 10b",
+    "13: mv a0, a2",
+    "    ret",
+    // 修复代码：访问异常后跳转到这里，a2仍是剩余字节数
+    "14: mv a0, a2",
+    "    ret",
+    ".section .rodata.ex_table, \"a\"",
+    ".balign 8",
+    ".globl __ex_table_start",
+    "__ex_table_start:",
+    ".dword 10b, 14b",
+    ".dword 11b, 14b",
+    ".globl __ex_table_end",
+    "__ex_table_end:",
+    ".text",
+);
+
+extern "C" {
+    fn __copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    static __ex_table_start: ExceptionTableEntry;
+    static __ex_table_end: ExceptionTableEntry;
+}
+
+/// 异常表项：可能出错的指令地址与对应的修复代码地址
+#[repr(C)]
+struct ExceptionTableEntry {
+    insn: usize,
+    fixup: usize,
+}
+
+/// 在用户与内核缓冲区之间复制，返回因访问异常而未复制的字节数
+///
+/// # Safety
+///
+/// 内核一侧的缓冲区必须有效；用户一侧的地址须已按VMA检查
+pub unsafe fn copy_user_raw(dst: *mut u8, src: *const u8, len: usize) -> usize {
+    // 允许S-mode访问U-mode页面（SUM）
+    sstatus::set_sum();
+    let remaining = __copy_user(dst, src, len);
+    sstatus::clear_sum();
+    remaining
+}
+
+/// 查找出错指令对应的修复地址
+pub fn search_exception_table(pc: usize) -> Option<usize> {
+    let table = unsafe {
+        let start = &__ex_table_start as *const ExceptionTableEntry;
+        let end = &__ex_table_end as *const ExceptionTableEntry;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    };
+    table.iter().find(|entry| entry.insn == pc).map(|entry| entry.fixup)
+}
@@ -0,0 +1,134 @@
+//! RISC-V vDSO代码页
+//!
+//! 代码页以只读可执行方式映射到每个用户进程，用户态调用其中的函数即可
+//! 读取时间与进程号，不必陷入内核。页首是跳转表，各函数的入口偏移固定：
+//! - `+0`：`clock_gettime(clockid, tp)`，不支持的时钟退回到ecall
+//! - `+4`：`getpid()`
+//!
+//! 时间数据页的布局见 `time::VdsoTimePage`：偏移0为顺序锁序号，
+//! 之后依次为换算系数、偏移量、微调量、微调起点与两个粗粒度时钟
+
+use crate::mm::vdso::{VDSO_PROCESS_PAGE, VDSO_TIME_PAGE};
+use crate::syscall::SYS_CLOCK_GETTIME;
+use core::arch::global_asm;
+
+/// `clock_gettime` 的入口偏移
+pub const VDSO_CLOCK_GETTIME_OFFSET: usize = 0;
+/// `getpid` 的入口偏移
+pub const VDSO_GETPID_OFFSET: usize = 4;
+
+global_asm!(
+    ".section .text.vdso, \"ax\"",
+    ".balign 4096",
+    ".globl __vdso_start",
+    "__vdso_start:",
+    // 跳转表不能使用压缩指令，保证每项4字节
+    ".option push",
+    ".option norvc",
+    "    j 20f",
+    "    j 30f",
+    ".option pop",
+    // clock_gettime(a0 = clockid, a1 = tp)
+    "20:",
+    "    li t3, 2",
+    "    bltu a0, t3, 21f",
+    "    li t3, 5",
+    "    beq a0, t3, 21f",
+    "    li t3, 6",
+    "    beq a0, t3, 21f",
+    // 不支持的时钟：由内核处理
+    "    li a7, {nr_clock_gettime}",
+    "    ecall",
+    "    ret",
+    "21:",
+    "    li t0, {time_page}",
+    "22:",
+    "    ld t1, 0(t0)",
+    "    andi t3, t1, 1",
+    "    bnez t3, 22b",
+    "    fence r, r",
+    // 单调时间 = (cycles * mult) >> 32
+    "    rdtime t2",
+    "    ld t3, 8(t0)",
+    "    mulhu t4, t2, t3",
+    "    mul t5, t2, t3",
+    "    srli t5, t5, 32",
+    "    slli t4, t4, 32",
+    "    or t2, t4, t5",
+    // 已完成的微调量 = clamp(slew, ±elapsed * 500ppm)
+    "    ld t5, 32(t0)",
+    "    li a2, 0",
+    "    bltu t2, t5, 23f",
+    "    sub a2, t2, t5",
+    "23:",
+    "    li t6, 1000000",
+    "    li a5, 500",
+    "    divu a3, a2, t6",
+    "    remu a4, a2, t6",
+    "    mul a3, a3, a5",
+    "    mul a4, a4, a5",
+    "    divu a4, a4, t6",
+    "    add a2, a3, a4",
+    "    ld a3, 24(t0)",
+    "    ble a3, a2, 24f",
+    "    mv a3, a2",
+    "24:",
+    "    neg a2, a2",
+    "    bge a3, a2, 25f",
+    "    mv a3, a2",
+    "25:",
+    // 实时时间 = 单调时间 + 偏移量 + 已完成的微调量
+    "    ld t4, 16(t0)",
+    "    add t4, t4, t2",
+    "    add t4, t4, a3",
+    "    ld a6, 40(t0)",
+    "    ld a7, 48(t0)",
+    "    fence r, r",
+    "    ld t3, 0(t0)",
+    "    bne t1, t3, 22b",
+    // 按时钟选择结果
+    "    mv t5, t4",
+    "    beqz a0, 26f",
+    "    mv t5, t2",
+    "    li t3, 1",
+    "    beq a0, t3, 26f",
+    "    mv t5, a7",
+    "    li t3, 5",
+    "    beq a0, t3, 26f",
+    "    mv t5, a6",
+    "26:",
+    "    li t6, 1000000000",
+    "    divu t3, t5, t6",
+    "    remu t5, t5, t6",
+    "    sd t3, 0(a1)",
+    "    sd t5, 8(a1)",
+    "    li a0, 0",
+    "    ret",
+    // getpid()
+    "30:",
+    "    li t0, {process_page}",
+    "    ld a0, 0(t0)",
+    "    ret",
+    ".balign 4096",
+    ".globl __vdso_end",
+    "__vdso_end:",
+    ".text",
+    time_page = const VDSO_TIME_PAGE,
+    process_page = const VDSO_PROCESS_PAGE,
+    nr_clock_gettime = const SYS_CLOCK_GETTIME,
+);
+
+extern "C" {
+    static __vdso_start: u8;
+    static __vdso_end: u8;
+}
+
+/// vDSO代码页的内核地址
+pub fn vdso_code_page() -> usize {
+    unsafe { &__vdso_start as *const u8 as usize }
+}
+
+/// vDSO代码的长度（页对齐）
+pub fn vdso_code_len() -> usize {
+    unsafe { &__vdso_end as *const u8 as usize - &__vdso_start as *const u8 as usize }
+}
@@ -0,0 +1,265 @@
+//! 用户任务向量寄存器的延迟切换
+//!
+//! 本模块按需保存与恢复用户任务的V扩展状态（vstart、vl、vtype、vcsr与v0-v31），
+//! 与浮点寄存器（见 `fpu`）的方式相同，包括：
+//! - 返回用户态时，寄存器中已是本任务的状态则sstatus.VS设为Clean，否则设为Off
+//! - VS为Off时用户程序的第一条向量指令引起非法指令异常，此时载入任务保存的状态后重新执行；
+//!   任务第一次使用时才按VLEN分配保存区，初始状态为0且vtype.vill置位
+//! - 切换任务时只有VS为Dirty才保存
+//! - 新的用户任务（clone）继承当前任务的向量状态
+//!
+//! 是否支持V扩展取自M-mode启动时的检测结果（`boot::get_machine_config`）；
+//! 向量浮点指令同时需要FS，非法指令异常先打开浮点单元，重新执行后再次陷入时打开向量单元
+
+use super::{hart_id, TrapFrame};
+use crate::sched::MAX_HARTS;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// sstatus.VS 字段及其取值
+const SSTATUS_VS: usize = 3 << 9;
+const VS_OFF: usize = 0;
+const VS_CLEAN: usize = 2 << 9;
+const VS_DIRTY: usize = 3 << 9;
+
+/// vtype.vill：vtype无效，执行向量运算指令引起非法指令异常
+const VTYPE_VILL: usize = 1 << (usize::BITS - 1);
+
+/// 寄存器中没有任何任务的状态
+const NO_OWNER: usize = usize::MAX;
+
+/// 各hart的向量寄存器中是哪个任务（编号）的状态
+#[allow(clippy::declare_interior_mutable_const)]
+const OWNER_INIT: AtomicUsize = AtomicUsize::new(NO_OWNER);
+static OWNERS: [AtomicUsize; MAX_HARTS] = [OWNER_INIT; MAX_HARTS];
+
+/// 保存的向量状态
+#[derive(Debug, Clone)]
+struct VectorRegs {
+    vstart: usize,
+    vl: usize,
+    vtype: usize,
+    vcsr: usize,
+    /// v0-v31，共 `32 * vlenb` 字节
+    data: Vec<u8>,
+}
+
+impl VectorRegs {
+    /// 初始状态
+    fn new(vlenb: usize) -> Self {
+        Self {
+            vstart: 0,
+            vl: 0,
+            vtype: VTYPE_VILL,
+            vcsr: 0,
+            data: vec![0; 32 * vlenb],
+        }
+    }
+}
+
+/// 任务的向量状态
+pub struct VectorContext {
+    /// 所属任务的编号
+    owner: usize,
+    /// 切换出去时保存的状态，从未使用过向量指令时为 `None`
+    regs: UnsafeCell<Option<VectorRegs>>,
+    /// 最近一次载入寄存器的hart
+    hart: AtomicUsize,
+}
+
+impl VectorContext {
+    /// 编号为 `owner` 的任务的向量状态
+    pub const fn new(owner: usize) -> Self {
+        Self {
+            owner,
+            regs: UnsafeCell::new(None),
+            hart: AtomicUsize::new(NO_OWNER),
+        }
+    }
+
+    /// 当前hart的寄存器中是否是本任务的状态
+    fn loaded(&self) -> bool {
+        let hart = hart_id();
+        self.hart.load(Ordering::Relaxed) == hart && OWNERS[hart].load(Ordering::Relaxed) == self.owner
+    }
+
+    /// 任务被切换出去：修改过的寄存器保存到任务中
+    pub fn switch_out(&self) {
+        if !self.loaded() || vs() != VS_DIRTY {
+            return;
+        }
+        // 载入过寄存器的任务一定已经分配了保存区
+        if let Some(regs) = unsafe { &mut *self.regs.get() } {
+            unsafe { save(regs) };
+            set_vs(VS_CLEAN);
+        }
+    }
+
+    /// 以 `parent` 的向量状态初始化新任务
+    pub fn copy_from(&self, parent: &VectorContext) {
+        unsafe {
+            let regs = (*parent.regs.get()).clone();
+            if parent.loaded() {
+                if let Some(mut regs) = regs {
+                    save(&mut regs);
+                    *self.regs.get() = Some(regs);
+                }
+            } else {
+                *self.regs.get() = regs;
+            }
+        }
+    }
+}
+
+/// 当前的sstatus.VS
+fn vs() -> usize {
+    let sstatus: usize;
+    unsafe {
+        core::arch::asm!("csrr {}, sstatus", out(reg) sstatus);
+    }
+    sstatus & SSTATUS_VS
+}
+
+/// 设置sstatus.VS
+fn set_vs(vs: usize) {
+    unsafe {
+        core::arch::asm!(
+            "csrc sstatus, {mask}",
+            "csrs sstatus, {vs}",
+            mask = in(reg) SSTATUS_VS,
+            vs = in(reg) vs,
+        );
+    }
+}
+
+/// 每个向量寄存器的字节数，VS必须已经打开
+fn vlenb() -> usize {
+    let vlenb: usize;
+    unsafe {
+        core::arch::asm!(
+            ".option push",
+            ".option arch, +v",
+            "csrr {}, vlenb",
+            ".option pop",
+            out(reg) vlenb,
+        );
+    }
+    vlenb
+}
+
+/// 把向量状态保存到 `regs`，VS为Off时先打开
+unsafe fn save(regs: &mut VectorRegs) {
+    core::arch::asm!(
+        ".option push",
+        ".option arch, +v",
+        "csrs sstatus, {clean}",
+        "csrr {vstart}, vstart",
+        "csrr {vl}, vl",
+        "csrr {vtype}, vtype",
+        "csrr {vcsr}, vcsr",
+        "csrw vstart, zero",
+        // 按字节每次保存8个寄存器，tmp为一组寄存器的字节数
+        "vsetvli {tmp}, x0, e8, m8, ta, ma",
+        "vse8.v v0, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vse8.v v8, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vse8.v v16, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vse8.v v24, ({data})",
+        // 寄存器仍属于本任务，恢复被改变的vl、vtype与vstart
+        "vsetvl x0, {vl}, {vtype}",
+        "csrw vstart, {vstart}",
+        ".option pop",
+        clean = in(reg) VS_CLEAN,
+        vstart = out(reg) regs.vstart,
+        vl = out(reg) regs.vl,
+        vtype = out(reg) regs.vtype,
+        vcsr = out(reg) regs.vcsr,
+        data = inout(reg) regs.data.as_mut_ptr() => _,
+        tmp = out(reg) _,
+    );
+}
+
+/// 从 `regs` 载入向量状态，VS必须已经打开
+unsafe fn restore(regs: &VectorRegs) {
+    core::arch::asm!(
+        ".option push",
+        ".option arch, +v",
+        "vsetvli {tmp}, x0, e8, m8, ta, ma",
+        "vle8.v v0, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vle8.v v8, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vle8.v v16, ({data})",
+        "add {data}, {data}, {tmp}",
+        "vle8.v v24, ({data})",
+        "vsetvl x0, {vl}, {vtype}",
+        "csrw vstart, {vstart}",
+        "csrw vcsr, {vcsr}",
+        ".option pop",
+        vstart = in(reg) regs.vstart,
+        vl = in(reg) regs.vl,
+        vtype = in(reg) regs.vtype,
+        vcsr = in(reg) regs.vcsr,
+        data = inout(reg) regs.data.as_ptr() => _,
+        tmp = out(reg) _,
+    );
+}
+
+/// 硬件是否支持V扩展
+fn supported() -> bool {
+    crate::boot::get_machine_config().is_some_and(|config| config.vector_support)
+}
+
+/// 设置返回用户态时的sstatus.VS：寄存器中是 `vector` 的状态时保留（至少为Clean），否则关闭
+pub fn prepare_return(vector: &VectorContext, frame: &mut TrapFrame) {
+    let vs = match frame.sstatus & SSTATUS_VS {
+        _ if !vector.loaded() => VS_OFF,
+        VS_OFF => VS_CLEAN,
+        vs => vs,
+    };
+    frame.sstatus = (frame.sstatus & !SSTATUS_VS) | vs;
+}
+
+/// 用户态非法指令异常：VS为Off且支持V扩展时载入当前任务的向量状态，返回是否应重新执行该指令
+pub fn lazy_restore(frame: &mut TrapFrame) -> bool {
+    if frame.sstatus & SSTATUS_VS != VS_OFF || !supported() {
+        return false;
+    }
+    let Some(task) = crate::sched::current() else {
+        return false;
+    };
+    set_vs(VS_CLEAN);
+    let vector = task.vector();
+    let regs = unsafe { &mut *vector.regs.get() };
+    let regs = regs.get_or_insert_with(|| VectorRegs::new(vlenb()));
+    unsafe { restore(regs) };
+    let hart = hart_id();
+    vector.hart.store(hart, Ordering::Relaxed);
+    OWNERS[hart].store(vector.owner, Ordering::Relaxed);
+    frame.sstatus = (frame.sstatus & !SSTATUS_VS) | VS_CLEAN;
+    true
+}
+
+crate::kernel_test! {
+    fn vector_state_follows_owner() {
+        let parent = VectorContext::new(usize::MAX - 1);
+        let child = VectorContext::new(usize::MAX - 2);
+        child.copy_from(&parent);
+        assert!(unsafe { (*child.regs.get()).is_none() });
+
+        unsafe { *parent.regs.get() = Some(VectorRegs::new(16)) };
+        child.copy_from(&parent);
+        let regs = unsafe { (*child.regs.get()).as_ref().unwrap() };
+        assert_eq!(regs.data.len(), 32 * 16);
+        assert_eq!(regs.vtype, VTYPE_VILL);
+
+        let mut frame = TrapFrame::new_user(0, 0);
+        frame.sstatus |= VS_DIRTY;
+        prepare_return(&child, &mut frame);
+        assert_eq!(frame.sstatus & SSTATUS_VS, VS_OFF);
+    }
+}
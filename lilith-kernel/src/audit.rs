@@ -0,0 +1,282 @@
+//! 安全审计
+//!
+//! 本模块把与安全相关的事件记录到专用的环形缓冲区，供特权守护进程读取，包括：
+//! - 能力检查失败（`sched::capable` 返回假）
+//! - 以 `EPERM`/`EACCES` 失败的系统调用，包括被seccomp拒绝的调用
+//! - 执行程序失败
+//!
+//! 每条记录带有序号、时间戳、进程号、用户号与正在执行的系统调用号，格式参照Linux审计日志：
+//!
+//! ```text
+//! audit(12.345678:7): type=CAPABILITY pid=5 uid=1000 euid=1000 syscall=142 comm="sh" capability=cap_sys_boot
+//! ```
+//!
+//! 缓冲区写满后丢弃最旧的记录并计数。/dev/audit 每次读取消耗若干条记录，没有记录时阻塞，
+//! 打开它需要 `CAP_AUDIT_READ`；/proc/sys/kernel/audit 开关记录（默认开启）
+
+use crate::error::{Errno, KernelError};
+use crate::fs::file::{File, FileStat, PollEvents, S_IFCHR};
+use crate::fs::poll::PollTable;
+use crate::fs::procfs;
+use crate::sched::{
+    self,
+    capability::{Capability, CAP_AUDIT_READ},
+};
+use crate::sync::WaitQueue;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 缓冲区中最多保存的记录数
+pub const AUDIT_BACKLOG: usize = 512;
+
+/// 审计事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEvent {
+    /// 缺少能力
+    Capability(Capability),
+    /// 系统调用因权限不足失败
+    Denied(Errno),
+    /// 执行程序失败
+    ExecFailed(Errno),
+}
+
+impl AuditEvent {
+    /// 记录类型
+    fn kind(&self) -> &'static str {
+        match self {
+            AuditEvent::Capability(_) => "CAPABILITY",
+            AuditEvent::Denied(_) => "DENIED",
+            AuditEvent::ExecFailed(_) => "EXECVE",
+        }
+    }
+}
+
+/// 一条审计记录
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    /// 序号
+    pub seq: u64,
+    /// 单调时钟时间戳
+    pub timestamp_ns: u64,
+    /// 事件
+    pub event: AuditEvent,
+    /// 进程号
+    pub pid: usize,
+    /// 实际用户号
+    pub uid: u32,
+    /// 有效用户号
+    pub euid: u32,
+    /// 正在执行的系统调用号
+    pub syscall: Option<usize>,
+    /// 任务名
+    pub comm: String,
+}
+
+impl AuditRecord {
+    /// 格式化为一行文本
+    fn format(&self, out: &mut String) {
+        let ts = self.timestamp_ns;
+        let _ = write!(
+            out,
+            "audit({}.{:06}:{}): type={} pid={} uid={} euid={}",
+            ts / 1_000_000_000,
+            ts % 1_000_000_000 / 1000,
+            self.seq,
+            self.event.kind(),
+            self.pid,
+            self.uid,
+            self.euid
+        );
+        if let Some(nr) = self.syscall {
+            let _ = write!(out, " syscall={}", nr);
+        }
+        let _ = write!(out, " comm=\"{}\"", self.comm);
+        let _ = match self.event {
+            AuditEvent::Capability(cap) => match cap.name() {
+                Some(name) => write!(out, " capability={}", name),
+                None => write!(out, " capability={}", cap.0),
+            },
+            AuditEvent::Denied(errno) | AuditEvent::ExecFailed(errno) => {
+                write!(out, " exit=-{}", errno.code())
+            }
+        };
+        out.push('\n');
+    }
+}
+
+/// 审计缓冲区
+struct AuditLog {
+    records: VecDeque<AuditRecord>,
+    /// 下一条记录的序号
+    next_seq: u64,
+}
+
+static AUDIT_LOG: Mutex<AuditLog> = Mutex::new(AuditLog {
+    records: VecDeque::new(),
+    next_seq: 0,
+});
+
+/// 是否记录事件
+static AUDIT_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 缓冲区已满而丢弃的记录数
+static AUDIT_LOST: AtomicU64 = AtomicU64::new(0);
+
+/// 等待新记录的读者
+static AUDIT_WAIT: WaitQueue = WaitQueue::new();
+
+/// 记录当前执行流触发的事件，内核任务不记录
+pub fn log(event: AuditEvent) {
+    if !AUDIT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(task) = sched::current() else { return };
+    let Some(process) = task.process() else { return };
+    let cred = process.credentials();
+    let mut record = AuditRecord {
+        seq: 0,
+        timestamp_ns: crate::time::monotonic_ns(),
+        event,
+        pid: process.pid,
+        uid: cred.uid,
+        euid: cred.euid,
+        syscall: task.syscall(),
+        comm: task.name.clone(),
+    };
+
+    let mut log = AUDIT_LOG.lock();
+    record.seq = log.next_seq;
+    log.next_seq += 1;
+    if log.records.len() >= AUDIT_BACKLOG {
+        log.records.pop_front();
+        AUDIT_LOST.fetch_add(1, Ordering::Relaxed);
+    }
+    log.records.push_back(record);
+    drop(log);
+    AUDIT_WAIT.wake_all();
+}
+
+/// 记录系统调用的失败结果：权限不足与执行程序失败
+pub fn log_syscall_result(nr: usize, result: Result<usize, Errno>) {
+    match result {
+        Err(errno) if nr == crate::syscall::SYS_EXECVE => log(AuditEvent::ExecFailed(errno)),
+        Err(errno) if errno == Errno::EPERM || errno == Errno::EACCES => log(AuditEvent::Denied(errno)),
+        _ => {}
+    }
+}
+
+/// 取出最多 `max` 字节的记录，没有记录时等待
+pub fn read(max: usize) -> String {
+    AUDIT_WAIT.wait_until(|| !AUDIT_LOG.lock().records.is_empty());
+    let mut out = String::new();
+    let mut log = AUDIT_LOG.lock();
+    while let Some(record) = log.records.front() {
+        let before = out.len();
+        record.format(&mut out);
+        if out.len() > max {
+            out.truncate(before);
+            break;
+        }
+        log.records.pop_front();
+    }
+    out
+}
+
+/// /dev/audit：按行读取并消耗审计记录
+pub struct AuditFile;
+
+impl AuditFile {
+    /// 打开 /dev/audit，需要 `CAP_AUDIT_READ`
+    pub fn open() -> Result<Self, KernelError> {
+        if !sched::capable(CAP_AUDIT_READ) {
+            return Err(KernelError::PermissionDenied);
+        }
+        Ok(Self)
+    }
+}
+
+impl File for AuditFile {
+    /// 阻塞直到有记录，缓冲区放不下一条完整记录时返回 `InvalidArgument`
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let text = read(buf.len());
+        if text.is_empty() {
+            return Err(KernelError::InvalidArgument);
+        }
+        buf[..text.len()].copy_from_slice(text.as_bytes());
+        Ok(text.len())
+    }
+
+    fn poll(&self) -> PollEvents {
+        if AUDIT_LOG.lock().records.is_empty() {
+            PollEvents::empty()
+        } else {
+            PollEvents::IN
+        }
+    }
+
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(&AUDIT_WAIT);
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o400,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 生成 /proc/sys/kernel/audit 的内容
+fn proc_read_audit() -> String {
+    let log = AUDIT_LOG.lock();
+    alloc::format!(
+        "enabled {}\nbacklog {}\nlost {}\n",
+        AUDIT_ENABLED.load(Ordering::Relaxed) as u8,
+        log.records.len(),
+        AUDIT_LOST.load(Ordering::Relaxed)
+    )
+}
+
+/// 注册 /proc/sys/kernel/audit，写入0或1关闭或开启记录
+pub fn audit_init() -> Result<(), KernelError> {
+    procfs::register(
+        "sys/kernel/audit",
+        Some(Box::new(proc_read_audit)),
+        Some(Box::new(|data: &str| {
+            match data.trim() {
+                "0" => AUDIT_ENABLED.store(false, Ordering::Relaxed),
+                "1" => AUDIT_ENABLED.store(true, Ordering::Relaxed),
+                _ => return Err(KernelError::InvalidArgument),
+            }
+            Ok(())
+        })),
+    )
+}
+
+crate::kernel_test! {
+    fn audit_record_format() {
+        let record = AuditRecord {
+            seq: 7,
+            timestamp_ns: 12_345_678_000,
+            event: AuditEvent::Capability(crate::sched::capability::CAP_SYS_BOOT),
+            pid: 5,
+            uid: 1000,
+            euid: 1000,
+            syscall: Some(142),
+            comm: String::from("sh"),
+        };
+        let mut out = String::new();
+        record.format(&mut out);
+        assert_eq!(
+            out,
+            "audit(12.345678:7): type=CAPABILITY pid=5 uid=1000 euid=1000 syscall=142 comm=\"sh\" capability=cap_sys_boot\n"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! 内核启动参数
+//!
+//! 启动参数是以空白分隔的 `key=value` 或 `flag` 列表，由引导程序通过
+//! 设备树的 /chosen/bootargs 传入。入口代码在调用 `kernel_main` 之前用
+//! `set_cmdline` 保存；未设置时使用编译时的 `LILITH_CMDLINE` 环境变量。
+//!
+//! 参数保存在静态缓冲区中，内存管理初始化之前即可查询
+
+use crate::error::BootError;
+use spin::Once;
+
+/// 启动参数的最大长度
+pub const CMDLINE_MAX: usize = 1024;
+
+/// 编译时指定的默认启动参数
+const DEFAULT_CMDLINE: &str = match option_env!("LILITH_CMDLINE") {
+    Some(cmdline) => cmdline,
+    None => "",
+};
+
+/// 保存的启动参数
+struct Cmdline {
+    buf: [u8; CMDLINE_MAX],
+    len: usize,
+}
+
+static CMDLINE: Once<Cmdline> = Once::new();
+
+/// 保存引导程序传入的启动参数，只有第一次调用有效
+pub fn set_cmdline(cmdline: &str) -> Result<(), BootError> {
+    if cmdline.len() > CMDLINE_MAX {
+        return Err(BootError::ConfigurationError);
+    }
+    CMDLINE.call_once(|| {
+        let mut buf = [0; CMDLINE_MAX];
+        buf[..cmdline.len()].copy_from_slice(cmdline.as_bytes());
+        Cmdline {
+            buf,
+            len: cmdline.len(),
+        }
+    });
+    Ok(())
+}
+
+/// 完整的启动参数
+pub fn cmdline() -> &'static str {
+    match CMDLINE.get() {
+        // 只由 `set_cmdline` 从 `&str` 复制而来
+        Some(cmdline) => core::str::from_utf8(&cmdline.buf[..cmdline.len]).unwrap_or(""),
+        None => DEFAULT_CMDLINE,
+    }
+}
+
+/// 依次访问每个参数，返回 `(key, value)`，没有 `=` 的参数值为 `None`
+pub fn params() -> impl Iterator<Item = (&'static str, Option<&'static str>)> {
+    cmdline().split_whitespace().map(|param| match param.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (param, None),
+    })
+}
+
+/// 参数的值，出现多次时取最后一个
+pub fn get(key: &str) -> Option<&'static str> {
+    params().filter(|&(k, _)| k == key).filter_map(|(_, value)| value).last()
+}
+
+/// 参数是否出现（不论是否带值）
+pub fn has(key: &str) -> bool {
+    params().any(|(k, _)| k == key)
+}
@@ -0,0 +1,401 @@
+//! 扁平设备树（FDT）
+//!
+//! 引导程序（OpenSBI/QEMU）通过a1寄存器传入设备树的物理地址，入口代码在
+//! 调用 `kernel_main` 之前用 `set_fdt` 保存。本模块读取设备树，包括：
+//! - 检查头部的魔数与版本
+//! - 按路径查找节点属性，如 `/cpus` 的 `timebase-frequency`
+//! - 保存 `/chosen/bootargs` 作为内核启动参数
+//! - 列出带 `compatible` 属性的设备节点
+//! - 生成修改了 `/chosen` 属性的副本，传给kexec启动的内核
+//!
+//! 设备树中的整数均为大端序
+
+use super::cmdline;
+use crate::error::BootError;
+use alloc::string::String;
+use alloc::vec::Vec;
+use spin::Once;
+
+/// 设备树魔数
+const FDT_MAGIC: u32 = 0xd00d_feed;
+/// 支持的最低兼容版本
+const FDT_LAST_COMP_VERSION: u32 = 16;
+/// 生成的设备树的版本
+const FDT_VERSION: u32 = 17;
+/// 头部大小
+const FDT_HEADER_SIZE: usize = 40;
+
+/// 结构块中的标记
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// 带 `compatible` 属性的设备节点
+#[derive(Debug, Clone)]
+pub struct DeviceNode {
+    /// 节点的完整路径
+    pub path: String,
+    /// `compatible` 的第一项
+    pub compatible: &'static str,
+    /// `status` 为 `okay`/`ok` 或没有 `status` 属性
+    pub enabled: bool,
+}
+
+/// 只读的设备树
+pub struct Fdt {
+    data: &'static [u8],
+    struct_offset: usize,
+    struct_size: usize,
+    strings_offset: usize,
+    strings_size: usize,
+}
+
+static FDT: Once<Fdt> = Once::new();
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// 按4字节对齐
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// 以0结尾的字符串
+fn cstr(data: &[u8], offset: usize) -> Option<&str> {
+    let rest = data.get(offset..)?;
+    let len = rest.iter().position(|&byte| byte == 0)?;
+    core::str::from_utf8(&rest[..len]).ok()
+}
+
+fn push_be32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// 补0到4字节对齐
+fn pad4(out: &mut Vec<u8>) {
+    out.resize(align4(out.len()), 0);
+}
+
+/// 属性名在字符串块中的偏移，没有时追加到末尾
+fn string_offset(strings: &mut Vec<u8>, name: &str) -> u32 {
+    let mut offset = 0;
+    while offset < strings.len() {
+        let len = strings[offset..].iter().position(|&byte| byte == 0).unwrap_or(strings.len() - offset);
+        if &strings[offset..offset + len] == name.as_bytes() {
+            return offset as u32;
+        }
+        offset += len + 1;
+    }
+    let offset = strings.len();
+    strings.extend_from_slice(name.as_bytes());
+    strings.push(0);
+    offset as u32
+}
+
+/// 节点名是否与路径中的一段匹配，路径段不含单元地址时忽略节点名中的 `@...`
+fn node_matches(node: &str, component: &str) -> bool {
+    if component.contains('@') {
+        node == component
+    } else {
+        node.split('@').next() == Some(component)
+    }
+}
+
+impl Fdt {
+    /// 解析位于 `addr` 的设备树
+    ///
+    /// # Safety
+    ///
+    /// `addr` 处必须是引导程序传入的设备树，且在内核运行期间保持不变
+    pub unsafe fn from_addr(addr: usize) -> Result<Self, BootError> {
+        if addr == 0 || addr % 8 != 0 {
+            return Err(BootError::ConfigurationError);
+        }
+        let header = core::slice::from_raw_parts(addr as *const u8, 40);
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(BootError::ConfigurationError);
+        }
+        let total_size = be32(header, 4).unwrap_or(0) as usize;
+        let data = core::slice::from_raw_parts(addr as *const u8, total_size);
+        Self::new(data)
+    }
+
+    /// 解析内存中的设备树
+    pub fn new(data: &'static [u8]) -> Result<Self, BootError> {
+        let field = |index: usize| be32(data, index * 4).map(|value| value as usize);
+        let invalid = BootError::ConfigurationError;
+
+        if field(0) != Some(FDT_MAGIC as usize) || field(1) != Some(data.len()) {
+            return Err(invalid);
+        }
+        if field(6).ok_or(invalid)? > FDT_LAST_COMP_VERSION as usize {
+            return Err(BootError::HardwareIncompatible);
+        }
+
+        let fdt = Self {
+            data,
+            struct_offset: field(2).ok_or(invalid)?,
+            strings_offset: field(3).ok_or(invalid)?,
+            strings_size: field(8).ok_or(invalid)?,
+            struct_size: field(9).ok_or(invalid)?,
+        };
+        if fdt.struct_offset + fdt.struct_size > data.len() || fdt.strings_offset + fdt.strings_size > data.len() {
+            return Err(invalid);
+        }
+        Ok(fdt)
+    }
+
+    /// 查找节点 `path` 的属性 `name`，如 `property("/cpus", "timebase-frequency")`
+    pub fn property(&self, path: &str, name: &str) -> Option<&'static [u8]> {
+        let data = self.data;
+        let strings = &data[self.strings_offset..self.strings_offset + self.strings_size];
+        let end = self.struct_offset + self.struct_size;
+        let mut components = path.split('/').filter(|component| !component.is_empty());
+        let mut target = components.next();
+
+        // depth为当前节点深度，matched为已匹配路径的深度
+        let mut depth = 0usize;
+        let mut matched = 0usize;
+        let mut offset = self.struct_offset;
+        while offset < end {
+            let token = be32(data, offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let node = cstr(data, offset)?;
+                    offset = align4(offset + node.len() + 1);
+                    // 根节点的名字为空，不占用路径段
+                    if depth > 0 && matched == depth {
+                        if let Some(component) = target {
+                            if node_matches(node, component) {
+                                matched += 1;
+                                target = components.next();
+                            }
+                        }
+                    } else if depth == 0 {
+                        matched = 1;
+                    }
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    // 离开已匹配的节点后不会再遇到目标节点
+                    if matched == depth && depth > 0 {
+                        return None;
+                    }
+                    depth = depth.checked_sub(1)?;
+                }
+                FDT_PROP => {
+                    let len = be32(data, offset)? as usize;
+                    let name_offset = be32(data, offset + 4)? as usize;
+                    let value = data.get(offset + 8..offset + 8 + len)?;
+                    offset = align4(offset + 8 + len);
+                    if target.is_none() && matched == depth && cstr(strings, name_offset)? == name {
+                        return Some(value);
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => return None,
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// 读取32位或64位的整数属性
+    pub fn property_u64(&self, path: &str, name: &str) -> Option<u64> {
+        let value = self.property(path, name)?;
+        match value.len() {
+            4 => be32(value, 0).map(u64::from),
+            8 => Some((u64::from(be32(value, 0)?) << 32) | u64::from(be32(value, 4)?)),
+            _ => None,
+        }
+    }
+
+    /// 读取字符串属性
+    pub fn property_str(&self, path: &str, name: &str) -> Option<&'static str> {
+        let value = self.property(path, name)?;
+        let value = value.strip_suffix(&[0]).unwrap_or(value);
+        core::str::from_utf8(value).ok()
+    }
+
+    /// 所有带 `compatible` 属性的节点，按路径排序
+    pub fn devices(&self) -> Vec<DeviceNode> {
+        let data = self.data;
+        let strings = &data[self.strings_offset..self.strings_offset + self.strings_size];
+        let end = self.struct_offset + self.struct_size;
+        // 从根节点到当前节点的名字与已读到的 `compatible`、`status`
+        let mut stack: Vec<(&'static str, Option<&'static str>, bool)> = Vec::new();
+        let mut devices = Vec::new();
+        let mut offset = self.struct_offset;
+        while offset < end {
+            let Some(token) = be32(data, offset) else { break };
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let Some(node) = cstr(data, offset) else { break };
+                    offset = align4(offset + node.len() + 1);
+                    stack.push((node, None, true));
+                }
+                FDT_END_NODE => {
+                    if let Some(&(_, Some(compatible), enabled)) = stack.last() {
+                        // 根节点的名字为空，路径从第二层开始
+                        let path: String = stack.iter().skip(1).flat_map(|&(name, _, _)| ["/", name]).collect();
+                        devices.push(DeviceNode {
+                            path: if path.is_empty() { String::from("/") } else { path },
+                            compatible,
+                            enabled,
+                        });
+                    }
+                    stack.pop();
+                }
+                FDT_PROP => {
+                    let (Some(len), Some(name_offset)) = (be32(data, offset), be32(data, offset + 4)) else { break };
+                    let Some(value) = data.get(offset + 8..offset + 8 + len as usize) else { break };
+                    offset = align4(offset + 8 + len as usize);
+                    let Some(current) = stack.last_mut() else { break };
+                    match cstr(strings, name_offset as usize) {
+                        Some("compatible") => current.1 = cstr(value, 0),
+                        Some("status") => current.2 = matches!(cstr(value, 0), Some("okay" | "ok")),
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                _ => break,
+            }
+        }
+        devices.sort_by(|a, b| a.path.cmp(&b.path));
+        devices
+    }
+
+    /// 复制设备树，把 `/chosen` 中的属性替换为 `props` 给出的值
+    ///
+    /// 原来没有的属性与 `/chosen` 节点会被添加，保留内存区表原样复制
+    pub fn with_chosen(&self, props: &[(&str, &[u8])]) -> Option<Vec<u8>> {
+        let data = self.data;
+        let mut strings = data[self.strings_offset..self.strings_offset + self.strings_size].to_vec();
+        let name_offsets: Vec<u32> = props.iter().map(|(name, _)| string_offset(&mut strings, name)).collect();
+        let emit_props = |out: &mut Vec<u8>| {
+            for ((_, value), &name_offset) in props.iter().zip(&name_offsets) {
+                push_be32(out, FDT_PROP);
+                push_be32(out, value.len() as u32);
+                push_be32(out, name_offset);
+                out.extend_from_slice(value);
+                pad4(out);
+            }
+        };
+
+        let mut structure = Vec::with_capacity(self.struct_size + 64);
+        let end = self.struct_offset + self.struct_size;
+        let mut depth = 0usize;
+        let mut in_chosen = false;
+        let mut found_chosen = false;
+        let mut offset = self.struct_offset;
+        while offset < end {
+            let start = offset;
+            let token = be32(data, offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let node = cstr(data, offset)?;
+                    offset = align4(offset + node.len() + 1);
+                    depth += 1;
+                    if depth == 2 && node == "chosen" {
+                        in_chosen = true;
+                        found_chosen = true;
+                    }
+                }
+                FDT_END_NODE => {
+                    if in_chosen && depth == 2 {
+                        emit_props(&mut structure);
+                        in_chosen = false;
+                    } else if depth == 1 && !found_chosen {
+                        // 在根节点结束前补上 /chosen
+                        push_be32(&mut structure, FDT_BEGIN_NODE);
+                        structure.extend_from_slice(b"chosen\0");
+                        pad4(&mut structure);
+                        emit_props(&mut structure);
+                        push_be32(&mut structure, FDT_END_NODE);
+                    }
+                    depth = depth.checked_sub(1)?;
+                }
+                FDT_PROP => {
+                    let len = be32(data, offset)? as usize;
+                    let name_offset = be32(data, offset + 4)? as usize;
+                    offset = align4(offset + 8 + len);
+                    if in_chosen && depth == 2 {
+                        let name = cstr(&strings, name_offset)?;
+                        if props.iter().any(|&(prop, _)| prop == name) {
+                            continue;
+                        }
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => {
+                    structure.extend_from_slice(data.get(start..offset)?);
+                    break;
+                }
+                _ => return None,
+            }
+            structure.extend_from_slice(data.get(start..offset)?);
+        }
+
+        // 保留内存区表：以地址与大小均为0的表项结束
+        let rsvmap_offset = be32(data, 16)? as usize;
+        let mut rsvmap = Vec::new();
+        loop {
+            let entry = data.get(rsvmap_offset + rsvmap.len()..rsvmap_offset + rsvmap.len() + 16)?;
+            rsvmap.extend_from_slice(entry);
+            if entry.iter().all(|&byte| byte == 0) {
+                break;
+            }
+        }
+
+        let struct_offset = FDT_HEADER_SIZE + rsvmap.len();
+        let strings_offset = struct_offset + structure.len();
+        let total_size = strings_offset + strings.len();
+        let mut out = Vec::with_capacity(total_size);
+        for field in [
+            FDT_MAGIC,
+            total_size as u32,
+            struct_offset as u32,
+            strings_offset as u32,
+            FDT_HEADER_SIZE as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            be32(data, 28)?,
+            strings.len() as u32,
+            structure.len() as u32,
+        ] {
+            push_be32(&mut out, field);
+        }
+        out.extend_from_slice(&rsvmap);
+        out.extend_from_slice(&structure);
+        out.extend_from_slice(&strings);
+        Some(out)
+    }
+}
+
+/// 保存引导程序传入的设备树，只有第一次调用有效
+///
+/// 设备树中有 `/chosen/bootargs` 时同时保存为启动参数
+///
+/// # Safety
+///
+/// 同 `Fdt::from_addr`
+pub unsafe fn set_fdt(addr: usize) -> Result<(), BootError> {
+    let fdt = Fdt::from_addr(addr)?;
+    let fdt = FDT.call_once(|| fdt);
+    if let Some(bootargs) = fdt.property_str("/chosen", "bootargs") {
+        cmdline::set_cmdline(bootargs)?;
+    }
+    Ok(())
+}
+
+/// 引导程序传入的设备树
+pub fn fdt() -> Option<&'static Fdt> {
+    FDT.get()
+}
@@ -0,0 +1,193 @@
+//! 内核地址空间布局随机化（KASLR）
+//!
+//! 入口代码在开启分页、调用 `set_fdt` 之前调用 `kaslr_relocate`，把内核映像复制到随机选择的
+//! 物理地址、应用重定位后从新位置重新进入。内核运行在恒等映射中，因此物理装载偏移同时就是
+//! 虚拟基址的偏移。本模块包括：
+//! - 早期熵：设备树 `/chosen/kaslr-seed` 与 `/chosen/rng-seed`，混入 `time` CSR
+//! - 在内存中内核原位置之上选择2MiB对齐的新位置，避开设备树与initrd
+//! - 按 `.rela.dyn` 中的 `R_RISCV_RELATIVE` 重定位项修正新映像中的绝对地址
+//! - `kaslr_offset`：新旧位置之差，符号表查找与恐慌信息使用
+//!
+//! 内核须以 `-C relocation-model=pie` 编译并以 `-pie` 链接，链接脚本导出
+//! `__rela_dyn_start`/`__rela_dyn_end`。没有重定位项、重定位项含其他类型或启动参数含
+//! `nokaslr` 时不移动内核
+
+use super::fdt::Fdt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 新位置的对齐，与大页大小一致
+const KASLR_ALIGN: usize = 2 * 1024 * 1024;
+
+/// 新位置距原位置的最大距离
+const KASLR_MAX_OFFSET: usize = 1024 * 1024 * 1024;
+
+/// 重定位类型：基址加常数
+const R_RISCV_RELATIVE: u64 = 3;
+
+/// ELF重定位项
+#[repr(C)]
+struct Elf64Rela {
+    offset: u64,
+    info: u64,
+    addend: i64,
+}
+
+extern "C" {
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+    static __rela_dyn_start: Elf64Rela;
+    static __rela_dyn_end: Elf64Rela;
+    /// 内核入口
+    fn _start();
+}
+
+// 以下两个变量放在.data中：入口代码会清零新映像的.bss
+
+/// 内核相对链接地址的偏移
+#[link_section = ".data"]
+static KASLR_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// 是否已经在新位置上运行，复制前设置，新映像中的副本因此为 `true`
+#[link_section = ".data"]
+static RELOCATED: AtomicBool = AtomicBool::new(false);
+
+/// 内核相对链接地址的偏移，没有随机化时为0
+pub fn kaslr_offset() -> usize {
+    KASLR_OFFSET.load(Ordering::Relaxed)
+}
+
+/// 内核映像中的重定位项
+fn relocations() -> &'static [Elf64Rela] {
+    unsafe {
+        let start = &__rela_dyn_start as *const Elf64Rela;
+        let end = &__rela_dyn_end as *const Elf64Rela;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 读取设备树中按 `cells` 个32位单元编码的整数
+fn read_cells(data: &[u8], cells: usize) -> Option<u64> {
+    let bytes = data.get(..cells * 4)?;
+    Some(bytes.chunks(4).fold(0u64, |value, chunk| {
+        (value << 32) | u64::from(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+    }))
+}
+
+/// 第一个内存区 `[起始, 结束)`
+fn memory_range(fdt: &Fdt) -> Option<(usize, usize)> {
+    let address_cells = fdt.property_u64("/", "#address-cells").unwrap_or(2) as usize;
+    let size_cells = fdt.property_u64("/", "#size-cells").unwrap_or(1) as usize;
+    let reg = fdt.property("/memory", "reg")?;
+    let base = read_cells(reg, address_cells)? as usize;
+    let size = read_cells(reg.get(address_cells * 4..)?, size_cells)? as usize;
+    Some((base, base.checked_add(size)?))
+}
+
+/// 混合设备树种子与计时器计数得到的早期随机数
+fn early_seed(fdt: &Fdt) -> u64 {
+    let time: u64;
+    unsafe {
+        core::arch::asm!("rdtime {}", out(reg) time);
+    }
+    let mut seed = time;
+    if let Some(kaslr_seed) = fdt.property_u64("/chosen", "kaslr-seed") {
+        seed ^= kaslr_seed;
+    }
+    if let Some(rng_seed) = fdt.property("/chosen", "rng-seed") {
+        for chunk in rng_seed.chunks(8) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            seed = seed.rotate_left(29) ^ u64::from_le_bytes(bytes);
+        }
+    }
+    // splitmix64的最终混合
+    seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    seed ^ (seed >> 31)
+}
+
+/// 启动参数是否关闭了KASLR
+fn disabled_by_cmdline(fdt: &Fdt) -> bool {
+    fdt.property_str("/chosen", "bootargs")
+        .is_some_and(|bootargs| bootargs.split_whitespace().any(|param| param == "nokaslr"))
+}
+
+/// 在 `[low, high)` 中选择能放下 `size` 字节、不与 `reserved` 重叠的2MiB对齐位置
+fn choose_base(low: usize, high: usize, size: usize, reserved: &[(usize, usize)], seed: u64) -> Option<usize> {
+    let first = (low + KASLR_ALIGN - 1) & !(KASLR_ALIGN - 1);
+    let usable = |base: usize| reserved.iter().all(|&(start, end)| base + size <= start || end <= base);
+    let candidates = || {
+        (first..high.saturating_sub(size))
+            .step_by(KASLR_ALIGN)
+            .filter(|&base| usable(base))
+    };
+    let count = candidates().count();
+    if count == 0 {
+        return None;
+    }
+    candidates().nth((seed % count as u64) as usize)
+}
+
+/// 把内核移动到随机位置并从新位置的入口重新开始，不移动时直接返回
+///
+/// 在新位置重新进入时再次调用本函数会直接返回
+///
+/// # Safety
+///
+/// 只能由启动hart在开启分页与启动其他hart之前调用，`fdt_addr` 为引导程序传入的设备树，
+/// 内核须运行在链接地址上
+pub unsafe fn kaslr_relocate(hart_id: usize, fdt_addr: usize) {
+    if RELOCATED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Ok(fdt) = Fdt::from_addr(fdt_addr) else {
+        return;
+    };
+    let relocations = relocations();
+    if disabled_by_cmdline(&fdt) || relocations.is_empty() {
+        return;
+    }
+    // 只支持位置无关的内核链接后剩下的相对重定位
+    if relocations
+        .iter()
+        .any(|rela| rela.info & 0xffff_ffff != R_RISCV_RELATIVE)
+    {
+        return;
+    }
+
+    let link_start = &__kernel_start as *const u8 as usize;
+    let link_end = &__kernel_end as *const u8 as usize;
+    let size = link_end - link_start;
+    let Some((_, memory_end)) = memory_range(&fdt) else {
+        return;
+    };
+
+    // 引导固件位于内核之下，只在内核原位置之上选择
+    let fdt_size = u32::from_be(*((fdt_addr + 4) as *const u32)) as usize;
+    let initrd = match (
+        fdt.property_u64("/chosen", "linux,initrd-start"),
+        fdt.property_u64("/chosen", "linux,initrd-end"),
+    ) {
+        (Some(start), Some(end)) => (start as usize, end as usize),
+        _ => (0, 0),
+    };
+    let reserved = [(fdt_addr, fdt_addr + fdt_size), initrd];
+    let high = memory_end.min(link_start + KASLR_MAX_OFFSET);
+    let Some(base) = choose_base(link_end, high, size, &reserved, early_seed(&fdt)) else {
+        return;
+    };
+    let delta = base - link_start;
+
+    // 先设置标志再复制，新映像中的副本记录了偏移
+    KASLR_OFFSET.store(delta, Ordering::Relaxed);
+    RELOCATED.store(true, Ordering::Relaxed);
+    core::ptr::copy_nonoverlapping(link_start as *const u8, base as *mut u8, size);
+    for rela in relocations {
+        let target = (rela.offset as usize - link_start + base) as *mut usize;
+        target.write((rela.addend as usize).wrapping_add(delta));
+    }
+
+    // 旧映像不再使用
+    let entry = _start as *const () as usize + delta;
+    crate::arch::kexec_jump(entry, hart_id, fdt_addr)
+}
@@ -3,6 +3,7 @@
 //! 本模块实现了RISC-V机器模式的寄存器配置和初始化流程
 //! 严格遵循RISC-V特权架构规范
 
+use super::{memory_detect, pmp};
 use crate::error::BootError;
 use riscv::register::*;
 use bitflags::bitflags;
@@ -17,6 +18,7 @@ bitflags! {
         const FLOAT_DOUBLE  = 1 << 3;   // D - 双精度浮点扩展
         const COMPRESSED    = 1 << 2;   // C - 压缩指令扩展
         const VECTOR        = 1 << 21;  // V - 向量扩展
+        const HYPERVISOR    = 1 << 7;   // H - 虚拟化扩展
         const SUPERVISOR    = 1 << 18;  // S - 监管者模式
         const USER          = 1 << 20;  // U - 用户模式
     }
@@ -35,6 +37,8 @@ pub struct MachineConfig {
     pub clock_frequency: u64,
     /// 是否支持向量扩展
     pub vector_support: bool,
+    /// 是否支持虚拟化扩展
+    pub hypervisor_support: bool,
 }
 
 /// 全局机器配置
@@ -63,6 +67,7 @@ pub fn verify_hardware_compatibility() -> Result<(), BootError> {
     if misa & (1 << 3) != 0 { supported_extensions |= RiscvExtensions::FLOAT_DOUBLE; }
     if misa & (1 << 2) != 0 { supported_extensions |= RiscvExtensions::COMPRESSED; }
     if misa & (1 << 21) != 0 { supported_extensions |= RiscvExtensions::VECTOR; }
+    if misa & (1 << 7) != 0 { supported_extensions |= RiscvExtensions::HYPERVISOR; }
     if misa & (1 << 18) != 0 { supported_extensions |= RiscvExtensions::SUPERVISOR; }
     if misa & (1 << 20) != 0 { supported_extensions |= RiscvExtensions::USER; }
     
@@ -84,6 +89,7 @@ pub fn verify_hardware_compatibility() -> Result<(), BootError> {
         memory_size: 0, // 后续通过内存检测获取
         clock_frequency: 0, // 后续通过设备树获取
         vector_support: supported_extensions.contains(RiscvExtensions::VECTOR),
+        hypervisor_support: supported_extensions.contains(RiscvExtensions::HYPERVISOR),
     };
     
     unsafe {
@@ -193,17 +199,18 @@ unsafe fn configure_vector_registers() -> Result<(), BootError> {
 
 /// 设置物理内存保护
 /// 
-/// 配置PMP寄存器以保护关键内存区域
+/// 按内存映射配置PMP寄存器：M模式固件对低特权级不可访问，内核代码段只读
 pub fn setup_physical_memory_protection() -> Result<(), BootError> {
+    if memory_detect::get_memory_map().is_none() {
+        memory_detect::detect_system_memory()?;
+    }
+    let memory_map = memory_detect::get_memory_map().ok_or(BootError::MemoryDetectionFailed)?;
+    let table = pmp::table_from_memory_map(memory_map)?;
     unsafe {
-        // PMP配置将在后续实现
-        // 这里需要根据内存布局来设置保护区域
-        
-        // 示例：保护内核代码段
-        // pmpaddr0::write(kernel_start_addr >> 2);
-        // pmpcfg0::write(pmpcfg0::read() | (0x1F << 0)); // RWX权限
+        table.apply();
     }
-    
+    crate::log_info!("PMP: 已配置 {} 个表项", table.len());
+
     Ok(())
 }
 
@@ -2,6 +2,7 @@
 //! 
 //! 本模块负责检测系统可用内存并建立基础的内存映射
 
+use crate::debug::pstore::{PSTORE_BASE, PSTORE_SIZE};
 use crate::error::BootError;
 use core::mem;
 
@@ -24,6 +25,8 @@ pub enum MemoryType {
     KernelData,
     /// 设备内存映射
     DeviceMemory,
+    /// M模式固件（如OpenSBI），低特权级不能访问
+    Firmware,
 }
 
 /// 内存区域描述符
@@ -132,7 +135,7 @@ impl MemoryMap {
         // 检查是否与现有区域重叠
         for i in 0..self.region_count {
             if self.regions[i].overlaps(&region) {
-                crate::early_println!("警告: 内存区域重叠 0x{:x}-0x{:x} 与 0x{:x}-0x{:x}",
+                crate::log_warn!("内存区域重叠 0x{:x}-0x{:x} 与 0x{:x}-0x{:x}",
                     region.start_addr, region.end_addr(),
                     self.regions[i].start_addr, self.regions[i].end_addr());
             }
@@ -182,7 +185,7 @@ impl MemoryMap {
 
 /// 检测系统内存
 pub fn detect_system_memory() -> Result<(), BootError> {
-    crate::early_println!("开始检测系统内存...");
+    crate::log_info!("开始检测系统内存...");
 
     let mut memory_map = MemoryMap::new();
 
@@ -204,7 +207,7 @@ pub fn detect_system_memory() -> Result<(), BootError> {
         MEMORY_MAP = Some(memory_map);
     }
 
-    crate::early_println!("内存检测完成");
+    crate::log_info!("内存检测完成");
     Ok(())
 }
 
@@ -213,14 +216,17 @@ fn detect_memory_from_device_tree(memory_map: &mut MemoryMap) -> Result<(), Boot
     // 这里应该解析设备树中的memory节点
     // 暂时使用硬编码的值作为示例
     
-    // 示例：添加主内存区域（128MB）
+    // 示例：添加主内存区域（128MB），末尾保留给pstore
     let main_memory = MemoryRegion::new(
         0x80000000,  // RISC-V典型的内存起始地址
-        128 * 1024 * 1024,  // 128MB
+        128 * 1024 * 1024 - PSTORE_SIZE,  // 128MB，去掉末尾的pstore区域
         MemoryType::Available,
     );
     memory_map.add_region(main_memory)?;
 
+    // 热重启后内容保留的崩溃记录区域
+    memory_map.add_region(MemoryRegion::new(PSTORE_BASE, PSTORE_SIZE, MemoryType::Reserved))?;
+
     // 示例：添加设备内存映射区域
     let device_memory = MemoryRegion {
         start_addr: 0x10000000,
@@ -263,6 +269,27 @@ fn add_kernel_regions(memory_map: &mut MemoryMap) -> Result<(), BootError> {
         let bss_start = &__bss_start as *const u8 as usize;
         let bss_end = &__bss_end as *const u8 as usize;
 
+        // 内存起始处到内核之间是引导时运行的M模式固件
+        let ram_start = memory_map
+            .find_region(kernel_start)
+            .filter(|region| region.memory_type == MemoryType::Available)
+            .map(|region| region.start_addr);
+        if let Some(ram_start) = ram_start.filter(|&ram_start| ram_start < kernel_start) {
+            let firmware_region = MemoryRegion {
+                start_addr: ram_start,
+                size: kernel_start - ram_start,
+                memory_type: MemoryType::Firmware,
+                attributes: MemoryAttributes {
+                    readable: false,
+                    writable: false,
+                    executable: false,
+                    cacheable: true,
+                    write_through: false,
+                },
+            };
+            memory_map.add_region(firmware_region)?;
+        }
+
         // 添加内核代码段
         if text_end > text_start {
             let code_region = MemoryRegion {
@@ -320,11 +347,12 @@ fn add_kernel_regions(memory_map: &mut MemoryMap) -> Result<(), BootError> {
 
 /// 打印内存映射信息
 fn print_memory_map(memory_map: &MemoryMap) {
-    crate::early_println!("内存映射信息:");
-    crate::early_println!("总内存: {} MB", memory_map.total_memory / (1024 * 1024));
-    crate::early_println!("可用内存: {} MB", memory_map.available_memory / (1024 * 1024));
-    crate::early_println!("内存区域数量: {}", memory_map.region_count);
-    crate::early_println!("");
+    crate::log_info!(
+        "内存映射: 总内存 {} MB, 可用 {} MB, {} 个区域",
+        memory_map.total_memory / (1024 * 1024),
+        memory_map.available_memory / (1024 * 1024),
+        memory_map.region_count
+    );
 
     for i in 0..memory_map.region_count {
         let region = &memory_map.regions[i];
@@ -337,9 +365,10 @@ fn print_memory_map(memory_map: &MemoryMap) {
             MemoryType::KernelCode => "内核代码",
             MemoryType::KernelData => "内核数据",
             MemoryType::DeviceMemory => "设备内存",
+            MemoryType::Firmware => "M模式固件",
         };
 
-        crate::early_println!(
+        crate::log_debug!(
             "  区域{}: 0x{:08x}-0x{:08x} ({} KB) - {}",
             i,
             region.start_addr,
@@ -348,7 +377,6 @@ fn print_memory_map(memory_map: &MemoryMap) {
             type_str
         );
     }
-    crate::early_println!("");
 }
 
 /// 获取内存映射
@@ -2,11 +2,18 @@
 //! 
 //! 本模块负责系统的早期初始化，包括：
 //! - M-mode机器模式寄存器配置
+//! - 按内存映射配置物理内存保护（PMP）
 //! - 硬件发现与初始化
 //! - S-mode准备工作
 //! - 早期调试支持
+//! - 启动参数与设备树
+//! - 内核地址空间布局随机化（KASLR）
 
+pub mod cmdline;
+pub mod fdt;
+pub mod kaslr;
 pub mod machine_mode;
+pub mod pmp;
 pub mod uart;
 pub mod memory_detect;
 
@@ -0,0 +1,168 @@
+//! 物理内存保护（PMP）
+//!
+//! 按检测到的内存映射生成PMP表项，限制S模式与U模式对物理内存的访问，包括：
+//! - M模式固件所在的区域：不可读、写、执行
+//! - 内核代码段：只读、可执行
+//! - 其余地址：不加限制（最后一项覆盖整个地址空间）
+//!
+//! 区域按大小与对齐选择NAPOT或TOR编码，表项不加锁定位，因此不限制M模式自身的访问。
+//! 表项按优先级从0开始排列，编号小的先匹配
+
+use super::memory_detect::{MemoryMap, MemoryRegion, MemoryType};
+use crate::error::BootError;
+
+/// PMP表项数（RV64上pmpcfg0与pmpcfg2各8项）
+pub const PMP_ENTRIES: usize = 16;
+
+/// 表项权限与地址匹配模式（pmpcfg中的一个字节）
+const PMP_R: u8 = 1 << 0;
+const PMP_W: u8 = 1 << 1;
+const PMP_X: u8 = 1 << 2;
+const PMP_A_OFF: u8 = 0;
+const PMP_A_TOR: u8 = 1 << 3;
+const PMP_A_NAPOT: u8 = 3 << 3;
+
+/// 一个PMP表项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PmpEntry {
+    /// pmpcfg字节
+    cfg: u8,
+    /// pmpaddr的值（物理地址右移2位）
+    addr: usize,
+}
+
+/// 待写入的PMP表
+pub struct PmpTable {
+    entries: [PmpEntry; PMP_ENTRIES],
+    count: usize,
+}
+
+impl PmpTable {
+    /// 空表
+    pub const fn new() -> Self {
+        Self {
+            entries: [PmpEntry { cfg: 0, addr: 0 }; PMP_ENTRIES],
+            count: 0,
+        }
+    }
+
+    /// 已使用的表项数
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// 是否没有表项
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn push(&mut self, cfg: u8, addr: usize) -> Result<(), BootError> {
+        if self.count >= PMP_ENTRIES {
+            return Err(BootError::ConfigurationError);
+        }
+        self.entries[self.count] = PmpEntry { cfg, addr };
+        self.count += 1;
+        Ok(())
+    }
+
+    /// 添加区域 `[start, end)`，`perm` 为 `PMP_R`/`PMP_W`/`PMP_X` 的组合
+    fn add(&mut self, start: usize, end: usize, perm: u8) -> Result<(), BootError> {
+        if start % 4 != 0 || end % 4 != 0 || start >= end {
+            return Err(BootError::ConfigurationError);
+        }
+        let size = end - start;
+        if size >= 8 && size.is_power_of_two() && start % size == 0 {
+            return self.push(perm | PMP_A_NAPOT, (start | (size / 2 - 1)) >> 2);
+        }
+        // TOR以上一项的地址为下界，上一项不是以 `start` 结束的TOR/OFF项时先加一个OFF项
+        let previous = self.count.checked_sub(1).map(|index| self.entries[index]);
+        let lower_ok = match previous {
+            None => start == 0,
+            Some(entry) => entry.cfg & PMP_A_NAPOT != PMP_A_NAPOT && entry.addr == start >> 2,
+        };
+        if !lower_ok {
+            self.push(PMP_A_OFF, start >> 2)?;
+        }
+        self.push(perm | PMP_A_TOR, end >> 2)
+    }
+
+    /// 添加覆盖整个地址空间的表项
+    fn add_all(&mut self, perm: u8) -> Result<(), BootError> {
+        self.push(perm | PMP_A_NAPOT, usize::MAX)
+    }
+
+    /// 写入PMP寄存器，未使用的表项关闭
+    ///
+    /// # Safety
+    ///
+    /// 必须在M模式下调用，新的表项不能禁止S模式访问内核正在使用的内存
+    pub unsafe fn apply(&self) {
+        let mut cfg = [0usize; 2];
+        for (index, entry) in self.entries[..self.count].iter().enumerate() {
+            write_pmpaddr(index, entry.addr);
+            cfg[index / 8] |= (entry.cfg as usize) << ((index % 8) * 8);
+        }
+        core::arch::asm!("csrw pmpcfg0, {}", in(reg) cfg[0]);
+        core::arch::asm!("csrw pmpcfg2, {}", in(reg) cfg[1]);
+        // PMP修改后须刷新地址转换缓存
+        core::arch::asm!("sfence.vma");
+    }
+}
+
+impl Default for PmpTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! write_pmpaddr_csr {
+    ($index:expr, $value:expr; $($num:literal => $csr:literal)*) => {
+        match $index {
+            $(
+                $num => core::arch::asm!(concat!("csrw ", $csr, ", {}"), in(reg) $value),
+            )*
+            _ => {}
+        }
+    };
+}
+
+/// 写入pmpaddr0-15
+unsafe fn write_pmpaddr(index: usize, value: usize) {
+    write_pmpaddr_csr!(index, value;
+        0 => "pmpaddr0" 1 => "pmpaddr1" 2 => "pmpaddr2" 3 => "pmpaddr3"
+        4 => "pmpaddr4" 5 => "pmpaddr5" 6 => "pmpaddr6" 7 => "pmpaddr7"
+        8 => "pmpaddr8" 9 => "pmpaddr9" 10 => "pmpaddr10" 11 => "pmpaddr11"
+        12 => "pmpaddr12" 13 => "pmpaddr13" 14 => "pmpaddr14" 15 => "pmpaddr15");
+}
+
+/// 区域对S模式与U模式的权限，取自内存映射中的属性
+fn region_perm(region: &MemoryRegion) -> u8 {
+    let attributes = &region.attributes;
+    let mut perm = 0;
+    if attributes.readable {
+        perm |= PMP_R;
+    }
+    if attributes.writable {
+        perm |= PMP_W;
+    }
+    if attributes.executable {
+        perm |= PMP_X;
+    }
+    perm
+}
+
+/// 由内存映射生成PMP表：固件与内核代码按属性限制，其余地址不加限制
+pub fn table_from_memory_map(memory_map: &MemoryMap) -> Result<PmpTable, BootError> {
+    let mut table = PmpTable::new();
+    for region in &memory_map.regions[..memory_map.region_count] {
+        if !matches!(region.memory_type, MemoryType::Firmware | MemoryType::KernelCode) || region.size == 0 {
+            continue;
+        }
+        // PMP的粒度至少为4字节，向外对齐
+        let start = region.start_addr & !3;
+        let end = (region.end_addr() + 3) & !3;
+        table.add(start, end, region_perm(region))?;
+    }
+    table.add_all(PMP_R | PMP_W | PMP_X)?;
+    Ok(table)
+}
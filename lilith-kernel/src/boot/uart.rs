@@ -2,8 +2,15 @@
 //! 
 //! 本模块实现了用于早期调试输出的串口驱动
 //! 在内存管理系统初始化之前提供基础的输出能力
-
-use crate::error::BootError;
+//!
+//! 16550寄存器可以通过内存映射（寄存器间距由 `reg-shift` 决定）或x86的I/O端口访问，
+//! 中断系统就绪后由设备树中的中断号开启接收中断，收到的字符直接送入控制台终端
+
+use super::fdt;
+use crate::debug::kshell;
+use crate::error::{BootError, KernelError};
+use crate::klog::console::{self, ConsoleDevice};
+use crate::power::suspend::{self, DevicePm};
 use core::fmt::{self, Arguments, Write};
 use spin::Mutex;
 
@@ -22,7 +29,11 @@ const UART_MCR: usize = 0x04;  // 调制解调器控制寄存器
 const UART_LSR: usize = 0x05;  // 线路状态寄存器
 const UART_MSR: usize = 0x06;  // 调制解调器状态寄存器
 
+/// 中断使能寄存器位定义
+const IER_RDI: u8 = 1 << 0;  // 接收数据可用中断
+
 /// 线路状态寄存器位定义
+const LSR_DR: u8 = 1 << 0;    // 接收数据就绪
 const LSR_THRE: u8 = 1 << 5;  // 发送保持寄存器空
 const LSR_TEMT: u8 = 1 << 6;  // 发送器空
 
@@ -30,11 +41,30 @@ const LSR_TEMT: u8 = 1 << 6;  // 发送器空
 const LCR_DLAB: u8 = 1 << 7;  // 除数锁存器访问位
 const LCR_8N1: u8 = 0x03;     // 8数据位，无奇偶校验，1停止位
 
+/// x86的第一个串口（COM1）的端口号
+#[cfg(target_arch = "x86_64")]
+const COM1_PORT: usize = 0x3f8;
+
+/// 一次中断最多读取的字节数（FIFO深度）
+const RX_BURST: usize = 16;
+
+/// 寄存器的访问方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterAccess {
+    /// 内存映射，第n个寄存器位于 `基地址 + (n << shift)`
+    Mmio { shift: u8 },
+    /// x86的I/O端口，第n个寄存器位于 `端口号 + n`
+    #[cfg(target_arch = "x86_64")]
+    Port,
+}
+
 /// UART配置结构
 #[derive(Debug, Clone, Copy)]
 pub struct UartConfig {
-    /// 基地址
+    /// 基地址（I/O端口访问时为端口号）
     pub base_addr: usize,
+    /// 寄存器的访问方式
+    pub access: RegisterAccess,
     /// 波特率
     pub baud_rate: u32,
     /// 时钟频率
@@ -58,16 +88,19 @@ pub enum Parity {
 /// UART驱动结构
 pub struct Uart {
     base_addr: usize,
+    access: RegisterAccess,
     config: UartConfig,
 }
 
 /// 全局早期UART实例
 static EARLY_UART: Mutex<Option<Uart>> = Mutex::new(None);
 
+#[cfg(not(target_arch = "x86_64"))]
 impl Default for UartConfig {
     fn default() -> Self {
         Self {
             base_addr: UART_BASE,
+            access: RegisterAccess::Mmio { shift: 0 },
             baud_rate: 115200,
             clock_freq: 50_000_000, // 50MHz，需要根据实际硬件调整
             data_bits: 8,
@@ -77,11 +110,28 @@ impl Default for UartConfig {
     }
 }
 
+/// PC的串口在COM1，时钟为1.8432MHz
+#[cfg(target_arch = "x86_64")]
+impl Default for UartConfig {
+    fn default() -> Self {
+        Self {
+            base_addr: COM1_PORT,
+            access: RegisterAccess::Port,
+            baud_rate: 115200,
+            clock_freq: 1_843_200,
+            data_bits: 8,
+            stop_bits: 1,
+            parity: Parity::None,
+        }
+    }
+}
+
 impl Uart {
     /// 创建新的UART实例
     pub fn new(config: UartConfig) -> Self {
         Self {
             base_addr: config.base_addr,
+            access: config.access,
             config,
         }
     }
@@ -145,7 +195,7 @@ impl Uart {
     pub fn read_byte(&self) -> Option<u8> {
         unsafe {
             // 检查是否有数据可读
-            if (self.read_reg(UART_LSR) & 0x01) != 0 {
+            if (self.read_reg(UART_LSR) & LSR_DR) != 0 {
                 Some(self.read_reg(UART_RBR))
             } else {
                 None
@@ -172,20 +222,47 @@ impl Uart {
         }
     }
 
+    /// 开启或关闭接收中断
+    pub fn set_rx_interrupt(&self, enabled: bool) {
+        unsafe {
+            self.write_reg(UART_IER, if enabled { IER_RDI } else { 0 });
+        }
+    }
+
     /// 读取寄存器
     unsafe fn read_reg(&self, offset: usize) -> u8 {
-        core::ptr::read_volatile((self.base_addr + offset) as *const u8)
+        match self.access {
+            RegisterAccess::Mmio { shift } => {
+                core::ptr::read_volatile((self.base_addr + (offset << shift)) as *const u8)
+            }
+            #[cfg(target_arch = "x86_64")]
+            RegisterAccess::Port => {
+                let value: u8;
+                core::arch::asm!("in al, dx", out("al") value, in("dx") (self.base_addr + offset) as u16,
+                    options(nomem, nostack, preserves_flags));
+                value
+            }
+        }
     }
 
     /// 写入寄存器
     unsafe fn write_reg(&self, offset: usize, value: u8) {
-        core::ptr::write_volatile((self.base_addr + offset) as *mut u8, value);
+        match self.access {
+            RegisterAccess::Mmio { shift } => {
+                core::ptr::write_volatile((self.base_addr + (offset << shift)) as *mut u8, value);
+            }
+            #[cfg(target_arch = "x86_64")]
+            RegisterAccess::Port => {
+                core::arch::asm!("out dx, al", in("dx") (self.base_addr + offset) as u16, in("al") value,
+                    options(nomem, nostack, preserves_flags));
+            }
+        }
     }
 }
 
 impl Write for Uart {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.write_str(s);
+        Uart::write_str(self, s);
         Ok(())
     }
 }
@@ -200,6 +277,10 @@ pub fn init_early_uart() -> Result<(), BootError> {
     
     // 保存到全局变量
     *EARLY_UART.lock() = Some(uart);
+
+    // 注册为内核控制台
+    console::register(&UART_CONSOLE).map_err(|_| BootError::DeviceInitializationFailed)?;
+    suspend::register_device(&UART_CONSOLE).map_err(|_| BootError::DeviceInitializationFailed)?;
     
     // 输出初始化成功信息
     early_print("Lilith OS - 早期UART初始化完成\n");
@@ -221,6 +302,117 @@ pub fn early_print_fmt(args: Arguments) {
     }
 }
 
+/// 早期输出原始字节（不要求是合法的UTF-8）
+pub fn early_write_bytes(bytes: &[u8]) {
+    if let Some(uart) = EARLY_UART.lock().as_ref() {
+        for &byte in bytes {
+            uart.write_byte(byte);
+        }
+    }
+}
+
+/// 早期串口作为内核控制台
+struct UartConsole;
+
+impl ConsoleDevice for UartConsole {
+    fn name(&self) -> &str {
+        "ttyS0"
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        if let Some(uart) = EARLY_UART.lock().as_ref() {
+            for &byte in bytes {
+                if byte == b'\n' {
+                    uart.write_byte(b'\r');
+                }
+                uart.write_byte(byte);
+            }
+        }
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        early_read_byte()
+    }
+}
+
+/// 睡眠前发送完缓冲的输出，唤醒后无需恢复（寄存器内容在挂起到空闲时保持）
+impl DevicePm for UartConsole {
+    fn name(&self) -> &str {
+        "ttyS0"
+    }
+
+    fn suspend(&self) -> Result<(), KernelError> {
+        if let Some(uart) = EARLY_UART.lock().as_ref() {
+            uart.flush();
+        }
+        Ok(())
+    }
+
+    fn resume(&self) {}
+}
+
+static UART_CONSOLE: UartConsole = UartConsole;
+
+/// 早期读取一个字节，没有数据时返回 `None`
+pub fn early_read_byte() -> Option<u8> {
+    EARLY_UART.lock().as_ref().and_then(|uart| uart.read_byte())
+}
+
+/// 串口接收中断：读出FIFO中的字符送入控制台终端
+///
+/// 输出时持有串口的锁，中断打断同一hart上的输出时不读取，
+/// 数据留在FIFO中，电平触发的中断会在输出结束后再次到来
+fn uart_irq(_irq: usize) {
+    let mut buf = [0u8; RX_BURST];
+    let mut len = 0;
+    if let Some(guard) = EARLY_UART.try_lock() {
+        if let Some(uart) = guard.as_ref() {
+            while len < RX_BURST {
+                let Some(byte) = uart.read_byte() else { break };
+                buf[len] = byte;
+                len += 1;
+            }
+        }
+    }
+    if len > 0 {
+        crate::fs::tty::receive(&buf[..len]);
+    }
+}
+
+/// 设备树中控制台串口的中断号
+fn console_irq() -> Option<usize> {
+    let fdt = fdt::fdt()?;
+    let base = EARLY_UART.lock().as_ref()?.base_addr;
+    // 节点名为 `serial@<基地址>`，compatible为 `ns16550a` 等
+    let device = fdt.devices().into_iter().find(|device| {
+        device.enabled
+            && device.compatible.starts_with("ns16550")
+            && device.path.ends_with(&alloc::format!("@{:x}", base))
+    })?;
+    fdt.property(&device.path, "interrupts")
+        .and_then(|value| value.get(..4))
+        .map(|cell| u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]]) as usize)
+}
+
+/// 中断系统就绪后开启控制台串口的接收中断
+///
+/// 设备树中找不到中断号时保持轮询。内核调试shell轮询串口读取命令，
+/// 启动参数含 `kshell` 时不开启，以免输入被送入终端
+pub fn uart_irq_init() -> Result<(), KernelError> {
+    if kshell::enabled() {
+        return Ok(());
+    }
+    let Some(irq) = console_irq() else {
+        return Ok(());
+    };
+    crate::arch::register_irq_handler(irq, uart_irq)?;
+    if let Some(uart) = EARLY_UART.lock().as_ref() {
+        uart.set_rx_interrupt(true);
+    }
+    crate::log_info!("串口接收中断已开启（中断号{}）", irq);
+    Ok(())
+}
+
 /// 紧急写入函数（用于panic处理）
 pub fn emergency_write_fmt(args: Arguments) {
     // 直接操作硬件，不使用锁
@@ -229,36 +421,36 @@ pub fn emergency_write_fmt(args: Arguments) {
     // 尝试快速初始化（可能失败，但不影响输出）
     let _ = uart.init();
     
-    // 格式化并输出
+    // 格式化并输出，同时记录到持久化的控制台环
     struct EmergencyWriter<'a>(&'a Uart);
-    
+
     impl<'a> Write for EmergencyWriter<'a> {
         fn write_str(&mut self, s: &str) -> fmt::Result {
             self.0.write_str(s);
+            crate::debug::pstore::console_write(s.as_bytes());
             Ok(())
         }
     }
-    
+
     let mut writer = EmergencyWriter(&uart);
     let _ = writer.write_fmt(args);
     uart.flush();
 }
 
-/// 早期调试宏
+/// 早期调试宏：以 `Info` 级别写入内核日志，并输出到控制台
 #[macro_export]
 macro_rules! early_println {
     () => {
-        $crate::boot::uart::early_print("\n")
+        $crate::klog::printk($crate::klog::LogLevel::Info, format_args!(""))
     };
     ($($arg:tt)*) => {
-        $crate::boot::uart::early_print_fmt(format_args!($($arg)*));
-        $crate::boot::uart::early_print("\n");
+        $crate::klog::printk($crate::klog::LogLevel::Info, format_args!($($arg)*));
     };
 }
 
 #[macro_export]
 macro_rules! early_print {
     ($($arg:tt)*) => {
-        $crate::boot::uart::early_print_fmt(format_args!($($arg)*));
+        $crate::klog::printk($crate::klog::LogLevel::Info, format_args!($($arg)*));
     };
-}
\ No newline at end of file
+}
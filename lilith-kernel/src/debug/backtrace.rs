@@ -0,0 +1,84 @@
+//! 调用栈回溯
+//!
+//! 内核以强制帧指针编译（见 `.cargo/config.toml`），每个栈帧的布局为：
+//!
+//! ```text
+//! fp - 8   返回地址（ra）
+//! fp - 16  调用者的帧指针
+//! ```
+//!
+//! 沿帧指针链向上遍历即可得到各级返回地址，不需要展开信息
+
+use super::kallsyms;
+use core::fmt::{self, Arguments};
+
+/// 最多回溯的层数
+const MAX_DEPTH: usize = 32;
+
+/// 相邻栈帧间的最大距离，超过时认为帧指针已损坏
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// 从帧指针 `fp` 开始回溯，对每个返回地址调用 `f`，`f` 返回 `false` 时停止
+///
+/// 帧指针必须8字节对齐且严格递增，否则认为已到达栈底或帧链已损坏
+pub fn walk(mut fp: usize, mut f: impl FnMut(usize) -> bool) {
+    for _ in 0..MAX_DEPTH {
+        if fp == 0 || fp % 8 != 0 || fp < 16 {
+            break;
+        }
+        let (ra, prev_fp) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if ra == 0 || !f(ra) {
+            break;
+        }
+        if prev_fp <= fp || prev_fp - fp > MAX_FRAME_SIZE {
+            break;
+        }
+        fp = prev_fp;
+    }
+}
+
+/// 返回地址所在的函数，显示为 `符号+偏移`，找不到符号时显示为 `?`
+pub struct Caller(pub usize);
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 返回地址指向调用指令之后，减一使其落在调用者内部
+        match kallsyms::lookup(self.0 - 1) {
+            Some(symbol) => write!(f, "{}+0x{:x}", symbol.name, symbol.offset + 1),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+/// 输出一个返回地址及其符号
+fn print_frame(print: fn(Arguments), depth: usize, ra: usize) {
+    print(format_args!("  #{:<2} 0x{:016x} {}\n", depth, ra, Caller(ra)));
+}
+
+/// 从指定帧指针开始输出调用栈
+pub fn print_backtrace_from(fp: usize, print: fn(Arguments)) {
+    print(format_args!("调用栈:\n"));
+    let mut depth = 0;
+    walk(fp, |ra| {
+        print_frame(print, depth, ra);
+        depth += 1;
+        true
+    });
+    if !kallsyms::is_available() {
+        print(format_args!("  （符号表未生成，运行 scripts/kallsyms.py 后可显示函数名）\n"));
+    }
+}
+
+/// 输出事先记录的调用栈，`trace` 中的0表示结束
+pub fn print_trace(trace: &[usize], print: fn(Arguments)) {
+    print(format_args!("调用栈:\n"));
+    for (depth, &ra) in trace.iter().take_while(|&&ra| ra != 0).enumerate() {
+        print_frame(print, depth, ra);
+    }
+}
+
+/// 输出当前调用栈
+#[inline(never)]
+pub fn print_backtrace(print: fn(Arguments)) {
+    print_backtrace_from(crate::arch::frame_pointer(), print);
+}
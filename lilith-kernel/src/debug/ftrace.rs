@@ -0,0 +1,377 @@
+//! 函数跟踪（ftrace）
+//!
+//! 本模块实现了基于编译器插桩的函数跟踪，用于分析调度与陷入路径上的延迟，包括：
+//! - `function`：记录每次函数调用的时间、函数与调用者
+//! - `function_graph`：另外记录函数返回的时间与耗时，按调用深度缩进显示
+//! - 每个hart一个环形缓冲区，写满后覆盖最旧的记录
+//! - /sys/kernel/tracing 下的控制文件：
+//!   - `current_tracer`：读取或选择跟踪器（nop、function、function_graph）
+//!   - `tracing_on`：写入1开始、写入0停止记录
+//!   - `trace`：按时间顺序输出所有hart的记录，写入任意内容清空缓冲区
+//!
+//! 内核须以 `ftrace` 特性并加上 `-Z instrument-mcount` 编译（`make ftrace`），否则不会产生任何记录。
+//! `function_graph` 会替换栈上的返回地址，跟踪期间的调用栈回溯中会出现 `return_to_handler`
+
+use crate::arch::{self, ftrace::return_to_handler};
+use crate::debug::kallsyms;
+use crate::error::KernelError;
+use crate::fs::kernfs;
+use crate::sched::MAX_HARTS;
+use crate::time::{self, NSEC_PER_SEC};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+/// 每个hart的环形缓冲区容量（记录数）
+const RING_SIZE: usize = 4096;
+
+/// 每个任务最多跟踪的嵌套返回层数，更深的调用只记录进入
+const RET_STACK_DEPTH: usize = 64;
+
+/// 跟踪器（`function` 为1，只在 `TRACER_NAMES` 中使用）
+const TRACER_NOP: u8 = 0;
+const TRACER_GRAPH: u8 = 2;
+
+/// 跟踪器名，下标为跟踪器编号
+const TRACER_NAMES: [&str; 3] = ["nop", "function", "function_graph"];
+
+/// 是否在记录（`mcount` 直接读取）
+pub static FTRACE_ENABLED: AtomicU8 = AtomicU8::new(0);
+
+/// 各hart是否正在处理跟踪事件（`mcount` 按tp索引），防止处理函数被自身的插桩重入
+#[allow(clippy::declare_interior_mutable_const)]
+const BUSY_INIT: AtomicU8 = AtomicU8::new(0);
+pub static FTRACE_BUSY: [AtomicU8; MAX_HARTS] = [BUSY_INIT; MAX_HARTS];
+
+/// 当前跟踪器
+static TRACER: AtomicU8 = AtomicU8::new(TRACER_NOP);
+
+/// 是否打开了 `tracing_on`
+static TRACING_ON: AtomicU8 = AtomicU8::new(1);
+
+/// 记录的种类
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// 函数进入
+    Entry,
+    /// 函数返回
+    Exit,
+}
+
+/// 一条跟踪记录
+#[derive(Clone, Copy)]
+struct Record {
+    /// 单调时间（纳秒）
+    time: u64,
+    /// 函数内的地址（`mcount` 的返回地址）
+    ip: usize,
+    /// 调用者的返回地址
+    parent: usize,
+    /// 函数耗时（纳秒），只对返回记录有效
+    duration: u64,
+    /// 调用深度，只对 `function_graph` 有效
+    depth: u16,
+    kind: Kind,
+}
+
+const EMPTY_RECORD: Record = Record {
+    time: 0,
+    ip: 0,
+    parent: 0,
+    duration: 0,
+    depth: 0,
+    kind: Kind::Entry,
+};
+
+/// hart的环形缓冲区，只由所属hart在处理跟踪事件时写入
+struct Ring {
+    records: UnsafeCell<Box<[Record]>>,
+    /// 写入过的记录总数
+    written: UnsafeCell<usize>,
+}
+
+// 写入只发生在所属hart上且不会重入；读取时可能看到正在写入的记录
+unsafe impl Sync for Ring {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const RING_INIT: AtomicPtr<Ring> = AtomicPtr::new(core::ptr::null_mut());
+
+/// 各hart的环形缓冲区，`ftrace_init` 中分配
+static RINGS: [AtomicPtr<Ring>; MAX_HARTS] = [RING_INIT; MAX_HARTS];
+
+/// 被替换的返回地址
+#[derive(Clone, Copy)]
+struct RetFrame {
+    /// 原返回地址
+    ret: usize,
+    /// 函数内的地址
+    ip: usize,
+    /// 进入时间
+    time: u64,
+}
+
+/// 任务的返回地址栈，随任务迁移，保存在任务控制块中
+pub struct RetStack {
+    depth: usize,
+    frames: [RetFrame; RET_STACK_DEPTH],
+}
+
+impl RetStack {
+    /// 空的返回地址栈
+    pub const fn new() -> Self {
+        Self {
+            depth: 0,
+            frames: [RetFrame { ret: 0, ip: 0, time: 0 }; RET_STACK_DEPTH],
+        }
+    }
+}
+
+impl Default for RetStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const RET_STACK_INIT: AtomicPtr<RetStack> = AtomicPtr::new(core::ptr::null_mut());
+
+/// 各hart上正在运行的任务的返回地址栈，任务切换期间为空
+static RET_STACKS: [AtomicPtr<RetStack>; MAX_HARTS] = [RET_STACK_INIT; MAX_HARTS];
+
+/// 任务切换前调用：切换期间不替换返回地址
+#[inline(always)]
+pub fn switch_out() {
+    RET_STACKS[arch::hart_id()].store(core::ptr::null_mut(), Ordering::Release);
+}
+
+/// 任务开始在本hart上运行时调用，之后的返回记录写入该任务的返回地址栈
+#[inline(always)]
+pub fn switch_in(ret_stack: *mut RetStack) {
+    RET_STACKS[arch::hart_id()].store(ret_stack, Ordering::Release);
+}
+
+/// 向本hart的环形缓冲区追加一条记录
+fn record(hart: usize, record: Record) {
+    let ring = RINGS[hart].load(Ordering::Acquire);
+    if ring.is_null() {
+        return;
+    }
+    unsafe {
+        let ring = &*ring;
+        let written = &mut *ring.written.get();
+        (*ring.records.get())[*written % RING_SIZE] = record;
+        *written += 1;
+    }
+}
+
+/// 函数进入事件，由 `mcount` 调用，`fp` 为被跟踪函数的帧指针
+pub extern "C" fn ftrace_entry(ip: usize, fp: usize) {
+    let hart = arch::hart_id();
+    let now = time::monotonic_ns();
+    let ra_slot = (fp - 8) as *mut usize;
+    let parent = unsafe { *ra_slot };
+
+    let mut depth = 0;
+    if TRACER.load(Ordering::Relaxed) == TRACER_GRAPH {
+        let ret_stack = RET_STACKS[hart].load(Ordering::Acquire);
+        if !ret_stack.is_null() {
+            let ret_stack = unsafe { &mut *ret_stack };
+            depth = ret_stack.depth;
+            if depth < RET_STACK_DEPTH {
+                ret_stack.frames[depth] = RetFrame { ret: parent, ip, time: now };
+                ret_stack.depth += 1;
+                unsafe {
+                    *ra_slot = return_to_handler as *const () as usize;
+                }
+            }
+        }
+    }
+
+    record(
+        hart,
+        Record {
+            time: now,
+            ip,
+            parent,
+            duration: 0,
+            depth: depth as u16,
+            kind: Kind::Entry,
+        },
+    );
+}
+
+/// 函数返回事件，由 `return_to_handler` 调用，返回原来的返回地址
+pub extern "C" fn ftrace_return() -> usize {
+    let hart = arch::hart_id();
+    let ret_stack = RET_STACKS[hart].load(Ordering::Acquire);
+    if ret_stack.is_null() {
+        panic!("ftrace: hart {} 上的返回地址栈丢失", hart);
+    }
+    let ret_stack = unsafe { &mut *ret_stack };
+    ret_stack.depth -= 1;
+    let frame = ret_stack.frames[ret_stack.depth];
+
+    if FTRACE_ENABLED.load(Ordering::Relaxed) != 0 {
+        let now = time::monotonic_ns();
+        record(
+            hart,
+            Record {
+                time: now,
+                ip: frame.ip,
+                parent: frame.ret,
+                duration: now.saturating_sub(frame.time),
+                depth: ret_stack.depth as u16,
+                kind: Kind::Exit,
+            },
+        );
+    }
+    frame.ret
+}
+
+/// 按 `tracing_on` 与当前跟踪器更新记录开关
+fn update_enabled() {
+    let enabled = TRACING_ON.load(Ordering::Relaxed) != 0 && TRACER.load(Ordering::Relaxed) != TRACER_NOP;
+    FTRACE_ENABLED.store(u8::from(enabled), Ordering::Release);
+}
+
+/// 地址所在的函数名
+fn symbol_name(addr: usize) -> &'static str {
+    kallsyms::lookup(addr).map_or("?", |symbol| symbol.name)
+}
+
+/// 输出时间戳（秒.微秒）
+fn write_time(out: &mut String, ns: u64) {
+    let _ = write!(out, "{:>6}.{:06}", ns / NSEC_PER_SEC, ns % NSEC_PER_SEC / 1000);
+}
+
+/// 生成 `trace` 文件内容
+fn read_trace() -> String {
+    let tracer = TRACER.load(Ordering::Relaxed);
+    let mut records: Vec<(usize, Record)> = Vec::new();
+    for (hart, ring) in RINGS.iter().enumerate() {
+        let ring = ring.load(Ordering::Acquire);
+        if ring.is_null() {
+            continue;
+        }
+        let (all, written) = unsafe { (&*(*ring).records.get(), *(*ring).written.get()) };
+        let count = written.min(RING_SIZE);
+        records.extend((written - count..written).map(|index| (hart, all[index % RING_SIZE])));
+    }
+    records.sort_by_key(|(_, record)| record.time);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# tracer: {}", TRACER_NAMES[tracer as usize]);
+    let _ = writeln!(out, "# entries: {}", records.len());
+    if tracer == TRACER_GRAPH {
+        let _ = writeln!(out, "#  CPU   TIMESTAMP      DURATION      FUNCTION");
+    } else {
+        let _ = writeln!(out, "#  CPU   TIMESTAMP      FUNCTION");
+    }
+
+    for (hart, record) in records {
+        let _ = write!(out, "  [{:03}] ", hart);
+        write_time(&mut out, record.time);
+        if tracer == TRACER_GRAPH {
+            let indent = record.depth as usize * 2;
+            match record.kind {
+                Kind::Entry => {
+                    let _ = writeln!(out, " |              | {:indent$}{}() {{", "", symbol_name(record.ip));
+                }
+                Kind::Exit => {
+                    let _ = writeln!(
+                        out,
+                        " | {:>8}.{:03} us | {:indent$}}} /* {} */",
+                        record.duration / 1000,
+                        record.duration % 1000,
+                        "",
+                        symbol_name(record.ip)
+                    );
+                }
+            }
+        } else if record.kind == Kind::Entry {
+            let _ = writeln!(out, ": {} <-{}", symbol_name(record.ip), symbol_name(record.parent.saturating_sub(1)));
+        }
+    }
+    out
+}
+
+/// 清空所有hart的环形缓冲区，须在停止记录后调用
+fn clear_trace() {
+    for ring in RINGS.iter() {
+        let ring = ring.load(Ordering::Acquire);
+        if !ring.is_null() {
+            unsafe {
+                *(*ring).written.get() = 0;
+            }
+        }
+    }
+}
+
+/// 分配环形缓冲区，注册 /sys/kernel/tracing 下的控制文件
+pub fn ftrace_init() -> Result<(), KernelError> {
+    for ring in RINGS.iter() {
+        let buffer = Box::new(Ring {
+            records: UnsafeCell::new(alloc::vec![EMPTY_RECORD; RING_SIZE].into_boxed_slice()),
+            written: UnsafeCell::new(0),
+        });
+        ring.store(Box::into_raw(buffer), Ordering::Release);
+    }
+
+    kernfs::register(
+        "/sys/kernel/tracing/available_tracers",
+        Some(Box::new(|| {
+            let mut list = TRACER_NAMES.join(" ");
+            list.push('\n');
+            list
+        })),
+        None,
+    )?;
+    kernfs::register(
+        "/sys/kernel/tracing/current_tracer",
+        Some(Box::new(|| {
+            let mut name = String::from(TRACER_NAMES[TRACER.load(Ordering::Relaxed) as usize]);
+            name.push('\n');
+            name
+        })),
+        Some(Box::new(|data| {
+            let tracer = TRACER_NAMES
+                .iter()
+                .position(|&name| name == data.trim())
+                .ok_or(KernelError::InvalidArgument)?;
+            // 切换跟踪器时丢弃旧的记录
+            FTRACE_ENABLED.store(0, Ordering::Release);
+            clear_trace();
+            TRACER.store(tracer as u8, Ordering::Relaxed);
+            update_enabled();
+            Ok(())
+        })),
+    )?;
+    kernfs::register(
+        "/sys/kernel/tracing/tracing_on",
+        Some(Box::new(|| alloc::format!("{}\n", TRACING_ON.load(Ordering::Relaxed)))),
+        Some(Box::new(|data| {
+            let on = match data.trim() {
+                "0" => 0,
+                "1" => 1,
+                _ => return Err(KernelError::InvalidArgument),
+            };
+            TRACING_ON.store(on, Ordering::Relaxed);
+            update_enabled();
+            Ok(())
+        })),
+    )?;
+    kernfs::register(
+        "/sys/kernel/tracing/trace",
+        Some(Box::new(read_trace)),
+        Some(Box::new(|_| {
+            let enabled = FTRACE_ENABLED.swap(0, Ordering::AcqRel);
+            clear_trace();
+            FTRACE_ENABLED.store(enabled, Ordering::Release);
+            Ok(())
+        })),
+    )?;
+    Ok(())
+}
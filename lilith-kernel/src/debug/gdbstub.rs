@@ -0,0 +1,658 @@
+//! GDB远程调试桩（gdbstub）
+//!
+//! 本模块实现了在第二个串口上使用GDB远程串行协议调试内核本身，包括：
+//! - 启动参数 `gdbstub=<串口基地址>` 开启调试桩，`gdbwait` 使内核在初始化时停下等待GDB连接
+//! - 内核态的断点异常进入调试桩，由GDB控制何时继续
+//! - 读写通用寄存器与pc（g/G/p/P）
+//! - 读写内存（m/M），访问无效地址返回错误而不会使内核崩溃
+//! - 软件断点（Z0/z0）：把指令替换为 `ebreak` 或 `c.ebreak`
+//! - 单步（s）：解码当前指令得到所有可能的下一条指令地址，在那里放置临时断点
+//! - 继续（c）、分离（D）与结束会话（k）
+//!
+//! 调试桩只停下触发断点的hart，其他hart继续运行
+
+use crate::arch::TrapFrame;
+use crate::boot::cmdline;
+use crate::boot::uart::{Uart, UartConfig};
+use crate::error::KernelError;
+use crate::mm::protect::text_poke;
+use crate::mm::uaccess::copy_from_kernel_nofault;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use spin::Mutex;
+
+/// 报告给GDB的停止原因：SIGTRAP
+const STOP_REPLY: &str = "S05";
+
+/// 寄存器数：x0-x31与pc
+const NUM_REGS: usize = 33;
+/// pc的寄存器编号
+const REG_PC: usize = 32;
+
+/// 接收数据包的最大长度
+const MAX_PACKET_SIZE: usize = 4096;
+
+/// 发送数据包时等待确认的最多重传次数
+const MAX_RETRANSMITS: usize = 8;
+
+/// `ebreak` 指令
+const EBREAK: u32 = 0x0010_0073;
+/// `c.ebreak` 指令
+const C_EBREAK: u16 = 0x9002;
+
+/// 内存错误的回复（EFAULT）
+const ERR_FAULT: &str = "E0e";
+/// 参数错误的回复（EINVAL）
+const ERR_INVALID: &str = "E16";
+
+/// 已插入的断点：被替换的原始指令
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    /// 原始指令字节
+    orig: [u8; 4],
+    /// 指令长度（2或4）
+    len: usize,
+}
+
+/// 调试桩状态
+struct GdbStub {
+    /// 与GDB通信的串口
+    uart: Uart,
+    /// GDB设置的断点
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    /// 单步使用的临时断点
+    step_breakpoints: Vec<(usize, Breakpoint)>,
+    /// GDB是否已连接（尚未分离）
+    attached: bool,
+}
+
+/// 恢复执行的方式
+enum Resume {
+    /// 继续运行
+    Continue,
+    /// 执行一条指令后重新停下
+    Step,
+}
+
+/// 调试桩，未开启时为 `None`
+static STUB: Mutex<Option<GdbStub>> = Mutex::new(None);
+
+/// 十六进制数字的值
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// 解析十六进制整数
+fn parse_hex(text: &[u8]) -> Option<usize> {
+    if text.is_empty() || text.len() > 16 {
+        return None;
+    }
+    text.iter().try_fold(0usize, |value, &byte| Some((value << 4) | hex_value(byte)? as usize))
+}
+
+/// 解析十六进制编码的字节
+fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| Some((hex_value(pair[0])? << 4) | hex_value(pair[1])?))
+        .collect()
+}
+
+/// 以十六进制编码字节
+fn encode_hex(out: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
+
+/// 解析 `addr,len` 形式的参数
+fn parse_addr_len(args: &[u8]) -> Option<(usize, usize)> {
+    let comma = args.iter().position(|&byte| byte == b',')?;
+    Some((parse_hex(&args[..comma])?, parse_hex(&args[comma + 1..])?))
+}
+
+/// 读取寄存器，x0恒为0
+fn read_reg(frame: &TrapFrame, reg: usize) -> usize {
+    match reg {
+        0 => 0,
+        REG_PC => frame.sepc,
+        _ => frame.regs[reg],
+    }
+}
+
+/// 写入寄存器，忽略对x0的写入
+fn write_reg(frame: &mut TrapFrame, reg: usize, value: usize) {
+    match reg {
+        0 => {}
+        REG_PC => frame.sepc = value,
+        _ => frame.regs[reg] = value,
+    }
+}
+
+/// 读取 `addr` 处指令的长度（2或4），地址无效时返回 `None`
+fn insn_len(addr: usize) -> Option<usize> {
+    let mut low = [0u8; 2];
+    copy_from_kernel_nofault(&mut low, addr).ok()?;
+    Some(if low[0] & 0b11 == 0b11 { 4 } else { 2 })
+}
+
+/// 读取 `addr` 处的指令，返回指令与长度
+fn read_insn(addr: usize) -> Option<(u32, usize)> {
+    let len = insn_len(addr)?;
+    let mut bytes = [0u8; 4];
+    copy_from_kernel_nofault(&mut bytes[..len], addr).ok()?;
+    Some((u32::from_le_bytes(bytes), len))
+}
+
+/// `addr` 处是否为断点指令，是则返回其长度
+pub(super) fn ebreak_at(addr: usize) -> Option<usize> {
+    match read_insn(addr)? {
+        (EBREAK, 4) => Some(4),
+        (insn, 2) if insn as u16 == C_EBREAK => Some(2),
+        _ => None,
+    }
+}
+
+/// 取 `insn` 的第 `lo` 位起的 `len` 位
+fn bits(insn: u32, lo: u32, len: u32) -> usize {
+    ((insn >> lo) & ((1 << len) - 1)) as usize
+}
+
+/// 将 `width` 位的立即数符号扩展后加到 `base` 上
+fn add_signed(base: usize, imm: usize, width: u32) -> usize {
+    let shift = usize::BITS - width;
+    base.wrapping_add((((imm << shift) as isize) >> shift) as usize)
+}
+
+/// 当前指令执行后所有可能的pc
+///
+/// 只需区分跳转与分支，其他指令都顺序执行；条件分支两个方向都可能
+fn next_pcs(frame: &TrapFrame) -> Option<Vec<usize>> {
+    let pc = frame.sepc;
+    let (insn, len) = read_insn(pc)?;
+    let fallthrough = pc.wrapping_add(len);
+    let mut targets = Vec::with_capacity(2);
+
+    if len == 4 {
+        match insn & 0x7f {
+            // jal
+            0x6f => {
+                let imm = (bits(insn, 31, 1) << 20)
+                    | (bits(insn, 21, 10) << 1)
+                    | (bits(insn, 20, 1) << 11)
+                    | (bits(insn, 12, 8) << 12);
+                targets.push(add_signed(pc, imm, 21));
+            }
+            // jalr
+            0x67 => {
+                let base = read_reg(frame, bits(insn, 15, 5));
+                targets.push(add_signed(base, bits(insn, 20, 12), 12) & !1);
+            }
+            // beq/bne/blt/bge/bltu/bgeu
+            0x63 => {
+                let imm = (bits(insn, 31, 1) << 12)
+                    | (bits(insn, 25, 6) << 5)
+                    | (bits(insn, 8, 4) << 1)
+                    | (bits(insn, 7, 1) << 11);
+                targets.push(add_signed(pc, imm, 13));
+                targets.push(fallthrough);
+            }
+            _ => targets.push(fallthrough),
+        }
+    } else {
+        let quadrant = insn & 0b11;
+        let funct3 = bits(insn, 13, 3);
+        match (quadrant, funct3) {
+            // c.j
+            (0b01, 0b101) => {
+                let imm = (bits(insn, 12, 1) << 11)
+                    | (bits(insn, 11, 1) << 4)
+                    | (bits(insn, 9, 2) << 8)
+                    | (bits(insn, 8, 1) << 10)
+                    | (bits(insn, 7, 1) << 6)
+                    | (bits(insn, 6, 1) << 7)
+                    | (bits(insn, 3, 3) << 1)
+                    | (bits(insn, 2, 1) << 5);
+                targets.push(add_signed(pc, imm, 12));
+            }
+            // c.beqz/c.bnez
+            (0b01, 0b110) | (0b01, 0b111) => {
+                let imm = (bits(insn, 12, 1) << 8)
+                    | (bits(insn, 10, 2) << 3)
+                    | (bits(insn, 5, 2) << 6)
+                    | (bits(insn, 3, 2) << 1)
+                    | (bits(insn, 2, 1) << 5);
+                targets.push(add_signed(pc, imm, 9));
+                targets.push(fallthrough);
+            }
+            // c.jr/c.jalr：rs2为0且rs1不为0
+            (0b10, 0b100) if bits(insn, 2, 5) == 0 && bits(insn, 7, 5) != 0 => {
+                targets.push(read_reg(frame, bits(insn, 7, 5)) & !1);
+            }
+            _ => targets.push(fallthrough),
+        }
+    }
+    targets.dedup();
+    Some(targets)
+}
+
+impl GdbStub {
+    /// 阻塞读取一个字节
+    fn read_byte(&self) -> u8 {
+        loop {
+            if let Some(byte) = self.uart.read_byte() {
+                return byte;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// 接收一个数据包，校验和错误的包请求重传
+    fn receive_packet(&self) -> Vec<u8> {
+        loop {
+            // 跳过确认字符与包外的中断请求
+            while self.read_byte() != b'$' {}
+
+            let mut data = Vec::new();
+            let mut checksum: u8 = 0;
+            let mut restart = false;
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    // 上一个包不完整，从新的包开始
+                    b'$' => {
+                        restart = true;
+                        break;
+                    }
+                    byte => {
+                        checksum = checksum.wrapping_add(byte);
+                        if data.len() < MAX_PACKET_SIZE {
+                            data.push(byte);
+                        }
+                    }
+                }
+            }
+            if restart {
+                continue;
+            }
+
+            let expected = [self.read_byte(), self.read_byte()];
+            if parse_hex(&expected) == Some(checksum as usize) {
+                self.uart.write_byte(b'+');
+                return data;
+            }
+            self.uart.write_byte(b'-');
+        }
+    }
+
+    /// 发送一个数据包，等待GDB确认
+    fn send_packet(&self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        for _ in 0..MAX_RETRANSMITS {
+            self.uart.write_byte(b'$');
+            self.uart.write_str(data);
+            self.uart.write_byte(b'#');
+            let mut trailer = String::new();
+            let _ = write!(trailer, "{:02x}", checksum);
+            self.uart.write_str(&trailer);
+            self.uart.flush();
+
+            match self.read_byte() {
+                b'+' => return,
+                b'-' => continue,
+                // GDB没有回应确认而直接发来新包时不再重传
+                _ => return,
+            }
+        }
+    }
+
+    /// 在 `addr` 处写入断点指令，返回被替换的原始指令
+    fn patch(addr: usize, len: usize) -> Result<Breakpoint, &'static str> {
+        let mut orig = [0u8; 4];
+        copy_from_kernel_nofault(&mut orig[..len], addr).map_err(|_| ERR_FAULT)?;
+        let result = if len == 2 {
+            text_poke(addr, &C_EBREAK.to_le_bytes())
+        } else {
+            text_poke(addr, &EBREAK.to_le_bytes())
+        };
+        result.map_err(|_| ERR_FAULT)?;
+        Ok(Breakpoint { orig, len })
+    }
+
+    /// 恢复断点处的原始指令
+    fn unpatch(addr: usize, breakpoint: &Breakpoint) {
+        let _ = text_poke(addr, &breakpoint.orig[..breakpoint.len]);
+    }
+
+    /// 插入GDB断点，`kind` 为断点指令的长度
+    fn insert_breakpoint(&mut self, addr: usize, kind: usize) -> Result<(), &'static str> {
+        if kind != 2 && kind != 4 {
+            return Err(ERR_INVALID);
+        }
+        if self.breakpoints.contains_key(&addr) {
+            return Ok(());
+        }
+        let breakpoint = Self::patch(addr, kind)?;
+        self.breakpoints.insert(addr, breakpoint);
+        Ok(())
+    }
+
+    /// 删除GDB断点
+    fn remove_breakpoint(&mut self, addr: usize) {
+        if let Some(breakpoint) = self.breakpoints.remove(&addr) {
+            Self::unpatch(addr, &breakpoint);
+        }
+    }
+
+    /// 删除全部GDB断点
+    fn remove_all_breakpoints(&mut self) {
+        while let Some((addr, breakpoint)) = self.breakpoints.pop_first() {
+            Self::unpatch(addr, &breakpoint);
+        }
+    }
+
+    /// 删除单步的临时断点
+    fn clear_step_breakpoints(&mut self) {
+        while let Some((addr, breakpoint)) = self.step_breakpoints.pop() {
+            Self::unpatch(addr, &breakpoint);
+        }
+    }
+
+    /// 在当前指令的所有后继处放置临时断点
+    fn set_step_breakpoints(&mut self, frame: &TrapFrame) -> Result<(), &'static str> {
+        let targets = next_pcs(frame).ok_or(ERR_FAULT)?;
+        for target in targets {
+            // 已有GDB断点的地址执行到时同样会停下
+            if self.breakpoints.contains_key(&target) {
+                continue;
+            }
+            let len = insn_len(target).ok_or(ERR_FAULT)?;
+            match Self::patch(target, len) {
+                Ok(breakpoint) => self.step_breakpoints.push((target, breakpoint)),
+                Err(err) => {
+                    self.clear_step_breakpoints();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// g：读取全部寄存器
+    fn read_registers(frame: &TrapFrame) -> String {
+        let mut reply = String::with_capacity(NUM_REGS * 16);
+        for reg in 0..NUM_REGS {
+            encode_hex(&mut reply, &read_reg(frame, reg).to_le_bytes());
+        }
+        reply
+    }
+
+    /// G：写入全部寄存器
+    fn write_registers(frame: &mut TrapFrame, args: &[u8]) -> &'static str {
+        let Some(bytes) = decode_hex(args) else {
+            return ERR_INVALID;
+        };
+        if bytes.len() < NUM_REGS * 8 {
+            return ERR_INVALID;
+        }
+        for (reg, chunk) in bytes.chunks_exact(8).take(NUM_REGS).enumerate() {
+            let mut value = [0u8; 8];
+            value.copy_from_slice(chunk);
+            write_reg(frame, reg, usize::from_le_bytes(value));
+        }
+        "OK"
+    }
+
+    /// p：读取一个寄存器
+    fn read_register(frame: &TrapFrame, args: &[u8]) -> String {
+        match parse_hex(args) {
+            Some(reg) if reg < NUM_REGS => {
+                let mut reply = String::new();
+                encode_hex(&mut reply, &read_reg(frame, reg).to_le_bytes());
+                reply
+            }
+            _ => String::from(ERR_INVALID),
+        }
+    }
+
+    /// P：写入一个寄存器
+    fn write_register(frame: &mut TrapFrame, args: &[u8]) -> &'static str {
+        let Some(eq) = args.iter().position(|&byte| byte == b'=') else {
+            return ERR_INVALID;
+        };
+        let reg = parse_hex(&args[..eq]);
+        let value = decode_hex(&args[eq + 1..]);
+        match (reg, value) {
+            (Some(reg), Some(value)) if reg < NUM_REGS && value.len() == 8 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&value);
+                write_reg(frame, reg, usize::from_le_bytes(bytes));
+                "OK"
+            }
+            _ => ERR_INVALID,
+        }
+    }
+
+    /// m：读取内存
+    fn read_memory(args: &[u8]) -> String {
+        let Some((addr, len)) = parse_addr_len(args) else {
+            return String::from(ERR_INVALID);
+        };
+        let mut bytes = alloc::vec![0u8; len.min(MAX_PACKET_SIZE / 2)];
+        if copy_from_kernel_nofault(&mut bytes, addr).is_err() {
+            return String::from(ERR_FAULT);
+        }
+        let mut reply = String::with_capacity(bytes.len() * 2);
+        encode_hex(&mut reply, &bytes);
+        reply
+    }
+
+    /// M：写入内存
+    fn write_memory(args: &[u8]) -> &'static str {
+        let Some(colon) = args.iter().position(|&byte| byte == b':') else {
+            return ERR_INVALID;
+        };
+        let (Some((addr, len)), Some(bytes)) = (parse_addr_len(&args[..colon]), decode_hex(&args[colon + 1..])) else {
+            return ERR_INVALID;
+        };
+        if bytes.len() != len {
+            return ERR_INVALID;
+        }
+        // 写入的可能是代码
+        if text_poke(addr, &bytes).is_err() {
+            return ERR_FAULT;
+        }
+        "OK"
+    }
+
+    /// 处理来自GDB的请求，直到GDB要求恢复执行
+    ///
+    /// `skip` 为pc处编译进内核的断点指令的长度，恢复时越过它
+    fn session(&mut self, frame: &mut TrapFrame, skip: usize) {
+        if self.attached {
+            self.send_packet(STOP_REPLY);
+        }
+        self.attached = true;
+        let stop_pc = frame.sepc;
+
+        loop {
+            let packet = self.receive_packet();
+            let Some((&command, args)) = packet.split_first() else {
+                continue;
+            };
+            let resume = match command {
+                b'?' => {
+                    self.send_packet(STOP_REPLY);
+                    None
+                }
+                b'g' => {
+                    self.send_packet(&Self::read_registers(frame));
+                    None
+                }
+                b'G' => {
+                    self.send_packet(Self::write_registers(frame, args));
+                    None
+                }
+                b'p' => {
+                    self.send_packet(&Self::read_register(frame, args));
+                    None
+                }
+                b'P' => {
+                    self.send_packet(Self::write_register(frame, args));
+                    None
+                }
+                b'm' => {
+                    self.send_packet(&Self::read_memory(args));
+                    None
+                }
+                b'M' => {
+                    self.send_packet(Self::write_memory(args));
+                    None
+                }
+                b'c' | b's' => {
+                    if let Some(addr) = parse_hex(args) {
+                        frame.sepc = addr;
+                    }
+                    Some(if command == b'c' { Resume::Continue } else { Resume::Step })
+                }
+                b'Z' | b'z' => {
+                    let reply = match args.strip_prefix(b"0,").and_then(parse_addr_len) {
+                        Some((addr, kind)) if command == b'Z' => match self.insert_breakpoint(addr, kind) {
+                            Ok(()) => "OK",
+                            Err(err) => err,
+                        },
+                        Some((addr, _)) => {
+                            self.remove_breakpoint(addr);
+                            "OK"
+                        }
+                        // 只支持软件断点
+                        None => "",
+                    };
+                    self.send_packet(reply);
+                    None
+                }
+                b'D' => {
+                    self.send_packet("OK");
+                    self.remove_all_breakpoints();
+                    self.attached = false;
+                    Some(Resume::Continue)
+                }
+                b'k' => {
+                    self.remove_all_breakpoints();
+                    self.attached = false;
+                    Some(Resume::Continue)
+                }
+                b'H' => {
+                    self.send_packet("OK");
+                    None
+                }
+                b'q' if packet.starts_with(b"qSupported") => {
+                    let mut reply = String::new();
+                    let _ = write!(reply, "PacketSize={:x}", MAX_PACKET_SIZE);
+                    self.send_packet(&reply);
+                    None
+                }
+                b'q' if packet.as_slice() == b"qAttached" => {
+                    self.send_packet("1");
+                    None
+                }
+                // 不支持的请求回复空包
+                _ => {
+                    self.send_packet("");
+                    None
+                }
+            };
+
+            // 仍停在编译进内核的断点上时越过它
+            let skip_here = skip != 0 && frame.sepc == stop_pc;
+            match resume {
+                None => {}
+                Some(Resume::Continue) => {
+                    if skip_here {
+                        frame.sepc += skip;
+                    }
+                    return;
+                }
+                Some(Resume::Step) if skip_here => {
+                    // 断点指令本身不做任何事，单步只需越过它
+                    frame.sepc += skip;
+                    self.send_packet(STOP_REPLY);
+                }
+                Some(Resume::Step) => match self.set_step_breakpoints(frame) {
+                    Ok(()) => return,
+                    Err(err) => self.send_packet(err),
+                },
+            }
+        }
+    }
+}
+
+/// 内核态断点异常的处理入口，返回异常是否已由调试桩处理
+pub fn handle_breakpoint(frame: &mut TrapFrame) -> bool {
+    let mut stub = STUB.lock();
+    let Some(stub) = stub.as_mut() else {
+        return false;
+    };
+    let pc = frame.sepc;
+
+    let stepped = stub.step_breakpoints.iter().any(|&(addr, _)| addr == pc);
+    stub.clear_step_breakpoints();
+    let skip = if stepped || stub.breakpoints.contains_key(&pc) {
+        0
+    } else {
+        match ebreak_at(pc) {
+            // 编译进内核的断点指令
+            Some(len) => len,
+            // 断点已被其他hart删除，重新执行原始指令
+            None => return true,
+        }
+    };
+
+    // GDB分离后只有编译进内核的断点会使调试桩重新等待连接
+    stub.session(frame, skip);
+    true
+}
+
+/// 执行一条断点指令，进入调试桩
+#[inline(always)]
+pub fn breakpoint() {
+    unsafe {
+        core::arch::asm!("ebreak");
+    }
+}
+
+/// 按启动参数开启调试桩
+pub fn gdbstub_init() -> Result<(), KernelError> {
+    let Some(value) = cmdline::get("gdbstub") else {
+        return Ok(());
+    };
+    let base_addr = parse_hex(value.trim_start_matches("0x").as_bytes()).ok_or(KernelError::InvalidArgument)?;
+
+    let uart = Uart::new(UartConfig {
+        base_addr,
+        ..Default::default()
+    });
+    uart.init().map_err(|_| KernelError::DeviceError)?;
+    *STUB.lock() = Some(GdbStub {
+        uart,
+        breakpoints: BTreeMap::new(),
+        step_breakpoints: Vec::new(),
+        attached: false,
+    });
+    crate::log_info!("gdbstub已在串口 0x{:x} 上开启", base_addr);
+
+    if cmdline::has("gdbwait") {
+        crate::log_info!("等待GDB连接...");
+        breakpoint();
+    }
+    Ok(())
+}
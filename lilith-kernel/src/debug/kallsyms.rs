@@ -0,0 +1,131 @@
+//! 内核符号表（kallsyms）
+//!
+//! 链接时在 `.kallsyms` 段中预留固定大小的空白区域，链接完成后由
+//! `scripts/kallsyms.py` 读取内核映像的符号，按地址排序后原地写入该区域。
+//! 区域大小不变，因此写入符号表不会改变任何代码或数据的地址。
+//!
+//! 区域布局（小端）：
+//!
+//! ```text
+//! +0   magic: u32 = "KSYM"   count: u32   names_len: u32   reserved: u32
+//! +16  [addr: u64, name_off: u32, name_len: u32] * count（按地址升序）
+//! ...  names: [u8; names_len]（拼接的符号名，不含结尾0）
+//! ```
+
+use core::cell::UnsafeCell;
+use core::mem::size_of;
+use core::ptr;
+
+/// 预留的符号表大小，生成的符号表超过时脚本报错
+pub const KALLSYMS_SIZE: usize = 256 * 1024;
+
+/// 符号表魔数 "KSYM"
+const KALLSYMS_MAGIC: u32 = u32::from_le_bytes(*b"KSYM");
+
+/// 符号表头部
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Header {
+    magic: u32,
+    count: u32,
+    names_len: u32,
+    _reserved: u32,
+}
+
+/// 符号项
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Entry {
+    addr: u64,
+    name_off: u32,
+    name_len: u32,
+}
+
+/// 预留的符号表区域
+///
+/// 内容在链接后才写入：内部可变且对外可见，编译器不能把读取折叠为初始值
+#[repr(C, align(8))]
+pub struct KallsymsArea(UnsafeCell<[u8; KALLSYMS_SIZE]>);
+
+// 运行时只读
+unsafe impl Sync for KallsymsArea {}
+
+#[no_mangle]
+#[used]
+#[link_section = ".kallsyms"]
+pub static KALLSYMS: KallsymsArea = KallsymsArea(UnsafeCell::new([0; KALLSYMS_SIZE]));
+
+/// 解析后的符号
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    /// 符号名（已去掉Rust的哈希后缀）
+    pub name: &'static str,
+    /// 符号起始地址
+    pub addr: usize,
+    /// 查询地址相对符号起始的偏移
+    pub offset: usize,
+}
+
+/// 符号表起始地址
+fn base() -> *const u8 {
+    KALLSYMS.0.get() as *const u8
+}
+
+/// 读取头部，符号表未生成时返回 `None`
+fn header() -> Option<Header> {
+    let header = unsafe { ptr::read_volatile(base() as *const Header) };
+    let entries_end = size_of::<Header>() + header.count as usize * size_of::<Entry>();
+    let valid = header.magic == KALLSYMS_MAGIC && entries_end + header.names_len as usize <= KALLSYMS_SIZE;
+    valid.then_some(header)
+}
+
+/// 读取第 `index` 个符号项
+fn entry(index: usize) -> Entry {
+    unsafe { ptr::read_volatile((base().add(size_of::<Header>()) as *const Entry).add(index)) }
+}
+
+/// 符号表是否已生成
+pub fn is_available() -> bool {
+    header().is_some()
+}
+
+/// 符号数量
+pub fn count() -> usize {
+    header().map_or(0, |header| header.count as usize)
+}
+
+/// 查找包含 `addr` 的符号（起始地址不大于 `addr` 的最后一个符号）
+///
+/// 符号表中是链接地址，内核被KASLR移动后按偏移换算
+pub fn lookup(addr: usize) -> Option<Symbol> {
+    let header = header()?;
+    let count = header.count as usize;
+    let kaslr_offset = crate::boot::kaslr::kaslr_offset();
+    let addr = addr.wrapping_sub(kaslr_offset);
+
+    // 二分查找第一个起始地址大于addr的符号
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = (low + high) / 2;
+        if entry(mid).addr as usize <= addr {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    if low == 0 {
+        return None;
+    }
+
+    let found = entry(low - 1);
+    if found.name_off as usize + found.name_len as usize > header.names_len as usize {
+        return None;
+    }
+    let names = unsafe { base().add(size_of::<Header>() + count * size_of::<Entry>()) };
+    let bytes = unsafe { core::slice::from_raw_parts(names.add(found.name_off as usize), found.name_len as usize) };
+    Some(Symbol {
+        name: core::str::from_utf8(bytes).unwrap_or("?"),
+        addr: found.addr as usize + kaslr_offset,
+        offset: addr - found.addr as usize,
+    })
+}
@@ -0,0 +1,146 @@
+//! 内核调试shell（kshell）
+//!
+//! 启动参数含 `kshell` 时，内核在初始化完成后创建一个内核任务，在控制台串口上提供交互式命令，
+//! 不依赖任何用户程序，用于硬件适配与早期调试：
+//! - `ps`：任务列表（任务号、进程号、进程组、状态与名字）
+//! - `free`：物理内存总量、可用量与用户地址空间的映射总量
+//! - `lsdev`：设备树中的设备节点及其状态
+//! - `md <地址> [长度]`、`mw <地址> <值> [宽度]`：显示与写入内存，同断点监视器
+//! - `mount`：已挂载的文件系统
+//! - `dmesg`：内核日志缓冲区
+//!
+//! shell轮询串口读取输入，与用户程序的控制台读取共用同一串口，输入先到先得。
+//! 启动参数含 `kshell` 且找不到init进程时，内核不恐慌而留在shell中
+
+use super::monitor::{self, DEFAULT_DUMP_LEN, MAX_DUMP_LEN};
+use crate::boot::{cmdline, fdt, memory_detect, uart};
+use crate::{fs, klog, sched, time};
+use core::fmt::Arguments;
+
+/// 没有输入时的轮询间隔
+const POLL_INTERVAL_NS: u64 = 20_000_000;
+
+/// `dmesg` 最多显示的字节数
+const DMESG_MAX: usize = 64 * 1024;
+
+fn print(args: Arguments) {
+    uart::early_print_fmt(args);
+}
+
+macro_rules! sh_print {
+    ($($arg:tt)*) => {
+        print(format_args!($($arg)*))
+    };
+}
+
+/// 启动参数是否启用了kshell
+pub fn enabled() -> bool {
+    cmdline::has("kshell")
+}
+
+/// 没有输入时睡眠，让出处理器
+fn idle() {
+    time::sleep_until(time::monotonic_ns() + POLL_INTERVAL_NS);
+}
+
+/// ps：任务列表
+fn ps() {
+    sh_print!("  TID   PID  PGID STATE    NAME\n");
+    for task in sched::tasks() {
+        let process = task.process();
+        let (pid, pgid) = process.map_or((0, 0), |process| (process.pid, process.pgid()));
+        sh_print!(
+            "{:>5} {:>5} {:>5} {:<8} {}\n",
+            task.id,
+            pid,
+            pgid,
+            alloc::format!("{:?}", task.state()),
+            task.name
+        );
+    }
+}
+
+/// free：内存用量（KiB）
+fn free() {
+    let (total, available) =
+        memory_detect::get_memory_map().map_or((0, 0), |map| (map.total_memory, map.available_memory));
+    let mapped: usize = sched::processes()
+        .iter()
+        .flat_map(|process| process.address_space.vmas())
+        .map(|vma| vma.len())
+        .sum();
+    sh_print!("物理内存总计 {:>10} KiB\n", total / 1024);
+    sh_print!("物理内存可用 {:>10} KiB\n", available / 1024);
+    sh_print!("用户映射总计 {:>10} KiB\n", mapped / 1024);
+}
+
+/// lsdev：设备树中的设备
+fn lsdev() {
+    let Some(fdt) = fdt::fdt() else {
+        sh_print!("没有设备树\n");
+        return;
+    };
+    for device in fdt.devices() {
+        let status = if device.enabled { "okay" } else { "disabled" };
+        sh_print!("{:<8} {:<32} {}\n", status, device.compatible, device.path);
+    }
+}
+
+/// mount：已挂载的文件系统
+fn mount() {
+    for (mountpoint, kind) in fs::mounts() {
+        sh_print!("{} on {} type {}\n", kind, mountpoint, kind);
+    }
+}
+
+/// 显示命令列表
+fn print_help() {
+    sh_print!("ps                     显示任务列表\n");
+    sh_print!("free                   显示内存用量\n");
+    sh_print!("lsdev                  显示设备树中的设备\n");
+    sh_print!("md <地址> [长度]       显示内存\n");
+    sh_print!("mw <地址> <值> [宽度]  写入内存（宽度为1/2/4/8字节，默认8）\n");
+    sh_print!("mount                  显示已挂载的文件系统\n");
+    sh_print!("dmesg                  显示内核日志\n");
+}
+
+/// 执行一行命令
+fn run(line: &str) {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return;
+    };
+    let args: [Option<usize>; 3] = [
+        words.next().and_then(monitor::parse_number),
+        words.next().and_then(monitor::parse_number),
+        words.next().and_then(monitor::parse_number),
+    ];
+    match (command, args) {
+        ("ps", _) => ps(),
+        ("free", _) => free(),
+        ("lsdev", _) => lsdev(),
+        ("md", [Some(addr), len, _]) => monitor::dump_memory(addr, len.unwrap_or(DEFAULT_DUMP_LEN).min(MAX_DUMP_LEN)),
+        ("mw", [Some(addr), Some(value), width]) => monitor::write_memory(addr, value, width.unwrap_or(8)),
+        ("mount", _) => mount(),
+        ("dmesg", _) => sh_print!("{}", klog::read_all(DMESG_MAX, false)),
+        ("help", _) | ("h", _) | ("?", _) => print_help(),
+        _ => sh_print!("无法识别的命令，输入 help 查看命令\n"),
+    }
+}
+
+/// shell任务
+fn kshell_main() {
+    sh_print!("\n内核调试shell，输入 help 查看命令\n");
+    loop {
+        sh_print!("kshell> ");
+        let line = monitor::read_line(idle);
+        run(&line);
+    }
+}
+
+/// 启动参数含 `kshell` 时创建shell任务
+pub fn kshell_init() {
+    if enabled() {
+        sched::spawn("kshell", kshell_main);
+    }
+}
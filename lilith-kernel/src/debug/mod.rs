@@ -0,0 +1,23 @@
+//! 调试支持
+//!
+//! 本模块实现了内核自身的调试手段，包括：
+//! - 沿帧指针回溯调用栈（backtrace）
+//! - 链接后嵌入的内核符号表（kallsyms），把返回地址解析为函数名
+//! - 用户程序引起的异常只结束该进程（oops），不使内核恐慌
+//! - 热重启后仍保留的控制台输出与崩溃记录（pstore）
+//! - 通过第二个串口使用GDB调试内核（gdbstub）
+//! - 没有GDB时在控制台上检查断点现场的监视器（monitor）
+//! - 不依赖用户程序的内核调试shell（kshell）
+//! - 基于编译器插桩的函数跟踪（ftrace，`ftrace` 特性）
+//! - 关键路径上的静态跟踪点与二进制事件缓冲区（tracepoint）
+
+pub mod backtrace;
+#[cfg(feature = "ftrace")]
+pub mod ftrace;
+pub mod gdbstub;
+pub mod kallsyms;
+pub mod kshell;
+pub mod monitor;
+pub mod oops;
+pub mod pstore;
+pub mod tracepoint;
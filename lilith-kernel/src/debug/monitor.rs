@@ -0,0 +1,187 @@
+//! 断点监视器
+//!
+//! 内核态的断点异常没有被GDB调试桩接管时，在控制台上进入交互式监视器而不是恐慌，包括：
+//! - `r`：显示陷入现场的寄存器
+//! - `m <地址> [长度]`：以十六进制显示内存
+//! - `w <地址> <值> [宽度]`：写入1、2、4或8字节
+//! - `bt`：显示断点处的调用栈
+//! - `c`：越过断点指令继续运行
+//!
+//! 内存访问经过异常表，无效地址只报告错误。监视器运行期间当前hart关闭中断，其他hart继续运行
+
+use super::{backtrace, gdbstub, kallsyms};
+use crate::arch::TrapFrame;
+use crate::boot::uart;
+use crate::mm::protect::text_poke;
+use crate::mm::uaccess::copy_from_kernel_nofault;
+use alloc::string::String;
+use core::fmt::Arguments;
+
+/// 寄存器的ABI名，下标为寄存器编号
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7", "s2",
+    "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// 一行命令的最大长度
+const MAX_LINE: usize = 128;
+
+/// `m` 命令默认与最多显示的字节数
+pub(super) const DEFAULT_DUMP_LEN: usize = 64;
+pub(super) const MAX_DUMP_LEN: usize = 4096;
+
+/// 输出到控制台串口
+fn print(args: Arguments) {
+    uart::early_print_fmt(args);
+}
+
+macro_rules! mon_print {
+    ($($arg:tt)*) => {
+        print(format_args!($($arg)*))
+    };
+}
+
+/// 阻塞读取一行命令，回显输入并处理退格，没有输入时调用 `idle`
+pub(super) fn read_line(idle: fn()) -> String {
+    let mut line = String::new();
+    loop {
+        let Some(byte) = uart::early_read_byte() else {
+            idle();
+            continue;
+        };
+        match byte {
+            b'\r' | b'\n' => {
+                mon_print!("\n");
+                return line;
+            }
+            // 退格与DEL
+            0x08 | 0x7f if !line.is_empty() => {
+                line.pop();
+                mon_print!("\x08 \x08");
+            }
+            0x20..=0x7e if line.len() < MAX_LINE => {
+                line.push(byte as char);
+                mon_print!("{}", byte as char);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 解析数字，`0x` 开头为十六进制
+pub(super) fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// 以 `符号+偏移` 形式显示地址
+fn print_symbol(addr: usize) {
+    if let Some(symbol) = kallsyms::lookup(addr) {
+        mon_print!(" <{}+0x{:x}>", symbol.name, symbol.offset);
+    }
+}
+
+/// r：显示寄存器
+fn dump_registers(frame: &TrapFrame) {
+    mon_print!("pc      0x{:016x}", frame.sepc);
+    print_symbol(frame.sepc);
+    mon_print!("\nra      0x{:016x}", frame.regs[1]);
+    print_symbol(frame.regs[1]);
+    mon_print!("\nsstatus 0x{:016x}\n", frame.sstatus);
+    for reg in (2..32).step_by(2) {
+        mon_print!(
+            "{:<4} 0x{:016x}    {:<4} 0x{:016x}\n",
+            REG_NAMES[reg],
+            frame.regs[reg],
+            REG_NAMES[reg + 1],
+            frame.regs[reg + 1]
+        );
+    }
+}
+
+/// m：以十六进制显示内存，每行16字节
+pub(super) fn dump_memory(addr: usize, len: usize) {
+    let mut offset = 0;
+    while offset < len {
+        let line_addr = addr.wrapping_add(offset);
+        let mut bytes = [0u8; 16];
+        let count = (len - offset).min(16);
+        if copy_from_kernel_nofault(&mut bytes[..count], line_addr).is_err() {
+            mon_print!("0x{:016x}: 无法访问\n", line_addr);
+            return;
+        }
+        mon_print!("0x{:016x}:", line_addr);
+        for byte in &bytes[..count] {
+            mon_print!(" {:02x}", byte);
+        }
+        mon_print!("{:width$}  ", "", width = (16 - count) * 3);
+        for &byte in &bytes[..count] {
+            let c = if (0x20..0x7f).contains(&byte) { byte as char } else { '.' };
+            mon_print!("{}", c);
+        }
+        mon_print!("\n");
+        offset += count;
+    }
+}
+
+/// w：写入内存，`width` 为1、2、4或8
+pub(super) fn write_memory(addr: usize, value: usize, width: usize) {
+    if !matches!(width, 1 | 2 | 4 | 8) {
+        mon_print!("宽度只能是1、2、4或8\n");
+        return;
+    }
+    let bytes = value.to_le_bytes();
+    // 写入的可能是代码
+    if text_poke(addr, &bytes[..width]).is_err() {
+        mon_print!("0x{:016x}: 无法写入\n", addr);
+    }
+}
+
+/// 显示命令列表
+fn print_help() {
+    mon_print!("r                     显示寄存器\n");
+    mon_print!("m <地址> [长度]       显示内存\n");
+    mon_print!("w <地址> <值> [宽度]  写入内存（宽度为1/2/4/8字节，默认8）\n");
+    mon_print!("bt                    显示调用栈\n");
+    mon_print!("c                     继续运行\n");
+}
+
+/// 内核态断点异常的处理入口，在控制台上交互直到用户选择继续
+pub fn handle_breakpoint(frame: &mut TrapFrame) {
+    // 异常地址处已不是断点指令（如断点已被删除）时直接重新执行
+    let Some(len) = gdbstub::ebreak_at(frame.sepc) else {
+        return;
+    };
+
+    mon_print!("\n内核断点 hart {} pc=0x{:016x}", crate::arch::hart_id(), frame.sepc);
+    print_symbol(frame.sepc);
+    mon_print!("\n输入 h 查看命令\n");
+
+    loop {
+        mon_print!("mon> ");
+        let line = read_line(core::hint::spin_loop);
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let args: [Option<usize>; 3] = [
+            words.next().and_then(parse_number),
+            words.next().and_then(parse_number),
+            words.next().and_then(parse_number),
+        ];
+        match (command, args) {
+            ("r", _) => dump_registers(frame),
+            ("m", [Some(addr), len, _]) => dump_memory(addr, len.unwrap_or(DEFAULT_DUMP_LEN).min(MAX_DUMP_LEN)),
+            ("w", [Some(addr), Some(value), width]) => write_memory(addr, value, width.unwrap_or(8)),
+            ("bt", _) => backtrace::print_backtrace_from(frame.regs[8], print),
+            ("c", _) => {
+                frame.sepc += len;
+                return;
+            }
+            ("h", _) | ("?", _) => print_help(),
+            _ => mon_print!("无法识别的命令，输入 h 查看命令\n"),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! 可恢复的内核错误（oops）
+//!
+//! 由用户程序引起的异常不应使整个内核恐慌：
+//! - 来自U-mode的异常：记录oops，以对应的信号结束进程
+//! - S-mode访问用户地址出错且没有修复代码：记录oops与内核调用栈，结束当前进程
+//!
+//! 内核自身的状态异常（如访问内核地址出错）仍然恐慌
+
+use super::backtrace;
+use crate::arch::{TrapFrame, REG_SP};
+use crate::sched;
+use core::fmt::Arguments;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 返回地址与帧指针寄存器编号
+const REG_RA: usize = 1;
+const REG_FP: usize = 8;
+
+/// 发生过的oops次数
+static OOPS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 发生过的oops次数
+pub fn oops_count() -> usize {
+    OOPS_COUNT.load(Ordering::Relaxed)
+}
+
+/// 记录由用户程序引起的异常，并以 `signal` 结束当前进程
+///
+/// 当前任务不属于任何用户进程时直接返回，由调用方按内核错误处理
+pub fn user_fault(frame: &TrapFrame, reason: Arguments, signal: u32) {
+    let process = match sched::current_process() {
+        Some(process) => process,
+        None => return,
+    };
+    let count = OOPS_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    let tid = sched::current().map_or(0, |task| task.id.0);
+
+    crate::early_println!("Oops #{}: {}", count, reason);
+    crate::early_println!(
+        "  进程{} 线程{} 模式={} sepc=0x{:x} ra=0x{:x} sp=0x{:x}",
+        process.pid,
+        tid,
+        if frame.from_user() { "U" } else { "S" },
+        frame.sepc,
+        frame.regs[REG_RA],
+        frame.regs[REG_SP]
+    );
+    // 用户态的帧指针链在用户内存中，不可信，只回溯内核栈
+    if !frame.from_user() {
+        backtrace::print_backtrace_from(frame.regs[REG_FP], crate::klog::print);
+    }
+    crate::early_println!("  以信号{}结束进程{}", signal, process.pid);
+
+    process.kill(signal);
+    drop(process);
+    sched::exit_current();
+}
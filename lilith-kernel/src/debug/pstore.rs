@@ -0,0 +1,254 @@
+//! 持久化崩溃记录（pstore）
+//!
+//! 在主内存末尾保留一块热重启后内容不丢失的区域：
+//! - 控制台环：作为内核控制台注册，本次启动的输出持续写入，重启后可查看上次启动的最后输出
+//! - 崩溃记录：内核恐慌时写入恐慌信息、寄存器、调用栈与最后的控制台输出
+//!
+//! 启动时把上次启动留下的内容复制出来，通过 /sys/fs/pstore 下的文件提供给
+//! 用户态做事后分析，写入这些文件即删除对应的记录。
+//!
+//! 区域布局：
+//!
+//! ```text
+//! +0                    PstoreHeader
+//! +HEADER_SIZE          控制台环 [u8; CONSOLE_SIZE]
+//! +HEADER_SIZE+CONSOLE  崩溃记录 [u8; DMESG_SIZE]
+//! ```
+
+use super::backtrace;
+use crate::error::KernelError;
+use crate::fs::kernfs;
+use crate::klog::console::{self, ConsoleDevice};
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt::{self, Arguments, Write};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 保留区域大小
+pub const PSTORE_SIZE: usize = 64 * 1024;
+
+/// 保留区域起始物理地址：主内存（0x8000_0000起128MB）的末尾
+pub const PSTORE_BASE: usize = 0x8000_0000 + 128 * 1024 * 1024 - PSTORE_SIZE;
+
+/// 魔数 "PSTR"
+const PSTORE_MAGIC: u32 = u32::from_le_bytes(*b"PSTR");
+
+/// 头部大小
+const HEADER_SIZE: usize = 64;
+/// 控制台环大小
+const CONSOLE_SIZE: usize = 32 * 1024;
+/// 崩溃记录大小
+const DMESG_SIZE: usize = PSTORE_SIZE - HEADER_SIZE - CONSOLE_SIZE;
+/// 崩溃记录中附带的控制台输出长度
+const DMESG_CONSOLE_TAIL: usize = 8 * 1024;
+
+/// 导出的文件
+const CONSOLE_FILE: &str = "/sys/fs/pstore/console-ramoops-0";
+const DMESG_FILE: &str = "/sys/fs/pstore/dmesg-ramoops-0";
+
+/// 保留区域头部
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PstoreHeader {
+    magic: u32,
+    /// 崩溃记录的有效长度，0表示没有记录
+    dmesg_len: u32,
+    /// 本次启动写入控制台环的总字节数
+    console_written: u64,
+    /// 累计的恐慌次数
+    panic_count: u64,
+}
+
+/// 上次启动留下的内容
+struct Saved {
+    console: Option<String>,
+    dmesg: Option<String>,
+}
+
+static SAVED: Mutex<Saved> = Mutex::new(Saved {
+    console: None,
+    dmesg: None,
+});
+
+/// 控制台环是否已开始记录（在此之前保留区域仍是上次启动的内容）
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+fn header_ptr() -> *mut PstoreHeader {
+    PSTORE_BASE as *mut PstoreHeader
+}
+
+fn console_ptr() -> *mut u8 {
+    (PSTORE_BASE + HEADER_SIZE) as *mut u8
+}
+
+fn dmesg_ptr() -> *mut u8 {
+    (PSTORE_BASE + HEADER_SIZE + CONSOLE_SIZE) as *mut u8
+}
+
+fn read_header() -> PstoreHeader {
+    unsafe { ptr::read_volatile(header_ptr()) }
+}
+
+fn write_header(header: PstoreHeader) {
+    unsafe { ptr::write_volatile(header_ptr(), header) }
+}
+
+/// 按写入顺序取出控制台环中最后 `max` 字节
+fn console_tail(header: &PstoreHeader, max: usize) -> impl Iterator<Item = u8> {
+    let written = header.console_written as usize;
+    let len = written.min(CONSOLE_SIZE).min(max);
+    (written - len..written).map(|pos| unsafe { ptr::read_volatile(console_ptr().add(pos % CONSOLE_SIZE)) })
+}
+
+/// 控制台环作为内核控制台注册，记录所有内核输出
+struct PstoreConsole;
+
+impl ConsoleDevice for PstoreConsole {
+    fn name(&self) -> &str {
+        "pstore"
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        console_write(bytes);
+    }
+}
+
+static PSTORE_CONSOLE: PstoreConsole = PstoreConsole;
+
+/// 记录控制台输出
+pub fn console_write(bytes: &[u8]) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let mut header = read_header();
+    for &byte in bytes {
+        let pos = header.console_written as usize % CONSOLE_SIZE;
+        unsafe { ptr::write_volatile(console_ptr().add(pos), byte) };
+        header.console_written += 1;
+    }
+    write_header(header);
+}
+
+/// 向崩溃记录区域写入，超出时截断
+struct DmesgWriter {
+    len: usize,
+}
+
+impl DmesgWriter {
+    fn push(&mut self, byte: u8) {
+        if self.len < DMESG_SIZE {
+            unsafe { ptr::write_volatile(dmesg_ptr().add(self.len), byte) };
+            self.len += 1;
+        }
+    }
+}
+
+impl Write for DmesgWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|byte| self.push(byte));
+        Ok(())
+    }
+}
+
+/// 读取陷入相关的寄存器
+fn dump_registers(out: &mut DmesgWriter) {
+    use riscv::register::{scause, sepc, stval};
+    let _ = writeln!(
+        out,
+        "寄存器: sp=0x{:x} fp=0x{:x} sepc=0x{:x} scause=0x{:x} stval=0x{:x}",
+        crate::arch::stack_pointer(),
+        crate::arch::frame_pointer(),
+        sepc::read(),
+        scause::read().bits(),
+        stval::read()
+    );
+}
+
+/// 内核恐慌时写入崩溃记录
+///
+/// 不分配内存、不获取锁，可在任何上下文中调用
+pub fn record_panic(message: Arguments) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let mut header = read_header();
+    header.panic_count += 1;
+
+    let mut out = DmesgWriter { len: 0 };
+    let _ = writeln!(out, "Panic#{} Part1", header.panic_count);
+    let _ = writeln!(out, "内核恐慌: {}", message);
+    dump_registers(&mut out);
+
+    let _ = writeln!(out, "调用栈:");
+    backtrace::walk(crate::arch::frame_pointer(), |ra| {
+        let _ = writeln!(out, "  0x{:016x} {}", ra, backtrace::Caller(ra));
+        true
+    });
+
+    let _ = writeln!(out, "最后的控制台输出:");
+    for byte in console_tail(&header, DMESG_CONSOLE_TAIL) {
+        out.push(byte);
+    }
+
+    header.dmesg_len = out.len as u32;
+    write_header(header);
+}
+
+/// 读取上次启动的内容，然后开始本次启动的记录
+pub fn pstore_init() -> Result<(), KernelError> {
+    let header = read_header();
+    let mut saved = SAVED.lock();
+
+    if header.magic == PSTORE_MAGIC {
+        if header.console_written > 0 {
+            let bytes: alloc::vec::Vec<u8> = console_tail(&header, CONSOLE_SIZE).collect();
+            saved.console = Some(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        let dmesg_len = (header.dmesg_len as usize).min(DMESG_SIZE);
+        if dmesg_len > 0 {
+            let bytes = unsafe { core::slice::from_raw_parts(dmesg_ptr(), dmesg_len) };
+            saved.dmesg = Some(String::from_utf8_lossy(bytes).into_owned());
+        }
+    }
+    let has_console = saved.console.is_some();
+    let has_dmesg = saved.dmesg.is_some();
+    drop(saved);
+
+    write_header(PstoreHeader {
+        magic: PSTORE_MAGIC,
+        dmesg_len: 0,
+        console_written: 0,
+        panic_count: if header.magic == PSTORE_MAGIC { header.panic_count } else { 0 },
+    });
+    ACTIVE.store(true, Ordering::Release);
+    console::register(&PSTORE_CONSOLE)?;
+
+    if has_console {
+        kernfs::register(
+            CONSOLE_FILE,
+            Some(Box::new(|| SAVED.lock().console.clone().unwrap_or_default())),
+            Some(Box::new(|_| {
+                SAVED.lock().console = None;
+                kernfs::unregister(CONSOLE_FILE);
+                Ok(())
+            })),
+        )?;
+    }
+    if has_dmesg {
+        kernfs::register(
+            DMESG_FILE,
+            Some(Box::new(|| SAVED.lock().dmesg.clone().unwrap_or_default())),
+            Some(Box::new(|_| {
+                SAVED.lock().dmesg = None;
+                kernfs::unregister(DMESG_FILE);
+                Ok(())
+            })),
+        )?;
+        crate::early_println!("pstore: 发现上次启动的崩溃记录，见 {}", DMESG_FILE);
+    }
+
+    crate::early_println!("pstore: 保留区域 0x{:x}-0x{:x}", PSTORE_BASE, PSTORE_BASE + PSTORE_SIZE);
+    Ok(())
+}
@@ -0,0 +1,480 @@
+//! 静态跟踪点
+//!
+//! 本模块实现了内核关键路径上的类型化跟踪事件，供之后做火焰图等离线分析，包括：
+//! - `tracepoint!` 宏：事件未开启时只有一次原子读取的开销
+//! - 事件类型：`sched_switch`、`irq_entry`、`page_fault`、`syscall_enter`
+//! - 每个hart一个无锁环形缓冲区：写入方用原子加法预留槽位，以序号提交，
+//!   中断嵌套与并发读取都不需要加锁，写满后覆盖最旧的事件
+//! - /sys/kernel/tracing 下的文件：
+//!   - `events/enable`、`events/<事件>/enable`：写入1开启、写入0关闭
+//!   - `events/header_event`、`events/<事件>/format`：二进制记录的布局与各字段名
+//!   - `trace_events`：按时间顺序输出所有hart的事件文本
+//!   - `trace_raw`：二进制记录流，每个打开的文件各自从最旧的事件开始读取
+//!
+//! 二进制记录为固定48字节的 `RawEvent`（小端序），被覆盖而丢失的事件直接跳过
+
+use crate::arch;
+use crate::error::KernelError;
+use crate::fs::file::{File, FileStat, S_IFREG};
+use crate::fs::kernfs;
+use crate::sched::MAX_HARTS;
+use crate::time::{self, NSEC_PER_SEC};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 每个hart的环形缓冲区容量（事件数），必须是2的幂
+const RING_SIZE: usize = 1024;
+
+/// 每个事件最多的字段数
+pub const MAX_FIELDS: usize = 4;
+
+/// 事件编号
+pub const EVENT_SCHED_SWITCH: u16 = 0;
+pub const EVENT_IRQ_ENTRY: u16 = 1;
+pub const EVENT_PAGE_FAULT: u16 = 2;
+pub const EVENT_SYSCALL_ENTER: u16 = 3;
+
+/// 事件种类数
+const NR_EVENTS: usize = 4;
+
+/// 类型化的跟踪事件
+pub trait TraceEvent {
+    /// 事件编号，即 `RawEvent::id`
+    const ID: u16;
+
+    /// 按 `format` 中的字段顺序编码，不足的字段为0
+    fn encode(&self) -> [u64; MAX_FIELDS];
+}
+
+/// 任务切换
+pub struct SchedSwitch {
+    /// 切换出的任务
+    pub prev: usize,
+    /// 切换到的任务
+    pub next: usize,
+}
+
+impl TraceEvent for SchedSwitch {
+    const ID: u16 = EVENT_SCHED_SWITCH;
+
+    fn encode(&self) -> [u64; MAX_FIELDS] {
+        [self.prev as u64, self.next as u64, 0, 0]
+    }
+}
+
+/// 进入中断处理
+pub struct IrqEntry {
+    /// 中断原因（scause去掉最高位）
+    pub cause: usize,
+    /// 被中断的指令地址
+    pub ip: usize,
+}
+
+impl TraceEvent for IrqEntry {
+    const ID: u16 = EVENT_IRQ_ENTRY;
+
+    fn encode(&self) -> [u64; MAX_FIELDS] {
+        [self.cause as u64, self.ip as u64, 0, 0]
+    }
+}
+
+/// 页错误与访问异常
+pub struct PageFault {
+    /// 出错的地址
+    pub addr: usize,
+    /// 出错的指令地址
+    pub ip: usize,
+    /// 异常原因（scause）
+    pub cause: usize,
+    /// 是否发生在用户态
+    pub user: bool,
+}
+
+impl TraceEvent for PageFault {
+    const ID: u16 = EVENT_PAGE_FAULT;
+
+    fn encode(&self) -> [u64; MAX_FIELDS] {
+        [
+            self.addr as u64,
+            self.ip as u64,
+            self.cause as u64,
+            u64::from(self.user),
+        ]
+    }
+}
+
+/// 进入系统调用
+pub struct SyscallEnter {
+    /// 调用号
+    pub nr: usize,
+    /// 前三个参数
+    pub args: [usize; 3],
+}
+
+impl TraceEvent for SyscallEnter {
+    const ID: u16 = EVENT_SYSCALL_ENTER;
+
+    fn encode(&self) -> [u64; MAX_FIELDS] {
+        [
+            self.nr as u64,
+            self.args[0] as u64,
+            self.args[1] as u64,
+            self.args[2] as u64,
+        ]
+    }
+}
+
+/// 事件的描述：名称与各字段（字段名，是否以十六进制显示）
+struct EventInfo {
+    name: &'static str,
+    fields: &'static [(&'static str, bool)],
+}
+
+/// 所有事件的描述，下标为事件编号
+const EVENTS: [EventInfo; NR_EVENTS] = [
+    EventInfo {
+        name: "sched_switch",
+        fields: &[("prev_tid", false), ("next_tid", false)],
+    },
+    EventInfo {
+        name: "irq_entry",
+        fields: &[("cause", false), ("ip", true)],
+    },
+    EventInfo {
+        name: "page_fault",
+        fields: &[("addr", true), ("ip", true), ("cause", false), ("user", false)],
+    },
+    EventInfo {
+        name: "syscall_enter",
+        fields: &[("nr", false), ("arg0", true), ("arg1", true), ("arg2", true)],
+    },
+];
+
+/// 二进制事件记录，即 `trace_raw` 中每条记录的布局
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RawEvent {
+    /// 单调时间（纳秒）
+    pub time: u64,
+    /// 事件编号
+    pub id: u16,
+    /// 产生事件的hart
+    pub hart: u16,
+    pub reserved: u32,
+    /// 字段值，含义见事件的 `format`
+    pub fields: [u64; MAX_FIELDS],
+}
+
+/// 二进制记录的字节数
+const RAW_EVENT_SIZE: usize = core::mem::size_of::<RawEvent>();
+
+/// 环形缓冲区的槽位
+struct Slot {
+    /// 提交序号：事件的全局下标为 `n` 时，写入中为 `2n+1`，写完为 `2n+2`
+    seq: AtomicU64,
+    event: UnsafeCell<RawEvent>,
+}
+
+/// hart的环形缓冲区
+struct Ring {
+    /// 已预留的事件总数
+    head: AtomicU64,
+    slots: Box<[Slot]>,
+}
+
+// 写入方各自预留不同的槽位，读取方以序号检查读到的事件是否完整
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn new() -> Self {
+        Self {
+            head: AtomicU64::new(0),
+            slots: (0..RING_SIZE)
+                .map(|_| Slot {
+                    seq: AtomicU64::new(0),
+                    event: UnsafeCell::new(RawEvent::default()),
+                })
+                .collect(),
+        }
+    }
+
+    /// 写入一个事件，可在中断处理中调用
+    fn push(&self, event: RawEvent) {
+        let index = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[index as usize % RING_SIZE];
+        slot.seq.store(2 * index + 1, Ordering::Relaxed);
+        core::sync::atomic::fence(Ordering::Release);
+        unsafe {
+            core::ptr::write_volatile(slot.event.get(), event);
+        }
+        slot.seq.store(2 * index + 2, Ordering::Release);
+    }
+
+    /// 读取全局下标为 `index` 的事件，尚未写完或已被覆盖时返回 `None`
+    fn get(&self, index: u64) -> Option<RawEvent> {
+        let slot = &self.slots[index as usize % RING_SIZE];
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq != 2 * index + 2 {
+            return None;
+        }
+        let event = unsafe { core::ptr::read_volatile(slot.event.get()) };
+        core::sync::atomic::fence(Ordering::Acquire);
+        (slot.seq.load(Ordering::Relaxed) == seq).then_some(event)
+    }
+
+    /// 仍可能读到的最旧事件的下标与已预留的事件总数
+    fn range(&self) -> (u64, u64) {
+        let head = self.head.load(Ordering::Acquire);
+        (head.saturating_sub(RING_SIZE as u64), head)
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const RING_INIT: AtomicPtr<Ring> = AtomicPtr::new(core::ptr::null_mut());
+
+/// 各hart的环形缓冲区，`tracepoint_init` 中分配
+static RINGS: [AtomicPtr<Ring>; MAX_HARTS] = [RING_INIT; MAX_HARTS];
+
+/// 已开启的事件，第n位对应编号为n的事件
+static ENABLED: AtomicU32 = AtomicU32::new(0);
+
+/// 在跟踪点处记录事件
+///
+/// ```ignore
+/// crate::tracepoint!(tracepoint::SchedSwitch { prev: 1, next: 2 });
+/// ```
+#[macro_export]
+macro_rules! tracepoint {
+    ($event:expr) => {
+        $crate::debug::tracepoint::trace(&$event)
+    };
+}
+
+/// 事件是否已开启
+#[inline(always)]
+pub fn enabled(id: u16) -> bool {
+    ENABLED.load(Ordering::Relaxed) & (1 << id) != 0
+}
+
+/// 记录事件，由 `tracepoint!` 调用
+#[inline(always)]
+pub fn trace<E: TraceEvent>(event: &E) {
+    if enabled(E::ID) {
+        emit(E::ID, event.encode());
+    }
+}
+
+/// 把事件写入本hart的环形缓冲区
+#[inline(never)]
+fn emit(id: u16, fields: [u64; MAX_FIELDS]) {
+    let hart = arch::hart_id();
+    let ring = RINGS[hart].load(Ordering::Acquire);
+    if ring.is_null() {
+        return;
+    }
+    let event = RawEvent {
+        time: time::monotonic_ns(),
+        id,
+        hart: hart as u16,
+        reserved: 0,
+        fields,
+    };
+    unsafe { &*ring }.push(event);
+}
+
+/// 开启或关闭事件
+fn set_enabled(mask: u32, on: bool) {
+    if on {
+        ENABLED.fetch_or(mask, Ordering::Relaxed);
+    } else {
+        ENABLED.fetch_and(!mask, Ordering::Relaxed);
+    }
+}
+
+/// 解析写入 `enable` 文件的内容
+fn parse_enable(data: &str) -> Result<bool, KernelError> {
+    match data.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(KernelError::InvalidArgument),
+    }
+}
+
+/// 所有hart中尚未被覆盖的事件，按时间排序
+fn collect_events() -> Vec<RawEvent> {
+    let mut events = Vec::new();
+    for ring in RINGS.iter() {
+        let ring = ring.load(Ordering::Acquire);
+        if ring.is_null() {
+            continue;
+        }
+        let ring = unsafe { &*ring };
+        let (start, end) = ring.range();
+        events.extend((start..end).filter_map(|index| ring.get(index)));
+    }
+    events.sort_by_key(|event| event.time);
+    events
+}
+
+/// 生成 `trace_events` 文件内容
+fn read_events() -> String {
+    let events = collect_events();
+    let mut out = String::new();
+    let _ = writeln!(out, "# entries: {}", events.len());
+    let _ = writeln!(out, "#  CPU   TIMESTAMP  EVENT");
+    for event in events {
+        let Some(info) = EVENTS.get(event.id as usize) else {
+            continue;
+        };
+        let _ = write!(
+            out,
+            "  [{:03}] {:>6}.{:06}: {}:",
+            event.hart,
+            event.time / NSEC_PER_SEC,
+            event.time % NSEC_PER_SEC / 1000,
+            info.name
+        );
+        for (&(name, hex), value) in info.fields.iter().zip(event.fields) {
+            if hex {
+                let _ = write!(out, " {}=0x{:x}", name, value);
+            } else {
+                let _ = write!(out, " {}={}", name, value);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// 生成事件的 `format` 文件内容
+fn read_format(id: usize) -> String {
+    let info = &EVENTS[id];
+    let mut out = String::new();
+    let _ = writeln!(out, "name: {}", info.name);
+    let _ = writeln!(out, "ID: {}", id);
+    let _ = writeln!(out, "format:");
+    for (index, (name, _)) in info.fields.iter().enumerate() {
+        let _ = writeln!(out, "\tfield:u64 {};\toffset:{};\tsize:8;", name, 16 + index * 8);
+    }
+    out
+}
+
+/// 二进制记录的公共部分
+const HEADER_EVENT: &str = "\tfield:u64 time;\toffset:0;\tsize:8;\n\
+                            \tfield:u16 id;\toffset:8;\tsize:2;\n\
+                            \tfield:u16 hart;\toffset:10;\tsize:2;\n\
+                            \tfield:u32 reserved;\toffset:12;\tsize:4;\n\
+                            \tfield:u64 fields[4];\toffset:16;\tsize:32;\n";
+
+/// /sys/kernel/tracing/trace_raw：二进制事件流
+///
+/// 每次读取返回整数条记录，没有新事件时返回0；读取跟随各hart的写入进度，
+/// 落后超过缓冲区容量时跳过已被覆盖的事件
+pub struct TraceRawFile {
+    /// 各hart下一个要读取的事件下标
+    cursors: Mutex<[u64; MAX_HARTS]>,
+}
+
+impl TraceRawFile {
+    pub fn new() -> Self {
+        Self {
+            cursors: Mutex::new([0; MAX_HARTS]),
+        }
+    }
+}
+
+impl Default for TraceRawFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl File for TraceRawFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.len() < RAW_EVENT_SIZE {
+            return Err(KernelError::InvalidArgument);
+        }
+        let mut cursors = self.cursors.lock();
+        let mut len = 0;
+        for (ring, cursor) in RINGS.iter().zip(cursors.iter_mut()) {
+            let ring = ring.load(Ordering::Acquire);
+            if ring.is_null() {
+                continue;
+            }
+            let ring = unsafe { &*ring };
+            let (start, end) = ring.range();
+            *cursor = (*cursor).max(start);
+            while *cursor < end && buf.len() - len >= RAW_EVENT_SIZE {
+                if let Some(event) = ring.get(*cursor) {
+                    let bytes =
+                        unsafe { core::slice::from_raw_parts(&event as *const RawEvent as *const u8, RAW_EVENT_SIZE) };
+                    buf[len..len + RAW_EVENT_SIZE].copy_from_slice(bytes);
+                    len += RAW_EVENT_SIZE;
+                } else if ring.range().0 <= *cursor {
+                    // 事件已预留但尚未写完，下次再读
+                    break;
+                }
+                *cursor += 1;
+            }
+        }
+        Ok(len)
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFREG | 0o400,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 分配环形缓冲区，注册 /sys/kernel/tracing 下的事件文件
+pub fn tracepoint_init() -> Result<(), KernelError> {
+    for ring in RINGS.iter() {
+        ring.store(Box::into_raw(Box::new(Ring::new())), Ordering::Release);
+    }
+
+    kernfs::register(
+        "/sys/kernel/tracing/events/enable",
+        Some(Box::new(|| {
+            let all = (1u32 << NR_EVENTS) - 1;
+            let state = match ENABLED.load(Ordering::Relaxed) & all {
+                0 => "0",
+                mask if mask == all => "1",
+                _ => "X",
+            };
+            alloc::format!("{}\n", state)
+        })),
+        Some(Box::new(|data| {
+            set_enabled((1 << NR_EVENTS) - 1, parse_enable(data)?);
+            Ok(())
+        })),
+    )?;
+    kernfs::register(
+        "/sys/kernel/tracing/events/header_event",
+        Some(Box::new(|| String::from(HEADER_EVENT))),
+        None,
+    )?;
+    for (id, info) in EVENTS.iter().enumerate() {
+        kernfs::register(
+            &alloc::format!("/sys/kernel/tracing/events/{}/enable", info.name),
+            Some(Box::new(move || alloc::format!("{}\n", u8::from(enabled(id as u16))))),
+            Some(Box::new(move |data| {
+                set_enabled(1 << id, parse_enable(data)?);
+                Ok(())
+            })),
+        )?;
+        kernfs::register(
+            &alloc::format!("/sys/kernel/tracing/events/{}/format", info.name),
+            Some(Box::new(move || read_format(id))),
+            None,
+        )?;
+    }
+    kernfs::register("/sys/kernel/tracing/trace_events", Some(Box::new(read_events)), None)?;
+    Ok(())
+}
@@ -1,6 +1,6 @@
 //! 错误类型定义
 //! 
-//! 本模块定义了内核中使用的各种错误类型
+//! 本模块定义了内核中使用的各种错误类型，以及返回给用户态的错误码 `Errno`
 
 use core::fmt;
 
@@ -25,6 +25,16 @@ pub enum KernelError {
     NetworkError,
     /// 文件系统错误
     FilesystemError,
+    /// 资源已存在
+    AlreadyExists,
+    /// 访问权限不足（文件权限位）
+    AccessDenied,
+    /// 操作需要等待，而调用方要求不阻塞
+    WouldBlock,
+    /// 等待超时
+    TimedOut,
+    /// 不是控制终端
+    NotTty,
 }
 
 /// 引导过程错误类型
@@ -70,6 +80,114 @@ pub enum SchedulerError {
     InvalidPriority,
 }
 
+/// 内核模块加载错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleError {
+    /// 不是可加载的RISC-V ELF目标文件
+    InvalidFormat,
+    /// 内存不足
+    OutOfMemory,
+    /// 引用了内核未导出的符号
+    UnknownSymbol,
+    /// 不支持的重定位类型
+    UnsupportedRelocation,
+    /// 重定位结果超出指令的寻址范围
+    RelocationOverflow,
+    /// 同名模块已加载
+    AlreadyLoaded,
+    /// 模块未加载
+    NotLoaded,
+    /// 模块的初始化函数返回错误
+    InitFailed,
+    /// 模块没有退出函数，不能卸载
+    NotRemovable,
+}
+
+/// 返回给用户态的错误码（取值与Linux一致）
+///
+/// 系统调用以负数形式返回，各内核错误类型都可以通过 `From` 转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Errno(i32);
+
+impl Errno {
+    pub const EPERM: Self = Self(1);
+    pub const ENOENT: Self = Self(2);
+    pub const ESRCH: Self = Self(3);
+    pub const EIO: Self = Self(5);
+    pub const ENXIO: Self = Self(6);
+    pub const ENOEXEC: Self = Self(8);
+    pub const EBADF: Self = Self(9);
+    pub const ECHILD: Self = Self(10);
+    pub const EAGAIN: Self = Self(11);
+    pub const ENOMEM: Self = Self(12);
+    pub const EACCES: Self = Self(13);
+    pub const EFAULT: Self = Self(14);
+    pub const EBUSY: Self = Self(16);
+    pub const EEXIST: Self = Self(17);
+    pub const ENODEV: Self = Self(19);
+    pub const ENOTDIR: Self = Self(20);
+    pub const EINVAL: Self = Self(22);
+    pub const EMFILE: Self = Self(24);
+    pub const ENOTTY: Self = Self(25);
+    pub const EFBIG: Self = Self(27);
+    pub const ESPIPE: Self = Self(29);
+    pub const ENAMETOOLONG: Self = Self(36);
+    pub const ENOSYS: Self = Self(38);
+    pub const EMSGSIZE: Self = Self(90);
+    pub const ENETUNREACH: Self = Self(101);
+    pub const ETIMEDOUT: Self = Self(110);
+
+    /// Linux保留给错误码的最大值，更大的负返回值是正常结果（如高地址）
+    pub const MAX: i32 = 4095;
+
+    /// 错误码的数值
+    pub const fn code(self) -> i32 {
+        self.0
+    }
+
+    /// 从系统调用的返回值中解析错误码，正常结果返回 `None`
+    pub const fn from_return(value: isize) -> Option<Self> {
+        if value < 0 && value >= -(Self::MAX as isize) {
+            Some(Self(-value as i32))
+        } else {
+            None
+        }
+    }
+
+    /// 错误码的符号名，未知的错误码返回 `None`
+    pub const fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Self::EPERM => "EPERM",
+            Self::ENOENT => "ENOENT",
+            Self::ESRCH => "ESRCH",
+            Self::EIO => "EIO",
+            Self::ENXIO => "ENXIO",
+            Self::ENOEXEC => "ENOEXEC",
+            Self::EBADF => "EBADF",
+            Self::ECHILD => "ECHILD",
+            Self::EAGAIN => "EAGAIN",
+            Self::ENOMEM => "ENOMEM",
+            Self::EACCES => "EACCES",
+            Self::EFAULT => "EFAULT",
+            Self::EBUSY => "EBUSY",
+            Self::EEXIST => "EEXIST",
+            Self::ENODEV => "ENODEV",
+            Self::ENOTDIR => "ENOTDIR",
+            Self::EINVAL => "EINVAL",
+            Self::EMFILE => "EMFILE",
+            Self::ENOTTY => "ENOTTY",
+            Self::EFBIG => "EFBIG",
+            Self::ESPIPE => "ESPIPE",
+            Self::ENAMETOOLONG => "ENAMETOOLONG",
+            Self::ENOSYS => "ENOSYS",
+            Self::EMSGSIZE => "EMSGSIZE",
+            Self::ENETUNREACH => "ENETUNREACH",
+            Self::ETIMEDOUT => "ETIMEDOUT",
+            _ => return None,
+        })
+    }
+}
+
 impl fmt::Display for KernelError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -82,6 +200,11 @@ impl fmt::Display for KernelError {
             KernelError::DeviceError => write!(f, "设备错误"),
             KernelError::NetworkError => write!(f, "网络错误"),
             KernelError::FilesystemError => write!(f, "文件系统错误"),
+            KernelError::AlreadyExists => write!(f, "资源已存在"),
+            KernelError::AccessDenied => write!(f, "访问被拒绝"),
+            KernelError::WouldBlock => write!(f, "操作将阻塞"),
+            KernelError::TimedOut => write!(f, "等待超时"),
+            KernelError::NotTty => write!(f, "不是控制终端"),
         }
     }
 }
@@ -98,6 +221,22 @@ impl fmt::Display for BootError {
     }
 }
 
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::InvalidFormat => write!(f, "无效的模块格式"),
+            ModuleError::OutOfMemory => write!(f, "内存不足"),
+            ModuleError::UnknownSymbol => write!(f, "未知符号"),
+            ModuleError::UnsupportedRelocation => write!(f, "不支持的重定位类型"),
+            ModuleError::RelocationOverflow => write!(f, "重定位超出范围"),
+            ModuleError::AlreadyLoaded => write!(f, "模块已加载"),
+            ModuleError::NotLoaded => write!(f, "模块未加载"),
+            ModuleError::InitFailed => write!(f, "模块初始化失败"),
+            ModuleError::NotRemovable => write!(f, "模块不能卸载"),
+        }
+    }
+}
+
 impl From<BootError> for KernelError {
     fn from(err: BootError) -> Self {
         match err {
@@ -120,4 +259,78 @@ impl From<MemoryError> for KernelError {
             MemoryError::AlignmentError => KernelError::InvalidArgument,
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "errno {}", self.0),
+        }
+    }
+}
+
+impl From<KernelError> for Errno {
+    fn from(err: KernelError) -> Self {
+        match err {
+            KernelError::OutOfMemory => Errno::ENOMEM,
+            KernelError::InvalidArgument => Errno::EINVAL,
+            KernelError::PermissionDenied => Errno::EPERM,
+            KernelError::ResourceBusy => Errno::EBUSY,
+            KernelError::NotFound => Errno::ENOENT,
+            KernelError::NotSupported => Errno::ENOSYS,
+            KernelError::DeviceError => Errno::EIO,
+            KernelError::NetworkError => Errno::ENETUNREACH,
+            KernelError::FilesystemError => Errno::EIO,
+            KernelError::AlreadyExists => Errno::EEXIST,
+            KernelError::AccessDenied => Errno::EACCES,
+            KernelError::WouldBlock => Errno::EAGAIN,
+            KernelError::TimedOut => Errno::ETIMEDOUT,
+            KernelError::NotTty => Errno::ENOTTY,
+        }
+    }
+}
+
+impl From<BootError> for Errno {
+    fn from(err: BootError) -> Self {
+        KernelError::from(err).into()
+    }
+}
+
+/// 内存错误来自访问用户内存，地址与权限错误都报告为 `EFAULT`
+impl From<MemoryError> for Errno {
+    fn from(err: MemoryError) -> Self {
+        match err {
+            MemoryError::InvalidAddress | MemoryError::PageFault | MemoryError::PermissionDenied => Errno::EFAULT,
+            MemoryError::OutOfMemory => Errno::ENOMEM,
+            MemoryError::AlignmentError => Errno::EINVAL,
+        }
+    }
+}
+
+impl From<ModuleError> for Errno {
+    fn from(err: ModuleError) -> Self {
+        match err {
+            ModuleError::InvalidFormat => Errno::ENOEXEC,
+            ModuleError::OutOfMemory => Errno::ENOMEM,
+            ModuleError::UnknownSymbol => Errno::ENOENT,
+            ModuleError::UnsupportedRelocation => Errno::ENOEXEC,
+            ModuleError::RelocationOverflow => Errno::ENOEXEC,
+            ModuleError::AlreadyLoaded => Errno::EEXIST,
+            ModuleError::NotLoaded => Errno::ENOENT,
+            ModuleError::InitFailed => Errno::EINVAL,
+            ModuleError::NotRemovable => Errno::EBUSY,
+        }
+    }
+}
+
+impl From<SchedulerError> for Errno {
+    fn from(err: SchedulerError) -> Self {
+        match err {
+            SchedulerError::ProcessNotFound => Errno::ESRCH,
+            SchedulerError::InvalidProcessState => Errno::EINVAL,
+            SchedulerError::ScheduleQueueFull => Errno::EAGAIN,
+            SchedulerError::InvalidPriority => Errno::EINVAL,
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! epoll实例
+//!
+//! 本模块实现了epoll的兴趣列表与就绪检查，包括：
+//! - 按描述符登记关心的事件与用户数据，`ERR`/`HUP` 总是报告
+//! - 水平触发：文件就绪期间每次等待都报告
+//! - 边沿触发（`EPOLLET`）：只在关心的事件从未就绪变为就绪时报告一次
+//! - 一次性（`EPOLLONESHOT`）：报告后停用，直到用 `EPOLL_CTL_MOD` 重新设置
+//!
+//! 兴趣列表不延长文件的寿命，文件被释放后自动移除。
+//! 边沿按两次检查之间的就绪状态变化判断，就绪期间又到达的数据不会再次报告
+
+use super::file::{File, FileStat, PollEvents};
+use super::poll::{self, PollTable};
+use crate::error::KernelError;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 事件中的标志位（取值与Linux一致）
+pub const EPOLLET: u32 = 1 << 31;
+pub const EPOLLONESHOT: u32 = 1 << 30;
+
+/// 关心的一个描述符
+struct Interest {
+    file: Weak<dyn File>,
+    /// 关心的事件
+    events: PollEvents,
+    edge: bool,
+    oneshot: bool,
+    /// 用户数据，随事件原样返回
+    data: u64,
+    /// 上次检查时的就绪状态（边沿触发）
+    last: PollEvents,
+}
+
+/// epoll实例
+pub struct Epoll {
+    interests: Mutex<BTreeMap<usize, Interest>>,
+}
+
+/// 所有epoll实例，用于从描述符识别
+static EPOLLS: Mutex<Vec<Weak<Epoll>>> = Mutex::new(Vec::new());
+
+impl Epoll {
+    /// 创建空的epoll实例
+    pub fn new() -> Arc<Self> {
+        let epoll = Arc::new(Self {
+            interests: Mutex::new(BTreeMap::new()),
+        });
+        let mut epolls = EPOLLS.lock();
+        epolls.retain(|epoll| epoll.strong_count() > 0);
+        epolls.push(Arc::downgrade(&epoll));
+        epoll
+    }
+
+    /// 登记描述符 `fd`，已登记时返回 `AlreadyExists`
+    pub fn add(&self, fd: usize, file: &Arc<dyn File>, events: u32, data: u64) -> Result<(), KernelError> {
+        let mut interests = self.interests.lock();
+        interests.retain(|_, interest| interest.file.strong_count() > 0);
+        if interests.contains_key(&fd) {
+            return Err(KernelError::AlreadyExists);
+        }
+        interests.insert(
+            fd,
+            Interest {
+                file: Arc::downgrade(file),
+                events: PollEvents::from_bits_truncate(events as u16),
+                edge: events & EPOLLET != 0,
+                oneshot: events & EPOLLONESHOT != 0,
+                data,
+                last: PollEvents::empty(),
+            },
+        );
+        Ok(())
+    }
+
+    /// 修改描述符 `fd` 关心的事件与用户数据，未登记时返回 `NotFound`
+    pub fn modify(&self, fd: usize, events: u32, data: u64) -> Result<(), KernelError> {
+        let mut interests = self.interests.lock();
+        let interest = interests.get_mut(&fd).ok_or(KernelError::NotFound)?;
+        interest.events = PollEvents::from_bits_truncate(events as u16);
+        interest.edge = events & EPOLLET != 0;
+        interest.oneshot = events & EPOLLONESHOT != 0;
+        interest.data = data;
+        interest.last = PollEvents::empty();
+        Ok(())
+    }
+
+    /// 移除描述符 `fd`，未登记时返回 `NotFound`
+    pub fn remove(&self, fd: usize) -> Result<(), KernelError> {
+        self.interests
+            .lock()
+            .remove(&fd)
+            .map(|_| ())
+            .ok_or(KernelError::NotFound)
+    }
+
+    /// 仍然存在的被关心的文件
+    fn files(&self) -> Vec<Arc<dyn File>> {
+        self.interests
+            .lock()
+            .values()
+            .filter_map(|interest| interest.file.upgrade())
+            .collect()
+    }
+
+    /// 检查所有描述符，返回最多 `max` 个就绪事件与对应的用户数据
+    fn collect(&self, max: usize) -> Vec<(PollEvents, u64)> {
+        let mut ready = Vec::new();
+        let mut interests = self.interests.lock();
+        interests.retain(|_, interest| interest.file.strong_count() > 0);
+        for interest in interests.values_mut() {
+            if ready.len() >= max {
+                break;
+            }
+            let Some(file) = interest.file.upgrade() else { continue };
+            let events = file.poll() & (interest.events | PollEvents::ERR | PollEvents::HUP);
+            let report = if interest.edge { events & !interest.last } else { events };
+            interest.last = events;
+            if report.is_empty() {
+                continue;
+            }
+            ready.push((events, interest.data));
+            if interest.oneshot {
+                interest.events = PollEvents::empty();
+            }
+        }
+        ready
+    }
+
+    /// 等待最多 `max` 个事件，直到单调时间 `deadline_ns`（`None` 为一直等待），超时返回空
+    pub fn wait(&self, max: usize, deadline_ns: Option<u64>) -> Vec<(PollEvents, u64)> {
+        let files = self.files();
+        poll::wait_ready(deadline_ns, |table| {
+            for file in &files {
+                file.poll_wait(table);
+            }
+            let ready = self.collect(max);
+            (!ready.is_empty()).then_some(ready)
+        })
+        .unwrap_or_default()
+    }
+}
+
+/// 查找描述符对应的epoll实例
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别
+pub fn lookup(file: &Arc<dyn File>) -> Option<Arc<Epoll>> {
+    let target = Arc::as_ptr(file) as *const ();
+    EPOLLS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|epoll| Arc::as_ptr(epoll) as *const () == target)
+}
+
+impl File for Epoll {
+    /// 有水平就绪的描述符时可读（不改变边沿触发的状态）
+    fn poll(&self) -> PollEvents {
+        let ready = self.interests.lock().values().any(|interest| {
+            interest
+                .file
+                .upgrade()
+                .is_some_and(|file| file.poll().intersects(interest.events))
+        });
+        if ready {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    /// 被关心的文件不归本实例所有，无法登记它们的等待队列，改为定期检查
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.periodic();
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat::default()
+    }
+}
+
+crate::kernel_test! {
+    fn epoll_edge_triggered() {
+        use super::eventfd::EventFd;
+
+        let eventfd: Arc<dyn File> = EventFd::new(0, false, true);
+        let level = Epoll::new();
+        let edge = Epoll::new();
+        level.add(3, &eventfd, PollEvents::IN.bits() as u32, 7).unwrap();
+        edge.add(3, &eventfd, PollEvents::IN.bits() as u32 | EPOLLET, 7).unwrap();
+        assert_eq!(edge.add(3, &eventfd, 0, 0), Err(KernelError::AlreadyExists));
+        assert!(level.wait(4, Some(0)).is_empty());
+
+        eventfd.write(&1u64.to_ne_bytes()).unwrap();
+        for _ in 0..2 {
+            assert_eq!(level.wait(4, Some(0)), [(PollEvents::IN, 7)]);
+        }
+        assert_eq!(edge.wait(4, Some(0)), [(PollEvents::IN, 7)]);
+        assert!(edge.wait(4, Some(0)).is_empty());
+
+        drop(eventfd);
+        assert!(level.wait(4, Some(0)).is_empty());
+        assert_eq!(level.remove(3), Err(KernelError::NotFound));
+    }
+}
@@ -0,0 +1,191 @@
+//! 事件计数文件（eventfd）
+//!
+//! 本模块实现了以64位计数器为核心的事件通知文件，语义与Linux一致，包括：
+//! - 写入8字节的值加到计数器上，计数器将超过 `u64::MAX - 1` 时写入阻塞
+//! - 读取8字节：普通模式返回计数器的值并清零，信号量模式返回1并减一，计数器为0时读取阻塞
+//! - 非阻塞模式下需要等待时返回 `WouldBlock`
+//! - 内核可以直接增加计数器（如提交环的完成通知），不会阻塞
+//!
+//! 计数器非0时可读，计数器小于 `u64::MAX - 1` 时可写
+
+use super::file::{File, FileStat, PollEvents};
+use super::poll::PollTable;
+use crate::error::KernelError;
+use crate::sync::WaitQueue;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 计数器的最大值
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+/// 事件计数文件
+pub struct EventFd {
+    counter: Mutex<u64>,
+    /// 信号量模式
+    semaphore: bool,
+    nonblock: AtomicBool,
+    /// 等待计数器变化的读者与写者
+    wait: WaitQueue,
+}
+
+/// 所有事件计数文件，用于从描述符识别
+static EVENT_FDS: Mutex<Vec<Weak<EventFd>>> = Mutex::new(Vec::new());
+
+impl EventFd {
+    /// 创建初值为 `initval` 的事件计数文件
+    pub fn new(initval: u32, semaphore: bool, nonblock: bool) -> Arc<Self> {
+        let eventfd = Arc::new(Self {
+            counter: Mutex::new(u64::from(initval)),
+            semaphore,
+            nonblock: AtomicBool::new(nonblock),
+            wait: WaitQueue::new(),
+        });
+        let mut files = EVENT_FDS.lock();
+        files.retain(|file| file.strong_count() > 0);
+        files.push(Arc::downgrade(&eventfd));
+        eventfd
+    }
+
+    /// 当前的计数
+    pub fn count(&self) -> u64 {
+        *self.counter.lock()
+    }
+
+    /// 内核增加计数器，超过上限的部分截断，不会阻塞
+    pub fn signal(&self, value: u64) {
+        if value == 0 {
+            return;
+        }
+        {
+            let mut counter = self.counter.lock();
+            *counter = counter.saturating_add(value).min(EVENTFD_MAX);
+        }
+        self.wait.wake_all();
+    }
+
+    /// 计数器非0时取出读取结果
+    fn try_take(&self) -> Option<u64> {
+        let mut counter = self.counter.lock();
+        match *counter {
+            0 => None,
+            _ if self.semaphore => {
+                *counter -= 1;
+                Some(1)
+            }
+            value => {
+                *counter = 0;
+                Some(value)
+            }
+        }
+    }
+
+    /// 计数器加上 `value` 不超过上限时加上
+    fn try_add(&self, value: u64) -> bool {
+        let mut counter = self.counter.lock();
+        if EVENTFD_MAX - *counter < value {
+            return false;
+        }
+        *counter += value;
+        true
+    }
+}
+
+/// 查找描述符对应的事件计数文件
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别
+pub fn lookup(file: &Arc<dyn File>) -> Option<Arc<EventFd>> {
+    let target = Arc::as_ptr(file) as *const ();
+    EVENT_FDS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|eventfd| Arc::as_ptr(eventfd) as *const () == target)
+}
+
+impl File for EventFd {
+    /// 读取8字节的计数，`buf` 不足8字节时返回 `InvalidArgument`
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.len() < 8 {
+            return Err(KernelError::InvalidArgument);
+        }
+        let value = loop {
+            if let Some(value) = self.try_take() {
+                break value;
+            }
+            if self.nonblock.load(Ordering::Relaxed) {
+                return Err(KernelError::WouldBlock);
+            }
+            self.wait.wait_until(|| self.count() != 0);
+        };
+        self.wait.wake_all();
+        buf[..8].copy_from_slice(&value.to_ne_bytes());
+        Ok(8)
+    }
+
+    /// 写入8字节的值，值为 `u64::MAX` 或 `buf` 不足8字节时返回 `InvalidArgument`
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        let bytes: [u8; 8] = buf
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(KernelError::InvalidArgument)?;
+        let value = u64::from_ne_bytes(bytes);
+        if value == u64::MAX {
+            return Err(KernelError::InvalidArgument);
+        }
+        while !self.try_add(value) {
+            if self.nonblock.load(Ordering::Relaxed) {
+                return Err(KernelError::WouldBlock);
+            }
+            self.wait.wait_until(|| EVENTFD_MAX - self.count() >= value);
+        }
+        if value != 0 {
+            self.wait.wake_all();
+        }
+        Ok(8)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let count = self.count();
+        let mut events = PollEvents::empty();
+        if count != 0 {
+            events |= PollEvents::IN;
+        }
+        if count < EVENTFD_MAX {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(&self.wait);
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat::default()
+    }
+}
+
+crate::kernel_test! {
+    fn eventfd_semaphore_mode() {
+        let counter = EventFd::new(2, false, true);
+        let semaphore = EventFd::new(2, true, true);
+        let mut buf = [0u8; 8];
+
+        assert_eq!(counter.read(&mut buf), Ok(8));
+        assert_eq!(u64::from_ne_bytes(buf), 2);
+        assert_eq!(counter.read(&mut buf), Err(KernelError::WouldBlock));
+
+        for _ in 0..2 {
+            assert_eq!(semaphore.read(&mut buf), Ok(8));
+            assert_eq!(u64::from_ne_bytes(buf), 1);
+        }
+        assert_eq!(semaphore.poll(), PollEvents::OUT);
+
+        assert_eq!(counter.write(&EVENTFD_MAX.to_ne_bytes()), Ok(8));
+        assert_eq!(counter.write(&1u64.to_ne_bytes()), Err(KernelError::WouldBlock));
+        assert_eq!(counter.poll(), PollEvents::IN);
+        assert_eq!(counter.write(&u64::MAX.to_ne_bytes()), Err(KernelError::InvalidArgument));
+    }
+}
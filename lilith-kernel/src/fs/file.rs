@@ -0,0 +1,392 @@
+//! 打开的文件与文件描述符表
+//!
+//! 本模块定义了系统调用层看到的文件抽象：
+//! - `File` trait：读、写、定位、就绪状态与获取元数据
+//! - 控制台、/dev/null、随机数设备与 kernfs 虚拟文件的实现
+//! - 每个进程的文件描述符表
+
+use super::kernfs;
+use super::poll::PollTable;
+use crate::error::KernelError;
+use crate::klog::console;
+use crate::mm::vma::MapOwner;
+use crate::random;
+use crate::sched::{self, capability::CAP_SYS_ADMIN};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use spin::Mutex;
+
+/// 每个进程最多打开的文件数
+pub const MAX_FDS: usize = 256;
+
+/// 文件类型（st_mode的高位，取值与Linux一致）
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// 执行时切换有效用户号/组号（st_mode中的权限位）
+pub const S_ISUID: u32 = 0o4000;
+pub const S_ISGID: u32 = 0o2000;
+
+/// 文件元数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStat {
+    /// 类型与权限
+    pub mode: u32,
+    /// 所有者的用户号
+    pub uid: u32,
+    /// 所属组的组号
+    pub gid: u32,
+    /// 节点编号
+    pub ino: u64,
+    /// 设备号（字符设备）
+    pub rdev: u64,
+    /// 文件大小
+    pub size: u64,
+}
+
+/// 文件定位方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+bitflags! {
+    /// 文件的就绪状态（取值与Linux的 `POLL*` 一致）
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        /// 可以读取而不阻塞
+        const IN  = 0x001;
+        /// 有紧急数据
+        const PRI = 0x002;
+        /// 可以写入而不阻塞
+        const OUT = 0x004;
+        /// 出错
+        const ERR = 0x008;
+        /// 对端已关闭
+        const HUP = 0x010;
+    }
+}
+
+/// 打开的文件
+pub trait File: Send + Sync {
+    /// 读取数据，返回读取的字节数，0表示文件结束
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, KernelError> {
+        Err(KernelError::PermissionDenied)
+    }
+
+    /// 写入数据，返回写入的字节数
+    fn write(&self, _buf: &[u8]) -> Result<usize, KernelError> {
+        Err(KernelError::PermissionDenied)
+    }
+
+    /// 从 `offset` 处读取数据，不改变读写位置（pread），不能定位的文件返回 `NotSupported`
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, KernelError> {
+        Err(KernelError::NotSupported)
+    }
+
+    /// 在 `offset` 处写入数据，不改变读写位置（pwrite），不能定位的文件返回 `NotSupported`
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> Result<usize, KernelError> {
+        Err(KernelError::NotSupported)
+    }
+
+    /// 移动读写位置，返回新的位置
+    fn seek(&self, _pos: SeekFrom) -> Result<u64, KernelError> {
+        Err(KernelError::NotSupported)
+    }
+
+    /// 将缓存的数据写回存储设备
+    fn sync(&self) -> Result<(), KernelError> {
+        Ok(())
+    }
+
+    /// 修改文件长度
+    fn truncate(&self, _len: u64) -> Result<(), KernelError> {
+        Err(KernelError::InvalidArgument)
+    }
+
+    /// 映射文件中从 `offset` 开始的 `len` 字节，返回文件内容所在的内核地址与内存的所有者
+    ///
+    /// 共享映射直接映射这段内存，写入对文件可见；`writable` 为真时要求文件以可写方式打开
+    fn mmap(&self, _offset: usize, _len: usize, _writable: bool) -> Result<(usize, MapOwner), KernelError> {
+        Err(KernelError::NotSupported)
+    }
+
+    /// 当前的就绪状态，默认总是可读写
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+
+    /// 登记就绪状态改变时会被唤醒的等待队列，默认状态不会改变
+    fn poll_wait<'a>(&'a self, _table: &mut PollTable<'a>) {}
+
+    /// 获取元数据
+    fn stat(&self) -> FileStat;
+
+    /// 是否为终端
+    fn is_tty(&self) -> bool {
+        false
+    }
+}
+
+/// 控制台（/dev/console），读写主控制台
+pub struct Console;
+
+impl File for Console {
+    /// 阻塞直到至少读到一个字节，然后读取已到达的所有数据
+    ///
+    /// 后台进程组读控制终端时先经过作业控制
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        super::tty::check_read()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut count = 0;
+        while count < buf.len() {
+            match super::tty::read_input().or_else(console::read_primary) {
+                Some(byte) => {
+                    buf[count] = byte;
+                    count += 1;
+                    if byte == b'\n' {
+                        break;
+                    }
+                }
+                None if count > 0 => break,
+                None => sched::yield_now(),
+            }
+        }
+        Ok(count)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        console::write_primary(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> PollEvents {
+        if super::tty::input_pending() || console::input_pending() {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+
+    /// 输入子系统送来的字符会唤醒等待者，控制台设备自身的输入靠轮询得到
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(super::tty::input_wait());
+        table.periodic();
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o620,
+            rdev: (5 << 8) | 1,
+            ..FileStat::default()
+        }
+    }
+
+    fn is_tty(&self) -> bool {
+        true
+    }
+}
+
+/// /dev/null
+pub struct NullFile;
+
+impl File for NullFile {
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, KernelError> {
+        Ok(0)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o666,
+            rdev: (1 << 8) | 3,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// /dev/random 与 /dev/urandom
+///
+/// 两者输出相同的随机数；/dev/random 在生成器完成初始化前阻塞。
+/// 写入的数据混入熵池但不计熵
+pub struct RandomFile {
+    /// 是否等待生成器初始化（/dev/random）
+    blocking: bool,
+}
+
+impl RandomFile {
+    /// /dev/random
+    pub const fn random() -> Self {
+        Self { blocking: true }
+    }
+
+    /// /dev/urandom
+    pub const fn urandom() -> Self {
+        Self { blocking: false }
+    }
+}
+
+impl File for RandomFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if self.blocking {
+            random::wait_for_initialized();
+        }
+        // 分块生成，避免长时间关中断持有生成器的锁
+        for chunk in buf.chunks_mut(256) {
+            random::get_random_bytes(chunk);
+        }
+        Ok(buf.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        random::add_device_randomness(buf);
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o666,
+            rdev: (1 << 8) | if self.blocking { 8 } else { 9 },
+            ..FileStat::default()
+        }
+    }
+}
+
+/// kernfs虚拟文件
+///
+/// 第一次读取时生成内容并缓存，之后按偏移读取，保证一次打开内读到的内容一致；
+/// 写入修改内核参数，需要 `CAP_SYS_ADMIN`
+pub struct KernfsFile {
+    path: String,
+    content: Mutex<Option<String>>,
+    offset: Mutex<u64>,
+}
+
+impl KernfsFile {
+    /// 打开kernfs虚拟文件
+    pub fn open(path: &str) -> Result<Self, KernelError> {
+        if !kernfs::exists(path) {
+            return Err(KernelError::NotFound);
+        }
+        Ok(Self {
+            path: String::from(path),
+            content: Mutex::new(None),
+            offset: Mutex::new(0),
+        })
+    }
+}
+
+impl File for KernfsFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        let mut offset = self.offset.lock();
+        let len = self.read_at(*offset, buf)?;
+        *offset += len as u64;
+        Ok(len)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, KernelError> {
+        let mut content = self.content.lock();
+        if content.is_none() {
+            *content = Some(kernfs::read(&self.path)?);
+        }
+        let data = content.as_deref().unwrap_or_default().as_bytes();
+
+        let start = (offset as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        if !sched::capable(CAP_SYS_ADMIN) {
+            return Err(KernelError::PermissionDenied);
+        }
+        let data = core::str::from_utf8(buf).map_err(|_| KernelError::InvalidArgument)?;
+        kernfs::write(&self.path, data)?;
+        Ok(buf.len())
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<u64, KernelError> {
+        let mut offset = self.offset.lock();
+        *offset = match pos {
+            SeekFrom::Start(position) => position,
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta).ok_or(KernelError::InvalidArgument)?,
+            // 内容动态生成，没有固定的文件大小
+            SeekFrom::End(_) => return Err(KernelError::NotSupported),
+        };
+        Ok(*offset)
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFREG | 0o644,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 文件描述符表
+#[derive(Clone, Default)]
+pub struct FdTable {
+    files: Vec<Option<Arc<dyn File>>>,
+}
+
+impl FdTable {
+    /// 创建空表
+    pub const fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// 创建标准输入、输出、错误都指向控制台的表
+    pub fn with_console() -> Self {
+        let console: Arc<dyn File> = Arc::new(Console);
+        Self {
+            files: alloc::vec![Some(console.clone()), Some(console.clone()), Some(console)],
+        }
+    }
+
+    /// 查找文件
+    pub fn get(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get(fd).cloned().flatten()
+    }
+
+    /// 分配不小于 `min_fd` 的最小空闲描述符
+    pub fn insert(&mut self, file: Arc<dyn File>, min_fd: usize) -> Result<usize, KernelError> {
+        let fd = (min_fd..MAX_FDS)
+            .find(|&fd| self.files.get(fd).map_or(true, Option::is_none))
+            .ok_or(KernelError::ResourceBusy)?;
+        self.install(fd, file)?;
+        Ok(fd)
+    }
+
+    /// 将文件安装到指定描述符，返回原来打开的文件
+    pub fn install(&mut self, fd: usize, file: Arc<dyn File>) -> Result<Option<Arc<dyn File>>, KernelError> {
+        if fd >= MAX_FDS {
+            return Err(KernelError::InvalidArgument);
+        }
+        if self.files.len() <= fd {
+            self.files.resize(fd + 1, None);
+        }
+        Ok(self.files[fd].replace(file))
+    }
+
+    /// 遍历打开的文件
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn File>> {
+        self.files.iter().flatten()
+    }
+
+    /// 关闭描述符
+    pub fn remove(&mut self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get_mut(fd).and_then(Option::take)
+    }
+}
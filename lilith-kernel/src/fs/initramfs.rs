@@ -0,0 +1,324 @@
+//! 初始内存文件系统（initramfs）
+//!
+//! 本模块实现了把引导程序加载的initrd挂载为只读的初始根文件系统，包括：
+//! - 从设备树 `/chosen` 的 `linux,initrd-start`/`linux,initrd-end` 找到initrd
+//! - 解析newc格式（`070701`/`070702`）的cpio归档，读到 `TRAILER!!!` 为止
+//! - 目录、普通文件与符号链接，文件内容直接引用initrd所在的内存，不复制
+//! - 按绝对路径查找，路径中的符号链接逐级解析
+//! - 打开普通文件与目录，只支持读取、定位、查询元数据与只读映射
+//! - 映射与加载程序时使用文件内容在页缓存中的页对齐副本，同一文件的映射共享这份副本
+//!
+//! 其他文件系统的路径（/dev、/proc、/dev/shm等）优先于initramfs，
+//! 归档中同名的文件被遮盖。initrd所在的内存在启动后不回收
+
+use super::file::{File, FileStat, SeekFrom, S_IFDIR, S_IFREG};
+use crate::boot::fdt;
+use crate::error::KernelError;
+use crate::mm::page_cache::{self, CachedFile};
+use crate::mm::vma::{MapOwner, PAGE_SIZE};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, Once};
+
+/// newc头部的魔数（`070702` 带校验和，校验和不检查）
+const NEWC_MAGIC: &[u8; 6] = b"070701";
+const CRC_MAGIC: &[u8; 6] = b"070702";
+/// newc头部长度：6字节魔数与13个8位十六进制字段
+const HEADER_SIZE: usize = 110;
+/// 归档结束标记
+const TRAILER: &str = "TRAILER!!!";
+
+/// 文件类型掩码与符号链接类型
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 解析一条路径时最多跟随的符号链接数
+const MAX_SYMLINKS: usize = 8;
+
+/// 归档中的一个文件
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    /// 节点编号
+    pub ino: u64,
+    /// 类型与权限
+    pub mode: u32,
+    /// 所有者的用户号
+    pub uid: u32,
+    /// 所属组的组号
+    pub gid: u32,
+    /// 文件内容（符号链接为目标路径）
+    pub data: &'static [u8],
+}
+
+impl Entry {
+    /// 是否为目录
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    /// 是否为普通文件
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// 文件内容在页缓存中的副本
+    pub fn cached(&self) -> Result<Arc<CachedFile>, KernelError> {
+        let data = self.data;
+        page_cache::get_or_fill(("initramfs", self.ino), data.len(), |buf| {
+            buf.copy_from_slice(data);
+            Ok(())
+        })
+    }
+}
+
+/// 按路径索引的文件，根目录为空字符串，其余为 `/a/b` 形式
+static ENTRIES: Once<BTreeMap<String, Entry>> = Once::new();
+
+/// 读取头部中第 `index` 个十六进制字段
+fn header_field(header: &[u8], index: usize) -> Result<u32, KernelError> {
+    let start = 6 + index * 8;
+    let text = core::str::from_utf8(&header[start..start + 8]).map_err(|_| KernelError::InvalidArgument)?;
+    u32::from_str_radix(text, 16).map_err(|_| KernelError::InvalidArgument)
+}
+
+/// 向上按4字节对齐
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// 归档中的路径（`.`、`./bin`、`bin` 等）转换为索引用的形式
+fn normalize(name: &str) -> String {
+    let mut path = String::new();
+    for component in name.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        path.push('/');
+        path.push_str(component);
+    }
+    path
+}
+
+/// 解析newc格式的cpio归档
+pub fn parse(archive: &'static [u8]) -> Result<BTreeMap<String, Entry>, KernelError> {
+    let mut entries = BTreeMap::new();
+    let mut offset = 0;
+    loop {
+        let header = archive
+            .get(offset..offset + HEADER_SIZE)
+            .ok_or(KernelError::InvalidArgument)?;
+        if &header[..6] != NEWC_MAGIC && &header[..6] != CRC_MAGIC {
+            return Err(KernelError::InvalidArgument);
+        }
+        let name_size = header_field(header, 11)? as usize;
+        let file_size = header_field(header, 6)? as usize;
+
+        let name_start = offset + HEADER_SIZE;
+        // 名称长度包含结尾的0
+        let name = archive
+            .get(name_start..name_start + name_size)
+            .and_then(|name| name.strip_suffix(&[0]))
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .ok_or(KernelError::InvalidArgument)?;
+        if name == TRAILER {
+            return Ok(entries);
+        }
+
+        let data_start = align4(name_start + name_size);
+        let data = archive
+            .get(data_start..data_start + file_size)
+            .ok_or(KernelError::InvalidArgument)?;
+        entries.insert(
+            normalize(name),
+            Entry {
+                ino: u64::from(header_field(header, 0)?),
+                mode: header_field(header, 1)?,
+                uid: header_field(header, 2)?,
+                gid: header_field(header, 3)?,
+                data,
+            },
+        );
+        offset = align4(data_start + file_size);
+    }
+}
+
+/// 按绝对路径查找文件，跟随路径中的符号链接
+///
+/// 没有挂载initramfs或文件不存在时返回 `NotFound`，符号链接过多时返回 `InvalidArgument`
+pub fn lookup(path: &str) -> Result<Entry, KernelError> {
+    let entries = ENTRIES.get().ok_or(KernelError::NotFound)?;
+    let mut pending: Vec<String> = path.split('/').rev().map(String::from).collect();
+    let mut resolved = String::new();
+    let mut links = 0;
+    while let Some(component) = pending.pop() {
+        match component.as_str() {
+            "" | "." => continue,
+            ".." => {
+                let parent = resolved.rfind('/').unwrap_or(0);
+                resolved.truncate(parent);
+                continue;
+            }
+            _ => {}
+        }
+        let candidate = alloc::format!("{}/{}", resolved, component);
+        let entry = entries.get(&candidate).ok_or(KernelError::NotFound)?;
+        if !entry.is_symlink() {
+            resolved = candidate;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(KernelError::InvalidArgument);
+        }
+        let target = core::str::from_utf8(entry.data).map_err(|_| KernelError::InvalidArgument)?;
+        if target.starts_with('/') {
+            resolved.clear();
+        }
+        pending.extend(target.split('/').rev().map(String::from));
+    }
+    entries.get(&resolved).copied().ok_or(KernelError::NotFound)
+}
+
+/// 是否已挂载initramfs
+pub fn mounted() -> bool {
+    ENTRIES.get().is_some()
+}
+
+/// 打开的initramfs文件
+pub struct InitramfsFile {
+    entry: Entry,
+    offset: Mutex<u64>,
+}
+
+/// 打开普通文件或目录
+pub fn open(path: &str) -> Result<InitramfsFile, KernelError> {
+    let entry = lookup(path)?;
+    if !entry.is_file() && !entry.is_dir() {
+        return Err(KernelError::NotSupported);
+    }
+    Ok(InitramfsFile {
+        entry,
+        offset: Mutex::new(0),
+    })
+}
+
+impl File for InitramfsFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        let mut offset = self.offset.lock();
+        let len = self.read_at(*offset, buf)?;
+        *offset += len as u64;
+        Ok(len)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if self.entry.is_dir() {
+            return Err(KernelError::InvalidArgument);
+        }
+        let data = self.entry.data;
+        let start = (offset as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<u64, KernelError> {
+        let mut offset = self.offset.lock();
+        *offset = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => (self.entry.data.len() as u64).checked_add_signed(delta),
+        }
+        .ok_or(KernelError::InvalidArgument)?;
+        Ok(*offset)
+    }
+
+    /// 文件只读，映射页缓存中的副本
+    fn mmap(&self, offset: usize, len: usize, writable: bool) -> Result<(usize, MapOwner), KernelError> {
+        if writable {
+            return Err(KernelError::AccessDenied);
+        }
+        if self.entry.is_dir() {
+            return Err(KernelError::InvalidArgument);
+        }
+        let cached = self.entry.cached()?;
+        let end = offset.checked_add(len).ok_or(KernelError::InvalidArgument)?;
+        if offset % PAGE_SIZE != 0 || end > cached.size() {
+            return Err(KernelError::InvalidArgument);
+        }
+        Ok((cached.addr() + offset, cached as MapOwner))
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: self.entry.mode,
+            uid: self.entry.uid,
+            gid: self.entry.gid,
+            ino: self.entry.ino,
+            size: self.entry.data.len() as u64,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 挂载引导程序传入的initrd作为初始根文件系统
+///
+/// 没有initrd时不挂载，由启动init进程时报告；initrd格式错误时返回 `InvalidArgument`
+pub fn initramfs_init() -> Result<(), KernelError> {
+    let Some(fdt) = fdt::fdt() else {
+        return Ok(());
+    };
+    let (Some(start), Some(end)) = (
+        fdt.property_u64("/chosen", "linux,initrd-start"),
+        fdt.property_u64("/chosen", "linux,initrd-end"),
+    ) else {
+        crate::log_info!("没有initrd，不挂载initramfs");
+        return Ok(());
+    };
+    if end <= start {
+        return Err(KernelError::InvalidArgument);
+    }
+    // 内核直接映射物理内存，initrd在启动后保持不变
+    let archive = unsafe { core::slice::from_raw_parts(start as usize as *const u8, (end - start) as usize) };
+    let entries = parse(archive)?;
+    crate::log_info!("initramfs: {}个文件，{}字节", entries.len(), end - start);
+    ENTRIES.call_once(|| entries);
+    Ok(())
+}
+
+crate::kernel_test! {
+    fn initramfs_parse_newc() {
+        fn header(ino: u32, mode: u32, name: &str, size: usize) -> alloc::vec::Vec<u8> {
+            let mut out = alloc::vec::Vec::from(*NEWC_MAGIC);
+            let fields = [ino, mode, 0, 0, 1, 0, size as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+            for field in fields {
+                out.extend_from_slice(alloc::format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+            out.resize(align4(out.len()), 0);
+            out
+        }
+
+        let mut archive = alloc::vec::Vec::new();
+        for (ino, mode, name, data) in [
+            (1, S_IFDIR | 0o755, ".", &b""[..]),
+            (2, S_IFDIR | 0o755, "bin", b""),
+            (3, S_IFREG | 0o755, "bin/busybox", b"\x7fELF"),
+            (4, S_IFLNK | 0o777, "init", b"bin/busybox"),
+            (5, 0, TRAILER, b""),
+        ] {
+            archive.extend(header(ino, mode, name, data.len()));
+            archive.extend_from_slice(data);
+            archive.resize(align4(archive.len()), 0);
+        }
+        let entries = parse(alloc::boxed::Box::leak(archive.into_boxed_slice())).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries[""].is_dir());
+        assert_eq!(entries["/bin/busybox"].data, b"\x7fELF");
+        assert!(entries["/init"].is_symlink());
+        assert!(parse(b"070701").is_err());
+    }
+}
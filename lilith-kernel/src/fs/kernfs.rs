@@ -0,0 +1,96 @@
+//! 内核虚拟文件
+//!
+//! 本模块为 /proc、/sys 等伪文件系统提供统一的后端：各子系统以绝对路径注册
+//! 读写回调，读取时动态生成内容，写入时解析并执行配置命令
+
+use crate::error::KernelError;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 读取回调：生成文件内容
+pub type ReadFn = Box<dyn Fn() -> String + Send + Sync>;
+/// 写入回调：处理写入的内容
+pub type WriteFn = Box<dyn Fn(&str) -> Result<(), KernelError> + Send + Sync>;
+
+/// 虚拟文件节点
+pub struct KernfsEntry {
+    /// 绝对路径
+    pub path: String,
+    /// 读取回调
+    read: Option<ReadFn>,
+    /// 写入回调
+    write: Option<WriteFn>,
+}
+
+/// 已注册的虚拟文件
+static ENTRIES: Mutex<Vec<Arc<KernfsEntry>>> = Mutex::new(Vec::new());
+
+/// 注册虚拟文件
+pub fn register(path: &str, read: Option<ReadFn>, write: Option<WriteFn>) -> Result<(), KernelError> {
+    if !path.starts_with('/') {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let mut entries = ENTRIES.lock();
+    if entries.iter().any(|e| e.path == path) {
+        return Err(KernelError::ResourceBusy);
+    }
+
+    entries.push(Arc::new(KernfsEntry {
+        path: String::from(path),
+        read,
+        write,
+    }));
+    Ok(())
+}
+
+/// 注销虚拟文件
+pub fn unregister(path: &str) {
+    ENTRIES.lock().retain(|e| e.path != path);
+}
+
+/// 查找虚拟文件
+fn lookup(path: &str) -> Result<Arc<KernfsEntry>, KernelError> {
+    ENTRIES
+        .lock()
+        .iter()
+        .find(|e| e.path == path)
+        .cloned()
+        .ok_or(KernelError::NotFound)
+}
+
+/// 虚拟文件是否存在
+pub fn exists(path: &str) -> bool {
+    lookup(path).is_ok()
+}
+
+/// 读取虚拟文件内容
+pub fn read(path: &str) -> Result<String, KernelError> {
+    let entry = lookup(path)?;
+    match &entry.read {
+        Some(read) => Ok(read()),
+        None => Err(KernelError::PermissionDenied),
+    }
+}
+
+/// 向虚拟文件写入内容
+pub fn write(path: &str, data: &str) -> Result<(), KernelError> {
+    let entry = lookup(path)?;
+    match &entry.write {
+        Some(write) => write(data),
+        None => Err(KernelError::PermissionDenied),
+    }
+}
+
+/// 列出指定目录前缀下的所有虚拟文件
+pub fn list(prefix: &str) -> Vec<String> {
+    ENTRIES
+        .lock()
+        .iter()
+        .filter(|e| e.path.starts_with(prefix))
+        .map(|e| e.path.clone())
+        .collect()
+}
@@ -0,0 +1,161 @@
+//! 文件系统模块
+//!
+//! 本模块实现了内核的文件系统支持，包括：
+//! - 内核虚拟文件（kernfs）
+//! - /proc 伪文件系统
+//! - 打开的文件与文件描述符表
+//! - POSIX共享内存对象（/dev/shm）
+//! - POSIX消息队列（/dev/mqueue）
+//! - 事件计数文件（eventfd）
+//! - 等待文件就绪（poll/select/epoll）
+//! - 通过读取文件接收信号（signalfd）
+//! - 控制终端与作业控制
+//! - 只读的初始根文件系统（initramfs）
+
+pub mod epoll;
+pub mod eventfd;
+pub mod file;
+pub mod initramfs;
+pub mod kernfs;
+pub mod mqueue;
+pub mod poll;
+pub mod procfs;
+pub mod shm;
+pub mod signalfd;
+pub mod tty;
+
+use crate::audit::AuditFile;
+use crate::debug::tracepoint::TraceRawFile;
+use crate::error::KernelError;
+use crate::sched;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use file::{Console, File, KernfsFile, NullFile, RandomFile};
+
+/// 打开文件的选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// 可读
+    pub read: bool,
+    /// 可写
+    pub write: bool,
+    /// 不存在时创建
+    pub create: bool,
+    /// 与 `create` 一起使用，已存在时失败
+    pub exclusive: bool,
+    /// 长度截断为0
+    pub truncate: bool,
+    /// 新建文件的权限位
+    pub mode: u32,
+}
+
+impl OpenOptions {
+    /// 以读写方式打开已有的文件
+    pub const READ_WRITE: OpenOptions = OpenOptions {
+        read: true,
+        write: true,
+        create: false,
+        exclusive: false,
+        truncate: false,
+        mode: 0,
+    };
+
+    /// 以只读方式打开已有的文件
+    pub const READ_ONLY: OpenOptions = OpenOptions {
+        write: false,
+        ..OpenOptions::READ_WRITE
+    };
+}
+
+/// 按选项打开文件，只有 /dev/shm 与 /dev/mqueue 下的文件可以创建
+pub fn open_with(path: &str, options: &OpenOptions) -> Result<Arc<dyn File>, KernelError> {
+    if let Some(name) = path.strip_prefix(shm::SHM_DIR) {
+        return Ok(shm::open(name, options)?);
+    }
+    match path.strip_prefix(mqueue::MQUEUE_DIR) {
+        Some(name) => Ok(mqueue::open(name, options, None, false)?),
+        None => open(path),
+    }
+}
+
+/// 按绝对路径打开文件
+pub fn open(path: &str) -> Result<Arc<dyn File>, KernelError> {
+    if let Some(name) = path.strip_prefix(shm::SHM_DIR) {
+        return Ok(shm::open(name, &OpenOptions::READ_ONLY)?);
+    }
+    if let Some(name) = path.strip_prefix(mqueue::MQUEUE_DIR) {
+        return Ok(mqueue::open(name, &OpenOptions::READ_ONLY, None, false)?);
+    }
+    match path {
+        "/dev/console" | "/dev/tty" => Ok(Arc::new(Console)),
+        "/dev/null" => Ok(Arc::new(NullFile)),
+        "/dev/random" => Ok(Arc::new(RandomFile::random())),
+        "/dev/urandom" => Ok(Arc::new(RandomFile::urandom())),
+        "/dev/audit" => Ok(Arc::new(AuditFile::open()?)),
+        "/dev/input/mice" => Ok(crate::input::mousedev::open(false)),
+        crate::virt::DEVICE_PATH => crate::virt::open(),
+        "/sys/kernel/tracing/trace_raw" => Ok(Arc::new(TraceRawFile::new())),
+        _ if kernfs::exists(path) => Ok(Arc::new(KernfsFile::open(path)?)),
+        _ => Ok(Arc::new(initramfs::open(path)?)),
+    }
+}
+
+/// 已挂载的文件系统 `(挂载点, 类型)`
+pub fn mounts() -> Vec<(&'static str, &'static str)> {
+    let mut mounts = Vec::new();
+    if initramfs::mounted() {
+        mounts.push(("/", "initramfs"));
+    }
+    mounts.extend([
+        ("/proc", "proc"),
+        ("/sys", "sysfs"),
+        ("/dev/shm", "tmpfs"),
+        ("/dev/mqueue", "mqueue"),
+    ]);
+    mounts
+}
+
+/// 把所有进程打开的文件缓存的数据写回存储设备，返回写回失败的文件数
+pub fn sync_all() -> usize {
+    let mut failed = 0;
+    for task in sched::tasks() {
+        let Some(process) = task.process() else { continue };
+        // 同一进程的线程共享描述符表，重复写回没有副作用
+        // 写回可能较慢，先复制文件列表再释放描述符表的锁
+        let files: Vec<Arc<dyn File>> = process.files().lock().iter().cloned().collect();
+        failed += files.iter().filter(|file| file.sync().is_err()).count();
+    }
+    failed
+}
+
+crate::kernel_test! {
+    fn kernfs_read_write() {
+        use alloc::boxed::Box;
+        use alloc::string::String;
+
+        const PATH: &str = "/sys/kernel/ktest";
+        kernfs::register(
+            PATH,
+            Some(Box::new(|| String::from("ok\n"))),
+            Some(Box::new(|data| match data {
+                "1" => Ok(()),
+                _ => Err(KernelError::InvalidArgument),
+            })),
+        )
+        .unwrap();
+        assert_eq!(kernfs::read(PATH).as_deref(), Ok("ok\n"));
+        assert_eq!(kernfs::write(PATH, "1"), Ok(()));
+        assert_eq!(kernfs::write(PATH, "2"), Err(KernelError::InvalidArgument));
+        kernfs::unregister(PATH);
+        assert!(!kernfs::exists(PATH));
+    }
+}
+
+crate::kernel_test! {
+    fn dev_null() {
+        let file = open("/dev/null").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(file.read(&mut buf), Ok(0));
+        assert_eq!(file.write(b"discarded"), Ok(9));
+    }
+}
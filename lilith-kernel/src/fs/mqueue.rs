@@ -0,0 +1,438 @@
+//! POSIX消息队列（/dev/mqueue）
+//!
+//! 本模块实现了以名字标识、保存在内核内存中的消息队列，包括：
+//! - 按名字创建、打开与删除，队列在名字删除且最后一个描述符关闭后释放
+//! - 消息按优先级排序，优先级高的先取出，同一优先级先进先出
+//! - 队列满时发送、队列空时接收阻塞，可以非阻塞或带超时
+//! - 按队列的所有者与权限位检查打开权限，`CAP_DAC_OVERRIDE` 不受限制
+//! - 读取 /dev/mqueue/名字 得到与Linux相同格式的状态行
+//!
+//! 容量与消息大小的默认值和上限与Linux的默认sysctl一致，超过普通上限需要 `CAP_SYS_RESOURCE`
+
+use super::file::{File, FileStat, PollEvents, SeekFrom, S_IFREG};
+use super::poll::PollTable;
+use super::OpenOptions;
+use crate::error::KernelError;
+use crate::sched;
+use crate::sched::capability::{CAP_DAC_OVERRIDE, CAP_SYS_RESOURCE};
+use crate::sched::cred::{self, MAY_READ, MAY_WRITE};
+use crate::sync::WaitQueue;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 消息队列所在的目录
+pub const MQUEUE_DIR: &str = "/dev/mqueue/";
+
+/// 优先级的上限（不含）
+pub const MQ_PRIO_MAX: u32 = 32768;
+
+/// 未指定属性时的容量与消息大小
+pub const DFLT_MSGMAX: usize = 10;
+pub const DFLT_MSGSIZEMAX: usize = 8192;
+
+/// 无特权时的容量与消息大小上限
+const MSG_MAX: usize = 10;
+const MSGSIZE_MAX: usize = 8192;
+
+/// 有 `CAP_SYS_RESOURCE` 时的上限
+const HARD_MSGMAX: usize = 65536;
+const HARD_MSGSIZEMAX: usize = 16 * 1024 * 1024;
+
+/// 名字的最大长度
+const NAME_MAX: usize = 255;
+
+/// 队列属性
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MqAttr {
+    /// 最多容纳的消息数
+    pub maxmsg: usize,
+    /// 单条消息的最大字节数
+    pub msgsize: usize,
+}
+
+impl MqAttr {
+    /// 默认属性
+    pub const DEFAULT: MqAttr = MqAttr {
+        maxmsg: DFLT_MSGMAX,
+        msgsize: DFLT_MSGSIZEMAX,
+    };
+
+    /// 检查属性：不能为0，超过普通上限需要 `CAP_SYS_RESOURCE`
+    fn check(&self) -> Result<(), KernelError> {
+        let (maxmsg, msgsize) = if self.maxmsg <= MSG_MAX && self.msgsize <= MSGSIZE_MAX {
+            (MSG_MAX, MSGSIZE_MAX)
+        } else if sched::capable(CAP_SYS_RESOURCE) {
+            (HARD_MSGMAX, HARD_MSGSIZEMAX)
+        } else {
+            return Err(KernelError::InvalidArgument);
+        };
+        if self.maxmsg == 0 || self.msgsize == 0 || self.maxmsg > maxmsg || self.msgsize > msgsize {
+            return Err(KernelError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+/// 一条消息
+struct Message {
+    priority: u32,
+    data: Vec<u8>,
+}
+
+/// 队列中的消息，按优先级从高到低排列
+struct Messages {
+    list: Vec<Message>,
+    /// 所有消息的总字节数
+    bytes: usize,
+}
+
+/// 消息队列
+pub struct MessageQueue {
+    ino: u64,
+    uid: u32,
+    gid: u32,
+    mode: u32,
+    attr: MqAttr,
+    messages: Mutex<Messages>,
+    /// 等待消息的接收者
+    not_empty: WaitQueue,
+    /// 等待空位的发送者
+    not_full: WaitQueue,
+}
+
+impl MessageQueue {
+    /// 队列属性
+    pub fn attr(&self) -> MqAttr {
+        self.attr
+    }
+
+    /// 当前的消息数
+    pub fn len(&self) -> usize {
+        self.messages.lock().list.len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 队列未满时放入消息，返回是否成功
+    fn try_send(&self, data: &[u8], priority: u32) -> bool {
+        let mut messages = self.messages.lock();
+        if messages.list.len() >= self.attr.maxmsg {
+            return false;
+        }
+        // 排在同优先级的已有消息之后
+        let index = messages.list.partition_point(|message| message.priority >= priority);
+        messages.list.insert(
+            index,
+            Message {
+                priority,
+                data: Vec::from(data),
+            },
+        );
+        messages.bytes += data.len();
+        true
+    }
+
+    /// 取出优先级最高的消息
+    fn try_receive(&self) -> Option<Message> {
+        let mut messages = self.messages.lock();
+        if messages.list.is_empty() {
+            return None;
+        }
+        let message = messages.list.remove(0);
+        messages.bytes -= message.data.len();
+        Some(message)
+    }
+
+    /// 按 `nonblock` 与 `deadline_ns` 等待 `ready` 返回真
+    fn wait(
+        queue: &WaitQueue,
+        ready: impl FnMut() -> bool,
+        nonblock: bool,
+        deadline_ns: Option<u64>,
+    ) -> Result<(), KernelError> {
+        if nonblock {
+            return Err(KernelError::WouldBlock);
+        }
+        match deadline_ns {
+            Some(deadline) => queue
+                .wait_until_deadline(ready, deadline)
+                .then_some(())
+                .ok_or(KernelError::TimedOut),
+            None => {
+                queue.wait_until(ready);
+                Ok(())
+            }
+        }
+    }
+
+    /// 状态行，格式与Linux的mqueue文件系统一致（不支持通知）
+    fn status(&self) -> String {
+        let bytes = self.messages.lock().bytes;
+        format!(
+            "QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}\n",
+            bytes, 0, 0, 0
+        )
+    }
+}
+
+/// 按名字索引的队列
+static QUEUES: Mutex<BTreeMap<String, Arc<MessageQueue>>> = Mutex::new(BTreeMap::new());
+
+/// 所有打开的队列，用于从描述符识别队列
+static MQ_FILES: Mutex<Vec<Weak<MqFile>>> = Mutex::new(Vec::new());
+
+/// 节点编号
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+/// 检查名字：非空、不含 `/` 且不超过 `NAME_MAX`
+fn check_name(name: &str) -> Result<(), KernelError> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." || name.len() > NAME_MAX {
+        return Err(KernelError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// 打开或创建名为 `name` 的队列，创建时使用 `attr`（`None` 为默认属性）
+///
+/// 已有的队列忽略 `attr`；`nonblock` 为描述符的初始非阻塞标志
+pub fn open(
+    name: &str,
+    options: &OpenOptions,
+    attr: Option<MqAttr>,
+    nonblock: bool,
+) -> Result<Arc<MqFile>, KernelError> {
+    check_name(name)?;
+    let cred = cred::current();
+    let mut queues = QUEUES.lock();
+    let queue = match queues.get(name) {
+        Some(_) if options.create && options.exclusive => return Err(KernelError::AlreadyExists),
+        Some(queue) => {
+            let may = if options.read { MAY_READ } else { 0 } | if options.write { MAY_WRITE } else { 0 };
+            if !cred.may_access(queue.uid, queue.gid, queue.mode, may) && !sched::capable(CAP_DAC_OVERRIDE) {
+                return Err(KernelError::AccessDenied);
+            }
+            queue.clone()
+        }
+        None if options.create => {
+            let attr = attr.unwrap_or(MqAttr::DEFAULT);
+            attr.check()?;
+            let queue = Arc::new(MessageQueue {
+                ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+                uid: cred.euid,
+                gid: cred.egid,
+                mode: options.mode & 0o777,
+                attr,
+                messages: Mutex::new(Messages {
+                    list: Vec::new(),
+                    bytes: 0,
+                }),
+                not_empty: WaitQueue::new(),
+                not_full: WaitQueue::new(),
+            });
+            queues.insert(String::from(name), queue.clone());
+            queue
+        }
+        None => return Err(KernelError::NotFound),
+    };
+    drop(queues);
+
+    let file = Arc::new(MqFile {
+        queue,
+        readable: options.read,
+        writable: options.write,
+        nonblock: AtomicBool::new(nonblock),
+        offset: Mutex::new(0),
+    });
+    let mut files = MQ_FILES.lock();
+    files.retain(|file| file.strong_count() > 0);
+    files.push(Arc::downgrade(&file));
+    Ok(file)
+}
+
+/// 删除名字，只有所有者或有 `CAP_DAC_OVERRIDE` 时可以删除
+pub fn unlink(name: &str) -> Result<(), KernelError> {
+    check_name(name)?;
+    let mut queues = QUEUES.lock();
+    let queue = queues.get(name).ok_or(KernelError::NotFound)?;
+    if queue.uid != cred::current().euid && !sched::capable(CAP_DAC_OVERRIDE) {
+        return Err(KernelError::AccessDenied);
+    }
+    queues.remove(name);
+    Ok(())
+}
+
+/// 查找描述符对应的队列
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别队列
+pub fn lookup(file: &Arc<dyn File>) -> Option<Arc<MqFile>> {
+    let target = Arc::as_ptr(file) as *const ();
+    MQ_FILES
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|mq| Arc::as_ptr(mq) as *const () == target)
+}
+
+/// 打开的消息队列
+pub struct MqFile {
+    queue: Arc<MessageQueue>,
+    readable: bool,
+    writable: bool,
+    nonblock: AtomicBool,
+    /// 读取状态行的位置
+    offset: Mutex<u64>,
+}
+
+impl MqFile {
+    /// 队列
+    pub fn queue(&self) -> &Arc<MessageQueue> {
+        &self.queue
+    }
+
+    /// 是否非阻塞
+    pub fn nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    /// 设置非阻塞标志
+    pub fn set_nonblock(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+
+    /// 以优先级 `priority` 发送消息，队列满时等待到单调时间 `deadline_ns`（`None` 为一直等待）
+    ///
+    /// 描述符不可写时返回 `PermissionDenied`，消息超过 `msgsize` 或优先级无效时返回 `InvalidArgument`
+    pub fn send(&self, data: &[u8], priority: u32, deadline_ns: Option<u64>) -> Result<(), KernelError> {
+        if !self.writable {
+            return Err(KernelError::PermissionDenied);
+        }
+        if data.len() > self.queue.attr.msgsize || priority >= MQ_PRIO_MAX {
+            return Err(KernelError::InvalidArgument);
+        }
+        let queue = &self.queue;
+        while !queue.try_send(data, priority) {
+            MessageQueue::wait(
+                &queue.not_full,
+                || queue.len() < queue.attr.maxmsg,
+                self.nonblock(),
+                deadline_ns,
+            )?;
+        }
+        queue.not_empty.wake_all();
+        Ok(())
+    }
+
+    /// 接收优先级最高的消息到 `buf`，返回长度与优先级；队列空时等待到 `deadline_ns`
+    ///
+    /// `buf` 小于 `msgsize` 时返回 `InvalidArgument`
+    pub fn receive(&self, buf: &mut [u8], deadline_ns: Option<u64>) -> Result<(usize, u32), KernelError> {
+        if !self.readable {
+            return Err(KernelError::PermissionDenied);
+        }
+        if buf.len() < self.queue.attr.msgsize {
+            return Err(KernelError::InvalidArgument);
+        }
+        let queue = &self.queue;
+        let message = loop {
+            match queue.try_receive() {
+                Some(message) => break message,
+                None => MessageQueue::wait(&queue.not_empty, || !queue.is_empty(), self.nonblock(), deadline_ns)?,
+            }
+        };
+        queue.not_full.wake_all();
+        buf[..message.data.len()].copy_from_slice(&message.data);
+        Ok((message.data.len(), message.priority))
+    }
+}
+
+impl File for MqFile {
+    /// 读取状态行
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        let status = self.queue.status();
+        let mut offset = self.offset.lock();
+        let start = (*offset as usize).min(status.len());
+        let len = buf.len().min(status.len() - start);
+        buf[..len].copy_from_slice(&status.as_bytes()[start..start + len]);
+        *offset += len as u64;
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<u64, KernelError> {
+        let mut offset = self.offset.lock();
+        let new = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => (self.queue.status().len() as u64).checked_add_signed(delta),
+        };
+        *offset = new.ok_or(KernelError::InvalidArgument)?;
+        Ok(*offset)
+    }
+
+    /// 有消息时可读，未满时可写
+    fn poll(&self) -> PollEvents {
+        let len = self.queue.len();
+        let mut events = PollEvents::empty();
+        if len > 0 {
+            events |= PollEvents::IN;
+        }
+        if len < self.queue.attr.maxmsg {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(&self.queue.not_empty);
+        table.wait(&self.queue.not_full);
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFREG | self.queue.mode,
+            uid: self.queue.uid,
+            gid: self.queue.gid,
+            ino: self.queue.ino,
+            size: self.queue.status().len() as u64,
+            ..FileStat::default()
+        }
+    }
+}
+
+crate::kernel_test! {
+    fn mqueue_priority_order() {
+        let options = OpenOptions {
+            create: true,
+            exclusive: true,
+            mode: 0o600,
+            ..OpenOptions::READ_WRITE
+        };
+        let attr = MqAttr { maxmsg: 3, msgsize: 16 };
+        let mq = open("ktest", &options, Some(attr), true).unwrap();
+        assert!(matches!(open("ktest", &options, None, true), Err(KernelError::AlreadyExists)));
+
+        mq.send(b"low", 1, None).unwrap();
+        mq.send(b"high", 5, None).unwrap();
+        mq.send(b"low2", 1, None).unwrap();
+        assert_eq!(mq.send(b"full", 9, None), Err(KernelError::WouldBlock));
+
+        let mut buf = [0u8; 16];
+        let mut received = Vec::new();
+        while let Ok((len, priority)) = mq.receive(&mut buf, None) {
+            received.push((Vec::from(&buf[..len]), priority));
+        }
+        assert_eq!(
+            received,
+            [(Vec::from(&b"high"[..]), 5), (Vec::from(&b"low"[..]), 1), (Vec::from(&b"low2"[..]), 1)]
+        );
+        assert_eq!(mq.receive(&mut buf[..8], None), Err(KernelError::InvalidArgument));
+        unlink("ktest").unwrap();
+    }
+}
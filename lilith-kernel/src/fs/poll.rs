@@ -0,0 +1,103 @@
+//! 等待文件就绪
+//!
+//! 本模块实现了同时等待多个文件就绪的机制，供poll/select/epoll使用：
+//! - 文件在 `File::poll_wait` 中登记状态改变时会被唤醒的等待队列
+//! - 不能在状态改变时唤醒等待者的文件（如轮询输入的控制台）要求定期重新检查
+//! - `wait_ready` 未就绪时挂到所有登记的等待队列上睡眠，被唤醒、超时或到达检查周期后重新检查
+//!
+//! 先把任务标记为阻塞再登记与检查，检查之后到来的唤醒不会丢失
+
+use crate::sched::{self, Task};
+use crate::sync::WaitQueue;
+use crate::time::{self, timer::Timer};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// 定期重新检查的周期
+pub const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+/// 一次检查中登记的等待队列，丢弃时把任务从队列中移除
+pub struct PollTable<'a> {
+    /// 要挂到队列上的任务，只检查不等待时为 `None`
+    task: Option<Arc<Task>>,
+    queues: Vec<&'a WaitQueue>,
+    /// 有文件要求定期重新检查
+    periodic: bool,
+}
+
+impl<'a> PollTable<'a> {
+    fn new(task: Option<Arc<Task>>) -> Self {
+        Self {
+            task,
+            queues: Vec::new(),
+            periodic: false,
+        }
+    }
+
+    /// 登记等待队列，队列被唤醒时重新检查
+    pub fn wait(&mut self, queue: &'a WaitQueue) {
+        if let Some(task) = &self.task {
+            queue.add_waiter(task);
+            self.queues.push(queue);
+        }
+    }
+
+    /// 要求每隔 `POLL_INTERVAL_NS` 重新检查
+    pub fn periodic(&mut self) {
+        self.periodic = true;
+    }
+}
+
+impl Drop for PollTable<'_> {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            for queue in &self.queues {
+                queue.remove_waiter(task);
+            }
+        }
+    }
+}
+
+/// 反复调用 `scan` 直到它返回 `Some`，或单调时间到达 `deadline_ns`（`None` 为一直等待）后返回 `None`
+///
+/// `scan` 检查所有文件并通过 `File::poll_wait` 登记等待队列。调度器尚未启动时退化为自旋
+pub fn wait_ready<'a, T>(deadline_ns: Option<u64>, mut scan: impl FnMut(&mut PollTable<'a>) -> Option<T>) -> Option<T> {
+    let task = sched::current();
+    loop {
+        if let Some(result) = scan(&mut PollTable::new(None)) {
+            return Some(result);
+        }
+        let now = time::monotonic_ns();
+        if deadline_ns.is_some_and(|deadline| now >= deadline) {
+            return None;
+        }
+        let Some(task) = &task else {
+            core::hint::spin_loop();
+            continue;
+        };
+
+        sched::set_current_blocked();
+        let mut table = PollTable::new(Some(task.clone()));
+        if let Some(result) = scan(&mut table) {
+            // 已标记为阻塞，唤醒自己后让出一次处理器
+            sched::wake(task);
+            sched::schedule();
+            return Some(result);
+        }
+        let period = table.periodic.then(|| now.saturating_add(POLL_INTERVAL_NS));
+        let timer = match (deadline_ns, period) {
+            (Some(deadline), Some(period)) => Some(deadline.min(period)),
+            (deadline, period) => deadline.or(period),
+        }
+        .map(|wake_at| {
+            let task = task.clone();
+            Timer::schedule_at_ns(wake_at, move || {
+                sched::wake(&task);
+            })
+        });
+        sched::schedule();
+        if let Some(timer) = timer {
+            timer.cancel();
+        }
+    }
+}
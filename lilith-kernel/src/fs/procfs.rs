@@ -0,0 +1,28 @@
+//! /proc 伪文件系统
+//!
+//! 对 kernfs 的简单封装，路径相对于 /proc
+
+use super::kernfs::{self, ReadFn, WriteFn};
+use crate::error::KernelError;
+use alloc::format;
+use alloc::string::String;
+
+/// 注册 /proc 下的文件
+pub fn register(name: &str, read: Option<ReadFn>, write: Option<WriteFn>) -> Result<(), KernelError> {
+    kernfs::register(&format!("/proc/{}", name), read, write)
+}
+
+/// 注销 /proc 下的文件
+pub fn unregister(name: &str) {
+    kernfs::unregister(&format!("/proc/{}", name));
+}
+
+/// 读取 /proc 下的文件
+pub fn read(name: &str) -> Result<String, KernelError> {
+    kernfs::read(&format!("/proc/{}", name))
+}
+
+/// 写入 /proc 下的文件
+pub fn write(name: &str, data: &str) -> Result<(), KernelError> {
+    kernfs::write(&format!("/proc/{}", name), data)
+}
@@ -0,0 +1,288 @@
+//! POSIX共享内存（/dev/shm）
+//!
+//! 本模块实现了以名字标识、保存在内核内存中的共享内存对象，包括：
+//! - 在 /dev/shm 下按名字创建与打开（C库的shm_open即为 `open("/dev/shm/名字")`）
+//! - ftruncate设置大小，新增的部分为0
+//! - 多个进程以 `MAP_SHARED` 映射同一对象时访问同一物理内存
+//! - unlink删除名字，已打开的描述符与已建立的映射仍然有效，最后一个引用消失时释放内存
+//! - 按对象的所有者与权限位检查打开权限，`CAP_DAC_OVERRIDE` 不受限制
+//!
+//! 对象的内存连续分配，存在映射时不能修改大小（返回 `ResourceBusy`）
+
+use super::file::{File, FileStat, SeekFrom, S_IFREG};
+use super::OpenOptions;
+use crate::error::KernelError;
+use crate::mm::page_owner::{alloc_pages, free_pages};
+use crate::mm::vma::{page_align_up, MapOwner, PAGE_SIZE};
+use crate::sched::cred::{self, MAY_READ, MAY_WRITE};
+use crate::sched::{self, capability::CAP_DAC_OVERRIDE};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 共享内存对象所在的目录
+pub const SHM_DIR: &str = "/dev/shm/";
+
+/// 名字的最大长度
+const NAME_MAX: usize = 255;
+
+/// 对象的内存，映射持有它的引用
+struct ShmPages {
+    memory: NonNull<u8>,
+    size: usize,
+}
+
+// 内存只通过复制访问，并发修改由用户态自行同步
+unsafe impl Send for ShmPages {}
+unsafe impl Sync for ShmPages {}
+
+impl ShmPages {
+    /// 分配 `size` 字节（按页取整）清零的内存
+    fn new(size: usize) -> Result<Self, KernelError> {
+        let memory = alloc_pages(size, "shm")?;
+        Ok(Self { memory, size: page_align_up(size) })
+    }
+
+    fn as_ptr(&self) -> *mut u8 {
+        self.memory.as_ptr()
+    }
+}
+
+impl Drop for ShmPages {
+    fn drop(&mut self) {
+        unsafe { free_pages(self.memory, self.size) };
+    }
+}
+
+/// 对象的大小与内存
+struct ShmData {
+    size: usize,
+    /// 大小为0时没有内存
+    pages: Option<Arc<ShmPages>>,
+}
+
+/// 共享内存对象
+pub struct ShmObject {
+    ino: u64,
+    uid: u32,
+    gid: u32,
+    mode: u32,
+    data: Mutex<ShmData>,
+}
+
+impl ShmObject {
+    /// 当前大小
+    pub fn size(&self) -> usize {
+        self.data.lock().size
+    }
+
+    /// 修改大小，保留原有内容，新增部分为0
+    fn resize(&self, size: usize) -> Result<(), KernelError> {
+        let mut data = self.data.lock();
+        if size == data.size {
+            return Ok(());
+        }
+        if data.pages.as_ref().is_some_and(|pages| Arc::strong_count(pages) > 1) {
+            return Err(KernelError::ResourceBusy);
+        }
+        let pages = (size > 0).then(|| ShmPages::new(size)).transpose()?.map(Arc::new);
+        if let (Some(old), Some(new)) = (&data.pages, &pages) {
+            unsafe { core::ptr::copy_nonoverlapping(old.as_ptr(), new.as_ptr(), data.size.min(size)) };
+        }
+        data.pages = pages;
+        data.size = size;
+        Ok(())
+    }
+}
+
+/// 按名字索引的对象
+static OBJECTS: Mutex<BTreeMap<String, Arc<ShmObject>>> = Mutex::new(BTreeMap::new());
+
+/// 节点编号
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+/// 检查名字：非空、不含 `/` 且不超过 `NAME_MAX`
+fn check_name(name: &str) -> Result<(), KernelError> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." || name.len() > NAME_MAX {
+        return Err(KernelError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// 打开或创建名为 `name` 的对象
+pub fn open(name: &str, options: &OpenOptions) -> Result<Arc<ShmFile>, KernelError> {
+    check_name(name)?;
+    let cred = cred::current();
+    let mut objects = OBJECTS.lock();
+    let object = match objects.get(name) {
+        Some(_) if options.create && options.exclusive => return Err(KernelError::AlreadyExists),
+        Some(object) => {
+            let may = if options.read { MAY_READ } else { 0 } | if options.write { MAY_WRITE } else { 0 };
+            if !cred.may_access(object.uid, object.gid, object.mode, may) && !sched::capable(CAP_DAC_OVERRIDE) {
+                return Err(KernelError::AccessDenied);
+            }
+            object.clone()
+        }
+        None if options.create => {
+            let object = Arc::new(ShmObject {
+                ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+                uid: cred.euid,
+                gid: cred.egid,
+                mode: options.mode & 0o777,
+                data: Mutex::new(ShmData { size: 0, pages: None }),
+            });
+            objects.insert(String::from(name), object.clone());
+            object
+        }
+        None => return Err(KernelError::NotFound),
+    };
+    drop(objects);
+
+    if options.truncate && options.write {
+        object.resize(0)?;
+    }
+    Ok(Arc::new(ShmFile {
+        object,
+        readable: options.read,
+        writable: options.write,
+        offset: Mutex::new(0),
+    }))
+}
+
+/// 删除名字，只有所有者或有 `CAP_DAC_OVERRIDE` 时可以删除
+pub fn unlink(name: &str) -> Result<(), KernelError> {
+    check_name(name)?;
+    let mut objects = OBJECTS.lock();
+    let object = objects.get(name).ok_or(KernelError::NotFound)?;
+    if object.uid != cred::current().euid && !sched::capable(CAP_DAC_OVERRIDE) {
+        return Err(KernelError::AccessDenied);
+    }
+    objects.remove(name);
+    Ok(())
+}
+
+/// 打开的共享内存对象
+pub struct ShmFile {
+    object: Arc<ShmObject>,
+    readable: bool,
+    writable: bool,
+    offset: Mutex<u64>,
+}
+
+impl File for ShmFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        let mut offset = self.offset.lock();
+        let len = self.read_at(*offset, buf)?;
+        *offset += len as u64;
+        Ok(len)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if !self.readable {
+            return Err(KernelError::PermissionDenied);
+        }
+        let data = self.object.data.lock();
+        let start = (offset as usize).min(data.size);
+        let len = buf.len().min(data.size - start);
+        if let Some(pages) = &data.pages {
+            unsafe { core::ptr::copy_nonoverlapping(pages.as_ptr().add(start), buf.as_mut_ptr(), len) };
+        }
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        let mut offset = self.offset.lock();
+        let len = self.write_at(*offset, buf)?;
+        *offset += len as u64;
+        Ok(len)
+    }
+
+    /// 只能写入已有的大小之内，超出部分不写入（需先ftruncate）
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize, KernelError> {
+        if !self.writable {
+            return Err(KernelError::PermissionDenied);
+        }
+        let data = self.object.data.lock();
+        let start = (offset as usize).min(data.size);
+        let len = buf.len().min(data.size - start);
+        if let Some(pages) = &data.pages {
+            unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), pages.as_ptr().add(start), len) };
+        }
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<u64, KernelError> {
+        let mut offset = self.offset.lock();
+        let new = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => (self.object.size() as u64).checked_add_signed(delta),
+        };
+        *offset = new.ok_or(KernelError::InvalidArgument)?;
+        Ok(*offset)
+    }
+
+    fn truncate(&self, len: u64) -> Result<(), KernelError> {
+        if !self.writable {
+            return Err(KernelError::InvalidArgument);
+        }
+        let len = usize::try_from(len).map_err(|_| KernelError::InvalidArgument)?;
+        self.object.resize(len)
+    }
+
+    fn mmap(&self, offset: usize, len: usize, writable: bool) -> Result<(usize, MapOwner), KernelError> {
+        if !self.readable || (writable && !self.writable) {
+            return Err(KernelError::AccessDenied);
+        }
+        let data = self.object.data.lock();
+        let end = offset.checked_add(len).ok_or(KernelError::InvalidArgument)?;
+        match &data.pages {
+            Some(pages) if offset % PAGE_SIZE == 0 && end <= pages.size => {
+                Ok((pages.as_ptr() as usize + offset, pages.clone() as MapOwner))
+            }
+            _ => Err(KernelError::InvalidArgument),
+        }
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFREG | self.object.mode,
+            uid: self.object.uid,
+            gid: self.object.gid,
+            ino: self.object.ino,
+            size: self.object.size() as u64,
+            ..FileStat::default()
+        }
+    }
+}
+
+crate::kernel_test! {
+    fn shm_shared_between_opens() {
+        let options = OpenOptions {
+            create: true,
+            exclusive: true,
+            mode: 0o600,
+            ..OpenOptions::READ_WRITE
+        };
+        let first = open("ktest", &options).unwrap();
+        assert!(matches!(open("ktest", &options), Err(KernelError::AlreadyExists)));
+        first.truncate(PAGE_SIZE as u64).unwrap();
+        assert_eq!(first.write(b"shared"), Ok(6));
+
+        let second = open("ktest", &OpenOptions::READ_WRITE).unwrap();
+        let mut buf = [0u8; 6];
+        assert_eq!(second.read(&mut buf), Ok(6));
+        assert_eq!(&buf, b"shared");
+
+        // 存在映射时不能修改大小，名字删除后映射仍然有效
+        let (addr, owner) = second.mmap(0, PAGE_SIZE, true).unwrap();
+        assert_eq!(first.truncate(0), Err(KernelError::ResourceBusy));
+        unlink("ktest").unwrap();
+        assert!(matches!(open("ktest", &OpenOptions::READ_WRITE), Err(KernelError::NotFound)));
+        assert_eq!(unsafe { *(addr as *const u8) }, b's');
+        drop(owner);
+    }
+}
@@ -0,0 +1,142 @@
+//! 信号文件（signalfd）
+//!
+//! 本模块实现了通过读取文件接收信号的机制，语义与Linux一致，包括：
+//! - 读取取出当前进程在集合中的待处理信号，每个信号填写一个128字节的 `signalfd_siginfo`
+//! - 没有信号时读取阻塞，非阻塞模式下返回 `WouldBlock`
+//! - 有集合中的待处理信号时可读，可以与poll/epoll一起使用
+//!
+//! 信号只有在被屏蔽时才会排队，使用前应先用 `rt_sigprocmask` 屏蔽集合中的信号。
+//! 文件不持有进程，读取的总是当前进程的信号
+
+use super::file::{File, FileStat, PollEvents};
+use super::poll::PollTable;
+use crate::error::KernelError;
+use crate::sched::signal::{SigInfo, SigSet};
+use crate::sched::{self, Process};
+use crate::sync::WaitQueue;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// `struct signalfd_siginfo` 的大小
+pub const SIGINFO_SIZE: usize = 128;
+
+/// 信号文件
+pub struct SignalFd {
+    /// 关心的信号
+    mask: Mutex<SigSet>,
+    nonblock: AtomicBool,
+    /// 创建者进程的信号等待队列
+    wait: Arc<WaitQueue>,
+}
+
+/// 所有信号文件，用于从描述符识别
+static SIGNAL_FDS: Mutex<Vec<Weak<SignalFd>>> = Mutex::new(Vec::new());
+
+impl SignalFd {
+    /// 为 `process` 创建关心 `mask` 的信号文件
+    pub fn new(process: &Process, mask: SigSet, nonblock: bool) -> Arc<Self> {
+        let signalfd = Arc::new(Self {
+            mask: Mutex::new(mask),
+            nonblock: AtomicBool::new(nonblock),
+            wait: process.signal_wait().clone(),
+        });
+        let mut files = SIGNAL_FDS.lock();
+        files.retain(|file| file.strong_count() > 0);
+        files.push(Arc::downgrade(&signalfd));
+        signalfd
+    }
+
+    /// 替换关心的信号
+    pub fn set_mask(&self, mask: SigSet) {
+        *self.mask.lock() = mask;
+        self.wait.wake_all();
+    }
+
+    /// 关心的信号中是否有待处理的
+    fn pending(&self) -> bool {
+        let mask = *self.mask.lock();
+        sched::current_process().is_some_and(|process| process.pending_signals().0 & mask.0 != 0)
+    }
+}
+
+/// 查找描述符对应的信号文件
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别
+pub fn lookup(file: &Arc<dyn File>) -> Option<Arc<SignalFd>> {
+    let target = Arc::as_ptr(file) as *const ();
+    SIGNAL_FDS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|signalfd| Arc::as_ptr(signalfd) as *const () == target)
+}
+
+/// 按 `struct signalfd_siginfo` 的布局填写一个信号
+fn encode(info: &SigInfo, record: &mut [u8]) {
+    record.fill(0);
+    record[0..4].copy_from_slice(&info.signo.to_ne_bytes());
+    record[8..12].copy_from_slice(&info.code.to_ne_bytes());
+    record[12..16].copy_from_slice(&(info.pid as u32).to_ne_bytes());
+    record[16..20].copy_from_slice(&info.uid.to_ne_bytes());
+}
+
+impl File for SignalFd {
+    /// 取出尽可能多的信号，`buf` 放不下一个信号时返回 `InvalidArgument`
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.len() < SIGINFO_SIZE {
+            return Err(KernelError::InvalidArgument);
+        }
+        let process = sched::current_process().ok_or(KernelError::InvalidArgument)?;
+        let mut len = 0;
+        for record in buf.chunks_exact_mut(SIGINFO_SIZE) {
+            let mask = *self.mask.lock();
+            let info = match process.dequeue_signal(mask) {
+                Some(info) => info,
+                None if len > 0 => break,
+                None if self.nonblock.load(Ordering::Relaxed) => return Err(KernelError::WouldBlock),
+                None => loop {
+                    self.wait.wait_until(|| self.pending());
+                    if let Some(info) = process.dequeue_signal(*self.mask.lock()) {
+                        break info;
+                    }
+                },
+            };
+            encode(&info, record);
+            len += SIGINFO_SIZE;
+        }
+        Ok(len)
+    }
+
+    fn poll(&self) -> PollEvents {
+        if self.pending() {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(&self.wait);
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat::default()
+    }
+}
+
+crate::kernel_test! {
+    fn signalfd_record_layout() {
+        use crate::sched::signal::{SIGUSR1, SI_USER};
+
+        let info = SigInfo { signo: SIGUSR1, code: SI_USER, pid: 7, uid: 1000 };
+        let mut record = [0xffu8; SIGINFO_SIZE];
+        encode(&info, &mut record);
+        assert_eq!(u32::from_ne_bytes(record[0..4].try_into().unwrap()), SIGUSR1);
+        assert_eq!(i32::from_ne_bytes(record[8..12].try_into().unwrap()), SI_USER);
+        assert_eq!(u32::from_ne_bytes(record[12..16].try_into().unwrap()), 7);
+        assert_eq!(u32::from_ne_bytes(record[16..20].try_into().unwrap()), 1000);
+        assert!(record[20..].iter().all(|&byte| byte == 0));
+    }
+}
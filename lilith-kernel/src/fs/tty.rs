@@ -0,0 +1,239 @@
+//! 控制终端与作业控制
+//!
+//! 本模块实现了控制台作为会话的控制终端，语义与Linux一致，包括：
+//! - 没有控制终端的会话首进程打开终端（未指定 `O_NOCTTY`）或用 `TIOCSCTTY` 取得控制终端
+//! - 查询与设置前台进程组（`TIOCGPGRP`/`TIOCSPGRP`）
+//! - 后台进程组读终端时向整个进程组发送 `SIGTTIN`，设置前台进程组时发送 `SIGTTOU`
+//! - 会话首进程放弃控制终端（`TIOCNOTTY`）或退出时向前台进程组发送 `SIGHUP` 与 `SIGCONT`
+//! - 输入子系统送来的字符进入输入缓冲区，`^C`/`^\`/`^Z` 向前台进程组发送 `SIGINT`/`SIGQUIT`/`SIGTSTP`
+//!
+//! 目前只有一个终端（控制台）。信号被屏蔽时不停止进程：读终端返回 `DeviceError`，
+//! 设置前台进程组照常进行。没有 `TOSTOP`，后台进程组可以直接写终端。
+//! 控制台设备自身的输入（如串口）由读者轮询，不经过输入缓冲区
+
+use crate::error::KernelError;
+use crate::sched::signal::{self, SigInfo, SI_KERNEL};
+use crate::sched::{self, capability, Process};
+use crate::sync::WaitQueue;
+use alloc::collections::VecDeque;
+use spin::Mutex;
+
+/// 输入缓冲区的容量，满时丢弃新到的字符
+const INPUT_MAX: usize = 4096;
+
+/// 产生信号的控制字符
+const VINTR: u8 = 0x03;
+const VQUIT: u8 = 0x1c;
+const VSUSP: u8 = 0x1a;
+
+/// 终端的会话与前台进程组
+struct Terminal {
+    /// 以本终端为控制终端的会话
+    session: Option<usize>,
+    /// 前台进程组
+    foreground: usize,
+    /// 输入缓冲区
+    input: VecDeque<u8>,
+}
+
+/// 控制台
+static CONSOLE: Mutex<Terminal> = Mutex::new(Terminal {
+    session: None,
+    foreground: 0,
+    input: VecDeque::new(),
+});
+
+/// 输入缓冲区有数据时唤醒
+static INPUT_WAIT: WaitQueue = WaitQueue::new();
+
+/// 向进程组 `pgid` 发送内核信号
+fn signal_group(pgid: usize, sig: u32) {
+    let info = SigInfo {
+        signo: sig,
+        code: SI_KERNEL,
+        pid: 0,
+        uid: 0,
+    };
+    for process in sched::process_group(pgid) {
+        process.send_signal(info);
+    }
+}
+
+/// 控制台是否为 `process` 的控制终端
+pub fn is_controlling(process: &Process) -> bool {
+    CONSOLE.lock().session == Some(process.sid())
+}
+
+/// 打开终端时，没有控制终端的会话首进程取得控制终端
+pub fn open_console(process: &Process) {
+    let mut console = CONSOLE.lock();
+    if process.is_session_leader() && console.session.is_none() {
+        console.session = Some(process.sid());
+        console.foreground = process.pgid();
+    }
+}
+
+/// `TIOCSCTTY`：会话首进程取得控制终端
+///
+/// 终端属于其他会话时，只有 `steal` 且有 `CAP_SYS_ADMIN` 才能夺取
+pub fn set_controlling(process: &Process, steal: bool) -> Result<(), KernelError> {
+    let mut console = CONSOLE.lock();
+    if console.session == Some(process.sid()) {
+        return Ok(());
+    }
+    if !process.is_session_leader() {
+        return Err(KernelError::PermissionDenied);
+    }
+    if console.session.is_some() && !(steal && capability::capable(capability::CAP_SYS_ADMIN)) {
+        return Err(KernelError::PermissionDenied);
+    }
+    console.session = Some(process.sid());
+    console.foreground = process.pgid();
+    Ok(())
+}
+
+/// `TIOCNOTTY`：放弃控制终端，会话首进程放弃时整个会话失去控制终端
+pub fn release(process: &Process) -> Result<(), KernelError> {
+    if !is_controlling(process) {
+        return Err(KernelError::NotTty);
+    }
+    if process.is_session_leader() {
+        hangup_session(process.sid());
+    }
+    Ok(())
+}
+
+/// 会话首进程退出：会话失去控制终端，前台进程组收到 `SIGHUP` 与 `SIGCONT`
+pub fn hangup_session(sid: usize) {
+    let foreground = {
+        let mut console = CONSOLE.lock();
+        if console.session != Some(sid) {
+            return;
+        }
+        console.session = None;
+        console.foreground
+    };
+    signal_group(foreground, signal::SIGHUP);
+    signal_group(foreground, signal::SIGCONT);
+}
+
+/// `TIOCGPGRP`：前台进程组
+pub fn foreground(process: &Process) -> Result<usize, KernelError> {
+    let console = CONSOLE.lock();
+    if console.session != Some(process.sid()) {
+        return Err(KernelError::NotTty);
+    }
+    Ok(console.foreground)
+}
+
+/// 后台进程组的进程访问终端时，向它的进程组发送 `sig` 并等待被继续运行
+///
+/// 返回 `Ok(true)` 表示可以继续访问，`sig` 被屏蔽时返回 `Ok(false)`，等待期间进程退出时返回 `DeviceError`
+fn job_control(process: &Process, sig: u32) -> Result<bool, KernelError> {
+    loop {
+        let console = CONSOLE.lock();
+        if console.session != Some(process.sid()) || console.foreground == process.pgid() {
+            return Ok(true);
+        }
+        drop(console);
+        if process.signal_mask().contains(sig) {
+            return Ok(false);
+        }
+        signal_group(process.pgid(), sig);
+        process.stop_point();
+        if process.is_exiting() {
+            return Err(KernelError::DeviceError);
+        }
+    }
+}
+
+/// `TIOCSPGRP`：设置前台进程组，进程组必须属于同一会话
+///
+/// 进程组不存在时返回 `NotFound`
+pub fn set_foreground(process: &Process, pgid: usize) -> Result<(), KernelError> {
+    if !is_controlling(process) {
+        return Err(KernelError::NotTty);
+    }
+    job_control(process, signal::SIGTTOU)?;
+    let group = sched::process_group(pgid);
+    if group.is_empty() {
+        return Err(KernelError::NotFound);
+    }
+    if group.iter().any(|member| member.sid() != process.sid()) {
+        return Err(KernelError::PermissionDenied);
+    }
+    let mut console = CONSOLE.lock();
+    if console.session != Some(process.sid()) {
+        return Err(KernelError::NotTty);
+    }
+    console.foreground = pgid;
+    Ok(())
+}
+
+/// 读控制台前的检查：后台进程组被 `SIGTTIN` 停止，信号被屏蔽时返回 `DeviceError`
+pub fn check_read() -> Result<(), KernelError> {
+    let Some(process) = sched::current_process() else {
+        return Ok(());
+    };
+    match job_control(&process, signal::SIGTTIN)? {
+        true => Ok(()),
+        false => Err(KernelError::DeviceError),
+    }
+}
+
+/// 输入子系统送来的字符，控制字符向前台进程组发送信号，其余进入输入缓冲区
+pub fn receive(bytes: &[u8]) {
+    let mut signals = alloc::vec::Vec::new();
+    {
+        let mut console = CONSOLE.lock();
+        for &byte in bytes {
+            let sig = match byte {
+                VINTR => signal::SIGINT,
+                VQUIT => signal::SIGQUIT,
+                VSUSP => signal::SIGTSTP,
+                _ => {
+                    if console.input.len() < INPUT_MAX {
+                        console.input.push_back(byte);
+                    }
+                    continue;
+                }
+            };
+            // 与Linux一样，产生信号时丢弃尚未读取的输入
+            console.input.clear();
+            if console.session.is_some() {
+                signals.push((console.foreground, sig));
+            }
+        }
+    }
+    for (pgid, sig) in signals {
+        signal_group(pgid, sig);
+    }
+    INPUT_WAIT.wake_all();
+}
+
+/// 从输入缓冲区取出一个字符
+pub fn read_input() -> Option<u8> {
+    CONSOLE.lock().input.pop_front()
+}
+
+/// 输入缓冲区是否有数据
+pub fn input_pending() -> bool {
+    !CONSOLE.lock().input.is_empty()
+}
+
+/// 输入缓冲区有数据时唤醒的等待队列
+pub fn input_wait() -> &'static WaitQueue {
+    &INPUT_WAIT
+}
+
+crate::kernel_test! {
+    fn tty_input_signal_chars() {
+        let _ = core::iter::from_fn(read_input).count();
+        receive(b"ab");
+        assert!(input_pending());
+        receive(&[VINTR]);
+        assert!(!input_pending());
+        receive(b"c\n");
+        assert_eq!(core::iter::from_fn(read_input).collect::<alloc::vec::Vec<u8>>(), b"c\n");
+    }
+}
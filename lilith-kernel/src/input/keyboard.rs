@@ -0,0 +1,153 @@
+//! 键盘到终端的翻译
+//!
+//! 本模块实现了把按键事件翻译为终端输入的处理者，行为与Linux控制台一致，包括：
+//! - 跟踪Shift、Ctrl、Alt的按下状态与CapsLock的开关
+//! - 按当前键位表翻译主键区，CapsLock只影响字母
+//! - Ctrl与字母等组合产生控制字符，Alt在字符前加ESC
+//! - 方向键与编辑键产生VT100转义序列
+//! - 按下与自动重复产生输入，松开只更新修饰键
+//!
+//! 翻译结果送入控制台终端，`^C` 等控制字符由终端转换为信号
+
+use super::keymap::{self, *};
+use super::{InputEvent, InputHandler, EV_KEY, KEY_PRESSED, KEY_RELEASED};
+use crate::fs::tty;
+use spin::Mutex;
+
+/// 修饰键状态
+#[derive(Debug, Default, Clone, Copy)]
+struct Modifiers {
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+    capslock: bool,
+}
+
+/// 把按键送入终端的键盘处理者
+pub struct Keyboard {
+    modifiers: Mutex<Modifiers>,
+}
+
+impl Keyboard {
+    pub const fn new() -> Self {
+        Self {
+            modifiers: Mutex::new(Modifiers {
+                shift: false,
+                ctrl: false,
+                alt: false,
+                capslock: false,
+            }),
+        }
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 功能键的转义序列
+fn escape_sequence(code: u16) -> Option<&'static [u8]> {
+    Some(match code {
+        KEY_UP => b"\x1b[A",
+        KEY_DOWN => b"\x1b[B",
+        KEY_RIGHT => b"\x1b[C",
+        KEY_LEFT => b"\x1b[D",
+        KEY_HOME => b"\x1b[H",
+        KEY_END => b"\x1b[F",
+        KEY_INSERT => b"\x1b[2~",
+        KEY_DELETE => b"\x1b[3~",
+        KEY_PAGEUP => b"\x1b[5~",
+        KEY_PAGEDOWN => b"\x1b[6~",
+        _ => return None,
+    })
+}
+
+/// 按修饰键状态翻译一个按下的键，结果写入 `out`，返回长度
+fn translate(modifiers: Modifiers, code: u16, out: &mut [u8; 8]) -> usize {
+    if let Some(sequence) = escape_sequence(code) {
+        out[..sequence.len()].copy_from_slice(sequence);
+        return sequence.len();
+    }
+    let keymap = keymap::active();
+    let Some(plain) = keymap.translate(code, false) else {
+        return 0;
+    };
+    let shift = modifiers.shift ^ (modifiers.capslock && plain.is_ascii_lowercase());
+    let mut byte = keymap.translate(code, shift).unwrap_or(plain);
+    if modifiers.ctrl {
+        byte = match byte {
+            b'?' => 0x7f,
+            b' ' | b'2' | b'@' => 0,
+            b'@'..=b'_' | b'a'..=b'z' => byte & 0x1f,
+            _ => byte,
+        };
+    }
+    let mut len = 0;
+    if modifiers.alt {
+        out[0] = 0x1b;
+        len = 1;
+    }
+    out[len] = byte;
+    len + 1
+}
+
+impl InputHandler for Keyboard {
+    fn name(&self) -> &str {
+        "keyboard"
+    }
+
+    fn event(&self, event: &InputEvent) {
+        if event.kind != EV_KEY {
+            return;
+        }
+        let pressed = event.value != KEY_RELEASED;
+        let modifiers = {
+            let mut modifiers = self.modifiers.lock();
+            match event.code {
+                KEY_LEFTSHIFT | KEY_RIGHTSHIFT => modifiers.shift = pressed,
+                KEY_LEFTCTRL | KEY_RIGHTCTRL => modifiers.ctrl = pressed,
+                KEY_LEFTALT | KEY_RIGHTALT => modifiers.alt = pressed,
+                KEY_CAPSLOCK if event.value == KEY_PRESSED => modifiers.capslock = !modifiers.capslock,
+                _ => {}
+            }
+            *modifiers
+        };
+        if !pressed {
+            return;
+        }
+        let mut out = [0u8; 8];
+        let len = translate(modifiers, event.code, &mut out);
+        if len > 0 {
+            tty::receive(&out[..len]);
+        }
+    }
+}
+
+crate::kernel_test! {
+    fn keyboard_translation() {
+        let mut out = [0u8; 8];
+        let plain = Modifiers::default();
+        let len = translate(plain, 30, &mut out);
+        assert_eq!(&out[..len], b"a");
+
+        let caps = Modifiers { capslock: true, ..plain };
+        let len = translate(caps, 30, &mut out);
+        assert_eq!(&out[..len], b"A");
+        let len = translate(caps, 2, &mut out);
+        assert_eq!(&out[..len], b"1");
+
+        let ctrl = Modifiers { ctrl: true, ..plain };
+        let len = translate(ctrl, 46, &mut out);
+        assert_eq!(&out[..len], [0x03]);
+
+        let alt = Modifiers { alt: true, ..plain };
+        let len = translate(alt, 48, &mut out);
+        assert_eq!(&out[..len], b"\x1bb");
+
+        let len = translate(plain, KEY_UP, &mut out);
+        assert_eq!(&out[..len], b"\x1b[A");
+        assert_eq!(translate(plain, KEY_LEFTSHIFT, &mut out), 0);
+    }
+}
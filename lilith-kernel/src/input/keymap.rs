@@ -0,0 +1,164 @@
+//! 按键编码与键位表
+//!
+//! 本模块定义了按键编码与把按键翻译为字符的键位表，包括：
+//! - 按键编码与Linux一致；PS/2第1套扫描码的基本键区与编码1到88相同，驱动可以直接使用
+//! - 键位表给出主键区每个键不按与按住Shift时的字符，0表示不产生字符
+//! - 内置美式（us）与Dvorak（dvorak）键位表，模块可以注册更多键位表
+//! - 按名字切换当前键位表：启动参数 `keymap=`，或写入 /sys/kernel/keymap
+//!
+//! 方向键、编辑键等功能键不在键位表中，由键盘处理者翻译为转义序列。
+//! 键位表只能产生ASCII字符
+
+use crate::boot::cmdline;
+use crate::error::KernelError;
+use crate::fs::kernfs;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 按键编码（取值与Linux一致）
+pub const KEY_ESC: u16 = 1;
+pub const KEY_BACKSPACE: u16 = 14;
+pub const KEY_TAB: u16 = 15;
+pub const KEY_ENTER: u16 = 28;
+pub const KEY_LEFTCTRL: u16 = 29;
+pub const KEY_LEFTSHIFT: u16 = 42;
+pub const KEY_RIGHTSHIFT: u16 = 54;
+pub const KEY_LEFTALT: u16 = 56;
+pub const KEY_SPACE: u16 = 57;
+pub const KEY_CAPSLOCK: u16 = 58;
+pub const KEY_NUMLOCK: u16 = 69;
+pub const KEY_SCROLLLOCK: u16 = 70;
+pub const KEY_KPENTER: u16 = 96;
+pub const KEY_RIGHTCTRL: u16 = 97;
+pub const KEY_KPSLASH: u16 = 98;
+pub const KEY_SYSRQ: u16 = 99;
+pub const KEY_RIGHTALT: u16 = 100;
+pub const KEY_HOME: u16 = 102;
+pub const KEY_UP: u16 = 103;
+pub const KEY_PAGEUP: u16 = 104;
+pub const KEY_LEFT: u16 = 105;
+pub const KEY_RIGHT: u16 = 106;
+pub const KEY_END: u16 = 107;
+pub const KEY_DOWN: u16 = 108;
+pub const KEY_PAGEDOWN: u16 = 109;
+pub const KEY_INSERT: u16 = 110;
+pub const KEY_DELETE: u16 = 111;
+pub const KEY_PAUSE: u16 = 119;
+pub const KEY_LEFTMETA: u16 = 125;
+pub const KEY_RIGHTMETA: u16 = 126;
+pub const KEY_COMPOSE: u16 = 127;
+
+/// 键位表覆盖的按键编码范围（主键区，`0..KEYMAP_SIZE`）
+pub const KEYMAP_SIZE: usize = 58;
+
+/// 键位表
+pub struct Keymap {
+    /// 名称（如 `us`）
+    pub name: &'static str,
+    /// 不按Shift时的字符
+    pub plain: [u8; KEYMAP_SIZE],
+    /// 按住Shift时的字符
+    pub shifted: [u8; KEYMAP_SIZE],
+}
+
+impl Keymap {
+    /// 按键 `code` 对应的字符，不产生字符时返回 `None`
+    pub fn translate(&self, code: u16, shift: bool) -> Option<u8> {
+        let table = if shift { &self.shifted } else { &self.plain };
+        table.get(code as usize).copied().filter(|&byte| byte != 0)
+    }
+}
+
+/// 美式键位表，退格键产生DEL（0x7f），与Linux控制台一致
+pub static US: Keymap = Keymap {
+    name: "us",
+    plain: *b"\0\x1b1234567890-=\x7f\tqwertyuiop[]\n\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ",
+    shifted: *b"\0\x1b!@#$%^&*()_+\x7f\tQWERTYUIOP{}\n\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ",
+};
+
+/// 美式Dvorak键位表
+pub static DVORAK: Keymap = Keymap {
+    name: "dvorak",
+    plain: *b"\0\x1b1234567890[]\x7f\t',.pyfgcrl/=\n\0aoeuidhtns-`\0\\;qjkxbmwvz\0*\0 ",
+    shifted: *b"\0\x1b!@#$%^&*(){}\x7f\t\"<>PYFGCRL?+\n\0AOEUIDHTNS_~\0|:QJKXBMWVZ\0*\0 ",
+};
+
+/// 当前使用的键位表
+static ACTIVE: Mutex<&'static Keymap> = Mutex::new(&US);
+
+/// 模块注册的键位表
+static REGISTERED: Mutex<Vec<&'static Keymap>> = Mutex::new(Vec::new());
+
+/// 当前使用的键位表
+pub fn active() -> &'static Keymap {
+    *ACTIVE.lock()
+}
+
+/// 切换键位表
+pub fn set_active(keymap: &'static Keymap) {
+    *ACTIVE.lock() = keymap;
+}
+
+/// 所有可用的键位表，内置的在前
+pub fn keymaps() -> Vec<&'static Keymap> {
+    let mut keymaps: Vec<&'static Keymap> = alloc::vec![&US, &DVORAK];
+    keymaps.extend(REGISTERED.lock().iter());
+    keymaps
+}
+
+/// 按名字查找键位表
+pub fn find(name: &str) -> Option<&'static Keymap> {
+    keymaps().into_iter().find(|keymap| keymap.name == name)
+}
+
+/// 注册键位表，同名的键位表已存在时返回 `AlreadyExists`
+pub fn register(keymap: &'static Keymap) -> Result<(), KernelError> {
+    if find(keymap.name).is_some() {
+        return Err(KernelError::AlreadyExists);
+    }
+    REGISTERED.lock().push(keymap);
+    Ok(())
+}
+
+/// 注销模块注册的键位表，正在使用时切换回美式键位表
+pub fn unregister(name: &str) {
+    REGISTERED.lock().retain(|keymap| keymap.name != name);
+    let mut active = ACTIVE.lock();
+    if active.name == name {
+        *active = &US;
+    }
+}
+
+/// 按名字切换键位表，不存在时返回 `NotFound`
+pub fn select(name: &str) -> Result<(), KernelError> {
+    set_active(find(name).ok_or(KernelError::NotFound)?);
+    Ok(())
+}
+
+/// 按启动参数 `keymap=` 选择键位表，注册 /sys/kernel/keymap
+///
+/// 读取列出所有键位表，当前使用的加方括号；写入名字切换键位表
+pub fn keymap_init() -> Result<(), KernelError> {
+    if let Some(name) = cmdline::get("keymap") {
+        if select(name).is_err() {
+            crate::log_warn!("未知的键位表: {}", name);
+        }
+    }
+    kernfs::register(
+        "/sys/kernel/keymap",
+        Some(Box::new(|| {
+            let active = active().name;
+            let names: Vec<String> = keymaps()
+                .iter()
+                .map(|keymap| match keymap.name == active {
+                    true => alloc::format!("[{}]", keymap.name),
+                    false => String::from(keymap.name),
+                })
+                .collect();
+            alloc::format!("{}\n", names.join(" "))
+        })),
+        Some(Box::new(|data| select(data.trim()))),
+    )
+}
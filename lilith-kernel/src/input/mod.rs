@@ -0,0 +1,118 @@
+//! 输入子系统
+//!
+//! 本模块实现了与具体设备无关的输入事件层，包括：
+//! - 事件类型与按键编码与Linux的 `input-event-codes.h` 一致
+//! - 设备驱动（PS/2键盘、virtio-input等）把硬件报告转换为事件后调用 `report`
+//! - 事件分发给所有已注册的处理者，处理者互不影响
+//! - 内置的键盘处理者按键位表把按键翻译为字符，送入当前终端（见 `keyboard`）
+//! - PS/2键盘的第1套扫描码与鼠标数据包解码为事件（见 `ps2`）
+//! - 所有鼠标合并为 /dev/input/mice（见 `mousedev`）
+//!
+//! 驱动只负责编码转换，键位表、修饰键与终端的交互全部在本层完成
+
+pub mod keyboard;
+pub mod keymap;
+pub mod mousedev;
+pub mod ps2;
+
+use crate::error::KernelError;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 事件类型（取值与Linux一致）
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+
+/// `EV_SYN` 的编码：一组事件结束
+pub const SYN_REPORT: u16 = 0;
+
+/// `EV_REL` 的编码
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_WHEEL: u16 = 0x08;
+
+/// 鼠标按键的编码（`EV_KEY`）
+pub const BTN_LEFT: u16 = 0x110;
+pub const BTN_RIGHT: u16 = 0x111;
+pub const BTN_MIDDLE: u16 = 0x112;
+
+/// `EV_KEY` 的值
+pub const KEY_RELEASED: i32 = 0;
+pub const KEY_PRESSED: i32 = 1;
+pub const KEY_REPEATED: i32 = 2;
+
+/// 一个输入事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// 事件类型（`EV_*`）
+    pub kind: u16,
+    /// 类型内的编码（如按键编码 `KEY_*`）
+    pub code: u16,
+    /// 值（按键为按下/松开/重复，相对坐标为位移）
+    pub value: i32,
+}
+
+/// 输入事件的处理者
+pub trait InputHandler: Send + Sync {
+    /// 名称，用于注销
+    fn name(&self) -> &str;
+
+    /// 处理一个事件，在报告事件的上下文（可能是中断）中调用，不能睡眠
+    fn event(&self, event: &InputEvent);
+}
+
+/// 已注册的处理者
+static HANDLERS: Mutex<Vec<Arc<dyn InputHandler>>> = Mutex::new(Vec::new());
+
+/// 注册处理者，同名的处理者已存在时返回 `AlreadyExists`
+pub fn register_handler(handler: Arc<dyn InputHandler>) -> Result<(), KernelError> {
+    let mut handlers = HANDLERS.lock();
+    if handlers.iter().any(|h| h.name() == handler.name()) {
+        return Err(KernelError::AlreadyExists);
+    }
+    handlers.push(handler);
+    Ok(())
+}
+
+/// 注销处理者
+pub fn unregister_handler(name: &str) {
+    HANDLERS.lock().retain(|handler| handler.name() != name);
+}
+
+/// 报告一个事件，分发给所有处理者
+///
+/// 分发时不持有处理者列表的锁，处理者可以注册或注销其他处理者
+pub fn report(event: InputEvent) {
+    let handlers = HANDLERS.lock().clone();
+    for handler in &handlers {
+        handler.event(&event);
+    }
+}
+
+/// 结束一组事件
+pub fn report_sync() {
+    report(InputEvent {
+        kind: EV_SYN,
+        code: SYN_REPORT,
+        value: 0,
+    });
+}
+
+/// 报告按键状态的变化并结束这一组事件
+pub fn report_key(code: u16, value: i32) {
+    report(InputEvent {
+        kind: EV_KEY,
+        code,
+        value,
+    });
+    report_sync();
+}
+
+/// 输入子系统初始化：选择键位表，注册键盘与 /dev/input/mice 的处理者
+pub fn input_init() -> Result<(), KernelError> {
+    keymap::keymap_init()?;
+    register_handler(Arc::new(keyboard::Keyboard::new()))?;
+    register_handler(Arc::new(mousedev::Mousedev::new()))
+}
@@ -0,0 +1,302 @@
+//! 合并的鼠标设备（/dev/input/mice）
+//!
+//! 本模块实现了把所有鼠标的相对位移与按键合并为PS/2数据包流的字符设备，行为与Linux的mousedev一致，包括：
+//! - 输入层的处理者累积 `EV_REL` 位移与鼠标按键，每组事件结束时向所有打开者发送数据包
+//! - 默认发送3字节的标准PS/2数据包，单个数据包的位移限制在±127，剩余部分随后续数据包发出
+//! - 打开者写入IntelliMouse的采样率序列后切换为4字节数据包（ImPS/2），第4字节为滚轮位移
+//! - 写入的每个命令字节都以 `FA` 应答，读取设备号（`F2`）还返回设备号
+//! - 读取在没有数据时阻塞，非阻塞打开时返回 `WouldBlock`，支持poll
+//!
+//! 每个打开者有独立的缓冲区，缓冲区满时丢弃最旧的字节
+
+use super::ps2::{INTELLIMOUSE_ID, INTELLIMOUSE_KNOCK, MOUSE_GET_ID};
+use super::{InputEvent, InputHandler, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, EV_KEY, EV_REL, EV_SYN, KEY_RELEASED};
+use super::{REL_WHEEL, REL_X, REL_Y, SYN_REPORT};
+use crate::error::KernelError;
+use crate::fs::file::{File, FileStat, PollEvents, S_IFCHR};
+use crate::fs::poll::PollTable;
+use crate::sync::WaitQueue;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 设备的主次设备号（input 13:63）
+const MICE_RDEV: u64 = (13 << 8) | 63;
+
+/// 每个打开者缓冲的最大字节数
+const QUEUE_CAPACITY: usize = 256;
+
+/// 命令的应答
+const MOUSE_ACK: u8 = 0xfa;
+
+/// 数据包中单个位移的范围
+const PACKET_LIMIT: i32 = 127;
+
+/// 累积的鼠标状态
+#[derive(Debug, Default, Clone, Copy)]
+struct MouseState {
+    /// 按下的按键，位0到2为左、右、中键
+    buttons: u8,
+    /// 自上次发送以来的位移，按PS/2的方向：Y向上、滚轮向后滚为正
+    dx: i32,
+    dy: i32,
+    dz: i32,
+}
+
+/// 一个打开者的协议与缓冲区
+struct Client {
+    /// 已切换为ImPS/2的4字节数据包
+    imps2: bool,
+    /// 最近写入的字节，用于识别采样率序列
+    history: [u8; INTELLIMOUSE_KNOCK.len()],
+    /// 待读取的字节
+    queue: VecDeque<u8>,
+}
+
+impl Client {
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.queue.len() == QUEUE_CAPACITY {
+                self.queue.pop_front();
+            }
+            self.queue.push_back(byte);
+        }
+    }
+
+    /// 处理写入的命令字节
+    fn command(&mut self, byte: u8) {
+        self.history.rotate_left(1);
+        *self.history.last_mut().unwrap() = byte;
+        if self.history == INTELLIMOUSE_KNOCK {
+            self.imps2 = true;
+        }
+        self.push(&[MOUSE_ACK]);
+        if byte == MOUSE_GET_ID {
+            self.push(&[if self.imps2 { INTELLIMOUSE_ID } else { 0 }]);
+        }
+    }
+}
+
+/// 从累积的位移中取出一个数据包，位移超出范围的部分留在 `state` 中
+fn encode_packet(state: &mut MouseState, imps2: bool) -> ([u8; 4], usize) {
+    let dx = state.dx.clamp(-PACKET_LIMIT, PACKET_LIMIT);
+    let dy = state.dy.clamp(-PACKET_LIMIT, PACKET_LIMIT);
+    state.dx -= dx;
+    state.dy -= dy;
+
+    let flags = 0x08 | state.buttons | u8::from(dx < 0) << 4 | u8::from(dy < 0) << 5;
+    if !imps2 {
+        return ([flags, dx as u8, dy as u8, 0], 3);
+    }
+    let dz = state.dz.clamp(-PACKET_LIMIT, PACKET_LIMIT);
+    state.dz -= dz;
+    ([flags, dx as u8, dy as u8, dz as u8], 4)
+}
+
+/// 打开的 /dev/input/mice
+pub struct MiceFile {
+    client: Mutex<Client>,
+    nonblock: AtomicBool,
+    /// 等待数据的读者
+    wait: WaitQueue,
+}
+
+/// 所有打开者，处理者向其中发送数据包
+static CLIENTS: Mutex<Vec<Weak<MiceFile>>> = Mutex::new(Vec::new());
+
+/// 打开 /dev/input/mice
+pub fn open(nonblock: bool) -> Arc<MiceFile> {
+    let file = Arc::new(MiceFile {
+        client: Mutex::new(Client {
+            imps2: false,
+            history: [0; INTELLIMOUSE_KNOCK.len()],
+            queue: VecDeque::new(),
+        }),
+        nonblock: AtomicBool::new(nonblock),
+        wait: WaitQueue::new(),
+    });
+    let mut clients = CLIENTS.lock();
+    clients.retain(|client| client.strong_count() > 0);
+    clients.push(Arc::downgrade(&file));
+    file
+}
+
+impl MiceFile {
+    fn readable(&self) -> bool {
+        !self.client.lock().queue.is_empty()
+    }
+
+    /// 把累积的状态编码为数据包放入缓冲区
+    fn send(&self, state: &MouseState) {
+        {
+            let mut client = self.client.lock();
+            let imps2 = client.imps2;
+            let mut state = *state;
+            loop {
+                let (packet, len) = encode_packet(&mut state, imps2);
+                client.push(&packet[..len]);
+                if state.dx == 0 && state.dy == 0 && (!imps2 || state.dz == 0) {
+                    break;
+                }
+            }
+        }
+        self.wait.wake_all();
+    }
+}
+
+impl File for MiceFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            {
+                let mut client = self.client.lock();
+                if !client.queue.is_empty() {
+                    let len = buf.len().min(client.queue.len());
+                    for (slot, byte) in buf.iter_mut().zip(client.queue.drain(..len)) {
+                        *slot = byte;
+                    }
+                    return Ok(len);
+                }
+            }
+            if self.nonblock.load(Ordering::Relaxed) {
+                return Err(KernelError::WouldBlock);
+            }
+            self.wait.wait_until(|| self.readable());
+        }
+    }
+
+    /// 写入PS/2鼠标命令，应答放入读取缓冲区
+    fn write(&self, buf: &[u8]) -> Result<usize, KernelError> {
+        {
+            let mut client = self.client.lock();
+            for &byte in buf {
+                client.command(byte);
+            }
+        }
+        if !buf.is_empty() {
+            self.wait.wake_all();
+        }
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> PollEvents {
+        if self.readable() {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+
+    fn poll_wait<'a>(&'a self, table: &mut PollTable<'a>) {
+        table.wait(&self.wait);
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o600,
+            rdev: MICE_RDEV,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 把鼠标事件合并后送往 /dev/input/mice 的处理者
+pub struct Mousedev {
+    state: Mutex<MouseState>,
+    /// 自上次发送以来是否有变化
+    dirty: AtomicBool,
+}
+
+impl Mousedev {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(MouseState {
+                buttons: 0,
+                dx: 0,
+                dy: 0,
+                dz: 0,
+            }),
+            dirty: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for Mousedev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputHandler for Mousedev {
+    fn name(&self) -> &str {
+        "mousedev"
+    }
+
+    fn event(&self, event: &InputEvent) {
+        match (event.kind, event.code) {
+            (EV_REL, code) => {
+                let mut state = self.state.lock();
+                match code {
+                    REL_X => state.dx += event.value,
+                    // 输入层的Y轴与滚轮与PS/2的方向相反
+                    REL_Y => state.dy -= event.value,
+                    REL_WHEEL => state.dz -= event.value,
+                    _ => return,
+                }
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+            (EV_KEY, BTN_LEFT | BTN_RIGHT | BTN_MIDDLE) => {
+                let bit = 1 << (event.code - BTN_LEFT);
+                let mut state = self.state.lock();
+                if event.value == KEY_RELEASED {
+                    state.buttons &= !bit;
+                } else {
+                    state.buttons |= bit;
+                }
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+            (EV_SYN, SYN_REPORT) if self.dirty.swap(false, Ordering::Relaxed) => {
+                let state = {
+                    let mut state = self.state.lock();
+                    let snapshot = *state;
+                    state.dx = 0;
+                    state.dy = 0;
+                    state.dz = 0;
+                    snapshot
+                };
+                let clients: Vec<_> = CLIENTS.lock().iter().filter_map(Weak::upgrade).collect();
+                for client in clients {
+                    client.send(&state);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+crate::kernel_test! {
+    fn mousedev_packets() {
+        let mice = open(true);
+        let mut buf = [0u8; 16];
+        let mut state = MouseState { buttons: 1, dx: 200, dy: -3, dz: 0 };
+        // 超出范围的位移分到两个数据包
+        mice.send(&state);
+        assert_eq!(mice.read(&mut buf), Ok(6));
+        assert_eq!(buf[..6], [0x29, 127, 0xfd, 0x09, 73, 0]);
+        assert_eq!(mice.read(&mut buf), Err(KernelError::WouldBlock));
+
+        assert_eq!(mice.write(&INTELLIMOUSE_KNOCK), Ok(6));
+        assert_eq!(mice.write(&[MOUSE_GET_ID]), Ok(1));
+        assert_eq!(mice.read(&mut buf), Ok(8));
+        assert_eq!(buf[6..8], [MOUSE_ACK, INTELLIMOUSE_ID]);
+
+        state = MouseState { buttons: 0, dx: 0, dy: 0, dz: 1 };
+        mice.send(&state);
+        assert_eq!(mice.read(&mut buf), Ok(4));
+        assert_eq!(buf[..4], [0x08, 0, 0, 1]);
+    }
+}
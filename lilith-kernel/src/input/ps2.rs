@@ -0,0 +1,363 @@
+//! PS/2键盘扫描码与鼠标数据包解码
+//!
+//! 本模块把PS/2键盘送来的第1套扫描码（i8042控制器默认把第2套转换为第1套）解码为按键事件，包括：
+//! - 基本键区：扫描码1到0x58与按键编码相同，最高位置位表示松开
+//! - `E0` 前缀的扩展键：右Ctrl/Alt、方向键、编辑键、小键盘回车与除号、Win键与菜单键
+//! - PrintScreen产生的假Shift（`E0 2A`/`E0 AA`）被忽略，Pause（`E1 1D 45 E1 9D C5`）只有按下码，
+//!   报告按下后立即报告松开
+//! - 按住不放时键盘重复发送按下码，解码为 `KEY_REPEATED`
+//! - 控制器的应答（`FA`）、重发请求（`FE`）等非按键字节被忽略
+//!
+//!
+//! 辅助端口上的鼠标数据包解码为相对位移与按键事件，包括：
+//! - 标准PS/2鼠标的3字节数据包：左、中、右键，9位有符号的X、Y位移
+//! - IntelliMouse（设备号3）的4字节数据包，第4字节为滚轮位移；
+//!   初始化时依次设置采样率200、100、80（`INTELLIMOUSE_KNOCK`）后读取设备号即可开启
+//! - 第1字节的第3位恒为1，不满足时丢弃字节重新同步；溢出的数据包整个丢弃
+//! - Y轴与滚轮转换为输入层的方向（Y轴向下、滚轮向前滚为正）
+//!
+//! 键盘控制器的中断处理把读到的字节交给 `receive`，辅助端口的字节交给 `receive_aux`，
+//! 修饰键与键位表由输入层的键盘处理者处理。本树没有i8042控制器驱动，
+//! 辅助端口的初始化命令序列（`AUX_INIT`）由平台的控制器驱动发送
+
+use super::keymap::*;
+use super::{InputEvent, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, EV_KEY, EV_REL, REL_WHEEL, REL_X, REL_Y};
+use super::{KEY_PRESSED, KEY_RELEASED, KEY_REPEATED};
+use spin::Mutex;
+
+/// 前缀与特殊字节
+const PREFIX_E0: u8 = 0xe0;
+const PREFIX_E1: u8 = 0xe1;
+const BREAK_BIT: u8 = 0x80;
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const ECHO: u8 = 0xee;
+const ERROR: u8 = 0xff;
+
+/// 基本键区的最大扫描码（F12）
+const MAX_BASE_SCANCODE: u8 = 0x58;
+
+/// Pause键前缀之后的字节数（`1D 45` 或 `9D C5`）
+const PAUSE_BYTES: u8 = 2;
+
+/// `E0` 前缀扩展键的按键编码，假Shift等不产生按键的返回 `None`
+fn extended_key(scancode: u8) -> Option<u16> {
+    Some(match scancode {
+        0x1c => KEY_KPENTER,
+        0x1d => KEY_RIGHTCTRL,
+        0x35 => KEY_KPSLASH,
+        0x37 => KEY_SYSRQ,
+        0x38 => KEY_RIGHTALT,
+        0x47 => KEY_HOME,
+        0x48 => KEY_UP,
+        0x49 => KEY_PAGEUP,
+        0x4b => KEY_LEFT,
+        0x4d => KEY_RIGHT,
+        0x4f => KEY_END,
+        0x50 => KEY_DOWN,
+        0x51 => KEY_PAGEDOWN,
+        0x52 => KEY_INSERT,
+        0x53 => KEY_DELETE,
+        0x5b => KEY_LEFTMETA,
+        0x5c => KEY_RIGHTMETA,
+        0x5d => KEY_COMPOSE,
+        _ => return None,
+    })
+}
+
+/// 解码器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    /// 收到 `E0`
+    Extended,
+    /// 收到 `E1`，还要跳过 `remaining` 个字节，`make` 表示这是按下的一半
+    Pause {
+        remaining: u8,
+        make: bool,
+    },
+}
+
+/// 第1套扫描码解码器
+pub struct ScancodeDecoder {
+    state: State,
+    /// 处于按下状态的按键编码（0到127）
+    pressed: [u64; 2],
+}
+
+impl ScancodeDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Normal,
+            pressed: [0; 2],
+        }
+    }
+
+    /// 记录按键状态，返回事件的值（按下、松开或重复）
+    fn update(&mut self, code: u16, released: bool) -> i32 {
+        let (word, bit) = (code as usize / 64 % 2, 1u64 << (code % 64));
+        let was_pressed = self.pressed[word] & bit != 0;
+        if released {
+            self.pressed[word] &= !bit;
+            KEY_RELEASED
+        } else {
+            self.pressed[word] |= bit;
+            if was_pressed {
+                KEY_REPEATED
+            } else {
+                KEY_PRESSED
+            }
+        }
+    }
+
+    /// 解码一个字节，凑成一个完整的按键变化时返回 `(按键编码, 值)`
+    pub fn decode(&mut self, byte: u8) -> Option<(u16, i32)> {
+        match self.state {
+            State::Pause { remaining, make } => {
+                let make = make || (remaining == PAUSE_BYTES && byte == 0x1d);
+                if remaining > 1 {
+                    self.state = State::Pause {
+                        remaining: remaining - 1,
+                        make,
+                    };
+                    return None;
+                }
+                self.state = State::Normal;
+                return make.then_some((KEY_PAUSE, KEY_PRESSED));
+            }
+            State::Extended => {
+                self.state = State::Normal;
+                let code = extended_key(byte & !BREAK_BIT)?;
+                return Some((code, self.update(code, byte & BREAK_BIT != 0)));
+            }
+            State::Normal => {}
+        }
+        match byte {
+            PREFIX_E0 => self.state = State::Extended,
+            PREFIX_E1 => {
+                self.state = State::Pause {
+                    remaining: PAUSE_BYTES,
+                    make: false,
+                }
+            }
+            0 | ACK | RESEND | ECHO | ERROR => {}
+            _ => {
+                let scancode = byte & !BREAK_BIT;
+                if (1..=MAX_BASE_SCANCODE).contains(&scancode) {
+                    let code = u16::from(scancode);
+                    return Some((code, self.update(code, byte & BREAK_BIT != 0)));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Default for ScancodeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 键盘控制器的解码器
+static DECODER: Mutex<ScancodeDecoder> = Mutex::new(ScancodeDecoder::new());
+
+/// 键盘控制器读到一个字节，解码后报告给输入层
+pub fn receive(byte: u8) {
+    let Some((code, value)) = DECODER.lock().decode(byte) else {
+        return;
+    };
+    super::report_key(code, value);
+    // Pause没有松开码
+    if code == KEY_PAUSE {
+        super::report_key(code, KEY_RELEASED);
+    }
+}
+
+/// 鼠标命令
+pub const MOUSE_SET_DEFAULTS: u8 = 0xf6;
+pub const MOUSE_SET_SAMPLE_RATE: u8 = 0xf3;
+pub const MOUSE_GET_ID: u8 = 0xf2;
+pub const MOUSE_ENABLE_REPORTING: u8 = 0xf4;
+
+/// 开启IntelliMouse滚轮的采样率序列
+pub const INTELLIMOUSE_KNOCK: [u8; 6] = [MOUSE_SET_SAMPLE_RATE, 200, MOUSE_SET_SAMPLE_RATE, 100, MOUSE_SET_SAMPLE_RATE, 80];
+
+/// 辅助端口的初始化命令序列：恢复默认设置，尝试开启滚轮，读取设备号，开始报告
+///
+/// 控制器驱动逐个发送并等待应答，把 `MOUSE_GET_ID` 应答后的设备号交给 `set_mouse_id`
+pub const AUX_INIT: [u8; 9] = [
+    MOUSE_SET_DEFAULTS,
+    MOUSE_SET_SAMPLE_RATE,
+    200,
+    MOUSE_SET_SAMPLE_RATE,
+    100,
+    MOUSE_SET_SAMPLE_RATE,
+    80,
+    MOUSE_GET_ID,
+    MOUSE_ENABLE_REPORTING,
+];
+
+/// IntelliMouse的设备号
+pub const INTELLIMOUSE_ID: u8 = 3;
+
+/// 数据包第1字节的各位
+const PACKET_LEFT: u8 = 0x01;
+const PACKET_RIGHT: u8 = 0x02;
+const PACKET_MIDDLE: u8 = 0x04;
+const PACKET_ALWAYS_ONE: u8 = 0x08;
+const PACKET_X_SIGN: u8 = 0x10;
+const PACKET_Y_SIGN: u8 = 0x20;
+const PACKET_OVERFLOW: u8 = 0xc0;
+
+/// 一个鼠标数据包
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MousePacket {
+    /// 按下的按键（`PACKET_LEFT` 等位）
+    pub buttons: u8,
+    /// X位移，向右为正
+    pub dx: i32,
+    /// Y位移，向上为正（PS/2的方向）
+    pub dy: i32,
+    /// 滚轮位移，向前滚为负（PS/2的方向）
+    pub dz: i32,
+}
+
+/// 鼠标数据包解码器
+pub struct MouseDecoder {
+    packet: [u8; 4],
+    len: usize,
+    /// 开启了滚轮时数据包为4字节
+    wheel: bool,
+}
+
+impl MouseDecoder {
+    pub const fn new() -> Self {
+        Self {
+            packet: [0; 4],
+            len: 0,
+            wheel: false,
+        }
+    }
+
+    /// 按设备号选择数据包长度，并丢弃未凑完的数据包
+    pub fn set_id(&mut self, id: u8) {
+        self.wheel = id == INTELLIMOUSE_ID;
+        self.len = 0;
+    }
+
+    fn packet_len(&self) -> usize {
+        if self.wheel {
+            4
+        } else {
+            3
+        }
+    }
+
+    /// 解码一个字节，凑成一个完整的数据包时返回
+    pub fn decode(&mut self, byte: u8) -> Option<MousePacket> {
+        if self.len == 0 && byte & PACKET_ALWAYS_ONE == 0 {
+            return None;
+        }
+        self.packet[self.len] = byte;
+        self.len += 1;
+        if self.len < self.packet_len() {
+            return None;
+        }
+        self.len = 0;
+
+        let [flags, x, y, z] = self.packet;
+        if flags & PACKET_OVERFLOW != 0 {
+            return None;
+        }
+        let extend = |low: u8, negative: bool| i32::from(low) - if negative { 0x100 } else { 0 };
+        Some(MousePacket {
+            buttons: flags & (PACKET_LEFT | PACKET_RIGHT | PACKET_MIDDLE),
+            dx: extend(x, flags & PACKET_X_SIGN != 0),
+            dy: extend(y, flags & PACKET_Y_SIGN != 0),
+            dz: if self.wheel { i32::from(z as i8) } else { 0 },
+        })
+    }
+}
+
+impl Default for MouseDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 辅助端口的解码器与上一个数据包的按键状态
+static MOUSE: Mutex<(MouseDecoder, u8)> = Mutex::new((MouseDecoder::new(), 0));
+
+/// 控制器驱动读到鼠标的设备号
+pub fn set_mouse_id(id: u8) {
+    MOUSE.lock().0.set_id(id);
+}
+
+/// 辅助端口读到一个字节，凑成数据包后报告给输入层
+pub fn receive_aux(byte: u8) {
+    let (packet, previous) = {
+        let mut mouse = MOUSE.lock();
+        let Some(packet) = mouse.0.decode(byte) else {
+            return;
+        };
+        let previous = core::mem::replace(&mut mouse.1, packet.buttons);
+        (packet, previous)
+    };
+
+    for (code, value) in [(REL_X, packet.dx), (REL_Y, -packet.dy), (REL_WHEEL, -packet.dz)] {
+        if value != 0 {
+            super::report(InputEvent {
+                kind: EV_REL,
+                code,
+                value,
+            });
+        }
+    }
+    for (bit, code) in [(PACKET_LEFT, BTN_LEFT), (PACKET_RIGHT, BTN_RIGHT), (PACKET_MIDDLE, BTN_MIDDLE)] {
+        if (packet.buttons ^ previous) & bit != 0 {
+            let value = if packet.buttons & bit != 0 { KEY_PRESSED } else { KEY_RELEASED };
+            super::report(InputEvent {
+                kind: EV_KEY,
+                code,
+                value,
+            });
+        }
+    }
+    super::report_sync();
+}
+
+crate::kernel_test! {
+    fn ps2_mouse_packets() {
+        let mut decoder = MouseDecoder::new();
+        let mut feed = |bytes: &[u8]| -> alloc::vec::Vec<MousePacket> {
+            bytes.iter().filter_map(|&byte| decoder.decode(byte)).collect()
+        };
+        let packet = |buttons, dx, dy, dz| MousePacket { buttons, dx, dy, dz };
+        // 左键按下，右移5，下移3（Y为负）
+        assert_eq!(feed(&[0x29, 5, 0xfd]), [packet(PACKET_LEFT, 5, -3, 0)]);
+        // 第3位为0的字节被丢弃，溢出的数据包被丢弃
+        assert_eq!(feed(&[0x00, 0x18, 0xff, 0]), [packet(0, -1, 0, 0)]);
+        assert_eq!(feed(&[0x48, 0, 0]), []);
+
+        decoder.set_id(INTELLIMOUSE_ID);
+        assert_eq!(decoder.decode(0x0c), None);
+        assert_eq!(decoder.decode(0), None);
+        assert_eq!(decoder.decode(0), None);
+        assert_eq!(decoder.decode(0xff), Some(packet(PACKET_MIDDLE, 0, 0, -1)));
+    }
+}
+
+crate::kernel_test! {
+    fn ps2_scancode_set1() {
+        let mut decoder = ScancodeDecoder::new();
+        let mut feed = |bytes: &[u8]| -> alloc::vec::Vec<(u16, i32)> {
+            bytes.iter().filter_map(|&byte| decoder.decode(byte)).collect()
+        };
+        assert_eq!(feed(&[0x1e, 0x1e, 0x9e]), [(30, KEY_PRESSED), (30, KEY_REPEATED), (30, KEY_RELEASED)]);
+        assert_eq!(feed(&[0xe0, 0x48, 0xe0, 0xc8]), [(KEY_UP, KEY_PRESSED), (KEY_UP, KEY_RELEASED)]);
+        assert_eq!(feed(&[0xe0, 0x2a, 0xe0, 0x37]), [(KEY_SYSRQ, KEY_PRESSED)]);
+        assert_eq!(feed(&[0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5]), [(KEY_PAUSE, KEY_PRESSED)]);
+        assert_eq!(feed(&[ACK, 0x2a]), [(KEY_LEFTSHIFT, KEY_PRESSED)]);
+    }
+}
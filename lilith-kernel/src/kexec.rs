@@ -0,0 +1,158 @@
+//! kexec：不经过固件直接启动新内核
+//!
+//! 本模块实现了把新内核加载到保留内存并跳转执行，用于开发时快速重启，包括：
+//! - 保留区域：位于pstore区域之下、按2MB对齐，加载时直接写入，执行时无需搬移
+//! - 映像格式：带RISC-V Image头部的Linux映像按头部中的内存大小预留空间，
+//!   其他映像按平坦二进制处理，从第一个字节开始执行
+//! - 设备树：复制引导程序传入的设备树，替换 `/chosen` 中的启动参数与initrd位置
+//! - 执行：经 `power::shutdown` 写回文件、停止其他hart后关闭分页跳转到新内核
+//!
+//! 保留区域布局：
+//!
+//! ```text
+//! KEXEC_BASE            内核映像（占用 max(映像长度, image_size)）
+//! 页对齐                initrd（可选）
+//! 页对齐                设备树
+//! ```
+
+use crate::arch;
+use crate::boot::fdt;
+use crate::debug::pstore::PSTORE_BASE;
+use crate::error::KernelError;
+use crate::fs::kernfs;
+use crate::mm::vma::page_align_up;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 保留区域大小
+pub const KEXEC_SIZE: usize = 16 * 1024 * 1024;
+/// 内核映像的对齐要求
+const KERNEL_ALIGN: usize = 2 * 1024 * 1024;
+/// 保留区域起始地址
+pub const KEXEC_BASE: usize = (PSTORE_BASE - KEXEC_SIZE) & !(KERNEL_ALIGN - 1);
+
+/// RISC-V Image头部的大小与字段偏移
+const IMAGE_HEADER_SIZE: usize = 64;
+const IMAGE_SIZE_OFFSET: usize = 16;
+const IMAGE_MAGIC2_OFFSET: usize = 56;
+/// RISC-V Image头部的魔数（"RSC\x05"）
+const IMAGE_MAGIC2: &[u8; 4] = b"RSC\x05";
+
+/// 已加载的映像
+#[derive(Debug, Clone, Copy)]
+struct LoadedImage {
+    /// 入口的物理地址
+    entry: usize,
+    /// 设备树的物理地址
+    fdt: usize,
+}
+
+static LOADED: Mutex<Option<LoadedImage>> = Mutex::new(None);
+
+/// 内核映像在内存中占用的大小
+fn image_footprint(kernel: &[u8]) -> usize {
+    if kernel.len() >= IMAGE_HEADER_SIZE && &kernel[IMAGE_MAGIC2_OFFSET..IMAGE_MAGIC2_OFFSET + 4] == IMAGE_MAGIC2 {
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&kernel[IMAGE_SIZE_OFFSET..IMAGE_SIZE_OFFSET + 8]);
+        // image_size包含bss，不能小于文件本身
+        (u64::from_le_bytes(size) as usize).max(kernel.len())
+    } else {
+        kernel.len()
+    }
+}
+
+/// 把数据写入保留区域中的 `addr`
+///
+/// # Safety
+///
+/// `addr..addr + data.len()` 必须位于保留区域内
+unsafe fn write_region(addr: usize, data: &[u8]) {
+    core::ptr::copy_nonoverlapping(data.as_ptr(), addr as *mut u8, data.len());
+}
+
+/// 加载新内核，替换之前加载的映像
+///
+/// `cmdline` 成为新内核的 `/chosen/bootargs`，有initrd时同时设置
+/// `linux,initrd-start` 与 `linux,initrd-end`
+pub fn load(kernel: &[u8], initrd: Option<&[u8]>, cmdline: &str) -> Result<(), KernelError> {
+    if kernel.is_empty() {
+        return Err(KernelError::InvalidArgument);
+    }
+    let fdt = fdt::fdt().ok_or(KernelError::NotSupported)?;
+
+    let initrd_start = KEXEC_BASE + page_align_up(image_footprint(kernel));
+    let initrd_end = initrd_start + initrd.map_or(0, <[u8]>::len);
+    let mut bootargs = Vec::with_capacity(cmdline.len() + 1);
+    bootargs.extend_from_slice(cmdline.as_bytes());
+    bootargs.push(0);
+    let start = (initrd_start as u64).to_be_bytes();
+    let end = (initrd_end as u64).to_be_bytes();
+    let mut props: Vec<(&str, &[u8])> = alloc::vec![("bootargs", &bootargs)];
+    if initrd.is_some() {
+        props.push(("linux,initrd-start", &start));
+        props.push(("linux,initrd-end", &end));
+    }
+    let blob = fdt.with_chosen(&props).ok_or(KernelError::InvalidArgument)?;
+
+    let fdt_addr = page_align_up(initrd_end);
+    if fdt_addr + blob.len() > KEXEC_BASE + KEXEC_SIZE {
+        return Err(KernelError::OutOfMemory);
+    }
+
+    let mut loaded = LOADED.lock();
+    *loaded = None;
+    unsafe {
+        write_region(KEXEC_BASE, kernel);
+        // bss清零，带头部的映像可能依赖这一点
+        core::ptr::write_bytes((KEXEC_BASE + kernel.len()) as *mut u8, 0, initrd_start - KEXEC_BASE - kernel.len());
+        if let Some(initrd) = initrd {
+            write_region(initrd_start, initrd);
+        }
+        write_region(fdt_addr, &blob);
+    }
+    arch::flush_icache();
+    *loaded = Some(LoadedImage {
+        entry: KEXEC_BASE,
+        fdt: fdt_addr,
+    });
+
+    crate::log_info!(
+        "kexec: 已加载内核 {}字节，initrd {}字节，设备树位于0x{:x}",
+        kernel.len(),
+        initrd.map_or(0, <[u8]>::len),
+        fdt_addr
+    );
+    Ok(())
+}
+
+/// 卸载已加载的映像
+pub fn unload() {
+    *LOADED.lock() = None;
+}
+
+/// 是否已加载映像
+pub fn is_loaded() -> bool {
+    LOADED.lock().is_some()
+}
+
+/// 跳转到已加载的内核，没有加载映像时停止所有核心
+///
+/// 由 `power::shutdown` 在停止其他hart、关闭中断之后调用
+pub fn machine_kexec() -> ! {
+    let Some(image) = *LOADED.lock() else {
+        arch::halt_all_cores()
+    };
+    unsafe { arch::kexec_jump(image.entry, arch::hart_id(), image.fdt) }
+}
+
+/// 注册 /sys/kernel/kexec_loaded
+pub fn kexec_init() -> Result<(), KernelError> {
+    kernfs::register(
+        "/sys/kernel/kexec_loaded",
+        Some(Box::new(|| alloc::format!("{}\n", u8::from(is_loaded())))),
+        None,
+    )?;
+    crate::log_info!("kexec: 保留区域 0x{:x}-0x{:x}", KEXEC_BASE, KEXEC_BASE + KEXEC_SIZE);
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! 控制台复用
+//!
+//! 内核输出同时写入所有已注册的控制台（串口、帧缓冲控制台、virtio-console等）。
+//! 启动参数 `console=<名称>[,选项]` 选择主控制台，出现多次时以最后一个为准；
+//! 主控制台承担 /dev/console 的用户读写。未指定时第一个注册的控制台为主控制台。
+//!
+//! 控制台以 `&'static` 注册，注册早期控制台不需要堆分配
+
+use crate::boot::cmdline;
+use crate::error::KernelError;
+use alloc::string::String;
+use spin::Mutex;
+
+/// 最多注册的控制台数量
+const MAX_CONSOLES: usize = 8;
+
+/// 控制台设备
+///
+/// 实现中不能记录日志，否则会递归写入控制台
+pub trait ConsoleDevice: Send + Sync {
+    /// 名称，与 `console=` 参数匹配（如 `ttyS0`）
+    fn name(&self) -> &str;
+
+    /// 输出字节，换行的转换由设备负责
+    fn write(&self, bytes: &[u8]);
+
+    /// 读取一个输入字节，没有输入或不支持输入时返回 `None`
+    fn read_byte(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// 已注册的控制台
+struct Consoles {
+    list: [Option<&'static dyn ConsoleDevice>; MAX_CONSOLES],
+    primary: Option<usize>,
+}
+
+static CONSOLES: Mutex<Consoles> = Mutex::new(Consoles {
+    list: [None; MAX_CONSOLES],
+    primary: None,
+});
+
+/// `console=` 参数选择的控制台名称
+fn preferred() -> Option<&'static str> {
+    cmdline::get("console").map(|value| value.split(',').next().unwrap_or(value))
+}
+
+/// 注册控制台
+///
+/// 名称与 `console=` 参数相符，或尚无主控制台时，成为主控制台
+pub fn register(console: &'static dyn ConsoleDevice) -> Result<(), KernelError> {
+    let mut consoles = CONSOLES.lock();
+    if consoles.list.iter().flatten().any(|c| c.name() == console.name()) {
+        return Err(KernelError::ResourceBusy);
+    }
+    let index = consoles
+        .list
+        .iter()
+        .position(Option::is_none)
+        .ok_or(KernelError::OutOfMemory)?;
+    consoles.list[index] = Some(console);
+
+    let selected = preferred().map_or(false, |name| name == console.name());
+    let primary_is_preferred = consoles
+        .primary
+        .and_then(|i| consoles.list[i])
+        .zip(preferred())
+        .map_or(false, |(c, name)| c.name() == name);
+    if consoles.primary.is_none() || (selected && !primary_is_preferred) {
+        consoles.primary = Some(index);
+    }
+    Ok(())
+}
+
+/// 注销控制台
+pub fn unregister(name: &str) {
+    let mut consoles = CONSOLES.lock();
+    if let Some(index) = consoles.list.iter().position(|c| c.map_or(false, |c| c.name() == name)) {
+        consoles.list[index] = None;
+        if consoles.primary == Some(index) {
+            consoles.primary = consoles.list.iter().position(Option::is_some);
+        }
+    }
+}
+
+/// 已注册控制台的快照，写入时不持有锁
+fn snapshot() -> [Option<&'static dyn ConsoleDevice>; MAX_CONSOLES] {
+    CONSOLES.lock().list
+}
+
+/// 主控制台
+pub fn primary() -> Option<&'static dyn ConsoleDevice> {
+    let consoles = CONSOLES.lock();
+    consoles.primary.and_then(|index| consoles.list[index])
+}
+
+/// 写入所有控制台
+pub fn write(bytes: &[u8]) {
+    for console in snapshot().iter().flatten() {
+        console.write(bytes);
+    }
+}
+
+/// 写入主控制台（/dev/console）
+pub fn write_primary(bytes: &[u8]) {
+    if let Some(console) = primary() {
+        console.write(bytes);
+    }
+}
+
+/// 检查输入时预先读出的字节
+static PENDING_INPUT: Mutex<Option<u8>> = Mutex::new(None);
+
+/// 从主控制台读取一个字节
+pub fn read_primary() -> Option<u8> {
+    let mut pending = PENDING_INPUT.lock();
+    pending.take().or_else(|| primary().and_then(|console| console.read_byte()))
+}
+
+/// 主控制台是否有输入，读出的字节留给下一次 `read_primary`
+pub fn input_pending() -> bool {
+    let mut pending = PENDING_INPUT.lock();
+    if pending.is_none() {
+        *pending = primary().and_then(|console| console.read_byte());
+    }
+    pending.is_some()
+}
+
+/// 生成 /proc/consoles 的内容，主控制台带有 `primary` 标记
+pub(super) fn proc_read_consoles() -> String {
+    let consoles = CONSOLES.lock();
+    let mut out = String::new();
+    for (index, console) in consoles.list.iter().enumerate() {
+        if let Some(console) = console {
+            out.push_str(console.name());
+            if consoles.primary == Some(index) {
+                out.push_str(" primary");
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,483 @@
+//! 内核日志
+//!
+//! 本模块实现了内核日志缓冲区，包括：
+//! - 固定大小的环形缓冲区，每条记录带有序号、级别与时间戳，写满后丢弃最旧的记录
+//! - 记录的同时按控制台级别输出到所有已注册的控制台
+//! - `syslog` 系统调用与 /proc/kmsg 读取接口
+//! - `log_error!` 等分级日志宏，按模块设置记录级别（/proc/sys/kernel/loglevel）
+//!
+//! 缓冲区是静态数组，内存管理初始化之前即可使用
+
+pub mod console;
+
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sync::{SpinLockIrqSave, WaitQueue};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Arguments, Write};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// 日志缓冲区大小
+pub const LOG_BUF_SIZE: usize = 64 * 1024;
+
+/// 单条记录的最大长度，超出部分被截断
+pub const LOG_LINE_MAX: usize = 512;
+
+/// 记录头部长度：长度(u16) + 级别(u8) + 时间戳(u64)
+const RECORD_HEADER: usize = 11;
+
+/// 日志级别（取值与Linux一致，数值越小越重要）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Emerg = 0,
+    Alert = 1,
+    Crit = 2,
+    Err = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+impl LogLevel {
+    /// 级别名称
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Emerg => "emerg",
+            LogLevel::Alert => "alert",
+            LogLevel::Crit => "crit",
+            LogLevel::Err => "error",
+            LogLevel::Warning => "warn",
+            LogLevel::Notice => "notice",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+
+    /// 解析级别名称或数值
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Ok(value) = s.parse::<u8>() {
+            return (value <= 7).then(|| Self::from_u8(value));
+        }
+        [
+            LogLevel::Emerg,
+            LogLevel::Alert,
+            LogLevel::Crit,
+            LogLevel::Err,
+            LogLevel::Warning,
+            LogLevel::Notice,
+            LogLevel::Info,
+            LogLevel::Debug,
+        ]
+        .into_iter()
+        .find(|level| level.name() == s)
+    }
+
+    /// 由数值转换，超出范围时取 `Debug`
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => LogLevel::Emerg,
+            1 => LogLevel::Alert,
+            2 => LogLevel::Crit,
+            3 => LogLevel::Err,
+            4 => LogLevel::Warning,
+            5 => LogLevel::Notice,
+            6 => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+/// 环形日志缓冲区
+///
+/// 位置均为累计写入的字节数，对缓冲区大小取模得到实际偏移
+struct LogBuffer {
+    buf: [u8; LOG_BUF_SIZE],
+    /// 最旧记录的位置与序号
+    head: usize,
+    first_seq: u64,
+    /// 下一条记录的位置与序号
+    tail: usize,
+    next_seq: u64,
+    /// syslog读取（/proc/kmsg）的下一条序号
+    syslog_seq: u64,
+    /// `SYSLOG_ACTION_CLEAR` 之后的第一条序号
+    clear_seq: u64,
+}
+
+/// 读取出的一条记录
+pub struct LogRecord<'a> {
+    pub seq: u64,
+    pub level: LogLevel,
+    pub timestamp_ns: u64,
+    pub text: &'a [u8],
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; LOG_BUF_SIZE],
+            head: 0,
+            first_seq: 0,
+            tail: 0,
+            next_seq: 0,
+            syslog_seq: 0,
+            clear_seq: 0,
+        }
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        self.buf[pos % LOG_BUF_SIZE]
+    }
+
+    fn put(&mut self, pos: usize, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.buf[(pos + i) % LOG_BUF_SIZE] = byte;
+        }
+    }
+
+    /// 位置 `pos` 处记录的文本长度
+    fn text_len(&self, pos: usize) -> usize {
+        u16::from_le_bytes([self.byte(pos), self.byte(pos + 1)]) as usize
+    }
+
+    /// 追加一条记录，空间不足时丢弃最旧的记录
+    fn push(&mut self, level: LogLevel, timestamp_ns: u64, text: &[u8]) {
+        let text = &text[..text.len().min(LOG_LINE_MAX)];
+        let size = RECORD_HEADER + text.len();
+        while self.tail + size - self.head > LOG_BUF_SIZE {
+            self.head += RECORD_HEADER + self.text_len(self.head);
+            self.first_seq += 1;
+        }
+
+        let tail = self.tail;
+        self.put(tail, &(text.len() as u16).to_le_bytes());
+        self.put(tail + 2, &[level as u8]);
+        self.put(tail + 3, &timestamp_ns.to_le_bytes());
+        self.put(tail + RECORD_HEADER, text);
+        self.tail += size;
+        self.next_seq += 1;
+    }
+
+    /// 从序号 `seq` 开始依次访问记录（已被覆盖的记录跳过），`f` 返回 `false` 时停止
+    ///
+    /// 返回访问的最后一条记录之后的序号
+    fn for_each_from(&self, seq: u64, mut f: impl FnMut(&LogRecord) -> bool) -> u64 {
+        let mut pos = self.head;
+        let mut current = self.first_seq;
+        let mut text = [0u8; LOG_LINE_MAX];
+        while current < self.next_seq {
+            let len = self.text_len(pos);
+            if current >= seq {
+                let mut timestamp = [0u8; 8];
+                for (i, byte) in timestamp.iter_mut().enumerate() {
+                    *byte = self.byte(pos + 3 + i);
+                }
+                for (i, byte) in text[..len].iter_mut().enumerate() {
+                    *byte = self.byte(pos + RECORD_HEADER + i);
+                }
+                let record = LogRecord {
+                    seq: current,
+                    level: LogLevel::from_u8(self.byte(pos + 2)),
+                    timestamp_ns: u64::from_le_bytes(timestamp),
+                    text: &text[..len],
+                };
+                if !f(&record) {
+                    return current;
+                }
+            }
+            pos += RECORD_HEADER + len;
+            current += 1;
+        }
+        current
+    }
+}
+
+/// 全局日志缓冲区，中断处理程序中也会记录日志，持锁时关闭本核中断
+static LOG: SpinLockIrqSave<LogBuffer> = SpinLockIrqSave::new(LogBuffer::new());
+
+/// 输出到控制台的级别上限（不含），默认输出 `Info` 及更重要的记录
+static CONSOLE_LOGLEVEL: AtomicU8 = AtomicU8::new(LogLevel::Debug as u8);
+
+/// 是否输出到控制台
+static CONSOLE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 等待新记录的读者
+static LOG_WAIT: WaitQueue = WaitQueue::new();
+
+/// 有新记录、尚未唤醒读者
+static WAKE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// 未单独设置的模块的记录级别：级别不大于它的日志才被记录
+static DEFAULT_LOGLEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// 按模块设置的记录级别，模块路径不含crate名（如 `mm`、`boot::memory_detect`）
+///
+/// 表为空时查询不分配内存，内存管理初始化之前也可以使用日志宏
+static MODULE_LEVELS: SpinLockIrqSave<Vec<(String, LogLevel)>> = SpinLockIrqSave::new(Vec::new());
+
+/// 去掉模块路径开头的crate名
+fn strip_crate(module: &str) -> &str {
+    module.split_once("::").map_or("", |(_, rest)| rest)
+}
+
+/// `module` 是否为 `prefix` 本身或其子模块
+fn module_matches(module: &str, prefix: &str) -> bool {
+    module
+        .strip_prefix(prefix)
+        .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// 模块的记录级别，取匹配最长的设置
+pub fn module_level(module: &str) -> LogLevel {
+    let module = strip_crate(module);
+    MODULE_LEVELS
+        .lock()
+        .iter()
+        .filter(|(prefix, _)| module_matches(module, prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(LogLevel::from_u8(DEFAULT_LOGLEVEL.load(Ordering::Relaxed)), |&(_, level)| level)
+}
+
+/// 设置模块的记录级别，`None` 表示恢复为默认级别
+pub fn set_module_level(module: &str, level: Option<LogLevel>) {
+    let mut levels = MODULE_LEVELS.lock();
+    levels.retain(|(prefix, _)| prefix != module);
+    if let Some(level) = level {
+        levels.push((module.to_string(), level));
+    }
+}
+
+/// 设置默认的记录级别
+pub fn set_default_level(level: LogLevel) {
+    DEFAULT_LOGLEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// 分级日志宏的实现：按模块的记录级别过滤后写入日志
+pub fn log(level: LogLevel, module: &str, args: Arguments) {
+    if level <= module_level(module) {
+        printk(level, args);
+    }
+}
+
+/// 格式化到固定大小的栈上缓冲区，超出部分截断
+struct LineBuffer {
+    buf: [u8; LOG_LINE_MAX],
+    len: usize,
+}
+
+impl LineBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; LOG_LINE_MAX],
+            len: 0,
+        }
+    }
+}
+
+impl Write for LineBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(LOG_LINE_MAX - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// 写入一条日志
+///
+/// 消息中的每一行成为一条记录；记录的同时按控制台级别输出到所有控制台
+pub fn printk(level: LogLevel, args: Arguments) {
+    // 先在锁外格式化，参数的格式化过程中也可以记录日志
+    let mut line = LineBuffer::new();
+    let _ = line.write_fmt(args);
+    let timestamp_ns = crate::time::monotonic_ns();
+    let text = &line.buf[..line.len];
+    let text = text.strip_suffix(b"\n").unwrap_or(text);
+
+    {
+        let mut log = LOG.lock();
+        for part in text.split(|&byte| byte == b'\n') {
+            log.push(level, timestamp_ns, part);
+        }
+    }
+    WAKE_PENDING.store(true, Ordering::Release);
+
+    if CONSOLE_ENABLED.load(Ordering::Relaxed) && (level as u8) < CONSOLE_LOGLEVEL.load(Ordering::Relaxed) {
+        let (sec, usec) = (timestamp_ns / 1_000_000_000, timestamp_ns % 1_000_000_000 / 1000);
+        let mut prefix = LineBuffer::new();
+        let _ = write!(prefix, "[{:5}.{:06}] ", sec, usec);
+        for part in text.split(|&byte| byte == b'\n') {
+            console::write(&prefix.buf[..prefix.len]);
+            console::write(part);
+            console::write(b"\n");
+        }
+    }
+}
+
+/// 以默认级别写入日志，可作为 `fn(Arguments)` 传递
+pub fn print(args: Arguments) {
+    printk(LogLevel::Info, args);
+}
+
+/// 唤醒等待新记录的读者
+///
+/// `printk` 可能在调度器内部调用，不能直接唤醒任务，由时钟中断延后处理
+pub fn wake_readers() {
+    if WAKE_PENDING.swap(false, Ordering::AcqRel) {
+        LOG_WAIT.wake_all();
+    }
+}
+
+/// 按syslog格式追加一条记录：`<级别>[秒.微秒] 文本`
+fn format_record(out: &mut String, record: &LogRecord) {
+    let ts = record.timestamp_ns;
+    let _ = write!(
+        out,
+        "<{}>[{:5}.{:06}] ",
+        record.level as u8,
+        ts / 1_000_000_000,
+        ts % 1_000_000_000 / 1000
+    );
+    out.push_str(&String::from_utf8_lossy(record.text));
+    out.push('\n');
+}
+
+/// 读取从 `seq` 开始的记录，最多 `max` 字节，返回内容与下一条序号
+fn read_from(log: &LogBuffer, seq: u64, max: usize) -> (String, u64) {
+    let mut out = String::new();
+    let next = log.for_each_from(seq, |record| {
+        let before = out.len();
+        format_record(&mut out, record);
+        if out.len() > max {
+            out.truncate(before);
+            return false;
+        }
+        true
+    });
+    (out, next)
+}
+
+/// 读取并消耗未读的记录（syslog READ 与 /proc/kmsg），没有未读记录时等待
+pub fn read_unread(max: usize) -> String {
+    LOG_WAIT.wait_until(|| {
+        let log = LOG.lock();
+        log.syslog_seq < log.next_seq
+    });
+    let mut log = LOG.lock();
+    let seq = log.syslog_seq.max(log.first_seq);
+    let (out, next) = read_from(&log, seq, max);
+    log.syslog_seq = next;
+    out
+}
+
+/// 读取缓冲区中的所有记录（不消耗），`clear` 为真时随后清空
+pub fn read_all(max: usize, clear: bool) -> String {
+    let mut log = LOG.lock();
+    let seq = log.clear_seq.max(log.first_seq);
+    let (out, _) = read_from(&log, seq, max);
+    if clear {
+        log.clear_seq = log.next_seq;
+    }
+    out
+}
+
+/// 清空缓冲区（只影响 `read_all`）
+pub fn clear() {
+    let mut log = LOG.lock();
+    log.clear_seq = log.next_seq;
+}
+
+/// 未读记录按syslog格式的总字节数
+pub fn unread_size() -> usize {
+    let log = LOG.lock();
+    let seq = log.syslog_seq.max(log.first_seq);
+    read_from(&log, seq, usize::MAX).0.len()
+}
+
+/// 开启或关闭控制台输出
+pub fn set_console_enabled(enabled: bool) {
+    CONSOLE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 设置控制台级别：级别小于 `level` 的记录输出到控制台
+pub fn set_console_loglevel(level: u8) {
+    CONSOLE_LOGLEVEL.store(level.clamp(1, 8), Ordering::Relaxed);
+}
+
+/// 生成 /proc/sys/kernel/loglevel 的内容
+fn proc_read_loglevel() -> String {
+    let mut out = String::new();
+    let default = LogLevel::from_u8(DEFAULT_LOGLEVEL.load(Ordering::Relaxed));
+    let _ = writeln!(out, "default {}", default.name());
+    for (module, level) in MODULE_LEVELS.lock().iter() {
+        let _ = writeln!(out, "{} {}", module, level.name());
+    }
+    out
+}
+
+/// 处理写入 /proc/sys/kernel/loglevel 的命令
+///
+/// 每行为 `<模块|default> <级别>`，级别为名称或0-7；模块的级别写 `default` 时取消单独设置
+fn proc_write_loglevel(data: &str) -> Result<(), KernelError> {
+    for line in data.lines() {
+        let mut tokens = line.split_whitespace();
+        let module = match tokens.next() {
+            Some(module) => module,
+            None => continue,
+        };
+        let level = tokens.next().ok_or(KernelError::InvalidArgument)?;
+        match (module, level) {
+            ("default", level) => set_default_level(LogLevel::parse(level).ok_or(KernelError::InvalidArgument)?),
+            (module, "default") => set_module_level(module, None),
+            (module, level) => set_module_level(module, Some(LogLevel::parse(level).ok_or(KernelError::InvalidArgument)?)),
+        }
+    }
+    Ok(())
+}
+
+/// 初始化日志读取与配置接口
+pub fn klog_init() -> Result<(), KernelError> {
+    procfs::register("kmsg", Some(Box::new(|| read_unread(LOG_BUF_SIZE))), None)?;
+    procfs::register("consoles", Some(Box::new(console::proc_read_consoles)), None)?;
+    procfs::register(
+        "sys/kernel/loglevel",
+        Some(Box::new(proc_read_loglevel)),
+        Some(Box::new(proc_write_loglevel)),
+    )
+}
+
+/// 记录错误
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::klog::log($crate::klog::LogLevel::Err, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// 记录警告
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::klog::log($crate::klog::LogLevel::Warning, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// 记录一般信息
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::klog::log($crate::klog::LogLevel::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// 记录调试信息，默认不记录
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::klog::log($crate::klog::LogLevel::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
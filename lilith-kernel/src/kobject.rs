@@ -0,0 +1,181 @@
+//! 内核对象引用计数
+//!
+//! 设备、inode、套接字等内核对象统一使用 `KRef` 管理生命周期：
+//! - 引用计数与对象存放在同一次分配中（侵入式）
+//! - 最后一个强引用释放时，先以独占方式调用 `KObject::release`，
+//!   再析构对象；子对象持有父对象的强引用，保证父对象总是最后释放
+//! - 查找表等不应延长对象寿命的地方使用 `KWeak`，使用前需升级
+
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+/// 引用计数上限，超过视为计数泄漏
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+/// 内核对象
+pub trait KObject: Send + Sync + 'static {
+    /// 最后一个强引用释放时调用，此时没有其他执行流能访问对象
+    ///
+    /// 用于注销对象、释放硬件资源等需要在析构前完成的清理
+    fn release(&mut self) {}
+}
+
+struct KObjectInner<T> {
+    /// 强引用数
+    strong: AtomicUsize,
+    /// 弱引用数（所有强引用共同持有一个弱引用）
+    weak: AtomicUsize,
+    value: UnsafeCell<ManuallyDrop<T>>,
+}
+
+/// 内核对象的强引用
+pub struct KRef<T: KObject> {
+    ptr: NonNull<KObjectInner<T>>,
+}
+
+/// 内核对象的弱引用
+pub struct KWeak<T: KObject> {
+    ptr: NonNull<KObjectInner<T>>,
+}
+
+unsafe impl<T: KObject> Send for KRef<T> {}
+unsafe impl<T: KObject> Sync for KRef<T> {}
+unsafe impl<T: KObject> Send for KWeak<T> {}
+unsafe impl<T: KObject> Sync for KWeak<T> {}
+
+impl<T: KObject> KRef<T> {
+    /// 创建对象，引用计数为1
+    pub fn new(value: T) -> Self {
+        let inner = Box::new(KObjectInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            value: UnsafeCell::new(ManuallyDrop::new(value)),
+        });
+        Self {
+            ptr: NonNull::from(Box::leak(inner)),
+        }
+    }
+
+    fn inner(&self) -> &KObjectInner<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// 创建弱引用
+    pub fn downgrade(this: &Self) -> KWeak<T> {
+        this.inner().weak.fetch_add(1, Ordering::Relaxed);
+        KWeak { ptr: this.ptr }
+    }
+
+    /// 当前强引用数
+    pub fn ref_count(this: &Self) -> usize {
+        this.inner().strong.load(Ordering::Relaxed)
+    }
+
+    /// 两个引用是否指向同一对象
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        a.ptr == b.ptr
+    }
+
+    /// 弱引用是否指向本对象
+    pub fn is_same(this: &Self, weak: &KWeak<T>) -> bool {
+        this.ptr == weak.ptr
+    }
+}
+
+impl<T: KObject> Clone for KRef<T> {
+    fn clone(&self) -> Self {
+        if self.inner().strong.fetch_add(1, Ordering::Relaxed) > MAX_REFCOUNT {
+            panic!("内核对象引用计数溢出");
+        }
+        Self { ptr: self.ptr }
+    }
+}
+
+impl<T: KObject> Deref for KRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.inner().value.get() }
+    }
+}
+
+impl<T: KObject> Drop for KRef<T> {
+    fn drop(&mut self) {
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // 与其他引用释放前的写入同步
+        fence(Ordering::Acquire);
+
+        unsafe {
+            let value = &mut *self.inner().value.get();
+            value.release();
+            ManuallyDrop::drop(value);
+        }
+        drop(KWeak { ptr: self.ptr });
+    }
+}
+
+impl<T: KObject + fmt::Debug> fmt::Debug for KRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: KObject> KWeak<T> {
+    fn inner(&self) -> &KObjectInner<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// 升级为强引用，对象已释放时返回 `None`
+    pub fn upgrade(&self) -> Option<KRef<T>> {
+        let strong = &self.inner().strong;
+        let mut count = strong.load(Ordering::Relaxed);
+        loop {
+            if count == 0 {
+                return None;
+            }
+            if count > MAX_REFCOUNT {
+                panic!("内核对象引用计数溢出");
+            }
+            match strong.compare_exchange_weak(count, count + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(KRef { ptr: self.ptr }),
+                Err(current) => count = current,
+            }
+        }
+    }
+
+    /// 对象是否仍然存活
+    pub fn is_alive(&self) -> bool {
+        self.inner().strong.load(Ordering::Acquire) != 0
+    }
+
+    /// 两个弱引用是否指向同一对象
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        a.ptr == b.ptr
+    }
+}
+
+impl<T: KObject> Clone for KWeak<T> {
+    fn clone(&self) -> Self {
+        self.inner().weak.fetch_add(1, Ordering::Relaxed);
+        Self { ptr: self.ptr }
+    }
+}
+
+impl<T: KObject> Drop for KWeak<T> {
+    fn drop(&mut self) {
+        if self.inner().weak.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        fence(Ordering::Acquire);
+        unsafe {
+            drop(Box::from_raw(self.ptr.as_ptr()));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! 内核内测试框架
+//!
+//! 以 `test` 特性编译时，内核在完成初始化后运行所有登记的测试而不进入调度循环：
+//! - `kernel_test!` 定义测试函数并登记到 `ktests` 节，各模块可就地编写测试
+//! - 测试函数恐慌即为失败：恐慌处理报告失败的测试后立即结束
+//! - 启动参数 `ktest=<字符串>` 只运行名称包含该字符串的测试
+//! - 结果通过QEMU virt平台的SiFive测试设备（test finisher）报告，
+//!   QEMU以0退出表示全部通过，否则以非0退出，CI据此判断结果
+//!
+//! 不在QEMU上运行时退回到SBI关机，此时无法区分成功与失败。
+//! 未开启 `test` 特性时只保留 `kernel_test!` 宏，测试不会编译进内核
+
+#[cfg(feature = "test")]
+use crate::arch::sbi;
+#[cfg(feature = "test")]
+use crate::boot::cmdline;
+#[cfg(feature = "test")]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// QEMU virt平台SiFive测试设备的地址
+#[cfg(feature = "test")]
+const SIFIVE_TEST_BASE: usize = 0x10_0000;
+/// 写入测试设备的值：成功退出
+#[cfg(feature = "test")]
+const FINISHER_PASS: u32 = 0x5555;
+/// 写入测试设备的值：失败退出，高16位为退出码
+#[cfg(feature = "test")]
+const FINISHER_FAIL: u32 = 0x3333;
+
+/// 登记的测试
+#[repr(C)]
+pub struct KernelTest {
+    /// 测试名（模块路径加函数名）
+    pub name: &'static str,
+    /// 测试函数
+    pub func: fn(),
+}
+
+/// 定义一个内核测试，只在以 `test` 特性编译时存在
+///
+/// ```ignore
+/// kernel_test! {
+///     fn vec_push() {
+///         let mut v = alloc::vec::Vec::new();
+///         v.push(1);
+///         assert_eq!(v.len(), 1);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! kernel_test {
+    (fn $name:ident() $body:block) => {
+        #[cfg(feature = "test")]
+        fn $name() $body
+
+        #[cfg(feature = "test")]
+        const _: () = {
+            #[used]
+            #[link_section = "ktests"]
+            static ENTRY: $crate::ktest::KernelTest = $crate::ktest::KernelTest {
+                name: concat!(module_path!(), "::", stringify!($name)),
+                func: $name,
+            };
+        };
+    };
+}
+
+#[cfg(feature = "test")]
+extern "C" {
+    // 链接器为名称是合法标识符的节自动定义起止符号
+    static __start_ktests: u8;
+    static __stop_ktests: u8;
+}
+
+/// 正在运行的测试名，恐慌时用于报告
+#[cfg(feature = "test")]
+static CURRENT: AtomicPtr<&'static str> = AtomicPtr::new(core::ptr::null_mut());
+
+/// 所有登记的测试
+#[cfg(feature = "test")]
+fn tests() -> &'static [KernelTest] {
+    unsafe {
+        let start = &__start_ktests as *const u8 as *const KernelTest;
+        let end = &__stop_ktests as *const u8 as *const KernelTest;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 结束QEMU，`code` 为0表示成功
+#[cfg(feature = "test")]
+pub fn exit_qemu(code: u16) -> ! {
+    let value = if code == 0 {
+        FINISHER_PASS
+    } else {
+        FINISHER_FAIL | (u32::from(code) << 16)
+    };
+    unsafe {
+        core::ptr::write_volatile(SIFIVE_TEST_BASE as *mut u32, value);
+    }
+    // 没有测试设备时关机
+    let reason = if code == 0 {
+        sbi::RESET_REASON_NONE
+    } else {
+        sbi::RESET_REASON_SYSTEM_FAILURE
+    };
+    sbi::system_reset(sbi::RESET_TYPE_SHUTDOWN, reason);
+    crate::arch::halt_all_cores()
+}
+
+/// 运行所有测试并结束QEMU
+#[cfg(feature = "test")]
+pub fn run_tests() -> ! {
+    let filter = cmdline::get("ktest").unwrap_or("");
+    let selected = || tests().iter().filter(move |test| test.name.contains(filter));
+
+    crate::log_info!("运行 {} 个内核测试", selected().count());
+    for test in selected() {
+        CURRENT.store(&test.name as *const &'static str as *mut &'static str, Ordering::Release);
+        crate::log_info!("test {} ...", test.name);
+        (test.func)();
+        crate::log_info!("test {} ... ok", test.name);
+    }
+    CURRENT.store(core::ptr::null_mut(), Ordering::Release);
+
+    crate::log_info!("测试结果: 全部通过");
+    exit_qemu(0)
+}
+
+/// 测试模式下的恐慌收尾，由恐慌处理调用：报告失败并结束QEMU
+#[cfg(feature = "test")]
+pub fn test_panic() -> ! {
+    let current = CURRENT.load(Ordering::Acquire);
+    if current.is_null() {
+        crate::boot::emergency_print(format_args!("测试结果: 运行测试前内核恐慌\n"));
+    } else {
+        let name = unsafe { *current };
+        crate::boot::emergency_print(format_args!("test {} ... FAILED\n测试结果: 失败\n", name));
+    }
+    exit_qemu(1)
+}
+
+// 框架自检，同时保证测试模式下 `ktests` 节总是存在
+kernel_test! {
+    fn heap_alloc() {
+        let mut values = alloc::vec::Vec::new();
+        values.extend(0..64usize);
+        assert_eq!(values.iter().sum::<usize>(), 64 * 63 / 2);
+    }
+}
@@ -6,6 +6,19 @@
 //! - 内存管理
 //! - 进程调度
 //! - 设备驱动框架
+//! - 调试支持（调用栈回溯与符号表）
+//! - 内核日志缓冲区
+//! - 软中断与tasklet（中断的下半部）
+//! - 安全审计日志
+//! - 随机数生成器
+//! - 输入子系统（键盘事件翻译为终端输入）
+//! - 可加载内核模块
+//! - 电源管理：关机、重启（含kexec）与挂起到空闲
+//! - 基于H扩展的虚拟机（/dev/lilith-kvm）
+//! - 内核内测试框架（`test` 特性）
+//!
+//! 网络、多hart、系统调用跟踪与可加载模块可以通过Cargo特性关闭，
+//! 例如 `--no-default-features` 得到只支持单hart的最小内核
 
 #![no_std]
 #![no_main]
@@ -23,10 +36,27 @@ pub mod boot;
 pub mod mm;
 pub mod sched;
 pub mod fs;
+#[cfg(feature = "net")]
 pub mod net;
+pub mod time;
+pub mod kobject;
+pub mod syscall;
 pub mod drivers;
 pub mod sync;
 pub mod error;
+pub mod debug;
+pub mod klog;
+pub mod audit;
+pub mod random;
+pub mod softirq;
+pub mod input;
+pub mod power;
+pub mod kexec;
+pub mod perf;
+pub mod virt;
+pub mod ktest;
+#[cfg(feature = "modules")]
+pub mod module;
 
 // 重新导出核心类型
 pub use arch::riscv::*;
@@ -74,12 +104,51 @@ pub fn kernel_init() -> KernelInitResult {
     if let Err(_) = boot::early_uart_init() {
         return KernelInitResult::DeviceInitFailed;
     }
+    // 入口代码已按KASLR移动内核，报告偏移便于调试
+    if boot::kaslr::kaslr_offset() != 0 {
+        crate::early_println!("KASLR: 内核偏移 0x{:x}", boot::kaslr::kaslr_offset());
+    }
 
     // 3. 内存子系统初始化
     if let Err(_) = mm::memory_init() {
         return KernelInitResult::InsufficientMemory;
     }
 
+    // 按段收紧内核映射的权限：代码只读可执行，数据不可执行
+    if let Err(_) = mm::protect::protect_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 保存上次启动留下的崩溃记录，失败不影响启动
+    if let Err(e) = debug::pstore::pstore_init() {
+        crate::early_println!("pstore初始化失败: {:?}", e);
+    }
+
+    // 内核日志的读取接口（/proc/kmsg）
+    if let Err(_) = klog::klog_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 安全审计日志（/dev/audit）
+    if let Err(_) = audit::audit_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 静态跟踪点的事件缓冲区（/sys/kernel/tracing/events）
+    if let Err(_) = debug::tracepoint::tracepoint_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 函数跟踪的环形缓冲区与控制文件（/sys/kernel/tracing）
+    #[cfg(feature = "ftrace")]
+    if let Err(_) = debug::ftrace::ftrace_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 堆分配器就绪后开启锁依赖检查
+    #[cfg(feature = "lockdep")]
+    sync::lockdep::lockdep_init();
+
     // 4. 中断系统初始化
     if let Err(_) = arch::interrupt_init() {
         return KernelInitResult::DeviceInitFailed;
@@ -90,6 +159,120 @@ pub fn kernel_init() -> KernelInitResult {
         return KernelInitResult::ConfigurationError;
     }
 
+    // 驱动的异步执行器（kasync任务）
+    sched::executor::executor_init();
+
+    // 工作队列的工作者任务（kworker）
+    sched::workqueue::workqueue_init();
+
+    // 软中断与tasklet，定时器回调与网络接收在软中断中执行
+    softirq::softirq_init();
+
+    // 外部中断控制器，/proc/irq/N/smp_affinity 与中断均衡
+    if let Err(_) = arch::plic::plic_init() {
+        return KernelInitResult::DeviceInitFailed;
+    }
+
+    // 内核堆的泄漏检测（/proc/kmemleak），需要工作队列
+    #[cfg(feature = "kmemleak")]
+    if let Err(_) = mm::kmemleak::kmemleak_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 内存不足时的缓存回收（/proc/sys/vm/drop_caches）
+    if let Err(_) = mm::shrinker::shrinker_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 映射文件使用的页缓存
+    if let Err(_) = mm::page_cache::page_cache_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 按页分配的归属（/sys/kernel/debug/page_owner）
+    #[cfg(feature = "page_owner")]
+    if let Err(_) = mm::page_owner::page_owner_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 硬件性能计数与采样分析的接口（/proc/perf、/proc/profile）
+    if let Err(_) = perf::perf_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 6. 时间子系统初始化
+    if let Err(_) = time::time_init() {
+        return KernelInitResult::DeviceInitFailed;
+    }
+
+    // 随机数生成器依赖时钟源与设备树中的种子
+    if let Err(_) = random::random_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 用户地址空间布局随机化的开关（/proc/sys/kernel/randomize_va_space）
+    if let Err(_) = mm::aslr::aslr_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 7. 网络子系统初始化（启动DHCP与SNTP客户端）
+    #[cfg(feature = "net")]
+    if let Err(_) = net::net_init() {
+        return KernelInitResult::DeviceInitFailed;
+    }
+
+    // 可加载模块的登记表（/proc/modules）
+    #[cfg(feature = "modules")]
+    if let Err(_) = module::module_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // kexec状态文件（/sys/kernel/kexec_loaded）
+    if let Err(_) = kexec::kexec_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 检测H扩展，登记 /proc/virt
+    if let Err(_) = virt::virt_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 系统睡眠接口（/sys/power/state）
+    if let Err(_) = power::suspend::suspend_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 内核调试桩（启动参数 gdbstub=），gdbwait时在此等待GDB连接
+    if let Err(e) = debug::gdbstub::gdbstub_init() {
+        crate::early_println!("gdbstub初始化失败: {:?}", e);
+    }
+
+    // 挂载引导程序传入的initrd作为初始根文件系统
+    if let Err(_) = fs::initramfs::initramfs_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 控制台串口的接收中断，收到的字符送入控制台终端
+    if let Err(_) = boot::uart::uart_irq_init() {
+        return KernelInitResult::DeviceInitFailed;
+    }
+
+    // 输入子系统：键盘事件经键位表翻译后送入控制台终端
+    if let Err(_) = input::input_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 8. 系统调用子系统初始化
+    if let Err(_) = syscall::syscall_init() {
+        return KernelInitResult::ConfigurationError;
+    }
+
+    // 启动参数含kshell时在控制台串口上启动内核调试shell
+    debug::kshell::kshell_init();
+
+    // 启动参数含bench时运行微基准测试
+    perf::bench::bench_init();
+
     KernelInitResult::Success
 }
 
@@ -98,11 +281,18 @@ pub fn kernel_init() -> KernelInitResult {
 /// 在完成初始化后，内核进入主循环等待事件处理
 pub fn kernel_main() -> ! {
     match kernel_init() {
+        // 测试模式下运行内核测试后结束QEMU
+        #[cfg(feature = "test")]
+        KernelInitResult::Success => ktest::run_tests(),
+        #[cfg(not(feature = "test"))]
         KernelInitResult::Success => {
             // 初始化成功，进入正常运行模式
+            // 启动1号进程，找不到init时恐慌
+            sched::init::start_init();
+            // 启动执行流成为空闲任务：有就绪任务时让出处理器，否则等待中断
             loop {
-                // 等待中断或调度事件
-                arch::wait_for_interrupt();
+                sched::schedule();
+                sched::idle_wait();
             }
         },
         error => {
@@ -130,7 +320,24 @@ fn panic(info: &PanicInfo) -> ! {
         ));
     }
 
+    if boot::kaslr::kaslr_offset() != 0 {
+        boot::emergency_print(format_args!("内核偏移: 0x{:x}\n", boot::kaslr::kaslr_offset()));
+    }
+
+    debug::backtrace::print_backtrace(boot::emergency_print);
+
+    // 写入热重启后仍保留的崩溃记录
+    match info.message() {
+        Some(message) => debug::pstore::record_panic(*message),
+        None => debug::pstore::record_panic(format_args!("未知原因")),
+    }
+
+    // 测试模式下报告失败的测试并结束QEMU
+    #[cfg(feature = "test")]
+    ktest::test_panic();
+
     // 停止所有CPU核心
+    #[cfg(not(feature = "test"))]
     arch::halt_all_cores();
 }
 
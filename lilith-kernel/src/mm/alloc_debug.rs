@@ -0,0 +1,329 @@
+//! 调试分配器（分配毒化与释放后使用检测）
+//!
+//! 启用 `alloc_debug` 特性后，内核堆分配器用 `DebugAllocator` 包装实际的分配器，对每个对象：
+//! - 在对象前放置记录大小、分配与释放调用栈的头部，对象两侧各有 `REDZONE` 字节的红区（填充 `0xbb`）
+//! - 新分配的对象填充 `0x5a`，读取未初始化内存的错误容易辨认
+//! - 释放时检查头部与两侧红区，重复释放、释放时大小不符或红区被改写时恐慌
+//! - 释放后对象填充 `0x6b`（最后一个字节为 `0xa5`）并放入隔离区，暂不交还给实际的分配器；
+//!   隔离区满时最早的对象在交还前检查毒化字节，被改写说明发生了释放后写入
+//!
+//! 恐慌信息包含被破坏的偏移、对象的分配处与释放处，以及发现错误时的调用栈
+
+use crate::arch::{local_irq_restore, local_irq_save};
+use crate::debug::backtrace;
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::mem::{align_of, size_of};
+
+/// 红区的字节数
+const REDZONE: usize = 16;
+
+/// 红区的填充
+const POISON_REDZONE: u8 = 0xbb;
+/// 新分配对象的填充
+const POISON_INUSE: u8 = 0x5a;
+/// 已释放对象的填充
+const POISON_FREE: u8 = 0x6b;
+/// 已释放对象的最后一个字节
+const POISON_END: u8 = 0xa5;
+
+/// 头部的状态
+const MAGIC_LIVE: usize = 0xa110_ca7e_d0b1_ec75;
+const MAGIC_FREE: usize = 0xf4ee_d0b1_ec75_dead;
+
+/// 头部记录的调用栈深度
+const TRACE_DEPTH: usize = 4;
+
+/// 隔离区容纳的对象数
+const QUARANTINE_SIZE: usize = 256;
+
+/// 对象前的头部，紧挨左侧红区
+#[repr(C)]
+struct Header {
+    magic: usize,
+    /// 对象的大小
+    size: usize,
+    /// 实际分配的起始地址到对象的距离
+    offset: usize,
+    alloc_trace: [usize; TRACE_DEPTH],
+    free_trace: [usize; TRACE_DEPTH],
+}
+
+/// 发现的破坏
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Corruption {
+    /// 左侧红区中相对对象起始的偏移（负数）被改写
+    LeftRedzone(isize),
+    /// 右侧红区中相对对象起始的偏移被改写
+    RightRedzone(usize),
+    /// 释放后对象中的偏移被改写
+    UseAfterFree(usize),
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Corruption::LeftRedzone(offset) => write!(f, "左侧红区被改写（偏移 {}）", offset),
+            Corruption::RightRedzone(offset) => write!(f, "右侧红区被改写（偏移 {}）", offset),
+            Corruption::UseAfterFree(offset) => write!(f, "释放后被写入（偏移 {}）", offset),
+        }
+    }
+}
+
+/// 以符号形式输出调用栈
+struct Trace<'a>(&'a [usize]);
+
+impl fmt::Display for Trace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &ra in self.0.iter().take_while(|&&ra| ra != 0) {
+            write!(f, "\n    0x{:016x} {}", ra, backtrace::Caller(ra))?;
+        }
+        Ok(())
+    }
+}
+
+/// 记录调用者的调用栈，跳过分配器内部的一层
+#[inline(always)]
+fn capture() -> [usize; TRACE_DEPTH] {
+    let mut trace = [0; TRACE_DEPTH];
+    let mut depth = 0;
+    backtrace::walk(crate::arch::frame_pointer(), |ra| {
+        if depth >= 1 {
+            trace[depth - 1] = ra;
+        }
+        depth += 1;
+        depth <= TRACE_DEPTH
+    });
+    trace
+}
+
+/// 对象前的距离：容纳头部与左侧红区，并保持对象的对齐
+fn object_offset(align: usize) -> usize {
+    (size_of::<Header>() + REDZONE).next_multiple_of(align)
+}
+
+/// 实际分配使用的布局
+fn inner_layout(layout: Layout) -> Layout {
+    let align = layout.align().max(align_of::<Header>());
+    let size = object_offset(align) + layout.size() + REDZONE;
+    // 大小与对齐都来自合法的布局，不会溢出
+    Layout::from_size_align(size, align).unwrap()
+}
+
+unsafe fn header<'a>(object: *mut u8) -> &'a mut Header {
+    &mut *(object.sub(REDZONE + size_of::<Header>()) as *mut Header)
+}
+
+/// 第一个不等于 `value` 的字节的下标
+unsafe fn find_mismatch(start: *const u8, len: usize, value: u8) -> Option<usize> {
+    core::slice::from_raw_parts(start, len)
+        .iter()
+        .position(|&byte| byte != value)
+}
+
+/// 检查两侧红区
+unsafe fn check_redzones(object: *mut u8, size: usize) -> Option<Corruption> {
+    if let Some(index) = find_mismatch(object.sub(REDZONE), REDZONE, POISON_REDZONE) {
+        return Some(Corruption::LeftRedzone(index as isize - REDZONE as isize));
+    }
+    find_mismatch(object.add(size), REDZONE, POISON_REDZONE).map(|index| Corruption::RightRedzone(size + index))
+}
+
+/// 检查已释放对象的毒化字节
+unsafe fn check_free_poison(object: *mut u8, size: usize) -> Option<Corruption> {
+    if size == 0 {
+        return None;
+    }
+    if let Some(index) = find_mismatch(object, size - 1, POISON_FREE) {
+        return Some(Corruption::UseAfterFree(index));
+    }
+    (*object.add(size - 1) != POISON_END).then_some(Corruption::UseAfterFree(size - 1))
+}
+
+/// 报告破坏并恐慌
+fn report(object: *mut u8, header: &Header, corruption: Corruption) -> ! {
+    panic!(
+        "alloc_debug: 对象 {:p}（大小 {}）{}\n  分配于:{}\n  释放于:{}\n  发现于:{}",
+        object,
+        header.size,
+        corruption,
+        Trace(&header.alloc_trace),
+        Trace(&header.free_trace),
+        Trace(&capture()),
+    );
+}
+
+/// 已释放、尚未交还的对象
+#[derive(Clone, Copy)]
+struct Quarantined {
+    object: *mut u8,
+    layout: Layout,
+}
+
+/// 隔离区：先进先出的环形缓冲区
+struct Quarantine {
+    entries: [Option<Quarantined>; QUARANTINE_SIZE],
+    next: usize,
+}
+
+unsafe impl Send for Quarantine {}
+
+/// 检查红区、毒化字节与隔离区的分配器包装
+pub struct DebugAllocator<A> {
+    inner: A,
+    quarantine: spin::Mutex<Quarantine>,
+}
+
+impl<A> DebugAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            quarantine: spin::Mutex::new(Quarantine {
+                entries: [None; QUARANTINE_SIZE],
+                next: 0,
+            }),
+        }
+    }
+}
+
+impl<A: GlobalAlloc> DebugAllocator<A> {
+    /// 检查毒化字节后把对象交还给实际的分配器
+    unsafe fn release(&self, entry: Quarantined) {
+        let header = header(entry.object);
+        if let Some(corruption) = check_free_poison(entry.object, header.size) {
+            report(entry.object, header, corruption);
+        }
+        let offset = header.offset;
+        self.inner.dealloc(entry.object.sub(offset), inner_layout(entry.layout));
+    }
+
+    /// 交还隔离区中的所有对象，内存紧张时调用
+    pub fn flush_quarantine(&self) {
+        loop {
+            let flags = local_irq_save();
+            let entry = {
+                let mut quarantine = self.quarantine.lock();
+                quarantine.entries.iter_mut().find_map(Option::take)
+            };
+            local_irq_restore(flags);
+            match entry {
+                Some(entry) => unsafe { self.release(entry) },
+                None => break,
+            }
+        }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for DebugAllocator<A> {
+    #[inline(never)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let inner = inner_layout(layout);
+        let base = self.inner.alloc(inner);
+        if base.is_null() {
+            return base;
+        }
+        let offset = object_offset(inner.align());
+        let object = base.add(offset);
+        header(object).magic = MAGIC_LIVE;
+        header(object).size = layout.size();
+        header(object).offset = offset;
+        header(object).alloc_trace = capture();
+        header(object).free_trace = [0; TRACE_DEPTH];
+        object.sub(REDZONE).write_bytes(POISON_REDZONE, REDZONE);
+        object.write_bytes(POISON_INUSE, layout.size());
+        object.add(layout.size()).write_bytes(POISON_REDZONE, REDZONE);
+        object
+    }
+
+    #[inline(never)]
+    unsafe fn dealloc(&self, object: *mut u8, layout: Layout) {
+        let header = header(object);
+        match header.magic {
+            MAGIC_LIVE => {}
+            MAGIC_FREE => panic!(
+                "alloc_debug: 重复释放对象 {:p}\n  分配于:{}\n  首次释放于:{}\n  再次释放于:{}",
+                object,
+                Trace(&header.alloc_trace),
+                Trace(&header.free_trace),
+                Trace(&capture()),
+            ),
+            _ => panic!(
+                "alloc_debug: 释放的 {:p} 不是有效的对象或头部已被改写{}",
+                object,
+                Trace(&capture())
+            ),
+        }
+        if header.size != layout.size() {
+            panic!(
+                "alloc_debug: 释放对象 {:p} 时大小为 {}，分配时为 {}\n  分配于:{}\n  释放于:{}",
+                object,
+                layout.size(),
+                header.size,
+                Trace(&header.alloc_trace),
+                Trace(&capture()),
+            );
+        }
+        header.free_trace = capture();
+        if let Some(corruption) = check_redzones(object, header.size) {
+            report(object, header, corruption);
+        }
+        header.magic = MAGIC_FREE;
+        if let Some(last) = layout.size().checked_sub(1) {
+            object.write_bytes(POISON_FREE, last);
+            *object.add(last) = POISON_END;
+        }
+
+        let flags = local_irq_save();
+        let evicted = {
+            let mut quarantine = self.quarantine.lock();
+            let next = quarantine.next;
+            quarantine.next = (next + 1) % QUARANTINE_SIZE;
+            quarantine.entries[next].replace(Quarantined { object, layout })
+        };
+        local_irq_restore(flags);
+        // 在锁外检查，恐慌处理中的分配不会死锁
+        if let Some(entry) = evicted {
+            self.release(entry);
+        }
+    }
+}
+
+crate::kernel_test! {
+    fn alloc_debug_detects_corruption() {
+        struct Heap;
+        unsafe impl GlobalAlloc for Heap {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                alloc::alloc::alloc(layout)
+            }
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                alloc::alloc::dealloc(ptr, layout)
+            }
+        }
+
+        let allocator = DebugAllocator::new(Heap);
+        let layout = Layout::from_size_align(24, 8).unwrap();
+        unsafe {
+            let object = allocator.alloc(layout);
+            assert_eq!(object as usize % 8, 0);
+            assert_eq!(find_mismatch(object, 24, POISON_INUSE), None);
+            assert_eq!(check_redzones(object, 24), None);
+
+            // 越界一个字节
+            *object.add(24) = 0;
+            assert_eq!(check_redzones(object, 24), Some(Corruption::RightRedzone(24)));
+            *object.add(24) = POISON_REDZONE;
+            *object.sub(1) = 0;
+            assert_eq!(check_redzones(object, 24), Some(Corruption::LeftRedzone(-1)));
+            *object.sub(1) = POISON_REDZONE;
+
+            allocator.dealloc(object, layout);
+            assert_eq!(header(object).magic, MAGIC_FREE);
+            assert_eq!(check_free_poison(object, 24), None);
+            // 对象仍在隔离区中，可以检查释放后写入
+            *object.add(3) = 1;
+            assert_eq!(check_free_poison(object, 24), Some(Corruption::UseAfterFree(3)));
+            *object.add(3) = POISON_FREE;
+            allocator.flush_quarantine();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! 用户地址空间布局随机化（ASLR）
+//!
+//! 每次加载程序时为新的地址空间选择随机的布局，包括：
+//! - 用户栈顶：在地址空间顶端之下最多1GiB内随机
+//! - mmap基址：在 `MMAP_TOP` 之下最多1GiB内随机，mmap从该地址向下分配
+//! - brk堆起始：在程序映像结束之后最多32MiB内随机
+//!
+//! 通过 /proc/sys/kernel/randomize_va_space 控制（取值与Linux一致）：
+//! 0 关闭，1 随机化栈与mmap基址，2（默认）同时随机化brk堆。调试时写入0得到固定的布局
+
+use super::vma::{MMAP_TOP, PAGE_SIZE, USER_SPACE_END};
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::random;
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 栈顶随机范围（页数）
+const STACK_RANDOM_PAGES: u64 = 1 << 18;
+/// mmap基址随机范围（页数）
+const MMAP_RANDOM_PAGES: u64 = 1 << 18;
+/// brk堆起始随机范围（页数）
+const BRK_RANDOM_PAGES: u64 = 1 << 13;
+
+/// 随机化级别
+static RANDOMIZE_VA_SPACE: AtomicUsize = AtomicUsize::new(2);
+
+/// 一个地址空间的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserLayout {
+    /// 用户栈顶（页对齐）
+    pub stack_top: usize,
+    /// mmap区域上界（页对齐）
+    pub mmap_base: usize,
+    /// brk堆起始相对程序映像结束的偏移（页对齐）
+    pub brk_offset: usize,
+}
+
+impl UserLayout {
+    /// 不随机化的布局
+    pub const FIXED: UserLayout = UserLayout {
+        stack_top: USER_SPACE_END,
+        mmap_base: MMAP_TOP,
+        brk_offset: 0,
+    };
+}
+
+/// 当前的随机化级别
+pub fn randomize_va_space() -> usize {
+    RANDOMIZE_VA_SPACE.load(Ordering::Relaxed)
+}
+
+/// `[0, pages)` 页内的随机偏移（字节）
+fn random_pages(pages: u64) -> usize {
+    (random::get_random_u64() % pages) as usize * PAGE_SIZE
+}
+
+/// 为新加载的程序选择布局
+pub fn choose_layout() -> UserLayout {
+    let level = randomize_va_space();
+    let mut layout = UserLayout::FIXED;
+    if level >= 1 {
+        layout.stack_top -= random_pages(STACK_RANDOM_PAGES);
+        layout.mmap_base -= random_pages(MMAP_RANDOM_PAGES);
+    }
+    if level >= 2 {
+        layout.brk_offset = random_pages(BRK_RANDOM_PAGES);
+    }
+    layout
+}
+
+/// 注册 /proc/sys/kernel/randomize_va_space
+pub fn aslr_init() -> Result<(), KernelError> {
+    procfs::register(
+        "sys/kernel/randomize_va_space",
+        Some(Box::new(|| alloc::format!("{}\n", randomize_va_space()))),
+        Some(Box::new(|data: &str| {
+            match data.trim() {
+                "0" => RANDOMIZE_VA_SPACE.store(0, Ordering::Relaxed),
+                "1" => RANDOMIZE_VA_SPACE.store(1, Ordering::Relaxed),
+                "2" => RANDOMIZE_VA_SPACE.store(2, Ordering::Relaxed),
+                _ => return Err(KernelError::InvalidArgument),
+            }
+            Ok(())
+        })),
+    )
+}
+
+crate::kernel_test! {
+    fn layout_within_bounds() {
+        let saved = randomize_va_space();
+        RANDOMIZE_VA_SPACE.store(0, Ordering::Relaxed);
+        assert_eq!(choose_layout(), UserLayout::FIXED);
+        RANDOMIZE_VA_SPACE.store(2, Ordering::Relaxed);
+        let layout = choose_layout();
+        RANDOMIZE_VA_SPACE.store(saved, Ordering::Relaxed);
+        assert!(layout.stack_top <= USER_SPACE_END && layout.stack_top > MMAP_TOP);
+        assert!(layout.mmap_base <= MMAP_TOP);
+        assert_eq!(layout.stack_top % PAGE_SIZE, 0);
+        assert_eq!(layout.mmap_base % PAGE_SIZE, 0);
+        assert!(layout.brk_offset < BRK_RANDOM_PAGES as usize * PAGE_SIZE);
+    }
+}
@@ -0,0 +1,407 @@
+//! 内核内存泄漏检测（kmemleak）
+//!
+//! 启用 `kmemleak` 特性后，本模块跟踪内核堆上的每个对象，并用类似垃圾回收标记阶段的方法寻找泄漏，包括：
+//! - 堆分配器在分配成功后调用 `kmemleak_alloc`、释放前调用 `kmemleak_free`，记录对象的地址、大小、
+//!   分配时间与分配处的调用栈
+//! - 扫描从内核的数据段与bss段出发，把其中每个对齐的字当作可能的指针，指向被跟踪对象起始地址的对象被标记
+//!   为可达，再扫描可达对象的内容，直到没有新的可达对象；任务的内核栈也是堆对象，随任务结构被扫描
+//! - 扫描结束后仍不可达、且分配已超过 `MIN_AGE_NS` 的对象作为疑似泄漏，避免把刚分配、指针还在寄存器中的对象误报
+//! - 每隔 `SCAN_INTERVAL` 自动扫描一次，发现新的疑似泄漏时记录日志
+//! - /proc/kmemleak：读取列出疑似泄漏的对象及其分配调用栈；写入 `scan` 立即扫描，写入 `clear`
+//!   忽略当前所有疑似泄漏，写入 `off` 停止跟踪。启动参数 `kmemleak=off` 不启用跟踪
+//!
+//! 跟踪表是固定大小的静态数组，记录对象时不分配内存，可以在分配器内部调用；表满后新对象不再跟踪。
+//! 只识别指向对象起始地址的指针，只通过对象内部指针引用的对象会被误报
+
+use crate::arch::{local_irq_restore, local_irq_save};
+use crate::boot::cmdline;
+use crate::debug::backtrace;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sched::workqueue::{self, DelayedWork};
+use crate::time;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use spin::Once;
+
+/// 跟踪表的槽位数（2的幂）
+const TABLE_BITS: u32 = 13;
+const TABLE_SIZE: usize = 1 << TABLE_BITS;
+
+/// 最多跟踪的对象数，保持较低的装载率
+const MAX_OBJECTS: usize = TABLE_SIZE * 3 / 4;
+
+/// 每个对象记录的调用栈深度
+const TRACE_DEPTH: usize = 6;
+
+/// 分配后经过这段时间才可能被报告
+const MIN_AGE_NS: u64 = 5_000_000_000;
+
+/// 自动扫描的间隔
+const SCAN_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 灰色对象栈的结束标记
+const NO_OBJECT: usize = usize::MAX;
+
+/// 一个被跟踪的对象，全零表示空槽位
+#[derive(Clone, Copy)]
+struct Object {
+    ptr: usize,
+    size: usize,
+    /// 分配时的单调时钟
+    time_ns: u64,
+    /// 分配处的返回地址，不足时以0结尾
+    trace: [usize; TRACE_DEPTH],
+    /// 本次扫描中可达
+    reachable: bool,
+    /// 上次扫描后判定为疑似泄漏
+    leak: bool,
+    /// 已被 `clear` 忽略
+    ignored: bool,
+    /// 灰色对象栈中的下一个槽位
+    next_gray: usize,
+}
+
+impl Object {
+    const EMPTY: Self = Self {
+        ptr: 0,
+        size: 0,
+        time_ns: 0,
+        trace: [0; TRACE_DEPTH],
+        reachable: false,
+        leak: false,
+        ignored: false,
+        next_gray: 0,
+    };
+}
+
+/// 以对象起始地址为键、线性探测的散列表
+struct ObjectTable {
+    slots: [Object; TABLE_SIZE],
+    count: usize,
+}
+
+impl ObjectTable {
+    const fn new() -> Self {
+        Self {
+            slots: [Object::EMPTY; TABLE_SIZE],
+            count: 0,
+        }
+    }
+
+    fn home(ptr: usize) -> usize {
+        ((ptr as u64 >> 4).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - TABLE_BITS)) as usize
+    }
+
+    /// 查找起始地址为 `ptr` 的对象所在的槽位
+    fn find(&self, ptr: usize) -> Option<usize> {
+        if ptr == 0 {
+            return None;
+        }
+        let mut slot = Self::home(ptr);
+        loop {
+            match self.slots[slot].ptr {
+                0 => return None,
+                p if p == ptr => return Some(slot),
+                _ => slot = (slot + 1) % TABLE_SIZE,
+            }
+        }
+    }
+
+    /// 记录对象，同一地址的旧记录被替换，表满时返回 `false`
+    fn insert(&mut self, object: Object) -> bool {
+        if let Some(slot) = self.find(object.ptr) {
+            self.slots[slot] = object;
+            return true;
+        }
+        if self.count >= MAX_OBJECTS {
+            return false;
+        }
+        let mut slot = Self::home(object.ptr);
+        while self.slots[slot].ptr != 0 {
+            slot = (slot + 1) % TABLE_SIZE;
+        }
+        self.slots[slot] = object;
+        self.count += 1;
+        true
+    }
+
+    /// 删除对象，把后面探测链上的记录前移，使查找不会提前遇到空槽位
+    fn remove(&mut self, ptr: usize) -> bool {
+        let Some(mut hole) = self.find(ptr) else {
+            return false;
+        };
+        let mut slot = hole;
+        loop {
+            slot = (slot + 1) % TABLE_SIZE;
+            let next = self.slots[slot].ptr;
+            if next == 0 {
+                break;
+            }
+            // 记录的初始槽位不在 (hole, slot] 内时可以移到空出的槽位
+            let home = Self::home(next);
+            let between = if hole <= slot {
+                hole < home && home <= slot
+            } else {
+                hole < home || home <= slot
+            };
+            if !between {
+                self.slots[hole] = self.slots[slot];
+                hole = slot;
+            }
+        }
+        self.slots[hole] = Object::EMPTY;
+        self.count -= 1;
+        true
+    }
+
+    fn clear(&mut self) {
+        self.slots.fill(Object::EMPTY);
+        self.count = 0;
+    }
+
+    fn objects(&self) -> impl Iterator<Item = &Object> {
+        self.slots.iter().filter(|object| object.ptr != 0)
+    }
+}
+
+/// 跟踪表，分配器中也会访问，使用 `spin::Mutex` 并在关中断时加锁
+static TABLE: spin::Mutex<ObjectTable> = spin::Mutex::new(ObjectTable::new());
+
+/// 是否正在跟踪
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 表满而未能跟踪的对象数
+static UNTRACKED: AtomicUsize = AtomicUsize::new(0);
+
+/// 在关中断状态下访问跟踪表
+fn with_table<R>(f: impl FnOnce(&mut ObjectTable) -> R) -> R {
+    let flags = local_irq_save();
+    let result = f(&mut TABLE.lock());
+    local_irq_restore(flags);
+    result
+}
+
+/// 记录新分配的对象，由堆分配器在分配成功后调用
+#[inline(never)]
+pub fn kmemleak_alloc(ptr: *const u8, size: usize) {
+    if !ENABLED.load(Ordering::Relaxed) || ptr.is_null() {
+        return;
+    }
+    let mut object = Object {
+        ptr: ptr as usize,
+        size,
+        time_ns: time::monotonic_ns(),
+        ..Object::EMPTY
+    };
+    // 第一个返回地址位于分配器中，不记录
+    let mut depth = 0;
+    backtrace::walk(crate::arch::frame_pointer(), |ra| {
+        if depth >= 1 {
+            object.trace[depth - 1] = ra;
+        }
+        depth += 1;
+        depth <= TRACE_DEPTH
+    });
+    if !with_table(|table| table.insert(object)) {
+        UNTRACKED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 删除对象的记录，由堆分配器在释放前调用
+pub fn kmemleak_free(ptr: *const u8) {
+    if !ENABLED.load(Ordering::Relaxed) || ptr.is_null() {
+        return;
+    }
+    with_table(|table| table.remove(ptr as usize));
+}
+
+extern "C" {
+    static __data_start: u8;
+    static __kernel_end: u8;
+}
+
+/// 把 `[start, end)` 中每个对齐的字当作指针，标记其指向的对象并压入灰色对象栈
+fn scan_range(table: &mut ObjectTable, start: usize, end: usize, gray: &mut usize) {
+    let align = core::mem::size_of::<usize>();
+    let mut addr = (start + align - 1) & !(align - 1);
+    while addr + align <= end {
+        let value = unsafe { core::ptr::read_volatile(addr as *const usize) };
+        if let Some(slot) = table.find(value) {
+            let object = &mut table.slots[slot];
+            if !object.reachable {
+                object.reachable = true;
+                object.next_gray = *gray;
+                *gray = slot;
+            }
+        }
+        addr += align;
+    }
+}
+
+/// 扫描一次，返回新发现的疑似泄漏数
+fn scan() -> usize {
+    let (data_start, kernel_end) =
+        unsafe { (&__data_start as *const u8 as usize, &__kernel_end as *const u8 as usize) };
+    with_table(|table| {
+        for object in table.slots.iter_mut() {
+            object.reachable = false;
+            object.next_gray = NO_OBJECT;
+        }
+
+        // 跟踪表本身位于bss段，其中的地址不算引用
+        let table_start = table as *const ObjectTable as usize;
+        let table_end = table_start + core::mem::size_of::<ObjectTable>();
+        let mut gray = NO_OBJECT;
+        scan_range(table, data_start, table_start.clamp(data_start, kernel_end), &mut gray);
+        scan_range(table, table_end.clamp(data_start, kernel_end), kernel_end, &mut gray);
+        while gray != NO_OBJECT {
+            let object = table.slots[gray];
+            gray = object.next_gray;
+            scan_range(table, object.ptr, object.ptr + object.size, &mut gray);
+        }
+
+        let now = time::monotonic_ns();
+        let mut new_leaks = 0;
+        for object in table.slots.iter_mut().filter(|object| object.ptr != 0) {
+            let leak = !object.reachable && !object.ignored && now.saturating_sub(object.time_ns) >= MIN_AGE_NS;
+            if leak && !object.leak {
+                new_leaks += 1;
+            }
+            object.leak = leak;
+        }
+        new_leaks
+    })
+}
+
+/// 扫描并在发现新的疑似泄漏时记录日志
+fn scan_and_report() {
+    let new_leaks = scan();
+    if new_leaks > 0 {
+        crate::log_warn!("kmemleak: 发现 {} 个新的疑似泄漏对象（见 /proc/kmemleak）", new_leaks);
+    }
+}
+
+/// 取出疑似泄漏对象的副本，持锁期间不分配内存
+fn leaks() -> Vec<Object> {
+    let count = with_table(|table| table.objects().filter(|object| object.leak).count());
+    let mut leaks = Vec::with_capacity(count);
+    with_table(|table| {
+        for object in table.objects().filter(|object| object.leak) {
+            if leaks.len() == leaks.capacity() {
+                break;
+            }
+            leaks.push(*object);
+        }
+    });
+    leaks
+}
+
+/// 生成 /proc/kmemleak 的内容
+fn report() -> String {
+    let now = time::monotonic_ns();
+    let mut out = String::new();
+    for object in leaks() {
+        let age = now.saturating_sub(object.time_ns);
+        let _ = writeln!(out, "unreferenced object 0x{:x} (size {}):", object.ptr, object.size);
+        let _ = writeln!(out, "  age {}.{:03}s", age / 1_000_000_000, age / 1_000_000 % 1000);
+        let _ = writeln!(out, "  backtrace:");
+        for &ra in object.trace.iter().take_while(|&&ra| ra != 0) {
+            let _ = writeln!(out, "    [<0x{:016x}>] {}", ra, backtrace::Caller(ra));
+        }
+    }
+    let untracked = UNTRACKED.load(Ordering::Relaxed);
+    if untracked > 0 {
+        let _ = writeln!(out, "跟踪表已满，{} 个对象未被跟踪", untracked);
+    }
+    out
+}
+
+/// 处理写入 /proc/kmemleak 的命令
+fn command(data: &str) -> Result<(), KernelError> {
+    match data.trim() {
+        "scan" => scan_and_report(),
+        "clear" => with_table(|table| {
+            for object in table.slots.iter_mut().filter(|object| object.leak) {
+                object.leak = false;
+                object.ignored = true;
+            }
+        }),
+        "off" => {
+            ENABLED.store(false, Ordering::Release);
+            with_table(ObjectTable::clear);
+            crate::log_info!("kmemleak: 已停止跟踪");
+        }
+        _ => return Err(KernelError::InvalidArgument),
+    }
+    Ok(())
+}
+
+/// 自动扫描任务，每次执行后重新排队
+static SCANNER: Once<Arc<DelayedWork>> = Once::new();
+
+fn scan_work() {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    scan_and_report();
+    if let Some(work) = SCANNER.get() {
+        workqueue::schedule_delayed_work(work, SCAN_INTERVAL);
+    }
+}
+
+/// 开始跟踪，登记 /proc/kmemleak 并启动自动扫描
+///
+/// 此前分配的对象不被跟踪，也不会被报告
+pub fn kmemleak_init() -> Result<(), KernelError> {
+    if cmdline::get("kmemleak") == Some("off") {
+        return Ok(());
+    }
+    procfs::register("kmemleak", Some(Box::new(report)), Some(Box::new(command)))?;
+    ENABLED.store(true, Ordering::Release);
+    let work = SCANNER.call_once(|| DelayedWork::new("kmemleak", scan_work));
+    workqueue::schedule_delayed_work(work, SCAN_INTERVAL);
+    crate::log_info!("kmemleak: 开始跟踪内核堆对象");
+    Ok(())
+}
+
+crate::kernel_test! {
+    fn kmemleak_table_probe_chain() {
+        // 表较大，直接在堆上创建全零的空表
+        let mut table = unsafe { Box::<ObjectTable>::new_zeroed().assume_init() };
+        let object = |ptr| Object { ptr, size: 16, ..Object::EMPTY };
+        // 初始槽位相同的对象排在同一探测链上
+        let home = ObjectTable::home(0x1000);
+        let colliding: Vec<usize> = (1..1 << 20)
+            .map(|i| 0x1000 + i * 16)
+            .filter(|&ptr| ObjectTable::home(ptr) == home)
+            .take(2)
+            .collect();
+        assert!(table.insert(object(0x1000)));
+        for &ptr in &colliding {
+            assert!(table.insert(object(ptr)));
+        }
+        assert_eq!(table.count, 3);
+
+        // 删除链首后其余对象仍能找到
+        assert!(table.remove(0x1000));
+        assert!(!table.remove(0x1000));
+        for &ptr in &colliding {
+            assert!(table.find(ptr).is_some());
+        }
+        assert_eq!(table.count, 2);
+
+        // 扫描把指向对象起始地址的字视为引用
+        let words = [colliding[0], colliding[1] + 8];
+        let mut gray = NO_OBJECT;
+        let start = words.as_ptr() as usize;
+        scan_range(&mut table, start, start + core::mem::size_of_val(&words), &mut gray);
+        assert!(table.slots[table.find(colliding[0]).unwrap()].reachable);
+        assert!(!table.slots[table.find(colliding[1]).unwrap()].reachable);
+    }
+}
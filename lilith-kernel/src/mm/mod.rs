@@ -5,10 +5,32 @@
 //! - 虚拟内存管理
 //! - 页面分配器
 //! - 内存映射
+//! - 内核映射的W^X保护
+//! - 用户地址空间与VMA
+//! - 用户地址空间布局随机化
+//! - 安全的用户内存访问
+//! - vDSO映射
+//! - 内核堆的内存泄漏检测（kmemleak，`kmemleak` 特性）
+//! - 检查红区与释放后写入的调试分配器（`alloc_debug` 特性）
+//! - 按页分配与页面归属跟踪（page_owner，`page_owner` 特性）
+//! - 内存不足时从各子系统的缓存中回收内存（shrinker）
+//! - 映射文件使用的页缓存
 
 pub mod physical;
 pub mod virtual_mem;
 pub mod allocator;
+pub mod protect;
+pub mod vma;
+pub mod aslr;
+pub mod uaccess;
+pub mod vdso;
+#[cfg(feature = "kmemleak")]
+pub mod kmemleak;
+#[cfg(feature = "alloc_debug")]
+pub mod alloc_debug;
+pub mod page_owner;
+pub mod shrinker;
+pub mod page_cache;
 
 use crate::error::{KernelError, MemoryError};
 
@@ -19,7 +41,7 @@ pub use allocator::*;
 
 /// 内存管理初始化
 pub fn memory_init() -> Result<(), KernelError> {
-    crate::early_println!("初始化内存管理系统...");
+    crate::log_info!("初始化内存管理系统...");
 
     // 1. 初始化物理内存管理器
     physical::init_physical_memory()?;
@@ -30,6 +52,6 @@ pub fn memory_init() -> Result<(), KernelError> {
     // 3. 初始化内核堆分配器
     allocator::init_kernel_allocator()?;
 
-    crate::early_println!("内存管理系统初始化完成");
+    crate::log_info!("内存管理系统初始化完成");
     Ok(())
 }
\ No newline at end of file
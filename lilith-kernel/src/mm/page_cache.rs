@@ -0,0 +1,125 @@
+//! 文件页缓存
+//!
+//! 本模块实现了映射文件时使用的页缓存，包括：
+//! - 按文件系统与节点编号缓存文件内容的页对齐副本，最后一页超出文件长度的部分为零
+//! - 同一文件的所有只读映射（mmap与程序的代码段）共享缓存中的页，私有可写映射复制一份
+//! - 没有映射引用的缓存可以被收缩器回收，之后再次映射时重新填充
+//!
+//! 缓存的内容在填充后不再改变，只用于内容不会改变的文件（如initramfs）；
+//! 可写的文件（如共享内存对象）直接映射文件自身的内存
+
+use super::page_owner::Pages;
+use super::shrinker::{self, Shrinker};
+use crate::error::KernelError;
+use crate::sync::SpinLock;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// 缓存的键：文件系统名与节点编号
+pub type CacheKey = (&'static str, u64);
+
+/// 缓存的文件内容
+pub struct CachedFile {
+    pages: Pages,
+    /// 文件长度
+    len: usize,
+}
+
+impl CachedFile {
+    /// 缓存页的起始地址
+    pub fn addr(&self) -> usize {
+        self.pages.addr()
+    }
+
+    /// 缓存页的大小（文件长度按页取整）
+    pub fn size(&self) -> usize {
+        self.pages.size()
+    }
+
+    /// 文件内容
+    pub fn data(&self) -> &[u8] {
+        &self.pages.as_slice()[..self.len]
+    }
+}
+
+/// 已缓存的文件
+static CACHE: SpinLock<BTreeMap<CacheKey, Arc<CachedFile>>> = SpinLock::new(BTreeMap::new());
+
+/// 查找缓存的文件，不存在时分配 `len` 字节并用 `fill` 填充后加入缓存
+///
+/// 空文件没有可以映射的页，返回 `InvalidArgument`
+pub fn get_or_fill(
+    key: CacheKey,
+    len: usize,
+    fill: impl FnOnce(&mut [u8]) -> Result<(), KernelError>,
+) -> Result<Arc<CachedFile>, KernelError> {
+    if let Some(file) = CACHE.lock().get(&key) {
+        return Ok(file.clone());
+    }
+    // 填充时不持有锁，同时填充同一文件时保留先加入的一份
+    let mut pages = Pages::new(len, "page_cache")?;
+    fill(&mut pages.as_mut_slice()[..len])?;
+    let file = Arc::new(CachedFile { pages, len });
+    Ok(CACHE.lock().entry(key).or_insert(file).clone())
+}
+
+/// 回收没有被映射的缓存
+struct PageCacheShrinker;
+
+impl Shrinker for PageCacheShrinker {
+    fn name(&self) -> &str {
+        "page_cache"
+    }
+
+    fn count_objects(&self) -> usize {
+        CACHE
+            .lock()
+            .values()
+            .filter(|file| Arc::strong_count(file) == 1)
+            .count()
+    }
+
+    fn scan_objects(&self, nr_to_scan: usize) -> usize {
+        let evicted: Vec<Arc<CachedFile>> = {
+            let mut cache = CACHE.lock();
+            let keys: Vec<CacheKey> = cache
+                .iter()
+                .filter(|(_, file)| Arc::strong_count(file) == 1)
+                .map(|(key, _)| *key)
+                .take(nr_to_scan)
+                .collect();
+            keys.iter().filter_map(|key| cache.remove(key)).collect()
+        };
+        // 在锁外释放内存
+        evicted.len()
+    }
+}
+
+/// 注册页缓存的收缩器
+pub fn page_cache_init() -> Result<(), KernelError> {
+    shrinker::register_shrinker(Arc::new(PageCacheShrinker))
+}
+
+crate::kernel_test! {
+    fn page_cache_shares_and_evicts() {
+        let key = ("ktest", 1);
+        let first = get_or_fill(key, 5, |buf| {
+            buf.copy_from_slice(b"hello");
+            Ok(())
+        })
+        .unwrap();
+        let second = get_or_fill(key, 5, |_| panic!("已缓存的文件不应重新填充")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.data(), b"hello");
+        assert!(first.pages.as_slice()[5..].iter().all(|&byte| byte == 0));
+        assert!(get_or_fill(("ktest", 2), 0, |_| Ok(())).is_err());
+
+        // 仍被引用时不回收
+        PageCacheShrinker.scan_objects(usize::MAX);
+        assert!(CACHE.lock().contains_key(&key));
+        drop((first, second));
+        assert!(PageCacheShrinker.scan_objects(usize::MAX) >= 1);
+        assert!(!CACHE.lock().contains_key(&key));
+    }
+}
@@ -0,0 +1,196 @@
+//! 按页分配与页面归属跟踪（page_owner）
+//!
+//! 本模块提供按页分配清零内存的接口，供页表、用户程序映像、共享内存等以页为单位使用内存的子系统调用，包括：
+//! - `alloc_pages` 按页取整、页对齐并清零，`free_pages` 释放；`Pages` 在离开作用域时释放
+//! - 启用 `page_owner` 特性后，每次分配记录所属的子系统、分配处的源码位置、分配时的任务与时间
+//! - /sys/kernel/debug/page_owner：先按子系统汇总占用的页数（从多到少），再逐条列出仍未释放的分配
+//!
+//! 未启用特性时不做任何记录，只保留分配接口
+
+use super::vma::{page_align_up, PAGE_SIZE};
+use crate::error::KernelError;
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use core::ptr::NonNull;
+
+/// 分配 `size` 字节（按页取整）页对齐、清零的内存，`owner` 为所属的子系统
+#[track_caller]
+pub fn alloc_pages(size: usize, owner: &'static str) -> Result<NonNull<u8>, KernelError> {
+    let size = page_align_up(size);
+    if size == 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    let layout = Layout::from_size_align(size, PAGE_SIZE).map_err(|_| KernelError::InvalidArgument)?;
+    // 分配失败时先回收缓存再重试一次
+    let memory = NonNull::new(unsafe { alloc_zeroed(layout) })
+        .or_else(|| {
+            super::shrinker::reclaim(super::shrinker::SHRINK_BATCH);
+            NonNull::new(unsafe { alloc_zeroed(layout) })
+        })
+        .ok_or(KernelError::OutOfMemory)?;
+    #[cfg(feature = "page_owner")]
+    tracking::record(
+        memory.as_ptr() as usize,
+        size / PAGE_SIZE,
+        owner,
+        core::panic::Location::caller(),
+    );
+    #[cfg(not(feature = "page_owner"))]
+    let _ = owner;
+    Ok(memory)
+}
+
+/// 释放 `alloc_pages` 分配的内存
+///
+/// # Safety
+///
+/// `memory` 必须由 `alloc_pages` 以相同的 `size` 分配，且此后不再访问
+pub unsafe fn free_pages(memory: NonNull<u8>, size: usize) {
+    let size = page_align_up(size);
+    #[cfg(feature = "page_owner")]
+    tracking::forget(memory.as_ptr() as usize);
+    dealloc(memory.as_ptr(), Layout::from_size_align_unchecked(size, PAGE_SIZE));
+}
+
+/// `alloc_pages` 分配的内存，离开作用域时释放
+pub struct Pages {
+    memory: NonNull<u8>,
+    size: usize,
+}
+
+// 内存由持有者独占，映射到用户空间时由VMA持有
+unsafe impl Send for Pages {}
+unsafe impl Sync for Pages {}
+
+impl Pages {
+    /// 分配 `size` 字节（按页取整）清零的内存
+    #[track_caller]
+    pub fn new(size: usize, owner: &'static str) -> Result<Self, KernelError> {
+        let memory = alloc_pages(size, owner)?;
+        Ok(Self {
+            memory,
+            size: page_align_up(size),
+        })
+    }
+
+    /// 起始地址
+    pub fn addr(&self) -> usize {
+        self.memory.as_ptr() as usize
+    }
+
+    /// 大小（页对齐）
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.memory.as_ptr(), self.size) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.memory.as_ptr(), self.size) }
+    }
+}
+
+impl Drop for Pages {
+    fn drop(&mut self) {
+        unsafe { free_pages(self.memory, self.size) };
+    }
+}
+
+#[cfg(feature = "page_owner")]
+mod tracking {
+    use super::PAGE_SIZE;
+    use crate::error::KernelError;
+    use crate::fs::kernfs;
+    use crate::sched;
+    use crate::sync::SpinLockIrqSave;
+    use crate::time;
+    use alloc::boxed::Box;
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt::Write;
+    use core::panic::Location;
+
+    /// 一次分配的归属
+    struct PageOwner {
+        pages: usize,
+        owner: &'static str,
+        location: &'static Location<'static>,
+        /// 分配时的任务名，没有当前任务时为空
+        task: String,
+        time_ns: u64,
+    }
+
+    /// 未释放的分配，以起始地址为键
+    static OWNERS: SpinLockIrqSave<BTreeMap<usize, PageOwner>> = SpinLockIrqSave::new(BTreeMap::new());
+
+    pub(super) fn record(addr: usize, pages: usize, owner: &'static str, location: &'static Location<'static>) {
+        let task = sched::current().map(|task| task.name.clone()).unwrap_or_default();
+        let record = PageOwner {
+            pages,
+            owner,
+            location,
+            task,
+            time_ns: time::monotonic_ns(),
+        };
+        OWNERS.lock().insert(addr, record);
+    }
+
+    pub(super) fn forget(addr: usize) {
+        OWNERS.lock().remove(&addr);
+    }
+
+    /// 生成 /sys/kernel/debug/page_owner 的内容
+    fn dump() -> String {
+        let owners = OWNERS.lock();
+        let mut totals: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for record in owners.values() {
+            *totals.entry(record.owner).or_default() += record.pages;
+        }
+        let mut totals: Vec<_> = totals.into_iter().collect();
+        totals.sort_by_key(|&(_, pages)| core::cmp::Reverse(pages));
+
+        let mut out = String::new();
+        for (owner, pages) in totals {
+            let _ = writeln!(out, "{:<16} {:>8} 页 {:>8} KiB", owner, pages, pages * PAGE_SIZE / 1024);
+        }
+        let now = time::monotonic_ns();
+        for (addr, record) in owners.iter() {
+            let age = now.saturating_sub(record.time_ns);
+            let _ = writeln!(
+                out,
+                "\n0x{:x} {} 页 owner={} task={} age={}.{:03}s\n  {}",
+                addr,
+                record.pages,
+                record.owner,
+                if record.task.is_empty() { "-" } else { &record.task },
+                age / 1_000_000_000,
+                age / 1_000_000 % 1000,
+                record.location,
+            );
+        }
+        out
+    }
+
+    pub(super) fn register() -> Result<(), KernelError> {
+        kernfs::register("/sys/kernel/debug/page_owner", Some(Box::new(dump)), None)
+    }
+}
+
+/// 登记 /sys/kernel/debug/page_owner
+#[cfg(feature = "page_owner")]
+pub fn page_owner_init() -> Result<(), KernelError> {
+    tracking::register()
+}
+
+crate::kernel_test! {
+    fn page_owner_alloc_pages() {
+        let memory = alloc_pages(PAGE_SIZE + 1, "test").unwrap();
+        assert_eq!(memory.as_ptr() as usize % PAGE_SIZE, 0);
+        let bytes = unsafe { core::slice::from_raw_parts(memory.as_ptr(), 2 * PAGE_SIZE) };
+        assert!(bytes.iter().all(|&byte| byte == 0));
+        assert_eq!(alloc_pages(0, "test"), Err(KernelError::InvalidArgument));
+        unsafe { free_pages(memory, PAGE_SIZE + 1) };
+    }
+}
@@ -0,0 +1,142 @@
+//! 内核映射的W^X保护
+//!
+//! 启动代码把整个内核映射为可读、可写、可执行，本模块按链接脚本给出的段边界收紧权限，包括：
+//! - .text：可读、可执行
+//! - .rodata：只读
+//! - .data、.bss与其余内核映射：可读写、不可执行
+//! - 启动时检查没有既可写又可执行的内核映射
+//! - `text_poke`：调试器写断点等需要修改代码时，临时给所在页加上写权限
+//!
+//! 段边界不是页对齐时无法按页区分权限，只报告而不修改
+
+use crate::arch::pgtable::{self, PAGE_SIZE, PTE_R, PTE_U, PTE_W, PTE_X};
+use crate::error::{KernelError, MemoryError};
+use crate::mm::uaccess::copy_to_kernel_nofault;
+
+extern "C" {
+    static __kernel_start: u8;
+    static __kernel_end: u8;
+    static __text_end: u8;
+    static __data_start: u8;
+}
+
+/// 内核映像的段边界
+struct KernelSections {
+    /// 代码段起始（内核映像起始）
+    text_start: usize,
+    /// 代码段结束，只读数据段起始
+    text_end: usize,
+    /// 数据段起始，只读数据段结束
+    data_start: usize,
+    /// 内核映像结束
+    kernel_end: usize,
+}
+
+fn kernel_sections() -> KernelSections {
+    unsafe {
+        KernelSections {
+            text_start: &__kernel_start as *const u8 as usize,
+            text_end: &__text_end as *const u8 as usize,
+            data_start: &__data_start as *const u8 as usize,
+            kernel_end: &__kernel_end as *const u8 as usize,
+        }
+    }
+}
+
+/// 检查所有内核映射，报告既可写又可执行的映射，存在时返回错误
+pub fn check_wx() -> Result<(), KernelError> {
+    let mut found = 0;
+    pgtable::for_each_leaf(|va, size, pte| {
+        if pte & PTE_U == 0 && pte & (PTE_W | PTE_X) == (PTE_W | PTE_X) {
+            crate::log_error!("W+X内核映射: 0x{:x}-0x{:x}", va, va.wrapping_add(size));
+            found += 1;
+        }
+    });
+    if found > 0 {
+        crate::log_error!("发现 {} 个W+X内核映射", found);
+        return Err(KernelError::PermissionDenied);
+    }
+    Ok(())
+}
+
+/// 把 `[addr, addr + size)` 设为可读、可执行、不可写
+pub fn set_memory_rox(addr: usize, size: usize) -> Result<(), KernelError> {
+    pgtable::update_kernel_flags(addr, addr + size, PTE_R | PTE_X, PTE_W)?;
+    pgtable::flush_tlb_all();
+    Ok(())
+}
+
+/// 把 `[addr, addr + size)` 设为可读写、不可执行
+pub fn set_memory_rw_nx(addr: usize, size: usize) -> Result<(), KernelError> {
+    pgtable::update_kernel_flags(addr, addr + size, PTE_R | PTE_W, PTE_X)?;
+    pgtable::flush_tlb_all();
+    Ok(())
+}
+
+/// 修改内核代码：临时给所在页加上写权限，写入后恢复并刷新指令缓存
+///
+/// 用于调试器写入断点等场景，可以在陷入处理中调用，不分配内存。大页整页临时可写
+pub fn text_poke(addr: usize, bytes: &[u8]) -> Result<(), MemoryError> {
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let dst = addr + offset;
+        let len = (PAGE_SIZE - dst % PAGE_SIZE).min(bytes.len() - offset);
+        let chunk = &bytes[offset..offset + len];
+        match pgtable::leaf_entry(dst) {
+            Some(entry) if unsafe { *entry } & (PTE_X | PTE_W) == PTE_X => {
+                let pte = unsafe { *entry };
+                unsafe {
+                    *entry = pte | PTE_W;
+                    core::arch::asm!("sfence.vma {}", in(reg) dst);
+                }
+                let result = copy_to_kernel_nofault(dst, chunk);
+                unsafe { *entry = pte };
+                pgtable::flush_tlb_all();
+                result?;
+            }
+            _ => copy_to_kernel_nofault(dst, chunk)?,
+        }
+        offset += len;
+    }
+    crate::arch::flush_icache();
+    Ok(())
+}
+
+/// 按段设置内核映射的权限并检查W^X
+pub fn protect_init() -> Result<(), KernelError> {
+    if pgtable::root_table().is_none() {
+        crate::log_warn!("未开启分页，跳过内核映射的W^X保护");
+        return Ok(());
+    }
+
+    let sections = kernel_sections();
+    let aligned = [sections.text_start, sections.text_end, sections.data_start]
+        .iter()
+        .all(|&addr| addr % PAGE_SIZE == 0);
+    if !aligned {
+        crate::log_warn!(
+            "内核段边界未按页对齐（text 0x{:x}-0x{:x}，data 0x{:x}），跳过W^X保护",
+            sections.text_start,
+            sections.text_end,
+            sections.data_start
+        );
+        return Ok(());
+    }
+
+    // 先去掉所有内核映射的执行权限，再只给代码段加回
+    pgtable::update_kernel_flags(0, usize::MAX, 0, PTE_X)?;
+    pgtable::update_kernel_flags(sections.text_start, sections.text_end, PTE_R | PTE_X, PTE_W)?;
+    pgtable::update_kernel_flags(sections.text_end, sections.data_start, PTE_R, PTE_W | PTE_X)?;
+    pgtable::flush_tlb_all();
+
+    crate::log_info!(
+        "内核W^X: text 0x{:x}-0x{:x} RX，rodata 0x{:x}-0x{:x} R，data 0x{:x}-0x{:x} RW",
+        sections.text_start,
+        sections.text_end,
+        sections.text_end,
+        sections.data_start,
+        sections.data_start,
+        sections.kernel_end
+    );
+    check_wx()
+}
@@ -0,0 +1,163 @@
+//! 内存回收与缓存收缩（shrinker）
+//!
+//! 本模块实现了内存不足时从各子系统的缓存中回收内存的机制，语义与Linux的shrinker一致，包括：
+//! - 持有可丢弃缓存的子系统注册收缩器（`Shrinker`），报告可回收的对象数并按要求释放其中一部分
+//! - 回收（`reclaim`）从低到高提高扫描强度：第 `p` 轮要求每个收缩器扫描其对象的 `1/2^p`，
+//!   释放的对象达到目标即停止，尽量只丢弃缓存的一小部分
+//! - 按页分配失败时先回收再重试一次，长时间运行后缓存占满内存也不会直接分配失败
+//! - /proc/sys/vm/drop_caches：写入 `1`、`2` 或 `3` 丢弃所有收缩器的全部缓存；
+//!   /proc/shrinkers 列出各收缩器可回收的对象数与累计回收的数量
+//!
+//! 收缩器在回收者的上下文中调用，可能持有分配者的锁，扫描时不应等待其他任务
+
+use crate::error::KernelError;
+use crate::fs::procfs;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 第一轮的扫描比例为 `1/2^DEF_PRIORITY`
+const DEF_PRIORITY: u32 = 12;
+
+/// 分配失败时回收的对象数
+pub const SHRINK_BATCH: usize = 128;
+
+/// 可回收的缓存
+pub trait Shrinker: Send + Sync {
+    /// 名称，用于注销
+    fn name(&self) -> &str;
+
+    /// 当前可以回收的对象数
+    fn count_objects(&self) -> usize;
+
+    /// 扫描至多 `nr_to_scan` 个对象，释放其中可以释放的，返回释放的数量
+    fn scan_objects(&self, nr_to_scan: usize) -> usize;
+}
+
+/// 已注册的收缩器
+static SHRINKERS: Mutex<Vec<Arc<dyn Shrinker>>> = Mutex::new(Vec::new());
+
+/// 回收的次数与释放的对象总数
+static RECLAIM_RUNS: AtomicU64 = AtomicU64::new(0);
+static RECLAIMED: AtomicU64 = AtomicU64::new(0);
+
+/// 注册收缩器，同名的收缩器已存在时返回 `AlreadyExists`
+pub fn register_shrinker(shrinker: Arc<dyn Shrinker>) -> Result<(), KernelError> {
+    let mut shrinkers = SHRINKERS.lock();
+    if shrinkers.iter().any(|s| s.name() == shrinker.name()) {
+        return Err(KernelError::AlreadyExists);
+    }
+    shrinkers.push(shrinker);
+    Ok(())
+}
+
+/// 注销收缩器
+pub fn unregister_shrinker(name: &str) {
+    SHRINKERS.lock().retain(|shrinker| shrinker.name() != name);
+}
+
+/// 逐步提高扫描强度回收对象，直到释放 `nr_wanted` 个或所有缓存都已扫描完，返回释放的数量
+///
+/// 回收时不持有收缩器列表的锁，收缩器可以注册或注销其他收缩器
+pub fn reclaim(nr_wanted: usize) -> usize {
+    let shrinkers = SHRINKERS.lock().clone();
+    let mut freed = 0;
+    for priority in (0..=DEF_PRIORITY).rev() {
+        for shrinker in &shrinkers {
+            let count = shrinker.count_objects();
+            if count == 0 {
+                continue;
+            }
+            freed += shrinker.scan_objects((count >> priority).max(1));
+            if freed >= nr_wanted {
+                break;
+            }
+        }
+        if freed >= nr_wanted {
+            break;
+        }
+    }
+    RECLAIM_RUNS.fetch_add(1, Ordering::Relaxed);
+    RECLAIMED.fetch_add(freed as u64, Ordering::Relaxed);
+    if freed > 0 {
+        crate::log_debug!("shrinker: 回收了 {} 个对象", freed);
+    }
+    freed
+}
+
+/// 丢弃所有收缩器的全部缓存，返回释放的数量
+pub fn drop_caches() -> usize {
+    let mut total = 0;
+    loop {
+        let freed = reclaim(usize::MAX);
+        if freed == 0 {
+            return total;
+        }
+        total += freed;
+    }
+}
+
+/// 各收缩器当前可回收的对象数与累计回收情况
+fn read_shrinkers() -> String {
+    let mut out = String::new();
+    for shrinker in SHRINKERS.lock().iter() {
+        let _ = writeln!(out, "{:<16} {}", shrinker.name(), shrinker.count_objects());
+    }
+    let _ = writeln!(out, "reclaim_runs {}", RECLAIM_RUNS.load(Ordering::Relaxed));
+    let _ = writeln!(out, "reclaimed {}", RECLAIMED.load(Ordering::Relaxed));
+    out
+}
+
+/// 登记 /proc/sys/vm/drop_caches 与 /proc/shrinkers
+pub fn shrinker_init() -> Result<(), KernelError> {
+    procfs::register(
+        "sys/vm/drop_caches",
+        Some(Box::new(|| String::from("0\n"))),
+        Some(Box::new(|data| match data.trim() {
+            "1" | "2" | "3" => {
+                let freed = drop_caches();
+                crate::log_info!("drop_caches: 释放了 {} 个缓存对象", freed);
+                Ok(())
+            }
+            _ => Err(KernelError::InvalidArgument),
+        })),
+    )?;
+    procfs::register("shrinkers", Some(Box::new(read_shrinkers)), None)
+}
+
+crate::kernel_test! {
+    fn shrinker_reclaim_stops_at_target() {
+        use core::sync::atomic::AtomicUsize;
+
+        struct TestCache(AtomicUsize);
+        impl Shrinker for TestCache {
+            fn name(&self) -> &str {
+                "test"
+            }
+            fn count_objects(&self) -> usize {
+                self.0.load(Ordering::Relaxed)
+            }
+            fn scan_objects(&self, nr_to_scan: usize) -> usize {
+                let freed = nr_to_scan.min(self.0.load(Ordering::Relaxed));
+                self.0.fetch_sub(freed, Ordering::Relaxed);
+                freed
+            }
+        }
+
+        let cache = Arc::new(TestCache(AtomicUsize::new(1 << 14)));
+        register_shrinker(cache.clone()).unwrap();
+        assert_eq!(register_shrinker(cache.clone()), Err(KernelError::AlreadyExists));
+
+        // 第一轮只扫描 1/4096，目标很小时不会丢弃大部分缓存
+        assert!(reclaim(1) >= 1);
+        assert!(cache.count_objects() >= (1 << 14) - 4);
+
+        drop_caches();
+        assert_eq!(cache.count_objects(), 0);
+        unregister_shrinker("test");
+    }
+}
@@ -0,0 +1,109 @@
+//! 安全的用户内存访问
+//!
+//! 系统调用不能直接解引用用户指针，必须通过本模块复制数据：
+//! - 先检查地址范围是否落在当前任务具有相应权限的VMA中
+//! - 复制过程中的页错误由异常表修复，返回 `MemoryError::PageFault`
+//!   而不会使内核崩溃
+//!
+//! 调试器访问任意内核地址时同样借助异常表，无效地址返回错误而不是使内核崩溃
+
+use super::vma::VmaFlags;
+use crate::arch::copy_user_raw;
+use crate::error::MemoryError;
+use crate::sched;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::{size_of, MaybeUninit};
+
+/// 检查当前任务对 `[addr, addr + len)` 是否具有 `required` 权限
+fn access_ok(addr: usize, len: usize, required: VmaFlags) -> Result<(), MemoryError> {
+    let address_space = sched::current()
+        .and_then(|task| task.address_space())
+        .ok_or(MemoryError::InvalidAddress)?;
+    if address_space.check_range(addr, len, required) {
+        Ok(())
+    } else {
+        Err(MemoryError::InvalidAddress)
+    }
+}
+
+/// 从用户地址 `src` 复制数据到 `dst`
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> Result<(), MemoryError> {
+    access_ok(src, dst.len(), VmaFlags::READ)?;
+    let remaining = unsafe { copy_user_raw(dst.as_mut_ptr(), src as *const u8, dst.len()) };
+    if remaining == 0 {
+        Ok(())
+    } else {
+        Err(MemoryError::PageFault)
+    }
+}
+
+/// 将 `src` 复制到用户地址 `dst`
+pub fn copy_to_user(dst: usize, src: &[u8]) -> Result<(), MemoryError> {
+    access_ok(dst, src.len(), VmaFlags::WRITE)?;
+    let remaining = unsafe { copy_user_raw(dst as *mut u8, src.as_ptr(), src.len()) };
+    if remaining == 0 {
+        Ok(())
+    } else {
+        Err(MemoryError::PageFault)
+    }
+}
+
+/// 从内核地址 `src` 复制数据到 `dst`，地址无效时返回 `PageFault`
+pub fn copy_from_kernel_nofault(dst: &mut [u8], src: usize) -> Result<(), MemoryError> {
+    let remaining = unsafe { copy_user_raw(dst.as_mut_ptr(), src as *const u8, dst.len()) };
+    if remaining == 0 {
+        Ok(())
+    } else {
+        Err(MemoryError::PageFault)
+    }
+}
+
+/// 将 `src` 复制到内核地址 `dst`，地址无效或只读时返回 `PageFault`
+pub fn copy_to_kernel_nofault(dst: usize, src: &[u8]) -> Result<(), MemoryError> {
+    let remaining = unsafe { copy_user_raw(dst as *mut u8, src.as_ptr(), src.len()) };
+    if remaining == 0 {
+        Ok(())
+    } else {
+        Err(MemoryError::PageFault)
+    }
+}
+
+/// 从用户地址读取一个值
+///
+/// `T` 必须是任意位模式都合法的类型（如整数与由整数组成的结构体）
+pub fn read_user<T: Copy>(src: usize) -> Result<T, MemoryError> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_from_user(bytes, src)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+/// 向用户地址写入一个值
+pub fn write_user<T: Copy>(dst: usize, value: &T) -> Result<(), MemoryError> {
+    let bytes = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    copy_to_user(dst, bytes)
+}
+
+/// 从用户地址读取以0结尾的字符串，最长 `max_len` 字节（不含结尾的0）
+pub fn strncpy_from_user(src: usize, max_len: usize) -> Result<String, MemoryError> {
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64];
+    let mut addr = src;
+
+    while bytes.len() < max_len {
+        // 按页边界分块，避免跨入不可访问的下一页
+        let page_left = super::vma::PAGE_SIZE - addr % super::vma::PAGE_SIZE;
+        let len = chunk.len().min(page_left).min(max_len - bytes.len() + 1);
+        copy_from_user(&mut chunk[..len], addr)?;
+
+        if let Some(nul) = chunk[..len].iter().position(|&b| b == 0) {
+            bytes.extend_from_slice(&chunk[..nul]);
+            return String::from_utf8(bytes).map_err(|_| MemoryError::InvalidAddress);
+        }
+        bytes.extend_from_slice(&chunk[..len]);
+        addr += len;
+    }
+    // 超过最大长度仍未找到结尾
+    Err(MemoryError::InvalidAddress)
+}
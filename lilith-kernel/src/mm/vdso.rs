@@ -0,0 +1,64 @@
+//! vDSO映射
+//!
+//! 每个用户进程在固定地址映射三段只读区域：
+//! - 时间数据页：与内核共享的时钟状态，由定时器中断经顺序锁更新
+//! - 进程数据页：进程号等只属于本进程的数据
+//! - 代码页：读取上面两页的函数，入口偏移见架构的vDSO实现
+//!
+//! 共享地址空间的vfork子进程在exec之前看到的是父进程的进程数据页
+
+use super::vma::{AddressSpace, Vma, VmaBacking, VmaFlags, MMAP_TOP, PAGE_SIZE};
+use crate::error::MemoryError;
+use alloc::boxed::Box;
+
+/// vDSO在用户地址空间中的起始地址
+pub const VDSO_BASE: usize = MMAP_TOP;
+/// 时间数据页的用户地址
+pub const VDSO_TIME_PAGE: usize = VDSO_BASE;
+/// 进程数据页的用户地址
+pub const VDSO_PROCESS_PAGE: usize = VDSO_BASE + PAGE_SIZE;
+/// 代码页的用户地址
+pub const VDSO_CODE: usize = VDSO_BASE + 2 * PAGE_SIZE;
+
+/// 进程数据页
+#[repr(C, align(4096))]
+pub struct VdsoProcessPage {
+    /// 进程号
+    pub pid: usize,
+}
+
+impl VdsoProcessPage {
+    /// 分配进程数据页
+    pub fn new(pid: usize) -> Box<Self> {
+        Box::new(Self { pid })
+    }
+}
+
+/// 将vDSO映射到地址空间（创建新地址空间时调用）
+pub fn map_vdso(address_space: &AddressSpace, process_page: &VdsoProcessPage) -> Result<(), MemoryError> {
+    let regions = [
+        (VDSO_TIME_PAGE, PAGE_SIZE, VmaFlags::READ, crate::time::vdso_time_page()),
+        (
+            VDSO_PROCESS_PAGE,
+            PAGE_SIZE,
+            VmaFlags::READ,
+            process_page as *const VdsoProcessPage as usize,
+        ),
+        (
+            VDSO_CODE,
+            crate::arch::vdso_code_len(),
+            VmaFlags::READ | VmaFlags::EXEC,
+            crate::arch::vdso_code_page(),
+        ),
+    ];
+
+    for (start, len, flags, kernel_page) in regions {
+        address_space.insert(Vma {
+            start,
+            end: start + len,
+            flags,
+            backing: VmaBacking::Kernel(kernel_page),
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,515 @@
+//! 用户地址空间与虚拟内存区域（VMA）
+//!
+//! 每个用户进程拥有一个地址空间，由若干互不重叠的VMA组成：
+//! - VMA描述一段页对齐的用户虚拟地址范围及其访问权限
+//! - 内核访问用户内存前按VMA检查地址范围与权限
+//! - brk堆与mmap区域的分配（物理页在缺页时按需建立映射）
+//! - 栈顶、mmap基址与brk堆起始按地址空间随机化（见 `aslr`）
+//! - 映射内核内存的VMA可以持有内存的所有者，最后一个指向它的VMA删除时释放
+//! - 修改一段范围的访问权限（mprotect），映射内核内存的VMA只能收回权限
+//! - 锁定一段范围（mlock），锁定的总长度受 `RLIMIT_MEMLOCK` 限制，锁定的页不会被丢弃
+
+use super::aslr::UserLayout;
+use crate::error::MemoryError;
+use crate::sync::{RwLock, SpinLock};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+/// 页大小
+pub const PAGE_SIZE: usize = 4096;
+
+/// 用户地址空间上界（Sv39低半部分）
+pub const USER_SPACE_END: usize = 0x40_0000_0000;
+
+/// mmap区域上界，之上留给用户栈
+pub const MMAP_TOP: usize = 0x3f_0000_0000;
+
+/// 映射到用户空间的内核内存的所有者，映射存在期间保持内存有效
+pub type MapOwner = Arc<dyn Send + Sync>;
+
+/// 向上按页对齐
+pub const fn page_align_up(addr: usize) -> usize {
+    (addr + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+bitflags! {
+    /// VMA访问权限
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VmaFlags: u32 {
+        const READ   = 1 << 0;
+        const WRITE  = 1 << 1;
+        const EXEC   = 1 << 2;
+        /// 多个地址空间共享同一物理页
+        const SHARED = 1 << 3;
+        /// 栈，向低地址增长
+        const STACK  = 1 << 4;
+        /// 已锁定，页不会被丢弃
+        const LOCKED = 1 << 5;
+    }
+}
+
+/// VMA的物理页来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmaBacking {
+    /// 匿名内存，首次访问时分配清零的物理页
+    Anonymous,
+    /// 映射从给定内核地址开始的已有内核页（如vDSO）
+    Kernel(usize),
+}
+
+impl VmaBacking {
+    /// 区域起点后移 `offset` 字节后的来源
+    fn offset(self, offset: usize) -> Self {
+        match self {
+            VmaBacking::Anonymous => VmaBacking::Anonymous,
+            VmaBacking::Kernel(base) => VmaBacking::Kernel(base + offset),
+        }
+    }
+}
+
+/// 虚拟内存区域 `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vma {
+    /// 起始地址（页对齐）
+    pub start: usize,
+    /// 结束地址（页对齐，不含）
+    pub end: usize,
+    /// 访问权限
+    pub flags: VmaFlags,
+    /// 物理页来源
+    pub backing: VmaBacking,
+}
+
+impl Vma {
+    /// 区域长度
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// 区域是否为空
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// 是否包含地址
+    pub fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+}
+
+/// 用户地址空间
+pub struct AddressSpace {
+    /// 按起始地址索引的VMA
+    vmas: RwLock<BTreeMap<usize, Vma>>,
+    /// brk堆 `(起始地址, 当前结束地址)`
+    heap: SpinLock<(usize, usize)>,
+    /// 栈顶、mmap基址与brk偏移
+    layout: SpinLock<UserLayout>,
+    /// 映射的内核内存 `[起始, 结束)` 及其所有者
+    owners: SpinLock<Vec<(usize, usize, MapOwner)>>,
+}
+
+impl AddressSpace {
+    /// 创建空的地址空间
+    pub const fn new() -> Self {
+        Self {
+            vmas: RwLock::new(BTreeMap::new()),
+            heap: SpinLock::new((0, 0)),
+            layout: SpinLock::new(UserLayout::FIXED),
+            owners: SpinLock::new(Vec::new()),
+        }
+    }
+
+    /// 设置地址空间的布局（加载程序时在映射栈与设置brk堆之前调用）
+    pub fn set_layout(&self, layout: UserLayout) {
+        *self.layout.lock() = layout;
+    }
+
+    /// 地址空间的布局
+    pub fn layout(&self) -> UserLayout {
+        *self.layout.lock()
+    }
+
+    /// 添加VMA，不能与已有区域重叠
+    pub fn insert(&self, vma: Vma) -> Result<(), MemoryError> {
+        if vma.start % PAGE_SIZE != 0 || vma.end % PAGE_SIZE != 0 {
+            return Err(MemoryError::AlignmentError);
+        }
+        if vma.start >= vma.end || vma.end > USER_SPACE_END {
+            return Err(MemoryError::InvalidAddress);
+        }
+
+        let mut vmas = self.vmas.write();
+        let overlaps = vmas
+            .range(..vma.end)
+            .next_back()
+            .map_or(false, |(_, prev)| prev.end > vma.start);
+        if overlaps {
+            return Err(MemoryError::InvalidAddress);
+        }
+        vmas.insert(vma.start, vma);
+        Ok(())
+    }
+
+    /// 添加映射内核内存的VMA，`owner` 保持到最后一个映射这段内存的VMA被删除
+    pub fn insert_owned(&self, vma: Vma, owner: MapOwner) -> Result<(), MemoryError> {
+        let VmaBacking::Kernel(base) = vma.backing else {
+            return Err(MemoryError::InvalidAddress);
+        };
+        self.insert(vma)?;
+        self.owners.lock().push((base, base + vma.len(), owner));
+        Ok(())
+    }
+
+    /// 删除 `[start, end)` 范围内的映射，部分覆盖的VMA会被拆分
+    pub fn remove(&self, start: usize, end: usize) -> Result<(), MemoryError> {
+        self.remove_vmas(start, end)?;
+        // 所有者可能释放内存，在VMA的锁外释放
+        let released = self.release_owners();
+        drop(released);
+        Ok(())
+    }
+
+    /// 取出已经没有VMA映射其内存的所有者
+    fn release_owners(&self) -> Vec<MapOwner> {
+        let mut owners = self.owners.lock();
+        if owners.is_empty() {
+            return Vec::new();
+        }
+        let vmas = self.vmas.read();
+        let mapped = |base: usize, end: usize| {
+            vmas.values().any(|vma| match vma.backing {
+                VmaBacking::Kernel(addr) => addr < end && base < addr + vma.len(),
+                VmaBacking::Anonymous => false,
+            })
+        };
+        let mut released = Vec::new();
+        owners.retain(|(base, end, owner)| {
+            let keep = mapped(*base, *end);
+            if !keep {
+                released.push(owner.clone());
+            }
+            keep
+        });
+        released
+    }
+
+    fn remove_vmas(&self, start: usize, end: usize) -> Result<(), MemoryError> {
+        if start % PAGE_SIZE != 0 || end % PAGE_SIZE != 0 || start > end {
+            return Err(MemoryError::InvalidAddress);
+        }
+
+        let mut vmas = self.vmas.write();
+        let affected: Vec<Vma> = vmas
+            .range(..end)
+            .map(|(_, vma)| *vma)
+            .filter(|vma| vma.end > start)
+            .collect();
+
+        for vma in affected {
+            vmas.remove(&vma.start);
+            if vma.start < start {
+                vmas.insert(vma.start, Vma { end: start, ..vma });
+            }
+            if vma.end > end {
+                vmas.insert(
+                    end,
+                    Vma {
+                        start: end,
+                        backing: vma.backing.offset(end - vma.start),
+                        ..vma
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// 把 `[start, end)` 的访问权限改为 `perms`（`READ`、`WRITE`、`EXEC` 的组合），部分覆盖的VMA会被拆分
+    ///
+    /// 范围必须完全被VMA覆盖，否则返回 `InvalidAddress`；映射内核内存的VMA不能获得原来没有的权限，
+    /// 否则返回 `PermissionDenied`。出错时不修改任何VMA
+    pub fn protect(&self, start: usize, end: usize, perms: VmaFlags) -> Result<(), MemoryError> {
+        let access = VmaFlags::READ | VmaFlags::WRITE | VmaFlags::EXEC;
+        let perms = perms & access;
+        self.update_range(
+            start,
+            end,
+            |_, affected| {
+                let gains = affected
+                    .iter()
+                    .any(|vma| matches!(vma.backing, VmaBacking::Kernel(_)) && !vma.flags.contains(perms));
+                if gains {
+                    return Err(MemoryError::PermissionDenied);
+                }
+                Ok(())
+            },
+            |flags| (flags - access) | perms,
+        )
+    }
+
+    /// 锁定（`locked` 为真）或解锁 `[start, end)`，部分覆盖的VMA会被拆分
+    ///
+    /// 范围必须完全被VMA覆盖，否则返回 `InvalidAddress`；锁定后地址空间锁定的总长度超过 `limit`
+    /// 字节时返回 `OutOfMemory`，`limit` 为 `None` 时不限制。出错时不修改任何VMA
+    pub fn set_locked(&self, start: usize, end: usize, locked: bool, limit: Option<usize>) -> Result<(), MemoryError> {
+        let lock = if locked { VmaFlags::LOCKED } else { VmaFlags::empty() };
+        self.update_range(
+            start,
+            end,
+            |vmas, affected| {
+                let Some(limit) = limit.filter(|_| locked) else {
+                    return Ok(());
+                };
+                let total: usize = vmas
+                    .values()
+                    .filter(|vma| vma.flags.contains(VmaFlags::LOCKED))
+                    .map(Vma::len)
+                    .sum();
+                // 范围内已经锁定的部分不重复计算
+                let already: usize = affected
+                    .iter()
+                    .filter(|vma| vma.flags.contains(VmaFlags::LOCKED))
+                    .map(|vma| vma.end.min(end) - vma.start.max(start))
+                    .sum();
+                if total - already + (end - start) > limit {
+                    return Err(MemoryError::OutOfMemory);
+                }
+                Ok(())
+            },
+            |flags| (flags - VmaFlags::LOCKED) | lock,
+        )
+    }
+
+    /// 锁定的总长度
+    pub fn locked_len(&self) -> usize {
+        self.vmas
+            .read()
+            .values()
+            .filter(|vma| vma.flags.contains(VmaFlags::LOCKED))
+            .map(Vma::len)
+            .sum()
+    }
+
+    /// 把完全被VMA覆盖的 `[start, end)` 的权限改为 `update(原权限)`，部分覆盖的VMA会被拆分
+    ///
+    /// 修改前以所有VMA与范围内的VMA调用 `check`，返回错误时不修改
+    fn update_range(
+        &self,
+        start: usize,
+        end: usize,
+        check: impl FnOnce(&BTreeMap<usize, Vma>, &[Vma]) -> Result<(), MemoryError>,
+        update: impl Fn(VmaFlags) -> VmaFlags,
+    ) -> Result<(), MemoryError> {
+        if start % PAGE_SIZE != 0 || end % PAGE_SIZE != 0 {
+            return Err(MemoryError::AlignmentError);
+        }
+        if start >= end || end > USER_SPACE_END {
+            return Err(MemoryError::InvalidAddress);
+        }
+
+        let mut vmas = self.vmas.write();
+        let affected: Vec<Vma> = vmas
+            .range(..end)
+            .map(|(_, vma)| *vma)
+            .filter(|vma| vma.end > start)
+            .collect();
+
+        // 先检查整个范围，再修改
+        let mut cursor = start;
+        for vma in &affected {
+            if vma.start > cursor {
+                return Err(MemoryError::InvalidAddress);
+            }
+            cursor = vma.end;
+        }
+        if cursor < end {
+            return Err(MemoryError::InvalidAddress);
+        }
+        check(&vmas, &affected)?;
+
+        for vma in affected {
+            vmas.remove(&vma.start);
+            if vma.start < start {
+                vmas.insert(vma.start, Vma { end: start, ..vma });
+            }
+            if vma.end > end {
+                vmas.insert(
+                    end,
+                    Vma {
+                        start: end,
+                        backing: vma.backing.offset(end - vma.start),
+                        ..vma
+                    },
+                );
+            }
+            let middle = vma.start.max(start);
+            vmas.insert(
+                middle,
+                Vma {
+                    start: middle,
+                    end: vma.end.min(end),
+                    flags: update(vma.flags),
+                    backing: vma.backing.offset(middle - vma.start),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// 查找包含地址的VMA
+    pub fn find(&self, addr: usize) -> Option<Vma> {
+        self.vmas
+            .read()
+            .range(..=addr)
+            .next_back()
+            .map(|(_, vma)| *vma)
+            .filter(|vma| vma.contains(addr))
+    }
+
+    /// 检查 `[addr, addr + len)` 是否完全位于具有 `required` 权限的VMA中
+    pub fn check_range(&self, addr: usize, len: usize, required: VmaFlags) -> bool {
+        if len == 0 {
+            return true;
+        }
+        let end = match addr.checked_add(len) {
+            Some(end) if end <= USER_SPACE_END => end,
+            _ => return false,
+        };
+
+        let vmas = self.vmas.read();
+        let mut cursor = addr;
+        // 逐个VMA检查，相邻的VMA可以连续覆盖整个范围
+        for (_, vma) in vmas.range(..end) {
+            if vma.end <= cursor {
+                continue;
+            }
+            if vma.start > cursor || !vma.flags.contains(required) {
+                return false;
+            }
+            cursor = vma.end;
+            if cursor >= end {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 在mmap基址之下从高到低查找长度为 `len` 的空闲区域
+    pub fn find_free(&self, len: usize) -> Option<usize> {
+        let len = page_align_up(len);
+        let mmap_base = self.layout().mmap_base;
+        let vmas = self.vmas.read();
+        let mut top = mmap_base;
+        for (_, vma) in vmas.range(..mmap_base).rev() {
+            if vma.end <= top && top - vma.end >= len {
+                break;
+            }
+            top = top.min(vma.start);
+        }
+        top.checked_sub(len).filter(|&start| start >= PAGE_SIZE)
+    }
+
+    /// 设置brk堆的起始地址（加载程序时以程序映像的结束地址调用），随机化时向后偏移
+    pub fn set_brk_base(&self, base: usize) {
+        let base = page_align_up(base) + self.layout().brk_offset;
+        *self.heap.lock() = (base, base);
+    }
+
+    /// 调整brk堆的结束地址，返回调整后的结束地址
+    ///
+    /// 与Linux一致，`new_end` 无效（低于堆的起点或超出用户空间）或分配失败时返回原来的结束地址
+    pub fn brk(&self, new_end: usize) -> usize {
+        let mut heap = self.heap.lock();
+        let (start, end) = *heap;
+        if new_end < start || new_end > USER_SPACE_END {
+            return end;
+        }
+
+        let old_top = page_align_up(end);
+        let new_top = page_align_up(new_end);
+        let result = if new_top > old_top {
+            self.insert(Vma {
+                start: old_top,
+                end: new_top,
+                flags: VmaFlags::READ | VmaFlags::WRITE,
+                backing: VmaBacking::Anonymous,
+            })
+        } else {
+            self.remove(new_top, old_top)
+        };
+        if result.is_err() {
+            return end;
+        }
+
+        heap.1 = new_end;
+        new_end
+    }
+
+    /// 所有VMA的快照
+    pub fn vmas(&self) -> Vec<Vma> {
+        self.vmas.read().values().copied().collect()
+    }
+}
+
+impl Default for AddressSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+crate::kernel_test! {
+    fn vma_protect_and_lock_ranges() {
+        let space = AddressSpace::new();
+        let rw = VmaFlags::READ | VmaFlags::WRITE;
+        space
+            .insert(Vma {
+                start: 0x10000,
+                end: 0x14000,
+                flags: rw,
+                backing: VmaBacking::Anonymous,
+            })
+            .unwrap();
+        space
+            .insert(Vma {
+                start: 0x20000,
+                end: 0x22000,
+                flags: VmaFlags::READ | VmaFlags::EXEC,
+                backing: VmaBacking::Kernel(0x8020_0000),
+            })
+            .unwrap();
+
+        space.protect(0x11000, 0x12000, VmaFlags::READ).unwrap();
+        let vmas = space.vmas();
+        assert_eq!(vmas.len(), 4);
+        assert_eq!(space.find(0x10000).unwrap().flags, rw);
+        assert_eq!(space.find(0x11000).unwrap().flags, VmaFlags::READ);
+        assert_eq!(space.find(0x12000).unwrap().flags, rw);
+
+        // 范围中有空洞
+        assert_eq!(space.protect(0x13000, 0x15000, rw), Err(MemoryError::InvalidAddress));
+        // 内核内存只能收回权限
+        assert_eq!(space.protect(0x20000, 0x21000, rw), Err(MemoryError::PermissionDenied));
+        space.protect(0x21000, 0x22000, VmaFlags::READ).unwrap();
+        assert_eq!(space.find(0x21000).unwrap().backing, VmaBacking::Kernel(0x8020_1000));
+        assert_eq!(space.find(0x20000).unwrap().flags, VmaFlags::READ | VmaFlags::EXEC);
+
+        // 已锁定的部分不重复计入限制
+        space.set_locked(0x10000, 0x12000, true, Some(0x3000)).unwrap();
+        space.set_locked(0x11000, 0x13000, true, Some(0x3000)).unwrap();
+        assert_eq!(space.locked_len(), 0x3000);
+        assert_eq!(space.set_locked(0x13000, 0x14000, true, Some(0x3000)), Err(MemoryError::OutOfMemory));
+        space.set_locked(0x10000, 0x14000, false, None).unwrap();
+        assert_eq!(space.locked_len(), 0);
+        assert_eq!(space.find(0x11000).unwrap().flags, VmaFlags::READ);
+
+        // 超出用户空间的brk被忽略，不影响已有的映射
+        space.set_brk_base(0x30000);
+        let end = space.brk(0);
+        let count = space.vmas().len();
+        assert_eq!(space.brk(usize::MAX), end);
+        assert_eq!(space.vmas().len(), count);
+    }
+}
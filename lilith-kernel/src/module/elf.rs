@@ -0,0 +1,222 @@
+//! ELF64可重定位目标文件的解析
+//!
+//! 只读取模块加载需要的部分：文件头、节头、符号表与RELA重定位表。
+//! 所有字段按小端序读取，越界的偏移一律视为格式错误
+
+use crate::error::ModuleError;
+use alloc::vec::Vec;
+
+/// e_type：可重定位文件
+const ET_REL: u16 = 1;
+/// e_machine：RISC-V
+const EM_RISCV: u16 = 243;
+/// e_ident[EI_CLASS]：64位
+const ELFCLASS64: u8 = 2;
+/// e_ident[EI_DATA]：小端序
+const ELFDATA2LSB: u8 = 1;
+
+/// 节类型
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOBITS: u32 = 8;
+
+/// 节标志
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+
+/// 特殊节编号
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+
+/// 符号绑定：弱符号
+pub const STB_WEAK: u8 = 2;
+
+/// 节头大小
+const SHDR_SIZE: usize = 64;
+/// 符号表项大小
+const SYM_SIZE: usize = 24;
+/// RELA重定位项大小
+const RELA_SIZE: usize = 24;
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ModuleError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ModuleError::InvalidFormat)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ModuleError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ModuleError::InvalidFormat)?;
+    let mut value = [0u8; 4];
+    value.copy_from_slice(bytes);
+    Ok(u32::from_le_bytes(value))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, ModuleError> {
+    let bytes = data.get(offset..offset + 8).ok_or(ModuleError::InvalidFormat)?;
+    let mut value = [0u8; 8];
+    value.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(value))
+}
+
+/// 节头
+#[derive(Debug, Clone, Copy)]
+pub struct SectionHeader {
+    pub name: u32,
+    pub kind: u32,
+    pub flags: u64,
+    pub offset: usize,
+    pub size: usize,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: usize,
+}
+
+/// 符号
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    pub name: u32,
+    pub info: u8,
+    pub shndx: u16,
+    pub value: u64,
+}
+
+impl Symbol {
+    /// 符号绑定
+    pub fn binding(&self) -> u8 {
+        self.info >> 4
+    }
+}
+
+/// RELA重定位项
+#[derive(Debug, Clone, Copy)]
+pub struct Rela {
+    /// 在目标节内的偏移
+    pub offset: usize,
+    /// 符号表下标
+    pub symbol: usize,
+    /// 重定位类型
+    pub kind: u32,
+    pub addend: i64,
+}
+
+/// 可重定位目标文件
+pub struct ElfObject<'a> {
+    data: &'a [u8],
+    sections: Vec<SectionHeader>,
+    shstrndx: usize,
+}
+
+impl<'a> ElfObject<'a> {
+    /// 检查文件头并读取所有节头
+    pub fn parse(data: &'a [u8]) -> Result<Self, ModuleError> {
+        if data.get(..4) != Some(b"\x7fELF".as_slice())
+            || data.get(4) != Some(&ELFCLASS64)
+            || data.get(5) != Some(&ELFDATA2LSB)
+            || read_u16(data, 16)? != ET_REL
+            || read_u16(data, 18)? != EM_RISCV
+        {
+            return Err(ModuleError::InvalidFormat);
+        }
+
+        let shoff = read_u64(data, 40)? as usize;
+        let shentsize = read_u16(data, 58)? as usize;
+        let shnum = read_u16(data, 60)? as usize;
+        let shstrndx = read_u16(data, 62)? as usize;
+        if shentsize != SHDR_SIZE || shstrndx >= shnum {
+            return Err(ModuleError::InvalidFormat);
+        }
+
+        let mut sections = Vec::with_capacity(shnum);
+        for index in 0..shnum {
+            let base = shoff + index * SHDR_SIZE;
+            let header = SectionHeader {
+                name: read_u32(data, base)?,
+                kind: read_u32(data, base + 4)?,
+                flags: read_u64(data, base + 8)?,
+                offset: read_u64(data, base + 24)? as usize,
+                size: read_u64(data, base + 32)? as usize,
+                link: read_u32(data, base + 40)?,
+                info: read_u32(data, base + 44)?,
+                addralign: read_u64(data, base + 48)? as usize,
+            };
+            // NOBITS节在文件中不占空间
+            if header.kind != SHT_NOBITS && header.offset.checked_add(header.size).map_or(true, |end| end > data.len()) {
+                return Err(ModuleError::InvalidFormat);
+            }
+            sections.push(header);
+        }
+        Ok(Self { data, sections, shstrndx })
+    }
+
+    /// 所有节头
+    pub fn sections(&self) -> &[SectionHeader] {
+        &self.sections
+    }
+
+    /// 节在文件中的内容
+    pub fn section_data(&self, section: &SectionHeader) -> &'a [u8] {
+        if section.kind == SHT_NOBITS {
+            return &[];
+        }
+        &self.data[section.offset..section.offset + section.size]
+    }
+
+    /// 字符串表 `strtab` 中偏移 `offset` 处的字符串
+    pub fn string(&self, strtab: usize, offset: u32) -> Result<&'a str, ModuleError> {
+        let table = self.sections.get(strtab).ok_or(ModuleError::InvalidFormat)?;
+        let data = self.section_data(table).get(offset as usize..).ok_or(ModuleError::InvalidFormat)?;
+        let len = data.iter().position(|&byte| byte == 0).ok_or(ModuleError::InvalidFormat)?;
+        core::str::from_utf8(&data[..len]).map_err(|_| ModuleError::InvalidFormat)
+    }
+
+    /// 节名
+    pub fn section_name(&self, section: &SectionHeader) -> Result<&'a str, ModuleError> {
+        self.string(self.shstrndx, section.name)
+    }
+
+    /// 按名称查找节
+    pub fn find_section(&self, name: &str) -> Option<&SectionHeader> {
+        self.sections
+            .iter()
+            .find(|section| self.section_name(section).map_or(false, |n| n == name))
+    }
+
+    /// 符号表节的下标
+    pub fn symtab(&self) -> Result<usize, ModuleError> {
+        self.sections
+            .iter()
+            .position(|section| section.kind == SHT_SYMTAB)
+            .ok_or(ModuleError::InvalidFormat)
+    }
+
+    /// 符号表中的所有符号
+    pub fn symbols(&self, symtab: usize) -> Result<Vec<Symbol>, ModuleError> {
+        let data = self.section_data(&self.sections[symtab]);
+        data.chunks_exact(SYM_SIZE)
+            .map(|entry| {
+                Ok(Symbol {
+                    name: read_u32(entry, 0)?,
+                    info: entry[4],
+                    shndx: read_u16(entry, 6)?,
+                    value: read_u64(entry, 8)?,
+                })
+            })
+            .collect()
+    }
+
+    /// RELA节中的重定位项
+    pub fn relocations(&self, section: &SectionHeader) -> Result<Vec<Rela>, ModuleError> {
+        self.section_data(section)
+            .chunks_exact(RELA_SIZE)
+            .map(|entry| {
+                let info = read_u64(entry, 8)?;
+                Ok(Rela {
+                    offset: read_u64(entry, 0)? as usize,
+                    symbol: (info >> 32) as usize,
+                    kind: info as u32,
+                    addend: read_u64(entry, 16)? as i64,
+                })
+            })
+            .collect()
+    }
+}
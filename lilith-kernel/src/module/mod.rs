@@ -0,0 +1,361 @@
+//! 可加载内核模块
+//!
+//! 本模块实现了运行时加载RISC-V ELF可重定位目标文件（.ko），包括：
+//! - 将带SHF_ALLOC标志的节复制到内核内存并按RELA表重定位
+//! - 代码节排在最前并按页对齐，重定位完成后设为只读可执行，其余节不可执行
+//! - 未定义符号按内核导出表（`export_symbol!`）解析
+//! - 调用模块的 `init_module` 与 `cleanup_module`，记录在 /proc/modules 中
+//! - init_module/finit_module/delete_module 系统调用的实现
+//!
+//! 模块的约定：
+//! - `.modinfo` 节中包含以0分隔的 `key=value` 字符串，必须有 `name=`
+//! - 导出C调用约定的 `init_module() -> i32`（返回0表示成功），
+//!   可选导出 `cleanup_module()`，没有时模块不能卸载
+//! - 以 `-mno-relax` 编译，只能引用内核导出的符号，模块之间不能互相引用
+
+mod elf;
+mod reloc;
+
+use crate::error::{KernelError, ModuleError};
+use crate::fs::procfs;
+use crate::sync::Mutex;
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use elf::{ElfObject, SHF_ALLOC, SHF_EXECINSTR, SHN_ABS, SHN_COMMON, SHN_UNDEF, SHT_NOBITS, SHT_RELA, STB_WEAK};
+
+/// 模块内存的最小对齐（页）
+const MODULE_ALIGN: usize = 4096;
+
+/// 内核导出的符号
+#[repr(C)]
+pub struct KernelSymbol {
+    /// 符号名，即模块中引用的链接名
+    pub name: &'static str,
+    /// 符号地址
+    pub addr: *const (),
+}
+
+// 导出表是只读的静态数据
+unsafe impl Sync for KernelSymbol {}
+
+/// 把内核符号加入导出表，供模块引用
+///
+/// 被导出的函数须为 `#[no_mangle] extern "C"`，模块按其名称链接
+#[macro_export]
+macro_rules! export_symbol {
+    ($symbol:ident) => {
+        const _: () = {
+            #[used]
+            #[link_section = "ksymtab"]
+            static ENTRY: $crate::module::KernelSymbol = $crate::module::KernelSymbol {
+                name: stringify!($symbol),
+                addr: $symbol as *const (),
+            };
+        };
+    };
+}
+
+extern "C" {
+    // 链接器为名称是合法标识符的节自动定义起止符号
+    static __start_ksymtab: u8;
+    static __stop_ksymtab: u8;
+}
+
+/// 内核导出表
+fn kernel_symbols() -> &'static [KernelSymbol] {
+    unsafe {
+        let start = &__start_ksymtab as *const u8 as *const KernelSymbol;
+        let end = &__stop_ksymtab as *const u8 as *const KernelSymbol;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 按名称查找导出的内核符号
+pub fn find_kernel_symbol(name: &str) -> Option<usize> {
+    kernel_symbols()
+        .iter()
+        .find(|symbol| symbol.name == name)
+        .map(|symbol| symbol.addr as usize)
+}
+
+/// 模块输出日志，`msg` 为长度 `len` 的UTF-8字符串
+///
+/// # Safety
+///
+/// `msg` 必须指向 `len` 个可读字节
+#[no_mangle]
+pub unsafe extern "C" fn module_printk(level: u8, msg: *const u8, len: usize) {
+    let bytes = core::slice::from_raw_parts(msg, len);
+    let text = core::str::from_utf8(bytes).unwrap_or("<无效的UTF-8>");
+    crate::klog::printk(crate::klog::LogLevel::from_u8(level), format_args!("{}", text));
+}
+
+/// 为模块分配内核内存，失败时返回空指针
+#[no_mangle]
+pub extern "C" fn kmalloc(size: usize, align: usize) -> *mut u8 {
+    match Layout::from_size_align(size.max(1), align.max(1)) {
+        Ok(layout) => unsafe { alloc::alloc::alloc(layout) },
+        Err(_) => core::ptr::null_mut(),
+    }
+}
+
+/// 释放 `kmalloc` 分配的内存
+///
+/// # Safety
+///
+/// `ptr` 必须为空或由 `kmalloc` 以相同的 `size` 与 `align` 分配
+#[no_mangle]
+pub unsafe extern "C" fn kfree(ptr: *mut u8, size: usize, align: usize) {
+    if let (false, Ok(layout)) = (ptr.is_null(), Layout::from_size_align(size.max(1), align.max(1))) {
+        dealloc(ptr, layout);
+    }
+}
+
+export_symbol!(module_printk);
+export_symbol!(kmalloc);
+export_symbol!(kfree);
+
+/// 已加载的模块
+struct LoadedModule {
+    /// 模块内存
+    base: *mut u8,
+    layout: Layout,
+    /// 代码节占用的大小（页对齐），位于模块内存开头
+    text_size: usize,
+    /// 退出函数
+    cleanup: Option<extern "C" fn()>,
+}
+
+// 模块内存只由模块表的持有者访问
+unsafe impl Send for LoadedModule {}
+
+impl Drop for LoadedModule {
+    fn drop(&mut self) {
+        // 归还给堆之前恢复为可写、不可执行
+        if self.text_size > 0 {
+            let _ = crate::mm::protect::set_memory_rw_nx(self.base as usize, self.text_size);
+        }
+        unsafe { dealloc(self.base, self.layout) };
+    }
+}
+
+/// 已加载的模块，按名称索引
+static MODULES: Mutex<BTreeMap<String, LoadedModule>> = Mutex::new(BTreeMap::new());
+
+/// 从 `.modinfo` 节读取 `key` 的值
+fn modinfo<'a>(object: &ElfObject<'a>, key: &str) -> Option<&'a str> {
+    let section = object.find_section(".modinfo")?;
+    object
+        .section_data(section)
+        .split(|&byte| byte == 0)
+        .filter_map(|entry| core::str::from_utf8(entry).ok())
+        .find_map(|entry| entry.strip_prefix(key)?.strip_prefix('='))
+}
+
+/// 把模块复制到内核内存并完成重定位，返回模块内存及各符号的运行地址
+fn layout_and_relocate(object: &ElfObject) -> Result<(LoadedModule, Vec<usize>), ModuleError> {
+    let sections = object.sections();
+
+    // 依次排列需要加载的节，代码节在前
+    let mut offsets = alloc::vec![usize::MAX; sections.len()];
+    let mut size = 0usize;
+    let mut align = MODULE_ALIGN;
+    let mut text_size = 0usize;
+    for executable in [true, false] {
+        for (index, section) in sections.iter().enumerate() {
+            if section.flags & SHF_ALLOC == 0 || section.size == 0 {
+                continue;
+            }
+            if (section.flags & SHF_EXECINSTR != 0) != executable {
+                continue;
+            }
+            let section_align = section.addralign.max(1);
+            if !section_align.is_power_of_two() {
+                return Err(ModuleError::InvalidFormat);
+            }
+            align = align.max(section_align);
+            size = (size + section_align - 1) & !(section_align - 1);
+            offsets[index] = size;
+            size += section.size;
+        }
+        if executable {
+            // 代码与数据不共用页
+            size = (size + MODULE_ALIGN - 1) & !(MODULE_ALIGN - 1);
+            text_size = size;
+        }
+    }
+    if size == 0 {
+        return Err(ModuleError::InvalidFormat);
+    }
+
+    let layout = Layout::from_size_align(size, align).map_err(|_| ModuleError::InvalidFormat)?;
+    let base = unsafe { alloc_zeroed(layout) };
+    if base.is_null() {
+        return Err(ModuleError::OutOfMemory);
+    }
+    let module = LoadedModule {
+        base,
+        layout,
+        text_size,
+        cleanup: None,
+    };
+
+    let addresses: Vec<usize> = offsets
+        .iter()
+        .map(|&offset| if offset == usize::MAX { 0 } else { base as usize + offset })
+        .collect();
+    for (index, section) in sections.iter().enumerate() {
+        if addresses[index] != 0 && section.kind != SHT_NOBITS {
+            let data = object.section_data(section);
+            unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), addresses[index] as *mut u8, data.len()) };
+        }
+    }
+
+    // 解析符号地址
+    let symtab = object.symtab()?;
+    let strtab = sections[symtab].link as usize;
+    let symbols = object.symbols(symtab)?;
+    let mut symbol_values = Vec::with_capacity(symbols.len());
+    for (index, symbol) in symbols.iter().enumerate() {
+        let value = match symbol.shndx {
+            // 第0项是保留的空符号
+            SHN_UNDEF if index == 0 => 0,
+            SHN_UNDEF => {
+                let name = object.string(strtab, symbol.name)?;
+                match find_kernel_symbol(name) {
+                    Some(addr) => addr,
+                    None if symbol.binding() == STB_WEAK => 0,
+                    None => {
+                        crate::log_error!("模块引用了未导出的符号: {}", name);
+                        return Err(ModuleError::UnknownSymbol);
+                    }
+                }
+            }
+            SHN_ABS => symbol.value as usize,
+            // 未分配的公共符号需要 -fno-common
+            SHN_COMMON => return Err(ModuleError::InvalidFormat),
+            shndx => match addresses.get(shndx as usize) {
+                Some(&base) if base != 0 => base + symbol.value as usize,
+                _ => 0,
+            },
+        };
+        symbol_values.push(value);
+    }
+
+    // 应用重定位
+    for section in sections.iter().filter(|section| section.kind == SHT_RELA) {
+        let target = section.info as usize;
+        let target_base = match addresses.get(target) {
+            Some(&base) if base != 0 => base,
+            // 调试信息等不加载的节
+            _ => continue,
+        };
+        let target_size = sections[target].size;
+        let relocations = object.relocations(section)?;
+
+        // PCREL_LO12引用的是auipc处的标号，先记录每个PCREL_HI20的偏移
+        let mut hi20 = BTreeMap::new();
+        for rela in &relocations {
+            if rela.kind == reloc::R_RISCV_PCREL_HI20 {
+                let symbol = *symbol_values.get(rela.symbol).ok_or(ModuleError::InvalidFormat)?;
+                let pc = target_base + rela.offset;
+                hi20.insert(pc, (symbol as u64).wrapping_add(rela.addend as u64).wrapping_sub(pc as u64));
+            }
+        }
+
+        for rela in &relocations {
+            if rela.offset >= target_size {
+                return Err(ModuleError::InvalidFormat);
+            }
+            let symbol = *symbol_values.get(rela.symbol).ok_or(ModuleError::InvalidFormat)?;
+            let pc = target_base + rela.offset;
+            let mut value = (symbol as u64).wrapping_add(rela.addend as u64);
+            if matches!(rela.kind, reloc::R_RISCV_PCREL_LO12_I | reloc::R_RISCV_PCREL_LO12_S) {
+                value = *hi20.get(&symbol).ok_or(ModuleError::InvalidFormat)?;
+            }
+            unsafe { reloc::apply(rela.kind, pc as *mut u8, pc as u64, value)? };
+        }
+    }
+
+    Ok((module, symbol_values))
+}
+
+/// 按名称查找模块定义的符号
+fn module_symbol(object: &ElfObject, symbol_values: &[usize], name: &str) -> Result<Option<usize>, ModuleError> {
+    let symtab = object.symtab()?;
+    let strtab = object.sections()[symtab].link as usize;
+    for (index, symbol) in object.symbols(symtab)?.iter().enumerate() {
+        if symbol.shndx != SHN_UNDEF && object.string(strtab, symbol.name)? == name {
+            return Ok(Some(symbol_values[index]));
+        }
+    }
+    Ok(None)
+}
+
+/// 加载模块并调用其初始化函数
+///
+/// 模块参数尚不支持，`params` 非空时返回错误
+pub fn load_module(image: &[u8], params: &str) -> Result<(), ModuleError> {
+    if !params.trim().is_empty() {
+        return Err(ModuleError::InitFailed);
+    }
+    let object = ElfObject::parse(image)?;
+    let name = modinfo(&object, "name").ok_or(ModuleError::InvalidFormat)?.to_string();
+
+    // 持有模块表的锁直到初始化完成，同名模块不会被同时加载
+    let mut modules = MODULES.lock();
+    if modules.contains_key(&name) {
+        return Err(ModuleError::AlreadyLoaded);
+    }
+
+    let (mut module, symbol_values) = layout_and_relocate(&object)?;
+    if module.text_size > 0 {
+        crate::mm::protect::set_memory_rox(module.base as usize, module.text_size)
+            .map_err(|_| ModuleError::OutOfMemory)?;
+    }
+    crate::arch::flush_icache();
+
+    let init = module_symbol(&object, &symbol_values, "init_module")?.ok_or(ModuleError::InvalidFormat)?;
+    module.cleanup = module_symbol(&object, &symbol_values, "cleanup_module")?
+        .map(|addr| unsafe { core::mem::transmute::<usize, extern "C" fn()>(addr) });
+
+    let init: extern "C" fn() -> i32 = unsafe { core::mem::transmute(init) };
+    let ret = init();
+    if ret != 0 {
+        crate::log_error!("模块 {} 初始化失败: {}", name, ret);
+        return Err(ModuleError::InitFailed);
+    }
+
+    crate::log_info!("已加载模块 {}，{} 字节，位于 {:#x}", name, module.layout.size(), module.base as usize);
+    modules.insert(name, module);
+    Ok(())
+}
+
+/// 调用模块的退出函数并卸载
+pub fn unload_module(name: &str) -> Result<(), ModuleError> {
+    let mut modules = MODULES.lock();
+    let module = modules.get(name).ok_or(ModuleError::NotLoaded)?;
+    let cleanup = module.cleanup.ok_or(ModuleError::NotRemovable)?;
+    cleanup();
+    modules.remove(name);
+    crate::log_info!("已卸载模块 {}", name);
+    Ok(())
+}
+
+/// 生成 /proc/modules 的内容，格式与Linux一致
+fn proc_read_modules() -> String {
+    let mut out = String::new();
+    for (name, module) in MODULES.lock().iter() {
+        let _ = writeln!(out, "{} {} 0 - Live {:#x}", name, module.layout.size(), module.base as usize);
+    }
+    out
+}
+
+/// 模块子系统初始化
+pub fn module_init() -> Result<(), KernelError> {
+    procfs::register("modules", Some(Box::new(proc_read_modules)), None)
+}
@@ -0,0 +1,196 @@
+//! RISC-V重定位
+//!
+//! 按RISC-V ELF psABI修改已复制到内核内存中的指令与数据。
+//! 加载器不做链接松弛，模块需以 `-mno-relax`（Rust：`-C target-feature=-relax`）编译，
+//! 遇到 `R_RISCV_ALIGN` 时拒绝加载
+
+use crate::error::ModuleError;
+use core::ptr;
+
+/// 重定位类型
+pub const R_RISCV_32: u32 = 1;
+pub const R_RISCV_64: u32 = 2;
+pub const R_RISCV_BRANCH: u32 = 16;
+pub const R_RISCV_JAL: u32 = 17;
+pub const R_RISCV_CALL: u32 = 18;
+pub const R_RISCV_CALL_PLT: u32 = 19;
+pub const R_RISCV_PCREL_HI20: u32 = 23;
+pub const R_RISCV_PCREL_LO12_I: u32 = 24;
+pub const R_RISCV_PCREL_LO12_S: u32 = 25;
+pub const R_RISCV_HI20: u32 = 26;
+pub const R_RISCV_LO12_I: u32 = 27;
+pub const R_RISCV_LO12_S: u32 = 28;
+pub const R_RISCV_ADD8: u32 = 33;
+pub const R_RISCV_ADD16: u32 = 34;
+pub const R_RISCV_ADD32: u32 = 35;
+pub const R_RISCV_ADD64: u32 = 36;
+pub const R_RISCV_SUB8: u32 = 37;
+pub const R_RISCV_SUB16: u32 = 38;
+pub const R_RISCV_SUB32: u32 = 39;
+pub const R_RISCV_SUB64: u32 = 40;
+pub const R_RISCV_ALIGN: u32 = 43;
+pub const R_RISCV_RVC_BRANCH: u32 = 44;
+pub const R_RISCV_RVC_JUMP: u32 = 45;
+pub const R_RISCV_RELAX: u32 = 51;
+pub const R_RISCV_SUB6: u32 = 52;
+pub const R_RISCV_SET6: u32 = 53;
+pub const R_RISCV_SET8: u32 = 54;
+pub const R_RISCV_SET16: u32 = 55;
+pub const R_RISCV_SET32: u32 = 56;
+pub const R_RISCV_32_PCREL: u32 = 57;
+
+/// `value` 是否可以表示为 `bits` 位有符号数
+fn fits_signed(value: i64, bits: u32) -> bool {
+    let limit = 1i64 << (bits - 1);
+    (-limit..limit).contains(&value)
+}
+
+/// auipc/lui的高20位（加上0x800补偿低12位的符号扩展）
+fn hi20(value: i64) -> u32 {
+    ((value + 0x800) as u32) & 0xffff_f000
+}
+
+/// 与 `hi20` 配对的低12位
+fn lo12(value: i64) -> u32 {
+    (value as u32) & 0xfff
+}
+
+/// 可以修改的指令或数据位置
+struct Location(*mut u8);
+
+impl Location {
+    unsafe fn read32(&self) -> u32 {
+        ptr::read_unaligned(self.0 as *const u32)
+    }
+
+    unsafe fn write32(&self, value: u32) {
+        ptr::write_unaligned(self.0 as *mut u32, value)
+    }
+
+    unsafe fn read16(&self) -> u16 {
+        ptr::read_unaligned(self.0 as *const u16)
+    }
+
+    unsafe fn write16(&self, value: u16) {
+        ptr::write_unaligned(self.0 as *mut u16, value)
+    }
+
+    unsafe fn read64(&self) -> u64 {
+        ptr::read_unaligned(self.0 as *const u64)
+    }
+
+    unsafe fn write64(&self, value: u64) {
+        ptr::write_unaligned(self.0 as *mut u64, value)
+    }
+
+    /// U型指令（auipc/lui）的立即数
+    unsafe fn set_u_imm(&self, value: i64) -> Result<(), ModuleError> {
+        if !fits_signed(value + 0x800, 32) {
+            return Err(ModuleError::RelocationOverflow);
+        }
+        self.write32((self.read32() & 0xfff) | hi20(value));
+        Ok(())
+    }
+
+    /// I型指令的12位立即数
+    unsafe fn set_i_imm(&self, value: i64) {
+        self.write32((self.read32() & 0x000f_ffff) | (lo12(value) << 20));
+    }
+
+    /// S型指令的12位立即数
+    unsafe fn set_s_imm(&self, value: i64) {
+        let imm = lo12(value);
+        self.write32((self.read32() & 0x01ff_f07f) | ((imm & 0x1f) << 7) | ((imm >> 5) << 25));
+    }
+}
+
+/// 应用一个重定位
+///
+/// `location` 为被修改的位置，`pc` 为其运行地址，`value` 为符号地址加上加数（S + A）。
+/// `PCREL_LO12` 类重定位的 `value` 须由调用方换算为配对的 `PCREL_HI20` 的偏移
+///
+/// # Safety
+///
+/// `location` 必须指向模块内存中按重定位类型可写的位置
+pub unsafe fn apply(kind: u32, location: *mut u8, pc: u64, value: u64) -> Result<(), ModuleError> {
+    let loc = Location(location);
+    let offset = value.wrapping_sub(pc) as i64;
+    match kind {
+        R_RISCV_32 => loc.write32(value as u32),
+        R_RISCV_64 => loc.write64(value),
+        R_RISCV_32_PCREL => {
+            if !fits_signed(offset, 32) {
+                return Err(ModuleError::RelocationOverflow);
+            }
+            loc.write32(offset as u32);
+        }
+        R_RISCV_BRANCH => {
+            if !fits_signed(offset, 13) {
+                return Err(ModuleError::RelocationOverflow);
+            }
+            let off = offset as u32;
+            let imm = ((off >> 12 & 1) << 31) | ((off >> 5 & 0x3f) << 25) | ((off >> 1 & 0xf) << 8) | ((off >> 11 & 1) << 7);
+            loc.write32((loc.read32() & 0x01ff_f07f) | imm);
+        }
+        R_RISCV_JAL => {
+            if !fits_signed(offset, 21) {
+                return Err(ModuleError::RelocationOverflow);
+            }
+            let off = offset as u32;
+            let imm = ((off >> 20 & 1) << 31) | ((off >> 1 & 0x3ff) << 21) | ((off >> 11 & 1) << 20) | ((off >> 12 & 0xff) << 12);
+            loc.write32((loc.read32() & 0xfff) | imm);
+        }
+        R_RISCV_CALL | R_RISCV_CALL_PLT => {
+            // auipc + jalr
+            loc.set_u_imm(offset)?;
+            Location(location.add(4)).set_i_imm(offset);
+        }
+        R_RISCV_PCREL_HI20 => loc.set_u_imm(offset)?,
+        // 调用方已将value换算为配对的偏移
+        R_RISCV_PCREL_LO12_I => loc.set_i_imm(value as i64),
+        R_RISCV_PCREL_LO12_S => loc.set_s_imm(value as i64),
+        R_RISCV_HI20 => loc.set_u_imm(value as i64)?,
+        R_RISCV_LO12_I => loc.set_i_imm(value as i64),
+        R_RISCV_LO12_S => loc.set_s_imm(value as i64),
+        R_RISCV_RVC_BRANCH => {
+            if !fits_signed(offset, 9) {
+                return Err(ModuleError::RelocationOverflow);
+            }
+            let off = offset as u16;
+            let imm = ((off >> 8 & 1) << 12) | ((off >> 3 & 3) << 10) | ((off >> 6 & 3) << 5) | ((off >> 1 & 3) << 3) | ((off >> 5 & 1) << 2);
+            loc.write16((loc.read16() & 0xe383) | imm);
+        }
+        R_RISCV_RVC_JUMP => {
+            if !fits_signed(offset, 12) {
+                return Err(ModuleError::RelocationOverflow);
+            }
+            let off = offset as u16;
+            let imm = ((off >> 11 & 1) << 12)
+                | ((off >> 4 & 1) << 11)
+                | ((off >> 8 & 3) << 9)
+                | ((off >> 10 & 1) << 8)
+                | ((off >> 6 & 1) << 7)
+                | ((off >> 7 & 1) << 6)
+                | ((off >> 1 & 7) << 3)
+                | ((off >> 5 & 1) << 2);
+            loc.write16((loc.read16() & 0xe003) | imm);
+        }
+        R_RISCV_ADD8 => *location = (*location).wrapping_add(value as u8),
+        R_RISCV_ADD16 => loc.write16(loc.read16().wrapping_add(value as u16)),
+        R_RISCV_ADD32 => loc.write32(loc.read32().wrapping_add(value as u32)),
+        R_RISCV_ADD64 => loc.write64(loc.read64().wrapping_add(value)),
+        R_RISCV_SUB6 => *location = (*location & 0xc0) | ((*location).wrapping_sub(value as u8) & 0x3f),
+        R_RISCV_SUB8 => *location = (*location).wrapping_sub(value as u8),
+        R_RISCV_SUB16 => loc.write16(loc.read16().wrapping_sub(value as u16)),
+        R_RISCV_SUB32 => loc.write32(loc.read32().wrapping_sub(value as u32)),
+        R_RISCV_SUB64 => loc.write64(loc.read64().wrapping_sub(value)),
+        R_RISCV_SET6 => *location = (*location & 0xc0) | (value as u8 & 0x3f),
+        R_RISCV_SET8 => *location = value as u8,
+        R_RISCV_SET16 => loc.write16(value as u16),
+        R_RISCV_SET32 => loc.write32(value as u32),
+        // 只是允许松弛的标记，不松弛时无需处理
+        R_RISCV_RELAX => {}
+        _ => return Err(ModuleError::UnsupportedRelocation),
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+//! ARP协议
+//!
+//! 本模块负责将下一跳IPv4地址解析为MAC地址：
+//! - 维护带老化时间的ARP缓存
+//! - 应答针对本机地址的ARP请求
+//! - 地址尚未解析时暂存待发数据包，解析完成后统一发出
+//! - 内存不足时丢弃最久未更新的已解析表项（收缩器），之后按需重新解析
+
+use super::ethernet::{self, EthernetHeader, ETHERTYPE_ARP};
+use super::skb::PacketBuffer;
+use super::{interface, Ipv4Addr, MacAddress, NetInterface};
+use crate::error::KernelError;
+use crate::mm::shrinker::Shrinker;
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// ARP报文长度（以太网/IPv4）
+const ARP_PACKET_LEN: usize = 28;
+
+/// ARP操作码
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+
+/// 缓存表项有效期（毫秒）
+const ENTRY_TIMEOUT_MS: u64 = 300_000;
+/// 请求重传间隔（毫秒）
+const RETRY_INTERVAL_MS: u64 = 1_000;
+/// 最大请求次数
+const MAX_RETRIES: u32 = 3;
+/// 每个表项最多暂存的数据包数
+const MAX_PENDING: usize = 8;
+
+/// ARP缓存表项
+struct ArpEntry {
+    iface_id: usize,
+    address: Ipv4Addr,
+    mac: Option<MacAddress>,
+    /// 等待解析的数据包及其以太网类型
+    pending: Vec<(PacketBuffer, u16)>,
+    updated_ms: u64,
+    retries: u32,
+}
+
+/// ARP缓存
+static CACHE: Mutex<Vec<ArpEntry>> = Mutex::new(Vec::new());
+
+/// 构造并发送ARP报文
+fn send_arp(
+    iface: &NetInterface,
+    operation: u16,
+    target_mac: MacAddress,
+    target_ip: Ipv4Addr,
+) -> Result<(), KernelError> {
+    let mut packet = PacketBuffer::new(ARP_PACKET_LEN);
+    let body = packet.put(ARP_PACKET_LEN);
+    body[0..2].copy_from_slice(&1u16.to_be_bytes()); // 以太网
+    body[2..4].copy_from_slice(&0x0800u16.to_be_bytes()); // IPv4
+    body[4] = 6;
+    body[5] = 4;
+    body[6..8].copy_from_slice(&operation.to_be_bytes());
+    body[8..14].copy_from_slice(&iface.mac_address().0);
+    body[14..18].copy_from_slice(&iface.config().address.0);
+    body[18..24].copy_from_slice(&target_mac.0);
+    body[24..28].copy_from_slice(&target_ip.0);
+
+    let destination = if operation == ARP_REQUEST {
+        MacAddress::BROADCAST
+    } else {
+        target_mac
+    };
+    ethernet::send(iface, destination, ETHERTYPE_ARP, packet)
+}
+
+/// 处理接收到的ARP报文
+pub fn receive(iface: &NetInterface, _eth: &EthernetHeader, packet: PacketBuffer) {
+    let body = packet.data();
+    if body.len() < ARP_PACKET_LEN
+        || body[0..2] != 1u16.to_be_bytes()
+        || body[2..4] != 0x0800u16.to_be_bytes()
+        || body[4] != 6
+        || body[5] != 4
+    {
+        return;
+    }
+
+    let operation = u16::from_be_bytes([body[6], body[7]]);
+    let sender_mac = MacAddress::from_slice(&body[8..14]);
+    let sender_ip = Ipv4Addr::from_slice(&body[14..18]);
+    let target_ip = Ipv4Addr::from_slice(&body[24..28]);
+
+    let config = iface.config();
+    let for_us = config.is_configured() && target_ip == config.address;
+
+    // RFC 826：已有表项时总是更新，发给本机时新建表项
+    let ready = update(iface.id, sender_ip, sender_mac, for_us);
+    for (packet, ethertype) in ready {
+        let _ = ethernet::send(iface, sender_mac, ethertype, packet);
+    }
+
+    if operation == ARP_REQUEST && for_us {
+        let _ = send_arp(iface, ARP_REPLY, sender_mac, sender_ip);
+    }
+}
+
+/// 更新缓存，返回因解析完成而可以发出的数据包
+fn update(iface_id: usize, address: Ipv4Addr, mac: MacAddress, create: bool) -> Vec<(PacketBuffer, u16)> {
+    let now_ms = super::now_ms();
+    let mut cache = CACHE.lock();
+
+    match cache
+        .iter_mut()
+        .find(|e| e.iface_id == iface_id && e.address == address)
+    {
+        Some(entry) => {
+            entry.mac = Some(mac);
+            entry.updated_ms = now_ms;
+            entry.retries = 0;
+            core::mem::take(&mut entry.pending)
+        }
+        None => {
+            if create {
+                cache.push(ArpEntry {
+                    iface_id,
+                    address,
+                    mac: Some(mac),
+                    pending: Vec::new(),
+                    updated_ms: now_ms,
+                    retries: 0,
+                });
+            }
+            Vec::new()
+        }
+    }
+}
+
+/// 查询缓存中的MAC地址
+pub fn lookup(iface_id: usize, address: Ipv4Addr) -> Option<MacAddress> {
+    CACHE
+        .lock()
+        .iter()
+        .find(|e| e.iface_id == iface_id && e.address == address)
+        .and_then(|e| e.mac)
+}
+
+/// 解析下一跳地址并发送数据包
+///
+/// 地址已在缓存中时立即发送，否则暂存数据包并发出ARP请求
+pub fn resolve_and_send(
+    iface: &NetInterface,
+    next_hop: Ipv4Addr,
+    ethertype: u16,
+    packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    let config = iface.config();
+    if next_hop.is_broadcast() || (config.in_subnet(next_hop) && next_hop == config.broadcast_address()) {
+        return ethernet::send(iface, MacAddress::BROADCAST, ethertype, packet);
+    }
+
+    if let Some(mac) = lookup(iface.id, next_hop) {
+        return ethernet::send(iface, mac, ethertype, packet);
+    }
+
+    let now_ms = super::now_ms();
+    let need_request = {
+        let mut cache = CACHE.lock();
+        match cache
+            .iter_mut()
+            .find(|e| e.iface_id == iface.id && e.address == next_hop)
+        {
+            Some(entry) => {
+                if entry.pending.len() >= MAX_PENDING {
+                    return Err(KernelError::ResourceBusy);
+                }
+                entry.pending.push((packet, ethertype));
+                false
+            }
+            None => {
+                cache.push(ArpEntry {
+                    iface_id: iface.id,
+                    address: next_hop,
+                    mac: None,
+                    pending: vec![(packet, ethertype)],
+                    updated_ms: now_ms,
+                    retries: 1,
+                });
+                true
+            }
+        }
+    };
+
+    if need_request {
+        send_arp(iface, ARP_REQUEST, MacAddress::default(), next_hop)?;
+    }
+    Ok(())
+}
+
+/// 驱动ARP定时器：重传未完成的请求并淘汰过期表项
+pub fn tick(now_ms: u64) {
+    let mut retransmit = Vec::new();
+
+    CACHE.lock().retain_mut(|entry| match entry.mac {
+        Some(_) => now_ms.saturating_sub(entry.updated_ms) < ENTRY_TIMEOUT_MS,
+        None => {
+            if now_ms.saturating_sub(entry.updated_ms) < RETRY_INTERVAL_MS {
+                return true;
+            }
+            if entry.retries >= MAX_RETRIES {
+                // 解析失败，丢弃暂存的数据包
+                return false;
+            }
+            entry.retries += 1;
+            entry.updated_ms = now_ms;
+            retransmit.push((entry.iface_id, entry.address));
+            true
+        }
+    });
+
+    for (iface_id, address) in retransmit {
+        if let Some(iface) = interface(iface_id) {
+            let _ = send_arp(&iface, ARP_REQUEST, MacAddress::default(), address);
+        }
+    }
+}
+
+/// ARP缓存的收缩器：只回收已解析的表项，正在解析的表项暂存着数据包，不回收
+pub struct ArpShrinker;
+
+impl Shrinker for ArpShrinker {
+    fn name(&self) -> &str {
+        "arp"
+    }
+
+    fn count_objects(&self) -> usize {
+        CACHE.lock().iter().filter(|entry| entry.mac.is_some()).count()
+    }
+
+    fn scan_objects(&self, nr_to_scan: usize) -> usize {
+        let mut cache = CACHE.lock();
+        let mut freed = 0;
+        while freed < nr_to_scan {
+            let oldest = cache
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.mac.is_some())
+                .min_by_key(|(_, entry)| entry.updated_ms)
+                .map(|(index, _)| index);
+            let Some(index) = oldest else {
+                break;
+            };
+            cache.swap_remove(index);
+            freed += 1;
+        }
+        freed
+    }
+}
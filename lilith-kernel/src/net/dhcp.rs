@@ -0,0 +1,583 @@
+//! DHCP客户端
+//!
+//! 本模块实现了RFC 2131定义的DHCP客户端，在启动时为网络接口获取
+//! 地址、子网掩码、网关与DNS配置，并在租约到期前自动续租：
+//! - INIT → SELECTING：广播DHCPDISCOVER并等待DHCPOFFER
+//! - REQUESTING：针对选中的OFFER广播DHCPREQUEST
+//! - BOUND：收到DHCPACK后配置接口
+//! - RENEWING：到达T1后向原服务器单播续租
+//! - REBINDING：到达T2后广播续租，租约到期则回到INIT
+//!
+//! 无限租约（0xffffffff）不续租；服务器没有给出子网掩码时按地址类别推断
+
+use super::skb::PacketBuffer;
+use super::udp::{self, UdpDatagram};
+use super::{InterfaceConfig, Ipv4Addr, MacAddress, NetInterface};
+use crate::error::KernelError;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+/// DHCP服务器端口
+pub const DHCP_SERVER_PORT: u16 = 67;
+/// DHCP客户端端口
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+/// BOOTP操作码
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+
+/// BOOTP固定部分长度
+const BOOTP_FIXED_LEN: usize = 236;
+/// BOOTP报文最小长度
+const BOOTP_MIN_LEN: usize = 300;
+/// DHCP魔数
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// 广播标志位
+const FLAG_BROADCAST: u16 = 0x8000;
+
+/// DHCP选项代码
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS: u8 = 6;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_PARAMETER_LIST: u8 = 55;
+const OPTION_RENEWAL_TIME: u8 = 58;
+const OPTION_REBINDING_TIME: u8 = 59;
+const OPTION_CLIENT_ID: u8 = 61;
+const OPTION_END: u8 = 255;
+
+/// 初始重传间隔（毫秒）
+const INITIAL_RETRANSMIT_MS: u64 = 4_000;
+/// 最大重传间隔（毫秒）
+const MAX_RETRANSMIT_MS: u64 = 64_000;
+/// 续租阶段最小重传间隔（毫秒）
+const MIN_RENEW_RETRANSMIT_MS: u64 = 60_000;
+/// 无限租约的租期（秒）
+const INFINITE_LEASE: u32 = u32::MAX;
+
+/// DHCP消息类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpMessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+}
+
+/// DHCP客户端状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpState {
+    /// 初始状态
+    Init,
+    /// 等待服务器OFFER
+    Selecting,
+    /// 等待服务器确认
+    Requesting,
+    /// 已获得租约
+    Bound,
+    /// 向原服务器续租
+    Renewing,
+    /// 向任意服务器续租
+    Rebinding,
+}
+
+/// DHCP租约信息
+#[derive(Debug, Clone, Copy)]
+pub struct DhcpLease {
+    /// 分配的地址与网络配置
+    pub config: InterfaceConfig,
+    /// 服务器标识
+    pub server_id: Ipv4Addr,
+    /// 服务器MAC地址（用于单播续租）
+    pub server_mac: MacAddress,
+    /// 租约时长（秒）
+    pub lease_time: u32,
+    /// 续租时间T1（秒）
+    pub renewal_time: u32,
+    /// 重绑定时间T2（秒）
+    pub rebinding_time: u32,
+    /// 获得租约的时刻（毫秒）
+    pub acquired_at_ms: u64,
+}
+
+/// 解析后的DHCP选项
+#[derive(Debug, Clone, Copy, Default)]
+struct DhcpOptions {
+    message_type: Option<u8>,
+    subnet_mask: Option<Ipv4Addr>,
+    router: Option<Ipv4Addr>,
+    dns: [Option<Ipv4Addr>; 2],
+    requested_ip: Option<Ipv4Addr>,
+    server_id: Option<Ipv4Addr>,
+    lease_time: Option<u32>,
+    renewal_time: Option<u32>,
+    rebinding_time: Option<u32>,
+}
+
+/// DHCP报文
+#[derive(Debug, Clone, Copy)]
+struct DhcpMessage {
+    op: u8,
+    xid: u32,
+    flags: u16,
+    ciaddr: Ipv4Addr,
+    yiaddr: Ipv4Addr,
+    chaddr: MacAddress,
+    options: DhcpOptions,
+}
+
+/// 单个接口上的DHCP客户端
+struct DhcpClient {
+    iface: Arc<NetInterface>,
+    state: DhcpState,
+    xid: u32,
+    offer: Option<(Ipv4Addr, Ipv4Addr)>,
+    lease: Option<DhcpLease>,
+    retransmit_ms: u64,
+    next_event_ms: u64,
+}
+
+/// 所有DHCP客户端
+static CLIENTS: Mutex<Vec<DhcpClient>> = Mutex::new(Vec::new());
+
+/// 事务ID计数器
+static XID_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+impl DhcpOptions {
+    /// 解析选项区域
+    fn parse(mut data: &[u8]) -> Self {
+        let mut options = DhcpOptions::default();
+
+        while let Some((&code, rest)) = data.split_first() {
+            match code {
+                OPTION_PAD => {
+                    data = rest;
+                    continue;
+                }
+                OPTION_END => break,
+                _ => {}
+            }
+
+            let len = match rest.first() {
+                Some(&len) => len as usize,
+                None => break,
+            };
+            if rest.len() < 1 + len {
+                break;
+            }
+            let value = &rest[1..1 + len];
+            data = &rest[1 + len..];
+
+            let addr = |v: &[u8]| (v.len() >= 4).then(|| Ipv4Addr::from_slice(v));
+            let seconds = |v: &[u8]| (v.len() >= 4).then(|| u32::from_be_bytes([v[0], v[1], v[2], v[3]]));
+
+            match code {
+                OPTION_MESSAGE_TYPE => options.message_type = value.first().copied(),
+                OPTION_SUBNET_MASK => options.subnet_mask = addr(value),
+                OPTION_ROUTER => options.router = addr(value),
+                OPTION_DNS => {
+                    for (slot, chunk) in options.dns.iter_mut().zip(value.chunks_exact(4)) {
+                        *slot = Some(Ipv4Addr::from_slice(chunk));
+                    }
+                }
+                OPTION_REQUESTED_IP => options.requested_ip = addr(value),
+                OPTION_SERVER_ID => options.server_id = addr(value),
+                OPTION_LEASE_TIME => options.lease_time = seconds(value),
+                OPTION_RENEWAL_TIME => options.renewal_time = seconds(value),
+                OPTION_REBINDING_TIME => options.rebinding_time = seconds(value),
+                _ => {}
+            }
+        }
+
+        options
+    }
+}
+
+impl DhcpMessage {
+    /// 解析DHCP报文
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < BOOTP_FIXED_LEN + MAGIC_COOKIE.len() {
+            return None;
+        }
+        if data[BOOTP_FIXED_LEN..BOOTP_FIXED_LEN + 4] != MAGIC_COOKIE {
+            return None;
+        }
+
+        Some(Self {
+            op: data[0],
+            xid: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            flags: u16::from_be_bytes([data[10], data[11]]),
+            ciaddr: Ipv4Addr::from_slice(&data[12..16]),
+            yiaddr: Ipv4Addr::from_slice(&data[16..20]),
+            chaddr: MacAddress::from_slice(&data[28..34]),
+            options: DhcpOptions::parse(&data[BOOTP_FIXED_LEN + 4..]),
+        })
+    }
+
+    /// 将DHCP报文直接编码到数据包缓冲区
+    fn encode(&self) -> PacketBuffer {
+        let mut buf = PacketBuffer::new(BOOTP_MIN_LEN);
+        buf.extend_from_slice(&[self.op, 1, 6, 0]); // 操作码、以太网、地址长度、跳数
+        buf.extend_from_slice(&self.xid.to_be_bytes());
+        buf.extend_from_slice(&[0, 0]); // 已用秒数
+        buf.extend_from_slice(&self.flags.to_be_bytes());
+        buf.extend_from_slice(&self.ciaddr.0);
+        buf.extend_from_slice(&self.yiaddr.0);
+        buf.put(8).fill(0); // siaddr、giaddr
+        buf.extend_from_slice(&self.chaddr.0);
+        buf.put(BOOTP_FIXED_LEN - buf.len()).fill(0); // chaddr填充、sname、file
+        buf.extend_from_slice(&MAGIC_COOKIE);
+
+        let options = &self.options;
+        if let Some(message_type) = options.message_type {
+            buf.extend_from_slice(&[OPTION_MESSAGE_TYPE, 1, message_type]);
+        }
+        buf.extend_from_slice(&[OPTION_CLIENT_ID, 7, 1]);
+        buf.extend_from_slice(&self.chaddr.0);
+        if let Some(addr) = options.requested_ip {
+            buf.extend_from_slice(&[OPTION_REQUESTED_IP, 4]);
+            buf.extend_from_slice(&addr.0);
+        }
+        if let Some(addr) = options.server_id {
+            buf.extend_from_slice(&[OPTION_SERVER_ID, 4]);
+            buf.extend_from_slice(&addr.0);
+        }
+        buf.extend_from_slice(&[
+            OPTION_PARAMETER_LIST,
+            6,
+            OPTION_SUBNET_MASK,
+            OPTION_ROUTER,
+            OPTION_DNS,
+            OPTION_LEASE_TIME,
+            OPTION_RENEWAL_TIME,
+            OPTION_REBINDING_TIME,
+        ]);
+        buf.extend_from_slice(&[OPTION_END]);
+
+        if buf.len() < BOOTP_MIN_LEN {
+            buf.put(BOOTP_MIN_LEN - buf.len()).fill(OPTION_PAD);
+        }
+        buf
+    }
+}
+
+impl DhcpLease {
+    /// 租约时刻换算为毫秒
+    fn deadline_ms(&self, seconds: u32) -> u64 {
+        self.acquired_at_ms + seconds as u64 * 1000
+    }
+
+    /// 是否为无限租约
+    fn is_infinite(&self) -> bool {
+        self.lease_time == INFINITE_LEASE
+    }
+}
+
+/// 按地址类别推断的子网掩码（A类/8、B类/16，其余/24）
+fn classful_netmask(address: Ipv4Addr) -> Ipv4Addr {
+    match address.0[0] {
+        0..=127 => Ipv4Addr::new(255, 0, 0, 0),
+        128..=191 => Ipv4Addr::new(255, 255, 0, 0),
+        _ => Ipv4Addr::new(255, 255, 255, 0),
+    }
+}
+
+impl DhcpClient {
+    /// 生成新的事务ID
+    fn new_xid(&mut self) {
+        let mac = self.iface.mac_address().0;
+        let seed = u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]);
+        self.xid = seed ^ XID_COUNTER.fetch_add(0x9e37_79b9, Ordering::Relaxed);
+    }
+
+    /// 构造并发送DHCP请求报文
+    fn send(&self, message_type: DhcpMessageType) -> Result<(), KernelError> {
+        let mut options = DhcpOptions {
+            message_type: Some(message_type as u8),
+            ..DhcpOptions::default()
+        };
+        let mut ciaddr = Ipv4Addr::UNSPECIFIED;
+
+        match self.state {
+            DhcpState::Requesting => {
+                if let Some((address, server_id)) = self.offer {
+                    options.requested_ip = Some(address);
+                    options.server_id = Some(server_id);
+                }
+            }
+            DhcpState::Renewing | DhcpState::Rebinding => {
+                if let Some(lease) = &self.lease {
+                    ciaddr = lease.config.address;
+                }
+            }
+            _ => {}
+        }
+
+        let message = DhcpMessage {
+            op: BOOTREQUEST,
+            xid: self.xid,
+            flags: if ciaddr.is_unspecified() { FLAG_BROADCAST } else { 0 },
+            ciaddr,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr: self.iface.mac_address(),
+            options,
+        };
+        let payload = message.encode();
+
+        // 续租阶段单播给原服务器，其余阶段广播
+        match (self.state, &self.lease) {
+            (DhcpState::Renewing, Some(lease)) => udp::send(
+                &self.iface,
+                lease.server_mac,
+                ciaddr,
+                lease.server_id,
+                DHCP_CLIENT_PORT,
+                DHCP_SERVER_PORT,
+                payload,
+            ),
+            _ => udp::send(
+                &self.iface,
+                MacAddress::BROADCAST,
+                ciaddr,
+                Ipv4Addr::BROADCAST,
+                DHCP_CLIENT_PORT,
+                DHCP_SERVER_PORT,
+                payload,
+            ),
+        }
+    }
+
+    /// 重新进入INIT状态并清除接口配置
+    fn restart(&mut self, now_ms: u64) {
+        if self.lease.take().is_some() {
+            self.iface.set_config(InterfaceConfig::default());
+            crate::early_println!("{}: DHCP租约失效，重新获取地址", self.iface.name);
+        }
+        self.state = DhcpState::Init;
+        self.offer = None;
+        self.next_event_ms = now_ms;
+    }
+
+    /// 发送报文并按指数退避安排下一次重传
+    fn transmit_with_backoff(&mut self, message_type: DhcpMessageType, now_ms: u64) {
+        let _ = self.send(message_type);
+        self.next_event_ms = now_ms + self.retransmit_ms;
+        self.retransmit_ms = (self.retransmit_ms * 2).min(MAX_RETRANSMIT_MS);
+    }
+
+    /// 处理超时事件
+    fn on_timeout(&mut self, now_ms: u64) {
+        match self.state {
+            DhcpState::Init => {
+                self.new_xid();
+                self.state = DhcpState::Selecting;
+                self.retransmit_ms = INITIAL_RETRANSMIT_MS;
+                self.transmit_with_backoff(DhcpMessageType::Discover, now_ms);
+            }
+            DhcpState::Selecting => {
+                self.transmit_with_backoff(DhcpMessageType::Discover, now_ms);
+            }
+            DhcpState::Requesting => {
+                if self.retransmit_ms >= MAX_RETRANSMIT_MS {
+                    self.restart(now_ms);
+                } else {
+                    self.transmit_with_backoff(DhcpMessageType::Request, now_ms);
+                }
+            }
+            DhcpState::Bound | DhcpState::Renewing | DhcpState::Rebinding => {
+                let lease = match self.lease {
+                    Some(lease) => lease,
+                    None => return self.restart(now_ms),
+                };
+                if lease.is_infinite() {
+                    self.next_event_ms = u64::MAX;
+                    return;
+                }
+                let t1 = lease.deadline_ms(lease.renewal_time);
+                let t2 = lease.deadline_ms(lease.rebinding_time);
+                let expiry = lease.deadline_ms(lease.lease_time);
+
+                if now_ms >= expiry {
+                    return self.restart(now_ms);
+                }
+
+                let deadline = if now_ms >= t2 {
+                    if self.state != DhcpState::Rebinding {
+                        self.new_xid();
+                        self.state = DhcpState::Rebinding;
+                    }
+                    expiry
+                } else if now_ms >= t1 {
+                    if self.state != DhcpState::Renewing {
+                        self.new_xid();
+                        self.state = DhcpState::Renewing;
+                    }
+                    t2
+                } else {
+                    self.next_event_ms = t1;
+                    return;
+                };
+
+                // RFC 2131：重传间隔取剩余时间的一半，但不少于60秒
+                let _ = self.send(DhcpMessageType::Request);
+                let wait = ((deadline - now_ms) / 2).max(MIN_RENEW_RETRANSMIT_MS);
+                self.next_event_ms = (now_ms + wait).min(deadline);
+            }
+        }
+    }
+
+    /// 处理服务器回复
+    fn on_reply(&mut self, message: &DhcpMessage, server_mac: MacAddress, now_ms: u64) {
+        let message_type = message.options.message_type;
+
+        match self.state {
+            DhcpState::Selecting if message_type == Some(DhcpMessageType::Offer as u8) => {
+                let server_id = match message.options.server_id {
+                    Some(server_id) => server_id,
+                    None => return,
+                };
+                self.offer = Some((message.yiaddr, server_id));
+                self.state = DhcpState::Requesting;
+                self.retransmit_ms = INITIAL_RETRANSMIT_MS;
+                self.transmit_with_backoff(DhcpMessageType::Request, now_ms);
+            }
+            DhcpState::Requesting | DhcpState::Renewing | DhcpState::Rebinding => {
+                if message_type == Some(DhcpMessageType::Ack as u8) {
+                    self.bind(message, server_mac, now_ms);
+                } else if message_type == Some(DhcpMessageType::Nak as u8) {
+                    self.restart(now_ms);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 根据DHCPACK建立租约并配置接口
+    fn bind(&mut self, message: &DhcpMessage, server_mac: MacAddress, now_ms: u64) {
+        let options = &message.options;
+        let server_id = options
+            .server_id
+            .or_else(|| self.lease.map(|lease| lease.server_id))
+            .unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let lease_time = options.lease_time.unwrap_or(INFINITE_LEASE);
+        let renewal_time = options.renewal_time.unwrap_or(lease_time / 2);
+        let rebinding_time = options
+            .rebinding_time
+            .unwrap_or((lease_time as u64 * 7 / 8) as u32);
+
+        let netmask = options.subnet_mask.unwrap_or_else(|| {
+            let netmask = classful_netmask(message.yiaddr);
+            crate::log_warn!("{}: DHCPACK没有子网掩码，按地址类别使用 {}", self.iface.name, netmask);
+            netmask
+        });
+        let config = InterfaceConfig {
+            address: message.yiaddr,
+            netmask,
+            gateway: options.router,
+            dns: options.dns,
+        };
+
+        let lease = DhcpLease {
+            config,
+            server_id,
+            server_mac,
+            lease_time,
+            renewal_time,
+            rebinding_time,
+            acquired_at_ms: now_ms,
+        };
+
+        if self.iface.config() != config {
+            self.iface.set_config(config);
+            crate::early_println!(
+                "{}: DHCP获得地址 {} 掩码 {} 网关 {:?}, 租期 {} 秒",
+                self.iface.name,
+                config.address,
+                config.netmask,
+                config.gateway,
+                lease_time
+            );
+        }
+
+        self.lease = Some(lease);
+        self.offer = None;
+        self.state = DhcpState::Bound;
+        self.next_event_ms = if lease.is_infinite() {
+            u64::MAX
+        } else {
+            lease.deadline_ms(renewal_time)
+        };
+    }
+}
+
+/// 为接口启动DHCP客户端
+pub fn start(iface: &Arc<NetInterface>) -> Result<(), KernelError> {
+    let mut clients = CLIENTS.lock();
+    if clients.iter().any(|c| c.iface.id == iface.id) {
+        return Err(KernelError::ResourceBusy);
+    }
+
+    clients.push(DhcpClient {
+        iface: iface.clone(),
+        state: DhcpState::Init,
+        xid: 0,
+        offer: None,
+        lease: None,
+        retransmit_ms: INITIAL_RETRANSMIT_MS,
+        next_event_ms: super::now_ms(),
+    });
+    Ok(())
+}
+
+/// 停止接口上的DHCP客户端
+pub fn stop(iface_id: usize) {
+    CLIENTS.lock().retain(|c| c.iface.id != iface_id);
+}
+
+/// 查询接口当前状态与租约
+pub fn lease(iface_id: usize) -> Option<(DhcpState, Option<DhcpLease>)> {
+    CLIENTS
+        .lock()
+        .iter()
+        .find(|c| c.iface.id == iface_id)
+        .map(|c| (c.state, c.lease))
+}
+
+/// 驱动DHCP定时器
+pub fn tick(now_ms: u64) {
+    for client in CLIENTS.lock().iter_mut() {
+        if now_ms >= client.next_event_ms {
+            client.on_timeout(now_ms);
+        }
+    }
+}
+
+/// 处理发往DHCP客户端端口的数据报
+pub fn handle_datagram(datagram: &UdpDatagram) {
+    let message = match DhcpMessage::parse(datagram.payload) {
+        Some(message) => message,
+        None => return,
+    };
+
+    if message.op != BOOTREPLY || message.chaddr != datagram.iface.mac_address() {
+        return;
+    }
+
+    let now_ms = super::now_ms();
+    let mut clients = CLIENTS.lock();
+    if let Some(client) = clients
+        .iter_mut()
+        .find(|c| c.iface.id == datagram.iface.id && c.xid == message.xid)
+    {
+        client.on_reply(&message, datagram.source_mac, now_ms);
+    }
+}
@@ -0,0 +1,89 @@
+//! 以太网帧处理
+//!
+//! 本模块负责以太网帧头的解析与构造，并按以太网类型分发上层协议
+
+use super::skb::PacketBuffer;
+use super::raw::{self, Direction};
+use super::{arp, ipv4, MacAddress, NetInterface};
+use crate::error::KernelError;
+
+/// 以太网帧头长度
+pub const ETHERNET_HEADER_LEN: usize = 14;
+
+/// 以太网类型：IPv4
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+/// 以太网类型：ARP
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+
+/// 以太网帧头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthernetHeader {
+    /// 目的MAC地址
+    pub destination: MacAddress,
+    /// 源MAC地址
+    pub source: MacAddress,
+    /// 以太网类型
+    pub ethertype: u16,
+}
+
+impl EthernetHeader {
+    /// 从帧数据解析帧头
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        if frame.len() < ETHERNET_HEADER_LEN {
+            return None;
+        }
+
+        Some(Self {
+            destination: MacAddress::from_slice(&frame[0..6]),
+            source: MacAddress::from_slice(&frame[6..12]),
+            ethertype: u16::from_be_bytes([frame[12], frame[13]]),
+        })
+    }
+
+    /// 将帧头写入长度为 `ETHERNET_HEADER_LEN` 的区域
+    pub fn write(&self, buf: &mut [u8]) {
+        buf[0..6].copy_from_slice(&self.destination.0);
+        buf[6..12].copy_from_slice(&self.source.0);
+        buf[12..14].copy_from_slice(&self.ethertype.to_be_bytes());
+    }
+}
+
+/// 处理接收到的以太网帧
+pub fn receive(iface: &NetInterface, mut packet: PacketBuffer) {
+    let header = match EthernetHeader::parse(packet.data()) {
+        Some(header) => header,
+        None => return,
+    };
+
+    // 丢弃既不是发给本机也不是广播的帧
+    if header.destination != iface.mac_address() && !header.destination.is_broadcast() {
+        return;
+    }
+
+    raw::deliver_frame(iface.id, Direction::Incoming, &packet);
+
+    packet.pull(ETHERNET_HEADER_LEN);
+    match header.ethertype {
+        ETHERTYPE_IPV4 => ipv4::receive(iface, &header, packet),
+        ETHERTYPE_ARP => arp::receive(iface, &header, packet),
+        _ => {}
+    }
+}
+
+/// 就地压入以太网帧头并发送
+pub fn send(
+    iface: &NetInterface,
+    destination: MacAddress,
+    ethertype: u16,
+    mut packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    EthernetHeader {
+        destination,
+        source: iface.mac_address(),
+        ethertype,
+    }
+    .write(packet.push(ETHERNET_HEADER_LEN));
+
+    raw::deliver_frame(iface.id, Direction::Outgoing, &packet);
+    iface.transmit(packet)
+}
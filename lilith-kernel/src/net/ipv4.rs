@@ -0,0 +1,293 @@
+//! IPv4协议处理
+//!
+//! 本模块实现了IPv4数据包的解析、校验、构造以及上层协议分发
+
+use super::ethernet::{self, EthernetHeader, ETHERTYPE_IPV4};
+use super::netfilter::{self, Hook, Verdict};
+use super::skb::PacketBuffer;
+use super::{arp, raw, route, udp, Ipv4Addr, MacAddress, NetInterface};
+use crate::error::KernelError;
+
+/// IPv4最小首部长度
+pub const IPV4_HEADER_LEN: usize = 20;
+
+/// 默认生存时间
+const DEFAULT_TTL: u8 = 64;
+
+/// 协议号：ICMP
+pub const PROTOCOL_ICMP: u8 = 1;
+/// 协议号：TCP
+pub const PROTOCOL_TCP: u8 = 6;
+/// 协议号：UDP
+pub const PROTOCOL_UDP: u8 = 17;
+
+/// IPv4首部
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Header {
+    /// 首部长度（字节）
+    pub header_len: usize,
+    /// 总长度（字节）
+    pub total_len: usize,
+    /// 标识
+    pub identification: u16,
+    /// 生存时间
+    pub ttl: u8,
+    /// 上层协议号
+    pub protocol: u8,
+    /// 源地址
+    pub source: Ipv4Addr,
+    /// 目的地址
+    pub destination: Ipv4Addr,
+}
+
+impl Ipv4Header {
+    /// 解析并校验IPv4首部
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < IPV4_HEADER_LEN || packet[0] >> 4 != 4 {
+            return None;
+        }
+
+        let header_len = ((packet[0] & 0x0f) as usize) * 4;
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        if header_len < IPV4_HEADER_LEN || total_len < header_len || total_len > packet.len() {
+            return None;
+        }
+
+        if checksum(&packet[..header_len]) != 0 {
+            return None;
+        }
+
+        // 暂不支持分片重组，直接丢弃分片
+        let flags_fragment = u16::from_be_bytes([packet[6], packet[7]]);
+        if flags_fragment & 0x3fff != 0 {
+            return None;
+        }
+
+        Some(Self {
+            header_len,
+            total_len,
+            identification: u16::from_be_bytes([packet[4], packet[5]]),
+            ttl: packet[8],
+            protocol: packet[9],
+            source: Ipv4Addr::from_slice(&packet[12..16]),
+            destination: Ipv4Addr::from_slice(&packet[16..20]),
+        })
+    }
+
+    /// 将首部写入长度为 `IPV4_HEADER_LEN` 的区域（自动计算校验和）
+    pub fn write(&self, buf: &mut [u8]) {
+        buf[0] = 0x45; // 版本4，首部长度5个32位字
+        buf[1] = 0;
+        buf[2..4].copy_from_slice(&(self.total_len as u16).to_be_bytes());
+        buf[4..6].copy_from_slice(&self.identification.to_be_bytes());
+        buf[6..8].copy_from_slice(&0x4000u16.to_be_bytes()); // 不分片
+        buf[8] = self.ttl;
+        buf[9] = self.protocol;
+        buf[10..12].copy_from_slice(&[0, 0]);
+        buf[12..16].copy_from_slice(&self.source.0);
+        buf[16..20].copy_from_slice(&self.destination.0);
+
+        let sum = checksum(&buf[..IPV4_HEADER_LEN]);
+        buf[10..12].copy_from_slice(&sum.to_be_bytes());
+    }
+}
+
+/// 累加16位反码和
+pub fn checksum_accumulate(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+/// 折叠累加和并取反得到校验和
+pub fn checksum_finish(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// 计算互联网校验和
+pub fn checksum(data: &[u8]) -> u16 {
+    checksum_finish(checksum_accumulate(0, data))
+}
+
+/// 处理接收到的IPv4数据包
+pub fn receive(iface: &NetInterface, eth: &EthernetHeader, mut packet: PacketBuffer) {
+    let header = match Ipv4Header::parse(packet.data()) {
+        Some(header) => header,
+        None => return,
+    };
+
+    // 去除链路层填充与IP首部，剩余部分即为上层负载；
+    // 保留含首部的引用供原始套接字使用，不复制数据
+    packet.trim(header.total_len);
+    let datagram = packet.clone();
+    packet.pull(header.header_len);
+
+    if netfilter::run_hook(Hook::Prerouting, &header, packet.data()) == Verdict::Drop {
+        return;
+    }
+
+    if !iface.accepts(header.destination) {
+        if route::forwarding_enabled() {
+            // 先释放共享引用，使转发时能够就地修改首部
+            drop(datagram);
+            forward(&header, packet);
+        }
+        return;
+    }
+
+    if netfilter::run_hook(Hook::Input, &header, packet.data()) == Verdict::Drop {
+        return;
+    }
+
+    raw::deliver_ip(iface.id, &header, &datagram);
+    drop(datagram);
+
+    if header.protocol == PROTOCOL_UDP {
+        udp::receive(iface, eth, &header, packet);
+    }
+}
+
+/// 将数据包转发到其他接口
+///
+/// 此时IP首部已被剥离，但仍保留在缓冲区的头部空间中，可就地恢复
+fn forward(header: &Ipv4Header, mut packet: PacketBuffer) {
+    if header.ttl <= 1 || header.destination.is_broadcast() {
+        return;
+    }
+
+    if netfilter::run_hook(Hook::Forward, header, packet.data()) == Verdict::Drop {
+        return;
+    }
+
+    let route = match route::lookup(header.destination) {
+        Some(route) => route,
+        None => return,
+    };
+    // 暂不支持分片，超过出接口MTU的数据包直接丢弃
+    if header.total_len > route.iface.mtu() {
+        return;
+    }
+
+    // 恢复首部，就地递减TTL并重新计算校验和
+    let raw = packet.push(header.header_len);
+    raw[8] -= 1;
+    raw[10..12].copy_from_slice(&[0, 0]);
+    let sum = checksum(raw);
+    raw[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    let _ = arp::resolve_and_send(&route.iface, route.next_hop, ETHERTYPE_IPV4, packet);
+}
+
+/// 构造首部、执行OUTPUT规则链并就地压入首部
+fn prepare_output(
+    iface: &NetInterface,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: u8,
+    packet: &mut PacketBuffer,
+) -> Result<(), KernelError> {
+    let total_len = IPV4_HEADER_LEN + packet.len();
+    if total_len > iface.mtu() {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let header = Ipv4Header {
+        header_len: IPV4_HEADER_LEN,
+        total_len,
+        identification: next_identification(),
+        ttl: DEFAULT_TTL,
+        protocol,
+        source,
+        destination,
+    };
+    if netfilter::run_hook(Hook::Output, &header, packet.data()) == Verdict::Drop {
+        return Err(KernelError::PermissionDenied);
+    }
+
+    header.write(packet.push(IPV4_HEADER_LEN));
+    Ok(())
+}
+
+/// 就地压入IPv4首部并发送到指定的下一跳MAC地址
+///
+/// 用于接口尚未配置地址、无法经过路由的场景（如DHCP）
+pub fn send(
+    iface: &NetInterface,
+    next_hop: MacAddress,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: u8,
+    mut packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    prepare_output(iface, source, destination, protocol, &mut packet)?;
+    ethernet::send(iface, next_hop, ETHERTYPE_IPV4, packet)
+}
+
+/// 查找路由并发送本机产生的IPv4数据包
+///
+/// `source` 为 `None` 时使用出接口的地址
+pub fn output(
+    source: Option<Ipv4Addr>,
+    destination: Ipv4Addr,
+    protocol: u8,
+    mut packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    let route = route::lookup(destination).ok_or(KernelError::NetworkError)?;
+    let source = source.unwrap_or_else(|| route.iface.config().address);
+
+    prepare_output(&route.iface, source, destination, protocol, &mut packet)?;
+    arp::resolve_and_send(&route.iface, route.next_hop, ETHERTYPE_IPV4, packet)
+}
+
+/// 发送已包含完整IPv4首部的数据包（原始套接字 `IPPROTO_RAW`）
+///
+/// 首部中的源地址为0时填入出接口地址，标识、总长度与校验和由内核重新填写
+pub fn output_with_header(mut packet: PacketBuffer) -> Result<(), KernelError> {
+    let data = packet.data();
+    if data.len() < IPV4_HEADER_LEN || data[0] >> 4 != 4 {
+        return Err(KernelError::InvalidArgument);
+    }
+    let header_len = ((data[0] & 0x0f) as usize) * 4;
+    if header_len < IPV4_HEADER_LEN || header_len > data.len() {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let destination = Ipv4Addr::from_slice(&data[16..20]);
+    let route = route::lookup(destination).ok_or(KernelError::NetworkError)?;
+    if packet.len() > route.iface.mtu() {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let total_len = packet.len();
+    let raw = packet.data_mut();
+    if Ipv4Addr::from_slice(&raw[12..16]).is_unspecified() {
+        raw[12..16].copy_from_slice(&route.iface.config().address.0);
+    }
+    raw[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+    raw[4..6].copy_from_slice(&next_identification().to_be_bytes());
+    raw[10..12].copy_from_slice(&[0, 0]);
+    let sum = checksum(&raw[..header_len]);
+    raw[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    let header = Ipv4Header::parse(packet.data()).ok_or(KernelError::InvalidArgument)?;
+    if netfilter::run_hook(Hook::Output, &header, &packet.data()[header_len..]) == Verdict::Drop {
+        return Err(KernelError::PermissionDenied);
+    }
+    arp::resolve_and_send(&route.iface, route.next_hop, ETHERTYPE_IPV4, packet)
+}
+
+/// 分配下一个数据包标识
+fn next_identification() -> u16 {
+    use core::sync::atomic::{AtomicU16, Ordering};
+
+    static IDENTIFICATION: AtomicU16 = AtomicU16::new(1);
+    IDENTIFICATION.fetch_add(1, Ordering::Relaxed)
+}
@@ -0,0 +1,335 @@
+//! 网络子系统
+//!
+//! 本模块实现了内核的网络协议栈，包括：
+//! - 网络设备抽象与接口管理
+//! - 引用计数的零拷贝数据包缓冲区
+//! - 以太网帧收发
+//! - ARP地址解析
+//! - IPv4与UDP协议处理
+//! - 最长前缀匹配路由与接口间转发
+//! - 原始套接字（AF_PACKET/SOCK_RAW）
+//! - DHCP客户端（启动时自动配置接口）
+//! - 内核线程 `netpoll` 定期触发网络接收软中断，驱动各协议的定时器
+//! - SNTP客户端（校正实时时钟）
+//! - 接收处理在网络接收软中断中进行
+
+pub mod skb;
+pub mod ethernet;
+pub mod arp;
+pub mod ipv4;
+pub mod route;
+pub mod udp;
+pub mod dhcp;
+pub mod netfilter;
+pub mod raw;
+pub mod sntp;
+
+use crate::error::KernelError;
+use crate::softirq::{self, SoftIrq};
+use skb::PacketBuffer;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// IPv4地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Ipv4Addr(pub [u8; 4]);
+
+/// MAC地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl Ipv4Addr {
+    /// 未指定地址 0.0.0.0
+    pub const UNSPECIFIED: Ipv4Addr = Ipv4Addr([0, 0, 0, 0]);
+    /// 受限广播地址 255.255.255.255
+    pub const BROADCAST: Ipv4Addr = Ipv4Addr([255, 255, 255, 255]);
+
+    /// 由四个字节创建地址
+    pub const fn new(a: u8, b: u8, c: u8, d: u8) -> Self {
+        Self([a, b, c, d])
+    }
+
+    /// 由网络字节序的32位整数创建地址
+    pub const fn from_u32(value: u32) -> Self {
+        Self(value.to_be_bytes())
+    }
+
+    /// 转换为32位整数
+    pub const fn to_u32(&self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    /// 获取地址字节
+    pub const fn octets(&self) -> [u8; 4] {
+        self.0
+    }
+
+    /// 从字节切片读取地址
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    /// 是否为未指定地址
+    pub fn is_unspecified(&self) -> bool {
+        self.0 == [0, 0, 0, 0]
+    }
+
+    /// 是否为受限广播地址
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [255, 255, 255, 255]
+    }
+}
+
+impl MacAddress {
+    /// 以太网广播地址
+    pub const BROADCAST: MacAddress = MacAddress([0xff; 6]);
+
+    /// 从字节切片读取地址
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut addr = [0u8; 6];
+        addr.copy_from_slice(&bytes[..6]);
+        Self(addr)
+    }
+
+    /// 是否为广播地址
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0xff; 6]
+    }
+}
+
+impl fmt::Display for Ipv4Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+}
+
+impl FromStr for Ipv4Addr {
+    type Err = KernelError;
+
+    /// 解析点分十进制地址
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0u8; 4];
+        let mut parts = s.split('.');
+        for octet in octets.iter_mut() {
+            *octet = parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or(KernelError::InvalidArgument)?;
+        }
+        if parts.next().is_some() {
+            return Err(KernelError::InvalidArgument);
+        }
+        Ok(Self(octets))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
+        )
+    }
+}
+
+/// 网络设备驱动接口
+///
+/// 由具体的网卡驱动（如virtio-net）实现，协议栈只通过该接口收发以太网帧
+pub trait NetDevice: Send + Sync {
+    /// 获取设备MAC地址
+    fn mac_address(&self) -> MacAddress;
+
+    /// 获取最大传输单元
+    fn mtu(&self) -> usize {
+        1500
+    }
+
+    /// 发送一个完整的以太网帧
+    ///
+    /// 驱动可以直接在缓冲区的头部空间内压入设备私有首部
+    fn transmit(&self, frame: PacketBuffer) -> Result<(), KernelError>;
+
+    /// 取出一个已接收的以太网帧
+    fn receive(&self) -> Option<PacketBuffer>;
+}
+
+/// 网络接口配置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceConfig {
+    /// 接口地址
+    pub address: Ipv4Addr,
+    /// 子网掩码
+    pub netmask: Ipv4Addr,
+    /// 默认网关
+    pub gateway: Option<Ipv4Addr>,
+    /// DNS服务器
+    pub dns: [Option<Ipv4Addr>; 2],
+}
+
+/// 网络接口
+pub struct NetInterface {
+    /// 接口编号
+    pub id: usize,
+    /// 接口名称
+    pub name: String,
+    /// 底层网络设备
+    device: Arc<dyn NetDevice>,
+    /// 当前地址配置
+    config: Mutex<InterfaceConfig>,
+}
+
+/// 全局网络接口表
+static INTERFACES: Mutex<Vec<Arc<NetInterface>>> = Mutex::new(Vec::new());
+
+/// 最近一次轮询的单调时间（毫秒）
+static NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+impl InterfaceConfig {
+    /// 接口是否已配置地址
+    pub fn is_configured(&self) -> bool {
+        !self.address.is_unspecified()
+    }
+
+    /// 判断地址是否位于本接口所在子网
+    pub fn in_subnet(&self, addr: Ipv4Addr) -> bool {
+        let mask = self.netmask.to_u32();
+        self.is_configured() && (addr.to_u32() & mask) == (self.address.to_u32() & mask)
+    }
+
+    /// 计算子网广播地址
+    pub fn broadcast_address(&self) -> Ipv4Addr {
+        let mask = self.netmask.to_u32();
+        Ipv4Addr::from_u32((self.address.to_u32() & mask) | !mask)
+    }
+}
+
+impl NetInterface {
+    /// 获取接口MAC地址
+    pub fn mac_address(&self) -> MacAddress {
+        self.device.mac_address()
+    }
+
+    /// 获取接口MTU
+    pub fn mtu(&self) -> usize {
+        self.device.mtu()
+    }
+
+    /// 获取接口当前配置
+    pub fn config(&self) -> InterfaceConfig {
+        *self.config.lock()
+    }
+
+    /// 更新接口配置并重建该接口的自动路由
+    pub fn set_config(&self, config: InterfaceConfig) {
+        *self.config.lock() = config;
+        route::update_interface_routes(self.id, &config);
+    }
+
+    /// 判断目的地址是否应被本接口接收
+    pub fn accepts(&self, addr: Ipv4Addr) -> bool {
+        let config = self.config();
+        addr.is_broadcast()
+            || !config.is_configured()
+            || addr == config.address
+            || (config.in_subnet(addr) && addr == config.broadcast_address())
+    }
+
+    /// 发送以太网帧
+    pub fn transmit(&self, frame: PacketBuffer) -> Result<(), KernelError> {
+        self.device.transmit(frame)
+    }
+}
+
+/// 注册网络接口
+pub fn register_interface(name: &str, device: Arc<dyn NetDevice>) -> Arc<NetInterface> {
+    let mut interfaces = INTERFACES.lock();
+    let iface = Arc::new(NetInterface {
+        id: interfaces.len(),
+        name: String::from(name),
+        device,
+        config: Mutex::new(InterfaceConfig::default()),
+    });
+    interfaces.push(iface.clone());
+
+    crate::early_println!("网络接口 {} 已注册, MAC {}", name, iface.mac_address());
+    iface
+}
+
+/// 按编号查找网络接口
+pub fn interface(id: usize) -> Option<Arc<NetInterface>> {
+    INTERFACES.lock().get(id).cloned()
+}
+
+/// 获取所有网络接口
+pub fn interfaces() -> Vec<Arc<NetInterface>> {
+    INTERFACES.lock().clone()
+}
+
+/// 网络子系统初始化
+///
+/// 为所有已注册的接口启动DHCP客户端
+pub fn net_init() -> Result<(), KernelError> {
+    crate::early_println!("初始化网络子系统...");
+
+    softirq::open_softirq(SoftIrq::NetRx, net_rx_action);
+    netfilter::netfilter_init()?;
+    route::route_init()?;
+    udp::bind(dhcp::DHCP_CLIENT_PORT, dhcp::handle_datagram)?;
+    sntp::sntp_init()?;
+    crate::mm::shrinker::register_shrinker(Arc::new(arp::ArpShrinker))?;
+
+    for iface in interfaces() {
+        dhcp::start(&iface)?;
+    }
+    crate::sched::spawn("netpoll", netpoll_main);
+
+    crate::early_println!("网络子系统初始化完成");
+    Ok(())
+}
+
+/// 协议栈当前时间（毫秒）
+pub fn now_ms() -> u64 {
+    NOW_MS.load(Ordering::Relaxed)
+}
+
+/// 网络接收软中断：以当前时间轮询协议栈
+///
+/// 网卡驱动在接收中断中调用 `softirq::raise_softirq(SoftIrq::NetRx)`
+fn net_rx_action() {
+    poll(crate::time::monotonic_ns() / 1_000_000);
+}
+
+/// 协议定时器的轮询间隔（纳秒）
+const POLL_INTERVAL_NS: u64 = 100_000_000;
+
+/// `netpoll` 线程：没有收到数据包时也定期轮询协议栈，使DHCP等协议的定时器得以推进
+///
+/// 轮询在软中断中进行，与接收中断触发的轮询不会在同一hart上重入
+fn netpoll_main() {
+    loop {
+        softirq::raise_softirq(SoftIrq::NetRx);
+        crate::time::sleep_until(crate::time::monotonic_ns() + POLL_INTERVAL_NS);
+    }
+}
+
+/// 网络协议栈轮询
+///
+/// 处理所有接口上已接收的帧，并驱动协议定时器。`now_ms` 为单调时间（毫秒）
+pub fn poll(now_ms: u64) {
+    NOW_MS.store(now_ms, Ordering::Relaxed);
+
+    for iface in interfaces() {
+        while let Some(frame) = iface.device.receive() {
+            ethernet::receive(&iface, frame);
+        }
+    }
+
+    arp::tick(now_ms);
+    dhcp::tick(now_ms);
+}
@@ -0,0 +1,375 @@
+//! 数据包过滤框架
+//!
+//! 本模块在IPv4收发路径上提供类似netfilter的挂载点：
+//! - PREROUTING：数据包进入协议栈、尚未判断目的地时
+//! - INPUT：确定发往本机之后、交给传输层之前
+//! - FORWARD：需要转发到其他接口的数据包
+//! - OUTPUT：本机产生的数据包发出之前
+//!
+//! 每个挂载点维护一条规则链，按顺序匹配地址、端口与协议，
+//! 首条命中的规则决定处理结果，均未命中时采用链的默认策略。
+//! 规则通过 /proc/net/netfilter 以类似iptables的命令配置：
+//!
+//! ```text
+//! -A INPUT -p udp -s 10.0.0.0/8 --dport 53 -j DROP
+//! -D INPUT 1
+//! -P OUTPUT DROP
+//! -F
+//! ```
+
+use super::ipv4::{Ipv4Header, PROTOCOL_ICMP, PROTOCOL_TCP, PROTOCOL_UDP};
+use super::route::prefix_mask;
+use super::Ipv4Addr;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use spin::Mutex;
+
+/// 挂载点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    Prerouting,
+    Input,
+    Forward,
+    Output,
+}
+
+/// 处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Drop,
+}
+
+/// 带前缀长度的地址匹配条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressMatch {
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+}
+
+/// 过滤规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// 协议号
+    pub protocol: Option<u8>,
+    /// 源地址
+    pub source: Option<AddressMatch>,
+    /// 目的地址
+    pub destination: Option<AddressMatch>,
+    /// 源端口
+    pub source_port: Option<u16>,
+    /// 目的端口
+    pub destination_port: Option<u16>,
+    /// 命中后的处理结果
+    pub verdict: Verdict,
+}
+
+/// 规则链
+struct Chain {
+    hook: Hook,
+    policy: Verdict,
+    rules: Vec<(Rule, u64)>,
+}
+
+/// 参与匹配的数据包信息
+struct PacketInfo {
+    protocol: u8,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: Option<u16>,
+    destination_port: Option<u16>,
+}
+
+/// 所有挂载点的规则链
+static CHAINS: Mutex<[Chain; 4]> = Mutex::new([
+    Chain::new(Hook::Prerouting),
+    Chain::new(Hook::Input),
+    Chain::new(Hook::Forward),
+    Chain::new(Hook::Output),
+]);
+
+impl Hook {
+    /// 挂载点名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::Prerouting => "PREROUTING",
+            Hook::Input => "INPUT",
+            Hook::Forward => "FORWARD",
+            Hook::Output => "OUTPUT",
+        }
+    }
+
+    /// 从名称解析挂载点
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "PREROUTING" => Some(Hook::Prerouting),
+            "INPUT" => Some(Hook::Input),
+            "FORWARD" => Some(Hook::Forward),
+            "OUTPUT" => Some(Hook::Output),
+            _ => None,
+        }
+    }
+}
+
+impl Verdict {
+    /// 处理结果名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verdict::Accept => "ACCEPT",
+            Verdict::Drop => "DROP",
+        }
+    }
+
+    /// 从名称解析处理结果
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ACCEPT" => Some(Verdict::Accept),
+            "DROP" => Some(Verdict::Drop),
+            _ => None,
+        }
+    }
+}
+
+impl AddressMatch {
+    /// 判断地址是否匹配
+    fn matches(&self, addr: Ipv4Addr) -> bool {
+        let mask = prefix_mask(self.prefix_len);
+        addr.to_u32() & mask == self.address.to_u32() & mask
+    }
+
+    /// 解析 `a.b.c.d[/len]` 形式的地址
+    fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => (addr, len.parse().ok().filter(|len| *len <= 32)?),
+            None => (s, 32),
+        };
+        Some(Self {
+            address: addr.parse().ok()?,
+            prefix_len,
+        })
+    }
+}
+
+impl Rule {
+    /// 判断数据包是否匹配本规则
+    fn matches(&self, packet: &PacketInfo) -> bool {
+        self.protocol.map_or(true, |p| p == packet.protocol)
+            && self.source.map_or(true, |m| m.matches(packet.source))
+            && self.destination.map_or(true, |m| m.matches(packet.destination))
+            && self.source_port.map_or(true, |p| Some(p) == packet.source_port)
+            && self
+                .destination_port
+                .map_or(true, |p| Some(p) == packet.destination_port)
+    }
+}
+
+impl Chain {
+    const fn new(hook: Hook) -> Self {
+        Self {
+            hook,
+            policy: Verdict::Accept,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// 协议名称与协议号互转
+fn protocol_from_name(name: &str) -> Option<u8> {
+    match name {
+        "icmp" => Some(PROTOCOL_ICMP),
+        "tcp" => Some(PROTOCOL_TCP),
+        "udp" => Some(PROTOCOL_UDP),
+        _ => name.parse().ok(),
+    }
+}
+
+fn protocol_name(protocol: u8) -> String {
+    match protocol {
+        PROTOCOL_ICMP => String::from("icmp"),
+        PROTOCOL_TCP => String::from("tcp"),
+        PROTOCOL_UDP => String::from("udp"),
+        _ => format!("{}", protocol),
+    }
+}
+
+/// 在挂载点上执行规则链
+///
+/// `payload` 为IP负载，TCP/UDP时从中提取端口号
+pub fn run_hook(hook: Hook, header: &Ipv4Header, payload: &[u8]) -> Verdict {
+    let has_ports = matches!(header.protocol, PROTOCOL_TCP | PROTOCOL_UDP) && payload.len() >= 4;
+    let packet = PacketInfo {
+        protocol: header.protocol,
+        source: header.source,
+        destination: header.destination,
+        source_port: has_ports.then(|| u16::from_be_bytes([payload[0], payload[1]])),
+        destination_port: has_ports.then(|| u16::from_be_bytes([payload[2], payload[3]])),
+    };
+
+    let mut chains = CHAINS.lock();
+    let chain = &mut chains[hook as usize];
+    for (rule, hits) in chain.rules.iter_mut() {
+        if rule.matches(&packet) {
+            *hits += 1;
+            return rule.verdict;
+        }
+    }
+    chain.policy
+}
+
+/// 在链尾追加规则
+pub fn append_rule(hook: Hook, rule: Rule) {
+    CHAINS.lock()[hook as usize].rules.push((rule, 0));
+}
+
+/// 删除链中的规则（序号从1开始）
+pub fn delete_rule(hook: Hook, index: usize) -> Result<(), KernelError> {
+    let mut chains = CHAINS.lock();
+    let rules = &mut chains[hook as usize].rules;
+    if index == 0 || index > rules.len() {
+        return Err(KernelError::NotFound);
+    }
+    rules.remove(index - 1);
+    Ok(())
+}
+
+/// 清空规则链，`None` 表示清空所有链
+pub fn flush(hook: Option<Hook>) {
+    for chain in CHAINS.lock().iter_mut() {
+        if hook.map_or(true, |h| h == chain.hook) {
+            chain.rules.clear();
+        }
+    }
+}
+
+/// 设置链的默认策略
+pub fn set_policy(hook: Hook, policy: Verdict) {
+    CHAINS.lock()[hook as usize].policy = policy;
+}
+
+/// 执行一条配置命令
+pub fn execute_command(command: &str) -> Result<(), KernelError> {
+    let mut tokens = command.split_whitespace();
+    let hook = |name: Option<&str>| name.and_then(Hook::from_name).ok_or(KernelError::InvalidArgument);
+
+    match tokens.next() {
+        Some("-A") => {
+            let hook = hook(tokens.next())?;
+            let rule = parse_rule(&mut tokens)?;
+            append_rule(hook, rule);
+            Ok(())
+        }
+        Some("-D") => {
+            let hook = hook(tokens.next())?;
+            let index = tokens
+                .next()
+                .and_then(|s| s.parse().ok())
+                .ok_or(KernelError::InvalidArgument)?;
+            delete_rule(hook, index)
+        }
+        Some("-P") => {
+            let hook = hook(tokens.next())?;
+            let policy = tokens
+                .next()
+                .and_then(Verdict::from_name)
+                .ok_or(KernelError::InvalidArgument)?;
+            set_policy(hook, policy);
+            Ok(())
+        }
+        Some("-F") => {
+            match tokens.next() {
+                Some(name) => flush(Some(hook(Some(name))?)),
+                None => flush(None),
+            }
+            Ok(())
+        }
+        None => Ok(()),
+        _ => Err(KernelError::InvalidArgument),
+    }
+}
+
+/// 解析规则匹配条件与目标
+fn parse_rule<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Rule, KernelError> {
+    let mut rule = Rule {
+        protocol: None,
+        source: None,
+        destination: None,
+        source_port: None,
+        destination_port: None,
+        verdict: Verdict::Accept,
+    };
+    let mut verdict = None;
+
+    while let Some(option) = tokens.next() {
+        let value = tokens.next().ok_or(KernelError::InvalidArgument)?;
+        let invalid = || KernelError::InvalidArgument;
+        match option {
+            "-p" => rule.protocol = Some(protocol_from_name(value).ok_or_else(invalid)?),
+            "-s" => rule.source = Some(AddressMatch::parse(value).ok_or_else(invalid)?),
+            "-d" => rule.destination = Some(AddressMatch::parse(value).ok_or_else(invalid)?),
+            "--sport" => rule.source_port = Some(value.parse().map_err(|_| invalid())?),
+            "--dport" => rule.destination_port = Some(value.parse().map_err(|_| invalid())?),
+            "-j" => verdict = Some(Verdict::from_name(value).ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        }
+    }
+
+    // 端口条件仅对TCP/UDP有意义
+    if (rule.source_port.is_some() || rule.destination_port.is_some())
+        && !matches!(rule.protocol, Some(PROTOCOL_TCP) | Some(PROTOCOL_UDP))
+    {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    rule.verdict = verdict.ok_or(KernelError::InvalidArgument)?;
+    Ok(rule)
+}
+
+/// 生成 /proc/net/netfilter 的内容
+fn proc_read() -> String {
+    let mut out = String::new();
+    for chain in CHAINS.lock().iter() {
+        let _ = writeln!(out, "Chain {} (policy {})", chain.hook.name(), chain.policy.name());
+        for (index, (rule, hits)) in chain.rules.iter().enumerate() {
+            let _ = write!(out, "{:>3} {:>8}", index + 1, hits);
+            if let Some(protocol) = rule.protocol {
+                let _ = write!(out, " -p {}", protocol_name(protocol));
+            }
+            if let Some(m) = rule.source {
+                let _ = write!(out, " -s {}/{}", m.address, m.prefix_len);
+            }
+            if let Some(m) = rule.destination {
+                let _ = write!(out, " -d {}/{}", m.address, m.prefix_len);
+            }
+            if let Some(port) = rule.source_port {
+                let _ = write!(out, " --sport {}", port);
+            }
+            if let Some(port) = rule.destination_port {
+                let _ = write!(out, " --dport {}", port);
+            }
+            let _ = writeln!(out, " -j {}", rule.verdict.name());
+        }
+    }
+    out
+}
+
+/// 处理写入 /proc/net/netfilter 的命令（每行一条）
+fn proc_write(data: &str) -> Result<(), KernelError> {
+    for line in data.lines() {
+        execute_command(line.trim())?;
+    }
+    Ok(())
+}
+
+/// 初始化数据包过滤框架
+pub fn netfilter_init() -> Result<(), KernelError> {
+    procfs::register(
+        "net/netfilter",
+        Some(Box::new(proc_read)),
+        Some(Box::new(proc_write)),
+    )
+}
@@ -0,0 +1,267 @@
+//! 原始套接字
+//!
+//! 本模块实现了两类原始套接字，为ping、tcpdump等用户态工具提供基础：
+//! - 数据包套接字（AF_PACKET）：收发完整的以太网帧，包括本机发出的帧
+//! - 原始IP套接字（SOCK_RAW）：收发指定协议号的IPv4数据包（含IP首部）；
+//!   与Linux一致，`IPPROTO_RAW` 套接字只能发送，不接收任何数据包
+//!
+//! 每个套接字可以设置简单的过滤条件（接口、IP协议号、源地址），
+//! 不需要BPF即可只接收感兴趣的数据包。投递时仅克隆数据包缓冲区的引用，
+//! 不复制数据。
+
+use super::ethernet::{EthernetHeader, ETHERNET_HEADER_LEN, ETHERTYPE_IPV4};
+use super::ipv4::{self, Ipv4Header};
+use super::skb::PacketBuffer;
+use super::{interface, Ipv4Addr};
+use crate::error::KernelError;
+use crate::kobject::{KObject, KRef, KWeak};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// 匹配所有以太网类型（ETH_P_ALL）
+pub const ETH_P_ALL: u16 = 0x0003;
+/// 发送时由用户提供完整IP首部（IPPROTO_RAW），只用于发送
+pub const IPPROTO_RAW: u8 = 255;
+
+/// 每个套接字接收队列的最大长度
+const MAX_QUEUE_LEN: usize = 64;
+
+/// 原始套接字类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawSocketKind {
+    /// 数据包套接字，按以太网类型接收
+    Packet { ethertype: u16 },
+    /// 原始IP套接字，按协议号接收
+    Ip { protocol: u8 },
+}
+
+/// 数据包方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// 接收自网络
+    Incoming,
+    /// 本机发出
+    Outgoing,
+}
+
+/// 套接字过滤条件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RawFilter {
+    /// 只接收指定接口的数据包
+    pub iface_id: Option<usize>,
+    /// 只接收指定IP协议号的数据包
+    pub ip_protocol: Option<u8>,
+    /// 只接收指定源地址的数据包
+    pub source: Option<Ipv4Addr>,
+}
+
+/// 投递给套接字的数据包
+#[derive(Clone)]
+pub struct RawPacket {
+    /// 收发接口
+    pub iface_id: usize,
+    /// 数据包方向
+    pub direction: Direction,
+    /// 数据包内容（数据包套接字为以太网帧，原始IP套接字为IP数据包）
+    pub data: PacketBuffer,
+}
+
+/// 原始套接字
+pub struct RawSocket {
+    /// 套接字编号
+    pub id: usize,
+    /// 套接字类型
+    pub kind: RawSocketKind,
+    filter: Mutex<RawFilter>,
+    queue: Mutex<VecDeque<RawPacket>>,
+    dropped: AtomicU64,
+}
+
+/// 所有打开的原始套接字，不延长套接字的寿命
+static SOCKETS: Mutex<Vec<KWeak<RawSocket>>> = Mutex::new(Vec::new());
+
+/// 套接字编号分配器
+static NEXT_SOCKET_ID: AtomicUsize = AtomicUsize::new(1);
+
+impl RawFilter {
+    /// 判断IP数据包是否满足过滤条件
+    fn matches(&self, iface_id: usize, ip: Option<&Ipv4Header>) -> bool {
+        if self.iface_id.map_or(false, |id| id != iface_id) {
+            return false;
+        }
+        if self.ip_protocol.is_none() && self.source.is_none() {
+            return true;
+        }
+        match ip {
+            Some(ip) => {
+                self.ip_protocol.map_or(true, |p| p == ip.protocol)
+                    && self.source.map_or(true, |s| s == ip.source)
+            }
+            None => false,
+        }
+    }
+}
+
+impl RawSocket {
+    /// 设置过滤条件
+    pub fn set_filter(&self, filter: RawFilter) {
+        *self.filter.lock() = filter;
+    }
+
+    /// 获取过滤条件
+    pub fn filter(&self) -> RawFilter {
+        *self.filter.lock()
+    }
+
+    /// 取出一个已接收的数据包
+    pub fn recv(&self) -> Option<RawPacket> {
+        self.queue.lock().pop_front()
+    }
+
+    /// 接收队列中的数据包数量
+    pub fn pending(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// 因队列已满而丢弃的数据包数量
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 发送数据
+    ///
+    /// 数据包套接字发送完整的以太网帧，需要指定出接口；
+    /// 原始IP套接字发送IP负载，协议号为 `IPPROTO_RAW` 时数据须包含IP首部
+    pub fn send(&self, iface_id: Option<usize>, destination: Ipv4Addr, data: &[u8]) -> Result<(), KernelError> {
+        match self.kind {
+            RawSocketKind::Packet { .. } => {
+                let iface = iface_id
+                    .or(self.filter().iface_id)
+                    .and_then(interface)
+                    .ok_or(KernelError::InvalidArgument)?;
+                if data.len() < ETHERNET_HEADER_LEN || data.len() > iface.mtu() + ETHERNET_HEADER_LEN {
+                    return Err(KernelError::InvalidArgument);
+                }
+                let mut packet = PacketBuffer::new(data.len());
+                packet.extend_from_slice(data);
+                deliver_frame(iface.id, Direction::Outgoing, &packet);
+                iface.transmit(packet)
+            }
+            RawSocketKind::Ip { protocol } => {
+                let mut packet = PacketBuffer::new(data.len());
+                packet.extend_from_slice(data);
+                if protocol == IPPROTO_RAW {
+                    ipv4::output_with_header(packet)
+                } else {
+                    ipv4::output(None, destination, protocol, packet)
+                }
+            }
+        }
+    }
+
+    /// 将数据包放入接收队列
+    fn enqueue(&self, packet: RawPacket) {
+        let mut queue = self.queue.lock();
+        if queue.len() >= MAX_QUEUE_LEN {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        queue.push_back(packet);
+    }
+}
+
+/// 最后一个引用释放时从套接字表中移除
+impl KObject for RawSocket {
+    fn release(&mut self) {
+        SOCKETS.lock().retain(KWeak::is_alive);
+    }
+}
+
+/// 打开套接字
+fn open(kind: RawSocketKind) -> KRef<RawSocket> {
+    let socket = KRef::new(RawSocket {
+        id: NEXT_SOCKET_ID.fetch_add(1, Ordering::Relaxed),
+        kind,
+        filter: Mutex::new(RawFilter::default()),
+        queue: Mutex::new(VecDeque::new()),
+        dropped: AtomicU64::new(0),
+    });
+    SOCKETS.lock().push(KRef::downgrade(&socket));
+    socket
+}
+
+/// 打开数据包套接字，`ethertype` 为 `ETH_P_ALL` 时接收所有帧
+pub fn open_packet(ethertype: u16) -> KRef<RawSocket> {
+    open(RawSocketKind::Packet { ethertype })
+}
+
+/// 打开原始IP套接字
+pub fn open_ip(protocol: u8) -> Result<KRef<RawSocket>, KernelError> {
+    if protocol == 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    Ok(open(RawSocketKind::Ip { protocol }))
+}
+
+/// 关闭套接字，不再接收数据包
+///
+/// 最后一个引用释放时套接字会自动关闭，只有需要提前停止接收时才需调用
+pub fn close(socket: &KRef<RawSocket>) {
+    // 持有表锁时不能升级弱引用：临时强引用可能成为最后一个引用并触发 `release`
+    SOCKETS.lock().retain(|s| !KRef::is_same(socket, s));
+}
+
+/// 获取当前所有套接字的快照，避免投递时持有全局锁
+fn sockets() -> Vec<KRef<RawSocket>> {
+    SOCKETS.lock().iter().filter_map(KWeak::upgrade).collect()
+}
+
+/// 向数据包套接字投递以太网帧
+pub fn deliver_frame(iface_id: usize, direction: Direction, frame: &PacketBuffer) {
+    let sockets = sockets();
+    if sockets.is_empty() {
+        return;
+    }
+
+    let ethertype = match EthernetHeader::parse(frame.data()) {
+        Some(header) => header.ethertype,
+        None => return,
+    };
+    let ip = if ethertype == ETHERTYPE_IPV4 {
+        Ipv4Header::parse(&frame.data()[ETHERNET_HEADER_LEN..])
+    } else {
+        None
+    };
+
+    for socket in sockets {
+        if let RawSocketKind::Packet { ethertype: wanted } = socket.kind {
+            if (wanted == ETH_P_ALL || wanted == ethertype) && socket.filter().matches(iface_id, ip.as_ref()) {
+                socket.enqueue(RawPacket {
+                    iface_id,
+                    direction,
+                    data: frame.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// 向协议号相同的原始IP套接字投递发往本机的IPv4数据包（含首部）
+pub fn deliver_ip(iface_id: usize, header: &Ipv4Header, packet: &PacketBuffer) {
+    for socket in sockets() {
+        if let RawSocketKind::Ip { protocol } = socket.kind {
+            if protocol != IPPROTO_RAW
+                && protocol == header.protocol
+                && socket.filter().matches(iface_id, Some(header))
+            {
+                socket.enqueue(RawPacket {
+                    iface_id,
+                    direction: Direction::Incoming,
+                    data: packet.clone(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! IPv4路由表
+//!
+//! 本模块实现了最长前缀匹配的路由表：
+//! - 接口配置地址后自动生成直连路由与默认路由
+//! - 支持手工添加按接口或按网关的静态路由
+//! - 通过 /proc/net/route 查看与修改路由，
+//!   通过 /proc/sys/net/ipv4/ip_forward 开关接口间转发
+//!
+//! 配置命令格式：
+//!
+//! ```text
+//! add 10.0.0.0/8 via 192.168.1.1 [dev eth0] [metric 10]
+//! add default via 192.168.1.1
+//! add 172.16.0.0/12 dev eth1
+//! del 10.0.0.0/8
+//! ```
+
+use super::{interface, interfaces, InterfaceConfig, Ipv4Addr, NetInterface};
+use crate::error::KernelError;
+use crate::fs::procfs;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 路由表项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// 目的网络
+    pub destination: Ipv4Addr,
+    /// 前缀长度
+    pub prefix_len: u8,
+    /// 网关，`None` 表示目的网络直连
+    pub gateway: Option<Ipv4Addr>,
+    /// 出接口编号
+    pub iface_id: usize,
+    /// 度量值，越小越优先
+    pub metric: u32,
+    /// 是否由接口配置自动生成
+    pub automatic: bool,
+}
+
+/// 路由查找结果
+pub struct RouteResult {
+    /// 出接口
+    pub iface: Arc<NetInterface>,
+    /// 下一跳地址
+    pub next_hop: Ipv4Addr,
+}
+
+/// 路由表，按前缀长度降序、度量值升序排列
+static ROUTES: Mutex<Vec<Route>> = Mutex::new(Vec::new());
+
+/// 是否允许在接口间转发数据包
+static IP_FORWARD: AtomicBool = AtomicBool::new(false);
+
+/// 生成前缀长度对应的掩码
+pub fn prefix_mask(prefix_len: u8) -> u32 {
+    if prefix_len == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix_len as u32)
+    }
+}
+
+/// 由子网掩码计算前缀长度
+fn mask_prefix_len(netmask: Ipv4Addr) -> u8 {
+    netmask.to_u32().leading_ones() as u8
+}
+
+impl Route {
+    /// 判断地址是否匹配本路由
+    fn matches(&self, addr: Ipv4Addr) -> bool {
+        let mask = prefix_mask(self.prefix_len);
+        addr.to_u32() & mask == self.destination.to_u32() & mask
+    }
+}
+
+/// 添加路由
+pub fn add_route(route: Route) -> Result<(), KernelError> {
+    if route.prefix_len > 32 || interface(route.iface_id).is_none() {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let mut routes = ROUTES.lock();
+    if routes.iter().any(|r| {
+        r.destination == route.destination && r.prefix_len == route.prefix_len && r.metric == route.metric
+    }) {
+        return Err(KernelError::ResourceBusy);
+    }
+
+    let position = routes
+        .iter()
+        .position(|r| (r.prefix_len, u32::MAX - r.metric) < (route.prefix_len, u32::MAX - route.metric))
+        .unwrap_or(routes.len());
+    routes.insert(position, route);
+    Ok(())
+}
+
+/// 删除目的网络的所有路由
+pub fn delete_route(destination: Ipv4Addr, prefix_len: u8) -> Result<(), KernelError> {
+    let mut routes = ROUTES.lock();
+    let before = routes.len();
+    routes.retain(|r| !(r.destination == destination && r.prefix_len == prefix_len));
+    if routes.len() == before {
+        return Err(KernelError::NotFound);
+    }
+    Ok(())
+}
+
+/// 获取当前路由表
+pub fn routes() -> Vec<Route> {
+    ROUTES.lock().clone()
+}
+
+/// 最长前缀匹配查找路由
+pub fn lookup(destination: Ipv4Addr) -> Option<RouteResult> {
+    // 受限广播不经过路由表，从第一个已配置的接口发出
+    if destination.is_broadcast() {
+        return interfaces()
+            .into_iter()
+            .find(|iface| iface.config().is_configured())
+            .map(|iface| RouteResult {
+                iface,
+                next_hop: destination,
+            });
+    }
+
+    let route = ROUTES.lock().iter().find(|r| r.matches(destination)).copied()?;
+    Some(RouteResult {
+        iface: interface(route.iface_id)?,
+        next_hop: route.gateway.unwrap_or(destination),
+    })
+}
+
+/// 接口配置变化时重建自动路由
+pub fn update_interface_routes(iface_id: usize, config: &InterfaceConfig) {
+    ROUTES.lock().retain(|r| !(r.automatic && r.iface_id == iface_id));
+
+    if !config.is_configured() {
+        return;
+    }
+
+    let prefix_len = mask_prefix_len(config.netmask);
+    let _ = add_route(Route {
+        destination: Ipv4Addr::from_u32(config.address.to_u32() & prefix_mask(prefix_len)),
+        prefix_len,
+        gateway: None,
+        iface_id,
+        metric: 0,
+        automatic: true,
+    });
+
+    if let Some(gateway) = config.gateway {
+        let _ = add_route(Route {
+            destination: Ipv4Addr::UNSPECIFIED,
+            prefix_len: 0,
+            gateway: Some(gateway),
+            iface_id,
+            metric: 100 + iface_id as u32,
+            automatic: true,
+        });
+    }
+}
+
+/// 是否开启IP转发
+pub fn forwarding_enabled() -> bool {
+    IP_FORWARD.load(Ordering::Relaxed)
+}
+
+/// 开关IP转发
+pub fn set_forwarding(enabled: bool) {
+    IP_FORWARD.store(enabled, Ordering::Relaxed);
+}
+
+/// 解析 `a.b.c.d/len` 或 `default` 形式的目的网络
+fn parse_destination(s: &str) -> Result<(Ipv4Addr, u8), KernelError> {
+    if s == "default" {
+        return Ok((Ipv4Addr::UNSPECIFIED, 0));
+    }
+
+    let (addr, prefix_len) = match s.split_once('/') {
+        Some((addr, len)) => (addr, len.parse().map_err(|_| KernelError::InvalidArgument)?),
+        None => (s, 32),
+    };
+    if prefix_len > 32 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let addr: Ipv4Addr = addr.parse()?;
+    Ok((Ipv4Addr::from_u32(addr.to_u32() & prefix_mask(prefix_len)), prefix_len))
+}
+
+/// 执行一条路由配置命令
+pub fn execute_command(command: &str) -> Result<(), KernelError> {
+    let mut tokens = command.split_whitespace();
+    let action = match tokens.next() {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+    let (destination, prefix_len) = parse_destination(tokens.next().ok_or(KernelError::InvalidArgument)?)?;
+
+    match action {
+        "add" => {
+            let mut gateway = None;
+            let mut iface_id = None;
+            let mut metric = 0;
+
+            while let Some(option) = tokens.next() {
+                let value = tokens.next().ok_or(KernelError::InvalidArgument)?;
+                match option {
+                    "via" => gateway = Some(value.parse()?),
+                    "dev" => {
+                        iface_id = Some(
+                            interfaces()
+                                .iter()
+                                .find(|iface| iface.name == value)
+                                .map(|iface| iface.id)
+                                .ok_or(KernelError::NotFound)?,
+                        )
+                    }
+                    "metric" => metric = value.parse().map_err(|_| KernelError::InvalidArgument)?,
+                    _ => return Err(KernelError::InvalidArgument),
+                }
+            }
+
+            // 未指定出接口时，按网关所在的直连网络选择
+            let iface_id = match (iface_id, gateway) {
+                (Some(id), _) => id,
+                (None, Some(gateway)) => lookup(gateway)
+                    .filter(|result| result.next_hop == gateway)
+                    .map(|result| result.iface.id)
+                    .ok_or(KernelError::NotFound)?,
+                (None, None) => return Err(KernelError::InvalidArgument),
+            };
+
+            add_route(Route {
+                destination,
+                prefix_len,
+                gateway,
+                iface_id,
+                metric,
+                automatic: false,
+            })
+        }
+        "del" => delete_route(destination, prefix_len),
+        _ => Err(KernelError::InvalidArgument),
+    }
+}
+
+/// 生成 /proc/net/route 的内容
+fn proc_read_routes() -> String {
+    let mut out = String::from("Destination        Gateway          Iface    Metric\n");
+    for route in routes() {
+        let name = interface(route.iface_id).map(|iface| iface.name.clone()).unwrap_or_default();
+        let mut destination = String::new();
+        let _ = write!(destination, "{}/{}", route.destination, route.prefix_len);
+        let mut gateway = String::new();
+        match route.gateway {
+            Some(gateway_addr) => {
+                let _ = write!(gateway, "{}", gateway_addr);
+            }
+            None => gateway.push('*'),
+        }
+        let _ = writeln!(out, "{:<18} {:<16} {:<8} {}", destination, gateway, name, route.metric);
+    }
+    out
+}
+
+/// 处理写入 /proc/net/route 的命令（每行一条）
+fn proc_write_routes(data: &str) -> Result<(), KernelError> {
+    for line in data.lines() {
+        execute_command(line.trim())?;
+    }
+    Ok(())
+}
+
+/// 初始化路由子系统
+pub fn route_init() -> Result<(), KernelError> {
+    procfs::register(
+        "net/route",
+        Some(Box::new(proc_read_routes)),
+        Some(Box::new(proc_write_routes)),
+    )?;
+    procfs::register(
+        "sys/net/ipv4/ip_forward",
+        Some(Box::new(|| String::from(if forwarding_enabled() { "1\n" } else { "0\n" }))),
+        Some(Box::new(|data: &str| {
+            match data.trim() {
+                "0" => set_forwarding(false),
+                "1" => set_forwarding(true),
+                _ => return Err(KernelError::InvalidArgument),
+            }
+            Ok(())
+        })),
+    )
+}
@@ -0,0 +1,137 @@
+//! 网络数据包缓冲区
+//!
+//! `PacketBuffer` 是协议栈各层之间传递数据包的唯一载体：
+//! - 底层存储通过引用计数共享，克隆缓冲区不会复制数据
+//! - 数据区前后预留头部空间与尾部空间，发送时各层就地压入首部，
+//!   接收时各层就地剥离首部，负载在整个路径上只写入一次
+//! - 仅在共享状态下修改数据时才复制底层存储（写时复制）
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 默认预留的头部空间，足以容纳以太网、IPv4与传输层首部
+pub const MAX_HEADER_LEN: usize = 128;
+
+/// 网络数据包缓冲区
+#[derive(Clone)]
+pub struct PacketBuffer {
+    /// 共享的底层存储
+    storage: Arc<Vec<u8>>,
+    /// 有效数据起始偏移
+    head: usize,
+    /// 有效数据结束偏移
+    tail: usize,
+}
+
+impl PacketBuffer {
+    /// 创建指定头部空间与数据容量的空缓冲区
+    pub fn with_headroom(headroom: usize, capacity: usize) -> Self {
+        Self {
+            storage: Arc::new(vec![0; headroom + capacity]),
+            head: headroom,
+            tail: headroom,
+        }
+    }
+
+    /// 创建预留默认头部空间的空缓冲区
+    pub fn new(capacity: usize) -> Self {
+        Self::with_headroom(MAX_HEADER_LEN, capacity)
+    }
+
+    /// 接管驱动接收到的帧数据（不复制）
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        let len = data.len();
+        Self {
+            storage: Arc::new(data),
+            head: 0,
+            tail: len,
+        }
+    }
+
+    /// 有效数据长度
+    pub fn len(&self) -> usize {
+        self.tail - self.head
+    }
+
+    /// 是否没有有效数据
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    /// 剩余头部空间
+    pub fn headroom(&self) -> usize {
+        self.head
+    }
+
+    /// 剩余尾部空间
+    pub fn tailroom(&self) -> usize {
+        self.storage.len() - self.tail
+    }
+
+    /// 底层存储是否被其他缓冲区共享
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.storage) > 1
+    }
+
+    /// 有效数据
+    pub fn data(&self) -> &[u8] {
+        &self.storage[self.head..self.tail]
+    }
+
+    /// 可写的有效数据（共享时先复制）
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let (head, tail) = (self.head, self.tail);
+        &mut Arc::make_mut(&mut self.storage)[head..tail]
+    }
+
+    /// 在数据前方压入 `len` 字节并返回该区域，用于写入首部
+    pub fn push(&mut self, len: usize) -> &mut [u8] {
+        if self.head < len {
+            self.grow_headroom(len - self.head + MAX_HEADER_LEN);
+        }
+        self.head -= len;
+        let head = self.head;
+        &mut Arc::make_mut(&mut self.storage)[head..head + len]
+    }
+
+    /// 从数据前方剥离 `len` 字节并返回被剥离的首部
+    pub fn pull(&mut self, len: usize) -> Option<&[u8]> {
+        if len > self.len() {
+            return None;
+        }
+        self.head += len;
+        Some(&self.storage[self.head - len..self.head])
+    }
+
+    /// 在数据末尾追加 `len` 字节并返回该区域
+    pub fn put(&mut self, len: usize) -> &mut [u8] {
+        let storage = Arc::make_mut(&mut self.storage);
+        if storage.len() - self.tail < len {
+            storage.resize(self.tail + len, 0);
+        }
+        self.tail += len;
+        &mut storage[self.tail - len..self.tail]
+    }
+
+    /// 在数据末尾追加字节
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.put(bytes.len()).copy_from_slice(bytes);
+    }
+
+    /// 将有效数据截断到 `len` 字节（用于去除链路层填充）
+    pub fn trim(&mut self, len: usize) {
+        if len < self.len() {
+            self.tail = self.head + len;
+        }
+    }
+
+    /// 头部空间不足时重新分配存储（慢路径）
+    fn grow_headroom(&mut self, extra: usize) {
+        let mut storage = vec![0; extra + self.storage.len()];
+        storage[extra..].copy_from_slice(&self.storage);
+        self.storage = Arc::new(storage);
+        self.head += extra;
+        self.tail += extra;
+    }
+}
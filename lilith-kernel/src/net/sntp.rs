@@ -0,0 +1,235 @@
+//! SNTP客户端
+//!
+//! 本模块实现了RFC 4330定义的SNTP客户端，定期向配置的服务器查询时间
+//! 并校正实时时钟：
+//! - 偏差小于阈值时按固定速率微调，避免时间跳变
+//! - 偏差较大（如首次同步）时直接步进
+//! - 通过 /proc/sys/net/sntp/server 与 /proc/sys/net/sntp/poll_interval 配置
+//!
+//! 查询由内核线程 `sntpd` 周期性发起，应答在网络接收软中断中处理，
+//! 因此客户端状态使用关中断自旋锁保护
+
+use super::skb::PacketBuffer;
+use super::udp::{self, UdpDatagram};
+use super::Ipv4Addr;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sync::SpinLockIrqSave;
+use crate::time::{self, Timespec, NSEC_PER_SEC};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+
+/// NTP服务器端口
+pub const NTP_SERVER_PORT: u16 = 123;
+/// SNTP客户端本地端口
+pub const SNTP_CLIENT_PORT: u16 = 1123;
+
+/// SNTP报文长度
+const SNTP_PACKET_LEN: usize = 48;
+/// NTP纪元（1900年）与Unix纪元（1970年）之间的秒数
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// LI=0，VN=4，Mode=3（客户端）
+const CLIENT_HEADER: u8 = (4 << 3) | 3;
+/// 服务器模式
+const MODE_SERVER: u8 = 4;
+/// 时钟未同步的闰秒指示
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// 默认查询间隔（毫秒）
+const DEFAULT_POLL_INTERVAL_MS: u64 = 64_000;
+/// 最小查询间隔（毫秒）
+const MIN_POLL_INTERVAL_MS: u64 = 16_000;
+/// 等待应答的超时时间（毫秒）
+const RESPONSE_TIMEOUT_MS: u64 = 5_000;
+/// `sntpd` 线程的唤醒间隔（纳秒）
+const SNTPD_INTERVAL_NS: u64 = NSEC_PER_SEC;
+/// 超过该偏差时直接步进而不是微调（纳秒）
+const STEP_THRESHOLD_NS: i64 = 128_000_000;
+
+/// 客户端状态
+struct SntpClient {
+    /// 时间服务器
+    server: Option<Ipv4Addr>,
+    /// 查询间隔（毫秒）
+    poll_interval_ms: u64,
+    /// 下一次查询时间（毫秒）
+    next_poll_ms: u64,
+    /// 正在等待应答的请求的发送时间戳（NTP格式）与发送时刻
+    outstanding: Option<(u64, u64)>,
+    /// 最近一次测得的偏差（纳秒）
+    last_offset_ns: Option<i64>,
+}
+
+static CLIENT: SpinLockIrqSave<SntpClient> = SpinLockIrqSave::new(SntpClient {
+    server: None,
+    poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+    next_poll_ms: 0,
+    outstanding: None,
+    last_offset_ns: None,
+});
+
+/// Unix纳秒时间转换为NTP时间戳（32位秒 + 32位小数）
+fn to_ntp(unix_ns: i64) -> u64 {
+    let time = Timespec::from_nanos(unix_ns);
+    let seconds = (time.sec + NTP_UNIX_OFFSET) as u64;
+    let fraction = ((time.nsec as u64) << 32) / NSEC_PER_SEC;
+    (seconds << 32) | fraction
+}
+
+/// NTP时间戳转换为Unix纳秒时间
+fn from_ntp(ntp: u64) -> i64 {
+    let seconds = (ntp >> 32) as i64 - NTP_UNIX_OFFSET;
+    let nanos = ((ntp & 0xffff_ffff) * NSEC_PER_SEC) >> 32;
+    seconds * NSEC_PER_SEC as i64 + nanos as i64
+}
+
+/// 读取报文中的时间戳
+fn timestamp(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// 设置时间服务器，`None` 表示停止同步
+pub fn set_server(server: Option<Ipv4Addr>) {
+    let mut client = CLIENT.lock();
+    client.server = server;
+    client.outstanding = None;
+    client.next_poll_ms = time::monotonic_ns() / 1_000_000;
+}
+
+/// 当前时间服务器
+pub fn server() -> Option<Ipv4Addr> {
+    CLIENT.lock().server
+}
+
+/// 最近一次测得的时钟偏差（纳秒）
+pub fn last_offset_ns() -> Option<i64> {
+    CLIENT.lock().last_offset_ns
+}
+
+/// 发送查询请求
+fn send_request(server: Ipv4Addr, transmit: u64) -> Result<(), KernelError> {
+    let mut packet = PacketBuffer::new(SNTP_PACKET_LEN);
+    let body = packet.put(SNTP_PACKET_LEN);
+    body[0] = CLIENT_HEADER;
+    body[40..48].copy_from_slice(&transmit.to_be_bytes());
+    udp::output(SNTP_CLIENT_PORT, server, NTP_SERVER_PORT, packet)
+}
+
+/// 处理服务器应答
+pub fn handle_datagram(datagram: &UdpDatagram) {
+    // 尽早记录接收时刻（T4）
+    let destination_ns = time::realtime_ns();
+
+    let data = datagram.payload;
+    if datagram.source_port != NTP_SERVER_PORT || data.len() < SNTP_PACKET_LEN {
+        return;
+    }
+
+    let mut client = CLIENT.lock();
+    let (originate, _) = match client.outstanding {
+        Some(outstanding) => outstanding,
+        None => return,
+    };
+    if client.server != Some(datagram.source)
+        || data[0] & 0x07 != MODE_SERVER
+        || data[0] >> 6 == LEAP_UNSYNCHRONIZED
+        // 层级为0表示服务器拒绝服务（Kiss-o'-Death）
+        || data[1] == 0
+        // 应答必须回显本次请求的发送时间戳，防止伪造或迟到的应答
+        || timestamp(data, 24) != originate
+    {
+        return;
+    }
+
+    let receive = timestamp(data, 32);
+    let transmit = timestamp(data, 40);
+    if transmit == 0 {
+        return;
+    }
+    client.outstanding = None;
+
+    // 偏差 = ((T2 - T1) + (T3 - T4)) / 2
+    let offset = ((from_ntp(receive) - from_ntp(originate)) + (from_ntp(transmit) - destination_ns)) / 2;
+    client.last_offset_ns = Some(offset);
+    drop(client);
+
+    if offset.abs() < STEP_THRESHOLD_NS {
+        time::adjtime(offset);
+    } else {
+        let _ = time::settime(Timespec::from_nanos(time::realtime_ns() + offset));
+    }
+}
+
+/// 驱动SNTP定时器：到期时发送查询，并处理应答超时
+pub fn tick(now_ms: u64) {
+    let mut client = CLIENT.lock();
+    let server = match client.server {
+        Some(server) => server,
+        None => return,
+    };
+
+    if let Some((_, sent_ms)) = client.outstanding {
+        if now_ms.saturating_sub(sent_ms) < RESPONSE_TIMEOUT_MS {
+            return;
+        }
+        // 应答超时，放弃本次查询并按正常间隔重试
+        client.outstanding = None;
+    }
+    if now_ms < client.next_poll_ms {
+        return;
+    }
+
+    let transmit = to_ntp(time::realtime_ns());
+    client.next_poll_ms = now_ms + client.poll_interval_ms;
+    client.outstanding = Some((transmit, now_ms));
+    drop(client);
+
+    // 路由尚未就绪（如DHCP未完成）时，等待超时后重试
+    let _ = send_request(server, transmit);
+}
+
+/// `sntpd` 线程：每秒检查一次查询是否到期或超时
+fn sntpd_main() {
+    loop {
+        tick(time::monotonic_ns() / 1_000_000);
+        time::sleep_until(time::monotonic_ns() + SNTPD_INTERVAL_NS);
+    }
+}
+
+/// 初始化SNTP客户端
+pub fn sntp_init() -> Result<(), KernelError> {
+    udp::bind(SNTP_CLIENT_PORT, handle_datagram)?;
+    crate::sched::spawn("sntpd", sntpd_main);
+
+    procfs::register(
+        "sys/net/sntp/server",
+        Some(Box::new(|| match server() {
+            Some(server) => format!("{}\n", server),
+            None => String::from("none\n"),
+        })),
+        Some(Box::new(|data: &str| {
+            match data.trim() {
+                "" | "none" => set_server(None),
+                addr => set_server(Some(addr.parse()?)),
+            }
+            Ok(())
+        })),
+    )?;
+    procfs::register(
+        "sys/net/sntp/poll_interval",
+        Some(Box::new(|| format!("{}\n", CLIENT.lock().poll_interval_ms / 1000))),
+        Some(Box::new(|data: &str| {
+            let seconds: u64 = data.trim().parse().map_err(|_| KernelError::InvalidArgument)?;
+            let interval_ms = seconds.saturating_mul(1000);
+            if interval_ms < MIN_POLL_INTERVAL_MS {
+                return Err(KernelError::InvalidArgument);
+            }
+            CLIENT.lock().poll_interval_ms = interval_ms;
+            Ok(())
+        })),
+    )
+}
@@ -0,0 +1,163 @@
+//! UDP协议处理
+//!
+//! 本模块实现了UDP数据报的解析、构造以及按端口分发
+
+use super::ethernet::EthernetHeader;
+use super::ipv4::{self, Ipv4Header, PROTOCOL_UDP};
+use super::skb::PacketBuffer;
+use super::{route, Ipv4Addr, MacAddress, NetInterface};
+use crate::error::KernelError;
+use crate::sched::{self, capability::CAP_NET_BIND_SERVICE};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// UDP首部长度
+pub const UDP_HEADER_LEN: usize = 8;
+
+/// 小于此值的端口为特权端口，绑定需要 `CAP_NET_BIND_SERVICE`
+pub const PROT_SOCK: u16 = 1024;
+
+/// 接收到的UDP数据报
+pub struct UdpDatagram<'a> {
+    /// 接收接口
+    pub iface: &'a NetInterface,
+    /// 发送方MAC地址
+    pub source_mac: MacAddress,
+    /// 源地址
+    pub source: Ipv4Addr,
+    /// 目的地址
+    pub destination: Ipv4Addr,
+    /// 源端口
+    pub source_port: u16,
+    /// 目的端口
+    pub destination_port: u16,
+    /// 负载数据
+    pub payload: &'a [u8],
+}
+
+/// UDP端口处理函数
+pub type UdpHandler = fn(&UdpDatagram);
+
+/// 已绑定的端口表
+static BINDINGS: Mutex<Vec<(u16, UdpHandler)>> = Mutex::new(Vec::new());
+
+/// 绑定端口处理函数
+pub fn bind(port: u16, handler: UdpHandler) -> Result<(), KernelError> {
+    if port < PROT_SOCK && !sched::capable(CAP_NET_BIND_SERVICE) {
+        return Err(KernelError::PermissionDenied);
+    }
+    let mut bindings = BINDINGS.lock();
+    if bindings.iter().any(|(p, _)| *p == port) {
+        return Err(KernelError::ResourceBusy);
+    }
+    bindings.push((port, handler));
+    Ok(())
+}
+
+/// 解除端口绑定
+pub fn unbind(port: u16) {
+    BINDINGS.lock().retain(|(p, _)| *p != port);
+}
+
+/// 计算UDP校验和（含伪首部）
+fn udp_checksum(source: Ipv4Addr, destination: Ipv4Addr, segment: &[u8]) -> u16 {
+    let mut sum = ipv4::checksum_accumulate(0, &source.0);
+    sum = ipv4::checksum_accumulate(sum, &destination.0);
+    sum += PROTOCOL_UDP as u32;
+    sum += segment.len() as u32;
+    sum = ipv4::checksum_accumulate(sum, segment);
+    ipv4::checksum_finish(sum)
+}
+
+/// 处理接收到的UDP数据报
+pub fn receive(iface: &NetInterface, eth: &EthernetHeader, ip: &Ipv4Header, mut packet: PacketBuffer) {
+    let segment = packet.data();
+    if segment.len() < UDP_HEADER_LEN {
+        return;
+    }
+
+    let length = u16::from_be_bytes([segment[4], segment[5]]) as usize;
+    if length < UDP_HEADER_LEN || length > segment.len() {
+        return;
+    }
+
+    // 校验和为0表示发送方未计算
+    let wire_checksum = u16::from_be_bytes([segment[6], segment[7]]);
+    if wire_checksum != 0 && udp_checksum(ip.source, ip.destination, &segment[..length]) != 0 {
+        return;
+    }
+
+    let source_port = u16::from_be_bytes([segment[0], segment[1]]);
+    let destination_port = u16::from_be_bytes([segment[2], segment[3]]);
+    packet.trim(length);
+    packet.pull(UDP_HEADER_LEN);
+
+    let handler = BINDINGS
+        .lock()
+        .iter()
+        .find(|(port, _)| *port == destination_port)
+        .map(|(_, handler)| *handler);
+
+    if let Some(handler) = handler {
+        handler(&UdpDatagram {
+            iface,
+            source_mac: eth.source,
+            source: ip.source,
+            destination: ip.destination,
+            source_port,
+            destination_port,
+            payload: packet.data(),
+        });
+    }
+}
+
+/// 就地压入UDP首部并填写校验和
+fn push_header(
+    packet: &mut PacketBuffer,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: u16,
+    destination_port: u16,
+) {
+    let length = UDP_HEADER_LEN + packet.len();
+    let header = packet.push(UDP_HEADER_LEN);
+    header[0..2].copy_from_slice(&source_port.to_be_bytes());
+    header[2..4].copy_from_slice(&destination_port.to_be_bytes());
+    header[4..6].copy_from_slice(&(length as u16).to_be_bytes());
+    header[6..8].copy_from_slice(&[0, 0]);
+
+    let mut sum = udp_checksum(source, destination, packet.data());
+    if sum == 0 {
+        sum = 0xffff;
+    }
+    packet.data_mut()[6..8].copy_from_slice(&sum.to_be_bytes());
+}
+
+/// 就地压入UDP首部并发送
+pub fn send(
+    iface: &NetInterface,
+    next_hop: MacAddress,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: u16,
+    destination_port: u16,
+    mut packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    push_header(&mut packet, source, destination, source_port, destination_port);
+    ipv4::send(iface, next_hop, source, destination, PROTOCOL_UDP, packet)
+}
+
+/// 查找路由并发送UDP数据报，源地址使用出接口的地址
+pub fn output(
+    source_port: u16,
+    destination: Ipv4Addr,
+    destination_port: u16,
+    mut packet: PacketBuffer,
+) -> Result<(), KernelError> {
+    let source = route::lookup(destination)
+        .map(|route| route.iface.config().address)
+        .ok_or(KernelError::NetworkError)?;
+
+    push_header(&mut packet, source, destination, source_port, destination_port);
+    ipv4::output(Some(source), destination, PROTOCOL_UDP, packet)
+}
@@ -0,0 +1,125 @@
+//! 启动时的微基准测试
+//!
+//! 启动参数含 `bench` 时，内核在初始化完成后创建基准测试任务，结果写入内核日志并输出到控制台：
+//! - 上下文切换：两个内核任务互相让出处理器，每次切换的平均耗时
+//! - 系统调用：经调用表分发getpid的平均耗时（内核任务不能经ecall进入，不含陷入与返回）
+//! - 页错误：访问未映射地址、陷入后经异常表修复返回的平均耗时
+//! - 分配器：不同大小的堆分配与释放的吞吐量
+//!
+//! 每项结果占一行，以 `bench:` 开头，全部完成后输出 `bench: 完成`，便于集成测试收集与比较
+
+use crate::boot::cmdline;
+use crate::mm::uaccess::copy_from_kernel_nofault;
+use crate::sched;
+use crate::syscall::{self, SyscallArgs, SYS_GETPID};
+use crate::time;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 上下文切换的往返次数
+const SWITCH_ROUNDS: u64 = 10_000;
+/// 系统调用次数
+const SYSCALL_ROUNDS: u64 = 100_000;
+/// 页错误次数
+const FAULT_ROUNDS: u64 = 10_000;
+/// 每种大小的分配次数
+const ALLOC_ROUNDS: u64 = 20_000;
+/// 分配器测试的大小
+const ALLOC_SIZES: [usize; 4] = [16, 128, 1024, 4096];
+
+/// 页错误测试访问的地址，内核页表不映射第一页
+const UNMAPPED_ADDR: usize = 0;
+
+/// 上下文切换测试的对端任务是否应退出
+static PARTNER_EXIT: AtomicBool = AtomicBool::new(false);
+
+/// 运行 `f` 共 `rounds` 次，返回每次的平均纳秒数
+fn measure(rounds: u64, mut f: impl FnMut()) -> u64 {
+    let start = time::monotonic_ns();
+    for _ in 0..rounds {
+        f();
+    }
+    (time::monotonic_ns() - start) / rounds
+}
+
+/// 上下文切换测试的对端：不断让出处理器直到测试结束
+fn switch_partner() {
+    while !PARTNER_EXIT.load(Ordering::Acquire) {
+        sched::yield_now();
+    }
+}
+
+/// 上下文切换
+///
+/// 每轮让出一次，对端再让回来，共两次切换；运行队列中有其他就绪任务时结果偏大
+fn bench_context_switch() {
+    PARTNER_EXIT.store(false, Ordering::Release);
+    sched::spawn("bench-partner", switch_partner);
+    // 让对端先开始运行
+    sched::yield_now();
+    let per_round = measure(SWITCH_ROUNDS, sched::yield_now);
+    PARTNER_EXIT.store(true, Ordering::Release);
+    crate::log_info!("bench: 上下文切换 {} ns/次", per_round / 2);
+}
+
+/// 系统调用分发
+fn bench_syscall() {
+    let args = SyscallArgs {
+        nr: SYS_GETPID,
+        args: [0; 6],
+    };
+    let per_call = measure(SYSCALL_ROUNDS, || {
+        core::hint::black_box(syscall::dispatch(core::hint::black_box(&args)));
+    });
+    crate::log_info!("bench: 系统调用 {} ns/次", per_call);
+}
+
+/// 内核页错误的陷入与修复
+fn bench_page_fault() {
+    let mut byte = [0u8; 1];
+    if copy_from_kernel_nofault(&mut byte, UNMAPPED_ADDR).is_ok() {
+        crate::log_info!("bench: 页错误 跳过（地址0x{:x}已映射）", UNMAPPED_ADDR);
+        return;
+    }
+    let per_fault = measure(FAULT_ROUNDS, || {
+        let _ = copy_from_kernel_nofault(&mut byte, core::hint::black_box(UNMAPPED_ADDR));
+    });
+    crate::log_info!("bench: 页错误 {} ns/次", per_fault);
+}
+
+/// 分配器：每种大小先连续分配一批再全部释放
+fn bench_alloc() {
+    for size in ALLOC_SIZES {
+        let mut blocks: Vec<Box<[u8]>> = Vec::with_capacity(ALLOC_ROUNDS as usize);
+        let start = time::monotonic_ns();
+        for _ in 0..ALLOC_ROUNDS {
+            blocks.push(alloc::vec![0u8; size].into_boxed_slice());
+        }
+        drop(core::hint::black_box(blocks));
+        let elapsed = (time::monotonic_ns() - start).max(1);
+        crate::log_info!(
+            "bench: 分配器 {} 字节 {} ns/次 {} 次/秒",
+            size,
+            elapsed / ALLOC_ROUNDS,
+            ALLOC_ROUNDS * time::NSEC_PER_SEC / elapsed
+        );
+    }
+}
+
+/// 基准测试任务
+fn bench_main() {
+    crate::log_info!("bench: 开始");
+    bench_context_switch();
+    bench_syscall();
+    bench_page_fault();
+    bench_alloc();
+    crate::log_info!("bench: 完成");
+}
+
+/// 启动参数含 `bench` 时创建基准测试任务
+pub fn bench_init() {
+    if cmdline::has("bench") {
+        sched::spawn("bench", bench_main);
+    }
+}
@@ -0,0 +1,370 @@
+//! 性能计数
+//!
+//! 本模块在体系结构的硬件计数器之上实现按任务与按hart的事件计数，包括：
+//! - 事件：周期、指令、缓存访问与缺失、分支与分支预测失败、数据与指令TLB缺失
+//! - 计数器在每个hart上持续计数，调度切换与时钟节拍时把增量计入当前任务与当前hart
+//! - `PerfEventFile`：perf_event_open返回的文件，统计一个任务或一个hart的事件
+//! - /proc/perf：各事件在各hart上的累计值与每个任务的累计值
+//! - 时钟中断驱动的采样分析器（profile）
+//! - 启动时的微基准测试（bench）
+//!
+//! 正在其他hart上运行的任务的计数在那个hart下一次切换或时钟节拍时才更新，误差不超过一个节拍
+
+pub mod bench;
+pub mod profile;
+
+use crate::arch::{self, pmu};
+use crate::error::KernelError;
+use crate::fs::file::{File, FileStat};
+use crate::fs::procfs;
+use crate::sched::{self, Task, MAX_HARTS};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// 事件种类数
+pub const NR_HW_EVENTS: usize = 8;
+
+/// 硬件事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwEvent {
+    /// 处理器周期
+    Cycles,
+    /// 退休的指令
+    Instructions,
+    /// 缓存访问
+    CacheReferences,
+    /// 缓存缺失
+    CacheMisses,
+    /// 分支指令
+    BranchInstructions,
+    /// 分支预测失败
+    BranchMisses,
+    /// 数据TLB读缺失
+    DtlbMisses,
+    /// 指令TLB缺失
+    ItlbMisses,
+}
+
+impl HwEvent {
+    /// 所有事件，下标与取值一致
+    pub const ALL: [HwEvent; NR_HW_EVENTS] = [
+        HwEvent::Cycles,
+        HwEvent::Instructions,
+        HwEvent::CacheReferences,
+        HwEvent::CacheMisses,
+        HwEvent::BranchInstructions,
+        HwEvent::BranchMisses,
+        HwEvent::DtlbMisses,
+        HwEvent::ItlbMisses,
+    ];
+
+    /// 事件名（与Linux perf工具一致）
+    pub fn name(self) -> &'static str {
+        match self {
+            HwEvent::Cycles => "cycles",
+            HwEvent::Instructions => "instructions",
+            HwEvent::CacheReferences => "cache-references",
+            HwEvent::CacheMisses => "cache-misses",
+            HwEvent::BranchInstructions => "branches",
+            HwEvent::BranchMisses => "branch-misses",
+            HwEvent::DtlbMisses => "dTLB-load-misses",
+            HwEvent::ItlbMisses => "iTLB-load-misses",
+        }
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const COUNT_INIT: AtomicU64 = AtomicU64::new(0);
+
+/// 按事件累计的计数
+pub struct PerfCounts {
+    counts: [AtomicU64; NR_HW_EVENTS],
+}
+
+impl PerfCounts {
+    /// 全部为0的计数
+    pub const fn new() -> Self {
+        Self {
+            counts: [COUNT_INIT; NR_HW_EVENTS],
+        }
+    }
+
+    /// 事件的累计值
+    pub fn get(&self, event: HwEvent) -> u64 {
+        self.counts[event as usize].load(Ordering::Relaxed)
+    }
+
+    fn add(&self, deltas: &[u64; NR_HW_EVENTS]) {
+        for (count, &delta) in self.counts.iter().zip(deltas) {
+            count.fetch_add(delta, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for PerfCounts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// hart的计数状态
+struct HartPerf {
+    /// 是否已配置计数器
+    online: AtomicBool,
+    /// 上次计入时各计数器的值，只由所属hart在关中断时访问
+    last: [AtomicU64; NR_HW_EVENTS],
+    /// 本hart上的累计值
+    total: PerfCounts,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const HART_INIT: HartPerf = HartPerf {
+    online: AtomicBool::new(false),
+    last: [COUNT_INIT; NR_HW_EVENTS],
+    total: PerfCounts::new(),
+};
+
+static HARTS: [HartPerf; MAX_HARTS] = [HART_INIT; MAX_HARTS];
+
+/// 读取本hart的计数器，返回自上次调用以来的增量，须在关中断时调用
+fn sample() -> [u64; NR_HW_EVENTS] {
+    let hart = &HARTS[arch::hart_id()];
+    let mut deltas = [0; NR_HW_EVENTS];
+    for (event, delta) in HwEvent::ALL.into_iter().zip(deltas.iter_mut()) {
+        if let Some((value, mask)) = pmu::read_counter(event) {
+            let last = hart.last[event as usize].swap(value, Ordering::Relaxed);
+            *delta = value.wrapping_sub(last) & mask;
+        }
+    }
+    deltas
+}
+
+/// 当前hart上线时配置计数器，由调度器在hart开始调度前调用
+pub fn hart_online() {
+    let configured = pmu::pmu_hart_init();
+    // 丢弃配置之前的计数
+    sample();
+    HARTS[arch::hart_id()].online.store(true, Ordering::Release);
+    crate::log_debug!("hart {} 的性能计数器: {} 个事件", arch::hart_id(), configured);
+}
+
+/// 当前hart下线前释放计数器
+pub fn hart_offline() {
+    HARTS[arch::hart_id()].online.store(false, Ordering::Release);
+    pmu::pmu_hart_exit();
+}
+
+/// 把上次计入以来本hart的计数记到 `task`（本hart上正在运行的任务）名下，须在关中断时调用
+pub fn account(task: &Task) {
+    let hart = &HARTS[arch::hart_id()];
+    if !hart.online.load(Ordering::Relaxed) {
+        return;
+    }
+    let deltas = sample();
+    task.perf_counts().add(&deltas);
+    hart.total.add(&deltas);
+}
+
+/// 计入当前任务到此刻为止的计数
+fn account_current() {
+    let flags = arch::local_irq_save();
+    if let Some(task) = sched::current() {
+        account(&task);
+    }
+    arch::local_irq_restore(flags);
+}
+
+/// 当前hart是否支持统计该事件
+pub fn event_available(event: HwEvent) -> bool {
+    pmu::counter_available(event)
+}
+
+/// 计数对象
+pub enum PerfTarget {
+    /// 一个任务，无论运行在哪个hart上
+    Task(Weak<Task>),
+    /// 一个hart上的所有任务
+    Hart(usize),
+}
+
+/// 计数文件的状态
+struct EventState {
+    /// 是否在计数
+    enabled: bool,
+    /// 开始计数时对象的累计值
+    base: u64,
+    /// 此前各次计数的总和
+    count: u64,
+    /// 对象最后一次读到的累计值，任务退出后使用
+    last: u64,
+}
+
+/// perf_event_open返回的文件
+///
+/// 读取得到8字节的计数值，ioctl开启、关闭计数与清零
+pub struct PerfEventFile {
+    event: HwEvent,
+    target: PerfTarget,
+    state: Mutex<EventState>,
+}
+
+impl PerfEventFile {
+    /// 创建计数文件，`enabled` 为 `false` 时须通过 `enable` 开始计数
+    pub fn new(event: HwEvent, target: PerfTarget, enabled: bool) -> Self {
+        let file = Self {
+            event,
+            target,
+            state: Mutex::new(EventState {
+                enabled,
+                base: 0,
+                count: 0,
+                last: 0,
+            }),
+        };
+        let total = file.total();
+        let mut state = file.state.lock();
+        state.base = total;
+        state.last = total;
+        drop(state);
+        file
+    }
+
+    /// 对象到目前为止的累计值
+    fn total(&self) -> u64 {
+        match &self.target {
+            PerfTarget::Task(task) => {
+                let Some(task) = task.upgrade() else {
+                    return self.state.lock().last;
+                };
+                if sched::current().is_some_and(|current| Arc::ptr_eq(&current, &task)) {
+                    account_current();
+                }
+                task.perf_counts().get(self.event)
+            }
+            PerfTarget::Hart(hart) => {
+                if *hart == arch::hart_id() {
+                    account_current();
+                }
+                HARTS[*hart].total.get(self.event)
+            }
+        }
+    }
+
+    /// 当前计数值
+    pub fn value(&self) -> u64 {
+        let total = self.total();
+        let mut state = self.state.lock();
+        state.last = total;
+        if state.enabled {
+            state.count + total.wrapping_sub(state.base)
+        } else {
+            state.count
+        }
+    }
+
+    /// 开始计数
+    pub fn enable(&self) {
+        let total = self.total();
+        let mut state = self.state.lock();
+        if !state.enabled {
+            state.enabled = true;
+            state.base = total;
+        }
+    }
+
+    /// 停止计数，保留已有的计数值
+    pub fn disable(&self) {
+        let total = self.total();
+        let mut state = self.state.lock();
+        if state.enabled {
+            state.enabled = false;
+            state.count += total.wrapping_sub(state.base);
+        }
+    }
+
+    /// 计数值清零
+    pub fn reset(&self) {
+        let total = self.total();
+        let mut state = self.state.lock();
+        state.count = 0;
+        state.base = total;
+    }
+}
+
+impl File for PerfEventFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, KernelError> {
+        if buf.len() < 8 {
+            return Err(KernelError::InvalidArgument);
+        }
+        buf[..8].copy_from_slice(&self.value().to_le_bytes());
+        Ok(8)
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat::default()
+    }
+}
+
+/// 生成 /proc/perf 的内容
+fn proc_read_perf() -> String {
+    account_current();
+    let harts: Vec<usize> = (0..MAX_HARTS)
+        .filter(|&hart| HARTS[hart].online.load(Ordering::Acquire))
+        .collect();
+
+    let mut out = String::new();
+    let _ = write!(out, "{:<18} {:>16}", "event", "total");
+    for hart in &harts {
+        let _ = write!(out, " {:>16}", alloc::format!("hart{}", hart));
+    }
+    out.push('\n');
+    for event in HwEvent::ALL {
+        let total: u64 = (0..MAX_HARTS).map(|hart| HARTS[hart].total.get(event)).sum();
+        let _ = write!(out, "{:<18} {:>16}", event.name(), total);
+        for &hart in &harts {
+            let _ = write!(out, " {:>16}", HARTS[hart].total.get(event));
+        }
+        out.push('\n');
+    }
+
+    out.push('\n');
+    for task in sched::tasks() {
+        let _ = write!(out, "{:>5} {:<16}", task.id, task.name);
+        for event in HwEvent::ALL {
+            let _ = write!(out, " {}={}", event.name(), task.perf_counts().get(event));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// 注册 /proc/perf 与 /proc/profile
+pub fn perf_init() -> Result<(), KernelError> {
+    procfs::register("perf", Some(Box::new(proc_read_perf)), None)?;
+    profile::profile_init()
+}
+
+crate::kernel_test! {
+    fn cycles_count_current_task() {
+        if !event_available(HwEvent::Cycles) {
+            return;
+        }
+        let task = sched::current().expect("测试在任务中运行");
+        let file = PerfEventFile::new(HwEvent::Cycles, PerfTarget::Task(Arc::downgrade(&task)), true);
+        let mut sum = 0usize;
+        for i in 0..10_000 {
+            sum = core::hint::black_box(sum.wrapping_add(i));
+        }
+        assert!(file.value() > 0);
+        file.disable();
+        let stopped = file.value();
+        assert_eq!(file.value(), stopped);
+        file.reset();
+        assert_eq!(file.value(), 0);
+    }
+}
@@ -0,0 +1,199 @@
+//! 采样分析器
+//!
+//! 本模块在时钟中断中对被打断的程序计数器采样，用于找出占用处理器时间的代码，包括：
+//! - 每次时钟中断记录当前任务、被打断的pc以及被打断时处于用户态还是内核态
+//! - 样本保存在开始采样时分配的固定大小缓冲区中，写满后丢弃新的样本并计数
+//! - /proc/profile：写入 `start`、`stop`、`reset` 控制采样，读取按任务与位置汇总的直方图
+//!
+//! 内核态样本通过kallsyms归到所在函数，用户态样本按pc汇总
+
+use crate::arch::TrapFrame;
+use crate::debug::kallsyms;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sched;
+use crate::sync::SpinLockIrqSave;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// 缓冲区容量（样本数）
+const MAX_SAMPLES: usize = 16384;
+
+/// 直方图最多显示的位置数
+const MAX_HOT_SPOTS: usize = 64;
+
+/// 一个样本
+#[derive(Clone, Copy)]
+struct Sample {
+    /// 被打断的任务
+    tid: usize,
+    /// 被打断的pc
+    pc: usize,
+    /// 是否打断了用户态
+    user: bool,
+}
+
+/// 是否在采样
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// 因缓冲区已满或正在读取而丢弃的样本数
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// 样本缓冲区，容量在开始采样时预留，中断中不分配内存
+static SAMPLES: SpinLockIrqSave<Vec<Sample>> = SpinLockIrqSave::new(Vec::new());
+
+/// 时钟中断中采样，由陷入处理调用
+pub fn profile_tick(frame: &TrapFrame) {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(task) = sched::current() else {
+        return;
+    };
+    let sample = Sample {
+        tid: task.id.0,
+        pc: frame.sepc,
+        user: frame.from_user(),
+    };
+    // 其他hart正在读取或清空缓冲区时不等待
+    match SAMPLES.try_lock() {
+        Some(mut samples) if samples.len() < samples.capacity() => samples.push(sample),
+        _ => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 开始采样，首次开始时分配缓冲区
+fn start() {
+    if SAMPLES.lock().capacity() == 0 {
+        // 不在关中断时分配
+        let buffer = Vec::with_capacity(MAX_SAMPLES);
+        let mut samples = SAMPLES.lock();
+        if samples.capacity() == 0 {
+            *samples = buffer;
+        }
+    }
+    RUNNING.store(true, Ordering::Relaxed);
+}
+
+/// 清空样本，保留缓冲区
+fn reset() {
+    SAMPLES.lock().clear();
+    DROPPED.store(0, Ordering::Relaxed);
+}
+
+/// 样本的位置：内核态为所在函数的起始地址，用户态为pc本身
+fn location(sample: &Sample) -> usize {
+    if sample.user {
+        return sample.pc;
+    }
+    kallsyms::lookup(sample.pc).map_or(sample.pc, |symbol| symbol.addr)
+}
+
+/// 任务名，任务已退出时为 `?`
+fn task_name(tid: usize) -> String {
+    sched::find_task(sched::TaskId(tid)).map_or_else(|| String::from("?"), |task| task.name.clone())
+}
+
+/// 生成 /proc/profile 的内容
+fn proc_read_profile() -> String {
+    let samples: Vec<Sample> = SAMPLES.lock().clone();
+    let total = samples.len();
+
+    // 任务编号 -> (用户态样本数, 内核态样本数)
+    let mut per_task: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    // (任务编号, 是否用户态, 位置) -> 样本数
+    let mut per_location: BTreeMap<(usize, bool, usize), usize> = BTreeMap::new();
+    for sample in &samples {
+        let counts = per_task.entry(sample.tid).or_default();
+        if sample.user {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+        }
+        *per_location
+            .entry((sample.tid, sample.user, location(sample)))
+            .or_default() += 1;
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# state: {}  samples: {}  dropped: {}",
+        if RUNNING.load(Ordering::Relaxed) {
+            "running"
+        } else {
+            "stopped"
+        },
+        total,
+        DROPPED.load(Ordering::Relaxed)
+    );
+    if total == 0 {
+        return out;
+    }
+
+    let _ = writeln!(out, "#   tid name                 total     user   kernel");
+    let mut tasks: Vec<(usize, (usize, usize))> = per_task.into_iter().collect();
+    tasks.sort_by_key(|&(_, (user, kernel))| core::cmp::Reverse(user + kernel));
+    for (tid, (user, kernel)) in tasks {
+        let _ = writeln!(
+            out,
+            "{:>7} {:<16} {:>9} {:>8} {:>8}",
+            tid,
+            task_name(tid),
+            user + kernel,
+            user,
+            kernel
+        );
+    }
+
+    let _ = writeln!(out, "#\n#  samples      %     tid mode   location");
+    let mut spots: Vec<((usize, bool, usize), usize)> = per_location.into_iter().collect();
+    spots.sort_by_key(|&(_, count)| core::cmp::Reverse(count));
+    for ((tid, user, addr), count) in spots.into_iter().take(MAX_HOT_SPOTS) {
+        let permille = count * 1000 / total;
+        let _ = write!(
+            out,
+            "{:>10} {:>3}.{}% {:>7} {:<6} ",
+            count,
+            permille / 10,
+            permille % 10,
+            tid,
+            if user { "user" } else { "kernel" }
+        );
+        match kallsyms::lookup(addr).filter(|_| !user) {
+            Some(symbol) => {
+                let _ = writeln!(out, "{}", symbol.name);
+            }
+            None => {
+                let _ = writeln!(out, "0x{:x}", addr);
+            }
+        }
+    }
+    out
+}
+
+/// 处理写入 /proc/profile 的命令
+fn proc_write_profile(data: &str) -> Result<(), KernelError> {
+    match data.trim() {
+        "start" => start(),
+        "stop" => RUNNING.store(false, Ordering::Relaxed),
+        "reset" => reset(),
+        _ => return Err(KernelError::InvalidArgument),
+    }
+    Ok(())
+}
+
+/// 注册 /proc/profile
+pub fn profile_init() -> Result<(), KernelError> {
+    procfs::register(
+        "profile",
+        Some(Box::new(proc_read_profile)),
+        Some(Box::new(proc_write_profile)),
+    )
+}
@@ -0,0 +1,86 @@
+//! 电源管理
+//!
+//! 本模块实现了内核的关机、重启与停机接口，供 `reboot` 系统调用与内核其他部分使用：
+//! - 写回所有打开文件缓存的数据
+//! - 关闭本hart的中断并停止其他hart
+//! - 通过SBI SRST扩展请求固件关机或重启，固件不支持时停止所有核心
+//! - 或者跳转到kexec加载的内核，不经过固件重启
+//!
+//! 系统睡眠（挂起到空闲）见 `suspend` 子模块
+
+pub mod suspend;
+
+use crate::arch;
+use crate::fs;
+use crate::kexec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 关机方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    /// 关闭电源
+    PowerOff,
+    /// 重启
+    Restart,
+    /// 停止所有核心但不关闭电源
+    Halt,
+    /// 启动kexec加载的内核
+    Kexec,
+}
+
+impl PowerAction {
+    fn description(self) -> &'static str {
+        match self {
+            PowerAction::PowerOff => "关机",
+            PowerAction::Restart => "重启",
+            PowerAction::Halt => "停机",
+            PowerAction::Kexec => "通过kexec重启",
+        }
+    }
+}
+
+/// 是否已经开始关机，之后的请求直接停在当前hart上
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// 执行关机、重启或停机，不返回
+pub fn shutdown(action: PowerAction) -> ! {
+    if SHUTTING_DOWN.swap(true, Ordering::AcqRel) {
+        // 其他执行流已在关机，等待它停止本hart
+        arch::local_irq_disable();
+        loop {
+            arch::wait_for_interrupt();
+        }
+    }
+
+    crate::log_info!("系统正在{}", action.description());
+    let failed = fs::sync_all();
+    if failed > 0 {
+        crate::log_warn!("{}个文件写回失败", failed);
+    }
+
+    arch::local_irq_disable();
+    #[cfg(feature = "smp")]
+    arch::halt_other_cores();
+
+    match action {
+        PowerAction::PowerOff => arch::machine_power_off(),
+        PowerAction::Restart => arch::machine_restart(),
+        PowerAction::Halt => arch::halt_all_cores(),
+        PowerAction::Kexec => kexec::machine_kexec(),
+    }
+}
+
+/// 关闭电源
+pub fn kernel_power_off() -> ! {
+    shutdown(PowerAction::PowerOff)
+}
+
+/// 重启
+pub fn kernel_restart() -> ! {
+    shutdown(PowerAction::Restart)
+}
+
+/// 停机
+pub fn kernel_halt() -> ! {
+    shutdown(PowerAction::Halt)
+}
@@ -0,0 +1,203 @@
+//! 挂起到空闲（suspend-to-idle）
+//!
+//! 本模块实现了系统睡眠状态 `freeze`，包括：
+//! - 设备电源管理回调：驱动注册 `DevicePm`，挂起时按注册的逆序调用 `suspend`，
+//!   恢复时按注册顺序调用 `resume`；某个设备挂起失败时恢复已挂起的设备并放弃挂起
+//! - 唤醒中断：驱动用 `enable_irq_wake` 声明哪些中断可以唤醒系统
+//! - 挂起期间停止除当前hart外的所有hart（`smp` 特性），屏蔽时钟中断，
+//!   当前hart在wfi中等待，直到收到允许唤醒的中断
+//! - /sys/power/state：读取支持的睡眠状态，写入 `freeze` 进入睡眠
+
+use crate::arch;
+use crate::error::KernelError;
+use crate::fs::{self, kernfs};
+use crate::sync::{Mutex, SpinLock, SpinLockIrqSave};
+use crate::time;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 设备的电源管理回调
+pub trait DevicePm: Send + Sync {
+    /// 设备名
+    fn name(&self) -> &str;
+
+    /// 系统睡眠前停止设备：完成进行中的传输，保存需要恢复的状态
+    fn suspend(&self) -> Result<(), KernelError>;
+
+    /// 系统唤醒后恢复设备
+    fn resume(&self);
+}
+
+/// 最多注册的设备数
+const MAX_PM_DEVICES: usize = 32;
+
+/// 已注册的设备，按注册顺序排列
+///
+/// 使用固定大小的表，早期串口等在堆分配器就绪前初始化的设备也能注册
+static DEVICES: SpinLock<[Option<&'static dyn DevicePm>; MAX_PM_DEVICES]> = SpinLock::new([None; MAX_PM_DEVICES]);
+
+/// 允许唤醒系统的中断
+static WAKE_IRQS: SpinLockIrqSave<BTreeSet<usize>> = SpinLockIrqSave::new(BTreeSet::new());
+
+/// 是否处于睡眠状态
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+/// 睡眠期间是否收到了唤醒中断
+static WAKEUP_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// 串行化睡眠请求
+static SUSPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// 注册设备的电源管理回调
+pub fn register_device(device: &'static dyn DevicePm) -> Result<(), KernelError> {
+    let mut devices = DEVICES.lock();
+    if devices.iter().flatten().any(|registered| registered.name() == device.name()) {
+        return Err(KernelError::ResourceBusy);
+    }
+    let slot = devices.iter_mut().find(|slot| slot.is_none()).ok_or(KernelError::OutOfMemory)?;
+    *slot = Some(device);
+    Ok(())
+}
+
+/// 注销设备的电源管理回调
+pub fn unregister_device(name: &str) {
+    let mut devices = DEVICES.lock();
+    if let Some(index) = devices.iter().position(|slot| slot.map_or(false, |device| device.name() == name)) {
+        // 保持注册顺序
+        devices[index..].rotate_left(1);
+        devices[MAX_PM_DEVICES - 1] = None;
+    }
+}
+
+/// 允许中断 `irq` 唤醒系统
+pub fn enable_irq_wake(irq: usize) {
+    WAKE_IRQS.lock().insert(irq);
+}
+
+/// 禁止中断 `irq` 唤醒系统
+pub fn disable_irq_wake(irq: usize) {
+    WAKE_IRQS.lock().remove(&irq);
+}
+
+/// 外部中断到达时调用（中断上下文），睡眠期间允许唤醒的中断结束睡眠
+pub fn pm_wakeup_irq(irq: usize) {
+    if SUSPENDED.load(Ordering::Acquire) && WAKE_IRQS.lock().contains(&irq) {
+        WAKEUP_PENDING.store(true, Ordering::Release);
+    }
+}
+
+/// 恢复已挂起的设备（挂起顺序的逆序）
+fn resume_devices(suspended: &[&'static dyn DevicePm]) {
+    for device in suspended.iter().rev() {
+        device.resume();
+    }
+}
+
+/// 在当前hart上等待唤醒中断
+fn enter_idle() {
+    let flags = arch::local_irq_save();
+    arch::disable_timer_interrupt();
+    WAKEUP_PENDING.store(false, Ordering::Relaxed);
+    SUSPENDED.store(true, Ordering::Release);
+
+    while !WAKEUP_PENDING.load(Ordering::Acquire) {
+        // 关中断时wfi仍会被挂起的中断唤醒，打开中断后由陷入处理判断是否为唤醒中断
+        arch::wait_for_interrupt();
+        arch::local_irq_enable();
+        arch::local_irq_disable();
+    }
+
+    SUSPENDED.store(false, Ordering::Release);
+    arch::enable_timer_interrupt();
+    arch::local_irq_restore(flags);
+    // 补上睡眠期间的节拍并执行到期的定时器
+    time::timer::run_timers();
+}
+
+/// 进入挂起到空闲状态，收到唤醒中断后返回
+///
+/// 没有允许唤醒的中断时返回 `InvalidArgument`，系统将无法醒来
+pub fn pm_suspend() -> Result<(), KernelError> {
+    let _guard = SUSPEND_LOCK.lock();
+    if WAKE_IRQS.lock().is_empty() {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    crate::log_info!("系统进入睡眠");
+    fs::sync_all();
+
+    // 按注册的逆序挂起，子设备先于其所在的总线
+    let devices: Vec<&'static dyn DevicePm> = DEVICES.lock().iter().flatten().rev().copied().collect();
+    let mut suspended = Vec::with_capacity(devices.len());
+    for device in devices {
+        if let Err(err) = device.suspend() {
+            crate::log_error!("设备 {} 挂起失败: {:?}", device.name(), err);
+            resume_devices(&suspended);
+            return Err(err);
+        }
+        suspended.push(device);
+    }
+
+    #[cfg(feature = "smp")]
+    let parked = match park_other_harts() {
+        Ok(parked) => parked,
+        Err(err) => {
+            resume_devices(&suspended);
+            return Err(err);
+        }
+    };
+
+    enter_idle();
+
+    #[cfg(feature = "smp")]
+    unpark_harts(&parked);
+    resume_devices(&suspended);
+    crate::log_info!("系统已唤醒");
+    Ok(())
+}
+
+/// 停止除当前hart外的所有在线hart，返回被停止的hart
+///
+/// 等待其他hart下线时当前任务可能被调度到别的hart上，因此每次重新确定当前hart
+#[cfg(feature = "smp")]
+fn park_other_harts() -> Result<Vec<usize>, KernelError> {
+    use crate::sched::{hotplug, MAX_HARTS};
+
+    let mut parked = Vec::new();
+    loop {
+        let this = arch::hart_id();
+        let Some(hart) = (0..MAX_HARTS).find(|&hart| hart != this && hotplug::is_online(hart)) else {
+            return Ok(parked);
+        };
+        if let Err(err) = hotplug::hart_offline(hart) {
+            unpark_harts(&parked);
+            return Err(err);
+        }
+        parked.push(hart);
+    }
+}
+
+/// 重新启动睡眠前停止的hart
+#[cfg(feature = "smp")]
+fn unpark_harts(parked: &[usize]) {
+    for &hart in parked {
+        if let Err(err) = crate::sched::hotplug::hart_online(hart) {
+            crate::log_error!("hart {} 唤醒后上线失败: {:?}", hart, err);
+        }
+    }
+}
+
+/// 注册 /sys/power/state
+pub fn suspend_init() -> Result<(), KernelError> {
+    kernfs::register(
+        "/sys/power/state",
+        Some(Box::new(|| String::from("freeze\n"))),
+        Some(Box::new(|data| match data.trim() {
+            "freeze" => pm_suspend(),
+            _ => Err(KernelError::InvalidArgument),
+        })),
+    )
+}
@@ -0,0 +1,269 @@
+//! 内核随机数生成器
+//!
+//! 本模块实现了基于ChaCha20的密码学安全随机数生成器，包括：
+//! - 熵池：收集中断时间抖动、计时器计数、设备树 `/chosen/rng-seed` 与硬件随机数源（virtio-rng）
+//! - 输出：以ChaCha20密钥流生成随机数，每次请求后用密钥流覆盖密钥（快速密钥擦除），
+//!   之前的输出无法由当前状态推出
+//! - 累计的熵达到256位后生成器完成初始化；此后新的熵在重新播种时混入密钥
+//! - /dev/random、/dev/urandom 与 `getrandom` 系统调用的数据来源
+//!
+//! 熵的收集可以发生在中断上下文中，状态由关中断自旋锁保护
+
+use crate::boot::fdt;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::sync::{SpinLockIrqSave, WaitQueue};
+use crate::time;
+use alloc::boxed::Box;
+use alloc::format;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 完成初始化所需的熵（位）
+const INIT_ENTROPY_BITS: usize = 256;
+
+/// 每多少个中断样本计1位熵
+const INTERRUPT_SAMPLES_PER_BIT: usize = 8;
+
+/// 初始化后两次重新播种的最短间隔（纳秒）
+const RESEED_INTERVAL_NS: u64 = 60 * time::NSEC_PER_SEC;
+
+/// ChaCha20常量 "expand 32-byte k"
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// ChaCha20的四分之一轮
+#[inline(always)]
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// 生成一个64字节的ChaCha20密钥流块
+fn chacha20_block(key: &[u32; 8], counter: u64, nonce: [u32; 2]) -> [u32; 16] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    input[14] = nonce[0];
+    input[15] = nonce[1];
+
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    for (word, initial) in state.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*initial);
+    }
+    state
+}
+
+/// 随机数生成器状态
+struct RandomState {
+    /// 收集输入的熵池
+    pool: [u32; 16],
+    /// 下一个写入熵池的位置
+    pool_index: usize,
+    /// 熵池中累计的熵（位）
+    pool_entropy: usize,
+    /// 尚未计入熵的中断样本数
+    interrupt_samples: usize,
+    /// 上一个中断样本的时间，用于计算抖动
+    last_interrupt: u64,
+    /// 输出使用的ChaCha20密钥
+    key: [u32; 8],
+    /// 生成次数，作为nonce保证同一密钥下的输出不重复
+    generation: u64,
+    /// 上次重新播种的时间
+    last_reseed: u64,
+}
+
+static STATE: SpinLockIrqSave<RandomState> = SpinLockIrqSave::new(RandomState {
+    pool: [0; 16],
+    pool_index: 0,
+    pool_entropy: 0,
+    interrupt_samples: 0,
+    last_interrupt: 0,
+    key: [0; 8],
+    generation: 0,
+    last_reseed: 0,
+});
+
+/// 生成器是否已完成初始化
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// 等待初始化完成的读者
+static INIT_WAIT: WaitQueue = WaitQueue::new();
+
+impl RandomState {
+    /// 把一个字混入熵池
+    fn mix_word(&mut self, word: u32) {
+        let i = self.pool_index;
+        let prev = self.pool[(i + 15) % 16];
+        self.pool[i] = (self.pool[i].rotate_left(7) ^ word).wrapping_add(prev.rotate_left(13));
+        self.pool_index = (i + 1) % 16;
+    }
+
+    /// 把任意字节混入熵池
+    fn mix_bytes(&mut self, data: &[u8]) {
+        for chunk in data.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.mix_word(u32::from_le_bytes(word));
+        }
+    }
+
+    /// 把熵池压缩进密钥：新密钥 = ChaCha20(旧密钥 ⊕ 熵池前半, nonce = 熵池后半)
+    fn reseed(&mut self, now: u64) {
+        let mut key = self.key;
+        for (word, pool) in key.iter_mut().zip(self.pool.iter()) {
+            *word ^= *pool;
+        }
+        let tail = self.pool[8..].iter().fold(0u64, |acc, &word| acc.rotate_left(17) ^ word as u64);
+        let block = chacha20_block(&key, tail, [self.pool[14], self.pool[15]]);
+        self.key.copy_from_slice(&block[..8]);
+        self.pool = [0; 16];
+        self.pool_index = 0;
+        self.pool_entropy = 0;
+        self.last_reseed = now;
+    }
+
+    /// 记入熵，达到阈值时重新播种；返回是否在本次完成初始化
+    fn credit(&mut self, bits: usize) -> bool {
+        self.pool_entropy = self.pool_entropy.saturating_add(bits);
+        if self.pool_entropy < INIT_ENTROPY_BITS {
+            return false;
+        }
+        let now = time::monotonic_ns();
+        let initialized = INITIALIZED.load(Ordering::Acquire);
+        if initialized && now.saturating_sub(self.last_reseed) < RESEED_INTERVAL_NS {
+            return false;
+        }
+        self.reseed(now);
+        if initialized {
+            return false;
+        }
+        INITIALIZED.store(true, Ordering::Release);
+        true
+    }
+
+    /// 生成随机字节，完成后用新的密钥流覆盖密钥
+    fn fill(&mut self, buf: &mut [u8]) {
+        self.generation = self.generation.wrapping_add(1);
+        let nonce = [self.generation as u32, (self.generation >> 32) as u32];
+
+        // 第0块用于替换密钥，之后的块作为输出
+        let next_key = chacha20_block(&self.key, 0, nonce);
+        for (index, chunk) in buf.chunks_mut(64).enumerate() {
+            let block = chacha20_block(&self.key, index as u64 + 1, nonce);
+            let mut bytes = [0u8; 64];
+            for (out, word) in bytes.chunks_exact_mut(4).zip(block.iter()) {
+                out.copy_from_slice(&word.to_le_bytes());
+            }
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        self.key.copy_from_slice(&next_key[..8]);
+    }
+}
+
+/// 混入数据并记入 `entropy_bits` 位熵
+fn add_entropy(data: &[u8], entropy_bits: usize) {
+    let became_ready = {
+        let mut state = STATE.lock();
+        state.mix_bytes(data);
+        state.mix_word(time::read_cycles() as u32);
+        state.credit(entropy_bits)
+    };
+    if became_ready {
+        INIT_WAIT.wake_all();
+    }
+}
+
+/// 记录一次中断的时间抖动，由陷入处理调用
+pub fn add_interrupt_randomness(irq: usize) {
+    let now = time::read_cycles();
+    let became_ready = {
+        let mut state = STATE.lock();
+        let delta = now.wrapping_sub(state.last_interrupt);
+        state.last_interrupt = now;
+        state.mix_word(now as u32 ^ irq as u32);
+        state.mix_word((delta as u32).rotate_left(16) ^ (delta >> 32) as u32);
+
+        state.interrupt_samples += 1;
+        if state.interrupt_samples < INTERRUPT_SAMPLES_PER_BIT {
+            false
+        } else {
+            state.interrupt_samples = 0;
+            state.credit(1)
+        }
+    };
+    if became_ready {
+        INIT_WAIT.wake_all();
+    }
+}
+
+/// 混入硬件随机数源（如virtio-rng）的输出，`entropy_bits` 为驱动估计的熵
+pub fn add_hwrng_randomness(data: &[u8], entropy_bits: usize) {
+    add_entropy(data, entropy_bits.min(data.len() * 8));
+}
+
+/// 混入不计熵的数据（如写入 /dev/random 的数据、设备序列号）
+pub fn add_device_randomness(data: &[u8]) {
+    add_entropy(data, 0);
+}
+
+/// 生成器是否已完成初始化
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Acquire)
+}
+
+/// 等待生成器完成初始化
+pub fn wait_for_initialized() {
+    INIT_WAIT.wait_until(is_initialized);
+}
+
+/// 生成随机字节，不等待初始化
+pub fn get_random_bytes(buf: &mut [u8]) {
+    STATE.lock().fill(buf);
+}
+
+/// 生成一个随机的64位整数
+pub fn get_random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    get_random_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// 随机数生成器初始化：混入计时器计数与设备树提供的种子
+pub fn random_init() -> Result<(), KernelError> {
+    add_device_randomness(&time::read_cycles().to_le_bytes());
+
+    // QEMU等引导程序在 /chosen/rng-seed 中提供由宿主机生成的随机种子
+    if let Some(seed) = fdt::fdt().and_then(|fdt| fdt.property("/chosen", "rng-seed")) {
+        add_hwrng_randomness(seed, seed.len() * 8);
+    }
+
+    procfs::register(
+        "sys/kernel/random/entropy_avail",
+        Some(Box::new(|| format!("{}\n", STATE.lock().pool_entropy))),
+        None,
+    )?;
+
+    crate::log_info!(
+        "随机数生成器: {}",
+        if is_initialized() { "已初始化" } else { "等待熵" }
+    );
+    Ok(())
+}
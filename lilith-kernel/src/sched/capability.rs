@@ -0,0 +1,201 @@
+//! 能力（capability）
+//!
+//! 把超级用户的特权拆分为互相独立的能力，特权操作只检查所需的一项，包括：
+//! - 能力编号与Linux一致，如 `CAP_SYS_ADMIN`、`CAP_NET_BIND_SERVICE`、`CAP_SYS_BOOT`
+//! - 每个进程持有有效集、许可集与可继承集，特权检查只看有效集
+//! - 子进程继承父进程的能力，第一个用户进程拥有全部能力
+//! - 内核任务不属于任何进程，总是拥有全部能力
+//!
+//! 进程只能通过capset缩小自己的能力，不能取得许可集之外的能力
+
+/// 一项能力
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability(pub u32);
+
+pub const CAP_CHOWN: Capability = Capability(0);
+pub const CAP_DAC_OVERRIDE: Capability = Capability(1);
+pub const CAP_DAC_READ_SEARCH: Capability = Capability(2);
+pub const CAP_FOWNER: Capability = Capability(3);
+pub const CAP_FSETID: Capability = Capability(4);
+pub const CAP_KILL: Capability = Capability(5);
+pub const CAP_SETGID: Capability = Capability(6);
+pub const CAP_SETUID: Capability = Capability(7);
+pub const CAP_SETPCAP: Capability = Capability(8);
+pub const CAP_LINUX_IMMUTABLE: Capability = Capability(9);
+pub const CAP_NET_BIND_SERVICE: Capability = Capability(10);
+pub const CAP_NET_BROADCAST: Capability = Capability(11);
+pub const CAP_NET_ADMIN: Capability = Capability(12);
+pub const CAP_NET_RAW: Capability = Capability(13);
+pub const CAP_IPC_LOCK: Capability = Capability(14);
+pub const CAP_IPC_OWNER: Capability = Capability(15);
+pub const CAP_SYS_MODULE: Capability = Capability(16);
+pub const CAP_SYS_RAWIO: Capability = Capability(17);
+pub const CAP_SYS_CHROOT: Capability = Capability(18);
+pub const CAP_SYS_PTRACE: Capability = Capability(19);
+pub const CAP_SYS_PACCT: Capability = Capability(20);
+pub const CAP_SYS_ADMIN: Capability = Capability(21);
+pub const CAP_SYS_BOOT: Capability = Capability(22);
+pub const CAP_SYS_NICE: Capability = Capability(23);
+pub const CAP_SYS_RESOURCE: Capability = Capability(24);
+pub const CAP_SYS_TIME: Capability = Capability(25);
+pub const CAP_SYS_TTY_CONFIG: Capability = Capability(26);
+pub const CAP_MKNOD: Capability = Capability(27);
+pub const CAP_LEASE: Capability = Capability(28);
+pub const CAP_AUDIT_WRITE: Capability = Capability(29);
+pub const CAP_AUDIT_CONTROL: Capability = Capability(30);
+pub const CAP_SETFCAP: Capability = Capability(31);
+pub const CAP_MAC_OVERRIDE: Capability = Capability(32);
+pub const CAP_MAC_ADMIN: Capability = Capability(33);
+pub const CAP_SYSLOG: Capability = Capability(34);
+pub const CAP_WAKE_ALARM: Capability = Capability(35);
+pub const CAP_BLOCK_SUSPEND: Capability = Capability(36);
+pub const CAP_AUDIT_READ: Capability = Capability(37);
+pub const CAP_PERFMON: Capability = Capability(38);
+pub const CAP_BPF: Capability = Capability(39);
+pub const CAP_CHECKPOINT_RESTORE: Capability = Capability(40);
+
+/// 编号最大的能力
+pub const CAP_LAST_CAP: Capability = CAP_CHECKPOINT_RESTORE;
+
+/// 能力名，下标为能力编号
+const NAMES: [&str; CAP_LAST_CAP.0 as usize + 1] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+impl Capability {
+    /// 能力名（与libcap一致），未知的编号返回 `None`
+    pub fn name(self) -> Option<&'static str> {
+        NAMES.get(self.0 as usize).copied()
+    }
+}
+
+/// 能力集合，第n位表示编号为n的能力
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapSet(pub u64);
+
+impl CapSet {
+    /// 空集合
+    pub const EMPTY: CapSet = CapSet(0);
+    /// 所有已知的能力
+    pub const FULL: CapSet = CapSet((1 << (CAP_LAST_CAP.0 + 1)) - 1);
+
+    /// 是否包含能力
+    pub fn contains(self, cap: Capability) -> bool {
+        cap.0 < 64 && self.0 & (1 << cap.0) != 0
+    }
+
+    /// 是否是 `other` 的子集
+    pub fn is_subset(self, other: CapSet) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    /// 并集
+    pub fn union(self, other: CapSet) -> CapSet {
+        CapSet(self.0 | other.0)
+    }
+}
+
+/// 进程的能力集合
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 有效集：特权检查时使用
+    pub effective: CapSet,
+    /// 许可集：有效集与可继承集的上限
+    pub permitted: CapSet,
+    /// 可继承集：exec后可以保留的能力
+    pub inheritable: CapSet,
+}
+
+impl Capabilities {
+    /// 拥有全部能力（第一个用户进程）
+    pub const FULL: Capabilities = Capabilities {
+        effective: CapSet::FULL,
+        permitted: CapSet::FULL,
+        inheritable: CapSet::EMPTY,
+    };
+
+    /// 检查从 `self` 改为 `new` 是否只缩小了能力
+    ///
+    /// 许可集不能扩大，有效集不能超出新的许可集，可继承集只能加入许可集中的能力
+    /// （有 `CAP_SETPCAP` 时不受此限制）
+    pub fn can_change_to(&self, new: &Capabilities) -> bool {
+        let inheritable_limit = if self.effective.contains(CAP_SETPCAP) {
+            CapSet::FULL
+        } else {
+            self.inheritable.union(self.permitted)
+        };
+        new.permitted.is_subset(self.permitted)
+            && new.effective.is_subset(new.permitted)
+            && new.inheritable.is_subset(inheritable_limit)
+    }
+}
+
+/// 当前执行流是否拥有能力 `cap`，没有时记录审计事件
+///
+/// 内核任务不属于任何进程，总是拥有全部能力
+pub fn capable(cap: Capability) -> bool {
+    let allowed = match super::current_process() {
+        Some(process) => process.capabilities().effective.contains(cap),
+        None => true,
+    };
+    if !allowed {
+        crate::audit::log(crate::audit::AuditEvent::Capability(cap));
+    }
+    allowed
+}
+
+crate::kernel_test! {
+    fn capset_can_only_drop() {
+        let full = Capabilities::FULL;
+        let mut reduced = full;
+        reduced.effective = CapSet(full.effective.0 & !(1 << CAP_SYS_BOOT.0));
+        assert!(full.can_change_to(&reduced));
+        assert!(!reduced.effective.contains(CAP_SYS_BOOT));
+
+        let mut dropped = reduced;
+        dropped.permitted = reduced.effective;
+        assert!(reduced.can_change_to(&dropped));
+        // 许可集中去掉的能力不能再取回
+        assert!(!dropped.can_change_to(&full));
+    }
+}
@@ -0,0 +1,179 @@
+//! 进程的用户身份
+//!
+//! 本模块实现了进程的用户与组标识，语义与Linux一致，包括：
+//! - 实际、有效与保存的用户号/组号，以及附加组
+//! - 身份改变时按Linux的规则调整能力：有效用户号离开0时清空有效集，
+//!   三个用户号都离开0时清空许可集，有效用户号变回0时恢复有效集
+//! - 加载程序时按文件的setuid/setgid位切换有效身份
+//!
+//! 第一个用户进程以root身份运行，子进程继承父进程的身份
+
+use super::capability::{CapSet, Capabilities};
+use crate::fs::file::{FileStat, S_ISGID, S_ISUID};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// 用户号
+pub type Uid = u32;
+/// 组号
+pub type Gid = u32;
+
+/// root的用户号与组号
+pub const ROOT_UID: Uid = 0;
+pub const ROOT_GID: Gid = 0;
+
+/// 访问检查中的读、写、执行权限
+pub const MAY_EXEC: u32 = 1;
+pub const MAY_WRITE: u32 = 2;
+pub const MAY_READ: u32 = 4;
+
+/// 附加组的最大个数（与Linux一致）
+pub const NGROUPS_MAX: usize = 65536;
+
+/// 进程的身份
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    /// 实际用户号
+    pub uid: Uid,
+    /// 有效用户号：权限检查时使用
+    pub euid: Uid,
+    /// 保存的用户号：非特权进程可以切换回的有效用户号
+    pub suid: Uid,
+    /// 实际组号
+    pub gid: Gid,
+    /// 有效组号
+    pub egid: Gid,
+    /// 保存的组号
+    pub sgid: Gid,
+    /// 附加组
+    pub groups: Vec<Gid>,
+}
+
+impl Credentials {
+    /// root身份
+    pub const fn root() -> Self {
+        Self {
+            uid: ROOT_UID,
+            euid: ROOT_UID,
+            suid: ROOT_UID,
+            gid: ROOT_GID,
+            egid: ROOT_GID,
+            sgid: ROOT_GID,
+            groups: Vec::new(),
+        }
+    }
+
+    /// 有效用户号是否为root
+    pub fn is_root(&self) -> bool {
+        self.euid == ROOT_UID
+    }
+
+    /// `uid` 是否为实际、有效或保存的用户号之一
+    pub fn has_uid(&self, uid: Uid) -> bool {
+        uid == self.uid || uid == self.euid || uid == self.suid
+    }
+
+    /// `gid` 是否为实际、有效或保存的组号之一
+    pub fn has_gid(&self, gid: Gid) -> bool {
+        gid == self.gid || gid == self.egid || gid == self.sgid
+    }
+
+    /// 是否属于组 `gid`（有效组号或附加组）
+    pub fn in_group(&self, gid: Gid) -> bool {
+        gid == self.egid || self.groups.contains(&gid)
+    }
+
+    /// 按所有者、所属组与权限位 `mode` 检查是否允许 `may`（`MAY_*` 的组合）
+    ///
+    /// 只看权限位，调用方另行检查 `CAP_DAC_OVERRIDE`
+    pub fn may_access(&self, uid: Uid, gid: Gid, mode: u32, may: u32) -> bool {
+        let bits = if self.euid == uid {
+            mode >> 6
+        } else if self.in_group(gid) {
+            mode >> 3
+        } else {
+            mode
+        };
+        bits & may == may
+    }
+
+    /// 加载文件属性为 `stat` 的程序后的身份
+    ///
+    /// setuid位把有效用户号切换为文件所有者，setgid位把有效组号切换为文件所属组，
+    /// 保存的用户号与组号随后等于新的有效值
+    pub fn exec(&self, stat: &FileStat) -> Credentials {
+        let mut new = self.clone();
+        if stat.mode & S_ISUID != 0 {
+            new.euid = stat.uid;
+        }
+        if stat.mode & S_ISGID != 0 {
+            new.egid = stat.gid;
+        }
+        new.suid = new.euid;
+        new.sgid = new.egid;
+        new
+    }
+}
+
+/// 当前进程的身份，内核任务视为root
+pub fn current() -> Arc<Credentials> {
+    super::current_process().map_or_else(|| Arc::new(Credentials::root()), |process| process.credentials())
+}
+
+/// 身份从 `old` 改为 `new` 后的能力（不保留能力的setuid语义）
+pub fn fixup_capabilities(old: &Credentials, new: &Credentials, caps: Capabilities) -> Capabilities {
+    let mut caps = caps;
+    let was_root = old.has_uid(ROOT_UID);
+    let is_root = new.has_uid(ROOT_UID);
+    if was_root && !is_root {
+        caps.permitted = CapSet::EMPTY;
+        caps.effective = CapSet::EMPTY;
+    }
+    if old.euid == ROOT_UID && new.euid != ROOT_UID {
+        caps.effective = CapSet::EMPTY;
+    }
+    if old.euid != ROOT_UID && new.euid == ROOT_UID {
+        caps.effective = caps.permitted;
+    }
+    caps
+}
+
+/// 加载程序后的能力：root获得全部能力，其他用户清空许可集与有效集
+///
+/// 没有文件能力，可继承集保持不变
+pub fn exec_capabilities(new: &Credentials, caps: Capabilities) -> Capabilities {
+    let mut caps = caps;
+    if new.euid == ROOT_UID || new.uid == ROOT_UID {
+        caps.permitted = CapSet::FULL;
+        caps.effective = if new.euid == ROOT_UID {
+            CapSet::FULL
+        } else {
+            CapSet::EMPTY
+        };
+    } else {
+        caps.permitted = CapSet::EMPTY;
+        caps.effective = CapSet::EMPTY;
+    }
+    caps
+}
+
+crate::kernel_test! {
+    fn setuid_drops_capabilities() {
+        let root = Credentials::root();
+        let mut user = root.clone();
+        user.uid = 1000;
+        user.euid = 1000;
+        user.suid = 1000;
+        let caps = fixup_capabilities(&root, &user, Capabilities::FULL);
+        assert_eq!(caps.permitted, CapSet::EMPTY);
+        assert_eq!(caps.effective, CapSet::EMPTY);
+
+        // 只改有效用户号，之后还能切换回root
+        let mut seteuid = root.clone();
+        seteuid.euid = 1000;
+        let caps = fixup_capabilities(&root, &seteuid, Capabilities::FULL);
+        assert_eq!(caps.permitted, CapSet::FULL);
+        assert_eq!(caps.effective, CapSet::EMPTY);
+        assert_eq!(fixup_capabilities(&seteuid, &root, caps).effective, CapSet::FULL);
+    }
+}
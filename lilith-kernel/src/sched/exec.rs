@@ -0,0 +1,353 @@
+//! 用户程序的加载
+//!
+//! 本模块实现了把ELF可执行文件加载到进程的用户地址空间，包括：
+//! - 检查文件头：64位小端RISC-V的可执行文件（`ET_EXEC`）或静态链接的位置无关程序（`ET_DYN`）
+//! - 只读且文件偏移与地址同余于页大小的 `PT_LOAD` 段直接映射页缓存中的文件内容，
+//!   运行同一程序的进程共享代码段；其他段分配页对齐的内核内存，复制文件内容并清零bss。按段的权限映射为VMA
+//! - 按地址空间布局在栈顶建立初始栈：argc、argv、envp与辅助向量（auxv），布局与Linux一致
+//! - 映射vDSO（代码页不是ELF映像，不提供 `AT_SYSINFO_EHDR`），把brk堆放在程序映像之后
+//!
+//! 段与栈的内存在加载时一次分配（或引用页缓存），由VMA持有，解除映射时释放。
+//! 需要动态链接器（`PT_INTERP`）的程序暂不支持
+
+use super::Process;
+use crate::error::KernelError;
+use crate::mm::aslr;
+use crate::mm::page_cache::CachedFile;
+use crate::mm::page_owner::Pages;
+use crate::mm::vdso;
+use crate::mm::vma::{page_align_up, MapOwner, Vma, VmaBacking, VmaFlags, MMAP_TOP, PAGE_SIZE, USER_SPACE_END};
+use crate::random;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// 文件头字段
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_RISCV: u16 = 243;
+/// 程序头大小
+const PHDR_SIZE: usize = 56;
+
+/// 程序头类型
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+
+/// 段权限
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+/// 辅助向量的类型（取值与Linux一致）
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_ENTRY: usize = 9;
+const AT_UID: usize = 11;
+const AT_EUID: usize = 12;
+const AT_GID: usize = 13;
+const AT_EGID: usize = 14;
+const AT_SECURE: usize = 23;
+const AT_RANDOM: usize = 25;
+const AT_EXECFN: usize = 31;
+
+/// 位置无关程序的加载地址（用户地址空间的2/3处，与Linux一致）
+const ET_DYN_BASE: usize = (USER_SPACE_END / 3 * 2) & !(PAGE_SIZE - 1);
+
+/// 用户栈大小
+const STACK_SIZE: usize = 1024 * 1024;
+
+/// 加载后的程序
+#[derive(Debug, Clone, Copy)]
+pub struct Program {
+    /// 入口地址
+    pub entry: usize,
+    /// 初始栈指针（指向argc，16字节对齐）
+    pub stack_pointer: usize,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, KernelError> {
+    let bytes = data.get(offset..offset + 2).ok_or(KernelError::InvalidArgument)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, KernelError> {
+    let bytes = data.get(offset..offset + 4).ok_or(KernelError::InvalidArgument)?;
+    let mut value = [0u8; 4];
+    value.copy_from_slice(bytes);
+    Ok(u32::from_le_bytes(value))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<usize, KernelError> {
+    let bytes = data.get(offset..offset + 8).ok_or(KernelError::InvalidArgument)?;
+    let mut value = [0u8; 8];
+    value.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(value) as usize)
+}
+
+/// 程序头
+#[derive(Debug, Clone, Copy)]
+struct ProgramHeader {
+    kind: u32,
+    flags: u32,
+    offset: usize,
+    vaddr: usize,
+    filesz: usize,
+    memsz: usize,
+}
+
+/// 检查文件头，返回文件类型、入口、程序头表偏移与所有程序头
+fn parse(image: &[u8]) -> Result<(u16, usize, usize, Vec<ProgramHeader>), KernelError> {
+    let kind = read_u16(image, 16)?;
+    if image.get(..4) != Some(b"\x7fELF".as_slice())
+        || image.get(4) != Some(&ELFCLASS64)
+        || image.get(5) != Some(&ELFDATA2LSB)
+        || (kind != ET_EXEC && kind != ET_DYN)
+        || read_u16(image, 18)? != EM_RISCV
+    {
+        return Err(KernelError::InvalidArgument);
+    }
+    let entry = read_u64(image, 24)?;
+    let phoff = read_u64(image, 32)?;
+    let phentsize = read_u16(image, 54)? as usize;
+    let phnum = read_u16(image, 56)? as usize;
+    if phentsize != PHDR_SIZE {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let mut headers = Vec::with_capacity(phnum);
+    for index in 0..phnum {
+        let base = phoff + index * PHDR_SIZE;
+        let header = ProgramHeader {
+            kind: read_u32(image, base)?,
+            flags: read_u32(image, base + 4)?,
+            offset: read_u64(image, base + 8)?,
+            vaddr: read_u64(image, base + 16)?,
+            filesz: read_u64(image, base + 32)?,
+            memsz: read_u64(image, base + 40)?,
+        };
+        if header.kind == PT_INTERP {
+            return Err(KernelError::NotSupported);
+        }
+        if header.kind == PT_LOAD
+            && (header.filesz > header.memsz
+                || header
+                    .offset
+                    .checked_add(header.filesz)
+                    .map_or(true, |end| end > image.len()))
+        {
+            return Err(KernelError::InvalidArgument);
+        }
+        headers.push(header);
+    }
+    Ok((kind, entry, phoff, headers))
+}
+
+/// 把一个 `PT_LOAD` 段映射到 `[bias + vaddr, bias + vaddr + memsz)`，返回段的结束地址
+fn load_segment(
+    process: &Process,
+    image: &Arc<CachedFile>,
+    bias: usize,
+    header: &ProgramHeader,
+) -> Result<usize, KernelError> {
+    let vaddr = bias.checked_add(header.vaddr).ok_or(KernelError::InvalidArgument)?;
+    let start = vaddr & !(PAGE_SIZE - 1);
+    let end = vaddr
+        .checked_add(header.memsz)
+        .map(page_align_up)
+        .filter(|&end| end <= MMAP_TOP)
+        .ok_or(KernelError::InvalidArgument)?;
+    let offset = vaddr - start;
+
+    let shareable = header.flags & PF_W == 0 && header.memsz == header.filesz && header.offset % PAGE_SIZE == offset;
+    let (addr, owner) = if shareable {
+        // 段末尾所在页的剩余部分是文件中随后的内容，与Linux一致
+        (image.addr() + header.offset - offset, image.clone() as MapOwner)
+    } else {
+        let mut pages = Pages::new(end - start, "exec")?;
+        pages.as_mut_slice()[offset..offset + header.filesz]
+            .copy_from_slice(&image.data()[header.offset..header.offset + header.filesz]);
+        (pages.addr(), Arc::new(pages) as MapOwner)
+    };
+
+    let mut flags = VmaFlags::empty();
+    for (bit, flag) in [(PF_R, VmaFlags::READ), (PF_W, VmaFlags::WRITE), (PF_X, VmaFlags::EXEC)] {
+        if header.flags & bit != 0 {
+            flags |= flag;
+        }
+    }
+    let vma = Vma {
+        start,
+        end,
+        flags,
+        backing: VmaBacking::Kernel(addr),
+    };
+    process.address_space.insert_owned(vma, owner)?;
+    Ok(end)
+}
+
+/// 向下增长的初始栈
+struct StackBuilder<'a> {
+    memory: &'a mut [u8],
+    /// `memory[0]` 的用户地址
+    base: usize,
+    /// 当前栈指针（用户地址）
+    sp: usize,
+}
+
+impl StackBuilder<'_> {
+    /// 压入数据，返回数据的用户地址
+    fn push(&mut self, bytes: &[u8]) -> Result<usize, KernelError> {
+        let sp = self
+            .sp
+            .checked_sub(bytes.len())
+            .filter(|&sp| sp >= self.base)
+            .ok_or(KernelError::InvalidArgument)?;
+        let offset = sp - self.base;
+        self.memory[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.sp = sp;
+        Ok(sp)
+    }
+
+    /// 压入以0结尾的字符串
+    fn push_str(&mut self, s: &str) -> Result<usize, KernelError> {
+        self.push(&[0])?;
+        self.push(s.as_bytes())
+    }
+}
+
+/// 在栈顶建立argc、argv、envp与辅助向量，返回栈指针
+fn build_stack(
+    process: &Process,
+    path: &str,
+    argv: &[&str],
+    envp: &[&str],
+    auxv: &[(usize, usize)],
+) -> Result<usize, KernelError> {
+    let top = process.address_space.layout().stack_top;
+    let mut pages = Pages::new(STACK_SIZE, "exec")?;
+    let mut stack = StackBuilder {
+        memory: pages.as_mut_slice(),
+        base: top - STACK_SIZE,
+        sp: top,
+    };
+
+    let execfn = stack.push_str(path)?;
+    let mut random_bytes = [0u8; 16];
+    random::get_random_bytes(&mut random_bytes);
+    let random_addr = stack.push(&random_bytes)?;
+    let envp = envp.iter().map(|s| stack.push_str(s)).collect::<Result<Vec<_>, _>>()?;
+    let argv = argv.iter().map(|s| stack.push_str(s)).collect::<Result<Vec<_>, _>>()?;
+
+    let credentials = process.credentials();
+    let mut words = Vec::new();
+    words.push(argv.len());
+    words.extend(&argv);
+    words.push(0);
+    words.extend(&envp);
+    words.push(0);
+    for &(key, value) in auxv.iter().chain(&[
+        (AT_UID, credentials.uid as usize),
+        (AT_EUID, credentials.euid as usize),
+        (AT_GID, credentials.gid as usize),
+        (AT_EGID, credentials.egid as usize),
+        (AT_SECURE, 0),
+        (AT_RANDOM, random_addr),
+        (AT_EXECFN, execfn),
+        (AT_NULL, 0),
+    ]) {
+        words.extend([key, value]);
+    }
+
+    // argc所在的栈指针按16字节对齐
+    stack.sp = (stack.sp - words.len() * 8) & !15;
+    stack.sp += words.len() * 8;
+    for word in words.iter().rev() {
+        stack.push(&word.to_le_bytes())?;
+    }
+    let sp = stack.sp;
+
+    let vma = Vma {
+        start: top - STACK_SIZE,
+        end: top,
+        flags: VmaFlags::READ | VmaFlags::WRITE | VmaFlags::STACK,
+        backing: VmaBacking::Kernel(pages.addr()),
+    };
+    process.address_space.insert_owned(vma, Arc::new(pages))?;
+    Ok(sp)
+}
+
+/// 把页缓存中的程序 `image` 加载到 `process` 的空地址空间，`path` 为程序路径（`AT_EXECFN`）
+///
+/// 文件格式错误时返回 `InvalidArgument`，需要动态链接器时返回 `NotSupported`
+pub fn load(
+    process: &Process,
+    path: &str,
+    image: &Arc<CachedFile>,
+    argv: &[&str],
+    envp: &[&str],
+) -> Result<Program, KernelError> {
+    let (kind, entry, phoff, headers) = parse(image.data())?;
+    let bias = if kind == ET_DYN { ET_DYN_BASE } else { 0 };
+
+    let address_space = &process.address_space;
+    address_space.set_layout(aslr::choose_layout());
+    let mut image_end = 0;
+    for header in headers.iter().filter(|header| header.kind == PT_LOAD) {
+        image_end = image_end.max(load_segment(process, image, bias, header)?);
+    }
+    if image_end == 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    address_space.set_brk_base(image_end);
+    vdso::map_vdso(address_space, process.vdso_page())?;
+
+    // 程序头表的用户地址：优先使用PT_PHDR，否则在包含它的段中换算
+    let phdr = headers
+        .iter()
+        .find(|header| header.kind == PT_PHDR)
+        .map(|header| header.vaddr)
+        .or_else(|| {
+            headers
+                .iter()
+                .find(|h| h.kind == PT_LOAD && h.offset <= phoff && phoff < h.offset + h.filesz)
+                .map(|h| h.vaddr + (phoff - h.offset))
+        })
+        .map_or(0, |vaddr| bias + vaddr);
+    let auxv = [
+        (AT_PHDR, phdr),
+        (AT_PHENT, PHDR_SIZE),
+        (AT_PHNUM, headers.len()),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_BASE, 0),
+        (AT_ENTRY, bias + entry),
+    ];
+    let stack_pointer = build_stack(process, path, argv, envp, &auxv)?;
+    Ok(Program {
+        entry: bias + entry,
+        stack_pointer,
+    })
+}
+
+crate::kernel_test! {
+    fn exec_rejects_bad_images() {
+        assert!(parse(b"not an elf").is_err());
+
+        let mut header = alloc::vec![0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = ELFCLASS64;
+        header[5] = ELFDATA2LSB;
+        header[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        header[18..20].copy_from_slice(&EM_RISCV.to_le_bytes());
+        header[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+        assert!(parse(&header).unwrap().3.is_empty());
+
+        header[18..20].copy_from_slice(&62u16.to_le_bytes());
+        assert!(parse(&header).is_err());
+    }
+}
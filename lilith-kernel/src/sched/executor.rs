@@ -0,0 +1,202 @@
+//! 内核异步执行器
+//!
+//! 本模块实现了驱动使用的异步任务执行器，包括：
+//! - 异步任务（`Future`）由专门的内核任务 `kasync` 轮询执行，就绪队列先进先出
+//! - 任务的 `Waker` 只把任务放回就绪队列并唤醒执行器，可以在中断处理函数中调用
+//! - 同一任务在就绪队列中最多出现一次，重复唤醒合并为一次轮询
+//! - 中断完成事件（`IrqCompletion`）：驱动提交请求后等待，中断处理函数调用 `complete` 唤醒等待的任务
+//!
+//! 异步任务在 `kasync` 中串行执行，不能调用会睡眠的函数，需要等待时应返回 `Pending`
+
+use crate::sync::{SpinLockIrqSave, WaitQueue};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// 一个异步任务
+pub struct AsyncTask {
+    /// 名称，用于调试
+    pub name: &'static str,
+    /// 尚未完成的任务体，完成后为 `None`
+    future: Mutex<Option<BoxedFuture>>,
+    /// 是否已在就绪队列中
+    queued: AtomicBool,
+}
+
+impl AsyncTask {
+    /// 任务是否已完成
+    pub fn is_finished(&self) -> bool {
+        self.future.lock().is_none()
+    }
+}
+
+/// 就绪队列，唤醒可能发生在中断中
+static READY: SpinLockIrqSave<VecDeque<Arc<AsyncTask>>> = SpinLockIrqSave::new(VecDeque::new());
+
+/// 执行器等待就绪任务的队列
+static EXECUTOR_WAIT: WaitQueue = WaitQueue::new();
+
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.queued.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        READY.lock().push_back(self.clone());
+        EXECUTOR_WAIT.wake_all();
+    }
+}
+
+/// 创建异步任务并放入就绪队列
+pub fn spawn<F>(name: &'static str, future: F) -> Arc<AsyncTask>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task = Arc::new(AsyncTask {
+        name,
+        future: Mutex::new(Some(Box::pin(future))),
+        queued: AtomicBool::new(false),
+    });
+    task.wake_by_ref();
+    task
+}
+
+/// 轮询一个就绪任务，完成后释放任务体
+fn poll_task(task: Arc<AsyncTask>) {
+    // 先清除标记，轮询期间到来的唤醒会再次入队
+    task.queued.store(false, Ordering::Release);
+    let waker = Waker::from(task.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = task.future.lock();
+    if let Some(body) = future.as_mut() {
+        if body.as_mut().poll(&mut cx).is_ready() {
+            *future = None;
+        }
+    }
+}
+
+/// 执行器任务：取出就绪任务轮询，没有就绪任务时睡眠
+fn executor_main() {
+    loop {
+        EXECUTOR_WAIT.wait_until(|| !READY.lock().is_empty());
+        while let Some(task) = READY.lock().pop_front() {
+            poll_task(task);
+        }
+    }
+}
+
+/// 中断完成事件的状态
+struct CompletionState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// 中断完成事件：异步任务等待，中断处理函数完成
+pub struct IrqCompletion {
+    state: SpinLockIrqSave<CompletionState>,
+}
+
+impl IrqCompletion {
+    pub const fn new() -> Self {
+        Self {
+            state: SpinLockIrqSave::new(CompletionState {
+                done: false,
+                waker: None,
+            }),
+        }
+    }
+
+    /// 标记完成并唤醒等待的任务，可在中断上下文中调用
+    pub fn complete(&self) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.done = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// 清除完成标记，提交下一个请求前调用
+    pub fn reset(&self) {
+        let mut state = self.state.lock();
+        state.done = false;
+        state.waker = None;
+    }
+
+    /// 等待完成
+    pub fn wait(&self) -> CompletionFuture<'_> {
+        CompletionFuture { completion: self }
+    }
+}
+
+impl Default for IrqCompletion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `IrqCompletion::wait` 返回的 `Future`
+pub struct CompletionFuture<'a> {
+    completion: &'a IrqCompletion,
+}
+
+impl Future for CompletionFuture<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.completion.state.lock();
+        if state.done {
+            return Poll::Ready(());
+        }
+        // 只保留最近一次轮询的waker
+        if !state.waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+            state.waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// 创建执行器任务
+pub fn executor_init() {
+    super::spawn("kasync", executor_main);
+}
+
+crate::kernel_test! {
+    fn executor_irq_completion() {
+        use core::sync::atomic::AtomicUsize;
+
+        struct CountWaker(AtomicUsize);
+        impl Wake for CountWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let completion = IrqCompletion::new();
+        let counter = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = completion.wait();
+
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Pending);
+        completion.complete();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Ready(()));
+
+        completion.reset();
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Pending);
+    }
+}
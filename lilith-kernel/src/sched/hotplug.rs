@@ -0,0 +1,223 @@
+//! hart热插拔
+//!
+//! 本模块实现了运行时让hart下线与重新上线，包括：
+//! - 下线：标记hart即将下线并发送核间中断。该hart在下一个调度点不再从运行队列取任务，
+//!   正在运行的任务放回全局运行队列由其他hart接着运行，随后空闲任务退出RCU宽限期检测并停止hart
+//! - 上线：为hart分配内核栈并通过固件启动，新hart建立空闲任务后进入调度循环
+//! - /sys/devices/system/cpu/cpuN/online：读取在线状态，写入0或1使hart下线或上线
+//! - /sys/devices/system/cpu/online：在线hart列表
+//!
+//! 调度是协作式的：下线请求在目标hart上正在运行的任务让出处理器后才生效
+
+use super::task::KERNEL_STACK_SIZE;
+use super::MAX_HARTS;
+use crate::arch::{self, hart_id, hotplug};
+use crate::error::KernelError;
+use crate::fs::kernfs;
+use crate::sync::{rcu, Mutex};
+use crate::time::{self, NSEC_PER_SEC};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// hart的热插拔状态
+const HART_OFFLINE: u8 = 0;
+const HART_ONLINE: u8 = 1;
+/// 已请求下线，等待hart自行停止
+const HART_DYING: u8 = 2;
+/// 已请求固件启动，等待hart进入调度器
+const HART_STARTING: u8 = 3;
+
+/// 等待hart完成上线的最长时间
+const HOTPLUG_TIMEOUT_NS: u64 = NSEC_PER_SEC;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const STATE_INIT: AtomicU8 = AtomicU8::new(HART_OFFLINE);
+#[allow(clippy::declare_interior_mutable_const)]
+const STACK_INIT: AtomicUsize = AtomicUsize::new(0);
+
+/// 各hart的热插拔状态
+static HART_STATE: [AtomicU8; MAX_HARTS] = [STATE_INIT; MAX_HARTS];
+
+/// 热插拔启动的hart使用的内核栈顶，重新上线时复用
+static STACK_TOPS: [AtomicUsize; MAX_HARTS] = [STACK_INIT; MAX_HARTS];
+
+/// 串行化上线与下线操作
+static HOTPLUG_LOCK: Mutex<()> = Mutex::new(());
+
+/// hart是否在线
+pub fn is_online(hart: usize) -> bool {
+    HART_STATE.get(hart).map_or(false, |state| state.load(Ordering::Acquire) == HART_ONLINE)
+}
+
+/// 在线hart的位图
+pub fn online_mask() -> usize {
+    (0..MAX_HARTS).filter(|&hart| is_online(hart)).fold(0, |mask, hart| mask | (1 << hart))
+}
+
+/// 当前hart进入调度器时调用
+pub(super) fn set_online() {
+    HART_STATE[hart_id()].store(HART_ONLINE, Ordering::Release);
+}
+
+/// 当前hart是否已被请求下线
+pub(super) fn is_dying() -> bool {
+    HART_STATE[hart_id()].load(Ordering::Acquire) == HART_DYING
+}
+
+/// 唤醒其他在线hart，使其接手本hart迁移出去的任务
+fn kick_online_harts() {
+    let this = hart_id();
+    (0..MAX_HARTS)
+        .filter(|&hart| hart != this && is_online(hart))
+        .for_each(hotplug::send_ipi);
+}
+
+/// 使hart下线，返回时hart已经停止
+///
+/// 不允许让最后一个在线的hart下线
+pub fn hart_offline(hart: usize) -> Result<(), KernelError> {
+    let state = HART_STATE.get(hart).ok_or(KernelError::InvalidArgument)?;
+    let _guard = HOTPLUG_LOCK.lock();
+    if online_mask() & !(1 << hart) == 0 {
+        return Err(KernelError::ResourceBusy);
+    }
+    match state.compare_exchange(HART_ONLINE, HART_DYING, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => {}
+        Err(HART_OFFLINE) => return Ok(()),
+        Err(_) => return Err(KernelError::ResourceBusy),
+    }
+
+    if hart == hart_id() {
+        // 当前任务被放回运行队列，由其他hart接着运行
+        super::schedule();
+    } else {
+        hotplug::send_ipi(hart);
+    }
+    while state.load(Ordering::Acquire) != HART_OFFLINE {
+        super::yield_now();
+    }
+    crate::log_info!("hart {} 已下线", hart);
+    Ok(())
+}
+
+/// 启动已停止的hart，返回时hart已进入调度器
+pub fn hart_online(hart: usize) -> Result<(), KernelError> {
+    let state = HART_STATE.get(hart).ok_or(KernelError::InvalidArgument)?;
+    let _guard = HOTPLUG_LOCK.lock();
+    match hotplug::hart_is_stopped(hart) {
+        None => return Err(KernelError::NotFound),
+        Some(false) if state.load(Ordering::Acquire) == HART_ONLINE => return Ok(()),
+        // 由其他途径启动、未经调度器管理的hart
+        Some(false) => return Err(KernelError::ResourceBusy),
+        Some(true) => {}
+    }
+    if state
+        .compare_exchange(HART_OFFLINE, HART_STARTING, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Err(KernelError::ResourceBusy);
+    }
+
+    let mut stack_top = STACK_TOPS[hart].load(Ordering::Relaxed);
+    if stack_top == 0 {
+        // 栈一直保留，hart下线时仍在其上运行
+        let stack = Box::leak(vec![0u8; KERNEL_STACK_SIZE].into_boxed_slice());
+        stack_top = (stack.as_ptr() as usize + KERNEL_STACK_SIZE) & !0xf;
+        STACK_TOPS[hart].store(stack_top, Ordering::Relaxed);
+    }
+    if let Err(err) = hotplug::hart_start(hart, stack_top) {
+        state.store(HART_OFFLINE, Ordering::Release);
+        return Err(err);
+    }
+
+    let deadline = time::monotonic_ns().saturating_add(HOTPLUG_TIMEOUT_NS);
+    while state.load(Ordering::Acquire) != HART_ONLINE {
+        if time::monotonic_ns() > deadline {
+            return Err(KernelError::DeviceError);
+        }
+        super::yield_now();
+    }
+    crate::log_info!("hart {} 已上线", hart);
+    Ok(())
+}
+
+/// 已请求下线的hart在空闲任务中调用，停止当前hart
+///
+/// 调用时中断已关闭，当前任务是空闲任务
+pub(super) fn hart_die() -> ! {
+    rcu::rcu_offline();
+    super::stop_on_this_hart();
+    HART_STATE[hart_id()].store(HART_OFFLINE, Ordering::Release);
+    kick_online_harts();
+    hotplug::hart_stop()
+}
+
+/// 热插拔启动的hart进入内核后的入口，由 `arch::hotplug` 的汇编入口调用
+pub extern "C" fn secondary_main(hart: usize) -> ! {
+    arch::init_trap();
+    arch::enable_timer_interrupt();
+    arch::enable_software_interrupt();
+    if arch::plic::present() {
+        arch::enable_external_interrupt();
+    }
+    super::start_on_this_hart();
+    // 设置本hart的第一次时钟中断
+    time::timer::run_timers();
+    crate::log_debug!("hart {} 进入调度器", hart);
+    arch::local_irq_enable();
+
+    loop {
+        super::schedule();
+        super::idle_wait();
+    }
+}
+
+/// 以Linux的cpulist格式（如 `0-2,5`）列出在线hart
+fn online_list() -> String {
+    let mut list = String::new();
+    let mut hart = 0;
+    while hart < MAX_HARTS {
+        if !is_online(hart) {
+            hart += 1;
+            continue;
+        }
+        let start = hart;
+        while hart + 1 < MAX_HARTS && is_online(hart + 1) {
+            hart += 1;
+        }
+        if !list.is_empty() {
+            list.push(',');
+        }
+        let _ = if start == hart {
+            write!(list, "{}", start)
+        } else {
+            write!(list, "{}-{}", start, hart)
+        };
+        hart += 1;
+    }
+    list.push('\n');
+    list
+}
+
+/// 注册 /sys/devices/system/cpu 下的热插拔控制文件
+pub fn hotplug_init() -> Result<(), KernelError> {
+    arch::enable_software_interrupt();
+
+    for hart in (0..MAX_HARTS).filter(|&hart| hotplug::hart_is_stopped(hart).is_some()) {
+        kernfs::register(
+            &format!("/sys/devices/system/cpu/cpu{}/online", hart),
+            Some(Box::new(move || format!("{}\n", u8::from(is_online(hart))))),
+            Some(Box::new(move |data| match data.trim() {
+                "0" => hart_offline(hart),
+                "1" => hart_online(hart),
+                _ => Err(KernelError::InvalidArgument),
+            })),
+        )?;
+    }
+    kernfs::register("/sys/devices/system/cpu/online", Some(Box::new(online_list)), None)?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! 第一个用户进程（init）
+//!
+//! 本模块实现了内核初始化完成后启动1号进程，行为与Linux一致，包括：
+//! - 程序路径由启动参数 `init=` 指定，未指定时依次尝试 /init 与 /sbin/init
+//! - 从initramfs经页缓存读取程序，加载到新的地址空间（见 `exec`）后作为用户任务运行
+//! - 标准输入、输出、错误指向控制台，环境变量只有 `HOME` 与 `TERM`
+//! - 所有候选程序都无法启动时内核恐慌，提示用 `init=` 指定；启用了内核调试shell时只报告错误
+//!
+//! init是会话首进程但不自动取得控制终端，需要作业控制的shell自行使用 `TIOCSCTTY`。
+//! 1号进程退出时内核同样恐慌
+
+use super::exec;
+use super::{Process, TaskId};
+use crate::arch::TrapFrame;
+use crate::boot::cmdline;
+use crate::debug::kshell;
+use crate::error::KernelError;
+use crate::fs::file::FdTable;
+use crate::fs::initramfs;
+use crate::mm::vma::AddressSpace;
+use crate::sync::SpinLock;
+use alloc::sync::Arc;
+
+/// 未指定 `init=` 时依次尝试的程序
+const DEFAULT_INIT: [&str; 2] = ["/init", "/sbin/init"];
+
+/// init的环境变量
+const INIT_ENVP: [&str; 2] = ["HOME=/", "TERM=linux"];
+
+/// 加载 `path` 并创建1号进程
+fn try_start(path: &str) -> Result<(), KernelError> {
+    let entry = initramfs::lookup(path)?;
+    if !entry.is_file() || entry.mode & 0o111 == 0 {
+        return Err(KernelError::AccessDenied);
+    }
+
+    let id = TaskId::INIT;
+    let files = Arc::new(SpinLock::new(FdTable::with_console()));
+    let process = Process::new(id.0, None, Arc::new(AddressSpace::new()), files);
+    let program = exec::load(&process, path, &entry.cached()?, &[path], &INIT_ENVP)?;
+
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let frame = TrapFrame::new_user(program.entry, program.stack_pointer);
+    super::spawn_user(id, name, &frame, process, 0);
+    crate::log_info!("启动init进程: {}", path);
+    Ok(())
+}
+
+/// 启动1号进程，进程在调度器第一次调度时开始运行
+///
+/// 所有候选程序都无法启动时恐慌，启用了内核调试shell时留在shell中
+pub fn start_init() {
+    let specified = cmdline::get("init");
+    let candidates = match &specified {
+        Some(path) => core::slice::from_ref(path),
+        None => &DEFAULT_INIT[..],
+    };
+    if initramfs::mounted() {
+        for path in candidates {
+            match try_start(path) {
+                Ok(()) => return,
+                Err(e) => crate::log_error!("无法启动init进程 {}: {:?}", path, e),
+            }
+        }
+    }
+
+    if kshell::enabled() {
+        crate::log_error!("没有可用的init进程，留在内核调试shell中");
+    } else if !initramfs::mounted() {
+        panic!("没有初始根文件系统，无法启动init进程（引导程序需要传入initrd）");
+    } else {
+        panic!("没有可用的init进程（尝试了 {:?}），请用启动参数 init= 指定", candidates);
+    }
+}
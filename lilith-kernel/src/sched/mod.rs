@@ -1,13 +1,565 @@
 //! 进程调度模块
+//!
+//! 本模块实现了内核任务的调度，包括：
+//! - 任务创建与退出
+//! - 任务表（按编号查找任务）
+//! - 全局先进先出运行队列，任务可以绑定到一个hart，绑定的hart下线后在任意hart上运行
+//! - 每个hart的当前任务与空闲任务，空闲时停止周期性时钟节拍，有任务入队时以核间中断唤醒
+//! - 任务阻塞与唤醒（供等待队列与睡眠锁使用）
+//! - 用户进程与从陷入现场返回用户态的任务
+//! - 进程的能力集合与特权检查
+//! - 进程的用户身份（用户号、组号与附加组）
+//! - 进程信号（屏蔽字、待处理信号与默认动作）
+//! - 进程的资源限制（rlimit）
+//! - 加载ELF用户程序与启动1号进程（init）
+//! - 驱动使用的异步执行器，中断可以唤醒异步任务
+//! - 工作队列：把中断中的耗时处理推迟到绑定hart或不绑定hart的工作者任务中执行
+//! - 内核栈溢出检测：切换与陷入时检查当前任务栈底的金丝雀值
+//! - 禁止抢占计数：计数非零时（如RCU读侧临界区内）不进行任务切换
+//! - hart热插拔（`smp` 特性）
 
+pub mod capability;
+pub mod cred;
+pub mod exec;
+pub mod executor;
+pub mod init;
+pub mod process;
+pub mod rlimit;
+pub mod signal;
+pub mod task;
+pub mod workqueue;
+#[cfg(feature = "smp")]
+pub mod hotplug;
+
+pub use capability::capable;
+pub use process::{ChildEvent, Process, WaitOptions, WaitTarget};
+pub use task::{Task, TaskEntry, TaskId, TaskState};
+
+use crate::arch::{fpu, vector};
+use crate::arch::{
+    enter_user, hart_id, local_irq_enable, local_irq_restore, local_irq_save, switch_context, wait_for_interrupt, TrapFrame,
+};
+use crate::debug::tracepoint;
 use crate::error::KernelError;
+use crate::perf;
+use crate::time;
+#[cfg(feature = "lockdep")]
+use crate::sync::lockdep;
+#[cfg(feature = "ftrace")]
+use crate::debug::ftrace;
+use crate::sync::{rcu, RwLock, SpinLockIrqSave};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 支持的最大hart数
+#[cfg(feature = "smp")]
+pub const MAX_HARTS: usize = 8;
+/// 支持的最大hart数（未开启 `smp` 特性时只使用启动hart）
+#[cfg(not(feature = "smp"))]
+pub const MAX_HARTS: usize = 1;
+
+/// 每个hart的调度状态
+struct HartState {
+    /// 当前运行的任务
+    current: Option<Arc<Task>>,
+    /// 空闲任务
+    idle: Option<Arc<Task>>,
+    /// 刚被切换出去、尚未完成切换收尾的任务
+    prev: Option<Arc<Task>>,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const HART_INIT: SpinLockIrqSave<HartState> = SpinLockIrqSave::new(HartState {
+    current: None,
+    idle: None,
+    prev: None,
+});
+
+/// 各hart的调度状态
+static HARTS: [SpinLockIrqSave<HartState>; MAX_HARTS] = [HART_INIT; MAX_HARTS];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const STACK_BASE_INIT: AtomicUsize = AtomicUsize::new(0);
+
+/// 各hart当前任务的内核栈底，陷入时据此检查金丝雀值，不需要获取调度锁
+static STACK_BASES: [AtomicUsize; MAX_HARTS] = [STACK_BASE_INIT; MAX_HARTS];
+
+/// 各hart的禁止抢占嵌套计数
+static PREEMPT_COUNT: [AtomicUsize; MAX_HARTS] = [STACK_BASE_INIT; MAX_HARTS];
+
+/// 运行队列
+static RUN_QUEUE: SpinLockIrqSave<VecDeque<Arc<Task>>> = SpinLockIrqSave::new(VecDeque::new());
+
+/// 在空闲任务中等待中断（可能停止了时钟节拍）的hart的位图
+#[cfg(feature = "smp")]
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// 任务表，读远多于写
+static TASKS: RwLock<BTreeMap<TaskId, Arc<Task>>> = RwLock::new(BTreeMap::new());
+
+/// 当前hart的调度状态
+fn this_hart() -> &'static SpinLockIrqSave<HartState> {
+    &HARTS[hart_id()]
+}
+
+/// 当前任务，调度器尚未在本hart启动时返回 `None`
+pub fn current() -> Option<Arc<Task>> {
+    this_hart().lock().current.clone()
+}
+
+/// 当前任务所属的进程
+pub fn current_process() -> Option<Arc<Process>> {
+    current().and_then(|task| task.process())
+}
+
+/// 禁止当前hart上的任务切换，可以嵌套
+pub fn preempt_disable() {
+    PREEMPT_COUNT[hart_id()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// 恢复任务切换，与 `preempt_disable` 配对
+pub fn preempt_enable() {
+    PREEMPT_COUNT[hart_id()].fetch_sub(1, Ordering::Relaxed);
+}
+
+/// 当前hart是否允许任务切换
+pub fn preemptible() -> bool {
+    PREEMPT_COUNT[hart_id()].load(Ordering::Relaxed) == 0
+}
+
+/// 内核栈溢出：报告任务并恐慌
+fn stack_overflow(task: &Task) -> ! {
+    let base = task.kernel_stack_base();
+    panic!(
+        "内核栈溢出: 任务 {} ({}) 的栈底金丝雀值被覆盖，栈 0x{:x}-0x{:x}",
+        task.id,
+        task.name,
+        base,
+        task.kernel_stack_top()
+    );
+}
+
+/// 检查当前任务的内核栈是否溢出，由陷入处理在入口调用
+pub fn check_stack_canary() {
+    let base = STACK_BASES[hart_id()].load(Ordering::Relaxed);
+    if base == 0 || task::stack_canary_intact(base) {
+        return;
+    }
+    // 避免恐慌处理中再次检查
+    STACK_BASES[hart_id()].store(0, Ordering::Relaxed);
+    match current() {
+        Some(task) => stack_overflow(&task),
+        None => panic!("内核栈溢出: 栈底 0x{:x} 的金丝雀值被覆盖", base),
+    }
+}
+
+/// 创建内核任务并加入运行队列
+pub fn spawn(name: &str, entry: TaskEntry) -> Arc<Task> {
+    let task = Task::new(TaskId::alloc(), name, Some(entry), task_start as *const () as usize);
+    enqueue_new(task)
+}
+
+/// 创建绑定到 `hart` 的内核任务
+pub fn spawn_on(hart: usize, name: &str, entry: TaskEntry) -> Arc<Task> {
+    let task = Task::new(TaskId::alloc(), name, Some(entry), task_start as *const () as usize);
+    task.bind_to_hart(Some(hart));
+    enqueue_new(task)
+}
+
+/// 创建属于 `process` 的用户任务，首次运行时以 `frame` 返回用户态
+///
+/// 新任务继承当前任务的浮点与向量状态
+pub fn spawn_user(id: TaskId, name: &str, frame: &TrapFrame, process: Arc<Process>, clear_child_tid: usize) -> Arc<Task> {
+    let task = Task::new(id, name, None, user_task_start as *const () as usize);
+    unsafe {
+        task.user_frame().write(*frame);
+    }
+    if let Some(parent) = current() {
+        task.fpu().copy_from(parent.fpu());
+        task.vector().copy_from(parent.vector());
+    }
+    task.set_process(process);
+    task.set_clear_child_tid(clear_child_tid);
+    enqueue_new(task)
+}
+
+/// 登记新任务并加入运行队列
+fn enqueue_new(task: Task) -> Arc<Task> {
+    let task = Arc::new(task);
+    TASKS.write().insert(task.id, task.clone());
+    enqueue(task.clone());
+    task
+}
+
+/// 将任务加入运行队列，并唤醒一个能运行它的空闲hart
+fn enqueue(task: Arc<Task>) {
+    #[cfg(feature = "smp")]
+    let bound = task.bound_hart().filter(|&hart| hotplug::is_online(hart));
+    RUN_QUEUE.lock().push_back(task);
+
+    // 空闲的hart可能停止了时钟节拍，在wfi中最长睡眠到下一个定时器，需要核间中断唤醒。
+    // 先入队再读取位图，与 `idle_wait` 先置位再检查队列配合，不会遗漏唤醒
+    #[cfg(feature = "smp")]
+    {
+        let idle = IDLE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+        let candidates = match bound {
+            Some(hart) => idle & (1 << hart),
+            None => idle,
+        };
+        if candidates != 0 {
+            crate::arch::hotplug::send_ipi(candidates.trailing_zeros() as usize);
+        }
+    }
+}
+
+/// 按编号查找任务
+pub fn find_task(id: TaskId) -> Option<Arc<Task>> {
+    TASKS.read().get(&id).cloned()
+}
+
+/// 按进程号查找进程
+pub fn find_process(pid: usize) -> Option<Arc<Process>> {
+    find_task(TaskId(pid))
+        .and_then(|task| task.process())
+        .filter(|process| process.pid == pid)
+}
+
+/// 所有仍有线程在运行的进程
+pub fn processes() -> Vec<Arc<Process>> {
+    let mut processes: Vec<Arc<Process>> = Vec::new();
+    for process in TASKS.read().values().filter_map(|task| task.process()) {
+        if !processes.iter().any(|known| known.pid == process.pid) {
+            processes.push(process);
+        }
+    }
+    processes
+}
+
+/// 进程组 `pgid` 中的所有进程
+pub fn process_group(pgid: usize) -> Vec<Arc<Process>> {
+    processes().into_iter().filter(|process| process.pgid() == pgid).collect()
+}
+
+/// 所有任务的快照
+pub fn tasks() -> Vec<Arc<Task>> {
+    TASKS.read().values().cloned().collect()
+}
+
+/// 函数跟踪改为使用当前任务的返回地址栈
+#[cfg(feature = "ftrace")]
+fn ftrace_switch_in() {
+    if let Some(task) = current() {
+        ftrace::switch_in(task.ret_stack.get());
+    }
+}
+
+/// 新任务首次被调度时的入口
+extern "C" fn task_start() -> ! {
+    finish_switch();
+    #[cfg(feature = "ftrace")]
+    ftrace_switch_in();
+    #[cfg(feature = "lockdep")]
+    lockdep::restore_held(&lockdep::HeldLocks::new());
+    local_irq_enable();
+
+    if let Some(entry) = current().and_then(|task| task.entry) {
+        entry();
+    }
+    exit_current()
+}
+
+/// 用户任务首次被调度时的入口
+extern "C" fn user_task_start() -> ! {
+    finish_switch();
+    #[cfg(feature = "ftrace")]
+    ftrace_switch_in();
+    #[cfg(feature = "lockdep")]
+    lockdep::restore_held(&lockdep::HeldLocks::new());
+
+    // 中断保持关闭，由返回用户态时恢复的sstatus重新打开
+    match current() {
+        Some(task) => unsafe {
+            fpu::prepare_return(task.fpu(), &mut *task.user_frame());
+            vector::prepare_return(task.vector(), &mut *task.user_frame());
+            enter_user(task.user_frame())
+        },
+        None => unreachable!("用户任务没有当前任务"),
+    }
+}
+
+/// 结束当前任务
+pub fn exit_current() -> ! {
+    if let Some(task) = current() {
+        process::exit_thread(&task);
+        TASKS.write().remove(&task.id);
+        *task.state.lock() = TaskState::Exited;
+    }
+    schedule();
+    unreachable!("已退出的任务被再次调度");
+}
+
+/// 将任务标记为就绪并加入运行队列
+///
+/// 只有处于阻塞状态的任务会被唤醒，返回是否唤醒成功
+pub fn wake(task: &Arc<Task>) -> bool {
+    let mut state = task.state.lock();
+    if *state != TaskState::Blocked {
+        return false;
+    }
+    *state = TaskState::Ready;
+    enqueue(task.clone());
+    drop(state);
+    true
+}
+
+/// 任务能否在当前hart上运行：没有绑定、绑定到当前hart或绑定的hart已下线
+fn runnable_here(task: &Task) -> bool {
+    match task.bound_hart() {
+        None => true,
+        Some(hart) if hart == hart_id() => true,
+        #[cfg(feature = "smp")]
+        Some(hart) => !hotplug::is_online(hart),
+        #[cfg(not(feature = "smp"))]
+        Some(_) => true,
+    }
+}
+
+/// 从运行队列中取出第一个能在当前hart上运行的任务
+fn pop_runnable() -> Option<Arc<Task>> {
+    let mut queue = RUN_QUEUE.lock();
+    let index = queue.iter().position(|task| runnable_here(task))?;
+    queue.remove(index)
+}
+
+/// 将当前任务标记为阻塞，需随后调用 `schedule` 让出处理器
+///
+/// 在调用 `schedule` 之前被唤醒时，任务不会真正睡眠
+pub fn set_current_blocked() {
+    if let Some(task) = current() {
+        *task.state.lock() = TaskState::Blocked;
+    }
+}
+
+/// 主动让出处理器
+pub fn yield_now() {
+    schedule();
+}
+
+/// 选择下一个任务并切换
+pub fn schedule() {
+    // 禁止抢占时切换会把hart相关的状态（如RCU读侧嵌套）带到其他任务
+    if !preemptible() {
+        crate::log_warn!("sched: 禁止抢占时调用schedule，忽略本次切换");
+        return;
+    }
+    let flags = local_irq_save();
+    // 调度切换点不可能位于RCU读侧临界区内
+    rcu::rcu_note_qs();
+
+    let (prev, idle) = {
+        let hart = this_hart().lock();
+        match (hart.current.clone(), hart.idle.clone()) {
+            (Some(prev), Some(idle)) => (prev, idle),
+            // 调度器尚未在本hart启动
+            _ => {
+                local_irq_restore(flags);
+                return;
+            }
+        }
+    };
+
+    let prev_state = *prev.state.lock();
+    // 即将下线的hart只切换到空闲任务，当前任务放回运行队列由其他hart运行
+    #[cfg(feature = "smp")]
+    let dying = hotplug::is_dying();
+    #[cfg(not(feature = "smp"))]
+    let dying = false;
+    let next = match if dying { None } else { pop_runnable() } {
+        Some(next) => next,
+        // 当前任务仍可运行时继续运行，否则切换到空闲任务
+        None if !dying
+            && prev_state != TaskState::Blocked
+            && prev_state != TaskState::Exited
+            && runnable_here(&prev) =>
+        {
+            *prev.state.lock() = TaskState::Running;
+            local_irq_restore(flags);
+            return;
+        }
+        None => idle.clone(),
+    };
+
+    if Arc::ptr_eq(&next, &prev) {
+        *prev.state.lock() = TaskState::Running;
+        local_irq_restore(flags);
+        return;
+    }
+
+    // 仍在运行的任务放回队列尾部；已被唤醒（Ready）的任务已由唤醒方入队
+    {
+        let mut state = prev.state.lock();
+        if *state == TaskState::Running {
+            *state = TaskState::Ready;
+            if !Arc::ptr_eq(&prev, &idle) {
+                enqueue(prev.clone());
+            }
+        }
+    }
+
+    // 等待下一个任务在其他hart上完成切换
+    while next.on_cpu.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+    next.on_cpu.store(true, Ordering::Relaxed);
+    *next.state.lock() = TaskState::Running;
+
+    if !prev.stack_canary_intact() {
+        STACK_BASES[hart_id()].store(0, Ordering::Relaxed);
+        stack_overflow(&prev);
+    }
+    STACK_BASES[hart_id()].store(next.kernel_stack_base(), Ordering::Relaxed);
+
+    let old_context = prev.context.get();
+    let new_context = next.context.get();
+    #[cfg(feature = "lockdep")]
+    let held_locks = prev.held_locks.get();
+    #[cfg(feature = "ftrace")]
+    let ret_stack = prev.ret_stack.get();
+    perf::account(&prev);
+    prev.fpu().switch_out();
+    prev.vector().switch_out();
+    crate::tracepoint!(tracepoint::SchedSwitch {
+        prev: prev.id.0,
+        next: next.id.0,
+    });
+    // 已退出的任务不会再返回此处，切换前不能在栈上保留引用
+    drop(idle);
+    {
+        let mut hart = this_hart().lock();
+        hart.current = Some(next);
+        hart.prev = Some(prev);
+    }
+
+    #[cfg(feature = "lockdep")]
+    lockdep::save_held(unsafe { &mut *held_locks });
+
+    // 切换期间不替换返回地址，回来后立即恢复本任务的返回地址栈
+    #[cfg(feature = "ftrace")]
+    ftrace::switch_out();
+    unsafe {
+        switch_context(old_context, new_context);
+    }
+    #[cfg(feature = "ftrace")]
+    ftrace::switch_in(ret_stack);
+
+    // 重新被调度回来（可能在另一个hart上），恢复本任务持有的锁
+    #[cfg(feature = "lockdep")]
+    lockdep::restore_held(unsafe { &*held_locks });
+
+    finish_switch();
+    local_irq_restore(flags);
+}
+
+/// 切换完成后的收尾：释放上一个任务的执行权
+fn finish_switch() {
+    let prev = this_hart().lock().prev.take();
+    if let Some(prev) = prev {
+        prev.on_cpu.store(false, Ordering::Release);
+        // 已退出的任务在此释放最后一个引用，其内核栈随之回收
+    }
+}
+
+/// 在当前hart上启动调度器，当前执行流成为该hart的空闲任务
+pub fn start_on_this_hart() {
+    let idle = Arc::new(Task::bootstrap("idle"));
+    TASKS.write().insert(idle.id, idle.clone());
+    #[cfg(feature = "ftrace")]
+    ftrace::switch_in(idle.ret_stack.get());
+    STACK_BASES[hart_id()].store(0, Ordering::Relaxed);
+    let mut hart = this_hart().lock();
+    hart.current = Some(idle.clone());
+    hart.idle = Some(idle);
+    drop(hart);
+
+    perf::hart_online();
+    rcu::rcu_online();
+    #[cfg(feature = "smp")]
+    hotplug::set_online();
+}
+
+/// 当前hart下线前清除其调度状态，空闲任务从任务表中移除
+#[cfg(feature = "smp")]
+fn stop_on_this_hart() {
+    perf::hart_offline();
+    #[cfg(feature = "ftrace")]
+    ftrace::switch_out();
+    STACK_BASES[hart_id()].store(0, Ordering::Relaxed);
+    let mut hart = this_hart().lock();
+    hart.current = None;
+    if let Some(idle) = hart.idle.take() {
+        TASKS.write().remove(&idle.id);
+    }
+}
+
+/// 空闲任务等待中断
+///
+/// 没有就绪任务时停止周期性时钟节拍，并在睡眠期间退出RCU宽限期检测，
+/// 使空闲的hart既不被节拍唤醒，也不拖延其他hart的宽限期
+pub fn idle_wait() {
+    let flags = local_irq_save();
+    #[cfg(feature = "smp")]
+    if hotplug::is_dying() {
+        hotplug::hart_die();
+    }
+    // 先登记为空闲再检查队列：此后入队的任务一定会发来核间中断
+    #[cfg(feature = "smp")]
+    IDLE_HARTS.fetch_or(1 << hart_id(), Ordering::SeqCst);
+    if RUN_QUEUE.lock().iter().any(|task| runnable_here(task)) {
+        #[cfg(feature = "smp")]
+        IDLE_HARTS.fetch_and(!(1 << hart_id()), Ordering::SeqCst);
+        local_irq_restore(flags);
+        return;
+    }
+
+    let nohz = time::timer::tick_nohz_idle_enter();
+    if nohz {
+        rcu::rcu_offline();
+    }
+    // 关中断时wfi仍会被挂起的中断唤醒，中断在恢复中断状态后处理
+    wait_for_interrupt();
+    #[cfg(feature = "smp")]
+    IDLE_HARTS.fetch_and(!(1 << hart_id()), Ordering::SeqCst);
+    if nohz {
+        rcu::rcu_online();
+    }
+    local_irq_restore(flags);
+
+    if nohz {
+        time::timer::tick_nohz_idle_exit();
+    }
+}
+
+/// 时钟节拍处理，由定时器中断调用
+pub fn scheduler_tick() {
+    rcu::rcu_tick();
+    if let Some(task) = current() {
+        perf::account(&task);
+    }
+}
 
 /// 调度器初始化
 pub fn scheduler_init() -> Result<(), KernelError> {
     crate::early_println!("初始化进程调度器...");
-    
-    // 这里将实现调度器的初始化
-    
+
+    if hart_id() >= MAX_HARTS {
+        return Err(KernelError::NotSupported);
+    }
+    start_on_this_hart();
+    #[cfg(feature = "smp")]
+    hotplug::hotplug_init()?;
+
     crate::early_println!("进程调度器初始化完成");
     Ok(())
-}
\ No newline at end of file
+}
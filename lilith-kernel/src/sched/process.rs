@@ -0,0 +1,502 @@
+//! 用户进程
+//!
+//! 进程是共享地址空间与文件描述符表的一组任务（线程）：
+//! - 进程号取自创建进程时第一个任务的编号
+//! - 最后一个线程退出后进程成为僵尸，由父进程通过 `wait_child` 回收
+//! - `exit_group` 标记整个进程退出，其他线程在下次进入内核时退出
+//! - 能力集合、用户身份与资源限制在创建时从父进程继承
+//! - 信号屏蔽字与待处理信号，未被屏蔽的信号执行默认动作
+//! - 进程组与会话；停止信号使进程在下次进入内核时停下，直到收到 `SIGCONT`
+
+use super::capability::Capabilities;
+use super::cred::{self, Credentials};
+use super::rlimit::{ResourceLimits, Rlimit};
+use super::signal::{self, SigInfo, SigSet, SignalState};
+use super::task::{Task, TaskId};
+use crate::error::KernelError;
+use crate::fs::file::{FdTable, File, FileStat};
+use crate::mm::uaccess::write_user;
+use crate::mm::vdso::VdsoProcessPage;
+use crate::mm::vma::AddressSpace;
+use crate::syscall::seccomp::SyscallFilter;
+use crate::sync::{SpinLock, WaitQueue};
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicUsize, Ordering};
+
+bitflags! {
+    /// `wait_child` 的选项（取值与Linux一致）
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WaitOptions: u32 {
+        /// 没有状态变化的子进程时立即返回
+        const NOHANG = 1;
+        /// 报告停止的子进程
+        const UNTRACED = 2;
+        /// 报告继续运行的子进程
+        const CONTINUED = 8;
+    }
+}
+
+/// `wait_child` 等待的子进程
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitTarget {
+    /// 任意子进程
+    Any,
+    /// 指定进程号的子进程
+    Pid(usize),
+    /// 进程组中的子进程
+    Group(usize),
+}
+
+/// 子进程的状态变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildEvent {
+    /// 已退出，附带退出码
+    Exited(i32),
+    /// 被信号停止
+    Stopped(u32),
+    /// 收到 `SIGCONT` 后继续运行
+    Continued,
+}
+
+/// 进程控制块
+pub struct Process {
+    /// 进程号
+    pub pid: usize,
+    /// 父进程
+    parent: SpinLock<Weak<Process>>,
+    /// 地址空间
+    pub address_space: Arc<AddressSpace>,
+    /// 文件描述符表（CLONE_FILES时与父进程共享）
+    files: Arc<SpinLock<FdTable>>,
+    /// 尚未回收的子进程
+    children: SpinLock<Vec<Arc<Process>>>,
+    /// 存活的线程数
+    live_threads: AtomicUsize,
+    /// 是否已调用exit_group
+    exiting: AtomicBool,
+    /// 退出码
+    exit_code: AtomicI32,
+    /// 所有线程都已退出
+    exited: AtomicBool,
+    /// 是否跟踪系统调用
+    tracing: AtomicBool,
+    /// 系统调用过滤器，安装后不可撤销
+    syscall_filter: SpinLock<Option<Arc<SyscallFilter>>>,
+    /// 能力集合
+    capabilities: SpinLock<Capabilities>,
+    /// 用户身份，整体替换
+    credentials: SpinLock<Arc<Credentials>>,
+    /// 资源限制
+    rlimits: SpinLock<ResourceLimits>,
+    /// vDSO进程数据页
+    vdso_page: Box<VdsoProcessPage>,
+    /// 子进程退出时唤醒
+    child_exited: WaitQueue,
+    /// 屏蔽字与待处理信号
+    signals: SpinLock<SignalState>,
+    /// 有信号排队时唤醒，signalfd持有它的引用
+    signal_wait: Arc<WaitQueue>,
+    /// 进程组号
+    pgid: AtomicUsize,
+    /// 会话号
+    sid: AtomicUsize,
+    /// 停止时为使其停止的信号，运行时为0
+    stopped: AtomicU32,
+    /// 尚未被父进程取走的停止或继续事件
+    job_event: SpinLock<Option<ChildEvent>>,
+    /// 继续运行或退出时唤醒停止的线程
+    resumed: WaitQueue,
+}
+
+impl Process {
+    /// 创建只有一个线程的进程，并登记为 `parent` 的子进程
+    pub fn new(
+        pid: usize,
+        parent: Option<&Arc<Process>>,
+        address_space: Arc<AddressSpace>,
+        files: Arc<SpinLock<FdTable>>,
+    ) -> Arc<Self> {
+        let process = Arc::new(Self {
+            pid,
+            parent: SpinLock::new(parent.map_or_else(Weak::new, Arc::downgrade)),
+            address_space,
+            files,
+            children: SpinLock::new(Vec::new()),
+            live_threads: AtomicUsize::new(1),
+            exiting: AtomicBool::new(false),
+            exit_code: AtomicI32::new(0),
+            exited: AtomicBool::new(false),
+            tracing: AtomicBool::new(false),
+            // 子进程继承父进程的过滤器
+            syscall_filter: SpinLock::new(parent.and_then(|parent| parent.syscall_filter())),
+            capabilities: SpinLock::new(parent.map_or(Capabilities::FULL, |parent| parent.capabilities())),
+            credentials: SpinLock::new(
+                parent.map_or_else(|| Arc::new(Credentials::root()), |parent| parent.credentials()),
+            ),
+            rlimits: SpinLock::new(parent.map_or(ResourceLimits::DEFAULT, |parent| *parent.rlimits.lock())),
+            vdso_page: VdsoProcessPage::new(pid),
+            child_exited: WaitQueue::new(),
+            signals: SpinLock::new(SignalState::default()),
+            signal_wait: Arc::new(WaitQueue::new()),
+            // 子进程加入父进程的进程组与会话，第一个进程自成一组
+            pgid: AtomicUsize::new(parent.map_or(pid, |parent| parent.pgid())),
+            sid: AtomicUsize::new(parent.map_or(pid, |parent| parent.sid())),
+            stopped: AtomicU32::new(0),
+            job_event: SpinLock::new(None),
+            resumed: WaitQueue::new(),
+        });
+        if let Some(parent) = parent {
+            parent.children.lock().push(process.clone());
+        }
+        process
+    }
+
+    /// vDSO进程数据页，随地址空间一起由 `mm::vdso::map_vdso` 映射
+    pub fn vdso_page(&self) -> &VdsoProcessPage {
+        &self.vdso_page
+    }
+
+    /// 能力集合
+    pub fn capabilities(&self) -> Capabilities {
+        *self.capabilities.lock()
+    }
+
+    /// 替换能力集合，调用方须已用 `Capabilities::can_change_to` 检查
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        *self.capabilities.lock() = capabilities;
+    }
+
+    /// 用户身份
+    pub fn credentials(&self) -> Arc<Credentials> {
+        self.credentials.lock().clone()
+    }
+
+    /// 替换用户身份，并按身份的变化调整能力
+    ///
+    /// 调用方须已检查权限（`CAP_SETUID`/`CAP_SETGID` 或只切换到已有的用户号/组号）
+    pub fn commit_credentials(&self, new: Credentials) {
+        let mut credentials = self.credentials.lock();
+        let mut capabilities = self.capabilities.lock();
+        *capabilities = cred::fixup_capabilities(&credentials, &new, *capabilities);
+        *credentials = Arc::new(new);
+    }
+
+    /// 加载属性为 `stat` 的程序时更新身份与能力，由程序加载在替换地址空间后调用
+    pub fn exec_credentials(&self, stat: &FileStat) {
+        let mut credentials = self.credentials.lock();
+        let new = credentials.exec(stat);
+        let mut capabilities = self.capabilities.lock();
+        *capabilities = cred::exec_capabilities(&new, *capabilities);
+        *credentials = Arc::new(new);
+    }
+
+    /// 资源 `resource` 的限制，编号无效时返回 `InvalidArgument`
+    pub fn rlimit(&self, resource: usize) -> Result<Rlimit, KernelError> {
+        self.rlimits.lock().get(resource)
+    }
+
+    /// 设置资源限制并返回原来的限制，权限由 `ResourceLimits::set` 检查
+    pub fn set_rlimit(&self, resource: usize, new: Rlimit, privileged: bool) -> Result<Rlimit, KernelError> {
+        self.rlimits.lock().set(resource, new, privileged)
+    }
+
+    /// 父进程
+    pub fn parent(&self) -> Option<Arc<Process>> {
+        self.parent.lock().upgrade()
+    }
+
+    /// 文件描述符表
+    pub fn files(&self) -> Arc<SpinLock<FdTable>> {
+        self.files.clone()
+    }
+
+    /// 查找打开的文件
+    pub fn file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.lock().get(fd)
+    }
+
+    /// 登记新线程（CLONE_THREAD）
+    pub fn add_thread(&self) {
+        self.live_threads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 设置退出码，已调用exit_group时保持不变
+    pub fn set_exit_code(&self, code: i32) {
+        if !self.exiting.load(Ordering::Acquire) {
+            self.exit_code.store(code, Ordering::Relaxed);
+        }
+    }
+
+    /// 标记整个进程退出
+    pub fn exit_group(&self, code: i32) {
+        if !self.exiting.swap(true, Ordering::AcqRel) {
+            self.exit_code.store(code, Ordering::Relaxed);
+        }
+        self.resumed.wake_all();
+    }
+
+    /// 因信号结束整个进程，退出码为 `128 + 信号编号`
+    pub fn kill(&self, signal: u32) {
+        self.exit_group(128 + signal as i32);
+    }
+
+    /// 发送信号：被屏蔽时排队并唤醒等待者，否则执行默认动作
+    ///
+    /// `SIGCONT` 与 `SIGKILL` 无论是否被屏蔽都先让停止的进程继续运行
+    pub fn send_signal(&self, info: SigInfo) {
+        if matches!(info.signo, signal::SIGCONT | signal::SIGKILL) {
+            self.resume();
+        }
+        let mut signals = self.signals.lock();
+        if signals.blocked.contains(info.signo) {
+            signals.enqueue(info);
+            drop(signals);
+            self.signal_wait.wake_all();
+        } else {
+            drop(signals);
+            self.default_action(info.signo);
+        }
+    }
+
+    /// 执行信号的默认动作
+    fn default_action(&self, sig: u32) {
+        if signal::stops_by_default(sig) {
+            self.stop(sig);
+        } else if !signal::ignored_by_default(sig) {
+            self.kill(sig);
+        }
+    }
+
+    /// 因信号 `sig` 停止，各线程在下次进入内核时停下
+    fn stop(&self, sig: u32) {
+        if self.stopped.swap(sig, Ordering::AcqRel) == 0 {
+            self.report_job_event(ChildEvent::Stopped(sig));
+        }
+    }
+
+    /// 停止的进程继续运行
+    fn resume(&self) {
+        if self.stopped.swap(0, Ordering::AcqRel) != 0 {
+            self.resumed.wake_all();
+            self.report_job_event(ChildEvent::Continued);
+        }
+    }
+
+    /// 记录停止或继续事件并通知父进程
+    fn report_job_event(&self, event: ChildEvent) {
+        *self.job_event.lock() = Some(event);
+        if let Some(parent) = self.parent() {
+            parent.child_exited.wake_all();
+        }
+    }
+
+    /// 是否已被信号停止
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire) != 0
+    }
+
+    /// 进程已停止时阻塞当前线程，直到继续运行或进程退出
+    pub fn stop_point(&self) {
+        self.resumed.wait_until(|| !self.is_stopped() || self.is_exiting());
+    }
+
+    /// 进程组号
+    pub fn pgid(&self) -> usize {
+        self.pgid.load(Ordering::Acquire)
+    }
+
+    /// 会话号
+    pub fn sid(&self) -> usize {
+        self.sid.load(Ordering::Acquire)
+    }
+
+    /// 是否为会话首进程
+    pub fn is_session_leader(&self) -> bool {
+        self.sid() == self.pid
+    }
+
+    /// 加入进程组 `pgid`，权限与会话由调用者检查
+    pub fn set_pgid(&self, pgid: usize) {
+        self.pgid.store(pgid, Ordering::Release);
+    }
+
+    /// 创建以本进程为首的新会话与进程组，已是进程组组长时返回 `PermissionDenied`
+    pub fn setsid(&self) -> Result<usize, KernelError> {
+        if self.pgid() == self.pid {
+            return Err(KernelError::PermissionDenied);
+        }
+        self.sid.store(self.pid, Ordering::Release);
+        self.pgid.store(self.pid, Ordering::Release);
+        Ok(self.pid)
+    }
+
+    /// 信号屏蔽字
+    pub fn signal_mask(&self) -> SigSet {
+        self.signals.lock().blocked
+    }
+
+    /// 设置屏蔽字（`SIGKILL`/`SIGSTOP` 不能屏蔽），解除屏蔽的待处理信号执行默认动作
+    pub fn set_signal_mask(&self, mask: SigSet) {
+        let mut signals = self.signals.lock();
+        signals.blocked = SigSet(mask.0 & !SigSet::UNBLOCKABLE.0);
+        let unblocked = SigSet(!signals.blocked.0);
+        let mut delivered = Vec::new();
+        while let Some(info) = signals.dequeue(unblocked) {
+            delivered.push(info.signo);
+        }
+        drop(signals);
+        for sig in delivered {
+            self.default_action(sig);
+        }
+    }
+
+    /// 待处理信号的集合
+    pub fn pending_signals(&self) -> SigSet {
+        self.signals.lock().pending()
+    }
+
+    /// 取出 `mask` 中的一个待处理信号
+    pub fn dequeue_signal(&self, mask: SigSet) -> Option<SigInfo> {
+        self.signals.lock().dequeue(mask)
+    }
+
+    /// 有信号排队时唤醒的等待队列
+    pub fn signal_wait(&self) -> &Arc<WaitQueue> {
+        &self.signal_wait
+    }
+
+    /// 是否正在退出
+    pub fn is_exiting(&self) -> bool {
+        self.exiting.load(Ordering::Acquire)
+    }
+
+    /// 是否所有线程都已退出
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Acquire)
+    }
+
+    /// 退出码
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code.load(Ordering::Relaxed)
+    }
+
+    /// 是否跟踪系统调用
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.load(Ordering::Relaxed)
+    }
+
+    /// 开关系统调用跟踪
+    pub fn set_tracing(&self, enabled: bool) {
+        self.tracing.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 当前的系统调用过滤器
+    pub fn syscall_filter(&self) -> Option<Arc<SyscallFilter>> {
+        self.syscall_filter.lock().clone()
+    }
+
+    /// 安装系统调用过滤器，已有过滤器（包括继承自父进程的）时返回 `PermissionDenied`
+    pub fn set_syscall_filter(&self, filter: Arc<SyscallFilter>) -> Result<(), KernelError> {
+        let mut current = self.syscall_filter.lock();
+        if current.is_some() {
+            return Err(KernelError::PermissionDenied);
+        }
+        *current = Some(filter);
+        Ok(())
+    }
+
+    /// 按 `options` 取出（`take` 为假时只查看）尚未报告的停止或继续事件
+    fn pending_job_event(&self, options: WaitOptions, take: bool) -> Option<ChildEvent> {
+        let mut event = self.job_event.lock();
+        let wanted = match *event {
+            Some(ChildEvent::Stopped(_)) => options.contains(WaitOptions::UNTRACED),
+            Some(ChildEvent::Continued) => options.contains(WaitOptions::CONTINUED),
+            _ => false,
+        };
+        match wanted {
+            true if take => event.take(),
+            true => *event,
+            false => None,
+        }
+    }
+
+    /// 等待子进程的状态变化，退出的子进程被回收
+    ///
+    /// 停止与继续只在 `options` 要求时报告，每次变化报告一次。
+    /// 没有符合条件的子进程时返回 `NotFound`；`NOHANG` 且没有状态变化时返回 `None`
+    pub fn wait_child(
+        &self,
+        target: WaitTarget,
+        options: WaitOptions,
+    ) -> Result<Option<(usize, ChildEvent)>, KernelError> {
+        let matches = |child: &Arc<Process>| match target {
+            WaitTarget::Any => true,
+            WaitTarget::Pid(pid) => child.pid == pid,
+            WaitTarget::Group(pgid) => child.pgid() == pgid,
+        };
+        let changed = |child: &Arc<Process>| child.has_exited() || child.pending_job_event(options, false).is_some();
+
+        if !options.contains(WaitOptions::NOHANG) {
+            self.child_exited.wait_until(|| {
+                let children = self.children.lock();
+                !children.iter().any(matches) || children.iter().any(|c| matches(c) && changed(c))
+            });
+        }
+
+        let mut children = self.children.lock();
+        if !children.iter().any(matches) {
+            return Err(KernelError::NotFound);
+        }
+        let Some(index) = children.iter().position(|c| matches(c) && changed(c)) else {
+            return Ok(None);
+        };
+        if children[index].has_exited() {
+            let child = children.remove(index);
+            return Ok(Some((child.pid, ChildEvent::Exited(child.exit_code()))));
+        }
+        let child = &children[index];
+        Ok(child.pending_job_event(options, true).map(|event| (child.pid, event)))
+    }
+
+    /// 等待指定子进程退出，不回收（vfork）
+    pub fn wait_child_exit(&self, child: &Process) {
+        self.child_exited.wait_until(|| child.has_exited());
+    }
+
+    /// 线程退出，最后一个线程退出时通知父进程
+    fn thread_exit(&self) {
+        if self.live_threads.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        self.exited.store(true, Ordering::Release);
+        if self.pid == TaskId::INIT.0 {
+            panic!("init进程退出（退出状态 {:#x}）", self.exit_code());
+        }
+        if self.is_session_leader() {
+            crate::fs::tty::hangup_session(self.sid());
+        }
+        // 孤儿进程不再能被回收
+        self.children.lock().clear();
+        if let Some(parent) = self.parent() {
+            parent.child_exited.wake_all();
+        }
+    }
+}
+
+/// 任务退出时的进程清理，由 `exit_current` 调用
+pub(super) fn exit_thread(task: &Task) {
+    let process = match task.process() {
+        Some(process) => process,
+        None => return,
+    };
+
+    // CLONE_CHILD_CLEARTID：清零用户态的线程号，供pthread_join等待
+    let clear_child_tid = task.clear_child_tid();
+    if clear_child_tid != 0 {
+        let _ = write_user(clear_child_tid, &0u32);
+    }
+    process.thread_exit();
+}
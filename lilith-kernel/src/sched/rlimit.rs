@@ -0,0 +1,119 @@
+//! 进程的资源限制
+//!
+//! 本模块实现了每个进程的资源限制（rlimit），编号与语义与Linux一致，包括：
+//! - 每种资源有软限制与硬限制，软限制不能超过硬限制
+//! - 非特权进程只能降低硬限制，提高硬限制需要 `CAP_SYS_RESOURCE`
+//! - 子进程继承父进程的限制
+//!
+//! 目前只有 `RLIMIT_MEMLOCK` 被检查（见 `mm::vma` 的mlock），其他资源的限制只保存
+
+use crate::error::KernelError;
+
+/// 资源编号
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIMIT_LOCKS: usize = 10;
+pub const RLIMIT_SIGPENDING: usize = 11;
+pub const RLIMIT_MSGQUEUE: usize = 12;
+pub const RLIMIT_NICE: usize = 13;
+pub const RLIMIT_RTPRIO: usize = 14;
+pub const RLIMIT_RTTIME: usize = 15;
+
+/// 资源的种数
+pub const RLIM_NLIMITS: usize = 16;
+
+/// 不限制
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// 默认可以锁定的内存字节数（与Linux一致）
+const MLOCK_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// 一种资源的限制，布局与 `struct rlimit64` 一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rlimit {
+    /// 软限制
+    pub cur: u64,
+    /// 硬限制
+    pub max: u64,
+}
+
+impl Rlimit {
+    pub const INFINITY: Self = Self {
+        cur: RLIM_INFINITY,
+        max: RLIM_INFINITY,
+    };
+
+    /// 软限制换算为字节数，不限制时返回 `None`
+    pub fn cur_bytes(&self) -> Option<usize> {
+        (self.cur != RLIM_INFINITY).then(|| usize::try_from(self.cur).unwrap_or(usize::MAX))
+    }
+}
+
+/// 进程的全部资源限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits([Rlimit; RLIM_NLIMITS]);
+
+impl ResourceLimits {
+    /// 第一个进程的限制：除锁定内存外都不限制
+    pub const DEFAULT: Self = {
+        let mut limits = [Rlimit::INFINITY; RLIM_NLIMITS];
+        limits[RLIMIT_MEMLOCK] = Rlimit {
+            cur: MLOCK_LIMIT,
+            max: MLOCK_LIMIT,
+        };
+        Self(limits)
+    };
+
+    /// 资源 `resource` 的限制，编号无效时返回 `InvalidArgument`
+    pub fn get(&self, resource: usize) -> Result<Rlimit, KernelError> {
+        self.0.get(resource).copied().ok_or(KernelError::InvalidArgument)
+    }
+
+    /// 设置资源 `resource` 的限制，返回原来的限制
+    ///
+    /// 软限制超过硬限制时返回 `InvalidArgument`，`privileged` 为假时提高硬限制返回 `PermissionDenied`
+    pub fn set(&mut self, resource: usize, new: Rlimit, privileged: bool) -> Result<Rlimit, KernelError> {
+        let limit = self.0.get_mut(resource).ok_or(KernelError::InvalidArgument)?;
+        if new.cur > new.max {
+            return Err(KernelError::InvalidArgument);
+        }
+        if new.max > limit.max && !privileged {
+            return Err(KernelError::PermissionDenied);
+        }
+        Ok(core::mem::replace(limit, new))
+    }
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+crate::kernel_test! {
+    fn rlimit_set_checks_hard_limit() {
+        let mut limits = ResourceLimits::DEFAULT;
+        let lower = Rlimit { cur: 4096, max: 8192 };
+        assert_eq!(limits.get(RLIMIT_MEMLOCK).unwrap().cur_bytes(), Some(MLOCK_LIMIT as usize));
+        assert_eq!(limits.set(RLIMIT_MEMLOCK, lower, false).unwrap().max, MLOCK_LIMIT);
+        // 降低后不能再提高硬限制
+        let raise = Rlimit { cur: 4096, max: MLOCK_LIMIT };
+        assert_eq!(limits.set(RLIMIT_MEMLOCK, raise, false), Err(KernelError::PermissionDenied));
+        assert!(limits.set(RLIMIT_MEMLOCK, raise, true).is_ok());
+        assert_eq!(
+            limits.set(RLIMIT_MEMLOCK, Rlimit { cur: 2, max: 1 }, true),
+            Err(KernelError::InvalidArgument)
+        );
+        assert_eq!(limits.get(RLIM_NLIMITS), Err(KernelError::InvalidArgument));
+        assert_eq!(limits.get(RLIMIT_NOFILE).unwrap().cur_bytes(), None);
+    }
+}
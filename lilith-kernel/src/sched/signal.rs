@@ -0,0 +1,153 @@
+//! 进程信号
+//!
+//! 本模块实现了信号的编号、集合与每个进程的待处理信号，包括：
+//! - 信号编号与 `sigset_t` 的位布局与Linux一致（信号 `n` 对应第 `n-1` 位）
+//! - 每个进程的屏蔽字与待处理信号，标准信号不重复排队，实时信号按到达顺序排队
+//! - 未被屏蔽的信号立即执行默认动作：默认忽略的信号丢弃，停止信号停止进程，其余结束进程
+//!
+//! 尚不支持用户态信号处理函数，被屏蔽的信号留在队列中，
+//! 由signalfd或 `rt_sigpending` 取得，解除屏蔽时再执行默认动作
+
+use alloc::collections::VecDeque;
+
+/// 信号编号（取值与Linux一致）
+pub const SIGHUP: u32 = 1;
+pub const SIGINT: u32 = 2;
+pub const SIGQUIT: u32 = 3;
+pub const SIGILL: u32 = 4;
+pub const SIGTRAP: u32 = 5;
+pub const SIGABRT: u32 = 6;
+pub const SIGBUS: u32 = 7;
+pub const SIGFPE: u32 = 8;
+pub const SIGKILL: u32 = 9;
+pub const SIGUSR1: u32 = 10;
+pub const SIGSEGV: u32 = 11;
+pub const SIGUSR2: u32 = 12;
+pub const SIGPIPE: u32 = 13;
+pub const SIGALRM: u32 = 14;
+pub const SIGTERM: u32 = 15;
+pub const SIGCHLD: u32 = 17;
+pub const SIGCONT: u32 = 18;
+pub const SIGSTOP: u32 = 19;
+pub const SIGTSTP: u32 = 20;
+pub const SIGTTIN: u32 = 21;
+pub const SIGTTOU: u32 = 22;
+pub const SIGURG: u32 = 23;
+pub const SIGWINCH: u32 = 28;
+pub const SIGSYS: u32 = 31;
+
+/// 第一个实时信号
+pub const SIGRTMIN: u32 = 32;
+/// 信号个数（编号从1到 `NSIG`）
+pub const NSIG: u32 = 64;
+
+/// `si_code`：由kill发送
+pub const SI_USER: i32 = 0;
+/// `si_code`：由内核发送
+pub const SI_KERNEL: i32 = 0x80;
+
+/// 信号集合
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SigSet(pub u64);
+
+impl SigSet {
+    /// 空集合
+    pub const EMPTY: SigSet = SigSet(0);
+
+    /// 不能屏蔽的信号
+    pub const UNBLOCKABLE: SigSet = SigSet(1 << (SIGKILL - 1) | 1 << (SIGSTOP - 1));
+
+    /// 只含 `sig` 的集合
+    pub const fn of(sig: u32) -> SigSet {
+        SigSet(1 << (sig - 1))
+    }
+
+    /// 是否包含 `sig`
+    pub fn contains(&self, sig: u32) -> bool {
+        self.0 & SigSet::of(sig).0 != 0
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// 编号是否有效
+pub fn valid(sig: u32) -> bool {
+    (1..=NSIG).contains(&sig)
+}
+
+/// 默认动作是否为忽略
+pub fn ignored_by_default(sig: u32) -> bool {
+    matches!(sig, SIGCHLD | SIGCONT | SIGURG | SIGWINCH)
+}
+
+/// 默认动作是否为停止进程
+pub fn stops_by_default(sig: u32) -> bool {
+    matches!(sig, SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU)
+}
+
+/// 一个待处理信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigInfo {
+    /// 信号编号
+    pub signo: u32,
+    /// 来源（`SI_USER`/`SI_KERNEL`）
+    pub code: i32,
+    /// 发送者的进程号，内核发送时为0
+    pub pid: usize,
+    /// 发送者的实际用户号
+    pub uid: u32,
+}
+
+/// 进程的屏蔽字与待处理信号
+#[derive(Debug, Default)]
+pub struct SignalState {
+    /// 屏蔽字
+    pub blocked: SigSet,
+    /// 待处理信号，按到达顺序
+    queue: VecDeque<SigInfo>,
+}
+
+impl SignalState {
+    /// 待处理信号的集合
+    pub fn pending(&self) -> SigSet {
+        SigSet(self.queue.iter().fold(0, |set, info| set | SigSet::of(info.signo).0))
+    }
+
+    /// 加入队列，标准信号已在队列中时不重复加入
+    pub fn enqueue(&mut self, info: SigInfo) {
+        if info.signo < SIGRTMIN && self.pending().contains(info.signo) {
+            return;
+        }
+        self.queue.push_back(info);
+    }
+
+    /// 取出 `mask` 中编号最小的待处理信号，同一信号取最早到达的
+    pub fn dequeue(&mut self, mask: SigSet) -> Option<SigInfo> {
+        let signo = (self.pending().0 & mask.0).trailing_zeros() + 1;
+        if signo > NSIG {
+            return None;
+        }
+        let index = self.queue.iter().position(|info| info.signo == signo)?;
+        self.queue.remove(index)
+    }
+}
+
+crate::kernel_test! {
+    fn signal_queue_order() {
+        let user = |signo| SigInfo { signo, code: SI_USER, pid: 1, uid: 0 };
+        let mut state = SignalState::default();
+        state.enqueue(user(SIGUSR2));
+        state.enqueue(user(SIGUSR1));
+        state.enqueue(user(SIGUSR1));
+        state.enqueue(user(SIGRTMIN));
+        state.enqueue(user(SIGRTMIN));
+        assert_eq!(state.pending(), SigSet(SigSet::of(SIGUSR1).0 | SigSet::of(SIGUSR2).0 | SigSet::of(SIGRTMIN).0));
+
+        let all = SigSet(u64::MAX);
+        let order: alloc::vec::Vec<u32> = core::iter::from_fn(|| state.dequeue(all)).map(|info| info.signo).collect();
+        assert_eq!(order, [SIGUSR1, SIGUSR2, SIGRTMIN, SIGRTMIN]);
+    }
+}
@@ -0,0 +1,301 @@
+//! 任务结构
+//!
+//! 每个任务拥有独立的内核栈与保存的寄存器上下文
+//!
+//! 内核栈最低处写有金丝雀值，调度切换与陷入时检查，被覆盖说明栈已溢出
+
+use super::process::Process;
+use crate::arch::fpu::FpuContext;
+use crate::arch::vector::VectorContext;
+use crate::arch::{TaskContext, TrapFrame};
+#[cfg(feature = "ftrace")]
+use crate::debug::ftrace::RetStack;
+use crate::mm::vma::AddressSpace;
+use crate::perf::PerfCounts;
+#[cfg(feature = "lockdep")]
+use crate::sync::lockdep::HeldLocks;
+use crate::sync::SpinLockIrqSave;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 内核栈大小
+pub const KERNEL_STACK_SIZE: usize = 16 * 1024;
+
+/// 不在系统调用中
+const NO_SYSCALL: usize = usize::MAX;
+
+/// 没有绑定hart
+const NO_HART: usize = usize::MAX;
+
+/// 内核栈底的金丝雀值
+pub const STACK_END_MAGIC: u64 = 0x57ac_6e9d_57ac_6e9d;
+
+/// 任务编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(pub usize);
+
+impl TaskId {
+    /// init进程的编号，分配时跳过
+    pub const INIT: TaskId = TaskId(1);
+
+    /// 分配新的任务编号
+    pub fn alloc() -> Self {
+        loop {
+            let id = Self(NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed));
+            if id != Self::INIT {
+                return id;
+            }
+        }
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// 在运行队列中等待运行
+    Ready,
+    /// 正在某个hart上运行
+    Running,
+    /// 等待被唤醒
+    Blocked,
+    /// 已退出，等待回收
+    Exited,
+}
+
+/// 任务入口函数
+pub type TaskEntry = fn();
+
+/// 任务控制块
+pub struct Task {
+    /// 任务编号
+    pub id: TaskId,
+    /// 任务名
+    pub name: String,
+    /// 入口函数（引导任务没有入口）
+    pub(super) entry: Option<TaskEntry>,
+    /// 任务状态
+    pub(super) state: SpinLockIrqSave<TaskState>,
+    /// 是否仍在某个hart上执行（切换尚未完成）
+    pub(super) on_cpu: AtomicBool,
+    /// 用户地址空间，内核任务为 `None`
+    address_space: SpinLockIrqSave<Option<Arc<AddressSpace>>>,
+    /// 所属进程，内核任务为 `None`
+    process: SpinLockIrqSave<Option<Arc<Process>>>,
+    /// 退出时需要清零的用户地址（set_tid_address）
+    clear_child_tid: AtomicUsize,
+    /// 正在执行的系统调用号，不在系统调用中时为 `NO_SYSCALL`
+    syscall_nr: AtomicUsize,
+    /// 绑定的hart，可在任意hart上运行时为 `NO_HART`
+    bound_hart: AtomicUsize,
+    /// 保存的寄存器上下文
+    pub(super) context: UnsafeCell<TaskContext>,
+    /// 用户态的浮点状态
+    fpu: FpuContext,
+    /// 用户态的向量状态
+    vector: VectorContext,
+    /// 任务切换出去时持有的锁（睡眠锁可跨越切换）
+    #[cfg(feature = "lockdep")]
+    pub(super) held_locks: UnsafeCell<HeldLocks>,
+    /// 函数跟踪替换的返回地址
+    #[cfg(feature = "ftrace")]
+    pub(super) ret_stack: UnsafeCell<RetStack>,
+    /// 硬件性能计数器的累计值
+    perf_counts: PerfCounts,
+    /// 内核栈（引导任务使用启动栈）
+    stack: Vec<u8>,
+}
+
+// 上下文只在持有调度权的hart上访问，由 `on_cpu` 保证互斥
+unsafe impl Sync for Task {}
+unsafe impl Send for Task {}
+
+/// 任务编号分配器
+static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl Task {
+    /// 创建任务，首次运行时从 `start` 开始执行
+    pub(super) fn new(id: TaskId, name: &str, entry: Option<TaskEntry>, start: usize) -> Self {
+        let mut stack = vec![0u8; KERNEL_STACK_SIZE];
+        stack[..size_of::<u64>()].copy_from_slice(&STACK_END_MAGIC.to_ne_bytes());
+        // 栈顶按16字节对齐
+        let stack_top = (stack.as_ptr() as usize + KERNEL_STACK_SIZE) & !0xf;
+        Self {
+            id,
+            name: String::from(name),
+            entry,
+            state: SpinLockIrqSave::new(TaskState::Ready),
+            on_cpu: AtomicBool::new(false),
+            address_space: SpinLockIrqSave::new(None),
+            process: SpinLockIrqSave::new(None),
+            clear_child_tid: AtomicUsize::new(0),
+            syscall_nr: AtomicUsize::new(NO_SYSCALL),
+            bound_hart: AtomicUsize::new(NO_HART),
+            context: UnsafeCell::new(TaskContext::new(start, stack_top)),
+            fpu: FpuContext::new(id.0),
+            vector: VectorContext::new(id.0),
+            #[cfg(feature = "lockdep")]
+            held_locks: UnsafeCell::new(HeldLocks::new()),
+            #[cfg(feature = "ftrace")]
+            ret_stack: UnsafeCell::new(RetStack::new()),
+            perf_counts: PerfCounts::new(),
+            stack,
+        }
+    }
+
+    /// 将当前执行流包装为任务（用作hart的空闲任务）
+    pub(super) fn bootstrap(name: &str) -> Self {
+        let id = TaskId::alloc();
+        Self {
+            id,
+            name: String::from(name),
+            entry: None,
+            state: SpinLockIrqSave::new(TaskState::Running),
+            on_cpu: AtomicBool::new(true),
+            address_space: SpinLockIrqSave::new(None),
+            process: SpinLockIrqSave::new(None),
+            clear_child_tid: AtomicUsize::new(0),
+            syscall_nr: AtomicUsize::new(NO_SYSCALL),
+            bound_hart: AtomicUsize::new(NO_HART),
+            context: UnsafeCell::new(TaskContext::default()),
+            fpu: FpuContext::new(id.0),
+            vector: VectorContext::new(id.0),
+            #[cfg(feature = "lockdep")]
+            held_locks: UnsafeCell::new(HeldLocks::new()),
+            #[cfg(feature = "ftrace")]
+            ret_stack: UnsafeCell::new(RetStack::new()),
+            perf_counts: PerfCounts::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// 硬件性能计数器的累计值
+    pub fn perf_counts(&self) -> &PerfCounts {
+        &self.perf_counts
+    }
+
+    /// 用户态的浮点状态
+    pub fn fpu(&self) -> &FpuContext {
+        &self.fpu
+    }
+
+    /// 用户态的向量状态
+    pub fn vector(&self) -> &VectorContext {
+        &self.vector
+    }
+
+    /// 当前状态
+    pub fn state(&self) -> TaskState {
+        *self.state.lock()
+    }
+
+    /// 用户地址空间
+    pub fn address_space(&self) -> Option<Arc<AddressSpace>> {
+        self.address_space.lock().clone()
+    }
+
+    /// 替换用户地址空间（如exec），返回旧的地址空间
+    pub fn set_address_space(&self, address_space: Option<Arc<AddressSpace>>) -> Option<Arc<AddressSpace>> {
+        core::mem::replace(&mut *self.address_space.lock(), address_space)
+    }
+
+    /// 所属进程
+    pub fn process(&self) -> Option<Arc<Process>> {
+        self.process.lock().clone()
+    }
+
+    /// 设置所属进程，同时切换到进程的地址空间
+    pub fn set_process(&self, process: Arc<Process>) {
+        self.set_address_space(Some(process.address_space.clone()));
+        *self.process.lock() = Some(process);
+    }
+
+    /// 退出时需要清零的用户地址
+    pub fn clear_child_tid(&self) -> usize {
+        self.clear_child_tid.load(Ordering::Relaxed)
+    }
+
+    /// 设置退出时需要清零的用户地址
+    pub fn set_clear_child_tid(&self, addr: usize) {
+        self.clear_child_tid.store(addr, Ordering::Relaxed);
+    }
+
+    /// 正在执行的系统调用号
+    pub fn syscall(&self) -> Option<usize> {
+        match self.syscall_nr.load(Ordering::Relaxed) {
+            NO_SYSCALL => None,
+            nr => Some(nr),
+        }
+    }
+
+    /// 记录进入（`Some`）或离开（`None`）系统调用
+    pub fn set_syscall(&self, nr: Option<usize>) {
+        self.syscall_nr.store(nr.unwrap_or(NO_SYSCALL), Ordering::Relaxed);
+    }
+
+    /// 绑定的hart
+    pub fn bound_hart(&self) -> Option<usize> {
+        match self.bound_hart.load(Ordering::Relaxed) {
+            NO_HART => None,
+            hart => Some(hart),
+        }
+    }
+
+    /// 绑定到 `hart`（`None` 解除绑定），正在运行的任务在下一次调度时迁移
+    pub fn bind_to_hart(&self, hart: Option<usize>) {
+        self.bound_hart.store(hart.unwrap_or(NO_HART), Ordering::Relaxed);
+    }
+
+    /// 内核栈底（最低地址），使用启动栈的引导任务为0
+    pub fn kernel_stack_base(&self) -> usize {
+        if self.stack.is_empty() {
+            0
+        } else {
+            self.stack.as_ptr() as usize
+        }
+    }
+
+    /// 内核栈底的金丝雀值是否完好，引导任务总是完好
+    pub fn stack_canary_intact(&self) -> bool {
+        let base = self.kernel_stack_base();
+        base == 0 || stack_canary_intact(base)
+    }
+
+    /// 内核栈顶
+    pub fn kernel_stack_top(&self) -> usize {
+        (self.stack.as_ptr() as usize + self.stack.len()) & !0xf
+    }
+
+    /// 从用户态陷入时保存的现场，位于内核栈顶
+    pub fn user_frame(&self) -> *mut TrapFrame {
+        (self.kernel_stack_top() - size_of::<TrapFrame>()) as *mut TrapFrame
+    }
+}
+
+/// 栈底位于 `base` 的内核栈的金丝雀值是否完好
+///
+/// 直接读取内存而不借用任务的栈，栈可能正在被使用
+pub fn stack_canary_intact(base: usize) -> bool {
+    unsafe { core::ptr::read_unaligned(base as *const u64) == STACK_END_MAGIC }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("state", &self.state())
+            .finish()
+    }
+}
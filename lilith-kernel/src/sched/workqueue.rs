@@ -0,0 +1,215 @@
+//! 工作队列
+//!
+//! 本模块实现了把工作推迟到内核任务中执行的工作队列，语义与Linux的workqueue一致，包括：
+//! - 工作项（`Work`）封装一个函数，排队后由工作者任务（kworker）在任务上下文中执行，可以睡眠
+//! - 每个hart一个工作池，工作者任务绑定到该hart；另有一个不绑定hart的工作池，由多个工作者共同服务
+//! - 已在队列中的工作项再次排队不重复加入；执行开始前清除排队标记，执行期间可以再次排队
+//! - 延迟工作（`DelayedWork`）在定时器到期后排队，可以在到期前取消
+//!
+//! 排队与取消只使用关中断自旋锁，可以在中断处理函数中调用，把耗时的处理移出中断上下文。
+//! hart下线后其工作者不再绑定，池中的工作由其他hart执行
+
+use super::{Task, MAX_HARTS};
+use crate::arch::hart_id;
+use crate::sync::{SpinLockIrqSave, WaitQueue};
+use crate::time::timer::Timer;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+/// 不绑定hart的工作池的工作者数
+const UNBOUND_WORKERS: usize = 2;
+
+/// 工作函数
+pub type WorkFn = Box<dyn Fn() + Send + Sync>;
+
+/// 一个工作项
+pub struct Work {
+    /// 名称，用于调试
+    pub name: &'static str,
+    func: WorkFn,
+    /// 是否已在某个工作池的队列中
+    pending: AtomicBool,
+}
+
+impl Work {
+    /// 创建执行 `func` 的工作项
+    pub fn new<F: Fn() + Send + Sync + 'static>(name: &'static str, func: F) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            func: Box::new(func),
+            pending: AtomicBool::new(false),
+        })
+    }
+
+    /// 是否在队列中等待执行
+    pub fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::Acquire)
+    }
+}
+
+/// 工作池：待执行的工作项与等待工作的工作者
+struct WorkerPool {
+    queue: SpinLockIrqSave<VecDeque<Arc<Work>>>,
+    wait: WaitQueue,
+}
+
+impl WorkerPool {
+    const fn new() -> Self {
+        Self {
+            queue: SpinLockIrqSave::new(VecDeque::new()),
+            wait: WaitQueue::new(),
+        }
+    }
+
+    /// 加入队列并唤醒工作者，工作项已在队列中时返回 `false`
+    fn enqueue(&self, work: &Arc<Work>) -> bool {
+        if work.pending.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        self.queue.lock().push_back(work.clone());
+        self.wait.wake_one();
+        true
+    }
+
+    /// 取出下一个工作项并清除其排队标记
+    fn dequeue(&self) -> Option<Arc<Work>> {
+        let work = self.queue.lock().pop_front()?;
+        work.pending.store(false, Ordering::Release);
+        Some(work)
+    }
+
+    fn has_work(&self) -> bool {
+        !self.queue.lock().is_empty()
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const POOL_INIT: WorkerPool = WorkerPool::new();
+
+/// 每个hart的工作池
+static HART_POOLS: [WorkerPool; MAX_HARTS] = [POOL_INIT; MAX_HARTS];
+
+/// 不绑定hart的工作池
+static UNBOUND_POOL: WorkerPool = WorkerPool::new();
+
+/// 在当前hart的工作池中排队，工作项已在队列中时返回 `false`
+pub fn schedule_work(work: &Arc<Work>) -> bool {
+    queue_work_on(hart_id(), work)
+}
+
+/// 在 `hart` 的工作池中排队，`hart` 超出范围时在不绑定hart的工作池中排队
+pub fn queue_work_on(hart: usize, work: &Arc<Work>) -> bool {
+    HART_POOLS.get(hart).unwrap_or(&UNBOUND_POOL).enqueue(work)
+}
+
+/// 在不绑定hart的工作池中排队，适合执行时间长、不关心在哪个hart上执行的工作
+pub fn queue_work_unbound(work: &Arc<Work>) -> bool {
+    UNBOUND_POOL.enqueue(work)
+}
+
+/// 延迟工作：定时器到期后在到期的hart的工作池中排队
+pub struct DelayedWork {
+    work: Arc<Work>,
+    timer: SpinLockIrqSave<Option<Timer>>,
+}
+
+impl DelayedWork {
+    /// 创建执行 `func` 的延迟工作
+    pub fn new<F: Fn() + Send + Sync + 'static>(name: &'static str, func: F) -> Arc<Self> {
+        Arc::new(Self {
+            work: Work::new(name, func),
+            timer: SpinLockIrqSave::new(None),
+        })
+    }
+
+    /// 内部的工作项
+    pub fn work(&self) -> &Arc<Work> {
+        &self.work
+    }
+
+    /// 定时器是否尚未到期
+    pub fn is_timer_pending(&self) -> bool {
+        self.timer.lock().as_ref().is_some_and(Timer::is_pending)
+    }
+}
+
+/// 经过 `delay` 后排队，定时器未到期或工作项已在队列中时返回 `false`
+pub fn schedule_delayed_work(dwork: &Arc<DelayedWork>, delay: Duration) -> bool {
+    if delay.is_zero() {
+        return schedule_work(&dwork.work);
+    }
+    let mut timer = dwork.timer.lock();
+    if dwork.work.is_pending() || timer.as_ref().is_some_and(Timer::is_pending) {
+        return false;
+    }
+    let work = dwork.work.clone();
+    *timer = Some(Timer::schedule_after(delay, move || {
+        schedule_work(&work);
+    }));
+    true
+}
+
+/// 取消尚未到期的延迟工作，返回是否取消成功
+///
+/// 已经排队或正在执行的工作不受影响
+pub fn cancel_delayed_work(dwork: &DelayedWork) -> bool {
+    dwork.timer.lock().take().is_some_and(|timer| timer.cancel())
+}
+
+/// 工作者服务的工作池：绑定的工作者服务所绑定hart的池，其余服务不绑定hart的池
+fn worker_pool(task: Option<Arc<Task>>) -> &'static WorkerPool {
+    task.and_then(|task| task.bound_hart())
+        .and_then(|hart| HART_POOLS.get(hart))
+        .unwrap_or(&UNBOUND_POOL)
+}
+
+/// 工作者任务：依次执行池中的工作项，没有工作时睡眠
+fn worker_main() {
+    let pool = worker_pool(super::current());
+    loop {
+        pool.wait.wait_until(|| pool.has_work());
+        while let Some(work) = pool.dequeue() {
+            (work.func)();
+        }
+    }
+}
+
+/// 为每个hart创建绑定的工作者，并创建不绑定hart的工作者
+pub fn workqueue_init() {
+    for hart in 0..MAX_HARTS {
+        super::spawn_on(hart, &format!("kworker/{}", hart), worker_main);
+    }
+    for index in 0..UNBOUND_WORKERS {
+        super::spawn(&format!("kworker/u{}", index), worker_main);
+    }
+}
+
+crate::kernel_test! {
+    fn workqueue_pending_dedup() {
+        use core::sync::atomic::AtomicUsize;
+
+        let pool = WorkerPool::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let work = Work::new("test", move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert!(pool.enqueue(&work));
+        assert!(!pool.enqueue(&work));
+        assert!(work.is_pending());
+
+        let next = pool.dequeue().unwrap();
+        assert!(!next.is_pending());
+        // 执行期间可以再次排队
+        assert!(pool.enqueue(&work));
+        (next.func)();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert!(pool.dequeue().is_some());
+        assert!(pool.dequeue().is_none());
+    }
+}
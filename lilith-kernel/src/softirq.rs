@@ -0,0 +1,245 @@
+//! 软中断与tasklet
+//!
+//! 本模块实现了中断的下半部，语义与Linux的softirq一致，包括：
+//! - 固定的软中断号（定时器、网络收发、块设备完成、tasklet），处理函数在初始化时登记
+//! - 每个hart一个待处理位图，`raise_softirq` 置位后由本hart处理，不在hart间迁移
+//! - 中断返回前（`irq_exit`）处理本hart待处理的软中断，同一hart上不会重入
+//! - 一次最多处理 `MAX_SOFTIRQ_RESTART` 轮或 `MAX_SOFTIRQ_TIME_NS`，剩余的交给本hart的 ksoftirqd 任务，
+//!   避免持续到来的中断使任务得不到运行；在任务上下文中置位时同样唤醒 ksoftirqd
+//! - tasklet：基于软中断的一次性回调，同一tasklet不会在两个hart上同时执行
+//!
+//! 软中断处理函数总在关中断的情况下执行，不能睡眠
+
+use crate::arch::{hart_id, local_irq_restore, local_irq_save};
+use crate::sched::{self, MAX_HARTS};
+use crate::sync::{SpinLockIrqSave, WaitQueue};
+use crate::time;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 软中断号，数值越小越先处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum SoftIrq {
+    /// 到期定时器的回调
+    Timer = 0,
+    /// 网络发送完成
+    NetTx = 1,
+    /// 网络接收
+    NetRx = 2,
+    /// 块设备请求完成
+    Block = 3,
+    /// tasklet
+    Tasklet = 4,
+}
+
+/// 软中断的数量
+pub const NR_SOFTIRQS: usize = 5;
+
+/// 中断返回前最多处理的轮数
+const MAX_SOFTIRQ_RESTART: usize = 10;
+
+/// 中断返回前处理软中断的最长时间
+const MAX_SOFTIRQ_TIME_NS: u64 = 2_000_000;
+
+/// 软中断处理函数
+pub type SoftIrqHandler = fn();
+
+/// 各软中断的处理函数
+static HANDLERS: SpinLockIrqSave<[Option<SoftIrqHandler>; NR_SOFTIRQS]> = SpinLockIrqSave::new([None; NR_SOFTIRQS]);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const FALSE: AtomicBool = AtomicBool::new(false);
+#[allow(clippy::declare_interior_mutable_const)]
+const WAIT_INIT: WaitQueue = WaitQueue::new();
+
+/// 各hart待处理的软中断位图
+static PENDING: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+
+/// 各hart的中断嵌套深度
+static IRQ_DEPTH: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+
+/// 各hart是否正在处理软中断
+static IN_SOFTIRQ: [AtomicBool; MAX_HARTS] = [FALSE; MAX_HARTS];
+
+/// 各hart的 ksoftirqd 等待待处理软中断的队列
+static KSOFTIRQD_WAIT: [WaitQueue; MAX_HARTS] = [WAIT_INIT; MAX_HARTS];
+
+/// 登记软中断的处理函数，重复登记时替换
+pub fn open_softirq(nr: SoftIrq, handler: SoftIrqHandler) {
+    HANDLERS.lock()[nr as usize] = Some(handler);
+}
+
+/// 当前hart是否在中断或软中断中
+pub fn in_interrupt() -> bool {
+    let hart = hart_id();
+    IRQ_DEPTH[hart].load(Ordering::Relaxed) > 0 || IN_SOFTIRQ[hart].load(Ordering::Relaxed)
+}
+
+/// 在当前hart上置位软中断
+///
+/// 中断中置位的软中断在中断返回前处理，任务上下文中置位时唤醒 ksoftirqd
+pub fn raise_softirq(nr: SoftIrq) {
+    let hart = hart_id();
+    PENDING[hart].fetch_or(1 << nr as usize, Ordering::AcqRel);
+    if !in_interrupt() {
+        KSOFTIRQD_WAIT[hart].wake_all();
+    }
+}
+
+/// 当前hart待处理的软中断位图
+pub fn local_pending() -> usize {
+    PENDING[hart_id()].load(Ordering::Acquire)
+}
+
+/// 处理当前hart待处理的软中断，须在关中断时调用
+///
+/// 已在处理软中断时直接返回，处理完后仍有待处理的软中断时唤醒 ksoftirqd
+fn do_softirq() {
+    let hart = hart_id();
+    if IN_SOFTIRQ[hart].swap(true, Ordering::Acquire) {
+        return;
+    }
+    let deadline = time::monotonic_ns().saturating_add(MAX_SOFTIRQ_TIME_NS);
+    for _ in 0..MAX_SOFTIRQ_RESTART {
+        let pending = PENDING[hart].swap(0, Ordering::AcqRel);
+        if pending == 0 {
+            break;
+        }
+        let handlers = *HANDLERS.lock();
+        for (nr, handler) in handlers.iter().enumerate() {
+            if pending & (1 << nr) != 0 {
+                if let Some(handler) = handler {
+                    handler();
+                }
+            }
+        }
+        if time::monotonic_ns() >= deadline {
+            break;
+        }
+    }
+    IN_SOFTIRQ[hart].store(false, Ordering::Release);
+
+    if PENDING[hart].load(Ordering::Acquire) != 0 {
+        KSOFTIRQD_WAIT[hart].wake_all();
+    }
+}
+
+/// 进入中断处理，由陷入处理在分发中断前调用
+pub fn irq_enter() {
+    IRQ_DEPTH[hart_id()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// 离开中断处理：最外层中断返回前处理待处理的软中断
+pub fn irq_exit() {
+    let hart = hart_id();
+    if IRQ_DEPTH[hart].fetch_sub(1, Ordering::Relaxed) == 1 && PENDING[hart].load(Ordering::Acquire) != 0 {
+        do_softirq();
+    }
+}
+
+/// ksoftirqd：处理中断返回前没有处理完或在任务上下文中置位的软中断
+fn ksoftirqd_main() {
+    loop {
+        KSOFTIRQD_WAIT[hart_id()].wait_until(|| local_pending() != 0);
+        let flags = local_irq_save();
+        do_softirq();
+        local_irq_restore(flags);
+        // 持续有软中断时也让其他任务得到运行
+        sched::yield_now();
+    }
+}
+
+/// tasklet
+pub struct Tasklet {
+    /// 名称，用于调试
+    pub name: &'static str,
+    func: Box<dyn Fn() + Send + Sync>,
+    /// 是否已在某个hart的列表中
+    scheduled: AtomicBool,
+    /// 是否正在某个hart上执行
+    running: AtomicBool,
+}
+
+impl Tasklet {
+    /// 创建执行 `func` 的tasklet
+    pub fn new<F: Fn() + Send + Sync + 'static>(name: &'static str, func: F) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            func: Box::new(func),
+            scheduled: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+        })
+    }
+
+    /// 是否等待执行
+    pub fn is_scheduled(&self) -> bool {
+        self.scheduled.load(Ordering::Acquire)
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const TASKLETS_INIT: SpinLockIrqSave<VecDeque<Arc<Tasklet>>> = SpinLockIrqSave::new(VecDeque::new());
+
+/// 各hart待执行的tasklet
+static TASKLETS: [SpinLockIrqSave<VecDeque<Arc<Tasklet>>>; MAX_HARTS] = [TASKLETS_INIT; MAX_HARTS];
+
+/// 在当前hart上调度tasklet，已在等待执行时返回 `false`
+pub fn tasklet_schedule(tasklet: &Arc<Tasklet>) -> bool {
+    if tasklet.scheduled.swap(true, Ordering::AcqRel) {
+        return false;
+    }
+    TASKLETS[hart_id()].lock().push_back(tasklet.clone());
+    raise_softirq(SoftIrq::Tasklet);
+    true
+}
+
+/// tasklet软中断：执行当前hart列表中的tasklet，正在其他hart上执行的留到下一轮
+fn tasklet_action() {
+    let list = core::mem::take(&mut *TASKLETS[hart_id()].lock());
+    for tasklet in list {
+        if tasklet.running.swap(true, Ordering::Acquire) {
+            TASKLETS[hart_id()].lock().push_back(tasklet);
+            raise_softirq(SoftIrq::Tasklet);
+            continue;
+        }
+        // 执行前清除标记，执行期间可以再次调度
+        tasklet.scheduled.store(false, Ordering::Release);
+        (tasklet.func)();
+        tasklet.running.store(false, Ordering::Release);
+    }
+}
+
+/// 登记tasklet软中断，为每个hart创建绑定的 ksoftirqd
+pub fn softirq_init() {
+    open_softirq(SoftIrq::Tasklet, tasklet_action);
+    for hart in 0..MAX_HARTS {
+        sched::spawn_on(hart, &format!("ksoftirqd/{}", hart), ksoftirqd_main);
+    }
+}
+
+crate::kernel_test! {
+    fn softirq_tasklet_runs_once() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let tasklet = Tasklet::new("test", move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let flags = local_irq_save();
+        irq_enter();
+        assert!(in_interrupt());
+        assert!(tasklet_schedule(&tasklet));
+        assert!(!tasklet_schedule(&tasklet));
+        irq_exit();
+        local_irq_restore(flags);
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert!(!tasklet.is_scheduled());
+    }
+}
@@ -0,0 +1,253 @@
+//! 锁依赖检查（lockdep）
+//!
+//! 启用 `lockdep` 特性后，每次加锁都会记录“持有A时获取B”的依赖边，
+//! 一旦新的加锁顺序与已记录的顺序构成环（如A→B与B→A），立即恐慌并
+//! 打印两条路径的加锁位置与调用栈，而不必等到真正死锁才发现问题：
+//! - 锁类以创建锁的源码位置区分（类似Linux的 `lock_class_key`），
+//!   同一位置创建的锁实例属于同一类，同类的不同实例之间不记录依赖
+//! - 记录依赖边时保存当时的调用栈，发现顺序反转时与当前调用栈一并输出
+//! - 已持有的锁按hart记录，任务切换时随任务保存与恢复
+//! - 尝试加锁（try_lock）不会阻塞，只记录持有而不检查依赖
+
+use crate::arch::{hart_id, local_irq_restore, local_irq_save};
+use crate::boot::emergency_print;
+use crate::debug::backtrace;
+use crate::sched::MAX_HARTS;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 锁类：创建锁的源码位置
+pub type LockClass = &'static Location<'static>;
+
+/// 每个执行流最多同时持有的锁数
+const MAX_HELD_LOCKS: usize = 32;
+
+/// 依赖边保存的调用栈深度
+const TRACE_DEPTH: usize = 16;
+
+/// 已持有的锁
+#[derive(Clone, Copy)]
+struct HeldLock {
+    class: LockClass,
+    /// 锁实例的地址
+    instance: usize,
+    location: &'static Location<'static>,
+}
+
+/// 执行流持有的锁栈
+#[derive(Clone, Copy)]
+pub struct HeldLocks {
+    locks: [Option<HeldLock>; MAX_HELD_LOCKS],
+    depth: usize,
+}
+
+impl HeldLocks {
+    /// 空锁栈
+    pub const fn new() -> Self {
+        Self {
+            locks: [None; MAX_HELD_LOCKS],
+            depth: 0,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &HeldLock> {
+        self.locks[..self.depth].iter().flatten()
+    }
+}
+
+impl Default for HeldLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 依赖边：持有 `held` 时在 `acquired` 处获取了另一个锁
+#[derive(Clone, Copy)]
+struct Dependency {
+    held_at: &'static Location<'static>,
+    acquired_at: &'static Location<'static>,
+    /// 记录依赖时的调用栈
+    trace: [usize; TRACE_DEPTH],
+}
+
+/// 每个hart当前的锁栈，只在关中断时访问
+struct PerHart([UnsafeCell<HeldLocks>; MAX_HARTS]);
+
+unsafe impl Sync for PerHart {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: UnsafeCell<HeldLocks> = UnsafeCell::new(HeldLocks::new());
+
+static HELD: PerHart = PerHart([EMPTY; MAX_HARTS]);
+
+/// 依赖图：先持有的锁类 → 后获取的锁类 → 首次出现的位置
+type Graph = BTreeMap<LockClass, BTreeMap<LockClass, Dependency>>;
+
+/// 依赖图，使用 `spin::Mutex`，避免检查自身时递归进入lockdep
+static GRAPH: spin::Mutex<Graph> = spin::Mutex::new(BTreeMap::new());
+
+/// 堆分配器就绪后才开始记录
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 在关中断状态下访问当前hart的锁栈
+fn with_held<R, F: FnOnce(&mut HeldLocks) -> R>(f: F) -> R {
+    let flags = local_irq_save();
+    let result = f(unsafe { &mut *HELD.0[hart_id()].get() });
+    local_irq_restore(flags);
+    result
+}
+
+/// 依赖图中是否存在从 `from` 到 `to` 的路径，存在时返回路径上的第一条边
+fn find_path(graph: &Graph, from: LockClass, to: LockClass) -> Option<Dependency> {
+    let mut visited = Vec::new();
+    let mut stack: Vec<(LockClass, Dependency)> = match graph.get(from) {
+        Some(edges) => edges.iter().map(|(&next, &dep)| (next, dep)).collect(),
+        None => return None,
+    };
+
+    while let Some((next, first)) = stack.pop() {
+        if next == to {
+            return Some(first);
+        }
+        if visited.contains(&next) {
+            continue;
+        }
+        visited.push(next);
+        if let Some(edges) = graph.get(next) {
+            stack.extend(edges.keys().map(|&class| (class, first)));
+        }
+    }
+    None
+}
+
+/// 记录调用栈，跳过lockdep自身的一层
+#[inline(always)]
+fn capture() -> [usize; TRACE_DEPTH] {
+    let mut trace = [0; TRACE_DEPTH];
+    let mut depth = 0;
+    backtrace::walk(crate::arch::frame_pointer(), |ra| {
+        if depth >= 1 {
+            trace[depth - 1] = ra;
+        }
+        depth += 1;
+        depth <= TRACE_DEPTH
+    });
+    trace
+}
+
+/// 获取锁之前调用：检查加锁顺序并记录依赖
+///
+/// `class` 为锁类，`instance` 为锁实例的地址
+#[track_caller]
+pub fn acquire(class: LockClass, instance: usize, check: bool) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    let location = Location::caller();
+
+    let violation = with_held(|held| {
+        let mut violation = None;
+        if check {
+            let mut graph = GRAPH.lock();
+            for lock in held.iter() {
+                if lock.instance == instance {
+                    violation = Some((*lock, None));
+                    break;
+                }
+                // 同类的不同实例（如父子目录项）之间的顺序由调用者保证
+                if lock.class == class {
+                    continue;
+                }
+                // 已存在 class → lock.class 的路径时，再加入 lock.class → class 就构成环
+                if let Some(dep) = find_path(&graph, class, lock.class) {
+                    violation = Some((*lock, Some(dep)));
+                    break;
+                }
+                graph
+                    .entry(lock.class)
+                    .or_default()
+                    .entry(class)
+                    .or_insert_with(|| Dependency {
+                        held_at: lock.location,
+                        acquired_at: location,
+                        trace: capture(),
+                    });
+            }
+        }
+
+        if violation.is_none() {
+            if held.depth == MAX_HELD_LOCKS {
+                ENABLED.store(false, Ordering::Release);
+                crate::early_println!("lockdep: 持有的锁超过 {} 个，停止检查", MAX_HELD_LOCKS);
+            } else {
+                held.locks[held.depth] = Some(HeldLock {
+                    class,
+                    instance,
+                    location,
+                });
+                held.depth += 1;
+            }
+        }
+        violation
+    });
+
+    if let Some((held, recorded)) = violation {
+        // 恐慌处理可能再次加锁，先关闭检查
+        ENABLED.store(false, Ordering::Release);
+        match recorded {
+            None => panic!(
+                "lockdep: 递归获取锁 {:#x}（类 {}）\n  首次获取于 {}\n  再次获取于 {}",
+                instance, class, held.location, location
+            ),
+            Some(dep) => {
+                // 两条路径的调用栈直接输出到控制台，不经过可能被持有的日志锁
+                emergency_print(format_args!("lockdep: 当前路径的"));
+                backtrace::print_backtrace(emergency_print);
+                emergency_print(format_args!("lockdep: 已记录路径的"));
+                backtrace::print_trace(&dep.trace, emergency_print);
+                panic!(
+                    "lockdep: 加锁顺序反转\n  当前路径: 持有类 {}（获取于 {}）时获取类 {}（于 {}）\n  已记录路径: 持有类 {}（获取于 {}）时获取（于 {}）",
+                    held.class, held.location, class, location, class, dep.held_at, dep.acquired_at
+                )
+            }
+        }
+    }
+}
+
+/// 释放锁之后调用，`instance` 为锁实例的地址
+pub fn release(instance: usize) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+
+    with_held(|held| {
+        // 锁不一定按获取的逆序释放
+        let position = held.locks[..held.depth]
+            .iter()
+            .rposition(|lock| lock.map_or(false, |lock| lock.instance == instance));
+        if let Some(index) = position {
+            held.locks.copy_within(index + 1..held.depth, index);
+            held.depth -= 1;
+            held.locks[held.depth] = None;
+        }
+    });
+}
+
+/// 任务切换前保存当前hart的锁栈
+pub fn save_held(slot: &mut HeldLocks) {
+    with_held(|held| *slot = *held);
+}
+
+/// 任务切换后恢复被切换进来的任务的锁栈
+pub fn restore_held(slot: &HeldLocks) {
+    with_held(|held| *held = *slot);
+}
+
+/// 开始检查（需要堆分配器）
+pub fn lockdep_init() {
+    ENABLED.store(true, Ordering::Release);
+    crate::early_println!("lockdep: 锁依赖检查已启用");
+}
@@ -0,0 +1,27 @@
+//! 同步原语模块
+//!
+//! 本模块实现了内核使用的同步原语，包括：
+//! - 自旋锁
+//! - 关中断自旋锁（可在中断上下文与任务上下文之间共享）
+//! - 等待队列
+//! - 睡眠互斥锁
+//! - 睡眠读写锁（写者优先）
+//! - RCU（读侧无锁，写者延迟释放旧版本）
+//! - 顺序锁（读者不阻塞写者）
+//! - 锁依赖检查（`lockdep` 特性）
+
+pub mod spinlock;
+pub mod wait_queue;
+pub mod mutex;
+pub mod rwlock;
+pub mod rcu;
+pub mod seqlock;
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+
+pub use spinlock::*;
+pub use wait_queue::*;
+pub use mutex::*;
+pub use rwlock::*;
+pub use rcu::*;
+pub use seqlock::*;
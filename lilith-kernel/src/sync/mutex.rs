@@ -0,0 +1,141 @@
+//! 睡眠互斥锁
+//!
+//! 适用于持有时间较长的锁（如文件系统与块设备层的状态）：
+//! 争用时先以指数退避短暂自旋，仍未获得锁则挂入等待队列睡眠，
+//! 不占用处理器。不能在中断上下文中使用
+
+#[cfg(feature = "lockdep")]
+use super::lockdep;
+use super::WaitQueue;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 睡眠前的最大自旋轮数
+const SPIN_ROUNDS: u32 = 6;
+
+/// 睡眠互斥锁
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    /// lockdep锁类，即创建锁的源码位置
+    #[cfg(feature = "lockdep")]
+    class: lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+/// 互斥锁守卫，离开作用域时释放锁并唤醒一个等待者
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    /// 创建互斥锁
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            #[cfg(feature = "lockdep")]
+            class: core::panic::Location::caller(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// 取出被保护的数据
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// 获取锁，争用时睡眠
+    #[track_caller]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.class, self.instance(), true);
+
+        if !self.try_acquire() && !self.spin_acquire() {
+            self.waiters.wait_until(|| self.try_acquire());
+        }
+        MutexGuard { lock: self }
+    }
+
+    /// 尝试获取锁，失败时立即返回 `None`
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        if self.try_acquire() {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(self.class, self.instance(), false);
+            Some(MutexGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// 锁当前是否被持有
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// 获取可变引用（独占访问时无需加锁）
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 以指数退避自旋若干轮，持锁者通常很快释放
+    fn spin_acquire(&self) -> bool {
+        for round in 0..SPIN_ROUNDS {
+            for _ in 0..(1 << round) {
+                spin_loop();
+            }
+            if !self.is_locked() && self.try_acquire() {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.instance());
+        self.waiters.wake_one();
+    }
+
+    /// lockdep中的锁实例（以锁的地址区分）
+    #[cfg(feature = "lockdep")]
+    fn instance(&self) -> usize {
+        self as *const Self as *const u8 as usize
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
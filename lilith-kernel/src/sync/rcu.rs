@@ -0,0 +1,240 @@
+//! RCU（读-复制-更新）
+//!
+//! 读者无需加锁即可访问受保护的数据，写者复制并发布新版本，
+//! 旧版本在宽限期结束、所有读者都已离开后才被释放：
+//! - `rcu_read_lock` 标记读侧临界区并禁止抢占，临界区内不能睡眠或让出处理器
+//! - hart在调度切换或时钟节拍时若不在读侧临界区内，即经过一次静止状态
+//! - 所有在线hart都经过静止状态后，宽限期结束
+//! - `call_rcu` 在宽限期结束后执行回调，`synchronize_rcu` 阻塞等待宽限期结束
+
+use super::SpinLockIrqSave;
+use crate::arch::hart_id;
+use crate::sched::{self, MAX_HARTS};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+/// 宽限期结束后执行的回调
+pub type RcuCallback = Box<dyn FnOnce() + Send>;
+
+/// 已开始的宽限期序号
+static GP_STARTED: AtomicU64 = AtomicU64::new(0);
+/// 已完成的宽限期序号
+static GP_COMPLETED: AtomicU64 = AtomicU64::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_U64: AtomicU64 = AtomicU64::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_USIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// 各hart最近一次报告静止状态时的宽限期序号
+static QS_REPORTED: [AtomicU64; MAX_HARTS] = [ZERO_U64; MAX_HARTS];
+/// 各hart读侧临界区嵌套深度
+static READ_NESTING: [AtomicUsize; MAX_HARTS] = [ZERO_USIZE; MAX_HARTS];
+/// 参与宽限期检测的hart位图
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// 等待宽限期的回调及其所需的宽限期序号
+static CALLBACKS: SpinLockIrqSave<VecDeque<(u64, RcuCallback)>> = SpinLockIrqSave::new(VecDeque::new());
+
+/// 读侧临界区守卫
+pub struct RcuReadGuard {
+    // 临界区绑定在当前hart上，守卫不能跨线程传递
+    _not_send: PhantomData<*const ()>,
+}
+
+/// 进入读侧临界区
+pub fn rcu_read_lock() -> RcuReadGuard {
+    // 禁止抢占后任务留在当前hart上，按hart记录的嵌套深度才能与守卫对应
+    sched::preempt_disable();
+    READ_NESTING[hart_id()].fetch_add(1, Ordering::Relaxed);
+    RcuReadGuard {
+        _not_send: PhantomData,
+    }
+}
+
+impl Drop for RcuReadGuard {
+    fn drop(&mut self) {
+        READ_NESTING[hart_id()].fetch_sub(1, Ordering::Release);
+        sched::preempt_enable();
+    }
+}
+
+/// 将当前hart加入宽限期检测
+pub fn rcu_online() {
+    let hart = hart_id();
+    QS_REPORTED[hart].store(GP_STARTED.load(Ordering::Acquire), Ordering::Relaxed);
+    ONLINE_HARTS.fetch_or(1 << hart, Ordering::AcqRel);
+}
+
+/// 将当前hart移出宽限期检测（如hart下线前）
+pub fn rcu_offline() {
+    ONLINE_HARTS.fetch_and(!(1 << hart_id()), Ordering::AcqRel);
+    try_complete_gp();
+}
+
+/// 报告当前hart的静止状态（调度切换时调用）
+pub fn rcu_note_qs() {
+    let hart = hart_id();
+    if READ_NESTING[hart].load(Ordering::Acquire) != 0 {
+        return;
+    }
+    QS_REPORTED[hart].store(GP_STARTED.load(Ordering::Acquire), Ordering::Release);
+    try_complete_gp();
+}
+
+/// 所有在线hart都已报告时结束当前宽限期
+fn try_complete_gp() {
+    let started = GP_STARTED.load(Ordering::Acquire);
+    if GP_COMPLETED.load(Ordering::Acquire) >= started {
+        return;
+    }
+
+    let online = ONLINE_HARTS.load(Ordering::Acquire);
+    let all_reported = (0..MAX_HARTS)
+        .filter(|hart| online & (1 << hart) != 0)
+        .all(|hart| QS_REPORTED[hart].load(Ordering::Acquire) >= started);
+    if all_reported {
+        GP_COMPLETED.fetch_max(started, Ordering::AcqRel);
+    }
+}
+
+/// 没有进行中的宽限期时开始新的宽限期
+fn start_gp_if_idle() {
+    let completed = GP_COMPLETED.load(Ordering::Acquire);
+    let _ = GP_STARTED.compare_exchange(completed, completed + 1, Ordering::AcqRel, Ordering::Relaxed);
+}
+
+/// 登记回调，在此后开始的宽限期结束后执行
+pub fn call_rcu(callback: RcuCallback) {
+    let mut callbacks = CALLBACKS.lock();
+    // 必须等待在登记之后开始的宽限期，进行中的宽限期不足以覆盖当前读者
+    let target = GP_STARTED.load(Ordering::Acquire) + 1;
+    callbacks.push_back((target, callback));
+    start_gp_if_idle();
+}
+
+/// 执行宽限期已结束的回调
+fn process_callbacks() {
+    let completed = GP_COMPLETED.load(Ordering::Acquire);
+    loop {
+        let callback = {
+            let mut callbacks = CALLBACKS.lock();
+            match callbacks.front() {
+                Some((target, _)) if *target <= completed => callbacks.pop_front().map(|(_, cb)| cb),
+                _ => {
+                    // 仍有回调等待时推进到下一个宽限期
+                    if !callbacks.is_empty() {
+                        start_gp_if_idle();
+                    }
+                    None
+                }
+            }
+        };
+        match callback {
+            Some(callback) => callback(),
+            None => break,
+        }
+    }
+}
+
+/// 时钟节拍处理：报告静止状态并执行到期回调
+pub fn rcu_tick() {
+    rcu_note_qs();
+    process_callbacks();
+}
+
+/// 阻塞等待一个完整的宽限期
+///
+/// 不能在读侧临界区内调用
+pub fn synchronize_rcu() {
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    call_rcu(Box::new(move || flag.store(true, Ordering::Release)));
+
+    // 让出处理器即经过静止状态；其他hart在调度切换或时钟节拍时报告
+    while !done.load(Ordering::Acquire) {
+        rcu_note_qs();
+        process_callbacks();
+        sched::yield_now();
+    }
+}
+
+/// RCU保护的指针
+///
+/// 读者通过 `read` 获得在读侧临界区内有效的引用；
+/// 写者通过 `replace`/`update` 发布新版本，旧版本在宽限期后释放
+pub struct RcuCell<T: Send + Sync + 'static> {
+    ptr: AtomicPtr<T>,
+    /// 串行化写者
+    writer: SpinLockIrqSave<()>,
+}
+
+unsafe impl<T: Send + Sync + 'static> Sync for RcuCell<T> {}
+unsafe impl<T: Send + Sync + 'static> Send for RcuCell<T> {}
+
+impl<T: Send + Sync + 'static> RcuCell<T> {
+    /// 创建空指针
+    pub const fn empty() -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            writer: SpinLockIrqSave::new(()),
+        }
+    }
+
+    /// 创建指向 `value` 的指针
+    pub fn new(value: T) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            writer: SpinLockIrqSave::new(()),
+        }
+    }
+
+    /// 在读侧临界区内读取当前版本
+    pub fn read<'a>(&'a self, _guard: &'a RcuReadGuard) -> Option<&'a T> {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        unsafe { ptr.as_ref() }
+    }
+
+    /// 发布新版本，旧版本在宽限期后释放
+    pub fn replace(&self, value: Option<T>) {
+        let _writer = self.writer.lock();
+        self.publish(value);
+    }
+
+    /// 基于当前版本生成并发布新版本
+    pub fn update<F: FnOnce(Option<&T>) -> Option<T>>(&self, f: F) {
+        let _writer = self.writer.lock();
+        // 持有写者锁时当前版本不会被释放
+        let current = unsafe { self.ptr.load(Ordering::Acquire).as_ref() };
+        let value = f(current);
+        self.publish(value);
+    }
+
+    fn publish(&self, value: Option<T>) {
+        let new = value.map_or(ptr::null_mut(), |value| Box::into_raw(Box::new(value)));
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            let old = old as usize;
+            call_rcu(Box::new(move || unsafe {
+                drop(Box::from_raw(old as *mut T));
+            }));
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+        if !ptr.is_null() {
+            // 独占访问，但读者可能仍持有旧引用，同样延迟释放
+            let ptr = ptr as usize;
+            call_rcu(Box::new(move || unsafe {
+                drop(Box::from_raw(ptr as *mut T));
+            }));
+        }
+    }
+}
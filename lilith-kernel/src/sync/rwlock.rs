@@ -0,0 +1,204 @@
+//! 睡眠读写锁
+//!
+//! 适用于读多写少的数据（如挂载表、进程表）：
+//! - 多个读者可同时持有锁
+//! - 写者优先：有写者等待时新的读者不再进入，避免写者饥饿
+//! - 获取失败时挂入等待队列睡眠，不能在中断上下文中使用
+
+#[cfg(feature = "lockdep")]
+use super::lockdep;
+use super::WaitQueue;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 写者持有锁时的状态值，其余值表示读者数量
+const WRITER: usize = usize::MAX;
+
+/// 睡眠读写锁
+pub struct RwLock<T: ?Sized> {
+    /// 0：空闲；`WRITER`：写者持有；其他：读者数量
+    state: AtomicUsize,
+    /// 正在等待的写者数量
+    waiting_writers: AtomicUsize,
+    readers: WaitQueue,
+    writers: WaitQueue,
+    /// lockdep锁类，即创建锁的源码位置
+    #[cfg(feature = "lockdep")]
+    class: lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// 读守卫
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+/// 写守卫
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// 创建读写锁
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            waiting_writers: AtomicUsize::new(0),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+            #[cfg(feature = "lockdep")]
+            class: core::panic::Location::caller(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// 取出被保护的数据
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// 获取读锁
+    ///
+    /// 写者优先意味着递归获取读锁可能死锁，lockdep同样会报告
+    #[track_caller]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.class, self.instance(), true);
+
+        if !self.try_acquire_read() {
+            self.readers.wait_until(|| self.try_acquire_read());
+        }
+        RwLockReadGuard { lock: self }
+    }
+
+    /// 获取写锁
+    #[track_caller]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.class, self.instance(), true);
+
+        if !self.try_acquire_write() {
+            self.waiting_writers.fetch_add(1, Ordering::Relaxed);
+            self.writers.wait_until(|| self.try_acquire_write());
+            self.waiting_writers.fetch_sub(1, Ordering::Relaxed);
+        }
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// 尝试获取读锁
+    #[track_caller]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        if self.try_acquire_read() {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(self.class, self.instance(), false);
+            Some(RwLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// 尝试获取写锁
+    #[track_caller]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        if self.try_acquire_write() {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(self.class, self.instance(), false);
+            Some(RwLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// 获取可变引用（独占访问时无需加锁）
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// lockdep中的锁实例（以锁的地址区分）
+    #[cfg(feature = "lockdep")]
+    fn instance(&self) -> usize {
+        self as *const Self as *const u8 as usize
+    }
+
+    fn try_acquire_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state == WRITER || state == WRITER - 1 || self.waiting_writers.load(Ordering::Relaxed) > 0 {
+                return false;
+            }
+            match self
+                .state
+                .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    fn try_acquire_write(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn read_unlock(&self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.instance());
+        // 最后一个读者离开时交给等待的写者
+        if self.state.fetch_sub(1, Ordering::Release) == 1 {
+            self.writers.wake_one();
+        }
+    }
+
+    fn write_unlock(&self) {
+        self.state.store(0, Ordering::Release);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.instance());
+        if self.waiting_writers.load(Ordering::Relaxed) > 0 && self.writers.wake_one() {
+            return;
+        }
+        self.readers.wake_all();
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
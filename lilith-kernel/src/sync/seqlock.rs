@@ -0,0 +1,68 @@
+//! 顺序锁
+//!
+//! 适用于读多写少、数据量小的场景（如时钟偏移）：
+//! 写者递增序号后修改数据，读者读取前后序号一致才采用读到的数据，
+//! 读者从不阻塞写者，写者也不必等待读者
+
+use super::SpinLockIrqSave;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ptr;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+/// 顺序锁
+///
+/// 布局固定（序号在前、数据在后），vDSO数据页中的顺序锁由用户态直接读取
+#[repr(C)]
+pub struct SeqLock<T: Copy> {
+    /// 写者进行中时为奇数
+    seq: AtomicUsize,
+    data: UnsafeCell<T>,
+    /// 串行化写者
+    writer: SpinLockIrqSave<()>,
+}
+
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// 创建顺序锁
+    pub const fn new(data: T) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+            writer: SpinLockIrqSave::new(()),
+        }
+    }
+
+    /// 读取数据的一致快照，遇到并发写入时重试
+    pub fn read(&self) -> T {
+        loop {
+            let start = self.seq.load(Ordering::Acquire);
+            if start & 1 != 0 {
+                spin_loop();
+                continue;
+            }
+
+            // 读到的可能是写入一半的数据，只有序号未变时才使用
+            let value = unsafe { ptr::read_volatile(self.data.get()) };
+            fence(Ordering::Acquire);
+
+            if self.seq.load(Ordering::Relaxed) == start {
+                return value;
+            }
+        }
+    }
+
+    /// 修改数据，写者之间互斥，持锁期间关闭本核中断
+    pub fn write<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        let _writer = self.writer.lock();
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let result = f(unsafe { &mut *self.data.get() });
+
+        self.seq.fetch_add(1, Ordering::Release);
+        result
+    }
+}
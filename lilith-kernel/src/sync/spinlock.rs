@@ -0,0 +1,226 @@
+//! 自旋锁
+//!
+//! 本模块提供两种自旋锁：
+//! - `SpinLock`：只保证多核互斥，不能在中断处理程序中获取
+//! - `SpinLockIrqSave`：持锁期间关闭本核中断（sstatus.SIE），
+//!   避免中断处理程序在同一核上争用已被任务持有的锁而死锁
+
+#[cfg(feature = "lockdep")]
+use super::lockdep;
+use crate::arch::{local_irq_restore, local_irq_save};
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 自旋锁
+pub struct SpinLock<T: ?Sized> {
+    locked: AtomicBool,
+    /// lockdep锁类，即创建锁的源码位置
+    #[cfg(feature = "lockdep")]
+    class: lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for SpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Send for SpinLock<T> {}
+
+/// 自旋锁守卫，离开作用域时释放锁
+pub struct SpinLockGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    /// 创建自旋锁
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            #[cfg(feature = "lockdep")]
+            class: core::panic::Location::caller(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// 取出被保护的数据
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinLock<T> {
+    /// 获取锁，必要时自旋等待
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        self.acquire();
+        SpinLockGuard { lock: self }
+    }
+
+    /// 尝试获取锁，失败时立即返回 `None`
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        if self.try_acquire_tracked() {
+            Some(SpinLockGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// 锁当前是否被持有
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// 获取可变引用（独占访问时无需加锁）
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 尝试获取锁，成功时登记到lockdep
+    #[track_caller]
+    fn try_acquire_tracked(&self) -> bool {
+        let acquired = self.try_acquire();
+        #[cfg(feature = "lockdep")]
+        if acquired {
+            lockdep::acquire(self.class, self.instance(), false);
+        }
+        acquired
+    }
+
+    #[track_caller]
+    fn acquire(&self) {
+        // 在自旋之前检查，使顺序反转在真正死锁之前暴露
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.class, self.instance(), true);
+
+        while !self.try_acquire() {
+            // 只读等待，避免持续抢占缓存行
+            while self.is_locked() {
+                spin_loop();
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.instance());
+    }
+
+    /// lockdep中的锁实例（以锁的地址区分）
+    #[cfg(feature = "lockdep")]
+    fn instance(&self) -> usize {
+        self as *const Self as *const u8 as usize
+    }
+}
+
+impl<T: ?Sized> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+/// 关中断自旋锁
+pub struct SpinLockIrqSave<T: ?Sized> {
+    inner: SpinLock<T>,
+}
+
+/// 关中断自旋锁守卫，离开作用域时先释放锁再恢复中断状态
+pub struct SpinLockIrqSaveGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock<T>,
+    flags: usize,
+}
+
+impl<T> SpinLockIrqSave<T> {
+    /// 创建关中断自旋锁
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: SpinLock::new(data),
+        }
+    }
+
+    /// 取出被保护的数据
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinLockIrqSave<T> {
+    /// 关闭本核中断并获取锁
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockIrqSaveGuard<'_, T> {
+        let flags = local_irq_save();
+        self.inner.acquire();
+        SpinLockIrqSaveGuard {
+            lock: &self.inner,
+            flags,
+        }
+    }
+
+    /// 尝试获取锁，失败时恢复中断状态并返回 `None`
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<SpinLockIrqSaveGuard<'_, T>> {
+        let flags = local_irq_save();
+        if self.inner.try_acquire_tracked() {
+            Some(SpinLockIrqSaveGuard {
+                lock: &self.inner,
+                flags,
+            })
+        } else {
+            local_irq_restore(flags);
+            None
+        }
+    }
+
+    /// 锁当前是否被持有
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    /// 获取可变引用（独占访问时无需加锁）
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: ?Sized> Deref for SpinLockIrqSaveGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinLockIrqSaveGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinLockIrqSaveGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+        local_irq_restore(self.flags);
+    }
+}
@@ -0,0 +1,171 @@
+//! 等待队列
+//!
+//! 任务在条件不满足时挂在等待队列上睡眠，条件改变后由唤醒方唤醒。
+//! 条件检查与入队在同一把锁下完成，不会丢失唤醒。
+//! 带超时的等待用高精度定时器在到期时唤醒任务
+
+use super::SpinLockIrqSave;
+use crate::sched::{self, Task};
+use crate::time::{self, timer::Timer};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+/// 等待队列
+pub struct WaitQueue {
+    waiters: SpinLockIrqSave<VecDeque<Arc<Task>>>,
+}
+
+impl WaitQueue {
+    /// 创建空的等待队列
+    pub const fn new() -> Self {
+        Self {
+            waiters: SpinLockIrqSave::new(VecDeque::new()),
+        }
+    }
+
+    /// 睡眠直到 `condition` 返回 `true`
+    ///
+    /// `condition` 在持有队列锁时调用，不能阻塞。调度器尚未启动时退化为自旋
+    pub fn wait_until<F: FnMut() -> bool>(&self, mut condition: F) {
+        loop {
+            let task = match sched::current() {
+                Some(task) => task,
+                None => {
+                    if condition() {
+                        return;
+                    }
+                    core::hint::spin_loop();
+                    continue;
+                }
+            };
+
+            {
+                let mut waiters = self.waiters.lock();
+                if condition() {
+                    return;
+                }
+                sched::set_current_blocked();
+                waiters.push_back(task.clone());
+            }
+
+            sched::schedule();
+
+            // 被其他原因唤醒时从队列中移除自己，避免重复入队
+            self.waiters.lock().retain(|t| !Arc::ptr_eq(t, &task));
+        }
+    }
+
+    /// 睡眠直到 `condition` 返回 `true` 或经过 `timeout`，返回条件是否满足
+    ///
+    /// 调度器尚未启动时退化为自旋
+    pub fn wait_until_timeout<F: FnMut() -> bool>(&self, condition: F, timeout: Duration) -> bool {
+        let deadline = time::monotonic_ns().saturating_add(timeout.as_nanos() as u64);
+        self.wait_until_deadline(condition, deadline)
+    }
+
+    /// 睡眠直到 `condition` 返回 `true` 或单调时间到达 `deadline_ns`，返回条件是否满足
+    pub fn wait_until_deadline<F: FnMut() -> bool>(&self, mut condition: F, deadline_ns: u64) -> bool {
+        let task = sched::current();
+        // 到期时唤醒任务，任务醒来后重新检查条件与时间
+        let timer = task.clone().map(|task| {
+            Timer::schedule_at_ns(deadline_ns, move || {
+                sched::wake(&task);
+            })
+        });
+
+        let satisfied = loop {
+            let task = match &task {
+                Some(task) => task,
+                None => {
+                    if condition() {
+                        break true;
+                    }
+                    if time::monotonic_ns() >= deadline_ns {
+                        break false;
+                    }
+                    core::hint::spin_loop();
+                    continue;
+                }
+            };
+
+            {
+                let mut waiters = self.waiters.lock();
+                if condition() {
+                    break true;
+                }
+                if time::monotonic_ns() >= deadline_ns {
+                    break false;
+                }
+                sched::set_current_blocked();
+                waiters.push_back(task.clone());
+            }
+
+            sched::schedule();
+            self.waiters.lock().retain(|t| !Arc::ptr_eq(t, task));
+        };
+
+        if let Some(timer) = timer {
+            timer.cancel();
+        }
+        satisfied
+    }
+
+    /// 睡眠直到被唤醒或经过 `timeout`，返回是否在超时前被唤醒
+    pub fn sleep_on_timeout(&self, timeout: Duration) -> bool {
+        let deadline = time::monotonic_ns().saturating_add(timeout.as_nanos() as u64);
+        let mut slept = false;
+        // 第一次检查时入队，之后任何一次醒来都使条件成立，再按时间区分唤醒与超时
+        self.wait_until_deadline(
+            || {
+                let woken = slept;
+                slept = true;
+                woken
+            },
+            deadline,
+        ) && time::monotonic_ns() < deadline
+    }
+
+    /// 唤醒一个等待者，返回是否有任务被唤醒
+    pub fn wake_one(&self) -> bool {
+        let mut waiters = self.waiters.lock();
+        while let Some(task) = waiters.pop_front() {
+            if sched::wake(&task) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 唤醒所有等待者，返回被唤醒的任务数
+    pub fn wake_all(&self) -> usize {
+        let waiters = core::mem::take(&mut *self.waiters.lock());
+        waiters.iter().filter(|task| sched::wake(task)).count()
+    }
+
+    /// 把 `task` 加入队列但不睡眠，用于同时等待多个队列
+    ///
+    /// 调用方负责随后睡眠并调用 `remove_waiter`
+    pub fn add_waiter(&self, task: &Arc<Task>) {
+        let mut waiters = self.waiters.lock();
+        if !waiters.iter().any(|t| Arc::ptr_eq(t, task)) {
+            waiters.push_back(task.clone());
+        }
+    }
+
+    /// 从队列中移除 `task`
+    pub fn remove_waiter(&self, task: &Arc<Task>) {
+        self.waiters.lock().retain(|t| !Arc::ptr_eq(t, task));
+    }
+
+    /// 是否有任务在等待
+    pub fn has_waiters(&self) -> bool {
+        !self.waiters.lock().is_empty()
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,134 @@
+//! 能力的系统调用接口
+//!
+//! capget/capset的结构体布局与版本号与Linux一致：v1只有32个能力，v2/v3各用两个数据结构
+//! 分别存放低32位与高32位
+
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::mm::uaccess::{read_user, write_user};
+use crate::sched::capability::{self, CapSet, Capabilities, Capability};
+use crate::sched::{self, Process};
+use alloc::sync::Arc;
+
+/// 头部版本号（取值与Linux一致）
+const LINUX_CAPABILITY_VERSION_1: u32 = 0x1998_0330;
+const LINUX_CAPABILITY_VERSION_2: u32 = 0x2007_1026;
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// `struct __user_cap_header_struct`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CapUserHeader {
+    version: u32,
+    pid: i32,
+}
+
+/// `struct __user_cap_data_struct`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// 当前进程没有能力 `cap` 时返回 `EPERM`
+pub(super) fn require(cap: Capability) -> Result<(), Errno> {
+    if capability::capable(cap) {
+        Ok(())
+    } else {
+        Err(Errno::EPERM)
+    }
+}
+
+/// 读取头部，返回版本对应的数据结构个数
+///
+/// 版本号无效时把支持的版本写回头部并返回 `EINVAL`
+fn read_header(header_ptr: usize) -> Result<(CapUserHeader, usize), Errno> {
+    let header: CapUserHeader = read_user(header_ptr)?;
+    match header.version {
+        LINUX_CAPABILITY_VERSION_1 => Ok((header, 1)),
+        LINUX_CAPABILITY_VERSION_2 | LINUX_CAPABILITY_VERSION_3 => Ok((header, 2)),
+        _ => {
+            let supported = CapUserHeader {
+                version: LINUX_CAPABILITY_VERSION_3,
+                ..header
+            };
+            write_user(header_ptr, &supported)?;
+            Err(Errno::EINVAL)
+        }
+    }
+}
+
+/// 按进程号查找进程，0表示当前进程
+fn target_process(pid: i32) -> Result<Arc<Process>, Errno> {
+    match pid {
+        0 => current_process(),
+        pid if pid > 0 => sched::find_process(pid as usize).ok_or(Errno::ESRCH),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// capget(header, data)
+///
+/// `data` 为空时只检查版本号
+pub(super) fn sys_capget(args: &SyscallArgs) -> SyscallResult {
+    let [header_ptr, data_ptr, ..] = args.args;
+    let (header, count) = read_header(header_ptr)?;
+    if data_ptr == 0 {
+        return Ok(0);
+    }
+    let caps = target_process(header.pid)?.capabilities();
+    for index in 0..count {
+        let shift = index * 32;
+        let data = CapUserData {
+            effective: (caps.effective.0 >> shift) as u32,
+            permitted: (caps.permitted.0 >> shift) as u32,
+            inheritable: (caps.inheritable.0 >> shift) as u32,
+        };
+        write_user(data_ptr + index * core::mem::size_of::<CapUserData>(), &data)?;
+    }
+    Ok(0)
+}
+
+/// capset(header, data)
+///
+/// 只能修改当前进程的能力，且只能缩小（见 `Capabilities::can_change_to`）
+pub(super) fn sys_capset(args: &SyscallArgs) -> SyscallResult {
+    let [header_ptr, data_ptr, ..] = args.args;
+    let (header, count) = read_header(header_ptr)?;
+    let process = current_process()?;
+    if header.pid != 0 && header.pid as usize != process.pid {
+        return Err(Errno::EPERM);
+    }
+
+    let old = process.capabilities();
+    let mut new = Capabilities {
+        effective: CapSet::EMPTY,
+        permitted: CapSet::EMPTY,
+        inheritable: CapSet::EMPTY,
+    };
+    for index in 0..count {
+        let shift = index * 32;
+        let data: CapUserData = read_user(data_ptr + index * core::mem::size_of::<CapUserData>())?;
+        new.effective.0 |= u64::from(data.effective) << shift;
+        new.permitted.0 |= u64::from(data.permitted) << shift;
+        new.inheritable.0 |= u64::from(data.inheritable) << shift;
+    }
+    // v1无法表示高32位的能力，保持不变
+    if count == 1 {
+        let high = !0u64 << 32;
+        new.effective.0 |= old.effective.0 & high;
+        new.permitted.0 |= old.permitted.0 & high;
+        new.inheritable.0 |= old.inheritable.0 & high;
+    }
+    // 未知的能力位忽略
+    new.effective.0 &= CapSet::FULL.0;
+    new.permitted.0 &= CapSet::FULL.0;
+    new.inheritable.0 &= CapSet::FULL.0;
+
+    if !old.can_change_to(&new) {
+        return Err(Errno::EPERM);
+    }
+    process.set_capabilities(new);
+    Ok(0)
+}
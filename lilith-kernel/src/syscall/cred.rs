@@ -0,0 +1,223 @@
+//! 用户身份的系统调用接口
+//!
+//! getuid/setuid一族，语义与Linux一致：有 `CAP_SETUID`（组号为 `CAP_SETGID`）时可以任意设置，
+//! 否则只能在实际、有效与保存的值之间切换。参数为-1表示不修改
+
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::mm::uaccess::{read_user, write_user};
+use crate::sched::capability::{self, CAP_SETGID, CAP_SETUID};
+use crate::sched::cred::{self, Credentials, Gid, Uid, NGROUPS_MAX};
+use alloc::vec::Vec;
+
+/// 表示不修改的参数值
+const UNCHANGED: u32 = u32::MAX;
+
+/// 参数转换为用户号，-1返回 `None`
+fn id_arg(value: usize) -> Option<u32> {
+    match value as u32 {
+        UNCHANGED => None,
+        id => Some(id),
+    }
+}
+
+/// 修改当前进程的身份，`update` 返回错误时不修改
+fn modify(update: impl FnOnce(&mut Credentials) -> Result<(), Errno>) -> SyscallResult {
+    let process = current_process()?;
+    let mut new = (*process.credentials()).clone();
+    update(&mut new)?;
+    process.commit_credentials(new);
+    Ok(0)
+}
+
+/// getuid()
+pub(super) fn sys_getuid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(cred::current().uid as usize)
+}
+
+/// geteuid()
+pub(super) fn sys_geteuid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(cred::current().euid as usize)
+}
+
+/// getgid()
+pub(super) fn sys_getgid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(cred::current().gid as usize)
+}
+
+/// getegid()
+pub(super) fn sys_getegid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(cred::current().egid as usize)
+}
+
+/// setuid(uid)
+///
+/// 特权进程同时设置实际、有效与保存的用户号，非特权进程只设置有效用户号
+pub(super) fn sys_setuid(args: &SyscallArgs) -> SyscallResult {
+    let uid: Uid = id_arg(args.args[0]).ok_or(Errno::EINVAL)?;
+    modify(|cred| {
+        if capability::capable(CAP_SETUID) {
+            cred.uid = uid;
+            cred.suid = uid;
+        } else if uid != cred.uid && uid != cred.suid {
+            return Err(Errno::EPERM);
+        }
+        cred.euid = uid;
+        Ok(())
+    })
+}
+
+/// setgid(gid)
+pub(super) fn sys_setgid(args: &SyscallArgs) -> SyscallResult {
+    let gid: Gid = id_arg(args.args[0]).ok_or(Errno::EINVAL)?;
+    modify(|cred| {
+        if capability::capable(CAP_SETGID) {
+            cred.gid = gid;
+            cred.sgid = gid;
+        } else if gid != cred.gid && gid != cred.sgid {
+            return Err(Errno::EPERM);
+        }
+        cred.egid = gid;
+        Ok(())
+    })
+}
+
+/// setreuid(ruid, euid)
+///
+/// 修改了实际用户号，或有效用户号改为实际用户号以外的值时，保存的用户号等于新的有效用户号
+pub(super) fn sys_setreuid(args: &SyscallArgs) -> SyscallResult {
+    let (ruid, euid) = (id_arg(args.args[0]), id_arg(args.args[1]));
+    modify(|cred| {
+        let privileged = capability::capable(CAP_SETUID);
+        let old = cred.clone();
+        if let Some(ruid) = ruid {
+            if !privileged && ruid != old.uid && ruid != old.euid {
+                return Err(Errno::EPERM);
+            }
+            cred.uid = ruid;
+        }
+        if let Some(euid) = euid {
+            if !privileged && !old.has_uid(euid) {
+                return Err(Errno::EPERM);
+            }
+            cred.euid = euid;
+        }
+        if ruid.is_some() || euid.is_some_and(|euid| euid != old.uid) {
+            cred.suid = cred.euid;
+        }
+        Ok(())
+    })
+}
+
+/// setregid(rgid, egid)
+pub(super) fn sys_setregid(args: &SyscallArgs) -> SyscallResult {
+    let (rgid, egid) = (id_arg(args.args[0]), id_arg(args.args[1]));
+    modify(|cred| {
+        let privileged = capability::capable(CAP_SETGID);
+        let old = cred.clone();
+        if let Some(rgid) = rgid {
+            if !privileged && rgid != old.gid && rgid != old.egid {
+                return Err(Errno::EPERM);
+            }
+            cred.gid = rgid;
+        }
+        if let Some(egid) = egid {
+            if !privileged && !old.has_gid(egid) {
+                return Err(Errno::EPERM);
+            }
+            cred.egid = egid;
+        }
+        if rgid.is_some() || egid.is_some_and(|egid| egid != old.gid) {
+            cred.sgid = cred.egid;
+        }
+        Ok(())
+    })
+}
+
+/// setresuid(ruid, euid, suid)
+pub(super) fn sys_setresuid(args: &SyscallArgs) -> SyscallResult {
+    let ids = [id_arg(args.args[0]), id_arg(args.args[1]), id_arg(args.args[2])];
+    modify(|cred| {
+        if !capability::capable(CAP_SETUID) && ids.iter().flatten().any(|&uid| !cred.has_uid(uid)) {
+            return Err(Errno::EPERM);
+        }
+        let [ruid, euid, suid] = ids;
+        cred.uid = ruid.unwrap_or(cred.uid);
+        cred.euid = euid.unwrap_or(cred.euid);
+        cred.suid = suid.unwrap_or(cred.suid);
+        Ok(())
+    })
+}
+
+/// setresgid(rgid, egid, sgid)
+pub(super) fn sys_setresgid(args: &SyscallArgs) -> SyscallResult {
+    let ids = [id_arg(args.args[0]), id_arg(args.args[1]), id_arg(args.args[2])];
+    modify(|cred| {
+        if !capability::capable(CAP_SETGID) && ids.iter().flatten().any(|&gid| !cred.has_gid(gid)) {
+            return Err(Errno::EPERM);
+        }
+        let [rgid, egid, sgid] = ids;
+        cred.gid = rgid.unwrap_or(cred.gid);
+        cred.egid = egid.unwrap_or(cred.egid);
+        cred.sgid = sgid.unwrap_or(cred.sgid);
+        Ok(())
+    })
+}
+
+/// 把三个标识写到用户提供的三个地址
+fn write_ids(ptrs: &[usize], ids: [u32; 3]) -> SyscallResult {
+    for (&ptr, id) in ptrs.iter().zip(ids) {
+        write_user(ptr, &id)?;
+    }
+    Ok(0)
+}
+
+/// getresuid(ruid, euid, suid)
+pub(super) fn sys_getresuid(args: &SyscallArgs) -> SyscallResult {
+    let cred = cred::current();
+    write_ids(&args.args[..3], [cred.uid, cred.euid, cred.suid])
+}
+
+/// getresgid(rgid, egid, sgid)
+pub(super) fn sys_getresgid(args: &SyscallArgs) -> SyscallResult {
+    let cred = cred::current();
+    write_ids(&args.args[..3], [cred.gid, cred.egid, cred.sgid])
+}
+
+/// getgroups(size, list)
+///
+/// `size` 为0时只返回附加组的个数
+pub(super) fn sys_getgroups(args: &SyscallArgs) -> SyscallResult {
+    let [size, list, ..] = args.args;
+    let cred = cred::current();
+    if size == 0 {
+        return Ok(cred.groups.len());
+    }
+    if (size as i32) < 0 || size < cred.groups.len() {
+        return Err(Errno::EINVAL);
+    }
+    for (index, gid) in cred.groups.iter().enumerate() {
+        write_user(list + index * core::mem::size_of::<Gid>(), gid)?;
+    }
+    Ok(cred.groups.len())
+}
+
+/// setgroups(size, list)：需要 `CAP_SETGID`
+pub(super) fn sys_setgroups(args: &SyscallArgs) -> SyscallResult {
+    let [size, list, ..] = args.args;
+    if size > NGROUPS_MAX {
+        return Err(Errno::EINVAL);
+    }
+    if !capability::capable(CAP_SETGID) {
+        return Err(Errno::EPERM);
+    }
+    let mut groups = Vec::with_capacity(size);
+    for index in 0..size {
+        groups.push(read_user::<Gid>(list + index * core::mem::size_of::<Gid>())?);
+    }
+    groups.sort_unstable();
+    groups.dedup();
+    modify(|cred| {
+        cred.groups = groups;
+        Ok(())
+    })
+}
@@ -0,0 +1,429 @@
+//! 文件相关的系统调用
+
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::error::KernelError;
+use crate::fs::file::{File, FileStat, SeekFrom};
+use crate::fs::eventfd::EventFd;
+use crate::fs::{mqueue, shm, tty, OpenOptions};
+use crate::mm::uaccess::{copy_from_user, copy_to_user, read_user, strncpy_from_user, write_user};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+
+/// 相对路径以当前目录为起点
+const AT_FDCWD: usize = -100isize as usize;
+/// fstatat：路径为空时对dirfd本身操作
+const AT_EMPTY_PATH: usize = 0x1000;
+/// 路径最大长度
+const PATH_MAX: usize = 4096;
+/// 单次读取的最大字节数
+const READ_CHUNK: usize = 4096;
+
+/// open的标志（取值与Linux一致）
+const O_ACCMODE: usize = 0o3;
+const O_WRONLY: usize = 0o1;
+const O_RDWR: usize = 0o2;
+const O_CREAT: usize = 0o100;
+const O_EXCL: usize = 0o200;
+const O_NOCTTY: usize = 0o400;
+const O_TRUNC: usize = 0o1000;
+pub(super) const O_NONBLOCK: usize = 0o4000;
+pub(super) const O_CLOEXEC: usize = 0o2000000;
+
+/// eventfd2的标志
+const EFD_SEMAPHORE: usize = 1;
+const EFD_NONBLOCK: usize = O_NONBLOCK;
+const EFD_CLOEXEC: usize = O_CLOEXEC;
+
+/// unlinkat：删除目录
+const AT_REMOVEDIR: usize = 0x200;
+
+/// ioctl请求
+const TIOCSCTTY: usize = 0x540e;
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+const TIOCGWINSZ: usize = 0x5413;
+const TIOCNOTTY: usize = 0x5422;
+const TIOCGSID: usize = 0x5429;
+
+/// Linux RV64的 `struct stat`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct LinuxStat {
+    st_dev: u64,
+    st_ino: u64,
+    st_mode: u32,
+    st_nlink: u32,
+    st_uid: u32,
+    st_gid: u32,
+    st_rdev: u64,
+    __pad1: u64,
+    st_size: i64,
+    st_blksize: i32,
+    __pad2: i32,
+    st_blocks: i64,
+    st_atime: i64,
+    st_atime_nsec: u64,
+    st_mtime: i64,
+    st_mtime_nsec: u64,
+    st_ctime: i64,
+    st_ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+impl From<FileStat> for LinuxStat {
+    fn from(stat: FileStat) -> Self {
+        Self {
+            st_ino: stat.ino,
+            st_mode: stat.mode,
+            st_nlink: 1,
+            st_uid: stat.uid,
+            st_gid: stat.gid,
+            st_rdev: stat.rdev,
+            st_size: stat.size as i64,
+            st_blksize: 4096,
+            st_blocks: stat.size.div_ceil(512) as i64,
+            ..Self::default()
+        }
+    }
+}
+
+/// 查找当前进程打开的文件
+pub(super) fn get_file(fd: usize) -> Result<Arc<dyn File>, Errno> {
+    current_process()?.file(fd).ok_or(Errno::EBADF)
+}
+
+/// 读取用户提供的路径，相对路径以根目录为起点（尚不支持当前目录）
+fn user_path(dirfd: usize, path: usize) -> Result<String, Errno> {
+    let path = strncpy_from_user(path, PATH_MAX)?;
+    if path.starts_with('/') {
+        Ok(path)
+    } else if dirfd == AT_FDCWD {
+        Ok(format!("/{}", path))
+    } else {
+        Err(Errno::ENOTDIR)
+    }
+}
+
+/// 安装文件，返回新的描述符
+pub(super) fn install_file(file: Arc<dyn File>, min_fd: usize) -> SyscallResult {
+    let process = current_process()?;
+    let result = process.files().lock().insert(file, min_fd);
+    result.map_err(|_| Errno::EMFILE)
+}
+
+/// 将文件的读写错误转换为错误码：不支持读或写的文件报告 `EBADF`
+pub(super) fn io_errno(error: KernelError) -> Errno {
+    match error {
+        KernelError::PermissionDenied => Errno::EBADF,
+        error => error.into(),
+    }
+}
+
+/// openat(dirfd, pathname, flags, mode)
+///
+/// 只有 /dev/shm 与 /dev/mqueue 下的文件支持 `O_CREAT`/`O_EXCL`/`O_TRUNC` 与访问模式，其余文件忽略标志。
+/// 没有控制终端的会话首进程打开终端时取得控制终端，除非指定 `O_NOCTTY`
+pub(super) fn sys_openat(args: &SyscallArgs) -> SyscallResult {
+    let [dirfd, path, flags, mode, ..] = args.args;
+    let path = user_path(dirfd, path)?;
+    let file = crate::fs::open_with(&path, &open_options(flags, mode))?;
+    if file.is_tty() && flags & O_NOCTTY == 0 {
+        let process = current_process()?;
+        tty::open_console(&process);
+    }
+    install_file(file, 0)
+}
+
+/// open的标志与权限位转换为打开选项
+pub(super) fn open_options(flags: usize, mode: usize) -> OpenOptions {
+    let access = flags & O_ACCMODE;
+    OpenOptions {
+        read: access != O_WRONLY,
+        write: access == O_WRONLY || access == O_RDWR,
+        create: flags & O_CREAT != 0,
+        exclusive: flags & O_EXCL != 0,
+        truncate: flags & O_TRUNC != 0,
+        mode: mode as u32,
+    }
+}
+
+/// unlinkat(dirfd, pathname, flags)：只能删除 /dev/shm 下的共享内存对象与 /dev/mqueue 下的消息队列
+pub(super) fn sys_unlinkat(args: &SyscallArgs) -> SyscallResult {
+    let [dirfd, path, flags, ..] = args.args;
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let path = user_path(dirfd, path)?;
+    match path.strip_prefix(shm::SHM_DIR) {
+        Some(name) if flags & AT_REMOVEDIR == 0 => shm::unlink(name)?,
+        _ => match path.strip_prefix(mqueue::MQUEUE_DIR) {
+            Some(name) if flags & AT_REMOVEDIR == 0 => mqueue::unlink(name)?,
+            _ => return Err(Errno::EPERM),
+        },
+    }
+    Ok(0)
+}
+
+/// ftruncate(fd, length)
+pub(super) fn sys_ftruncate(args: &SyscallArgs) -> SyscallResult {
+    let [fd, length, ..] = args.args;
+    if (length as isize) < 0 {
+        return Err(Errno::EINVAL);
+    }
+    get_file(fd)?.truncate(length as u64)?;
+    Ok(0)
+}
+
+/// close(fd)
+pub(super) fn sys_close(args: &SyscallArgs) -> SyscallResult {
+    let process = current_process()?;
+    let file = process.files().lock().remove(args.args[0]);
+    file.map(|_| 0).ok_or(Errno::EBADF)
+}
+
+/// eventfd2(initval, flags)
+///
+/// 没有exec，`EFD_CLOEXEC` 接受但不起作用
+pub(super) fn sys_eventfd2(args: &SyscallArgs) -> SyscallResult {
+    let [initval, flags, ..] = args.args;
+    if flags & !(EFD_SEMAPHORE | EFD_NONBLOCK | EFD_CLOEXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let eventfd = EventFd::new(initval as u32, flags & EFD_SEMAPHORE != 0, flags & EFD_NONBLOCK != 0);
+    install_file(eventfd, 0)
+}
+
+/// dup(oldfd)
+pub(super) fn sys_dup(args: &SyscallArgs) -> SyscallResult {
+    install_file(get_file(args.args[0])?, 0)
+}
+
+/// dup3(oldfd, newfd, flags)
+pub(super) fn sys_dup3(args: &SyscallArgs) -> SyscallResult {
+    let [oldfd, newfd, ..] = args.args;
+    if oldfd == newfd {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process()?;
+    let file = get_file(oldfd)?;
+    // 原来打开的文件在锁外释放
+    let result = process.files().lock().install(newfd, file);
+    result.map(|_| newfd).map_err(|_| Errno::EBADF)
+}
+
+/// 从文件读取到用户缓冲区，允许短读：每次最多读取一块
+pub(super) fn read_to_user(file: &dyn File, buf: usize, count: usize) -> SyscallResult {
+    let mut data = vec![0u8; count.min(READ_CHUNK)];
+    let len = file.read(&mut data).map_err(io_errno)?;
+    copy_to_user(buf, &data[..len])?;
+    Ok(len)
+}
+
+/// 按位置读写的错误码：不能定位的文件返回 `ESPIPE`
+fn positional_errno(error: KernelError) -> Errno {
+    match error {
+        KernelError::NotSupported => Errno::ESPIPE,
+        error => io_errno(error),
+    }
+}
+
+/// 从文件的 `offset` 处读取到用户缓冲区，不改变文件的读写位置
+pub(super) fn pread_to_user(file: &dyn File, offset: u64, buf: usize, count: usize) -> SyscallResult {
+    let mut data = vec![0u8; count.min(READ_CHUNK)];
+    let len = file.read_at(offset, &mut data).map_err(positional_errno)?;
+    copy_to_user(buf, &data[..len])?;
+    Ok(len)
+}
+
+/// read(fd, buf, count)
+pub(super) fn sys_read(args: &SyscallArgs) -> SyscallResult {
+    let [fd, buf, count, ..] = args.args;
+    read_to_user(get_file(fd)?.as_ref(), buf, count)
+}
+
+/// 将用户缓冲区写入文件，部分写入后出错时返回已写入的字节数
+pub(super) fn write_from_user(file: &dyn File, buf: usize, count: usize) -> SyscallResult {
+    write_chunks(buf, count, |chunk, _| file.write(chunk).map_err(io_errno))
+}
+
+/// 将用户缓冲区写入文件的 `offset` 处，不改变文件的读写位置
+pub(super) fn pwrite_from_user(file: &dyn File, offset: u64, buf: usize, count: usize) -> SyscallResult {
+    write_chunks(buf, count, |chunk, written| {
+        file.write_at(offset + written as u64, chunk).map_err(positional_errno)
+    })
+}
+
+/// 分块复制用户缓冲区并交给 `write`，其第二个参数为已写入的字节数
+fn write_chunks(
+    buf: usize,
+    count: usize,
+    mut write: impl FnMut(&[u8], usize) -> Result<usize, Errno>,
+) -> SyscallResult {
+    let mut chunk = [0u8; 256];
+    let mut written = 0;
+    while written < count {
+        let len = chunk.len().min(count - written);
+        let result = copy_from_user(&mut chunk[..len], buf + written)
+            .map_err(Errno::from)
+            .and_then(|()| write(&chunk[..len], written));
+        match result {
+            Ok(n) => {
+                written += n;
+                if n < len {
+                    break;
+                }
+            }
+            Err(_) if written > 0 => break,
+            Err(errno) => return Err(errno),
+        }
+    }
+    Ok(written)
+}
+
+/// write(fd, buf, count)
+pub(super) fn sys_write(args: &SyscallArgs) -> SyscallResult {
+    let [fd, buf, count, ..] = args.args;
+    write_from_user(get_file(fd)?.as_ref(), buf, count)
+}
+
+/// readv(fd, iov, iovcnt)
+pub(super) fn sys_readv(args: &SyscallArgs) -> SyscallResult {
+    let [fd, iov, iovcnt, ..] = args.args;
+    let file = get_file(fd)?;
+
+    let mut total = 0;
+    for i in 0..iovcnt {
+        let [base, len] = read_user::<[usize; 2]>(iov + i * 16)?;
+        let n = match read_to_user(file.as_ref(), base, len) {
+            Ok(n) => n,
+            Err(_) if total > 0 => break,
+            Err(errno) => return Err(errno),
+        };
+        total += n;
+        if n < len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// writev(fd, iov, iovcnt)
+pub(super) fn sys_writev(args: &SyscallArgs) -> SyscallResult {
+    let [fd, iov, iovcnt, ..] = args.args;
+    let file = get_file(fd)?;
+
+    let mut total = 0;
+    for i in 0..iovcnt {
+        let [base, len] = read_user::<[usize; 2]>(iov + i * 16)?;
+        let written = match write_from_user(file.as_ref(), base, len) {
+            Ok(written) => written,
+            Err(_) if total > 0 => break,
+            Err(errno) => return Err(errno),
+        };
+        total += written;
+        if written < len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// lseek(fd, offset, whence)
+pub(super) fn sys_lseek(args: &SyscallArgs) -> SyscallResult {
+    let [fd, offset, whence, ..] = args.args;
+    let file = get_file(fd)?;
+    let pos = match whence {
+        0 => SeekFrom::Start(offset as u64),
+        1 => SeekFrom::Current(offset as i64),
+        2 => SeekFrom::End(offset as i64),
+        _ => return Err(Errno::EINVAL),
+    };
+    match file.seek(pos) {
+        Ok(pos) => Ok(pos as usize),
+        Err(KernelError::NotSupported) => Err(Errno::ESPIPE),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 将元数据写入用户的 `struct stat`
+fn write_stat(file: &dyn File, statbuf: usize) -> SyscallResult {
+    write_user(statbuf, &LinuxStat::from(file.stat()))?;
+    Ok(0)
+}
+
+/// fstat(fd, statbuf)
+pub(super) fn sys_fstat(args: &SyscallArgs) -> SyscallResult {
+    let [fd, statbuf, ..] = args.args;
+    write_stat(get_file(fd)?.as_ref(), statbuf)
+}
+
+/// newfstatat(dirfd, pathname, statbuf, flags)
+pub(super) fn sys_newfstatat(args: &SyscallArgs) -> SyscallResult {
+    let [dirfd, path, statbuf, flags, ..] = args.args;
+    if flags & AT_EMPTY_PATH != 0 && read_user::<u8>(path) == Ok(0) {
+        return write_stat(get_file(dirfd)?.as_ref(), statbuf);
+    }
+
+    let path = user_path(dirfd, path)?;
+    write_stat(crate::fs::open(&path)?.as_ref(), statbuf)
+}
+
+/// ioctl(fd, request, arg)：终端的窗口大小与作业控制，开关性能计数文件，配置与运行虚拟机
+pub(super) fn sys_ioctl(args: &SyscallArgs) -> SyscallResult {
+    let [fd, request, arg, ..] = args.args;
+    let file = get_file(fd)?;
+    if let Some(result) = super::perf::ioctl(&file, request) {
+        return result;
+    }
+    if let Some(result) = super::virt::ioctl(&file, request, arg) {
+        return result;
+    }
+    if !file.is_tty() {
+        return Err(Errno::ENOTTY);
+    }
+
+    match request {
+        TIOCGWINSZ => {
+            // struct winsize { ws_row, ws_col, ws_xpixel, ws_ypixel }
+            write_user(arg, &[24u16, 80, 0, 0])?;
+            Ok(0)
+        }
+        TIOCSCTTY => {
+            let process = current_process()?;
+            tty::set_controlling(&process, arg == 1)?;
+            Ok(0)
+        }
+        TIOCNOTTY => {
+            let process = current_process()?;
+            tty::release(&process)?;
+            Ok(0)
+        }
+        TIOCGPGRP => {
+            let process = current_process()?;
+            write_user(arg, &(tty::foreground(&process)? as i32))?;
+            Ok(0)
+        }
+        TIOCSPGRP => {
+            let pgid: i32 = read_user(arg)?;
+            if pgid < 0 {
+                return Err(Errno::EINVAL);
+            }
+            let process = current_process()?;
+            match tty::set_foreground(&process, pgid as usize) {
+                Err(KernelError::NotFound) => Err(Errno::ESRCH),
+                result => result.map(|()| 0).map_err(Errno::from),
+            }
+        }
+        TIOCGSID => {
+            let process = current_process()?;
+            if !tty::is_controlling(&process) {
+                return Err(Errno::ENOTTY);
+            }
+            write_user(arg, &(process.sid() as i32))?;
+            Ok(0)
+        }
+        _ => Err(Errno::ENOTTY),
+    }
+}
@@ -0,0 +1,77 @@
+//! kexec的系统调用接口
+
+use super::capability::require;
+use super::fs::get_file;
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::kexec;
+use crate::mm::uaccess::copy_from_user;
+use crate::sched::capability::CAP_SYS_BOOT;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// kexec_file_load的标志（取值与Linux一致）
+const KEXEC_FILE_UNLOAD: usize = 0x1;
+const KEXEC_FILE_ON_CRASH: usize = 0x2;
+const KEXEC_FILE_NO_INITRAMFS: usize = 0x4;
+
+/// 映像文件的最大长度
+const KEXEC_FILE_MAX: usize = kexec::KEXEC_SIZE;
+/// 启动参数的最大长度（含结尾的0）
+const KEXEC_CMDLINE_MAX: usize = 4096;
+
+/// 读取整个文件
+fn read_file(fd: usize) -> Result<Vec<u8>, Errno> {
+    let file = get_file(fd)?;
+    let mut data = Vec::new();
+    let mut chunk = vec![0u8; 4096];
+    loop {
+        let count = file.read(&mut chunk).map_err(|_| Errno::EBADF)?;
+        if count == 0 {
+            break;
+        }
+        if data.len() + count > KEXEC_FILE_MAX {
+            return Err(Errno::EFBIG);
+        }
+        data.extend_from_slice(&chunk[..count]);
+    }
+    Ok(data)
+}
+
+/// kexec_file_load(kernel_fd, initrd_fd, cmdline_len, cmdline, flags)
+///
+/// `cmdline_len` 包含结尾的0；不支持崩溃内核（`KEXEC_FILE_ON_CRASH`）。需要 `CAP_SYS_BOOT`
+pub(super) fn sys_kexec_file_load(args: &SyscallArgs) -> SyscallResult {
+    let [kernel_fd, initrd_fd, cmdline_len, cmdline_ptr, flags, ..] = args.args;
+    require(CAP_SYS_BOOT)?;
+    if flags & !(KEXEC_FILE_UNLOAD | KEXEC_FILE_ON_CRASH | KEXEC_FILE_NO_INITRAMFS) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if flags & KEXEC_FILE_ON_CRASH != 0 {
+        return Err(Errno::ENOSYS);
+    }
+    if flags & KEXEC_FILE_UNLOAD != 0 {
+        kexec::unload();
+        return Ok(0);
+    }
+
+    if cmdline_len > KEXEC_CMDLINE_MAX {
+        return Err(Errno::EINVAL);
+    }
+    let mut cmdline = vec![0u8; cmdline_len];
+    if cmdline_len > 0 {
+        copy_from_user(&mut cmdline, cmdline_ptr)?;
+        if cmdline.pop() != Some(0) {
+            return Err(Errno::EINVAL);
+        }
+    }
+    let cmdline = core::str::from_utf8(&cmdline).map_err(|_| Errno::EINVAL)?;
+
+    let kernel = read_file(kernel_fd)?;
+    let initrd = if flags & KEXEC_FILE_NO_INITRAMFS == 0 {
+        Some(read_file(initrd_fd)?)
+    } else {
+        None
+    };
+    kexec::load(&kernel, initrd.as_deref(), cmdline)?;
+    Ok(0)
+}
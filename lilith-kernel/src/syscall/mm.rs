@@ -0,0 +1,319 @@
+//! 内存管理相关的系统调用
+//!
+//! 这些调用只维护地址空间中的VMA，物理页在首次访问时由缺页处理建立映射。
+//! 文件映射指向文件在内核中的内存（共享内存对象自身的内存或页缓存中的副本），
+//! 私有可写映射在映射时复制一份。
+//! mprotect同时修改VMA与已经建立的页表项。
+//! madvise丢弃匿名私有映射中的页时原地清零，物理页仍归缺页处理管理。
+//! mlock只标记VMA并按 `RLIMIT_MEMLOCK` 计数，内核不回收用户页，已映射的页本来就不会被换出
+
+use super::fs::get_file;
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::arch::pgtable;
+use crate::error::{KernelError, MemoryError};
+use crate::mm::page_owner::Pages;
+use crate::mm::vma::{page_align_up, MapOwner, Vma, VmaBacking, VmaFlags, PAGE_SIZE, USER_SPACE_END};
+use crate::sched::capability::{self, CAP_IPC_LOCK};
+use crate::sched::rlimit::RLIMIT_MEMLOCK;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// mmap保护位
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+
+/// mmap标志
+const MAP_SHARED: usize = 0x01;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+/// madvise建议
+const MADV_NORMAL: usize = 0;
+const MADV_RANDOM: usize = 1;
+const MADV_SEQUENTIAL: usize = 2;
+const MADV_WILLNEED: usize = 3;
+const MADV_DONTNEED: usize = 4;
+const MADV_FREE: usize = 8;
+
+/// brk(addr)
+pub(super) fn sys_brk(args: &SyscallArgs) -> SyscallResult {
+    Ok(current_process()?.address_space.brk(args.args[0]))
+}
+
+/// 将保护位转换为VMA权限
+fn prot_to_flags(prot: usize, flags: usize) -> VmaFlags {
+    let mut vma_flags = VmaFlags::empty();
+    if prot & PROT_READ != 0 {
+        vma_flags |= VmaFlags::READ;
+    }
+    if prot & PROT_WRITE != 0 {
+        vma_flags |= VmaFlags::WRITE;
+    }
+    if prot & PROT_EXEC != 0 {
+        vma_flags |= VmaFlags::EXEC;
+    }
+    if flags & MAP_SHARED != 0 {
+        vma_flags |= VmaFlags::SHARED;
+    }
+    vma_flags
+}
+
+/// 复制从内核地址 `addr` 开始的 `len`（页对齐）字节，作为私有映射的内存
+fn private_copy(addr: usize, len: usize) -> Result<(usize, MapOwner), KernelError> {
+    let mut pages = Pages::new(len, "mmap")?;
+    let source = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+    pages.as_mut_slice().copy_from_slice(source);
+    Ok((pages.addr(), Arc::new(pages) as MapOwner))
+}
+
+/// mmap(addr, length, prot, flags, fd, offset)
+///
+/// 支持匿名映射与文件的共享、私有映射，不能映射的文件返回 `ENODEV`。
+/// 文件的私有只读映射与其他映射共享文件的内存，之后不能用mprotect增加写权限
+pub(super) fn sys_mmap(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, prot, flags, fd, offset] = args.args;
+    if len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    // 超出用户空间的长度不可能满足，也避免按页取整时溢出
+    if len > USER_SPACE_END {
+        return Err(Errno::ENOMEM);
+    }
+    let process = current_process()?;
+    let address_space = &process.address_space;
+    let len = page_align_up(len);
+
+    // 先检查文件，失败时不影响MAP_FIXED范围内原有的映射
+    let file_mapping = if flags & MAP_ANONYMOUS == 0 {
+        let file = get_file(fd)?;
+        let writable = prot & PROT_WRITE != 0;
+        // 私有映射的写入不写回文件，只要求文件可读
+        let shared = flags & MAP_SHARED != 0;
+        match file.mmap(offset, len, shared && writable) {
+            Ok((addr, _)) if !shared && writable => Some(private_copy(addr, len)?),
+            Ok(mapping) => Some(mapping),
+            Err(KernelError::NotSupported) => return Err(Errno::ENODEV),
+            Err(error) => return Err(error.into()),
+        }
+    } else {
+        None
+    };
+
+    let start = if flags & MAP_FIXED != 0 {
+        if addr % PAGE_SIZE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let end = addr
+            .checked_add(len)
+            .filter(|&end| end <= USER_SPACE_END)
+            .ok_or(Errno::ENOMEM)?;
+        // MAP_FIXED替换范围内原有的映射
+        address_space.remove(addr, end).map_err(|_| Errno::EINVAL)?;
+        addr
+    } else {
+        address_space.find_free(len).ok_or(Errno::ENOMEM)?
+    };
+
+    let mut vma = Vma {
+        start,
+        end: start + len,
+        flags: prot_to_flags(prot, flags),
+        backing: VmaBacking::Anonymous,
+    };
+    let result = match file_mapping {
+        Some((kernel_addr, owner)) => {
+            vma.backing = VmaBacking::Kernel(kernel_addr);
+            address_space.insert_owned(vma, owner)
+        }
+        None => address_space.insert(vma),
+    };
+    result.map_err(|_| Errno::ENOMEM)?;
+    Ok(start)
+}
+
+/// munmap(addr, length)
+///
+/// 范围超出用户空间时返回 `EINVAL`
+pub(super) fn sys_munmap(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, ..] = args.args;
+    if addr % PAGE_SIZE != 0 || len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    let end = addr
+        .checked_add(len)
+        .filter(|&end| end <= USER_SPACE_END)
+        .map(page_align_up)
+        .ok_or(Errno::EINVAL)?;
+    let process = current_process()?;
+    process.address_space.remove(addr, end).map_err(|_| Errno::EINVAL)?;
+    Ok(0)
+}
+
+/// mprotect(addr, length, prot)
+///
+/// 范围中有未映射的部分时返回 `ENOMEM`，给映射内核内存的区域增加权限时返回 `EACCES`
+pub(super) fn sys_mprotect(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, prot, ..] = args.args;
+    if addr % PAGE_SIZE != 0 || prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if len == 0 {
+        return Ok(0);
+    }
+    let end = addr
+        .checked_add(len)
+        .filter(|&end| end <= USER_SPACE_END)
+        .map(page_align_up)
+        .ok_or(Errno::ENOMEM)?;
+
+    let process = current_process()?;
+    process
+        .address_space
+        .protect(addr, end, prot_to_flags(prot, 0))
+        .map_err(|error| match error {
+            MemoryError::PermissionDenied => Errno::EACCES,
+            _ => Errno::ENOMEM,
+        })?;
+
+    let mut perms = 0;
+    if prot & PROT_READ != 0 {
+        perms |= pgtable::PTE_R;
+    }
+    if prot & PROT_WRITE != 0 {
+        perms |= pgtable::PTE_W;
+    }
+    if prot & PROT_EXEC != 0 {
+        perms |= pgtable::PTE_X;
+    }
+    pgtable::update_user_flags(addr, end, perms)?;
+    pgtable::flush_tlb_range(addr, end);
+    Ok(0)
+}
+
+/// 清零 `[start, end)` 中已经建立映射的页，之后读到的内容与新分配的匿名页相同
+fn discard_pages(start: usize, end: usize) {
+    for va in (start..end).step_by(PAGE_SIZE) {
+        if let Some(page) = pgtable::user_page(va) {
+            unsafe { core::ptr::write_bytes(page as *mut u8, 0, PAGE_SIZE) };
+        }
+    }
+}
+
+/// madvise(addr, length, advice)
+///
+/// - `MADV_DONTNEED`：匿名私有映射中的页被丢弃，再次访问时为零；共享映射的内容保留
+/// - `MADV_FREE`：只用于匿名私有映射，页可以被丢弃。内核不回收用户页，调用时立即丢弃
+/// - `MADV_WILLNEED`：映射的内存都已常驻，无需预读
+/// - `MADV_NORMAL`、`MADV_RANDOM`、`MADV_SEQUENTIAL` 不影响行为
+///
+/// 范围中有未映射的部分时返回 `ENOMEM`，不支持的建议、映射或丢弃锁定的页返回 `EINVAL`
+pub(super) fn sys_madvise(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, advice, ..] = args.args;
+    if addr % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if !matches!(
+        advice,
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED | MADV_DONTNEED | MADV_FREE
+    ) {
+        return Err(Errno::EINVAL);
+    }
+    if len == 0 {
+        return Ok(0);
+    }
+    let end = addr
+        .checked_add(len)
+        .filter(|&end| end <= USER_SPACE_END)
+        .map(page_align_up)
+        .ok_or(Errno::ENOMEM)?;
+
+    let process = current_process()?;
+    let address_space = &process.address_space;
+    if !address_space.check_range(addr, end - addr, VmaFlags::empty()) {
+        return Err(Errno::ENOMEM);
+    }
+    if !matches!(advice, MADV_DONTNEED | MADV_FREE) {
+        return Ok(0);
+    }
+
+    let vmas: Vec<Vma> = address_space
+        .vmas()
+        .into_iter()
+        .filter(|vma| vma.start < end && vma.end > addr)
+        .collect();
+    // 先检查整个范围，再丢弃
+    for vma in &vmas {
+        let private_anonymous = vma.backing == VmaBacking::Anonymous && !vma.flags.contains(VmaFlags::SHARED);
+        let supported = match advice {
+            MADV_FREE => private_anonymous,
+            // 不属于任何内存对象的内核页（如vDSO）不能丢弃
+            _ => private_anonymous || vma.flags.contains(VmaFlags::SHARED),
+        };
+        // 锁定的页不能丢弃
+        if !supported || vma.flags.contains(VmaFlags::LOCKED) {
+            return Err(Errno::EINVAL);
+        }
+    }
+    for vma in vmas {
+        if vma.flags.contains(VmaFlags::SHARED) {
+            continue;
+        }
+        discard_pages(vma.start.max(addr), vma.end.min(end));
+        if vma.flags.contains(VmaFlags::EXEC) {
+            crate::arch::flush_icache();
+        }
+    }
+    Ok(0)
+}
+
+/// `[addr, addr + len)` 向外按页对齐后的范围，超出用户地址空间时返回 `ENOMEM`
+fn lock_range(addr: usize, len: usize) -> Result<(usize, usize), Errno> {
+    let start = addr & !(PAGE_SIZE - 1);
+    let end = addr
+        .checked_add(len)
+        .filter(|&end| end <= USER_SPACE_END)
+        .map(page_align_up)
+        .ok_or(Errno::ENOMEM)?;
+    Ok((start, end))
+}
+
+/// mlock(addr, length)
+///
+/// 没有 `CAP_IPC_LOCK` 时锁定的总长度不能超过 `RLIMIT_MEMLOCK` 的软限制，超过时返回 `ENOMEM`，
+/// 限制为0时返回 `EPERM`
+pub(super) fn sys_mlock(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, ..] = args.args;
+    if len == 0 {
+        return Ok(0);
+    }
+    let (start, end) = lock_range(addr, len)?;
+    let process = current_process()?;
+    let limit = if capability::capable(CAP_IPC_LOCK) {
+        None
+    } else {
+        process.rlimit(RLIMIT_MEMLOCK)?.cur_bytes()
+    };
+    if limit == Some(0) {
+        return Err(Errno::EPERM);
+    }
+    process
+        .address_space
+        .set_locked(start, end, true, limit)
+        .map_err(|_| Errno::ENOMEM)?;
+    Ok(0)
+}
+
+/// munlock(addr, length)
+pub(super) fn sys_munlock(args: &SyscallArgs) -> SyscallResult {
+    let [addr, len, ..] = args.args;
+    if len == 0 {
+        return Ok(0);
+    }
+    let (start, end) = lock_range(addr, len)?;
+    current_process()?
+        .address_space
+        .set_locked(start, end, false, None)
+        .map_err(|_| Errno::ENOMEM)?;
+    Ok(0)
+}
@@ -0,0 +1,438 @@
+//! 系统调用
+//!
+//! 本模块实现了与架构无关的系统调用分发：
+//! - 各架构的陷入入口只负责从寄存器中取出调用号与参数，再调用 `dispatch`
+//! - 调用号与Linux RV64一致，通过调用表查找处理函数
+//! - 处理函数返回 `Result<usize, Errno>`，返回用户态时编码为非负结果或负的错误码
+//! - 可按进程开启调用跟踪（strace）
+//! - 查表之前按进程的过滤位图检查调用（seccomp）
+//! - 共享内存提交环，批量提交读写操作（uring）
+//! - 内核模块的加载与卸载
+//! - 硬件性能计数（perf_event_open的计数模式）
+//! - 虚拟机的配置与运行（/dev/lilith-kvm的ioctl）
+//! - 关机与重启，加载kexec内核
+//! - 进程能力的查询与修改（capget/capset），特权调用检查所需的能力
+//! - 用户身份的查询与修改（setuid一族）
+//! - POSIX消息队列
+//! - 多路等待（ppoll/pselect6/epoll）
+//! - 信号的发送、屏蔽与signalfd
+//!
+//! 调用号、参数与结构体布局与Linux一致。复制地址空间的fork与execve尚未实现（返回ENOSYS），
+//! 匿名映射也没有缺页时分配的路径，因此只有不依赖这些调用的静态链接程序可以直接运行
+
+mod capability;
+mod cred;
+mod fs;
+mod kexec;
+mod mm;
+mod mqueue;
+#[cfg(feature = "modules")]
+mod module;
+mod perf;
+mod poll;
+mod process;
+mod random;
+mod reboot;
+pub mod seccomp;
+mod signal;
+mod syslog;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod uring;
+mod virt;
+
+pub use crate::error::Errno;
+use crate::audit;
+use crate::debug::tracepoint;
+use crate::error::KernelError;
+use crate::mm::uaccess::{read_user, write_user};
+use crate::sched::{self, Process};
+use crate::time::{self, ClockId, Timespec, NSEC_PER_SEC};
+use alloc::sync::Arc;
+
+/// 系统调用号（Linux RV64，asm-generic）
+pub const SYS_EVENTFD2: usize = 19;
+pub const SYS_EPOLL_CREATE1: usize = 20;
+pub const SYS_EPOLL_CTL: usize = 21;
+pub const SYS_EPOLL_PWAIT: usize = 22;
+pub const SYS_DUP: usize = 23;
+pub const SYS_DUP3: usize = 24;
+pub const SYS_IOCTL: usize = 29;
+pub const SYS_UNLINKAT: usize = 35;
+pub const SYS_FTRUNCATE: usize = 46;
+pub const SYS_OPENAT: usize = 56;
+pub const SYS_CLOSE: usize = 57;
+pub const SYS_LSEEK: usize = 62;
+pub const SYS_READ: usize = 63;
+pub const SYS_WRITE: usize = 64;
+pub const SYS_READV: usize = 65;
+pub const SYS_WRITEV: usize = 66;
+pub const SYS_PSELECT6: usize = 72;
+pub const SYS_PPOLL: usize = 73;
+pub const SYS_SIGNALFD4: usize = 74;
+pub const SYS_NEWFSTATAT: usize = 79;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_CAPGET: usize = 90;
+pub const SYS_CAPSET: usize = 91;
+pub const SYS_EXIT: usize = 93;
+pub const SYS_EXIT_GROUP: usize = 94;
+pub const SYS_SET_TID_ADDRESS: usize = 96;
+pub const SYS_NANOSLEEP: usize = 101;
+pub const SYS_INIT_MODULE: usize = 105;
+pub const SYS_DELETE_MODULE: usize = 106;
+pub const SYS_CLOCK_GETTIME: usize = 113;
+pub const SYS_CLOCK_NANOSLEEP: usize = 115;
+pub const SYS_SYSLOG: usize = 116;
+pub const SYS_SCHED_YIELD: usize = 124;
+pub const SYS_KILL: usize = 129;
+pub const SYS_TGKILL: usize = 131;
+pub const SYS_RT_SIGACTION: usize = 134;
+pub const SYS_RT_SIGPROCMASK: usize = 135;
+pub const SYS_RT_SIGPENDING: usize = 136;
+pub const SYS_RT_SIGRETURN: usize = 139;
+pub const SYS_REBOOT: usize = 142;
+pub const SYS_SETREGID: usize = 143;
+pub const SYS_SETGID: usize = 144;
+pub const SYS_SETREUID: usize = 145;
+pub const SYS_SETUID: usize = 146;
+pub const SYS_SETRESUID: usize = 147;
+pub const SYS_GETRESUID: usize = 148;
+pub const SYS_SETRESGID: usize = 149;
+pub const SYS_GETRESGID: usize = 150;
+pub const SYS_SETPGID: usize = 154;
+pub const SYS_GETPGID: usize = 155;
+pub const SYS_GETSID: usize = 156;
+pub const SYS_SETSID: usize = 157;
+pub const SYS_GETGROUPS: usize = 158;
+pub const SYS_SETGROUPS: usize = 159;
+pub const SYS_UNAME: usize = 160;
+pub const SYS_GETRLIMIT: usize = 163;
+pub const SYS_SETRLIMIT: usize = 164;
+pub const SYS_GETTIMEOFDAY: usize = 169;
+pub const SYS_GETPID: usize = 172;
+pub const SYS_GETPPID: usize = 173;
+pub const SYS_GETUID: usize = 174;
+pub const SYS_GETEUID: usize = 175;
+pub const SYS_GETGID: usize = 176;
+pub const SYS_GETEGID: usize = 177;
+pub const SYS_GETTID: usize = 178;
+pub const SYS_MQ_OPEN: usize = 180;
+pub const SYS_MQ_UNLINK: usize = 181;
+pub const SYS_MQ_TIMEDSEND: usize = 182;
+pub const SYS_MQ_TIMEDRECEIVE: usize = 183;
+pub const SYS_MQ_GETSETATTR: usize = 185;
+pub const SYS_BRK: usize = 214;
+pub const SYS_MUNMAP: usize = 215;
+pub const SYS_CLONE: usize = 220;
+pub const SYS_EXECVE: usize = 221;
+pub const SYS_MMAP: usize = 222;
+pub const SYS_MPROTECT: usize = 226;
+pub const SYS_MLOCK: usize = 228;
+pub const SYS_MUNLOCK: usize = 229;
+pub const SYS_MADVISE: usize = 233;
+pub const SYS_PERF_EVENT_OPEN: usize = 241;
+pub const SYS_WAIT4: usize = 260;
+pub const SYS_PRLIMIT64: usize = 261;
+pub const SYS_FINIT_MODULE: usize = 273;
+pub const SYS_SECCOMP: usize = 277;
+pub const SYS_GETRANDOM: usize = 278;
+pub const SYS_KEXEC_FILE_LOAD: usize = 294;
+
+/// Lilith私有调用号（Linux未使用的范围）
+pub const SYS_URING_SETUP: usize = 500;
+pub const SYS_URING_ENTER: usize = 501;
+pub const SYS_URING_REGISTER: usize = 502;
+
+/// 调用表大小
+pub const NR_SYSCALLS: usize = 512;
+
+/// 系统调用参数
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallArgs {
+    /// 调用号
+    pub nr: usize,
+    /// 参数（最多6个）
+    pub args: [usize; 6],
+}
+
+/// 系统调用的结果
+pub type SyscallResult = Result<usize, Errno>;
+
+/// 系统调用处理函数
+pub type SyscallHandler = fn(&SyscallArgs) -> SyscallResult;
+
+/// 系统调用表
+static SYSCALL_TABLE: [Option<SyscallHandler>; NR_SYSCALLS] = build_table();
+
+/// 构建系统调用表
+const fn build_table() -> [Option<SyscallHandler>; NR_SYSCALLS] {
+    let mut table: [Option<SyscallHandler>; NR_SYSCALLS] = [None; NR_SYSCALLS];
+    table[SYS_EVENTFD2] = Some(fs::sys_eventfd2);
+    table[SYS_EPOLL_CREATE1] = Some(poll::sys_epoll_create1);
+    table[SYS_EPOLL_CTL] = Some(poll::sys_epoll_ctl);
+    table[SYS_EPOLL_PWAIT] = Some(poll::sys_epoll_pwait);
+    table[SYS_DUP] = Some(fs::sys_dup);
+    table[SYS_DUP3] = Some(fs::sys_dup3);
+    table[SYS_IOCTL] = Some(fs::sys_ioctl);
+    table[SYS_UNLINKAT] = Some(fs::sys_unlinkat);
+    table[SYS_FTRUNCATE] = Some(fs::sys_ftruncate);
+    table[SYS_OPENAT] = Some(fs::sys_openat);
+    table[SYS_CLOSE] = Some(fs::sys_close);
+    table[SYS_LSEEK] = Some(fs::sys_lseek);
+    table[SYS_READ] = Some(fs::sys_read);
+    table[SYS_WRITE] = Some(fs::sys_write);
+    table[SYS_READV] = Some(fs::sys_readv);
+    table[SYS_WRITEV] = Some(fs::sys_writev);
+    table[SYS_PSELECT6] = Some(poll::sys_pselect6);
+    table[SYS_PPOLL] = Some(poll::sys_ppoll);
+    table[SYS_SIGNALFD4] = Some(signal::sys_signalfd4);
+    table[SYS_NEWFSTATAT] = Some(fs::sys_newfstatat);
+    table[SYS_FSTAT] = Some(fs::sys_fstat);
+    table[SYS_CAPGET] = Some(capability::sys_capget);
+    table[SYS_CAPSET] = Some(capability::sys_capset);
+    table[SYS_EXIT] = Some(process::sys_exit);
+    table[SYS_EXIT_GROUP] = Some(process::sys_exit_group);
+    table[SYS_SET_TID_ADDRESS] = Some(process::sys_set_tid_address);
+    table[SYS_NANOSLEEP] = Some(sys_nanosleep);
+    #[cfg(feature = "modules")]
+    {
+        table[SYS_INIT_MODULE] = Some(module::sys_init_module);
+        table[SYS_DELETE_MODULE] = Some(module::sys_delete_module);
+        table[SYS_FINIT_MODULE] = Some(module::sys_finit_module);
+    }
+    table[SYS_CLOCK_GETTIME] = Some(sys_clock_gettime);
+    table[SYS_CLOCK_NANOSLEEP] = Some(sys_clock_nanosleep);
+    table[SYS_SYSLOG] = Some(syslog::sys_syslog);
+    table[SYS_SCHED_YIELD] = Some(sys_sched_yield);
+    table[SYS_KILL] = Some(signal::sys_kill);
+    table[SYS_TGKILL] = Some(signal::sys_tgkill);
+    table[SYS_RT_SIGACTION] = Some(signal::sys_rt_sigaction);
+    table[SYS_RT_SIGPROCMASK] = Some(signal::sys_rt_sigprocmask);
+    table[SYS_RT_SIGPENDING] = Some(signal::sys_rt_sigpending);
+    table[SYS_REBOOT] = Some(reboot::sys_reboot);
+    table[SYS_SETREGID] = Some(cred::sys_setregid);
+    table[SYS_SETGID] = Some(cred::sys_setgid);
+    table[SYS_SETREUID] = Some(cred::sys_setreuid);
+    table[SYS_SETUID] = Some(cred::sys_setuid);
+    table[SYS_SETRESUID] = Some(cred::sys_setresuid);
+    table[SYS_GETRESUID] = Some(cred::sys_getresuid);
+    table[SYS_SETRESGID] = Some(cred::sys_setresgid);
+    table[SYS_GETRESGID] = Some(cred::sys_getresgid);
+    table[SYS_SETPGID] = Some(process::sys_setpgid);
+    table[SYS_GETPGID] = Some(process::sys_getpgid);
+    table[SYS_GETSID] = Some(process::sys_getsid);
+    table[SYS_SETSID] = Some(process::sys_setsid);
+    table[SYS_GETGROUPS] = Some(cred::sys_getgroups);
+    table[SYS_SETGROUPS] = Some(cred::sys_setgroups);
+    table[SYS_UNAME] = Some(process::sys_uname);
+    table[SYS_GETRLIMIT] = Some(process::sys_getrlimit);
+    table[SYS_SETRLIMIT] = Some(process::sys_setrlimit);
+    table[SYS_GETTIMEOFDAY] = Some(sys_gettimeofday);
+    table[SYS_GETPID] = Some(process::sys_getpid);
+    table[SYS_GETPPID] = Some(process::sys_getppid);
+    table[SYS_GETUID] = Some(cred::sys_getuid);
+    table[SYS_GETEUID] = Some(cred::sys_geteuid);
+    table[SYS_GETGID] = Some(cred::sys_getgid);
+    table[SYS_GETEGID] = Some(cred::sys_getegid);
+    table[SYS_GETTID] = Some(process::sys_gettid);
+    table[SYS_MQ_OPEN] = Some(mqueue::sys_mq_open);
+    table[SYS_MQ_UNLINK] = Some(mqueue::sys_mq_unlink);
+    table[SYS_MQ_TIMEDSEND] = Some(mqueue::sys_mq_timedsend);
+    table[SYS_MQ_TIMEDRECEIVE] = Some(mqueue::sys_mq_timedreceive);
+    table[SYS_MQ_GETSETATTR] = Some(mqueue::sys_mq_getsetattr);
+    table[SYS_BRK] = Some(mm::sys_brk);
+    table[SYS_MUNMAP] = Some(mm::sys_munmap);
+    table[SYS_CLONE] = Some(process::sys_clone);
+    table[SYS_MMAP] = Some(mm::sys_mmap);
+    table[SYS_MPROTECT] = Some(mm::sys_mprotect);
+    table[SYS_MLOCK] = Some(mm::sys_mlock);
+    table[SYS_MUNLOCK] = Some(mm::sys_munlock);
+    table[SYS_MADVISE] = Some(mm::sys_madvise);
+    table[SYS_PERF_EVENT_OPEN] = Some(perf::sys_perf_event_open);
+    table[SYS_WAIT4] = Some(process::sys_wait4);
+    table[SYS_PRLIMIT64] = Some(process::sys_prlimit64);
+    table[SYS_SECCOMP] = Some(seccomp::sys_seccomp);
+    table[SYS_GETRANDOM] = Some(random::sys_getrandom);
+    table[SYS_KEXEC_FILE_LOAD] = Some(kexec::sys_kexec_file_load);
+    table[SYS_URING_SETUP] = Some(uring::sys_uring_setup);
+    table[SYS_URING_ENTER] = Some(uring::sys_uring_enter);
+    table[SYS_URING_REGISTER] = Some(uring::sys_uring_register);
+    table
+}
+
+/// 将结果编码为返回给用户态的值：非负结果或负的错误码
+pub fn encode(result: SyscallResult) -> isize {
+    match result {
+        Ok(value) => value as isize,
+        Err(errno) => -(errno.code() as isize),
+    }
+}
+
+/// 当前进程，内核任务发起的系统调用返回 `ESRCH`
+fn current_process() -> Result<Arc<Process>, Errno> {
+    sched::current_process().ok_or(Errno::ESRCH)
+}
+
+/// 分发系统调用
+pub fn dispatch(args: &SyscallArgs) -> isize {
+    crate::tracepoint!(tracepoint::SyscallEnter {
+        nr: args.nr,
+        args: [args.args[0], args.args[1], args.args[2]],
+    });
+    let task = sched::current();
+    let process = task.as_ref().and_then(|task| task.process());
+    // 进程被停止时在进入内核时停下；已调用exit_group时，其他线程在进入内核时退出
+    if let Some(process) = &process {
+        process.stop_point();
+    }
+    if process.as_ref().map_or(false, |process| process.is_exiting()) {
+        sched::exit_current();
+    }
+
+    #[cfg(feature = "tracing")]
+    let traced = process.as_ref().filter(|process| process.is_tracing());
+    #[cfg(feature = "tracing")]
+    if let Some(process) = traced {
+        trace::trace_enter(process, args);
+    }
+
+    if let Some(task) = &task {
+        task.set_syscall(Some(args.nr));
+    }
+    let denied = process.as_ref().and_then(|process| seccomp::check(process, args.nr));
+    let result = match denied {
+        Some(errno) => Err(errno),
+        None => match SYSCALL_TABLE.get(args.nr).copied().flatten() {
+            Some(handler) => handler(args),
+            None => Err(Errno::ENOSYS),
+        },
+    };
+    audit::log_syscall_result(args.nr, result);
+    if let Some(task) = &task {
+        task.set_syscall(None);
+    }
+
+    #[cfg(feature = "tracing")]
+    if let Some(process) = traced {
+        trace::trace_exit(process, args, result);
+    }
+    encode(result)
+}
+
+/// 系统调用子系统初始化
+pub fn syscall_init() -> Result<(), KernelError> {
+    #[cfg(feature = "tracing")]
+    trace::trace_init()?;
+    Ok(())
+}
+
+/// clock_nanosleep的标志：`req` 为绝对时间
+const TIMER_ABSTIME: usize = 1;
+
+/// 用户态的时钟编号
+fn clock_id(clock: usize) -> Result<ClockId, Errno> {
+    match clock {
+        0 => Ok(ClockId::Realtime),
+        1 => Ok(ClockId::Monotonic),
+        5 => Ok(ClockId::RealtimeCoarse),
+        6 => Ok(ClockId::MonotonicCoarse),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// 从用户态读取并检查睡眠时长
+fn read_timespec(addr: usize) -> Result<Timespec, Errno> {
+    let [sec, nsec] = read_user::<[i64; 2]>(addr)?;
+    if sec < 0 || !(0..NSEC_PER_SEC as i64).contains(&nsec) {
+        return Err(Errno::EINVAL);
+    }
+    Ok(Timespec { sec, nsec })
+}
+
+/// clock_gettime(clockid, tp)
+fn sys_clock_gettime(args: &SyscallArgs) -> SyscallResult {
+    let [clock, tp, ..] = args.args;
+    let clock = clock_id(clock)?;
+
+    let now = time::clock_gettime(clock);
+    write_user(tp, &[now.sec, now.nsec])?;
+    Ok(0)
+}
+
+/// gettimeofday(tv, tz)
+///
+/// 不支持时区，`tz` 非空时写入全0（UTC）
+fn sys_gettimeofday(args: &SyscallArgs) -> SyscallResult {
+    let [tv, tz, ..] = args.args;
+    if tv != 0 {
+        let now = time::gettimeofday();
+        write_user(tv, &[now.sec, now.usec])?;
+    }
+    if tz != 0 {
+        write_user(tz, &[0i32, 0i32])?;
+    }
+    Ok(0)
+}
+
+/// nanosleep(req, rem)
+///
+/// 尚不支持用户态信号处理函数：未被屏蔽的信号按默认动作结束进程（或被忽略、停止进程），
+/// 被屏蔽的信号只排队，都不会打断睡眠，因此 `rem` 不会被写入
+fn sys_nanosleep(args: &SyscallArgs) -> SyscallResult {
+    let [req, _rem, ..] = args.args;
+    let duration = read_timespec(req)?;
+    time::sleep_until(time::monotonic_ns().saturating_add(duration.as_nanos() as u64));
+    Ok(0)
+}
+
+/// clock_nanosleep(clockid, flags, req, rem)
+///
+/// 粗粒度时钟不能用于睡眠
+fn sys_clock_nanosleep(args: &SyscallArgs) -> SyscallResult {
+    let [clock, flags, req, _rem, ..] = args.args;
+    let clock = clock_id(clock)?;
+    if !matches!(clock, ClockId::Realtime | ClockId::Monotonic) {
+        return Err(Errno::EINVAL);
+    }
+    let request = read_timespec(req)?.as_nanos();
+
+    let now = time::monotonic_ns();
+    let deadline = if flags & TIMER_ABSTIME == 0 {
+        now.saturating_add(request as u64)
+    } else {
+        // 绝对时间换算为单调时间；之后对实时时钟的调整不影响本次睡眠
+        let current = time::clock_gettime(clock).as_nanos();
+        now.saturating_add(request.saturating_sub(current).max(0) as u64)
+    };
+    time::sleep_until(deadline);
+    Ok(0)
+}
+
+/// sched_yield()
+fn sys_sched_yield(_args: &SyscallArgs) -> SyscallResult {
+    sched::yield_now();
+    Ok(0)
+}
+
+crate::kernel_test! {
+    fn dispatch_unknown_syscall() {
+        let args = SyscallArgs { nr: SYSCALL_TABLE.len(), args: [0; 6] };
+        assert_eq!(dispatch(&args), encode(Err(Errno::ENOSYS)));
+    }
+}
+
+crate::kernel_test! {
+    fn getpid_from_kernel_task() {
+        let args = SyscallArgs { nr: SYS_GETPID, args: [0; 6] };
+        let expected = sched::current().map_or(0, |task| task.id.0);
+        assert_eq!(dispatch(&args), expected as isize);
+    }
+}
+
+crate::kernel_test! {
+    fn bad_user_pointer_is_efault() {
+        // 内核任务没有用户地址空间，任何用户指针都无效
+        let args = SyscallArgs { nr: SYS_CLOCK_GETTIME, args: [1, 0x1000, 0, 0, 0, 0] };
+        assert_eq!(dispatch(&args), encode(Err(Errno::EFAULT)));
+    }
+}
@@ -0,0 +1,72 @@
+//! 内核模块的系统调用接口
+
+use super::capability::require;
+use super::fs::get_file;
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::mm::uaccess::{copy_from_user, strncpy_from_user};
+use crate::module;
+use crate::sched::capability::CAP_SYS_MODULE;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 模块映像的最大长度
+const MODULE_MAX_SIZE: usize = 64 * 1024 * 1024;
+/// 模块参数的最大长度
+const MODULE_PARAMS_MAX: usize = 4096;
+/// 模块名的最大长度
+const MODULE_NAME_MAX: usize = 64;
+
+/// 读取用户提供的模块参数，空指针视为没有参数
+fn user_params(params: usize) -> Result<String, Errno> {
+    if params == 0 {
+        return Ok(String::new());
+    }
+    Ok(strncpy_from_user(params, MODULE_PARAMS_MAX)?)
+}
+
+/// init_module(module_image, len, param_values)
+pub(super) fn sys_init_module(args: &SyscallArgs) -> SyscallResult {
+    let [image, len, params, ..] = args.args;
+    require(CAP_SYS_MODULE)?;
+    if len > MODULE_MAX_SIZE {
+        return Err(Errno::EFBIG);
+    }
+    let mut data = vec![0u8; len];
+    copy_from_user(&mut data, image)?;
+    module::load_module(&data, &user_params(params)?)?;
+    Ok(0)
+}
+
+/// finit_module(fd, param_values, flags)
+pub(super) fn sys_finit_module(args: &SyscallArgs) -> SyscallResult {
+    let [fd, params, flags, ..] = args.args;
+    require(CAP_SYS_MODULE)?;
+    if flags != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let file = get_file(fd)?;
+    let mut data = Vec::new();
+    let mut chunk = vec![0u8; 4096];
+    loop {
+        let count = file.read(&mut chunk).map_err(|_| Errno::EBADF)?;
+        if count == 0 {
+            break;
+        }
+        if data.len() + count > MODULE_MAX_SIZE {
+            return Err(Errno::EFBIG);
+        }
+        data.extend_from_slice(&chunk[..count]);
+    }
+    module::load_module(&data, &user_params(params)?)?;
+    Ok(0)
+}
+
+/// delete_module(name, flags)
+pub(super) fn sys_delete_module(args: &SyscallArgs) -> SyscallResult {
+    let [name, _flags, ..] = args.args;
+    require(CAP_SYS_MODULE)?;
+    let name = strncpy_from_user(name, MODULE_NAME_MAX)?;
+    module::unload_module(&name)?;
+    Ok(0)
+}
@@ -0,0 +1,161 @@
+//! POSIX消息队列的系统调用
+//!
+//! mq_open/mq_unlink/mq_timedsend/mq_timedreceive/mq_getsetattr，`struct mq_attr` 的布局与Linux一致：
+//! - 名字以 `/` 开头，对应 /dev/mqueue 下去掉 `/` 的文件
+//! - 超时为 `CLOCK_REALTIME` 的绝对时间，为空时一直等待
+//! - 不支持mq_notify（返回 `ENOSYS`）
+
+use super::fs::{install_file, io_errno, open_options, O_NONBLOCK};
+use super::{current_process, read_timespec, Errno, SyscallArgs, SyscallResult};
+use crate::error::KernelError;
+use crate::fs::mqueue::{self, MqAttr, MqFile};
+use crate::mm::uaccess::{copy_from_user, copy_to_user, read_user, strncpy_from_user, write_user};
+use crate::time::{self, ClockId};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+
+/// 名字的最大长度（含开头的 `/`）
+const MQ_NAME_MAX: usize = 256;
+
+/// `struct mq_attr`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct LinuxMqAttr {
+    flags: i64,
+    maxmsg: i64,
+    msgsize: i64,
+    curmsgs: i64,
+    reserved: [i64; 4],
+}
+
+/// 读取名字并去掉开头的 `/`
+fn user_name(name: usize) -> Result<String, Errno> {
+    let name = strncpy_from_user(name, MQ_NAME_MAX)?;
+    match name.strip_prefix('/') {
+        Some(name) => Ok(String::from(name)),
+        None => Err(Errno::EINVAL),
+    }
+}
+
+/// 查找描述符对应的队列
+fn mq_file(mqdes: usize) -> Result<Arc<MqFile>, Errno> {
+    let file = current_process()?.file(mqdes).ok_or(Errno::EBADF)?;
+    mqueue::lookup(&file).ok_or(Errno::EBADF)
+}
+
+/// 绝对超时换算为单调时间的截止时间，`abs_timeout` 为空时返回 `None`
+fn deadline(abs_timeout: usize) -> Result<Option<u64>, Errno> {
+    if abs_timeout == 0 {
+        return Ok(None);
+    }
+    let timeout = read_timespec(abs_timeout)?.as_nanos();
+    let current = time::clock_gettime(ClockId::Realtime).as_nanos();
+    Ok(Some(
+        time::monotonic_ns().saturating_add(timeout.saturating_sub(current).max(0) as u64),
+    ))
+}
+
+/// 队列操作的错误转换为错误码：参数错误报告 `EMSGSIZE`，其余与读写相同
+fn mq_errno(error: KernelError) -> Errno {
+    match error {
+        KernelError::InvalidArgument => Errno::EMSGSIZE,
+        error => io_errno(error),
+    }
+}
+
+/// mq_open(name, oflag, mode, attr)
+pub(super) fn sys_mq_open(args: &SyscallArgs) -> SyscallResult {
+    let [name, oflag, mode, attr, ..] = args.args;
+    let name = user_name(name)?;
+    let options = open_options(oflag, mode);
+    let attr = if options.create && attr != 0 {
+        let attr: LinuxMqAttr = read_user(attr)?;
+        if attr.maxmsg <= 0 || attr.msgsize <= 0 {
+            return Err(Errno::EINVAL);
+        }
+        Some(MqAttr {
+            maxmsg: attr.maxmsg as usize,
+            msgsize: attr.msgsize as usize,
+        })
+    } else {
+        None
+    };
+    let file = mqueue::open(&name, &options, attr, oflag & O_NONBLOCK != 0)?;
+    install_file(file, 0)
+}
+
+/// mq_unlink(name)
+pub(super) fn sys_mq_unlink(args: &SyscallArgs) -> SyscallResult {
+    let name = user_name(args.args[0])?;
+    mqueue::unlink(&name)?;
+    Ok(0)
+}
+
+/// mq_timedsend(mqdes, msg_ptr, msg_len, msg_prio, abs_timeout)
+pub(super) fn sys_mq_timedsend(args: &SyscallArgs) -> SyscallResult {
+    let [mqdes, msg_ptr, msg_len, msg_prio, abs_timeout, _] = args.args;
+    let mq = mq_file(mqdes)?;
+    if msg_prio >= mqueue::MQ_PRIO_MAX as usize {
+        return Err(Errno::EINVAL);
+    }
+    if msg_len > mq.queue().attr().msgsize {
+        return Err(Errno::EMSGSIZE);
+    }
+    let mut data = vec![0u8; msg_len];
+    copy_from_user(&mut data, msg_ptr)?;
+    mq.send(&data, msg_prio as u32, deadline(abs_timeout)?)
+        .map_err(mq_errno)?;
+    Ok(0)
+}
+
+/// mq_timedreceive(mqdes, msg_ptr, msg_len, msg_prio, abs_timeout)
+///
+/// `msg_prio` 非空时写入消息的优先级
+pub(super) fn sys_mq_timedreceive(args: &SyscallArgs) -> SyscallResult {
+    let [mqdes, msg_ptr, msg_len, msg_prio, abs_timeout, _] = args.args;
+    let mq = mq_file(mqdes)?;
+    let msgsize = mq.queue().attr().msgsize;
+    if msg_len < msgsize {
+        return Err(Errno::EMSGSIZE);
+    }
+    let mut buf = vec![0u8; msgsize];
+    let (len, priority) = mq.receive(&mut buf, deadline(abs_timeout)?).map_err(mq_errno)?;
+    copy_to_user(msg_ptr, &buf[..len])?;
+    if msg_prio != 0 {
+        write_user(msg_prio, &priority)?;
+    }
+    Ok(len)
+}
+
+/// mq_getsetattr(mqdes, newattr, oldattr)
+///
+/// 只能修改 `O_NONBLOCK`，其余字段忽略
+pub(super) fn sys_mq_getsetattr(args: &SyscallArgs) -> SyscallResult {
+    let [mqdes, newattr, oldattr, ..] = args.args;
+    let mq = mq_file(mqdes)?;
+    let new = if newattr != 0 {
+        let new: LinuxMqAttr = read_user(newattr)?;
+        if new.flags as usize & !O_NONBLOCK != 0 {
+            return Err(Errno::EINVAL);
+        }
+        Some(new)
+    } else {
+        None
+    };
+    if oldattr != 0 {
+        let attr = mq.queue().attr();
+        let old = LinuxMqAttr {
+            flags: if mq.nonblock() { O_NONBLOCK as i64 } else { 0 },
+            maxmsg: attr.maxmsg as i64,
+            msgsize: attr.msgsize as i64,
+            curmsgs: mq.queue().len() as i64,
+            ..LinuxMqAttr::default()
+        };
+        write_user(oldattr, &old)?;
+    }
+    if let Some(new) = new {
+        mq.set_nonblock(new.flags as usize & O_NONBLOCK != 0);
+    }
+    Ok(0)
+}
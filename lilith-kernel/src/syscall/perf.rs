@@ -0,0 +1,130 @@
+//! 硬件性能计数的系统调用
+//!
+//! perf_event_open的计数模式，`perf_event_attr` 的布局与Linux一致：
+//! - 类型为 `PERF_TYPE_HARDWARE` 的通用事件，与 `PERF_TYPE_HW_CACHE` 中的TLB读缺失
+//! - `pid` 为0或任务编号时统计该任务，`pid` 为-1时统计 `cpu` 指定的hart
+//! - 不支持采样、事件组与按hart统计单个任务
+//! - 按hart统计需要 `CAP_PERFMON`，否则返回 `EACCES`
+
+use super::fs::install_file;
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::fs::file::File;
+use crate::mm::uaccess::read_user;
+use crate::perf::{self, HwEvent, PerfEventFile, PerfTarget};
+use crate::sched::capability::CAP_PERFMON;
+use crate::sched::{self, TaskId, MAX_HARTS};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// 事件类型
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_HW_CACHE: u32 = 3;
+
+/// `PERF_TYPE_HW_CACHE` 的配置：`缓存 | 操作<<8 | 结果<<16`
+const HW_CACHE_DTLB_READ_MISS: u64 = 3 | 1 << 16;
+const HW_CACHE_ITLB_READ_MISS: u64 = 4 | 1 << 16;
+
+/// `perf_event_attr.flags` 中的创建时不开始计数
+const ATTR_FLAG_DISABLED: u64 = 1 << 0;
+
+/// perf_event_open的flags中唯一支持的标志
+const PERF_FLAG_FD_CLOEXEC: usize = 1 << 3;
+
+/// ioctl请求
+const PERF_EVENT_IOC_ENABLE: usize = 0x2400;
+const PERF_EVENT_IOC_DISABLE: usize = 0x2401;
+const PERF_EVENT_IOC_RESET: usize = 0x2403;
+
+/// `perf_event_attr` 中用到的前48字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+}
+
+/// 所有计数文件，用于识别ioctl的目标
+static EVENT_FILES: Mutex<Vec<Weak<PerfEventFile>>> = Mutex::new(Vec::new());
+
+/// 事件类型与配置对应的事件
+fn decode_event(attr: &PerfEventAttr) -> Result<HwEvent, Errno> {
+    match (attr.type_, attr.config) {
+        (PERF_TYPE_HARDWARE, config) if config < 6 => Ok(HwEvent::ALL[config as usize]),
+        (PERF_TYPE_HW_CACHE, HW_CACHE_DTLB_READ_MISS) => Ok(HwEvent::DtlbMisses),
+        (PERF_TYPE_HW_CACHE, HW_CACHE_ITLB_READ_MISS) => Ok(HwEvent::ItlbMisses),
+        (PERF_TYPE_HARDWARE | PERF_TYPE_HW_CACHE, _) => Err(Errno::ENOENT),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// perf_event_open(attr, pid, cpu, group_fd, flags)
+pub(super) fn sys_perf_event_open(args: &SyscallArgs) -> SyscallResult {
+    let [attr, pid, cpu, group_fd, flags, _] = args.args;
+    let attr: PerfEventAttr = read_user(attr)?;
+    if attr.sample_period != 0 || group_fd as isize != -1 || flags & !PERF_FLAG_FD_CLOEXEC != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let event = decode_event(&attr)?;
+    if !perf::event_available(event) {
+        return Err(Errno::ENOENT);
+    }
+
+    let target = match (pid as isize, cpu as isize) {
+        (0, -1) => PerfTarget::Task(Arc::downgrade(&sched::current().ok_or(Errno::ESRCH)?)),
+        (pid, -1) if pid > 0 => PerfTarget::Task(Arc::downgrade(
+            &sched::find_task(TaskId(pid as usize)).ok_or(Errno::ESRCH)?,
+        )),
+        (-1, cpu) if cpu >= 0 && (cpu as usize) < MAX_HARTS => {
+            if !sched::capable(CAP_PERFMON) {
+                return Err(Errno::EACCES);
+            }
+            PerfTarget::Hart(cpu as usize)
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+
+    let file = Arc::new(PerfEventFile::new(event, target, attr.flags & ATTR_FLAG_DISABLED == 0));
+    let mut files = EVENT_FILES.lock();
+    files.retain(|file| file.strong_count() > 0);
+    files.push(Arc::downgrade(&file));
+    drop(files);
+    install_file(file, 0)
+}
+
+/// 查找描述符对应的计数文件
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别计数文件
+fn lookup(file: &Arc<dyn File>) -> Option<Arc<PerfEventFile>> {
+    let target = Arc::as_ptr(file) as *const ();
+    EVENT_FILES
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|event| Arc::as_ptr(event) as *const () == target)
+}
+
+/// 计数文件的ioctl，`file` 不是计数文件时返回 `None`
+pub(super) fn ioctl(file: &Arc<dyn File>, request: usize) -> Option<SyscallResult> {
+    let event = lookup(file)?;
+    Some(match request {
+        PERF_EVENT_IOC_ENABLE => {
+            event.enable();
+            Ok(0)
+        }
+        PERF_EVENT_IOC_DISABLE => {
+            event.disable();
+            Ok(0)
+        }
+        PERF_EVENT_IOC_RESET => {
+            event.reset();
+            Ok(0)
+        }
+        _ => Err(Errno::ENOTTY),
+    })
+}
@@ -0,0 +1,235 @@
+//! 多路等待的系统调用
+//!
+//! ppoll/pselect6与epoll，结构体布局与Linux RV64一致：
+//! - 超时为相对时间，为空时一直等待；不回写剩余时间
+//! - 信号掩码参数忽略，等待期间不临时替换屏蔽字
+//! - epoll支持水平触发、边沿触发（`EPOLLET`）与一次性（`EPOLLONESHOT`）
+
+use super::fs::{get_file, install_file, O_CLOEXEC};
+use super::{read_timespec, Errno, SyscallArgs, SyscallResult};
+use crate::fs::epoll::{self, Epoll};
+use crate::fs::file::{File, PollEvents, MAX_FDS};
+use crate::fs::poll;
+use crate::mm::uaccess::{read_user, write_user};
+use crate::time;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+/// pollfd中的描述符无效
+const POLLNVAL: i16 = 0x020;
+
+/// epoll_ctl的操作
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+/// `struct pollfd`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+/// `struct epoll_event`（RV64上不是紧凑布局）
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+/// 相对超时换算为单调时间的截止时间，`timeout` 为空时返回 `None`
+fn deadline(timeout: usize) -> Result<Option<u64>, Errno> {
+    if timeout == 0 {
+        return Ok(None);
+    }
+    let timeout = read_timespec(timeout)?.as_nanos() as u64;
+    Ok(Some(time::monotonic_ns().saturating_add(timeout)))
+}
+
+/// ppoll(fds, nfds, tmo_p, sigmask, sigsetsize)
+///
+/// 负的描述符忽略，无效的描述符报告 `POLLNVAL`
+pub(super) fn sys_ppoll(args: &SyscallArgs) -> SyscallResult {
+    let [fds, nfds, timeout, ..] = args.args;
+    if nfds > MAX_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let mut entries = Vec::with_capacity(nfds);
+    for index in 0..nfds {
+        entries.push(read_user::<PollFd>(fds + index * size_of::<PollFd>())?);
+    }
+    let files: Vec<Option<Arc<dyn File>>> = entries
+        .iter()
+        .map(|entry| (entry.fd >= 0).then(|| get_file(entry.fd as usize).ok()).flatten())
+        .collect();
+    let deadline = deadline(timeout)?;
+
+    let scan = |table: &mut _| {
+        let mut ready = 0;
+        for (entry, file) in entries.iter_mut().zip(&files) {
+            entry.revents = match file {
+                _ if entry.fd < 0 => 0,
+                None => POLLNVAL,
+                Some(file) => {
+                    file.poll_wait(table);
+                    let wanted =
+                        PollEvents::from_bits_truncate(entry.events as u16) | PollEvents::ERR | PollEvents::HUP;
+                    (file.poll() & wanted).bits() as i16
+                }
+            };
+            if entry.revents != 0 {
+                ready += 1;
+            }
+        }
+        (ready > 0).then_some(ready)
+    };
+    let ready = poll::wait_ready(deadline, scan).unwrap_or(0);
+
+    for (index, entry) in entries.iter().enumerate() {
+        let revents = if ready > 0 { entry.revents } else { 0 };
+        write_user(fds + index * size_of::<PollFd>() + 6, &revents)?;
+    }
+    Ok(ready)
+}
+
+/// 读取 `nfds` 位的描述符集合，`addr` 为空时返回 `None`
+fn read_fd_set(addr: usize, nfds: usize) -> Result<Option<Vec<u64>>, Errno> {
+    if addr == 0 {
+        return Ok(None);
+    }
+    let mut words = Vec::with_capacity(nfds.div_ceil(64));
+    for index in 0..nfds.div_ceil(64) {
+        words.push(read_user::<u64>(addr + index * size_of::<u64>())?);
+    }
+    if let Some(last) = words.last_mut() {
+        if nfds % 64 != 0 {
+            *last &= (1 << (nfds % 64)) - 1;
+        }
+    }
+    Ok(Some(words))
+}
+
+/// 集合中是否包含 `fd`
+fn fd_isset(set: &Option<Vec<u64>>, fd: usize) -> bool {
+    set.as_ref().is_some_and(|words| words[fd / 64] & (1 << (fd % 64)) != 0)
+}
+
+/// pselect6(nfds, readfds, writefds, exceptfds, timeout, sigmask)
+///
+/// 可读对应 `IN`/`HUP`/`ERR`，可写对应 `OUT`/`ERR`，异常对应 `PRI`
+pub(super) fn sys_pselect6(args: &SyscallArgs) -> SyscallResult {
+    let [nfds, readfds, writefds, exceptfds, timeout, _] = args.args;
+    if nfds > MAX_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let sets = [
+        read_fd_set(readfds, nfds)?,
+        read_fd_set(writefds, nfds)?,
+        read_fd_set(exceptfds, nfds)?,
+    ];
+    let mut files = Vec::new();
+    for fd in (0..nfds).filter(|&fd| sets.iter().any(|set| fd_isset(set, fd))) {
+        files.push((fd, get_file(fd)?));
+    }
+    let conditions = [
+        PollEvents::IN | PollEvents::HUP | PollEvents::ERR,
+        PollEvents::OUT | PollEvents::ERR,
+        PollEvents::PRI,
+    ];
+    let deadline = deadline(timeout)?;
+
+    let scan = |table: &mut _| {
+        let mut result: [Vec<u64>; 3] = core::array::from_fn(|_| alloc::vec![0; nfds.div_ceil(64)]);
+        let mut ready = 0;
+        for (fd, file) in &files {
+            file.poll_wait(table);
+            let events = file.poll();
+            for ((set, condition), words) in sets.iter().zip(conditions).zip(result.iter_mut()) {
+                if fd_isset(set, *fd) && events.intersects(condition) {
+                    words[fd / 64] |= 1 << (fd % 64);
+                    ready += 1;
+                }
+            }
+        }
+        (ready > 0).then_some((ready, result))
+    };
+    let (ready, result) = poll::wait_ready(deadline, scan)
+        .unwrap_or_else(|| (0, core::array::from_fn(|_| alloc::vec![0; nfds.div_ceil(64)])));
+
+    for ((addr, set), words) in [readfds, writefds, exceptfds].into_iter().zip(&sets).zip(&result) {
+        if set.is_some() {
+            for (index, word) in words.iter().enumerate() {
+                write_user(addr + index * size_of::<u64>(), word)?;
+            }
+        }
+    }
+    Ok(ready)
+}
+
+/// epoll_create1(flags)
+///
+/// 没有exec，`EPOLL_CLOEXEC` 接受但不起作用
+pub(super) fn sys_epoll_create1(args: &SyscallArgs) -> SyscallResult {
+    if args.args[0] & !O_CLOEXEC != 0 {
+        return Err(Errno::EINVAL);
+    }
+    install_file(Epoll::new(), 0)
+}
+
+/// 查找描述符对应的epoll实例，不是epoll时返回 `EINVAL`
+fn epoll_file(epfd: usize) -> Result<Arc<Epoll>, Errno> {
+    epoll::lookup(&get_file(epfd)?).ok_or(Errno::EINVAL)
+}
+
+/// epoll_ctl(epfd, op, fd, event)
+pub(super) fn sys_epoll_ctl(args: &SyscallArgs) -> SyscallResult {
+    let [epfd, op, fd, event, ..] = args.args;
+    let epoll = epoll_file(epfd)?;
+    let file = get_file(fd)?;
+    if epfd == fd {
+        return Err(Errno::EINVAL);
+    }
+    match op {
+        EPOLL_CTL_ADD => {
+            let event: EpollEvent = read_user(event)?;
+            epoll.add(fd, &file, event.events, event.data)?;
+        }
+        EPOLL_CTL_MOD => {
+            let event: EpollEvent = read_user(event)?;
+            epoll.modify(fd, event.events, event.data)?;
+        }
+        EPOLL_CTL_DEL => epoll.remove(fd)?,
+        _ => return Err(Errno::EINVAL),
+    }
+    Ok(0)
+}
+
+/// epoll_pwait(epfd, events, maxevents, timeout, sigmask, sigsetsize)
+///
+/// `timeout` 以毫秒为单位，-1表示一直等待
+pub(super) fn sys_epoll_pwait(args: &SyscallArgs) -> SyscallResult {
+    let [epfd, events_ptr, maxevents, timeout, ..] = args.args;
+    let maxevents = maxevents as i32;
+    if maxevents <= 0 {
+        return Err(Errno::EINVAL);
+    }
+    let epoll = epoll_file(epfd)?;
+    let deadline = match timeout as i32 {
+        timeout if timeout < 0 => None,
+        timeout => Some(time::monotonic_ns().saturating_add(timeout as u64 * 1_000_000)),
+    };
+
+    let ready = epoll.wait(maxevents as usize, deadline);
+    for (index, (events, data)) in ready.iter().enumerate() {
+        let event = EpollEvent {
+            events: events.bits() as u32,
+            data: *data,
+        };
+        write_user(events_ptr + index * size_of::<EpollEvent>(), &event)?;
+    }
+    Ok(ready.len())
+}
@@ -0,0 +1,276 @@
+//! 进程相关的系统调用
+
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::arch::{REG_A0, REG_SP, REG_TP};
+use crate::error::KernelError;
+use crate::mm::uaccess::{copy_to_user, read_user, write_user};
+use crate::sched::capability::{self, CAP_SYS_RESOURCE};
+use crate::sched::rlimit::Rlimit;
+use crate::sched::{self, ChildEvent, Process, TaskId, WaitOptions, WaitTarget};
+use crate::sync::SpinLock;
+use alloc::sync::Arc;
+
+/// clone标志
+const CLONE_VM: usize = 0x0000_0100;
+const CLONE_FILES: usize = 0x0000_0400;
+const CLONE_VFORK: usize = 0x0000_4000;
+const CLONE_THREAD: usize = 0x0001_0000;
+const CLONE_SETTLS: usize = 0x0008_0000;
+const CLONE_PARENT_SETTID: usize = 0x0010_0000;
+const CLONE_CHILD_CLEARTID: usize = 0x0020_0000;
+const CLONE_CHILD_SETTID: usize = 0x0100_0000;
+
+
+/// `struct utsname` 每个字段的长度
+const UTSNAME_FIELD_LEN: usize = 65;
+
+/// exit(status)：结束当前线程
+pub(super) fn sys_exit(args: &SyscallArgs) -> SyscallResult {
+    if let Some(process) = sched::current_process() {
+        process.set_exit_code(args.args[0] as i32);
+    }
+    sched::exit_current()
+}
+
+/// exit_group(status)：结束整个进程
+pub(super) fn sys_exit_group(args: &SyscallArgs) -> SyscallResult {
+    if let Some(process) = sched::current_process() {
+        process.exit_group(args.args[0] as i32);
+    }
+    sched::exit_current()
+}
+
+/// set_tid_address(tidptr)
+pub(super) fn sys_set_tid_address(args: &SyscallArgs) -> SyscallResult {
+    match sched::current() {
+        Some(task) => {
+            task.set_clear_child_tid(args.args[0]);
+            Ok(task.id.0)
+        }
+        None => Ok(0),
+    }
+}
+
+/// getpid()：内核任务返回任务编号
+pub(super) fn sys_getpid(_args: &SyscallArgs) -> SyscallResult {
+    match sched::current() {
+        Some(task) => Ok(task.process().map_or(task.id.0, |process| process.pid)),
+        None => Ok(0),
+    }
+}
+
+/// getppid()
+pub(super) fn sys_getppid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(sched::current_process()
+        .and_then(|process| process.parent())
+        .map_or(0, |parent| parent.pid))
+}
+
+/// `pid` 为0时取当前进程，否则按进程号查找
+fn process_arg(pid: usize) -> Result<Arc<Process>, Errno> {
+    let process = current_process()?;
+    match pid as isize {
+        0 => Ok(process),
+        pid if pid > 0 => sched::find_process(pid as usize).ok_or(Errno::ESRCH),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// getpgid(pid)
+pub(super) fn sys_getpgid(args: &SyscallArgs) -> SyscallResult {
+    Ok(process_arg(args.args[0])?.pgid())
+}
+
+/// getsid(pid)
+pub(super) fn sys_getsid(args: &SyscallArgs) -> SyscallResult {
+    Ok(process_arg(args.args[0])?.sid())
+}
+
+/// setsid()：创建新会话，新会话没有控制终端
+pub(super) fn sys_setsid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(current_process()?.setsid()?)
+}
+
+/// setpgid(pid, pgid)
+///
+/// 只能设置自己或子进程，目标必须在同一会话中且不是会话首进程；
+/// `pgid` 不等于目标的进程号时，进程组必须已在本会话中存在
+pub(super) fn sys_setpgid(args: &SyscallArgs) -> SyscallResult {
+    let [pid, pgid, ..] = args.args;
+    if (pgid as isize) < 0 {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process()?;
+    let target = process_arg(pid)?;
+    let is_child = target.parent().is_some_and(|parent| Arc::ptr_eq(&parent, &process));
+    if !Arc::ptr_eq(&target, &process) && !is_child {
+        return Err(Errno::ESRCH);
+    }
+    if target.sid() != process.sid() || target.is_session_leader() {
+        return Err(Errno::EPERM);
+    }
+    let pgid = if pgid == 0 { target.pid } else { pgid };
+    if pgid != target.pid && !sched::process_group(pgid).iter().any(|member| member.sid() == process.sid()) {
+        return Err(Errno::EPERM);
+    }
+    target.set_pgid(pgid);
+    Ok(0)
+}
+
+/// gettid()
+pub(super) fn sys_gettid(_args: &SyscallArgs) -> SyscallResult {
+    Ok(sched::current().map_or(0, |task| task.id.0))
+}
+
+/// uname(buf)
+pub(super) fn sys_uname(args: &SyscallArgs) -> SyscallResult {
+    let fields = [
+        "Lilith",
+        "lilith",
+        crate::KERNEL_VERSION,
+        "#1",
+        "riscv64",
+        "",
+    ];
+    let mut utsname = [0u8; UTSNAME_FIELD_LEN * 6];
+    for (i, field) in fields.iter().enumerate() {
+        let len = field.len().min(UTSNAME_FIELD_LEN - 1);
+        utsname[i * UTSNAME_FIELD_LEN..][..len].copy_from_slice(&field.as_bytes()[..len]);
+    }
+    copy_to_user(args.args[0], &utsname)?;
+    Ok(0)
+}
+
+/// clone(flags, stack, parent_tid, tls, child_tid)
+///
+/// 支持线程（CLONE_VM | CLONE_THREAD）与共享地址空间的vfork；
+/// 复制地址空间的fork需要写时复制的页表支持，暂时返回ENOSYS
+pub(super) fn sys_clone(args: &SyscallArgs) -> SyscallResult {
+    let [flags, stack, parent_tid, tls, child_tid, _] = args.args;
+    if flags & CLONE_VM == 0 {
+        return Err(Errno::ENOSYS);
+    }
+    let task = sched::current().ok_or(Errno::EINVAL)?;
+    let process = current_process()?;
+
+    // 子任务从clone返回，返回值为0
+    let mut frame = unsafe { *task.user_frame() };
+    frame.regs[REG_A0] = 0;
+    if stack != 0 {
+        frame.regs[REG_SP] = stack;
+    }
+    if flags & CLONE_SETTLS != 0 {
+        frame.regs[REG_TP] = tls;
+    }
+
+    let tid = TaskId::alloc();
+    // 地址空间是共享的，先写回线程号：写入失败时还没有创建任何状态，无需回滚
+    for (flag, addr) in [(CLONE_PARENT_SETTID, parent_tid), (CLONE_CHILD_SETTID, child_tid)] {
+        if flags & flag != 0 {
+            write_user(addr, &(tid.0 as u32))?;
+        }
+    }
+
+    let child_process = if flags & CLONE_THREAD != 0 {
+        process.add_thread();
+        process.clone()
+    } else {
+        let files = if flags & CLONE_FILES != 0 {
+            process.files()
+        } else {
+            let table = process.files().lock().clone();
+            Arc::new(SpinLock::new(table))
+        };
+        Process::new(tid.0, Some(&process), process.address_space.clone(), files)
+    };
+
+    let clear_child_tid = if flags & CLONE_CHILD_CLEARTID != 0 { child_tid } else { 0 };
+    sched::spawn_user(tid, &task.name, &frame, child_process.clone(), clear_child_tid);
+
+    // vfork：子进程退出前父进程不能继续使用共享的用户栈
+    if flags & CLONE_VFORK != 0 && !Arc::ptr_eq(&child_process, &process) {
+        process.wait_child_exit(&child_process);
+    }
+    Ok(tid.0)
+}
+
+/// wait4(pid, wstatus, options, rusage)
+///
+/// `pid` 为0时等待同一进程组的子进程，小于-1时等待进程组 `-pid` 中的子进程
+pub(super) fn sys_wait4(args: &SyscallArgs) -> SyscallResult {
+    let [pid, wstatus, options, ..] = args.args;
+    let process = current_process()?;
+    let target = match pid as isize {
+        -1 => WaitTarget::Any,
+        0 => WaitTarget::Group(process.pgid()),
+        pid if pid < 0 => WaitTarget::Group(pid.unsigned_abs()),
+        pid => WaitTarget::Pid(pid as usize),
+    };
+    let options = WaitOptions::from_bits(options as u32).ok_or(Errno::EINVAL)?;
+
+    match process.wait_child(target, options) {
+        Ok(Some((pid, event))) => {
+            if wstatus != 0 {
+                let status = match event {
+                    ChildEvent::Exited(code) => (code & 0xff) << 8,
+                    ChildEvent::Stopped(sig) => (sig as i32) << 8 | 0x7f,
+                    ChildEvent::Continued => 0xffff,
+                };
+                write_user(wstatus, &status)?;
+            }
+            Ok(pid)
+        }
+        Ok(None) => Ok(0),
+        Err(KernelError::NotFound) => Err(Errno::ECHILD),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 读取并修改 `target` 的资源限制：`new` 非空时设置新的限制，`old` 非空时写回原来的限制
+fn update_rlimit(target: &Process, resource: usize, new: usize, old: usize) -> SyscallResult {
+    let previous = if new != 0 {
+        let limit: Rlimit = read_user(new)?;
+        target.set_rlimit(resource, limit, capability::capable(CAP_SYS_RESOURCE))?
+    } else {
+        target.rlimit(resource)?
+    };
+    if old != 0 {
+        write_user(old, &previous)?;
+    }
+    Ok(0)
+}
+
+/// getrlimit(resource, rlim)
+pub(super) fn sys_getrlimit(args: &SyscallArgs) -> SyscallResult {
+    let [resource, rlim, ..] = args.args;
+    let limit = current_process()?.rlimit(resource)?;
+    write_user(rlim, &limit)?;
+    Ok(0)
+}
+
+/// setrlimit(resource, rlim)
+pub(super) fn sys_setrlimit(args: &SyscallArgs) -> SyscallResult {
+    let [resource, rlim, ..] = args.args;
+    let process = current_process()?;
+    update_rlimit(&process, resource, rlim, 0)
+}
+
+/// prlimit64(pid, resource, new_limit, old_limit)
+///
+/// 访问其他进程时，目标的实际、有效与保存的用户号和组号必须都等于调用者的实际用户号和组号，
+/// 否则需要 `CAP_SYS_RESOURCE`
+pub(super) fn sys_prlimit64(args: &SyscallArgs) -> SyscallResult {
+    let [pid, resource, new, old, ..] = args.args;
+    let process = current_process()?;
+    let target = process_arg(pid)?;
+    if !Arc::ptr_eq(&target, &process) {
+        let caller = process.credentials();
+        let cred = target.credentials();
+        let same_user = [cred.uid, cred.euid, cred.suid].iter().all(|&uid| uid == caller.uid)
+            && [cred.gid, cred.egid, cred.sgid].iter().all(|&gid| gid == caller.gid);
+        if !same_user && !capability::capable(CAP_SYS_RESOURCE) {
+            return Err(Errno::EPERM);
+        }
+    }
+    update_rlimit(&target, resource, new, old)
+}
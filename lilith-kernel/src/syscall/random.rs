@@ -0,0 +1,46 @@
+//! 随机数的系统调用接口
+
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::mm::uaccess::copy_to_user;
+use crate::random;
+
+/// getrandom的标志（取值与Linux一致）
+const GRND_NONBLOCK: usize = 0x1;
+const GRND_RANDOM: usize = 0x2;
+const GRND_INSECURE: usize = 0x4;
+
+/// 单次调用最多返回的字节数
+const GETRANDOM_MAX: usize = 32 * 1024 * 1024;
+
+/// getrandom(buf, buflen, flags)
+///
+/// 生成器初始化前阻塞，`GRND_NONBLOCK` 时返回 `EAGAIN`，`GRND_INSECURE` 时不等待；
+/// `GRND_RANDOM` 与不带此标志相同
+pub(super) fn sys_getrandom(args: &SyscallArgs) -> SyscallResult {
+    let [buf, len, flags, ..] = args.args;
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+        || flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+    {
+        return Err(Errno::EINVAL);
+    }
+
+    if flags & GRND_INSECURE == 0 && !random::is_initialized() {
+        if flags & GRND_NONBLOCK != 0 {
+            return Err(Errno::EAGAIN);
+        }
+        random::wait_for_initialized();
+    }
+
+    let len = len.min(GETRANDOM_MAX);
+    let mut chunk = [0u8; 256];
+    let mut done = 0;
+    while done < len {
+        let count = chunk.len().min(len - done);
+        random::get_random_bytes(&mut chunk[..count]);
+        copy_to_user(buf + done, &chunk[..count])?;
+        done += count;
+    }
+    // 栈上不留下生成的随机数
+    chunk.fill(0);
+    Ok(done)
+}
@@ -0,0 +1,43 @@
+//! 关机与重启的系统调用接口
+
+use super::capability::require;
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::kexec;
+use crate::power::{self, PowerAction};
+use crate::sched::capability::CAP_SYS_BOOT;
+
+/// reboot的魔数（取值与Linux一致）
+const REBOOT_MAGIC1: usize = 0xfee1_dead;
+const REBOOT_MAGIC2: [usize; 4] = [672_274_793, 85_072_278, 369_367_448, 537_993_216];
+
+/// reboot的命令
+const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+const REBOOT_CMD_HALT: usize = 0xcdef_0123;
+const REBOOT_CMD_CAD_ON: usize = 0x89ab_cdef;
+const REBOOT_CMD_CAD_OFF: usize = 0;
+const REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
+const REBOOT_CMD_RESTART2: usize = 0xa1b2_c3d4;
+const REBOOT_CMD_KEXEC: usize = 0x4558_4543;
+
+/// reboot(magic1, magic2, cmd, arg)
+///
+/// 关机、重启与停机成功时不返回；没有Ctrl-Alt-Del按键，开关它的命令只检查参数。
+/// `REBOOT_CMD_RESTART2` 的命令字符串被忽略，按普通重启处理；
+/// `REBOOT_CMD_KEXEC` 在没有加载映像时返回 `EINVAL`。需要 `CAP_SYS_BOOT`
+pub(super) fn sys_reboot(args: &SyscallArgs) -> SyscallResult {
+    let [magic1, magic2, cmd, ..] = args.args;
+    require(CAP_SYS_BOOT)?;
+    if magic1 as u32 as usize != REBOOT_MAGIC1 || !REBOOT_MAGIC2.contains(&(magic2 as u32 as usize)) {
+        return Err(Errno::EINVAL);
+    }
+
+    let action = match cmd as u32 as usize {
+        REBOOT_CMD_CAD_ON | REBOOT_CMD_CAD_OFF => return Ok(0),
+        REBOOT_CMD_POWER_OFF => PowerAction::PowerOff,
+        REBOOT_CMD_RESTART | REBOOT_CMD_RESTART2 => PowerAction::Restart,
+        REBOOT_CMD_HALT => PowerAction::Halt,
+        REBOOT_CMD_KEXEC if kexec::is_loaded() => PowerAction::Kexec,
+        _ => return Err(Errno::EINVAL),
+    };
+    power::shutdown(action)
+}
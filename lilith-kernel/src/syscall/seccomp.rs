@@ -0,0 +1,141 @@
+//! 系统调用过滤（seccomp）
+//!
+//! 每个进程可以安装一张按调用号索引的允许位图，分发器在查表之前检查：
+//! - 过滤器一经安装即不可撤销，也不能被替换，子进程继承父进程的过滤器
+//! - 严格模式与Linux的 `SECCOMP_SET_MODE_STRICT` 一致
+//! - 被拒绝的调用返回 `EPERM`，或直接结束进程
+
+use super::*;
+use crate::mm::uaccess::read_user;
+use crate::sched::signal::SIGSYS;
+use alloc::sync::Arc;
+
+/// seccomp操作
+const SECCOMP_SET_MODE_STRICT: usize = 0;
+/// Lilith扩展：安装允许位图，参数为位图地址与拒绝时的动作
+const SECCOMP_SET_MODE_BITMAP: usize = 0x4c00;
+
+/// 位图的字数
+const BITMAP_WORDS: usize = NR_SYSCALLS / 64;
+
+/// `SECCOMP_SET_MODE_BITMAP` 的参数
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SeccompBitmap {
+    /// 允许位图，按调用号索引
+    bitmap: [u64; BITMAP_WORDS],
+    /// 非零时拒绝的调用结束进程，否则返回 `EPERM`
+    kill: u64,
+}
+
+/// 严格模式允许的调用
+const STRICT_ALLOWED: [usize; 4] = [SYS_READ, SYS_WRITE, SYS_EXIT, SYS_RT_SIGRETURN];
+
+/// 调用被拒绝时的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// 返回 `EPERM`
+    Errno,
+    /// 结束整个进程
+    Kill,
+}
+
+/// 系统调用允许位图
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallFilter {
+    allowed: [u64; BITMAP_WORDS],
+    action: FilterAction,
+}
+
+impl SyscallFilter {
+    /// 拒绝所有调用的过滤器
+    pub const fn deny_all(action: FilterAction) -> Self {
+        Self {
+            allowed: [0; BITMAP_WORDS],
+            action,
+        }
+    }
+
+    /// 允许所有调用的过滤器
+    pub const fn allow_all(action: FilterAction) -> Self {
+        Self {
+            allowed: [u64::MAX; BITMAP_WORDS],
+            action,
+        }
+    }
+
+    /// 严格模式：只允许read、write、exit与rt_sigreturn，其余调用结束进程
+    pub fn strict() -> Self {
+        let mut filter = Self::deny_all(FilterAction::Kill);
+        for nr in STRICT_ALLOWED {
+            filter.allow(nr);
+        }
+        filter
+    }
+
+    /// 允许调用
+    pub fn allow(&mut self, nr: usize) {
+        if nr < NR_SYSCALLS {
+            self.allowed[nr / 64] |= 1 << (nr % 64);
+        }
+    }
+
+    /// 拒绝调用
+    pub fn deny(&mut self, nr: usize) {
+        if nr < NR_SYSCALLS {
+            self.allowed[nr / 64] &= !(1 << (nr % 64));
+        }
+    }
+
+    /// 调用是否被允许
+    pub fn is_allowed(&self, nr: usize) -> bool {
+        nr < NR_SYSCALLS && self.allowed[nr / 64] & (1 << (nr % 64)) != 0
+    }
+
+    /// 拒绝时的动作
+    pub fn action(&self) -> FilterAction {
+        self.action
+    }
+}
+
+/// 检查当前进程是否允许调用，返回 `None` 表示允许
+pub(super) fn check(process: &Process, nr: usize) -> Option<Errno> {
+    let filter = process.syscall_filter()?;
+    if filter.is_allowed(nr) {
+        return None;
+    }
+    match filter.action() {
+        FilterAction::Errno => Some(Errno::EPERM),
+        FilterAction::Kill => {
+            crate::early_println!("seccomp: 进程{}的调用{}被拒绝，结束进程", process.pid, nr);
+            // 与Linux一致，以SIGSYS结束
+            process.kill(SIGSYS);
+            sched::exit_current()
+        }
+    }
+}
+
+/// seccomp(operation, flags, args)
+pub(super) fn sys_seccomp(args: &SyscallArgs) -> SyscallResult {
+    let [operation, _flags, uargs, ..] = args.args;
+    let process = current_process()?;
+
+    let filter = match operation {
+        SECCOMP_SET_MODE_STRICT => SyscallFilter::strict(),
+        SECCOMP_SET_MODE_BITMAP => {
+            let args: SeccompBitmap = read_user(uargs)?;
+            let action = if args.kill != 0 { FilterAction::Kill } else { FilterAction::Errno };
+            SyscallFilter {
+                allowed: args.bitmap,
+                action,
+            }
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+
+    // 已安装的过滤器不能被替换，否则进程可以换上全部允许的位图逃出沙箱
+    process
+        .set_syscall_filter(Arc::new(filter))
+        .map_err(|_| Errno::EPERM)?;
+    Ok(0)
+}
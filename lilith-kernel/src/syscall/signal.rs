@@ -0,0 +1,165 @@
+//! 信号相关的系统调用
+//!
+//! kill/tgkill（可发给进程组）、屏蔽字与signalfd，信号集合的布局与Linux一致（`sigsetsize` 必须为8）：
+//! - 尚不支持用户态信号处理函数，rt_sigaction总是报告默认处理
+//! - 被屏蔽的信号排队，可由rt_sigpending查询、由signalfd读取
+//! - 发送信号需要与目标进程的用户号匹配，或者有 `CAP_KILL`
+
+use super::fs::{get_file, install_file, O_CLOEXEC, O_NONBLOCK};
+use super::{current_process, Errno, SyscallArgs, SyscallResult};
+use crate::fs::signalfd::{self, SignalFd};
+use crate::mm::uaccess::{read_user, write_user};
+use crate::sched::capability::{self, CAP_KILL};
+use crate::sched::signal::{self, SigInfo, SigSet, SI_USER};
+use crate::sched::{self, cred, Process, TaskId};
+
+/// rt_sigprocmask的操作
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// signalfd4标志
+const SFD_NONBLOCK: usize = O_NONBLOCK;
+const SFD_CLOEXEC: usize = O_CLOEXEC;
+
+/// 检查 `sigsetsize` 并读取信号集合
+fn read_sigset(addr: usize, size: usize) -> Result<SigSet, Errno> {
+    if size != core::mem::size_of::<SigSet>() {
+        return Err(Errno::EINVAL);
+    }
+    Ok(SigSet(read_user::<u64>(addr)?))
+}
+
+/// rt_sigaction(sig, act, oldact, sigsetsize)：不支持信号处理函数，总是报告默认处理
+pub(super) fn sys_rt_sigaction(args: &SyscallArgs) -> SyscallResult {
+    let [_, _, oldact, ..] = args.args;
+    if oldact != 0 {
+        // struct sigaction { sa_handler, sa_flags, sa_mask }
+        write_user(oldact, &[0usize; 3])?;
+    }
+    Ok(0)
+}
+
+/// rt_sigprocmask(how, set, oldset, sigsetsize)
+///
+/// 屏蔽字属于进程，同一进程的线程共用
+pub(super) fn sys_rt_sigprocmask(args: &SyscallArgs) -> SyscallResult {
+    let [how, set, oldset, sigsetsize, ..] = args.args;
+    if sigsetsize != core::mem::size_of::<SigSet>() {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process()?;
+    let old = process.signal_mask();
+    if set != 0 {
+        let set = read_sigset(set, sigsetsize)?;
+        let mask = match how {
+            SIG_BLOCK => SigSet(old.0 | set.0),
+            SIG_UNBLOCK => SigSet(old.0 & !set.0),
+            SIG_SETMASK => set,
+            _ => return Err(Errno::EINVAL),
+        };
+        process.set_signal_mask(mask);
+    }
+    if oldset != 0 {
+        write_user(oldset, &old.0)?;
+    }
+    Ok(0)
+}
+
+/// rt_sigpending(set, sigsetsize)
+pub(super) fn sys_rt_sigpending(args: &SyscallArgs) -> SyscallResult {
+    let [set, sigsetsize, ..] = args.args;
+    if sigsetsize != core::mem::size_of::<SigSet>() {
+        return Err(Errno::EINVAL);
+    }
+    write_user(set, &current_process()?.pending_signals().0)?;
+    Ok(0)
+}
+
+/// 检查信号编号（0只检查目标是否存在）与发送权限，返回要发送的信号
+fn check_kill(target: &Process, sig: usize) -> Result<Option<SigInfo>, Errno> {
+    if sig != 0 && !signal::valid(sig as u32) {
+        return Err(Errno::EINVAL);
+    }
+    let sender = cred::current();
+    let owner = target.credentials();
+    let permitted = [sender.uid, sender.euid]
+        .iter()
+        .any(|&uid| uid == owner.uid || uid == owner.suid);
+    if !permitted && !capability::capable(CAP_KILL) {
+        return Err(Errno::EPERM);
+    }
+    Ok((sig != 0).then(|| SigInfo {
+        signo: sig as u32,
+        code: SI_USER,
+        pid: sched::current_process().map_or(0, |process| process.pid),
+        uid: sender.uid,
+    }))
+}
+
+/// kill(pid, sig)
+///
+/// `pid` 为0时发给同一进程组，为-1时发给除1号进程与自己以外的所有进程，小于-1时发给进程组 `-pid`。
+/// 发给多个进程时至少一个成功即返回成功
+pub(super) fn sys_kill(args: &SyscallArgs) -> SyscallResult {
+    let [pid, sig, ..] = args.args;
+    let process = current_process()?;
+    let targets = match pid as isize {
+        pid if pid > 0 => alloc::vec![sched::find_process(pid as usize).ok_or(Errno::ESRCH)?],
+        0 => sched::process_group(process.pgid()),
+        -1 => sched::processes()
+            .into_iter()
+            .filter(|target| target.pid != 1 && target.pid != process.pid)
+            .collect(),
+        pid => sched::process_group(pid.unsigned_abs()),
+    };
+
+    let mut result = Err(Errno::ESRCH);
+    for target in targets {
+        match check_kill(&target, sig) {
+            Ok(info) => {
+                if let Some(info) = info {
+                    target.send_signal(info);
+                }
+                result = Ok(0);
+            }
+            Err(errno) if result.is_err() => result = Err(errno),
+            Err(_) => {}
+        }
+    }
+    result
+}
+
+/// tgkill(tgid, tid, sig)
+///
+/// 信号属于进程，发给线程等同于发给它所在的进程
+pub(super) fn sys_tgkill(args: &SyscallArgs) -> SyscallResult {
+    let [tgid, tid, sig, ..] = args.args;
+    let target = sched::find_task(TaskId(tid))
+        .and_then(|task| task.process())
+        .filter(|process| process.pid == tgid)
+        .ok_or(Errno::ESRCH)?;
+    if let Some(info) = check_kill(&target, sig)? {
+        target.send_signal(info);
+    }
+    Ok(0)
+}
+
+/// signalfd4(fd, mask, sizemask, flags)
+///
+/// `fd` 为-1时创建新的信号文件，否则替换已有信号文件关心的信号。
+/// 没有exec，`SFD_CLOEXEC` 接受但不起作用
+pub(super) fn sys_signalfd4(args: &SyscallArgs) -> SyscallResult {
+    let [fd, mask, sizemask, flags, ..] = args.args;
+    if flags & !(SFD_NONBLOCK | SFD_CLOEXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let mask = SigSet(read_sigset(mask, sizemask)?.0 & !SigSet::UNBLOCKABLE.0);
+    if fd as isize == -1 {
+        let process = current_process()?;
+        return install_file(SignalFd::new(&process, mask, flags & SFD_NONBLOCK != 0), 0);
+    }
+    let signalfd = signalfd::lookup(&get_file(fd)?).ok_or(Errno::EINVAL)?;
+    signalfd.set_mask(mask);
+    Ok(fd)
+}
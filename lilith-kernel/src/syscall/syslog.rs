@@ -0,0 +1,69 @@
+//! 内核日志的系统调用接口
+
+use super::capability::require;
+use super::{Errno, SyscallArgs, SyscallResult};
+use crate::klog::{self, LOG_BUF_SIZE};
+use crate::mm::uaccess::copy_to_user;
+use crate::sched::capability::CAP_SYSLOG;
+
+/// syslog操作（取值与Linux一致）
+const SYSLOG_ACTION_CLOSE: usize = 0;
+const SYSLOG_ACTION_OPEN: usize = 1;
+const SYSLOG_ACTION_READ: usize = 2;
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+const SYSLOG_ACTION_CONSOLE_OFF: usize = 6;
+const SYSLOG_ACTION_CONSOLE_ON: usize = 7;
+const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+
+/// 复制读取的日志到用户缓冲区
+fn copy_log(buf: usize, text: &str) -> SyscallResult {
+    copy_to_user(buf, text.as_bytes())?;
+    Ok(text.len())
+}
+
+/// syslog(type, bufp, len)
+///
+/// 除打开、关闭、读取全部与查询缓冲区大小外的操作需要 `CAP_SYSLOG`
+pub(super) fn sys_syslog(args: &SyscallArgs) -> SyscallResult {
+    let [action, buf, len, ..] = args.args;
+    let is_read = matches!(action, SYSLOG_ACTION_READ | SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR);
+    if is_read && (len as isize) < 0 {
+        return Err(Errno::EINVAL);
+    }
+    if !matches!(
+        action,
+        SYSLOG_ACTION_CLOSE | SYSLOG_ACTION_OPEN | SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_SIZE_BUFFER
+    ) {
+        require(CAP_SYSLOG)?;
+    }
+
+    match action {
+        SYSLOG_ACTION_CLOSE | SYSLOG_ACTION_OPEN => Ok(0),
+        SYSLOG_ACTION_READ if len == 0 => Ok(0),
+        SYSLOG_ACTION_READ => copy_log(buf, &klog::read_unread(len)),
+        SYSLOG_ACTION_READ_ALL => copy_log(buf, &klog::read_all(len, false)),
+        SYSLOG_ACTION_READ_CLEAR => copy_log(buf, &klog::read_all(len, true)),
+        SYSLOG_ACTION_CLEAR => {
+            klog::clear();
+            Ok(0)
+        }
+        SYSLOG_ACTION_CONSOLE_OFF | SYSLOG_ACTION_CONSOLE_ON => {
+            klog::set_console_enabled(action == SYSLOG_ACTION_CONSOLE_ON);
+            Ok(0)
+        }
+        SYSLOG_ACTION_CONSOLE_LEVEL => {
+            if !(1..=8).contains(&len) {
+                return Err(Errno::EINVAL);
+            }
+            klog::set_console_loglevel(len as u8);
+            Ok(0)
+        }
+        SYSLOG_ACTION_SIZE_UNREAD => Ok(klog::unread_size()),
+        SYSLOG_ACTION_SIZE_BUFFER => Ok(LOG_BUF_SIZE),
+        _ => Err(Errno::EINVAL),
+    }
+}
@@ -0,0 +1,232 @@
+//! 系统调用跟踪
+//!
+//! 类似strace：对开启跟踪的进程，在系统调用进入与返回时把调用名、
+//! 解码后的参数与返回值写入内核日志。
+//!
+//! 通过 /proc/sys/kernel/strace 控制，写入 `<pid> on` 或 `<pid> off`，
+//! 读取时列出正在跟踪的进程号
+
+use super::*;
+use crate::error::KernelError;
+use crate::fs::procfs;
+use crate::mm::uaccess::strncpy_from_user;
+use crate::sched::{self, Process, TaskId};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt::Write;
+
+/// 路径参数最多显示的长度
+const MAX_PATH_DISPLAY: usize = 64;
+
+/// 参数的显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    /// 有符号整数
+    Int,
+    /// 十六进制（标志、地址）
+    Hex,
+    /// 文件描述符或dirfd
+    Fd,
+    /// 用户态字符串
+    Path,
+}
+
+/// 调用名与参数的显示方式
+fn describe(nr: usize) -> Option<(&'static str, &'static [Arg])> {
+    use Arg::*;
+    Some(match nr {
+        SYS_EVENTFD2 => ("eventfd2", &[Int, Hex]),
+        SYS_EPOLL_CREATE1 => ("epoll_create1", &[Hex]),
+        SYS_EPOLL_CTL => ("epoll_ctl", &[Fd, Int, Fd, Hex]),
+        SYS_EPOLL_PWAIT => ("epoll_pwait", &[Fd, Hex, Int, Int, Hex, Int]),
+        SYS_DUP => ("dup", &[Fd]),
+        SYS_DUP3 => ("dup3", &[Fd, Fd, Hex]),
+        SYS_IOCTL => ("ioctl", &[Fd, Hex, Hex]),
+        SYS_UNLINKAT => ("unlinkat", &[Fd, Path, Hex]),
+        SYS_FTRUNCATE => ("ftruncate", &[Fd, Int]),
+        SYS_OPENAT => ("openat", &[Fd, Path, Hex, Hex]),
+        SYS_CLOSE => ("close", &[Fd]),
+        SYS_LSEEK => ("lseek", &[Fd, Int, Int]),
+        SYS_READ => ("read", &[Fd, Hex, Int]),
+        SYS_WRITE => ("write", &[Fd, Hex, Int]),
+        SYS_READV => ("readv", &[Fd, Hex, Int]),
+        SYS_WRITEV => ("writev", &[Fd, Hex, Int]),
+        SYS_PSELECT6 => ("pselect6", &[Int, Hex, Hex, Hex, Hex, Hex]),
+        SYS_PPOLL => ("ppoll", &[Hex, Int, Hex, Hex, Int]),
+        SYS_SIGNALFD4 => ("signalfd4", &[Fd, Hex, Int, Hex]),
+        SYS_NEWFSTATAT => ("newfstatat", &[Fd, Path, Hex, Hex]),
+        SYS_FSTAT => ("fstat", &[Fd, Hex]),
+        SYS_CAPGET => ("capget", &[Hex, Hex]),
+        SYS_CAPSET => ("capset", &[Hex, Hex]),
+        SYS_EXIT => ("exit", &[Int]),
+        SYS_EXIT_GROUP => ("exit_group", &[Int]),
+        SYS_SET_TID_ADDRESS => ("set_tid_address", &[Hex]),
+        SYS_NANOSLEEP => ("nanosleep", &[Hex, Hex]),
+        SYS_INIT_MODULE => ("init_module", &[Hex, Int, Path]),
+        SYS_DELETE_MODULE => ("delete_module", &[Path, Hex]),
+        SYS_CLOCK_GETTIME => ("clock_gettime", &[Int, Hex]),
+        SYS_CLOCK_NANOSLEEP => ("clock_nanosleep", &[Int, Hex, Hex, Hex]),
+        SYS_SYSLOG => ("syslog", &[Int, Hex, Int]),
+        SYS_SCHED_YIELD => ("sched_yield", &[]),
+        SYS_KILL => ("kill", &[Int, Int]),
+        SYS_TGKILL => ("tgkill", &[Int, Int, Int]),
+        SYS_RT_SIGACTION => ("rt_sigaction", &[Int, Hex, Hex, Int]),
+        SYS_RT_SIGPROCMASK => ("rt_sigprocmask", &[Int, Hex, Hex, Int]),
+        SYS_RT_SIGPENDING => ("rt_sigpending", &[Hex, Int]),
+        SYS_REBOOT => ("reboot", &[Hex, Hex, Hex, Hex]),
+        SYS_SETREGID => ("setregid", &[Int, Int]),
+        SYS_SETGID => ("setgid", &[Int]),
+        SYS_SETREUID => ("setreuid", &[Int, Int]),
+        SYS_SETUID => ("setuid", &[Int]),
+        SYS_SETRESUID => ("setresuid", &[Int, Int, Int]),
+        SYS_GETRESUID => ("getresuid", &[Hex, Hex, Hex]),
+        SYS_SETRESGID => ("setresgid", &[Int, Int, Int]),
+        SYS_GETRESGID => ("getresgid", &[Hex, Hex, Hex]),
+        SYS_SETPGID => ("setpgid", &[Int, Int]),
+        SYS_GETPGID => ("getpgid", &[Int]),
+        SYS_GETSID => ("getsid", &[Int]),
+        SYS_SETSID => ("setsid", &[]),
+        SYS_GETGROUPS => ("getgroups", &[Int, Hex]),
+        SYS_SETGROUPS => ("setgroups", &[Int, Hex]),
+        SYS_UNAME => ("uname", &[Hex]),
+        SYS_GETRLIMIT => ("getrlimit", &[Int, Hex]),
+        SYS_SETRLIMIT => ("setrlimit", &[Int, Hex]),
+        SYS_GETTIMEOFDAY => ("gettimeofday", &[Hex, Hex]),
+        SYS_GETPID => ("getpid", &[]),
+        SYS_GETPPID => ("getppid", &[]),
+        SYS_GETUID => ("getuid", &[]),
+        SYS_GETEUID => ("geteuid", &[]),
+        SYS_GETGID => ("getgid", &[]),
+        SYS_GETEGID => ("getegid", &[]),
+        SYS_GETTID => ("gettid", &[]),
+        SYS_MQ_OPEN => ("mq_open", &[Path, Hex, Hex, Hex]),
+        SYS_MQ_UNLINK => ("mq_unlink", &[Path]),
+        SYS_MQ_TIMEDSEND => ("mq_timedsend", &[Fd, Hex, Int, Int, Hex]),
+        SYS_MQ_TIMEDRECEIVE => ("mq_timedreceive", &[Fd, Hex, Int, Hex, Hex]),
+        SYS_MQ_GETSETATTR => ("mq_getsetattr", &[Fd, Hex, Hex]),
+        SYS_BRK => ("brk", &[Hex]),
+        SYS_MUNMAP => ("munmap", &[Hex, Int]),
+        SYS_CLONE => ("clone", &[Hex, Hex, Hex, Hex, Hex]),
+        SYS_EXECVE => ("execve", &[Path, Hex, Hex]),
+        SYS_MMAP => ("mmap", &[Hex, Int, Hex, Hex, Fd, Hex]),
+        SYS_MPROTECT => ("mprotect", &[Hex, Int, Hex]),
+        SYS_MLOCK => ("mlock", &[Hex, Int]),
+        SYS_MUNLOCK => ("munlock", &[Hex, Int]),
+        SYS_MADVISE => ("madvise", &[Hex, Int, Int]),
+        SYS_WAIT4 => ("wait4", &[Int, Hex, Hex, Hex]),
+        SYS_PRLIMIT64 => ("prlimit64", &[Int, Int, Hex, Hex]),
+        SYS_FINIT_MODULE => ("finit_module", &[Fd, Path, Hex]),
+        SYS_SECCOMP => ("seccomp", &[Int, Hex, Hex]),
+        SYS_GETRANDOM => ("getrandom", &[Hex, Int, Hex]),
+        SYS_KEXEC_FILE_LOAD => ("kexec_file_load", &[Fd, Fd, Int, Path, Hex]),
+        SYS_URING_SETUP => ("uring_setup", &[Int, Hex]),
+        SYS_URING_ENTER => ("uring_enter", &[Fd, Int]),
+        SYS_URING_REGISTER => ("uring_register", &[Fd, Int, Fd]),
+        _ => return None,
+    })
+}
+
+/// 格式化一个参数
+fn format_arg(out: &mut String, kind: Arg, value: usize) {
+    let _ = match kind {
+        Arg::Int => write!(out, "{}", value as isize),
+        Arg::Hex => write!(out, "{:#x}", value),
+        Arg::Fd if value == -100isize as usize => write!(out, "AT_FDCWD"),
+        Arg::Fd => write!(out, "{}", value as isize),
+        Arg::Path => match strncpy_from_user(value, MAX_PATH_DISPLAY) {
+            Ok(path) => write!(out, "{:?}", path),
+            Err(_) => write!(out, "{:#x}", value),
+        },
+    };
+}
+
+/// 记录系统调用进入
+pub(super) fn trace_enter(process: &Process, args: &SyscallArgs) {
+    let tid = sched::current().map_or(0, |task| task.id.0);
+    let mut line = String::new();
+    let _ = write!(line, "[{} {}] ", process.pid, tid);
+
+    match describe(args.nr) {
+        Some((name, kinds)) => {
+            line.push_str(name);
+            line.push('(');
+            for (i, (&kind, &value)) in kinds.iter().zip(args.args.iter()).enumerate() {
+                if i > 0 {
+                    line.push_str(", ");
+                }
+                format_arg(&mut line, kind, value);
+            }
+            line.push(')');
+        }
+        None => {
+            let _ = write!(line, "syscall_{}({:#x}, {:#x}, {:#x}, ...)", args.nr, args.args[0], args.args[1], args.args[2]);
+        }
+    }
+    crate::log_info!("{}", line);
+}
+
+/// 记录系统调用返回
+pub(super) fn trace_exit(process: &Process, args: &SyscallArgs, result: SyscallResult) {
+    let tid = sched::current().map_or(0, |task| task.id.0);
+    let name = describe(args.nr).map_or("syscall", |(name, _)| name);
+    match result {
+        Ok(value) => {
+            crate::log_info!("[{} {}] {} = {:#x}", process.pid, tid, name, value);
+        }
+        Err(errno) => {
+            crate::log_info!("[{} {}] {} = -{} {}", process.pid, tid, name, errno.code(), errno);
+        }
+    }
+}
+
+/// 按进程号查找进程（进程号即首个线程的任务编号）
+fn find_process(pid: usize) -> Option<Arc<Process>> {
+    sched::find_task(TaskId(pid))
+        .and_then(|task| task.process())
+        .filter(|process| process.pid == pid)
+}
+
+/// 生成 /proc/sys/kernel/strace 的内容
+fn proc_read_strace() -> String {
+    let mut out = String::new();
+    let mut pids: alloc::vec::Vec<usize> = sched::tasks()
+        .iter()
+        .filter_map(|task| task.process())
+        .filter(|process| process.is_tracing())
+        .map(|process| process.pid)
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    for pid in pids {
+        let _ = writeln!(out, "{}", pid);
+    }
+    out
+}
+
+/// 处理写入 /proc/sys/kernel/strace 的命令
+fn proc_write_strace(data: &str) -> Result<(), KernelError> {
+    for line in data.lines() {
+        let mut tokens = line.split_whitespace();
+        let pid = match tokens.next() {
+            Some(pid) => pid.parse().map_err(|_| KernelError::InvalidArgument)?,
+            None => continue,
+        };
+        let enabled = match tokens.next() {
+            Some("on") => true,
+            Some("off") => false,
+            _ => return Err(KernelError::InvalidArgument),
+        };
+        find_process(pid).ok_or(KernelError::NotFound)?.set_tracing(enabled);
+    }
+    Ok(())
+}
+
+/// 初始化系统调用跟踪
+pub fn trace_init() -> Result<(), KernelError> {
+    procfs::register(
+        "sys/kernel/strace",
+        Some(Box::new(proc_read_strace)),
+        Some(Box::new(proc_write_strace)),
+    )
+}
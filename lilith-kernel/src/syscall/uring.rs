@@ -0,0 +1,337 @@
+//! 异步系统调用提交环（io_uring-lite）
+//!
+//! 用户态与内核共享一块内存，其中包含提交队列（SQ）与完成队列（CQ）：
+//! - 用户态填写提交项后推进 `sq_tail`，一次 `uring_enter` 即可提交一批操作
+//! - 内核处理后把结果写入完成队列并推进 `cq_tail`，用户态无需陷入即可收取
+//! - 目前支持nop、read、write与fsync，提交时同步执行；指定偏移的读写不改变文件的当前位置
+//! - 可以登记一个eventfd，每批完成项写入后按数量增加其计数，用户态借此等待完成
+//!
+//! 共享内存的布局（偏移由 `uring_setup` 写回）：
+//!
+//! ```text
+//! +0      RingHeader
+//! sq_off  UringSqe[sq_entries]
+//! cq_off  UringCqe[cq_entries]
+//! ```
+//!
+//! 调用号为Lilith私有，布局与Linux的io_uring不兼容
+
+use super::fs::{get_file, install_file, io_errno, pread_to_user, pwrite_from_user, read_to_user, write_from_user};
+use super::{current_process, encode, Errno, SyscallArgs, SyscallResult};
+use crate::fs::eventfd::{self, EventFd};
+use crate::fs::file::{File, FileStat};
+use crate::mm::page_owner::{alloc_pages, free_pages};
+use crate::mm::uaccess::write_user;
+use crate::mm::vma::{page_align_up, AddressSpace, Vma, VmaBacking, VmaFlags};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+/// 提交队列的最大长度
+const MAX_ENTRIES: u32 = 256;
+
+/// 操作码
+pub const URING_OP_NOP: u8 = 0;
+pub const URING_OP_READ: u8 = 1;
+pub const URING_OP_WRITE: u8 = 2;
+pub const URING_OP_FSYNC: u8 = 3;
+
+/// uring_register的操作
+pub const URING_REGISTER_EVENTFD: usize = 0;
+pub const URING_UNREGISTER_EVENTFD: usize = 1;
+
+/// `off` 取此值时使用并推进文件的当前位置，否则按位置读写（pread/pwrite），不改变当前位置
+const OFFSET_CURRENT: u64 = u64::MAX;
+
+/// 共享内存头部
+#[repr(C)]
+struct RingHeader {
+    /// 内核已取走的提交项
+    sq_head: AtomicU32,
+    /// 用户态已填写的提交项
+    sq_tail: AtomicU32,
+    /// 用户态已收取的完成项
+    cq_head: AtomicU32,
+    /// 内核已写入的完成项
+    cq_tail: AtomicU32,
+    /// 因完成队列已满而未能提交的次数
+    overflow: AtomicU32,
+}
+
+/// 提交项
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UringSqe {
+    /// 操作码
+    pub opcode: u8,
+    pub flags: u8,
+    pub _reserved: u16,
+    /// 文件描述符
+    pub fd: i32,
+    /// 文件偏移，`u64::MAX` 表示当前位置
+    pub off: u64,
+    /// 用户缓冲区地址
+    pub addr: u64,
+    /// 缓冲区长度
+    pub len: u32,
+    pub _pad: u32,
+    /// 原样带回完成项
+    pub user_data: u64,
+}
+
+/// 完成项
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UringCqe {
+    /// 对应提交项的 `user_data`
+    pub user_data: u64,
+    /// 结果，与同步系统调用的返回值相同
+    pub res: i32,
+    pub flags: u32,
+}
+
+/// 写回用户态的参数
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct UringParams {
+    sq_entries: u32,
+    cq_entries: u32,
+    sq_off: u32,
+    cq_off: u32,
+    ring_addr: u64,
+    ring_size: u64,
+}
+
+/// 提交环，作为文件保存在描述符表中，关闭最后一个描述符时解除映射
+pub struct Uring {
+    memory: NonNull<u8>,
+    size: usize,
+    sq_entries: u32,
+    cq_entries: u32,
+    sq_off: usize,
+    cq_off: usize,
+    /// 映射到的地址空间与用户地址
+    address_space: Weak<AddressSpace>,
+    user_addr: usize,
+    /// 完成通知
+    eventfd: Mutex<Option<Arc<EventFd>>>,
+}
+
+/// 所有打开的提交环，不延长提交环的寿命
+static RINGS: Mutex<Vec<Weak<Uring>>> = Mutex::new(Vec::new());
+
+// 共享内存只通过原子变量与按值复制访问
+unsafe impl Send for Uring {}
+unsafe impl Sync for Uring {}
+
+impl Uring {
+    /// 分配共享内存并映射到地址空间
+    fn new(entries: u32, address_space: &Arc<AddressSpace>) -> Result<Self, Errno> {
+        let sq_entries = entries.next_power_of_two();
+        let cq_entries = sq_entries * 2;
+        let sq_off = size_of::<RingHeader>().next_multiple_of(8);
+        let cq_off = sq_off + sq_entries as usize * size_of::<UringSqe>();
+        let size = page_align_up(cq_off + cq_entries as usize * size_of::<UringCqe>());
+
+        let memory = alloc_pages(size, "io_uring")?;
+
+        let mapped = address_space.find_free(size).and_then(|user_addr| {
+            address_space
+                .insert(Vma {
+                    start: user_addr,
+                    end: user_addr + size,
+                    flags: VmaFlags::READ | VmaFlags::WRITE | VmaFlags::SHARED,
+                    backing: VmaBacking::Kernel(memory.as_ptr() as usize),
+                })
+                .ok()
+                .map(|()| user_addr)
+        });
+        let user_addr = match mapped {
+            Some(user_addr) => user_addr,
+            None => {
+                unsafe { free_pages(memory, size) };
+                return Err(Errno::ENOMEM);
+            }
+        };
+
+        Ok(Self {
+            memory,
+            size,
+            sq_entries,
+            cq_entries,
+            sq_off,
+            cq_off,
+            address_space: Arc::downgrade(address_space),
+            user_addr,
+            eventfd: Mutex::new(None),
+        })
+    }
+
+    fn header(&self) -> &RingHeader {
+        unsafe { &*(self.memory.as_ptr() as *const RingHeader) }
+    }
+
+    /// 读取提交项（按值复制，用户态随后的修改不影响处理）
+    fn sqe(&self, index: u32) -> UringSqe {
+        let slot = (index & (self.sq_entries - 1)) as usize;
+        unsafe { ptr::read_volatile((self.memory.as_ptr().add(self.sq_off) as *const UringSqe).add(slot)) }
+    }
+
+    /// 写入完成项，完成队列已满时返回 `false`
+    fn post(&self, cqe: UringCqe) -> bool {
+        let header = self.header();
+        let tail = header.cq_tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(header.cq_head.load(Ordering::Acquire)) >= self.cq_entries {
+            return false;
+        }
+        let slot = (tail & (self.cq_entries - 1)) as usize;
+        unsafe {
+            ptr::write_volatile((self.memory.as_ptr().add(self.cq_off) as *mut UringCqe).add(slot), cqe);
+        }
+        header.cq_tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// 完成队列是否还有空位
+    fn cq_has_room(&self) -> bool {
+        let header = self.header();
+        header.cq_tail.load(Ordering::Relaxed).wrapping_sub(header.cq_head.load(Ordering::Acquire)) < self.cq_entries
+    }
+
+    /// 处理最多 `to_submit` 个提交项，返回已处理的数量
+    fn submit(&self, to_submit: u32) -> u32 {
+        let header = self.header();
+        let tail = header.sq_tail.load(Ordering::Acquire);
+        let mut head = header.sq_head.load(Ordering::Relaxed);
+        let mut submitted = 0;
+
+        while head != tail && submitted < to_submit {
+            if !self.cq_has_room() {
+                header.overflow.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+            let sqe = self.sqe(head);
+            let res = encode(execute(&sqe));
+            self.post(UringCqe {
+                user_data: sqe.user_data,
+                res: res as i32,
+                flags: 0,
+            });
+            head = head.wrapping_add(1);
+            header.sq_head.store(head, Ordering::Release);
+            submitted += 1;
+        }
+        if submitted > 0 {
+            if let Some(eventfd) = &*self.eventfd.lock() {
+                eventfd.signal(u64::from(submitted));
+            }
+        }
+        submitted
+    }
+}
+
+impl Drop for Uring {
+    fn drop(&mut self) {
+        // 用户态可能已自行解除映射并复用了该地址，只移除仍指向本环的映射
+        if let Some(address_space) = self.address_space.upgrade() {
+            let backing = VmaBacking::Kernel(self.memory.as_ptr() as usize);
+            if address_space.find(self.user_addr).map_or(false, |vma| vma.backing == backing) {
+                let _ = address_space.remove(self.user_addr, self.user_addr + self.size);
+            }
+        }
+        unsafe { free_pages(self.memory, self.size) };
+        RINGS.lock().retain(|ring| ring.strong_count() > 0);
+    }
+}
+
+impl File for Uring {
+    fn stat(&self) -> FileStat {
+        FileStat::default()
+    }
+}
+
+/// 查找描述符对应的提交环
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别提交环。持有 `RINGS` 时只比较地址、
+/// 不升级其他提交环：升级后再释放的可能是最后一个引用，其 `Drop` 会再次获取 `RINGS`
+fn lookup(file: &Arc<dyn File>) -> Option<Arc<Uring>> {
+    let target = Arc::as_ptr(file) as *const ();
+    let ring = RINGS
+        .lock()
+        .iter()
+        .find(|ring| ring.as_ptr() as *const () == target)
+        .cloned()?;
+    ring.upgrade()
+}
+
+/// 执行一个提交项，返回值与对应的同步系统调用相同
+fn execute(sqe: &UringSqe) -> SyscallResult {
+    if sqe.opcode == URING_OP_NOP {
+        return Ok(0);
+    }
+    let file = get_file(sqe.fd as usize)?;
+    let (addr, len) = (sqe.addr as usize, sqe.len as usize);
+    match sqe.opcode {
+        URING_OP_READ if sqe.off == OFFSET_CURRENT => read_to_user(file.as_ref(), addr, len),
+        URING_OP_READ => pread_to_user(file.as_ref(), sqe.off, addr, len),
+        URING_OP_WRITE if sqe.off == OFFSET_CURRENT => write_from_user(file.as_ref(), addr, len),
+        URING_OP_WRITE => pwrite_from_user(file.as_ref(), sqe.off, addr, len),
+        URING_OP_FSYNC => file.sync().map(|()| 0).map_err(io_errno),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// uring_setup(entries, params)
+pub(super) fn sys_uring_setup(args: &SyscallArgs) -> SyscallResult {
+    let [entries, params, ..] = args.args;
+    if entries == 0 || entries > MAX_ENTRIES as usize {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process()?;
+    let ring = Uring::new(entries as u32, &process.address_space)?;
+
+    let result = UringParams {
+        sq_entries: ring.sq_entries,
+        cq_entries: ring.cq_entries,
+        sq_off: ring.sq_off as u32,
+        cq_off: ring.cq_off as u32,
+        ring_addr: ring.user_addr as u64,
+        ring_size: ring.size as u64,
+    };
+    write_user(params, &result)?;
+    let ring = Arc::new(ring);
+    RINGS.lock().push(Arc::downgrade(&ring));
+    install_file(ring, 0)
+}
+
+/// uring_enter(fd, to_submit)：返回已提交的数量
+pub(super) fn sys_uring_enter(args: &SyscallArgs) -> SyscallResult {
+    let [fd, to_submit, ..] = args.args;
+    let ring = lookup(&get_file(fd)?).ok_or(Errno::EBADF)?;
+    match ring.submit(to_submit.min(u32::MAX as usize) as u32) {
+        0 if !ring.cq_has_room() => Err(Errno::EBUSY),
+        submitted => Ok(submitted as usize),
+    }
+}
+
+/// uring_register(fd, opcode, arg)
+///
+/// `URING_REGISTER_EVENTFD` 登记 `arg` 指定的eventfd（替换已登记的），`URING_UNREGISTER_EVENTFD` 取消登记
+pub(super) fn sys_uring_register(args: &SyscallArgs) -> SyscallResult {
+    let [fd, opcode, arg, ..] = args.args;
+    let ring = lookup(&get_file(fd)?).ok_or(Errno::EBADF)?;
+    match opcode {
+        URING_REGISTER_EVENTFD => {
+            let eventfd = eventfd::lookup(&get_file(arg)?).ok_or(Errno::EINVAL)?;
+            *ring.eventfd.lock() = Some(eventfd);
+        }
+        URING_UNREGISTER_EVENTFD => {
+            ring.eventfd.lock().take().ok_or(Errno::ENXIO)?;
+        }
+        _ => return Err(Errno::EINVAL),
+    }
+    Ok(0)
+}
@@ -0,0 +1,135 @@
+//! 虚拟机控制设备的ioctl
+//!
+//! /dev/lilith-kvm 的每个描述符是一个虚拟机（见 `crate::virt`），请求编号借用Linux KVM的 `0xae` 类型：
+//! - `LKVM_GET_API_VERSION`：接口版本
+//! - `LKVM_SET_MEMORY`：分配客户机内存，之后映射该描述符即可读写
+//! - `LKVM_GET_REGS`/`LKVM_SET_REGS`：虚拟处理器的pc与通用寄存器
+//! - `LKVM_RUN`：运行到需要用户态处理为止，原因与参数写入 `struct lkvm_run`；
+//!   上次因SBI调用返回时，先把其中的 `sbi_ret` 作为该调用的返回值
+
+use super::{Errno, SyscallResult};
+use crate::arch::hypervisor::GuestRegs;
+use crate::fs::file::File;
+use crate::mm::uaccess::{read_user, write_user};
+use crate::virt::{self, VcpuExit};
+use alloc::sync::Arc;
+
+/// ioctl请求
+const LKVM_GET_API_VERSION: usize = 0xae00;
+const LKVM_SET_MEMORY: usize = 0xae01;
+const LKVM_GET_REGS: usize = 0xae02;
+const LKVM_SET_REGS: usize = 0xae03;
+const LKVM_RUN: usize = 0xae04;
+
+/// 接口版本
+const LKVM_API_VERSION: usize = 1;
+
+/// `lkvm_run.exit_reason`
+const LKVM_EXIT_SBI: u32 = 1;
+const LKVM_EXIT_SYSTEM_RESET: u32 = 2;
+const LKVM_EXIT_FAULT: u32 = 3;
+const LKVM_EXIT_INTR: u32 = 4;
+
+/// `LKVM_SET_MEMORY` 的参数
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LkvmMemory {
+    guest_phys_addr: u64,
+    size: u64,
+}
+
+/// `LKVM_GET_REGS`/`LKVM_SET_REGS` 的参数
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LkvmRegs {
+    pc: u64,
+    /// x0-x31，x0被忽略
+    regs: [u64; 32],
+}
+
+/// `LKVM_RUN` 的参数，按运行结束的原因使用其中的字段
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LkvmRun {
+    exit_reason: u32,
+    _pad: u32,
+    /// `LKVM_EXIT_SBI`：扩展编号、功能编号与a0-a5，`sbi_ret` 由用户态填写
+    sbi_eid: u64,
+    sbi_fid: u64,
+    sbi_args: [u64; 6],
+    sbi_ret: [u64; 2],
+    /// `LKVM_EXIT_SYSTEM_RESET`：复位类型与原因
+    reset_type: u64,
+    reset_reason: u64,
+    /// `LKVM_EXIT_FAULT`：scause、stval、客户机物理地址与转换后的指令
+    scause: u64,
+    stval: u64,
+    gpa: u64,
+    htinst: u64,
+}
+
+/// 运行虚拟处理器，结束的原因写入 `arg` 指向的 `lkvm_run`
+fn run(vm: &virt::Vm, arg: usize) -> SyscallResult {
+    let mut info: LkvmRun = read_user(arg)?;
+    if vm.sbi_pending() {
+        vm.set_sbi_result(info.sbi_ret[0] as usize, info.sbi_ret[1] as usize);
+    }
+    match vm.run()? {
+        VcpuExit::Sbi { eid, fid, args } => {
+            info.exit_reason = LKVM_EXIT_SBI;
+            info.sbi_eid = eid as u64;
+            info.sbi_fid = fid as u64;
+            info.sbi_args = args.map(|arg| arg as u64);
+            info.sbi_ret = [0; 2];
+        }
+        VcpuExit::SystemReset { reset_type, reason } => {
+            info.exit_reason = LKVM_EXIT_SYSTEM_RESET;
+            info.reset_type = reset_type as u64;
+            info.reset_reason = reason as u64;
+        }
+        VcpuExit::Fault {
+            scause,
+            stval,
+            gpa,
+            htinst,
+        } => {
+            info.exit_reason = LKVM_EXIT_FAULT;
+            info.scause = scause as u64;
+            info.stval = stval as u64;
+            info.gpa = gpa as u64;
+            info.htinst = htinst as u64;
+        }
+        VcpuExit::Interrupted => info.exit_reason = LKVM_EXIT_INTR,
+    }
+    write_user(arg, &info)?;
+    Ok(0)
+}
+
+/// 虚拟机的ioctl，`file` 不是虚拟机时返回 `None`
+pub(super) fn ioctl(file: &Arc<dyn File>, request: usize, arg: usize) -> Option<SyscallResult> {
+    let vm = virt::lookup(file)?;
+    Some(match request {
+        LKVM_GET_API_VERSION => Ok(LKVM_API_VERSION),
+        LKVM_SET_MEMORY => read_user::<LkvmMemory>(arg).map_err(Errno::from).and_then(|memory| {
+            vm.set_memory(memory.guest_phys_addr as usize, memory.size as usize)?;
+            Ok(0)
+        }),
+        LKVM_GET_REGS => {
+            let regs = vm.regs();
+            let regs = LkvmRegs {
+                pc: regs.pc as u64,
+                regs: regs.regs.map(|reg| reg as u64),
+            };
+            write_user(arg, &regs).map(|()| 0).map_err(Errno::from)
+        }
+        LKVM_SET_REGS => read_user::<LkvmRegs>(arg).map_err(Errno::from).map(|regs| {
+            vm.set_regs(GuestRegs {
+                pc: regs.pc as usize,
+                regs: regs.regs.map(|reg| reg as usize),
+            });
+            0
+        }),
+        LKVM_RUN => run(&vm, arg),
+        _ => Err(Errno::ENOTTY),
+    })
+}
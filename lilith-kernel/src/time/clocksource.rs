@@ -0,0 +1,64 @@
+//! 时钟源
+//!
+//! 时钟源是以固定频率递增的计数器，单调时钟与实时时钟都由它换算得到：
+//! - `ClockSource` trait：读取计数与频率
+//! - RISC-V的 `time` CSR，频率取自设备树 `/cpus` 节点的 `timebase-frequency`
+//! - 初始化前使用QEMU virt平台的默认频率
+//!
+//! vDSO直接用 `rdtime` 读取计数，因此只有 `time` CSR可以作为当前时钟源
+
+use crate::boot::fdt;
+
+/// 默认的计时器频率（QEMU virt平台）
+pub const DEFAULT_FREQUENCY_HZ: u64 = 10_000_000;
+
+/// 时钟源
+pub trait ClockSource: Sync {
+    /// 名称
+    fn name(&self) -> &str;
+
+    /// 读取计数
+    fn read(&self) -> u64;
+
+    /// 计数频率（Hz）
+    fn frequency(&self) -> u64;
+}
+
+/// RISC-V `time` CSR
+pub struct RiscvTime {
+    frequency: u64,
+}
+
+impl RiscvTime {
+    /// 指定频率的 `time` CSR
+    pub const fn new(frequency: u64) -> Self {
+        Self { frequency }
+    }
+
+    /// 从设备树读取频率，设备树中没有时使用默认频率
+    pub fn probe() -> Self {
+        let frequency = fdt::fdt()
+            .and_then(|fdt| fdt.property_u64("/cpus", "timebase-frequency"))
+            .filter(|&frequency| frequency > 0)
+            .unwrap_or(DEFAULT_FREQUENCY_HZ);
+        Self { frequency }
+    }
+}
+
+impl ClockSource for RiscvTime {
+    fn name(&self) -> &str {
+        "riscv-time"
+    }
+
+    fn read(&self) -> u64 {
+        let cycles: u64;
+        unsafe {
+            core::arch::asm!("rdtime {}", out(reg) cycles);
+        }
+        cycles
+    }
+
+    fn frequency(&self) -> u64 {
+        self.frequency
+    }
+}
@@ -0,0 +1,290 @@
+//! 时间管理模块
+//!
+//! 本模块实现了内核的时钟，包括：
+//! - 时钟源抽象，单调时钟由时钟源的计数按频率换算得到
+//! - 实时时钟（CLOCK_REALTIME），由单调时钟加偏移量得到
+//! - 直接设置实时时钟（步进）与按固定速率逐步调整（微调）
+//! - 时钟节拍更新的粗粒度时钟
+//! - 时钟状态位于vDSO数据页中，用户态无需陷入即可读取时间
+//! - 时间轮与高精度定时器，节拍频率可配置，空闲时停止周期性节拍（NO_HZ）
+
+pub mod clocksource;
+pub mod timer;
+
+use crate::error::KernelError;
+use crate::sync::{SeqLock, WaitQueue};
+use clocksource::{ClockSource, RiscvTime, DEFAULT_FREQUENCY_HZ};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Once;
+
+/// 每秒纳秒数
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// 默认的时钟节拍频率
+const DEFAULT_HZ: u64 = 250;
+
+/// 时钟节拍频率，编译时由 `LILITH_HZ` 环境变量指定（如 `LILITH_HZ=100`）
+pub const HZ: u64 = parse_hz(option_env!("LILITH_HZ"));
+
+/// 在编译时解析节拍频率，取值无效时编译失败
+const fn parse_hz(value: Option<&str>) -> u64 {
+    let bytes = match value {
+        Some(value) => value.as_bytes(),
+        None => return DEFAULT_HZ,
+    };
+    let mut hz = 0u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "LILITH_HZ必须是十进制整数");
+        hz = hz * 10 + (bytes[i] - b'0') as u64;
+        i += 1;
+    }
+    assert!(hz >= 10 && hz <= 10_000, "LILITH_HZ必须在10到10000之间");
+    hz
+}
+
+/// 计数到纳秒的换算系数（32.32定点数），vDSO使用相同的换算
+const fn ns_per_cycle_mult(frequency: u64) -> u64 {
+    (NSEC_PER_SEC << 32) / frequency
+}
+
+/// 时钟源注册前使用默认频率的 `time` CSR
+static DEFAULT_CLOCKSOURCE: RiscvTime = RiscvTime::new(DEFAULT_FREQUENCY_HZ);
+
+/// 当前时钟源
+static CLOCKSOURCE: Once<RiscvTime> = Once::new();
+
+/// 当前时钟源的换算系数，与时间数据页中的 `mult` 保持一致
+static CLOCK_MULT: AtomicU64 = AtomicU64::new(ns_per_cycle_mult(DEFAULT_FREQUENCY_HZ));
+
+/// 微调速率上限：每秒最多调整500微秒（500ppm）
+const MAX_SLEW_PPM: i64 = 500;
+
+/// 时钟类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockId {
+    /// 墙上时间，自1970-01-01 00:00:00 UTC起
+    Realtime,
+    /// 单调时间，自系统启动起
+    Monotonic,
+    /// 上一个时钟节拍时的墙上时间
+    RealtimeCoarse,
+    /// 上一个时钟节拍时的单调时间
+    MonotonicCoarse,
+}
+
+/// 秒与纳秒表示的时间
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timespec {
+    /// 秒
+    pub sec: i64,
+    /// 纳秒
+    pub nsec: i64,
+}
+
+impl Timespec {
+    /// 由纳秒数构造
+    pub fn from_nanos(ns: i64) -> Self {
+        Self {
+            sec: ns.div_euclid(NSEC_PER_SEC as i64),
+            nsec: ns.rem_euclid(NSEC_PER_SEC as i64),
+        }
+    }
+
+    /// 转换为纳秒数
+    pub fn as_nanos(&self) -> i64 {
+        self.sec * NSEC_PER_SEC as i64 + self.nsec
+    }
+}
+
+/// 实时时钟状态
+///
+/// 布局固定，vDSO代码按偏移读取各字段
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RealtimeClock {
+    /// 计时器计数到纳秒的换算系数
+    mult: u64,
+    /// 实时时钟相对单调时钟的偏移（纳秒）
+    offset_ns: i64,
+    /// 尚待微调的总量（纳秒）
+    slew_ns: i64,
+    /// 本次微调开始时的单调时间（纳秒）
+    slew_start_ns: u64,
+    /// 上一个时钟节拍时的单调时间（纳秒）
+    coarse_monotonic_ns: u64,
+    /// 上一个时钟节拍时的实时时间（纳秒）
+    coarse_realtime_ns: i64,
+}
+
+/// vDSO时间数据页，以只读方式映射到每个用户进程
+#[repr(C, align(4096))]
+pub struct VdsoTimePage {
+    /// 读者（`clock_gettime` 与vDSO）不会阻塞定时器中断中的写者
+    clock: SeqLock<RealtimeClock>,
+}
+
+/// 时钟状态
+static TIME_PAGE: VdsoTimePage = VdsoTimePage {
+    clock: SeqLock::new(RealtimeClock {
+        mult: ns_per_cycle_mult(DEFAULT_FREQUENCY_HZ),
+        offset_ns: 0,
+        slew_ns: 0,
+        slew_start_ns: 0,
+        coarse_monotonic_ns: 0,
+        coarse_realtime_ns: 0,
+    }),
+};
+
+impl RealtimeClock {
+    /// 截至 `now_ns` 已完成的微调量
+    ///
+    /// vDSO的 `clock_gettime` 以相同的算式计算，修改时需同步修改
+    fn slewed(&self, now_ns: u64) -> i64 {
+        let elapsed = now_ns.saturating_sub(self.slew_start_ns) as i64;
+        let limit = elapsed / 1_000_000 * MAX_SLEW_PPM + elapsed % 1_000_000 * MAX_SLEW_PPM / 1_000_000;
+        self.slew_ns.clamp(-limit, limit)
+    }
+
+    /// `now_ns` 时的实时时间
+    fn realtime_at(&self, now_ns: u64) -> i64 {
+        now_ns as i64 + self.offset_ns + self.slewed(now_ns)
+    }
+
+    /// 将已完成的微调并入偏移量
+    fn fold(&mut self, now_ns: u64) {
+        let done = self.slewed(now_ns);
+        self.offset_ns += done;
+        self.slew_ns -= done;
+        self.slew_start_ns = now_ns;
+    }
+}
+
+/// 当前时钟源
+pub fn clocksource() -> &'static dyn ClockSource {
+    match CLOCKSOURCE.get() {
+        Some(source) => source,
+        None => &DEFAULT_CLOCKSOURCE,
+    }
+}
+
+/// 读取时钟源计数
+pub fn read_cycles() -> u64 {
+    clocksource().read()
+}
+
+/// 单调时间（纳秒）
+pub fn monotonic_ns() -> u64 {
+    let cycles = read_cycles() as u128;
+    ((cycles * CLOCK_MULT.load(Ordering::Relaxed) as u128) >> 32) as u64
+}
+
+/// 微秒表示的时间（`gettimeofday`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeval {
+    /// 秒
+    pub sec: i64,
+    /// 微秒
+    pub usec: i64,
+}
+
+/// 当前的墙上时间（微秒精度）
+pub fn gettimeofday() -> Timeval {
+    let now = Timespec::from_nanos(realtime_ns());
+    Timeval {
+        sec: now.sec,
+        usec: now.nsec / 1000,
+    }
+}
+
+/// 单调时间（毫秒）
+pub fn monotonic_ms() -> u64 {
+    monotonic_ns() / 1_000_000
+}
+
+/// 实时时间（纳秒）
+pub fn realtime_ns() -> i64 {
+    let now = monotonic_ns();
+    TIME_PAGE.clock.read().realtime_at(now)
+}
+
+/// 读取指定时钟
+pub fn clock_gettime(clock: ClockId) -> Timespec {
+    match clock {
+        ClockId::Realtime => Timespec::from_nanos(realtime_ns()),
+        ClockId::Monotonic => Timespec::from_nanos(monotonic_ns() as i64),
+        ClockId::RealtimeCoarse => Timespec::from_nanos(TIME_PAGE.clock.read().coarse_realtime_ns),
+        ClockId::MonotonicCoarse => Timespec::from_nanos(TIME_PAGE.clock.read().coarse_monotonic_ns as i64),
+    }
+}
+
+/// 睡眠到单调时间 `deadline_ns`
+pub fn sleep_until(deadline_ns: u64) {
+    WaitQueue::new().wait_until_deadline(|| false, deadline_ns);
+}
+
+/// 时钟节拍处理：更新粗粒度时钟
+pub fn tick() {
+    let now = monotonic_ns();
+    TIME_PAGE.clock.write(|clock| {
+        clock.coarse_monotonic_ns = now;
+        clock.coarse_realtime_ns = clock.realtime_at(now);
+    });
+}
+
+/// vDSO时间数据页的内核地址
+pub fn vdso_time_page() -> usize {
+    &TIME_PAGE as *const VdsoTimePage as usize
+}
+
+/// 直接设置实时时钟（步进），并取消尚未完成的微调
+pub fn settime(time: Timespec) -> Result<(), KernelError> {
+    if time.sec < 0 || !(0..NSEC_PER_SEC as i64).contains(&time.nsec) {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let now = monotonic_ns();
+    TIME_PAGE.clock.write(|clock| {
+        clock.offset_ns = time.as_nanos() - now as i64;
+        clock.slew_ns = 0;
+        clock.slew_start_ns = now;
+    });
+    Ok(())
+}
+
+/// 以不超过500ppm的速率逐步调整实时时钟（与 `adjtime` 语义相同）
+///
+/// 新的调整量替换尚未完成的部分，返回被替换的剩余量（纳秒）
+pub fn adjtime(delta_ns: i64) -> i64 {
+    let now = monotonic_ns();
+    TIME_PAGE.clock.write(|clock| {
+        clock.fold(now);
+        let remaining = clock.slew_ns;
+        clock.slew_ns = delta_ns;
+        remaining
+    })
+}
+
+/// 时间子系统初始化
+pub fn time_init() -> Result<(), KernelError> {
+    crate::early_println!("初始化时间子系统...");
+
+    // 切换到设备树给出的频率，此前的时间戳按默认频率换算
+    let source = CLOCKSOURCE.call_once(RiscvTime::probe);
+    let frequency = source.frequency();
+    let mult = ns_per_cycle_mult(frequency);
+    CLOCK_MULT.store(mult, Ordering::Relaxed);
+
+    let now = monotonic_ns();
+    TIME_PAGE.clock.write(|clock| {
+        clock.mult = mult;
+        clock.slew_start_ns = now;
+    });
+
+    crate::log_info!("时钟源: {}, {} Hz", source.name(), frequency);
+
+    timer::timer_init()?;
+
+    crate::early_println!("时间子系统初始化完成");
+    Ok(())
+}
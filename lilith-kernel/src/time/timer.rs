@@ -0,0 +1,316 @@
+//! 内核定时器
+//!
+//! 本模块实现了到期后在时钟中断中执行回调的一次性定时器，包括：
+//! - 分层时间轮：4层、每层64个槽，粒度为一个时钟节拍，插入与删除均为O(1)，
+//!   适合大量精度要求不高、经常在到期前取消的超时（如重传）
+//! - 高精度定时器（hrtimer）：按纳秒到期时间排序，直接设置硬件比较值，
+//!   用于睡眠与调度截止时间等需要精确到期的场合
+//! - 每次中断后按最早的高精度定时器与下一个节拍重新设置时钟中断
+//! - 空闲时停止周期性节拍（NO_HZ），只在下一个定时器到期时唤醒；
+//!   启动参数 `nohz=off` 关闭此行为
+//!
+//! 到期的回调在定时器软中断中执行，不能睡眠，执行时不持有定时器的锁
+
+use super::{clocksource, monotonic_ns, HZ, NSEC_PER_SEC};
+use crate::arch::{enable_timer_interrupt, sbi};
+use crate::boot::cmdline;
+use crate::error::KernelError;
+use crate::softirq::{self, SoftIrq};
+use crate::sync::SpinLockIrqSave;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+/// 时钟节拍间隔（纳秒），即时间轮的粒度
+pub const TICK_NS: u64 = NSEC_PER_SEC / HZ;
+
+/// 停止节拍时最长的睡眠时间，保证推迟到节拍中的工作（如唤醒日志读者）最终得到执行
+const NOHZ_MAX_IDLE_NS: u64 = NSEC_PER_SEC;
+
+/// 是否允许空闲时停止节拍
+static NOHZ_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 时间轮层数
+const WHEEL_LEVELS: usize = 4;
+/// 每层槽位数的位数
+const WHEEL_BITS: u32 = 6;
+/// 每层槽位数
+const WHEEL_SLOTS: usize = 1 << WHEEL_BITS;
+/// 时间轮能直接表示的最远到期节拍数，更远的定时器放在最高层并在到期前重新插入
+const WHEEL_RANGE: u64 = 1 << (WHEEL_BITS * WHEEL_LEVELS as u32);
+
+/// 定时器回调
+pub type TimerCallback = Box<dyn FnOnce() + Send>;
+
+/// 下一个定时器编号
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 时间轮中的定时器
+struct WheelEntry {
+    /// 到期节拍
+    expires: u64,
+    callback: TimerCallback,
+}
+
+/// 定时器状态
+struct Timers {
+    /// 时间轮已处理到的节拍
+    jiffies: u64,
+    /// 各层槽位中的定时器编号，已取消的编号在处理槽位时跳过
+    wheel: [[Vec<u64>; WHEEL_SLOTS]; WHEEL_LEVELS],
+    /// 时间轮中尚未到期的定时器
+    wheel_entries: BTreeMap<u64, WheelEntry>,
+    /// 按 `(到期时间, 编号)` 排序的高精度定时器
+    hrtimers: BTreeMap<(u64, u64), TimerCallback>,
+    /// 高精度定时器编号到到期时间
+    hrtimer_deadlines: BTreeMap<u64, u64>,
+}
+
+static TIMERS: SpinLockIrqSave<Timers> = SpinLockIrqSave::new(Timers::new());
+
+/// 已到期、等待定时器软中断执行的回调
+static EXPIRED: SpinLockIrqSave<Vec<TimerCallback>> = SpinLockIrqSave::new(Vec::new());
+
+impl Timers {
+    const fn new() -> Self {
+        const SLOT: Vec<u64> = Vec::new();
+        const LEVEL: [Vec<u64>; WHEEL_SLOTS] = [SLOT; WHEEL_SLOTS];
+        Self {
+            jiffies: 0,
+            wheel: [LEVEL; WHEEL_LEVELS],
+            wheel_entries: BTreeMap::new(),
+            hrtimers: BTreeMap::new(),
+            hrtimer_deadlines: BTreeMap::new(),
+        }
+    }
+
+    /// 按到期节拍把定时器放入对应层的槽位
+    fn place(&mut self, id: u64, expires: u64) {
+        let delta = expires.saturating_sub(self.jiffies).min(WHEEL_RANGE - 1);
+        let expires = self.jiffies + delta;
+        let mut level = 0;
+        while level + 1 < WHEEL_LEVELS && delta >= 1 << (WHEEL_BITS * (level as u32 + 1)) {
+            level += 1;
+        }
+        let slot = (expires >> (WHEEL_BITS * level as u32)) as usize & (WHEEL_SLOTS - 1);
+        self.wheel[level][slot].push(id);
+    }
+
+    /// 时间轮前进一个节拍，把到期的回调加入 `expired`
+    fn advance(&mut self, expired: &mut Vec<TimerCallback>) {
+        self.jiffies += 1;
+
+        // 低层转满一圈时，把上一层当前槽位中的定时器重新分配到低层
+        for level in 1..WHEEL_LEVELS {
+            let shift = WHEEL_BITS * level as u32;
+            if self.jiffies & ((1 << shift) - 1) != 0 {
+                break;
+            }
+            let slot = (self.jiffies >> shift) as usize & (WHEEL_SLOTS - 1);
+            for id in core::mem::take(&mut self.wheel[level][slot]) {
+                if let Some(expires) = self.wheel_entries.get(&id).map(|entry| entry.expires) {
+                    self.place(id, expires);
+                }
+            }
+        }
+
+        let slot = self.jiffies as usize & (WHEEL_SLOTS - 1);
+        for id in core::mem::take(&mut self.wheel[0][slot]) {
+            let expires = match self.wheel_entries.get(&id) {
+                Some(entry) => entry.expires,
+                None => continue,
+            };
+            if expires <= self.jiffies {
+                if let Some(entry) = self.wheel_entries.remove(&id) {
+                    expired.push(entry.callback);
+                }
+            } else {
+                // 超出时间轮范围的定时器
+                self.place(id, expires);
+            }
+        }
+    }
+
+    /// 处理截至 `now` 到期的定时器
+    fn expire(&mut self, now: u64, expired: &mut Vec<TimerCallback>) {
+        let target = now / TICK_NS;
+        if self.wheel_entries.is_empty() {
+            // 没有时间轮定时器时直接跳到当前节拍
+            self.jiffies = self.jiffies.max(target);
+        }
+        while self.jiffies < target {
+            self.advance(expired);
+        }
+
+        while let Some(entry) = self.hrtimers.first_entry() {
+            let (deadline, id) = *entry.key();
+            if deadline > now {
+                break;
+            }
+            expired.push(entry.remove());
+            self.hrtimer_deadlines.remove(&id);
+        }
+    }
+
+    /// 最早到期的定时器（纳秒），时间轮定时器按到期节拍计算
+    fn next_expiry(&self) -> Option<u64> {
+        let wheel = self.wheel_entries.values().map(|entry| entry.expires * TICK_NS).min();
+        let hrtimer = self.hrtimers.keys().next().map(|&(deadline, _)| deadline);
+        match (wheel, hrtimer) {
+            (Some(wheel), Some(hrtimer)) => Some(wheel.min(hrtimer)),
+            (wheel, hrtimer) => wheel.or(hrtimer),
+        }
+    }
+
+    /// 下一次需要处理的时间：下一个节拍与最早的高精度定时器中较早者
+    fn next_event(&self) -> u64 {
+        let next_tick = (self.jiffies + 1) * TICK_NS;
+        self.hrtimers
+            .keys()
+            .next()
+            .map_or(next_tick, |&(deadline, _)| deadline.min(next_tick))
+    }
+}
+
+/// 按单调时间（纳秒）设置下一次时钟中断
+fn program(deadline_ns: u64) {
+    let frequency = clocksource().frequency() as u128;
+    let cycles = deadline_ns as u128 * frequency / NSEC_PER_SEC as u128;
+    sbi::set_timer(cycles as u64);
+}
+
+/// 定时器句柄
+///
+/// 丢弃句柄不会取消定时器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timer {
+    id: u64,
+    /// 是否为高精度定时器
+    hrtimer: bool,
+}
+
+impl Timer {
+    /// 在 `duration` 之后执行回调，到期时间向上取整到时钟节拍
+    pub fn schedule_after<F: FnOnce() + Send + 'static>(duration: Duration, callback: F) -> Self {
+        let ticks = (duration.as_nanos() as u64).div_ceil(TICK_NS);
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+        let mut timers = TIMERS.lock();
+        // 当前节拍已经开始，多等一个节拍保证不会提前到期
+        let expires = timers.jiffies + ticks + 1;
+        timers.wheel_entries.insert(
+            id,
+            WheelEntry {
+                expires,
+                callback: Box::new(callback),
+            },
+        );
+        timers.place(id, expires);
+        Self { id, hrtimer: false }
+    }
+
+    /// 在单调时间 `deadline_ns` 时执行回调（高精度）
+    pub fn schedule_at_ns<F: FnOnce() + Send + 'static>(deadline_ns: u64, callback: F) -> Self {
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+        let mut timers = TIMERS.lock();
+        let earliest = timers.hrtimers.keys().next().map(|&(deadline, _)| deadline);
+        timers.hrtimers.insert((deadline_ns, id), Box::new(callback));
+        timers.hrtimer_deadlines.insert(id, deadline_ns);
+        // 比已设置的中断更早时重新设置
+        if earliest.map_or(true, |earliest| deadline_ns < earliest) {
+            program(timers.next_event());
+        }
+        Self { id, hrtimer: true }
+    }
+
+    /// 在 `duration` 之后执行回调（高精度）
+    pub fn hrtimer_after<F: FnOnce() + Send + 'static>(duration: Duration, callback: F) -> Self {
+        let deadline = monotonic_ns().saturating_add(duration.as_nanos() as u64);
+        Self::schedule_at_ns(deadline, callback)
+    }
+
+    /// 取消定时器，返回定时器是否尚未到期
+    pub fn cancel(&self) -> bool {
+        let mut timers = TIMERS.lock();
+        if self.hrtimer {
+            match timers.hrtimer_deadlines.remove(&self.id) {
+                Some(deadline) => timers.hrtimers.remove(&(deadline, self.id)).is_some(),
+                None => false,
+            }
+        } else {
+            // 槽位中的编号在处理时跳过
+            timers.wheel_entries.remove(&self.id).is_some()
+        }
+    }
+
+    /// 定时器是否尚未到期也未取消
+    pub fn is_pending(&self) -> bool {
+        let timers = TIMERS.lock();
+        if self.hrtimer {
+            timers.hrtimer_deadlines.contains_key(&self.id)
+        } else {
+            timers.wheel_entries.contains_key(&self.id)
+        }
+    }
+}
+
+/// 时钟中断处理：取出到期的定时器交给定时器软中断，并设置下一次中断，返回是否经过了新的节拍
+pub fn run_timers() -> bool {
+    let now = monotonic_ns();
+    let mut expired = Vec::new();
+    let ticked = {
+        let mut timers = TIMERS.lock();
+        let before = timers.jiffies;
+        timers.expire(now, &mut expired);
+        program(timers.next_event());
+        timers.jiffies != before
+    };
+
+    if !expired.is_empty() {
+        EXPIRED.lock().append(&mut expired);
+        softirq::raise_softirq(SoftIrq::Timer);
+    }
+    ticked
+}
+
+/// 定时器软中断：执行到期的回调
+fn timer_softirq() {
+    let expired = core::mem::take(&mut *EXPIRED.lock());
+    for callback in expired {
+        callback();
+    }
+}
+
+/// 空闲任务等待中断前调用：停止周期性节拍，只在下一个定时器到期时产生中断
+///
+/// 返回是否停止了节拍。须在关中断时调用，醒来后调用 `tick_nohz_idle_exit`
+pub fn tick_nohz_idle_enter() -> bool {
+    if !NOHZ_ENABLED.load(Ordering::Relaxed) {
+        return false;
+    }
+    let limit = monotonic_ns().saturating_add(NOHZ_MAX_IDLE_NS);
+    let timers = TIMERS.lock();
+    program(timers.next_expiry().map_or(limit, |next| next.min(limit)));
+    true
+}
+
+/// 空闲任务醒来后调用：补上停止期间的节拍并恢复周期性节拍
+pub fn tick_nohz_idle_exit() {
+    run_timers();
+}
+
+/// 定时器初始化：设置第一次时钟中断并允许时钟中断
+pub fn timer_init() -> Result<(), KernelError> {
+    softirq::open_softirq(SoftIrq::Timer, timer_softirq);
+    if cmdline::get("nohz") == Some("off") {
+        NOHZ_ENABLED.store(false, Ordering::Relaxed);
+    }
+    {
+        let mut timers = TIMERS.lock();
+        timers.jiffies = monotonic_ns() / TICK_NS;
+        program(timers.next_event());
+    }
+    enable_timer_interrupt();
+    Ok(())
+}
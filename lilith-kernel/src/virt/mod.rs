@@ -0,0 +1,337 @@
+//! 虚拟机
+//!
+//! 本模块在H扩展（见 `arch::hypervisor`）之上实现了最小的虚拟机，包括：
+//! - /dev/lilith-kvm：每次打开得到一个带一个虚拟处理器的虚拟机，通过ioctl配置与运行
+//! - 客户机内存：一段连续的客户机物理内存，映射该文件即可读写（如放入客户机内核映像）
+//! - 运行循环：客户机陷入后在内核中处理能处理的原因，其余原因返回用户态的虚拟机监视器
+//! - 陷入并模拟客户机的SBI调用（见 `sbi`）
+//! - 定时器：客户机设置的时间到达后注入VS级时钟中断，客户机执行wfi时让出处理器
+//!
+//! 客户机内存以外的访问（MMIO）与无法处理的异常结束运行，由用户态决定如何继续。
+//! 硬件没有H扩展时 /dev/lilith-kvm 不存在；打开它需要root或 `CAP_SYS_ADMIN`，
+//! 所有客户机内存合计不超过可用内存的一半
+
+pub mod sbi;
+
+use crate::arch::hypervisor::{self, GuestCsrs, GuestExit, GuestMemory, GuestRegs, HVIP_VSTIP};
+use crate::arch::{local_irq_restore, local_irq_save};
+use crate::boot::memory_detect;
+use crate::error::KernelError;
+use crate::fs::file::{File, FileStat, S_IFCHR};
+use crate::fs::procfs;
+use crate::mm::vma::{MapOwner, PAGE_SIZE};
+use crate::sched::{self, capability::CAP_SYS_ADMIN};
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// 控制设备的路径
+pub const DEVICE_PATH: &str = "/dev/lilith-kvm";
+
+/// scause的中断位与宿主的时钟中断
+const SCAUSE_INTERRUPT: usize = 1 << (usize::BITS - 1);
+const IRQ_S_TIMER: usize = 5;
+
+/// 客户机引起的异常
+const EXC_VS_ECALL: usize = 10;
+const EXC_INSN_GUEST_PAGE_FAULT: usize = 20;
+const EXC_LOAD_GUEST_PAGE_FAULT: usize = 21;
+const EXC_VIRTUAL_INSTRUCTION: usize = 22;
+const EXC_STORE_GUEST_PAGE_FAULT: usize = 23;
+
+/// wfi的编码
+const INSN_WFI: usize = 0x1050_0073;
+
+/// 客户机定时器未设置
+const NO_TIMER: u64 = u64::MAX;
+
+/// 运行结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcpuExit {
+    /// 交给用户态处理的SBI调用，下次运行前通过 `Vm::set_sbi_result` 设置返回值
+    Sbi { eid: usize, fid: usize, args: [usize; 6] },
+    /// 客户机请求关机或重启
+    SystemReset { reset_type: usize, reason: usize },
+    /// 客户机访问了内存以外的地址（`gpa` 为客户机物理地址）或引起无法处理的异常
+    Fault {
+        scause: usize,
+        stval: usize,
+        gpa: usize,
+        htinst: usize,
+    },
+    /// 进程被停止或正在退出
+    Interrupted,
+}
+
+/// 虚拟处理器的状态
+struct VcpuState {
+    regs: GuestRegs,
+    csrs: GuestCsrs,
+    /// 客户机定时器的到期时间（time计数）
+    timer: u64,
+    /// 上次交给用户态的SBI调用还没有设置返回值
+    sbi_pending: bool,
+}
+
+/// 计入合计用量的客户机内存，释放时（虚拟机关闭且映射都已解除）扣除
+struct GuestRam(GuestMemory);
+
+impl Deref for GuestRam {
+    type Target = GuestMemory;
+
+    fn deref(&self) -> &GuestMemory {
+        &self.0
+    }
+}
+
+impl Drop for GuestRam {
+    fn drop(&mut self) {
+        GUEST_MEMORY.fetch_sub(self.0.size(), Ordering::Relaxed);
+    }
+}
+
+/// 虚拟机
+pub struct Vm {
+    /// 客户机内存，设置后不再改变
+    memory: Mutex<Option<Arc<GuestRam>>>,
+    vcpu: Mutex<VcpuState>,
+}
+
+/// 打开的虚拟机，用于识别ioctl的目标
+static VMS: Mutex<Vec<Weak<Vm>>> = Mutex::new(Vec::new());
+
+/// 硬件是否支持虚拟化，启动时检测
+static AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// 客户机陷入宿主的次数
+static EXITS: AtomicU64 = AtomicU64::new(0);
+
+/// 所有虚拟机的客户机内存合计（字节）
+static GUEST_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// 打开 /dev/lilith-kvm，创建新的虚拟机
+///
+/// 硬件不支持时返回 `NotFound`；调用者不是root也没有 `CAP_SYS_ADMIN` 时返回 `PermissionDenied`
+pub fn open() -> Result<Arc<dyn File>, KernelError> {
+    if !AVAILABLE.load(Ordering::Relaxed) {
+        return Err(KernelError::NotFound);
+    }
+    let is_root = sched::current_process().map_or(true, |process| process.credentials().is_root());
+    if !is_root && !sched::capable(CAP_SYS_ADMIN) {
+        return Err(KernelError::PermissionDenied);
+    }
+    let vm = Arc::new(Vm {
+        memory: Mutex::new(None),
+        vcpu: Mutex::new(VcpuState {
+            regs: GuestRegs::default(),
+            csrs: GuestCsrs::default(),
+            timer: NO_TIMER,
+            sbi_pending: false,
+        }),
+    });
+    let mut vms = VMS.lock();
+    vms.retain(|vm| vm.strong_count() > 0);
+    vms.push(Arc::downgrade(&vm));
+    Ok(vm)
+}
+
+/// 查找描述符对应的虚拟机
+///
+/// 描述符表只保存 `dyn File`，通过比较对象地址识别虚拟机
+pub fn lookup(file: &Arc<dyn File>) -> Option<Arc<Vm>> {
+    let target = Arc::as_ptr(file) as *const ();
+    VMS.lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|vm| Arc::as_ptr(vm) as *const () == target)
+}
+
+/// 为 `size` 字节的客户机内存记账，合计超过可用内存的一半时返回 `OutOfMemory`
+fn reserve_memory(size: usize) -> Result<(), KernelError> {
+    let limit = memory_detect::get_memory_map().map_or(0, |map| map.available_memory) / 2;
+    GUEST_MEMORY
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(size).filter(|&total| total <= limit)
+        })
+        .map(|_| ())
+        .map_err(|_| KernelError::OutOfMemory)
+}
+
+impl Vm {
+    /// 分配 `size` 字节的客户机内存，从客户机物理地址 `base` 开始
+    ///
+    /// 已经设置过时返回 `AlreadyExists`，超出客户机内存的合计上限时返回 `OutOfMemory`
+    pub fn set_memory(&self, base: usize, size: usize) -> Result<(), KernelError> {
+        let mut memory = self.memory.lock();
+        if memory.is_some() {
+            return Err(KernelError::AlreadyExists);
+        }
+        reserve_memory(size)?;
+        match GuestMemory::new(base, size) {
+            Ok(ram) => *memory = Some(Arc::new(GuestRam(ram))),
+            Err(error) => {
+                GUEST_MEMORY.fetch_sub(size, Ordering::Relaxed);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// 虚拟处理器的通用寄存器与pc
+    pub fn regs(&self) -> GuestRegs {
+        self.vcpu.lock().regs
+    }
+
+    /// 设置虚拟处理器的通用寄存器与pc（如客户机内核的入口、a0为hart编号、a1为设备树地址）
+    pub fn set_regs(&self, regs: GuestRegs) {
+        self.vcpu.lock().regs = regs;
+    }
+
+    /// 是否有交给用户态的SBI调用在等待返回值
+    pub fn sbi_pending(&self) -> bool {
+        self.vcpu.lock().sbi_pending
+    }
+
+    /// 设置交给用户态的SBI调用的返回值（a0为错误码，a1为值）
+    pub fn set_sbi_result(&self, error: usize, value: usize) {
+        let mut vcpu = self.vcpu.lock();
+        if vcpu.sbi_pending {
+            vcpu.regs.regs[sbi::REG_A0] = error;
+            vcpu.regs.regs[sbi::REG_A0 + 1] = value;
+            vcpu.sbi_pending = false;
+        }
+    }
+
+    /// 运行虚拟处理器直到需要用户态处理，没有设置客户机内存时返回 `InvalidArgument`
+    pub fn run(&self) -> Result<VcpuExit, KernelError> {
+        let memory = self.memory.lock().clone().ok_or(KernelError::InvalidArgument)?;
+        let mut vcpu = self.vcpu.lock();
+        let process = sched::current_process();
+        loop {
+            // 停止与exit_group在下次进入内核时生效，先返回用户态
+            if process
+                .as_ref()
+                .is_some_and(|process| process.is_exiting() || process.is_stopped())
+            {
+                return Ok(VcpuExit::Interrupted);
+            }
+            let hvip = if hypervisor::read_time() >= vcpu.timer {
+                HVIP_VSTIP
+            } else {
+                0
+            };
+            let VcpuState { regs, csrs, .. } = &mut *vcpu;
+            let flags = local_irq_save();
+            let exit = hypervisor::run_guest(&memory, regs, csrs, hvip);
+            // 打断客户机的宿主中断在这里得到处理
+            local_irq_restore(flags);
+            EXITS.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(exit) = handle_exit(&memory, &mut vcpu, &exit) {
+                return Ok(exit);
+            }
+        }
+    }
+}
+
+/// 引起虚拟指令异常的指令
+///
+/// 硬件可以不把指令写入stval（为0），此时依次使用htinst与客户机内存中pc处的指令
+fn trapped_insn(memory: &GuestMemory, vcpu: &VcpuState, exit: &GuestExit) -> Option<usize> {
+    if exit.stval != 0 {
+        return Some(exit.stval);
+    }
+    if exit.htinst != 0 {
+        return Some(exit.htinst);
+    }
+    memory
+        .fetch_insn(vcpu.csrs.vsatp, vcpu.regs.pc)
+        .map(|insn| insn as usize)
+}
+
+/// 在内核中处理客户机的陷入，需要返回用户态时返回原因
+fn handle_exit(memory: &GuestMemory, vcpu: &mut VcpuState, exit: &GuestExit) -> Option<VcpuExit> {
+    if exit.scause & SCAUSE_INTERRUPT != 0 {
+        // 宿主的时钟节拍：让出处理器，客户机不独占hart
+        if exit.scause & !SCAUSE_INTERRUPT == IRQ_S_TIMER {
+            sched::yield_now();
+        }
+        return None;
+    }
+    match exit.scause {
+        EXC_VS_ECALL => {
+            vcpu.regs.pc += 4;
+            let exit = sbi::handle(&mut vcpu.regs, &mut vcpu.timer);
+            vcpu.sbi_pending = matches!(exit, Some(VcpuExit::Sbi { .. }));
+            exit
+        }
+        EXC_VIRTUAL_INSTRUCTION if trapped_insn(memory, vcpu, exit) == Some(INSN_WFI) => {
+            // 等待的中断只可能是定时器，未到期时让出处理器
+            vcpu.regs.pc += 4;
+            if hypervisor::read_time() < vcpu.timer {
+                sched::yield_now();
+            }
+            None
+        }
+        scause => {
+            let gpa = match scause {
+                EXC_INSN_GUEST_PAGE_FAULT | EXC_LOAD_GUEST_PAGE_FAULT | EXC_STORE_GUEST_PAGE_FAULT => {
+                    (exit.htval << 2) | (exit.stval & 3)
+                }
+                _ => 0,
+            };
+            Some(VcpuExit::Fault {
+                scause,
+                stval: exit.stval,
+                gpa,
+                htinst: exit.htinst,
+            })
+        }
+    }
+}
+
+impl File for Vm {
+    /// 映射客户机内存，`offset` 为相对客户机内存起点的偏移
+    fn mmap(&self, offset: usize, len: usize, _writable: bool) -> Result<(usize, MapOwner), KernelError> {
+        let memory = self.memory.lock().clone().ok_or(KernelError::InvalidArgument)?;
+        let end = offset.checked_add(len).ok_or(KernelError::InvalidArgument)?;
+        if offset % PAGE_SIZE != 0 || end > memory.size() {
+            return Err(KernelError::InvalidArgument);
+        }
+        Ok((memory.addr() + offset, memory as MapOwner))
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            mode: S_IFCHR | 0o600,
+            // 与Linux的 /dev/kvm 相同的杂项设备号
+            rdev: (10 << 8) | 232,
+            ..FileStat::default()
+        }
+    }
+}
+
+/// 检测硬件的虚拟化支持，登记 /proc/virt
+pub fn virt_init() -> Result<(), KernelError> {
+    if hypervisor::available() {
+        AVAILABLE.store(true, Ordering::Relaxed);
+        crate::log_info!("虚拟化: 支持H扩展，{} 可用", DEVICE_PATH);
+    }
+    procfs::register("virt", Some(Box::new(read_stats)), None)
+}
+
+/// /proc/virt：是否支持虚拟化、打开的虚拟机数、客户机内存合计（KiB）与客户机陷入的次数
+fn read_stats() -> String {
+    format!(
+        "available {}\nvms {}\nmemory {}\nexits {}\n",
+        AVAILABLE.load(Ordering::Relaxed) as u8,
+        VMS.lock().iter().filter(|vm| vm.strong_count() > 0).count(),
+        GUEST_MEMORY.load(Ordering::Relaxed) / 1024,
+        EXITS.load(Ordering::Relaxed)
+    )
+}
@@ -0,0 +1,137 @@
+//! 客户机SBI调用的模拟
+//!
+//! 客户机在VS-mode执行ecall时陷入宿主，按SBI规范的扩展编号（a7）与功能编号（a6）处理：
+//! - 基本扩展：规范版本2.0、实现编号与版本、探测扩展，机器编号都为0
+//! - 定时器扩展与旧版set_timer：记录到期时间，到期前不再注入时钟中断
+//! - 系统复位扩展与旧版shutdown：结束运行，由用户态关闭或重启虚拟机
+//! - 调试控制台扩展与旧版控制台：交给用户态处理，用户态提供返回值
+//! - 其他扩展返回 `SBI_ERR_NOT_SUPPORTED`
+//!
+//! 旧版扩展只使用a0返回，用户态设置的值（a1）被客户机忽略
+
+use super::VcpuExit;
+use crate::arch::hypervisor::GuestRegs;
+
+/// 参数与返回值寄存器
+pub const REG_A0: usize = 10;
+const REG_A6: usize = 16;
+const REG_A7: usize = 17;
+
+/// 扩展编号
+const EID_LEGACY_SET_TIMER: usize = 0x00;
+const EID_LEGACY_CONSOLE_PUTCHAR: usize = 0x01;
+const EID_LEGACY_CONSOLE_GETCHAR: usize = 0x02;
+const EID_LEGACY_SHUTDOWN: usize = 0x08;
+const EID_BASE: usize = 0x10;
+const EID_TIME: usize = 0x5449_4d45;
+const EID_SRST: usize = 0x5352_5354;
+const EID_DBCN: usize = 0x4442_434e;
+
+/// 基本扩展的功能编号
+const BASE_GET_SPEC_VERSION: usize = 0;
+const BASE_GET_IMPL_ID: usize = 1;
+const BASE_GET_IMPL_VERSION: usize = 2;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+/// 错误码
+const SBI_SUCCESS: isize = 0;
+const SBI_ERR_NOT_SUPPORTED: isize = -2;
+
+/// 实现的SBI规范版本（2.0）
+const SPEC_VERSION: usize = 2 << 24;
+/// 实现编号（未在规范中登记）与版本
+const IMPL_ID: usize = 0x4c4b;
+const IMPL_VERSION: usize = 1;
+
+/// 系统复位扩展：关机
+const RESET_TYPE_SHUTDOWN: usize = 0;
+
+/// 实现了的扩展（`probe_extension` 返回1）
+const EXTENSIONS: [usize; 8] = [
+    EID_LEGACY_SET_TIMER,
+    EID_LEGACY_CONSOLE_PUTCHAR,
+    EID_LEGACY_CONSOLE_GETCHAR,
+    EID_LEGACY_SHUTDOWN,
+    EID_BASE,
+    EID_TIME,
+    EID_SRST,
+    EID_DBCN,
+];
+
+/// 设置返回值
+fn set_return(regs: &mut GuestRegs, error: isize, value: usize) {
+    regs.regs[REG_A0] = error as usize;
+    regs.regs[REG_A0 + 1] = value;
+}
+
+/// 处理客户机的SBI调用（pc已越过ecall），`timer` 为客户机定时器的到期时间
+///
+/// 在内核中完成时设置返回值并返回 `None`，需要用户态处理时返回运行结束的原因
+pub fn handle(regs: &mut GuestRegs, timer: &mut u64) -> Option<VcpuExit> {
+    let eid = regs.regs[REG_A7];
+    let fid = regs.regs[REG_A6];
+    let mut args = [0; 6];
+    args.copy_from_slice(&regs.regs[REG_A0..REG_A0 + 6]);
+
+    match (eid, fid) {
+        (EID_BASE, BASE_GET_SPEC_VERSION) => set_return(regs, SBI_SUCCESS, SPEC_VERSION),
+        (EID_BASE, BASE_GET_IMPL_ID) => set_return(regs, SBI_SUCCESS, IMPL_ID),
+        (EID_BASE, BASE_GET_IMPL_VERSION) => set_return(regs, SBI_SUCCESS, IMPL_VERSION),
+        (EID_BASE, BASE_PROBE_EXTENSION) => set_return(regs, SBI_SUCCESS, EXTENSIONS.contains(&args[0]) as usize),
+        // mvendorid、marchid与mimpid
+        (EID_BASE, 4..=6) => set_return(regs, SBI_SUCCESS, 0),
+        (EID_TIME, 0) => {
+            *timer = args[0] as u64;
+            set_return(regs, SBI_SUCCESS, 0);
+        }
+        (EID_LEGACY_SET_TIMER, _) => {
+            *timer = args[0] as u64;
+            regs.regs[REG_A0] = 0;
+        }
+        (EID_SRST, 0) => {
+            return Some(VcpuExit::SystemReset {
+                reset_type: args[0],
+                reason: args[1],
+            })
+        }
+        (EID_LEGACY_SHUTDOWN, _) => {
+            return Some(VcpuExit::SystemReset {
+                reset_type: RESET_TYPE_SHUTDOWN,
+                reason: 0,
+            })
+        }
+        (EID_LEGACY_CONSOLE_PUTCHAR | EID_LEGACY_CONSOLE_GETCHAR | EID_DBCN, _) => {
+            return Some(VcpuExit::Sbi { eid, fid, args })
+        }
+        _ => set_return(regs, SBI_ERR_NOT_SUPPORTED, 0),
+    }
+    None
+}
+
+crate::kernel_test! {
+    fn virt_sbi_emulation() {
+        let mut regs = GuestRegs::default();
+        let mut timer = u64::MAX;
+
+        regs.regs[REG_A7] = EID_BASE;
+        regs.regs[REG_A6] = BASE_PROBE_EXTENSION;
+        regs.regs[REG_A0] = EID_TIME;
+        assert_eq!(handle(&mut regs, &mut timer), None);
+        assert_eq!(regs.regs[REG_A0..REG_A0 + 2], [0, 1]);
+
+        regs.regs[REG_A7] = EID_TIME;
+        regs.regs[REG_A6] = 0;
+        regs.regs[REG_A0] = 12345;
+        assert_eq!(handle(&mut regs, &mut timer), None);
+        assert_eq!(timer, 12345);
+
+        regs.regs[REG_A7] = EID_DBCN;
+        regs.regs[REG_A6] = 2;
+        regs.regs[REG_A0] = b'x' as usize;
+        assert!(matches!(handle(&mut regs, &mut timer), Some(VcpuExit::Sbi { eid: EID_DBCN, fid: 2, .. })));
+
+        regs.regs[REG_A7] = 0x1234_5678;
+        assert_eq!(handle(&mut regs, &mut timer), None);
+        assert_eq!(regs.regs[REG_A0] as isize, SBI_ERR_NOT_SUPPORTED);
+    }
+}
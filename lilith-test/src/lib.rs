@@ -0,0 +1,311 @@
+//! Lilith OS 宿主机集成测试框架
+//!
+//! 本库在宿主机上构建内核，在QEMU中启动并通过串口与其交互，包括：
+//! - 按Cargo特性构建内核（每组特性使用独立的目标目录，互不覆盖）
+//! - 启动 `qemu-system-riscv64 -machine virt`，串口连接到标准输入输出
+//! - expect风格的交互：等待输出中出现指定字符串（带超时），向串口发送一行输入
+//! - 等待QEMU退出并取得退出码（内核测试通过SiFive测试设备报告结果）
+//!
+//! 环境变量：
+//! - `LILITH_QEMU`：QEMU可执行文件，默认 `qemu-system-riscv64`
+//! - `LILITH_KERNEL`：使用已构建的内核ELF，不再调用cargo构建
+//!
+//! 找不到QEMU时 `Qemu::available` 返回 `false`，测试应跳过而不是失败
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 内核的目标三元组
+pub const KERNEL_TARGET: &str = "riscv64gc-unknown-none-elf";
+
+/// 默认的等待超时
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 测试框架错误
+#[derive(Debug)]
+pub enum Error {
+    /// 构建内核失败
+    Build(String),
+    /// 启动QEMU或读写其管道失败
+    Io(io::Error),
+    /// 超时前没有等到期望的输出
+    Timeout {
+        /// 期望的字符串
+        expected: String,
+        /// 到超时为止的全部输出
+        output: String,
+    },
+    /// 等到期望的输出之前QEMU已退出
+    Exited {
+        /// 期望的字符串
+        expected: String,
+        /// QEMU退出前的全部输出
+        output: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Build(message) => write!(f, "构建内核失败: {}", message),
+            Error::Io(err) => write!(f, "QEMU I/O错误: {}", err),
+            Error::Timeout { expected, output } => {
+                write!(f, "等待 {:?} 超时，串口输出:\n{}", expected, output)
+            }
+            Error::Exited { expected, output } => {
+                write!(f, "等到 {:?} 之前QEMU已退出，串口输出:\n{}", expected, output)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// 仓库根目录
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("lilith-test 位于仓库根目录下")
+        .to_path_buf()
+}
+
+/// 以 `features` 构建内核（release），返回内核ELF的路径
+///
+/// `default_features` 为 `false` 时相当于 `--no-default-features`。
+/// 设置了 `LILITH_KERNEL` 时直接返回该路径
+pub fn build_kernel(default_features: bool, features: &[&str]) -> Result<PathBuf, Error> {
+    if let Some(kernel) = std::env::var_os("LILITH_KERNEL") {
+        return Ok(PathBuf::from(kernel));
+    }
+
+    let root = repo_root();
+    let mut name = String::from(if default_features { "default" } else { "minimal" });
+    for feature in features {
+        name.push('-');
+        name.push_str(feature);
+    }
+    let target_dir = root.join("target").join("lilith-test").join(name);
+
+    let mut cargo = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    cargo
+        .arg("build")
+        .arg("--release")
+        .arg("--manifest-path")
+        .arg(root.join("lilith-kernel").join("Cargo.toml"))
+        .arg("--target")
+        .arg(KERNEL_TARGET)
+        .arg("--target-dir")
+        .arg(&target_dir);
+    if !default_features {
+        cargo.arg("--no-default-features");
+    }
+    if !features.is_empty() {
+        cargo.arg("--features").arg(features.join(","));
+    }
+    let output = cargo.output()?;
+    if !output.status.success() {
+        return Err(Error::Build(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(target_dir.join(KERNEL_TARGET).join("release").join("lilith-kernel"))
+}
+
+/// 串口输出缓冲区，由读取线程填充
+#[derive(Default)]
+struct Output {
+    /// 到目前为止的全部输出
+    data: Vec<u8>,
+    /// QEMU的标准输出是否已关闭
+    closed: bool,
+}
+
+/// 运行中的QEMU虚拟机
+pub struct Qemu {
+    child: Child,
+    stdin: ChildStdin,
+    output: Arc<(Mutex<Output>, Condvar)>,
+    /// 已被 `expect` 消耗的输出长度，之后的匹配从这里开始
+    consumed: usize,
+}
+
+impl Qemu {
+    /// QEMU可执行文件
+    fn program() -> std::ffi::OsString {
+        std::env::var_os("LILITH_QEMU").unwrap_or_else(|| "qemu-system-riscv64".into())
+    }
+
+    /// 宿主机上是否有可用的QEMU
+    pub fn available() -> bool {
+        Command::new(Self::program())
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// 启动内核，`cmdline` 作为内核启动参数
+    pub fn boot(kernel: &Path, cmdline: &str) -> Result<Self, Error> {
+        let mut command = Command::new(Self::program());
+        command
+            .args(["-machine", "virt", "-m", "256M", "-smp", "2"])
+            .args(["-nographic", "-bios", "default", "-no-reboot"])
+            .arg("-kernel")
+            .arg(kernel)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if !cmdline.is_empty() {
+            command.arg("-append").arg(cmdline);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("标准输入已设置为管道");
+        let mut stdout = child.stdout.take().expect("标准输出已设置为管道");
+
+        let output = Arc::new((Mutex::new(Output::default()), Condvar::new()));
+        let reader = output.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stdout.read(&mut buf).unwrap_or(0);
+                let (lock, cond) = &*reader;
+                let mut output = lock.lock().unwrap();
+                if n == 0 {
+                    output.closed = true;
+                    cond.notify_all();
+                    return;
+                }
+                output.data.extend_from_slice(&buf[..n]);
+                cond.notify_all();
+            }
+        });
+
+        Ok(Qemu {
+            child,
+            stdin,
+            output,
+            consumed: 0,
+        })
+    }
+
+    /// 到目前为止的全部串口输出
+    pub fn output(&self) -> String {
+        let (lock, _) = &*self.output;
+        String::from_utf8_lossy(&lock.lock().unwrap().data).into_owned()
+    }
+
+    /// 等待上次匹配之后的输出中出现 `expected`，返回从上次匹配到本次匹配结尾的输出
+    pub fn expect(&mut self, expected: &str) -> Result<String, Error> {
+        self.expect_timeout(expected, DEFAULT_TIMEOUT)
+    }
+
+    /// 同 `expect`，指定超时
+    pub fn expect_timeout(&mut self, expected: &str, timeout: Duration) -> Result<String, Error> {
+        let deadline = Instant::now() + timeout;
+        let (lock, cond) = &*self.output;
+        let mut output = lock.lock().unwrap();
+        loop {
+            let pending = &output.data[self.consumed..];
+            if let Some(pos) = find(pending, expected.as_bytes()) {
+                let end = self.consumed + pos + expected.len();
+                let matched = String::from_utf8_lossy(&output.data[self.consumed..end]).into_owned();
+                self.consumed = end;
+                return Ok(matched);
+            }
+
+            if output.closed {
+                return Err(Error::Exited {
+                    expected: expected.to_string(),
+                    output: String::from_utf8_lossy(&output.data).into_owned(),
+                });
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout {
+                    expected: expected.to_string(),
+                    output: String::from_utf8_lossy(&output.data).into_owned(),
+                });
+            }
+            output = cond.wait_timeout(output, deadline - now).unwrap().0;
+        }
+    }
+
+    /// 向串口发送一行输入
+    pub fn send_line(&mut self, line: &str) -> Result<(), Error> {
+        self.stdin.write_all(line.as_bytes())?;
+        self.stdin.write_all(b"\r")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// 等待QEMU退出，超时时结束QEMU并返回 `Timeout`
+    pub fn wait_exit(&mut self, timeout: Duration) -> Result<ExitStatus, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                return Err(Error::Timeout {
+                    expected: String::from("QEMU退出"),
+                    output: self.output(),
+                });
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Qemu {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 在 `haystack` 中查找 `needle` 第一次出现的位置
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// 没有QEMU时跳过当前测试
+#[macro_export]
+macro_rules! require_qemu {
+    () => {
+        if !$crate::Qemu::available() {
+            eprintln!("跳过：找不到QEMU（可用 LILITH_QEMU 指定）");
+            return;
+        }
+    };
+}
+
+/// 以 `test` 特性构建内核，运行名称包含 `filter` 的内核测试，检查全部通过
+pub fn run_kernel_tests(filter: &str) {
+    let kernel = build_kernel(true, &["test"]).unwrap_or_else(|err| panic!("{}", err));
+    let mut qemu = Qemu::boot(&kernel, &format!("ktest={}", filter)).unwrap_or_else(|err| panic!("{}", err));
+
+    let summary = qemu.expect("测试结果: ").unwrap_or_else(|err| panic!("{}", err));
+    let status = qemu.wait_exit(DEFAULT_TIMEOUT).unwrap_or_else(|err| panic!("{}", err));
+    let output = qemu.output();
+    assert!(
+        status.success() && output.contains("测试结果: 全部通过"),
+        "内核测试失败（{}），串口输出:\n{}",
+        status,
+        output
+    );
+    assert!(summary.contains("... ok"), "没有运行任何匹配 {:?} 的测试，串口输出:\n{}", filter, output);
+}
@@ -0,0 +1,24 @@
+//! 微基准测试：以 `bench` 启动参数启动，收集各项结果
+//!
+//! 只检查每项都有结果，不设阈值；结果输出到测试日志，供比较不同版本
+
+use lilith_test::{build_kernel, require_qemu, Qemu};
+use std::time::Duration;
+
+#[test]
+fn boot_benchmarks_report() {
+    require_qemu!();
+    let kernel = build_kernel(true, &[]).unwrap_or_else(|err| panic!("{}", err));
+    let mut qemu = Qemu::boot(&kernel, "bench").unwrap_or_else(|err| panic!("{}", err));
+
+    qemu.expect("bench: 开始").unwrap_or_else(|err| panic!("{}", err));
+    let report = qemu
+        .expect_timeout("bench: 完成", Duration::from_secs(120))
+        .unwrap_or_else(|err| panic!("{}", err));
+    for item in ["上下文切换", "系统调用", "页错误", "分配器"] {
+        assert!(report.contains(&format!("bench: {}", item)), "缺少 {} 的结果:\n{}", item, report);
+    }
+    for line in report.lines().filter(|line| line.starts_with("bench:")) {
+        eprintln!("{}", line);
+    }
+}
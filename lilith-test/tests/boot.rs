@@ -0,0 +1,33 @@
+//! 启动：内核依次完成各子系统的初始化，不发生恐慌
+
+use lilith_test::{build_kernel, require_qemu, Qemu};
+use std::time::Duration;
+
+#[test]
+fn boots_to_scheduler() {
+    require_qemu!();
+    let kernel = build_kernel(true, &[]).unwrap_or_else(|err| panic!("{}", err));
+    let mut qemu = Qemu::boot(&kernel, "").unwrap_or_else(|err| panic!("{}", err));
+
+    for stage in [
+        "RISC-V中断系统初始化完成",
+        "进程调度器初始化完成",
+        "时间子系统初始化完成",
+        "网络子系统初始化完成",
+    ] {
+        qemu.expect(stage).unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    // 进入调度循环后一段时间内不应恐慌
+    std::thread::sleep(Duration::from_secs(2));
+    let output = qemu.output();
+    assert!(!output.contains("内核恐慌"), "启动后内核恐慌，串口输出:\n{}", output);
+}
+
+#[test]
+fn boots_minimal_kernel() {
+    require_qemu!();
+    let kernel = build_kernel(false, &[]).unwrap_or_else(|err| panic!("{}", err));
+    let mut qemu = Qemu::boot(&kernel, "").unwrap_or_else(|err| panic!("{}", err));
+    qemu.expect("时间子系统初始化完成").unwrap_or_else(|err| panic!("{}", err));
+}
@@ -0,0 +1,9 @@
+//! 文件系统：在内核中运行 `fs` 模块的测试（kernfs读写、设备文件）
+
+use lilith_test::{require_qemu, run_kernel_tests};
+
+#[test]
+fn fs_kernel_tests() {
+    require_qemu!();
+    run_kernel_tests("::fs::");
+}
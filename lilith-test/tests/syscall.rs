@@ -0,0 +1,9 @@
+//! 系统调用：在内核中运行 `syscall` 模块的测试（分发、错误码、用户指针检查）
+
+use lilith_test::{require_qemu, run_kernel_tests};
+
+#[test]
+fn syscall_kernel_tests() {
+    require_qemu!();
+    run_kernel_tests("::syscall::");
+}
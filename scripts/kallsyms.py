@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""生成内核符号表并写入内核映像的 .kallsyms 段
+
+用法: kallsyms.py <内核ELF>
+
+读取ELF中的函数符号（通过 nm，可用环境变量 NM 指定），去掉Rust符号的
+哈希后缀后按地址排序，按 lilith-kernel/src/debug/kallsyms.rs 中描述的
+布局原地写入 .kallsyms 段。段大小保持不变，不影响其他地址。
+"""
+
+import os
+import re
+import struct
+import subprocess
+import sys
+
+MAGIC = b"KSYM"
+HEADER = struct.Struct("<4sIII")
+ENTRY = struct.Struct("<QII")
+# Rust符号的哈希后缀，如 ::h0123456789abcdef
+RUST_HASH = re.compile(r"::h[0-9a-f]{16}$")
+
+
+def read_symbols(elf):
+    nm = os.environ.get("NM", "nm")
+    output = subprocess.run(
+        [nm, "-n", "-C", "--defined-only", elf],
+        check=True,
+        capture_output=True,
+        text=True,
+    ).stdout
+
+    symbols = {}
+    for line in output.splitlines():
+        parts = line.split(None, 2)
+        if len(parts) != 3 or parts[1] not in "tTwW":
+            continue
+        addr = int(parts[0], 16)
+        name = RUST_HASH.sub("", parts[2])
+        # 同一地址保留第一个名字
+        symbols.setdefault(addr, name)
+    return sorted(symbols.items())
+
+
+def build_table(symbols):
+    entries = bytearray()
+    names = bytearray()
+    for addr, name in symbols:
+        encoded = name.encode("utf-8")
+        entries += ENTRY.pack(addr, len(names), len(encoded))
+        names += encoded
+    header = HEADER.pack(MAGIC, len(symbols), len(names), 0)
+    return bytes(header + entries + names)
+
+
+def find_section(data, wanted):
+    """返回ELF64小端映像中指定段的 (文件偏移, 大小)"""
+    if data[:4] != b"\x7fELF" or data[4] != 2 or data[5] != 1:
+        sys.exit("kallsyms: 只支持64位小端ELF")
+    (shoff,) = struct.unpack_from("<Q", data, 0x28)
+    shentsize, shnum, shstrndx = struct.unpack_from("<HHH", data, 0x3A)
+
+    def section(index):
+        # sh_name, sh_type, sh_flags, sh_addr, sh_offset, sh_size
+        return struct.unpack_from("<IIQQQQ", data, shoff + index * shentsize)
+
+    strtab_offset = section(shstrndx)[4]
+    for index in range(shnum):
+        name_offset, _, _, _, offset, size = section(index)
+        start = strtab_offset + name_offset
+        name = data[start:data.index(b"\0", start)].decode()
+        if name == wanted:
+            return offset, size
+    sys.exit("kallsyms: 找不到 %s 段" % wanted)
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit(__doc__)
+    elf = sys.argv[1]
+
+    table = build_table(read_symbols(elf))
+    with open(elf, "r+b") as f:
+        data = f.read()
+        offset, size = find_section(data, ".kallsyms")
+        if len(table) > size:
+            sys.exit("kallsyms: 符号表需要 %d 字节，超过预留的 %d 字节，请增大 KALLSYMS_SIZE" % (len(table), size))
+        f.seek(offset)
+        f.write(table + bytes(size - len(table)))
+    print("kallsyms: 写入 %d 字节" % len(table))
+
+
+if __name__ == "__main__":
+    main()